@@ -1,6 +1,8 @@
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int, c_void};
-use std::sync::mpsc;
+use std::sync::{mpsc, OnceLock};
+
+use libloading::os::unix::{Library, RTLD_LOCAL, RTLD_NOW};
 
 #[allow(non_camel_case_types)]
 type c_bool = std::os::raw::c_uchar;
@@ -11,74 +13,111 @@ mod channel;
 pub use channel::*;
 pub use ctp_common::*;
 
-#[allow(dead_code)]
-#[link(name = "sina_thostmduserapi_se")]
-extern "C" {
-    #[link_name = "_ZN15CThostFtdcMdApi15CreateFtdcMdApiEPKcbb"]
-    fn CThostFtdcMdApiCreateFtdcMdApi(
+// The API library is loaded at runtime with `RTLD_LOCAL` instead of being linked, so that
+// CTP-compatible libraries exporting the same C++ symbols (CTP, openctp QQ/Sina) can be used
+// side by side in one process, each crate resolving the symbols of its own library.
+const LIBRARY_NAME: &str = "libsina_thostmduserapi_se.so";
+
+macro_rules! md_api_functions {
+    ($($symbol:literal fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        #[allow(non_snake_case)]
+        struct MdApiFunctions {
+            $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+        }
+
+        impl MdApiFunctions {
+            unsafe fn load(library: &Library) -> Result<Self, libloading::Error> {
+                Ok(MdApiFunctions {
+                    $($name: *library.get($symbol.as_bytes())?,)*
+                })
+            }
+        }
+
+        $(
+            #[allow(non_snake_case, dead_code)]
+            unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                (md_api_functions().$name)($($arg),*)
+            }
+        )*
+    };
+}
+
+md_api_functions! {
+    "_ZN15CThostFtdcMdApi15CreateFtdcMdApiEPKcbb" fn CThostFtdcMdApiCreateFtdcMdApi(
         pszFlowPath: *const c_char,
         bIsUsingUdp: c_bool,
         bIsMulticast: c_bool,
     ) -> *mut c_void;
-    #[link_name = "_ZN15CThostFtdcMdApi13GetApiVersionEv"]
-    fn CThostFtdcMdApiGetApiVersion() -> *const c_char;
-    #[link_name = "_ZN14CFtdcMdApiImpl7ReleaseEv"]
-    fn CFtdcMdApiImplRelease(api: *mut c_void);
-    #[link_name = "_ZN14CFtdcMdApiImpl4InitEv"]
-    fn CFtdcMdApiImplInit(api: *mut c_void);
-    #[link_name = "_ZN14CFtdcMdApiImpl4JoinEv"]
-    fn CFtdcMdApiImplJoin(api: *mut c_void) -> c_int;
-    #[link_name = "_ZN14CFtdcMdApiImpl13GetTradingDayEv"]
-    fn CFtdcMdApiImplGetTradingDay(api: *mut c_void) -> *const c_char;
-    #[link_name = "_ZN14CFtdcMdApiImpl13RegisterFrontEPc"]
-    fn CFtdcMdApiImplRegisterFront(api: *mut c_void, pszFrontAddress: *const c_char);
-    #[link_name = "_ZN14CFtdcMdApiImpl18RegisterNameServerEPc"]
-    fn CFtdcMdApiImplRegisterNameServer(api: *mut c_void, pszNsAddress: *const c_char);
-    #[link_name = "_ZN14CFtdcMdApiImpl20RegisterFensUserInfoEP27CThostFtdcFensUserInfoField"]
-    fn CFtdcMdApiImplRegisterFensUserInfo(
+    "_ZN15CThostFtdcMdApi13GetApiVersionEv" fn CThostFtdcMdApiGetApiVersion() -> *const c_char;
+    "_ZN14CFtdcMdApiImpl7ReleaseEv" fn CFtdcMdApiImplRelease(api: *mut c_void);
+    "_ZN14CFtdcMdApiImpl4InitEv" fn CFtdcMdApiImplInit(api: *mut c_void);
+    "_ZN14CFtdcMdApiImpl4JoinEv" fn CFtdcMdApiImplJoin(api: *mut c_void) -> c_int;
+    "_ZN14CFtdcMdApiImpl13GetTradingDayEv" fn CFtdcMdApiImplGetTradingDay(api: *mut c_void) -> *const c_char;
+    "_ZN14CFtdcMdApiImpl13RegisterFrontEPc" fn CFtdcMdApiImplRegisterFront(api: *mut c_void, pszFrontAddress: *const c_char);
+    "_ZN14CFtdcMdApiImpl18RegisterNameServerEPc" fn CFtdcMdApiImplRegisterNameServer(api: *mut c_void, pszNsAddress: *const c_char);
+    "_ZN14CFtdcMdApiImpl20RegisterFensUserInfoEP27CThostFtdcFensUserInfoField" fn CFtdcMdApiImplRegisterFensUserInfo(
         api: *mut c_void,
         pFensUserInfo: *const CThostFtdcFensUserInfoField,
     );
-    #[link_name = "_ZN14CFtdcMdApiImpl11RegisterSpiEP15CThostFtdcMdSpi"]
-    fn CFtdcMdApiImplRegisterSpi(api: *mut c_void, pSpi: *mut c_void);
-    #[link_name = "_ZN14CFtdcMdApiImpl19SubscribeMarketDataEPPci"]
-    fn CFtdcMdApiImplSubscribeMarketData(
+    "_ZN14CFtdcMdApiImpl11RegisterSpiEP15CThostFtdcMdSpi" fn CFtdcMdApiImplRegisterSpi(api: *mut c_void, pSpi: *mut c_void);
+    "_ZN14CFtdcMdApiImpl19SubscribeMarketDataEPPci" fn CFtdcMdApiImplSubscribeMarketData(
         api: *mut c_void,
         ppInstrumentID: *const *const c_char,
         nCount: c_int,
     ) -> c_int;
-    #[link_name = "_ZN14CFtdcMdApiImpl21UnSubscribeMarketDataEPPci"]
-    fn CFtdcMdApiImplUnSubscribeMarketData(
+    "_ZN14CFtdcMdApiImpl21UnSubscribeMarketDataEPPci" fn CFtdcMdApiImplUnSubscribeMarketData(
         api: *mut c_void,
         ppInstrumentID: *const *const c_char,
         nCount: c_int,
     ) -> c_int;
-    #[link_name = "_ZN14CFtdcMdApiImpl20SubscribeForQuoteRspEPPci"]
-    fn CFtdcMdApiImplSubscribeForQuoteRsp(
+    "_ZN14CFtdcMdApiImpl20SubscribeForQuoteRspEPPci" fn CFtdcMdApiImplSubscribeForQuoteRsp(
         api: *mut c_void,
         ppInstrumentID: *const *const c_char,
         nCount: c_int,
     ) -> c_int;
-    #[link_name = "_ZN14CFtdcMdApiImpl22UnSubscribeForQuoteRspEPPci"]
-    fn CFtdcMdApiImplUnSubscribeForQuoteRsp(
+    "_ZN14CFtdcMdApiImpl22UnSubscribeForQuoteRspEPPci" fn CFtdcMdApiImplUnSubscribeForQuoteRsp(
         api: *mut c_void,
         ppInstrumentID: *const *const c_char,
         nCount: c_int,
     ) -> c_int;
-    #[link_name = "_ZN14CFtdcMdApiImpl12ReqUserLoginEP27CThostFtdcReqUserLoginFieldi"]
-    fn CFtdcMdApiImplReqUserLogin(
+    "_ZN14CFtdcMdApiImpl12ReqUserLoginEP27CThostFtdcReqUserLoginFieldi" fn CFtdcMdApiImplReqUserLogin(
         api: *mut c_void,
         pReqUserLoginField: *const CThostFtdcReqUserLoginField,
         nRequestID: c_int,
     ) -> c_int;
-    #[link_name = "_ZN14CFtdcMdApiImpl13ReqUserLogoutEP25CThostFtdcUserLogoutFieldi"]
-    fn CFtdcMdApiImplReqUserLogout(
+    "_ZN14CFtdcMdApiImpl13ReqUserLogoutEP25CThostFtdcUserLogoutFieldi" fn CFtdcMdApiImplReqUserLogout(
         api: *mut c_void,
         pUserLogoutField: *const CThostFtdcUserLogoutField,
         nRequestID: c_int,
     ) -> c_int;
 }
 
+fn load_md_api_functions() -> &'static Result<MdApiFunctions, String> {
+    static FUNCTIONS: OnceLock<Result<MdApiFunctions, String>> = OnceLock::new();
+    FUNCTIONS.get_or_init(|| unsafe {
+        let library = Library::open(Some(LIBRARY_NAME), RTLD_NOW | RTLD_LOCAL)
+            .map_err(|e| format!("failed to load {}: {}", LIBRARY_NAME, e))?;
+        let functions = MdApiFunctions::load(&library)
+            .map_err(|e| format!("failed to resolve symbols of {}: {}", LIBRARY_NAME, e))?;
+        // The library stays loaded for the lifetime of the process
+        std::mem::forget(library);
+        Ok(functions)
+    })
+}
+
+fn md_api_functions() -> &'static MdApiFunctions {
+    match load_md_api_functions() {
+        Ok(functions) => functions,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Load the market data API library, reporting why it is unavailable instead of panicking
+/// on first use.
+pub fn load_api() -> Result<(), String> {
+    load_md_api_functions().as_ref().map(|_| ()).map_err(Clone::clone)
+}
+
 pub trait GenericMdApi {
     fn new(flow_path: CString, use_udp: bool, use_multicast: bool) -> Self;
     fn init(&mut self);
@@ -0,0 +1,33 @@
+use futures_util::StreamExt;
+use qamd_client::MdClient;
+use std::env;
+
+/// Print quotes for the instruments given on the command line
+///
+/// Usage: cargo run --example subscribe -- [url] [instrument...]
+#[tokio::main]
+async fn main() -> qamd_client::ClientResult<()> {
+    env_logger::init();
+
+    let mut args = env::args().skip(1);
+    let url = args.next().unwrap_or_else(|| "ws://localhost:8081/ws/market".to_string());
+    let mut instruments: Vec<String> = args.collect();
+    if instruments.is_empty() {
+        instruments = vec!["SHFE.rb2410".to_string(), "SHFE.au2412".to_string()];
+    }
+
+    let mut client = MdClient::connect(&url).await?;
+    client.subscribe(instruments)?;
+    while let Some(snapshot) = client.next().await {
+        println!(
+            "{} {} last={} bid={} ask={} volume={}",
+            snapshot.datetime,
+            snapshot.instrument_id,
+            snapshot.last_price,
+            snapshot.bid_price1,
+            snapshot.ask_price1,
+            snapshot.volume
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,384 @@
+use futures_util::stream::{SplitSink, Stream};
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use qamd_rs::MDSnapshot;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::config::ClientConfig;
+use crate::error::{ClientError, ClientResult};
+use crate::quotes::QuoteBook;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Subscription changes requested through [`MdClient`]
+enum Command {
+    Subscribe(Vec<String>),
+    Unsubscribe(Vec<String>),
+}
+
+/// Why a connection ended
+enum Exit {
+    /// The gateway went away; reconnect
+    Disconnected,
+    /// The client was dropped; stop
+    Closed,
+}
+
+/// Async client of the QAMD gateway's WebSocket API
+///
+/// The client keeps one connection to the gateway on a background task. It
+/// pings the gateway, reconnects with exponential backoff when the connection
+/// drops or goes silent, and restores the subscriptions on every new
+/// connection. Quotes are yielded as complete [`MDSnapshot`]s by polling the
+/// client as a [`Stream`].
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use qamd_client::MdClient;
+///
+/// # async fn run() -> qamd_client::ClientResult<()> {
+/// let mut client = MdClient::connect("ws://localhost:8081/ws/market").await?;
+/// client.subscribe(["SHFE.rb2410", "SHFE.au2412"])?;
+/// while let Some(snapshot) = client.next().await {
+///     println!("{} {}", snapshot.instrument_id, snapshot.last_price);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct MdClient {
+    commands: mpsc::UnboundedSender<Command>,
+    snapshots: mpsc::Receiver<MDSnapshot>,
+    task: JoinHandle<()>,
+}
+
+impl MdClient {
+    /// Connect to a gateway endpoint with the default settings
+    pub async fn connect(url: &str) -> ClientResult<Self> {
+        Self::connect_with(ClientConfig::new(url)?).await
+    }
+
+    /// Connect with custom settings
+    ///
+    /// Fails if the first connection cannot be established; later
+    /// disconnects are retried in the background.
+    pub async fn connect_with(config: ClientConfig) -> ClientResult<Self> {
+        let (stream, _) = connect_async(config.url.as_str()).await?;
+        info!("Connected to gateway {}", config.url);
+
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (snapshot_tx, snapshots) = mpsc::channel(config.buffer);
+        let connection = Connection {
+            config,
+            subscriptions: BTreeSet::new(),
+            quotes: QuoteBook::new(),
+            commands: command_rx,
+            snapshots: snapshot_tx,
+        };
+        let task = tokio::spawn(connection.run(stream));
+        Ok(Self {
+            commands,
+            snapshots,
+            task,
+        })
+    }
+
+    /// Subscribe to instruments, instrument groups or wildcard patterns
+    pub fn subscribe<I, S>(&self, instruments: I) -> ClientResult<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.send(Command::Subscribe(instruments.into_iter().map(Into::into).collect()))
+    }
+
+    /// Unsubscribe from instruments or wildcard patterns
+    pub fn unsubscribe<I, S>(&self, instruments: I) -> ClientResult<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.send(Command::Unsubscribe(instruments.into_iter().map(Into::into).collect()))
+    }
+
+    fn send(&self, command: Command) -> ClientResult<()> {
+        self.commands.send(command).map_err(|_| ClientError::Closed)
+    }
+}
+
+impl Stream for MdClient {
+    type Item = MDSnapshot;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.snapshots.poll_recv(cx)
+    }
+}
+
+impl Drop for MdClient {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Connection state owned by the background task
+struct Connection {
+    config: ClientConfig,
+    // Everything subscribed through the client, restored after a reconnect
+    subscriptions: BTreeSet<String>,
+    quotes: QuoteBook,
+    commands: mpsc::UnboundedReceiver<Command>,
+    snapshots: mpsc::Sender<MDSnapshot>,
+}
+
+impl Connection {
+    async fn run(mut self, stream: WsStream) {
+        let mut stream = Some(stream);
+        let mut delay = self.config.reconnect_delay;
+        loop {
+            let ws = match stream.take() {
+                Some(ws) => ws,
+                None => match connect_async(self.config.url.as_str()).await {
+                    Ok((ws, _)) => {
+                        info!("Reconnected to gateway {}", self.config.url);
+                        delay = self.config.reconnect_delay;
+                        ws
+                    }
+                    Err(e) => {
+                        warn!("Failed to reconnect to {}: {}, retrying in {:?}", self.config.url, e, delay);
+                        if !self.wait(delay).await {
+                            return;
+                        }
+                        delay = (delay * 2).min(self.config.max_reconnect_delay);
+                        continue;
+                    }
+                },
+            };
+
+            match self.session(ws).await {
+                Exit::Closed => return,
+                Exit::Disconnected => {
+                    warn!("Disconnected from gateway {}", self.config.url);
+                    if !self.wait(delay).await {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Wait before reconnecting, keeping track of subscription changes meanwhile
+    ///
+    /// Returns false once the client has been dropped.
+    async fn wait(&mut self, delay: std::time::Duration) -> bool {
+        let sleep = time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return true,
+                command = self.commands.recv() => match command {
+                    Some(command) => {
+                        self.apply(command);
+                    }
+                    None => return false,
+                },
+            }
+        }
+    }
+
+    /// Serve one connection until it drops or the client is dropped
+    async fn session(&mut self, ws: WsStream) -> Exit {
+        let (mut sink, mut source) = ws.split();
+
+        // Every connection is a new gateway session, so subscribe to everything again
+        if !self.subscriptions.is_empty() {
+            let instruments: Vec<String> = self.subscriptions.iter().cloned().collect();
+            debug!("Restoring {} subscriptions", instruments.len());
+            if send_json(&mut sink, &subscription_message("subscribe", &instruments)).await.is_err() {
+                return Exit::Disconnected;
+            }
+        }
+
+        let mut heartbeat = time::interval_at(
+            Instant::now() + self.config.heartbeat_interval,
+            self.config.heartbeat_interval,
+        );
+        let mut last_seen = Instant::now();
+        loop {
+            tokio::select! {
+                frame = source.next() => match frame {
+                    Some(Ok(Message::Text(text))) => {
+                        last_seen = Instant::now();
+                        if !self.handle_text(&text).await {
+                            return Exit::Closed;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => return Exit::Disconnected,
+                    Some(Ok(_)) => last_seen = Instant::now(),
+                    Some(Err(e)) => {
+                        warn!("WebSocket error: {}", e);
+                        return Exit::Disconnected;
+                    }
+                },
+                command = self.commands.recv() => {
+                    let Some(command) = command else {
+                        let _ = sink.close().await;
+                        return Exit::Closed;
+                    };
+                    if let Some(message) = self.apply(command) {
+                        if send_json(&mut sink, &message).await.is_err() {
+                            return Exit::Disconnected;
+                        }
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > self.config.timeout {
+                        warn!("No data from gateway for {:?}", last_seen.elapsed());
+                        return Exit::Disconnected;
+                    }
+                    if sink.send(Message::Ping(Vec::new())).await.is_err() {
+                        return Exit::Disconnected;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Record a subscription change and return the message announcing it to the gateway
+    fn apply(&mut self, command: Command) -> Option<Value> {
+        match command {
+            Command::Subscribe(instruments) => {
+                let added: Vec<String> = instruments
+                    .into_iter()
+                    .filter(|instrument| self.subscriptions.insert(instrument.clone()))
+                    .collect();
+                (!added.is_empty()).then(|| subscription_message("subscribe", &added))
+            }
+            Command::Unsubscribe(instruments) => {
+                let removed: Vec<String> = instruments
+                    .into_iter()
+                    .filter(|instrument| self.subscriptions.remove(instrument))
+                    .collect();
+                for instrument in &removed {
+                    self.quotes.remove(instrument);
+                }
+                (!removed.is_empty()).then(|| subscription_message("unsubscribe", &removed))
+            }
+        }
+    }
+
+    /// Handle a text frame; returns false once the client has been dropped
+    async fn handle_text(&mut self, text: &str) -> bool {
+        let message: Value = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Ignoring malformed message from gateway: {}", e);
+                return true;
+            }
+        };
+        if message.get("aid").and_then(Value::as_str) == Some("rtn_error") {
+            warn!("Gateway error: {}", message.get("message").unwrap_or(&Value::Null));
+            return true;
+        }
+        for snapshot in self.quotes.apply(&message) {
+            if self.snapshots.send(snapshot).await.is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn subscription_message(kind: &str, instruments: &[String]) -> Value {
+    json!({
+        "type": kind,
+        "payload": { "instruments": instruments }
+    })
+}
+
+async fn send_json(
+    sink: &mut SplitSink<WsStream, Message>,
+    message: &Value,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    sink.send(Message::Text(message.to_string())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    async fn next_text(ws: &mut WebSocketStream<TcpStream>) -> Value {
+        loop {
+            match ws.next().await {
+                Some(Ok(Message::Text(text))) => return serde_json::from_str(&text).unwrap(),
+                Some(Ok(_)) => continue,
+                other => panic!("unexpected frame: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resubscribes_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws/market", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            // First connection: receive the subscription, send a full quote, then drop
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            let subscribe = next_text(&mut ws).await;
+            assert_eq!(subscribe["type"], "subscribe");
+            assert_eq!(subscribe["payload"]["instruments"], json!(["SHFE.rb2410"]));
+            let full = json!({
+                "aid": "rtn_data",
+                "data": [{ "quotes": { "SHFE.rb2410": {
+                    "instrument_id": "SHFE.rb2410",
+                    "last_price": 3500.0,
+                    "volume": 10,
+                    "datetime": "2024-07-01T01:00:00Z"
+                }}}]
+            });
+            ws.send(Message::Text(full.to_string())).await.unwrap();
+            drop(ws);
+
+            // Second connection: the client subscribes again by itself
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            let subscribe = next_text(&mut ws).await;
+            assert_eq!(subscribe["payload"]["instruments"], json!(["SHFE.rb2410"]));
+            let diff = json!({
+                "aid": "rtn_data",
+                "data": [{ "quotes": { "SHFE.rb2410": { "last_price": 3502.0 } } }]
+            });
+            ws.send(Message::Text(diff.to_string())).await.unwrap();
+            ws
+        });
+
+        let config = ClientConfig::new(&url)
+            .unwrap()
+            .with_reconnect_delay(Duration::from_millis(10), Duration::from_millis(50));
+        let mut client = MdClient::connect_with(config).await.unwrap();
+        client.subscribe(["SHFE.rb2410"]).unwrap();
+
+        let first = client.next().await.unwrap();
+        assert_eq!(first.last_price, 3500.0);
+        let second = time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.last_price, 3502.0);
+        assert_eq!(second.volume, 10);
+
+        server.await.unwrap();
+    }
+}
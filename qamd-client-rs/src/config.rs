@@ -0,0 +1,56 @@
+use std::time::Duration;
+use url::Url;
+
+use crate::error::ClientResult;
+
+/// Connection settings for [`MdClient`](crate::MdClient)
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Gateway WebSocket endpoint, e.g. `ws://localhost:8081/ws/market`
+    pub url: Url,
+    /// Interval between WebSocket pings sent to the gateway
+    pub heartbeat_interval: Duration,
+    /// Reconnect when no frame arrives from the gateway for this long
+    pub timeout: Duration,
+    /// Delay before the first reconnect attempt, doubled after each failure
+    pub reconnect_delay: Duration,
+    /// Upper bound of the reconnect delay
+    pub max_reconnect_delay: Duration,
+    /// Snapshots buffered before the connection waits for the stream to be polled
+    pub buffer: usize,
+}
+
+impl ClientConfig {
+    /// Create settings for a gateway endpoint with the default timings
+    pub fn new(url: &str) -> ClientResult<Self> {
+        Ok(Self {
+            url: Url::parse(url)?,
+            heartbeat_interval: Duration::from_secs(10),
+            // Matches the gateway's own client timeout
+            timeout: Duration::from_secs(30),
+            reconnect_delay: Duration::from_secs(1),
+            max_reconnect_delay: Duration::from_secs(30),
+            buffer: 1024,
+        })
+    }
+
+    /// Set the ping interval and the silence after which the client reconnects
+    pub fn with_heartbeat(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the initial and maximum reconnect delays
+    pub fn with_reconnect_delay(mut self, initial: Duration, max: Duration) -> Self {
+        self.reconnect_delay = initial;
+        self.max_reconnect_delay = max.max(initial);
+        self
+    }
+
+    /// Set the number of snapshots buffered for the stream
+    pub fn with_buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer.max(1);
+        self
+    }
+}
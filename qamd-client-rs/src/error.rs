@@ -0,0 +1,26 @@
+use thiserror::Error;
+
+/// Errors returned by the gateway client
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// The gateway URL could not be parsed
+    #[error("Invalid gateway URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    /// The initial WebSocket connection failed
+    #[error("WebSocket error: {0}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+
+    /// The connection task has stopped
+    #[error("Client is closed")]
+    Closed,
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for ClientError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        ClientError::WebSocket(Box::new(e))
+    }
+}
+
+/// Result type for client operations
+pub type ClientResult<T> = Result<T, ClientError>;
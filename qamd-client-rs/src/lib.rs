@@ -0,0 +1,17 @@
+//! # qamd-client-rs
+//!
+//! Async client for the QAMD gateway's WebSocket market data API.
+//!
+//! [`MdClient`] maintains the connection (heartbeats, reconnects with backoff
+//! and resubscription) and yields complete [`qamd_rs::MDSnapshot`]s rebuilt
+//! from the gateway's incremental updates.
+
+pub mod client;
+pub mod config;
+pub mod error;
+pub mod quotes;
+
+pub use client::MdClient;
+pub use config::ClientConfig;
+pub use error::{ClientError, ClientResult};
+pub use quotes::QuoteBook;
@@ -0,0 +1,122 @@
+use log::warn;
+use qamd_rs::MDSnapshot;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Quote state rebuilt from the gateway's `rtn_data` messages
+///
+/// The gateway sends the full quote when an instrument is subscribed and only
+/// the changed fields afterwards, so each update is merged into the last known
+/// fields of its instrument before it is decoded as an [`MDSnapshot`].
+#[derive(Debug, Default)]
+pub struct QuoteBook {
+    quotes: HashMap<String, Map<String, Value>>,
+}
+
+impl QuoteBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge an `rtn_data` message and return the updated snapshots
+    ///
+    /// Other messages are ignored and return no snapshots.
+    pub fn apply(&mut self, message: &Value) -> Vec<MDSnapshot> {
+        if message.get("aid").and_then(Value::as_str) != Some("rtn_data") {
+            return Vec::new();
+        }
+        let Some(items) = message.get("data").and_then(Value::as_array) else {
+            return Vec::new();
+        };
+
+        let mut snapshots = Vec::new();
+        for quotes in items.iter().filter_map(|item| item.get("quotes")?.as_object()) {
+            for (instrument, fields) in quotes {
+                let Some(fields) = fields.as_object() else {
+                    continue;
+                };
+                let quote = self
+                    .quotes
+                    .entry(instrument.clone())
+                    .or_insert_with(|| empty_quote(instrument));
+                quote.extend(fields.iter().map(|(field, value)| (field.clone(), value.clone())));
+                match serde_json::from_value(Value::Object(quote.clone())) {
+                    Ok(snapshot) => snapshots.push(snapshot),
+                    Err(e) => warn!("Failed to decode quote for {}: {}", instrument, e),
+                }
+            }
+        }
+        snapshots
+    }
+
+    /// Latest snapshot of an instrument
+    pub fn get(&self, instrument: &str) -> Option<MDSnapshot> {
+        let quote = self.quotes.get(instrument)?;
+        serde_json::from_value(Value::Object(quote.clone())).ok()
+    }
+
+    /// Forget an instrument's quote
+    pub fn remove(&mut self, instrument: &str) {
+        self.quotes.remove(instrument);
+    }
+}
+
+/// Fields of a default snapshot, so that fields the gateway never sends still decode
+fn empty_quote(instrument: &str) -> Map<String, Value> {
+    let snapshot = MDSnapshot {
+        instrument_id: instrument.to_string(),
+        ..MDSnapshot::default()
+    };
+    match serde_json::to_value(snapshot) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qamd_rs::OptionalF64;
+    use serde_json::json;
+
+    fn rtn_data(quotes: Value) -> Value {
+        json!({ "aid": "rtn_data", "data": [{ "quotes": quotes }] })
+    }
+
+    #[test]
+    fn test_merges_incremental_updates() {
+        let mut book = QuoteBook::new();
+        let full = rtn_data(json!({
+            "SHFE.rb2410": {
+                "instrument_id": "SHFE.rb2410",
+                "last_price": 3500.0,
+                "volume": 100,
+                "open_interest": 2000.0,
+                "datetime": "2024-07-01T01:00:00Z"
+            }
+        }));
+        let snapshots = book.apply(&full);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].last_price, 3500.0);
+        assert_eq!(snapshots[0].open_interest, OptionalF64::Value(2000.0));
+
+        // Only the changed fields are sent after the first quote
+        let diff = rtn_data(json!({ "SHFE.rb2410": { "last_price": 3501.0 } }));
+        let snapshots = book.apply(&diff);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].instrument_id, "SHFE.rb2410");
+        assert_eq!(snapshots[0].last_price, 3501.0);
+        assert_eq!(snapshots[0].volume, 100);
+        assert_eq!(book.get("SHFE.rb2410").unwrap().last_price, 3501.0);
+
+        book.remove("SHFE.rb2410");
+        assert!(book.get("SHFE.rb2410").is_none());
+    }
+
+    #[test]
+    fn test_ignores_other_messages() {
+        let mut book = QuoteBook::new();
+        assert!(book.apply(&json!({ "aid": "rtn_session", "session_id": "x" })).is_empty());
+        assert!(book.apply(&json!({ "type": "system", "payload": { "message": "hi" } })).is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::Utc;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use qamd_rs::{MDSnapshot, OptionalF64, OrderBook};
+
+/// A snapshot with five levels per side, volumes shifted by `round` so
+/// consecutive books differ on every level and prices move every other round
+fn create_depth_snapshot(round: i64) -> MDSnapshot {
+    let mid = 3_500.0 + (round / 2) as f64;
+    let volume = |level: i64| Some(10 + (level + round) % 7);
+    MDSnapshot {
+        instrument_id: "SHFE.rb2410".to_string(),
+        amount: 1_000_000.0,
+        ask_price1: mid + 1.0,
+        ask_volume1: 10 + round % 5,
+        bid_price1: mid - 1.0,
+        bid_volume1: 12 + round % 3,
+        last_price: mid,
+        datetime: Utc::now(),
+        highest: mid,
+        lowest: 3_400.0,
+        open: 3_450.0,
+        close: OptionalF64::Null,
+        volume: 1_000 + round,
+        pre_close: 3_440.0,
+        lower_limit: 3_100.0,
+        upper_limit: 3_800.0,
+        average: mid,
+        ask_price2: Some(mid + 2.0),
+        ask_volume2: volume(2),
+        bid_price2: Some(mid - 2.0),
+        bid_volume2: volume(3),
+        ask_price3: Some(mid + 3.0),
+        ask_volume3: volume(4),
+        bid_price3: Some(mid - 3.0),
+        bid_volume3: volume(5),
+        ask_price4: Some(mid + 4.0),
+        ask_price5: Some(mid + 5.0),
+        ask_price6: None,
+        ask_price7: None,
+        ask_price8: None,
+        ask_price9: None,
+        ask_price10: None,
+        ask_volume4: volume(6),
+        ask_volume5: volume(7),
+        ask_volume6: None,
+        ask_volume7: None,
+        ask_volume8: None,
+        ask_volume9: None,
+        ask_volume10: None,
+        bid_price4: Some(mid - 4.0),
+        bid_price5: Some(mid - 5.0),
+        bid_price6: None,
+        bid_price7: None,
+        bid_price8: None,
+        bid_price9: None,
+        bid_price10: None,
+        bid_volume4: volume(8),
+        bid_volume5: volume(9),
+        bid_volume6: None,
+        bid_volume7: None,
+        bid_volume8: None,
+        bid_volume9: None,
+        bid_volume10: None,
+        open_interest: OptionalF64::Value(100_000.0),
+        pre_open_interest: OptionalF64::Value(99_000.0),
+        pre_settlement: OptionalF64::Value(3_440.0),
+        settlement: OptionalF64::Null,
+        iopv: OptionalF64::Null,
+        ins_class: None,
+        underlying_symbol: None,
+        strike_price: None,
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let snapshots: Vec<MDSnapshot> = (0..2).map(create_depth_snapshot).collect();
+    let moved = create_depth_snapshot(2);
+
+    c.bench_function("orderbook_update_from_snapshot", |b| {
+        let mut book = OrderBook::new("SHFE.rb2410");
+        let mut round = 0;
+        b.iter(|| {
+            book.update(black_box(&snapshots[round % 2]));
+            round += 1;
+        })
+    });
+
+    let previous = OrderBook::from_snapshot(&snapshots[0]);
+    let volumes_changed = OrderBook::from_snapshot(&snapshots[1]);
+    let prices_moved = OrderBook::from_snapshot(&moved);
+    c.bench_function("orderbook_diff_volumes", |b| {
+        b.iter(|| black_box(&previous).diff(black_box(&volumes_changed)))
+    });
+    c.bench_function("orderbook_diff_prices", |b| {
+        b.iter(|| black_box(&previous).diff(black_box(&prices_moved)))
+    });
+
+    // Client side: apply the level updates of a moved book
+    let updates = previous.diff(&prices_moved);
+    c.bench_function("orderbook_apply_updates", |b| {
+        b.iter(|| {
+            let mut book = previous.clone();
+            book.apply(black_box(&updates));
+            book
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
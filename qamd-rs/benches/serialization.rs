@@ -65,6 +65,9 @@ fn create_test_snapshot() -> MDSnapshot {
         pre_settlement: OptionalF64::String("-".to_string()),
         settlement: OptionalF64::String("-".to_string()),
         iopv: OptionalF64::Null,
+        ins_class: None,
+        underlying_symbol: None,
+        strike_price: None,
     }
 }
 
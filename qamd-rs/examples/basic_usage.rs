@@ -111,5 +111,8 @@ fn create_example_snapshot() -> MDSnapshot {
         pre_settlement: OptionalF64::String("-".to_string()),
         settlement: OptionalF64::String("-".to_string()),
         iopv: OptionalF64::Null,
+        ins_class: None,
+        underlying_symbol: None,
+        strike_price: None,
     }
 } 
\ No newline at end of file
@@ -1,3 +1,6 @@
+/// Exchange registry: sessions, price ticks and instrument ID formats
+pub mod exchange;
+
 /// Market data source identifiers
 pub mod source {
     /// Shanghai Stock Exchange
@@ -0,0 +1,458 @@
+//! Exchange registry
+//!
+//! Static reference data for the mainland exchanges: trading sessions, price tick
+//! conventions and instrument ID formats, plus [`exchange_of`] to find the exchange
+//! of a prefixed (`SHFE.rb2410`) or bare (`rb2410`, `600000`) instrument ID.
+
+use chrono::NaiveTime;
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::daily::InstrumentType;
+
+/// A continuous trading session in exchange local time, `end` before `start` crosses midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradingSession {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Night sessions belong to the next trading day
+    pub night: bool,
+}
+
+impl TradingSession {
+    /// A day session
+    pub const fn day(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end, night: false }
+    }
+
+    /// A night session
+    pub const fn night(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end, night: true }
+    }
+
+    /// Whether the session crosses midnight
+    pub fn crosses_midnight(&self) -> bool {
+        self.end < self.start
+    }
+}
+
+const fn hm(hour: u32, min: u32) -> NaiveTime {
+    match NaiveTime::from_hms_opt(hour, min, 0) {
+        Some(time) => time,
+        None => panic!("invalid session time"),
+    }
+}
+
+/// Commodity futures day session, with a break 10:15-10:30 and lunch 11:30-13:30
+const COMMODITY_DAY: [TradingSession; 3] = [
+    TradingSession::day(hm(9, 0), hm(10, 15)),
+    TradingSession::day(hm(10, 30), hm(11, 30)),
+    TradingSession::day(hm(13, 30), hm(15, 0)),
+];
+
+const SHFE_SESSIONS: [TradingSession; 4] = [
+    COMMODITY_DAY[0],
+    COMMODITY_DAY[1],
+    COMMODITY_DAY[2],
+    // Gold, silver and crude oil trade until 02:30
+    TradingSession::night(hm(21, 0), hm(2, 30)),
+];
+
+const DCE_CZCE_SESSIONS: [TradingSession; 4] = [
+    COMMODITY_DAY[0],
+    COMMODITY_DAY[1],
+    COMMODITY_DAY[2],
+    TradingSession::night(hm(21, 0), hm(23, 0)),
+];
+
+const CFFEX_SESSIONS: [TradingSession; 2] = [
+    TradingSession::day(hm(9, 30), hm(11, 30)),
+    // Treasury futures close at 15:15
+    TradingSession::day(hm(13, 0), hm(15, 15)),
+];
+
+const STOCK_SESSIONS: [TradingSession; 2] = [
+    // Including the call auction from 09:15
+    TradingSession::day(hm(9, 15), hm(11, 30)),
+    TradingSession::day(hm(13, 0), hm(15, 0)),
+];
+
+/// A product listed on an exchange with its minimum price movement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Product {
+    /// Product code as it appears in instrument IDs, e.g. `rb` or `SR`
+    pub code: &'static str,
+    pub price_tick: f64,
+}
+
+const fn product(code: &'static str, price_tick: f64) -> Product {
+    Product { code, price_tick }
+}
+
+/// Static description of an exchange
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExchangeInfo {
+    /// Exchange code used as instrument prefix, e.g. `SHFE`
+    pub code: &'static str,
+    pub name: &'static str,
+    /// What the exchange lists, `Future` for futures and options exchanges
+    pub kind: InstrumentType,
+    /// UTC offset of the exchange local time in seconds
+    pub utc_offset_secs: i32,
+    /// Trading sessions, night sessions use the latest close of the exchange
+    pub sessions: &'static [TradingSession],
+    /// Price tick of instruments whose product is not listed
+    pub default_tick: f64,
+    /// Listed products, empty for stock exchanges
+    pub products: &'static [Product],
+    /// Regex of bare instrument IDs (futures, options or securities codes)
+    pub instrument_pattern: &'static str,
+}
+
+const CHINA_OFFSET: i32 = 8 * 3600;
+
+/// Futures exchanges, whose night session quotes belong to the next trading day
+pub const FUTURES_EXCHANGES: [&str; 6] = ["SHFE", "INE", "DCE", "CZCE", "CFFEX", "GFEX"];
+
+/// All known exchanges
+pub const EXCHANGES: [ExchangeInfo; 9] = [
+    ExchangeInfo {
+        code: "SHFE",
+        name: "Shanghai Futures Exchange",
+        kind: InstrumentType::Future,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &SHFE_SESSIONS,
+        default_tick: 1.0,
+        products: &[
+            product("cu", 10.0),
+            product("al", 5.0),
+            product("zn", 5.0),
+            product("pb", 5.0),
+            product("ni", 10.0),
+            product("sn", 10.0),
+            product("ao", 1.0),
+            product("au", 0.02),
+            product("ag", 1.0),
+            product("rb", 1.0),
+            product("wr", 1.0),
+            product("hc", 1.0),
+            product("ss", 5.0),
+            product("fu", 1.0),
+            product("bu", 1.0),
+            product("ru", 5.0),
+            product("br", 5.0),
+            product("sp", 2.0),
+        ],
+        // rb2410, options cu2409C70000
+        instrument_pattern: r"^[a-z]{1,2}\d{4}([CP]\d+)?$",
+    },
+    ExchangeInfo {
+        code: "INE",
+        name: "Shanghai International Energy Exchange",
+        kind: InstrumentType::Future,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &SHFE_SESSIONS,
+        default_tick: 1.0,
+        products: &[
+            product("sc", 0.1),
+            product("lu", 1.0),
+            product("nr", 5.0),
+            product("bc", 10.0),
+            product("ec", 0.1),
+        ],
+        instrument_pattern: r"^[a-z]{1,2}\d{4}([CP]\d+)?$",
+    },
+    ExchangeInfo {
+        code: "DCE",
+        name: "Dalian Commodity Exchange",
+        kind: InstrumentType::Future,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &DCE_CZCE_SESSIONS,
+        default_tick: 1.0,
+        products: &[
+            product("a", 1.0),
+            product("b", 1.0),
+            product("m", 1.0),
+            product("y", 2.0),
+            product("p", 2.0),
+            product("c", 1.0),
+            product("cs", 1.0),
+            product("rr", 1.0),
+            product("jd", 1.0),
+            product("lh", 5.0),
+            product("l", 1.0),
+            product("v", 1.0),
+            product("pp", 1.0),
+            product("eb", 1.0),
+            product("eg", 1.0),
+            product("pg", 1.0),
+            product("j", 0.5),
+            product("jm", 0.5),
+            product("i", 0.5),
+            product("fb", 0.5),
+            product("bb", 0.05),
+        ],
+        // m2409, options m2409-C-3000
+        instrument_pattern: r"^[a-z]{1,2}\d{4}(-[CP]-\d+)?$",
+    },
+    ExchangeInfo {
+        code: "CZCE",
+        name: "Zhengzhou Commodity Exchange",
+        kind: InstrumentType::Future,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &DCE_CZCE_SESSIONS,
+        default_tick: 1.0,
+        products: &[
+            product("WH", 1.0),
+            product("PM", 1.0),
+            product("RI", 1.0),
+            product("LR", 1.0),
+            product("JR", 1.0),
+            product("CF", 5.0),
+            product("CY", 5.0),
+            product("SR", 1.0),
+            product("OI", 1.0),
+            product("RS", 1.0),
+            product("RM", 1.0),
+            product("AP", 1.0),
+            product("CJ", 5.0),
+            product("PK", 2.0),
+            product("TA", 2.0),
+            product("PF", 2.0),
+            product("PX", 2.0),
+            product("MA", 1.0),
+            product("FG", 1.0),
+            product("SA", 1.0),
+            product("SH", 1.0),
+            product("UR", 1.0),
+            product("SF", 2.0),
+            product("SM", 2.0),
+            product("ZC", 0.2),
+        ],
+        // Three digit delivery month: SR409, options SR409C6000
+        instrument_pattern: r"^[A-Z]{2}\d{3}([CP]\d+)?$",
+    },
+    ExchangeInfo {
+        code: "CFFEX",
+        name: "China Financial Futures Exchange",
+        kind: InstrumentType::Future,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &CFFEX_SESSIONS,
+        default_tick: 0.2,
+        products: &[
+            product("IF", 0.2),
+            product("IH", 0.2),
+            product("IC", 0.2),
+            product("IM", 0.2),
+            product("IO", 0.2),
+            product("HO", 0.2),
+            product("MO", 0.2),
+            product("TS", 0.002),
+            product("TF", 0.005),
+            product("T", 0.005),
+            product("TL", 0.01),
+        ],
+        // IF2409, options IO2409-C-3500
+        instrument_pattern: r"^[A-Z]{1,2}\d{4}(-[CP]-\d+)?$",
+    },
+    ExchangeInfo {
+        code: "GFEX",
+        name: "Guangzhou Futures Exchange",
+        kind: InstrumentType::Future,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &COMMODITY_DAY,
+        default_tick: 5.0,
+        products: &[
+            product("si", 5.0),
+            product("lc", 20.0),
+            product("ps", 5.0),
+        ],
+        instrument_pattern: r"^[a-z]{2}\d{4}(-[CP]-\d+)?$",
+    },
+    ExchangeInfo {
+        code: "SSE",
+        name: "Shanghai Stock Exchange",
+        kind: InstrumentType::Stock,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &STOCK_SESSIONS,
+        default_tick: 0.01,
+        products: &[],
+        // Main board 60, STAR market 68, B shares 90, funds 5x, bonds 11
+        instrument_pattern: r"^(60|68|90|5\d|11)\d{4}$",
+    },
+    ExchangeInfo {
+        code: "SZSE",
+        name: "Shenzhen Stock Exchange",
+        kind: InstrumentType::Stock,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &STOCK_SESSIONS,
+        default_tick: 0.01,
+        products: &[],
+        // Main board 00, ChiNext 30, B shares 20, bonds and funds 12-18, indices 39
+        instrument_pattern: r"^(00|30|20|1[2-8]|39)\d{4}$",
+    },
+    ExchangeInfo {
+        code: "BSE",
+        name: "Beijing Stock Exchange",
+        kind: InstrumentType::Stock,
+        utc_offset_secs: CHINA_OFFSET,
+        sessions: &STOCK_SESSIONS,
+        default_tick: 0.01,
+        products: &[],
+        instrument_pattern: r"^(8[3-9]|4[3-9]|92)\d{4}$",
+    },
+];
+
+/// Compiled instrument patterns, in the order of [`EXCHANGES`]
+fn patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        EXCHANGES
+            .iter()
+            .map(|exchange| Regex::new(exchange.instrument_pattern).expect("valid instrument pattern"))
+            .collect()
+    })
+}
+
+impl ExchangeInfo {
+    fn index(&self) -> usize {
+        EXCHANGES
+            .iter()
+            .position(|exchange| exchange.code == self.code)
+            .expect("registered exchange")
+    }
+
+    /// Whether a bare instrument ID follows the exchange's format
+    pub fn matches(&self, instrument: &str) -> bool {
+        patterns()[self.index()].is_match(instrument)
+    }
+
+    /// Listed product of a bare or prefixed instrument ID
+    pub fn product_of(&self, instrument_id: &str) -> Option<&'static Product> {
+        let code = product_code(bare_code(instrument_id));
+        self.products.iter().find(|product| product.code == code)
+    }
+
+    /// Price tick of an instrument, the exchange default for unknown products
+    pub fn price_tick(&self, instrument_id: &str) -> f64 {
+        self.product_of(instrument_id)
+            .map_or(self.default_tick, |product| product.price_tick)
+    }
+
+    /// Whether the exchange lists futures and options
+    pub fn is_futures(&self) -> bool {
+        self.kind == InstrumentType::Future
+    }
+}
+
+/// Instrument ID without its exchange prefix
+fn bare_code(instrument_id: &str) -> &str {
+    instrument_id
+        .split_once('.')
+        .map_or(instrument_id, |(_, code)| code)
+}
+
+/// Leading letters of an instrument ID, e.g. `rb` of `rb2410`
+fn product_code(code: &str) -> &str {
+    let end = code
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(code.len());
+    &code[..end]
+}
+
+/// Look up an exchange by code, case insensitive
+pub fn lookup(code: &str) -> Option<&'static ExchangeInfo> {
+    EXCHANGES
+        .iter()
+        .find(|exchange| exchange.code.eq_ignore_ascii_case(code))
+}
+
+/// Exchange of an instrument ID
+///
+/// A known exchange prefix (`SHFE.rb2410`) decides directly. Bare futures and options
+/// IDs are resolved by their product code and checked against the exchange's format,
+/// bare securities codes by the stock exchanges' code ranges.
+pub fn exchange_of(instrument_id: &str) -> Option<&'static ExchangeInfo> {
+    if let Some((prefix, _)) = instrument_id.split_once('.') {
+        return lookup(prefix);
+    }
+
+    let product = product_code(instrument_id);
+    if product.is_empty() {
+        return EXCHANGES
+            .iter()
+            .find(|exchange| !exchange.is_futures() && exchange.matches(instrument_id));
+    }
+    EXCHANGES
+        .iter()
+        .find(|exchange| exchange.products.iter().any(|p| p.code == product))
+        .filter(|exchange| exchange.matches(instrument_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_of(instrument_id: &str) -> Option<&'static str> {
+        exchange_of(instrument_id).map(|exchange| exchange.code)
+    }
+
+    #[test]
+    fn test_exchange_of_futures() {
+        assert_eq!(code_of("rb2410"), Some("SHFE"));
+        assert_eq!(code_of("sc2409"), Some("INE"));
+        assert_eq!(code_of("m2409"), Some("DCE"));
+        assert_eq!(code_of("SR409"), Some("CZCE"));
+        assert_eq!(code_of("IF2409"), Some("CFFEX"));
+        assert_eq!(code_of("T2409"), Some("CFFEX"));
+        assert_eq!(code_of("lc2409"), Some("GFEX"));
+    }
+
+    #[test]
+    fn test_exchange_of_options() {
+        assert_eq!(code_of("cu2409C70000"), Some("SHFE"));
+        assert_eq!(code_of("m2409-C-3000"), Some("DCE"));
+        assert_eq!(code_of("SR409C6000"), Some("CZCE"));
+        assert_eq!(code_of("IO2409-P-3500"), Some("CFFEX"));
+    }
+
+    #[test]
+    fn test_exchange_of_stocks() {
+        assert_eq!(code_of("600000"), Some("SSE"));
+        assert_eq!(code_of("688286"), Some("SSE"));
+        assert_eq!(code_of("000001"), Some("SZSE"));
+        assert_eq!(code_of("300750"), Some("SZSE"));
+        assert_eq!(code_of("830799"), Some("BSE"));
+    }
+
+    #[test]
+    fn test_exchange_of_prefixed_and_unknown() {
+        assert_eq!(code_of("SHFE.rb2410"), Some("SHFE"));
+        assert_eq!(code_of("sse.600000"), Some("SSE"));
+        assert_eq!(code_of("BINANCE.BTCUSDT"), None);
+        // Known product in the wrong format
+        assert_eq!(code_of("rb24100"), None);
+        assert_eq!(code_of("SR2409"), None);
+        assert_eq!(code_of("zz2409"), None);
+        assert_eq!(code_of("123"), None);
+    }
+
+    #[test]
+    fn test_price_tick() {
+        let shfe = lookup("SHFE").unwrap();
+        assert_eq!(shfe.price_tick("SHFE.au2412"), 0.02);
+        assert_eq!(shfe.price_tick("xx2412"), shfe.default_tick);
+        assert_eq!(lookup("cffex").unwrap().price_tick("TS2409"), 0.002);
+        assert_eq!(lookup("SSE").unwrap().price_tick("600000"), 0.01);
+    }
+
+    #[test]
+    fn test_sessions() {
+        let shfe = lookup("SHFE").unwrap();
+        assert!(shfe.sessions.iter().any(|s| s.night && s.crosses_midnight()));
+        assert!(lookup("CFFEX").unwrap().sessions.iter().all(|s| !s.night));
+        for code in FUTURES_EXCHANGES {
+            assert!(lookup(code).unwrap().is_futures());
+        }
+    }
+}
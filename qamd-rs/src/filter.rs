@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::MDSnapshot;
+
+/// Result of checking a snapshot against the filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterVerdict {
+    /// New data, should be forwarded
+    Accept,
+    /// Same timestamp and content as the last accepted tick
+    Duplicate,
+    /// Older than the last accepted tick
+    OutOfOrder,
+}
+
+impl FilterVerdict {
+    /// Check if the snapshot should be forwarded
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, FilterVerdict::Accept)
+    }
+}
+
+/// Counters of filtered ticks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickFilterStats {
+    /// Ticks that passed the filter
+    pub accepted: u64,
+    /// Ticks dropped as exact duplicates
+    pub duplicates: u64,
+    /// Ticks dropped because they were older than the last accepted tick
+    pub out_of_order: u64,
+}
+
+impl TickFilterStats {
+    /// Total number of ticks dropped
+    pub fn dropped(&self) -> u64 {
+        self.duplicates + self.out_of_order
+    }
+
+    fn record(&mut self, verdict: FilterVerdict) {
+        match verdict {
+            FilterVerdict::Accept => self.accepted += 1,
+            FilterVerdict::Duplicate => self.duplicates += 1,
+            FilterVerdict::OutOfOrder => self.out_of_order += 1,
+        }
+    }
+}
+
+/// Fields identifying a tick, used to detect duplicates
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TickKey {
+    datetime: DateTime<Utc>,
+    volume: i64,
+    last_price: f64,
+    bid_price1: f64,
+    bid_volume1: i64,
+    ask_price1: f64,
+    ask_volume1: i64,
+}
+
+impl TickKey {
+    fn from_snapshot(snapshot: &MDSnapshot) -> Self {
+        Self {
+            datetime: snapshot.datetime,
+            volume: snapshot.volume,
+            last_price: snapshot.last_price,
+            bid_price1: snapshot.bid_price1,
+            bid_volume1: snapshot.bid_volume1,
+            ask_price1: snapshot.ask_price1,
+            ask_volume1: snapshot.ask_volume1,
+        }
+    }
+}
+
+/// Per-instrument guard against duplicate and out-of-order ticks
+///
+/// Upstream feeds may replay the last ticks after a reconnect. A tick is
+/// dropped when its timestamp is older than the last accepted tick of the
+/// same instrument, or when it repeats the last accepted tick exactly.
+/// Ticks sharing a timestamp but carrying different data are accepted.
+#[derive(Debug, Clone, Default)]
+pub struct TickFilter {
+    last: HashMap<String, TickKey>,
+    stats: TickFilterStats,
+}
+
+impl TickFilter {
+    /// Create an empty filter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check a snapshot and remember it if accepted
+    pub fn check(&mut self, snapshot: &MDSnapshot) -> FilterVerdict {
+        let key = TickKey::from_snapshot(snapshot);
+        let verdict = match self.last.get(&snapshot.instrument_id) {
+            Some(last) if key.datetime < last.datetime => FilterVerdict::OutOfOrder,
+            Some(last) if key == *last => FilterVerdict::Duplicate,
+            _ => FilterVerdict::Accept,
+        };
+
+        if verdict.is_accepted() {
+            self.last.insert(snapshot.instrument_id.clone(), key);
+        }
+        self.stats.record(verdict);
+        verdict
+    }
+
+    /// Forget the last tick of an instrument (e.g. after switching source)
+    pub fn reset(&mut self, instrument_id: &str) {
+        self.last.remove(instrument_id);
+    }
+
+    /// Forget all instruments, keeping the counters
+    pub fn clear(&mut self) {
+        self.last.clear();
+    }
+
+    /// Counters since the filter was created
+    pub fn stats(&self) -> TickFilterStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn snapshot(datetime: DateTime<Utc>, volume: i64) -> MDSnapshot {
+        serde_json::from_value(serde_json::json!({
+            "instrument_id": "SHFE.rb2410",
+            "amount": 0.0,
+            "ask_price1": 3501.0, "ask_volume1": 10,
+            "bid_price1": 3500.0, "bid_volume1": 30,
+            "close": null,
+            "datetime": datetime,
+            "highest": 0.0, "last_price": 3500.0, "lower_limit": 0.0, "lowest": 0.0,
+            "open": 0.0, "open_interest": null, "pre_close": 0.0,
+            "pre_open_interest": null, "pre_settlement": null, "settlement": null,
+            "upper_limit": 0.0, "volume": volume, "average": 0.0, "iopv": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tick_filter_drops_duplicates_and_stale() {
+        let now = Utc::now();
+        let mut filter = TickFilter::new();
+
+        assert_eq!(filter.check(&snapshot(now, 100)), FilterVerdict::Accept);
+        assert_eq!(filter.check(&snapshot(now, 100)), FilterVerdict::Duplicate);
+        // Same timestamp with new data is still accepted
+        assert_eq!(filter.check(&snapshot(now, 101)), FilterVerdict::Accept);
+        assert_eq!(
+            filter.check(&snapshot(now - Duration::milliseconds(500), 99)),
+            FilterVerdict::OutOfOrder
+        );
+        assert_eq!(
+            filter.check(&snapshot(now + Duration::milliseconds(500), 102)),
+            FilterVerdict::Accept
+        );
+
+        let stats = filter.stats();
+        assert_eq!(stats.accepted, 3);
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.out_of_order, 1);
+        assert_eq!(stats.dropped(), 2);
+    }
+
+    #[test]
+    fn test_tick_filter_reset() {
+        let now = Utc::now();
+        let mut filter = TickFilter::new();
+
+        filter.check(&snapshot(now, 100));
+        filter.reset("SHFE.rb2410");
+        assert!(filter
+            .check(&snapshot(now - Duration::seconds(1), 90))
+            .is_accepted());
+    }
+}
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{QAMDError, Result};
+use crate::snapshot::MDSnapshot;
+
+/// Default value of a new index on its base date
+pub const DEFAULT_BASE_VALUE: f64 = 1000.0;
+
+/// How constituents are weighted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum IndexWeighting {
+    /// Weighted by market value (price times shares)
+    Capitalization,
+    /// Every constituent has the same weight at the base date and after each rebalance
+    Equal,
+}
+
+/// A constituent of an index basket
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Constituent {
+    pub instrument_id: String,
+    /// Shares counted in the market value (e.g. free float), ignored for equal weighting
+    pub shares: f64,
+}
+
+impl Constituent {
+    pub fn new(instrument_id: impl Into<String>, shares: f64) -> Self {
+        Self {
+            instrument_id: instrument_id.into(),
+            shares,
+        }
+    }
+}
+
+/// Index level computed from the constituent quotes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct IndexValue {
+    /// Current index level
+    pub value: f64,
+    /// Index level at the previous close of the constituents
+    pub pre_close: f64,
+    /// Summed traded volume of the constituents
+    pub volume: i64,
+    /// Summed turnover of the constituents
+    pub amount: f64,
+    /// Latest quote time among the constituents
+    pub datetime: DateTime<Utc>,
+}
+
+/// Price used for a constituent: the last price, or the previous close before the first trade
+/// (and for suspended instruments)
+pub fn constituent_price(snapshot: &MDSnapshot) -> Option<f64> {
+    [snapshot.last_price, snapshot.pre_close]
+        .into_iter()
+        .find(|price| price.is_finite() && *price > 0.0)
+}
+
+/// Capitalization- or equal-weighted index over a basket of instruments
+///
+/// The index level is the sum of price times units over all constituents divided by the
+/// divisor. Units are the shares for capitalization weighting and `1 / price` at the base
+/// date for equal weighting. Without a known divisor the first successful computation fixes
+/// it so that the index starts at the base value. Rebalancing changes the units and adjusts
+/// the divisor so that the index level does not jump.
+#[derive(Debug, Clone)]
+pub struct IndexCalculator {
+    weighting: IndexWeighting,
+    base_value: f64,
+    divisor: Option<f64>,
+    constituents: Vec<Constituent>,
+    // Units per constituent (same order as constituents), fixed with the divisor
+    units: Vec<f64>,
+}
+
+impl IndexCalculator {
+    /// Create an index starting at `base_value` on the first computation
+    pub fn new(weighting: IndexWeighting, constituents: Vec<Constituent>, base_value: f64) -> Self {
+        let units = match weighting {
+            IndexWeighting::Capitalization => constituents.iter().map(|c| c.shares).collect(),
+            IndexWeighting::Equal => Vec::new(),
+        };
+        Self {
+            weighting,
+            base_value,
+            divisor: None,
+            constituents,
+            units,
+        }
+    }
+
+    /// Continue an existing index with a published divisor
+    ///
+    /// Equal-weighted indexes still take their units from the first computed prices.
+    pub fn with_divisor(mut self, divisor: f64) -> Self {
+        self.divisor = Some(divisor);
+        self
+    }
+
+    pub fn weighting(&self) -> IndexWeighting {
+        self.weighting
+    }
+
+    pub fn constituents(&self) -> &[Constituent] {
+        &self.constituents
+    }
+
+    /// Divisor, None until the first computation
+    pub fn divisor(&self) -> Option<f64> {
+        self.divisor
+    }
+
+    /// Prices of all constituents, None if any is missing
+    fn prices<'a>(&self, quote: &impl Fn(&str) -> Option<&'a MDSnapshot>) -> Option<Vec<f64>> {
+        self.constituents
+            .iter()
+            .map(|constituent| quote(&constituent.instrument_id).and_then(constituent_price))
+            .collect()
+    }
+
+    /// Units giving every constituent the same weight at `prices`
+    fn equal_units(prices: &[f64]) -> Vec<f64> {
+        prices.iter().map(|price| 1.0 / price).collect()
+    }
+
+    fn aggregate(units: &[f64], prices: &[f64]) -> f64 {
+        units.iter().zip(prices).map(|(units, price)| units * price).sum()
+    }
+
+    /// Compute the index level, None while a constituent has no quote
+    pub fn compute<'a>(&mut self, quote: impl Fn(&str) -> Option<&'a MDSnapshot>) -> Option<IndexValue> {
+        if self.constituents.is_empty() {
+            return None;
+        }
+        let prices = self.prices(&quote)?;
+        if self.units.is_empty() {
+            self.units = Self::equal_units(&prices);
+        }
+        let aggregate = Self::aggregate(&self.units, &prices);
+        let divisor = *self.divisor.get_or_insert(aggregate / self.base_value);
+        if divisor == 0.0 || !divisor.is_finite() {
+            return None;
+        }
+
+        let mut pre_close = 0.0;
+        let mut volume = 0;
+        let mut amount = 0.0;
+        let mut datetime: Option<DateTime<Utc>> = None;
+        for ((constituent, units), price) in self.constituents.iter().zip(&self.units).zip(&prices) {
+            let snapshot = quote(&constituent.instrument_id)?;
+            let close = if snapshot.pre_close > 0.0 { snapshot.pre_close } else { *price };
+            pre_close += units * close;
+            volume += snapshot.volume;
+            amount += snapshot.amount;
+            datetime = Some(datetime.map_or(snapshot.datetime, |dt| dt.max(snapshot.datetime)));
+        }
+
+        Some(IndexValue {
+            value: aggregate / divisor,
+            pre_close: pre_close / divisor,
+            volume,
+            amount,
+            datetime: datetime?,
+        })
+    }
+
+    /// Replace the constituents (or their shares) keeping the index level continuous
+    ///
+    /// The current level is computed with the old basket, then the divisor is chosen so that
+    /// the new basket has the same level at the same prices. Equal-weighted indexes are
+    /// re-weighted at these prices.
+    pub fn rebalance<'a>(
+        &mut self,
+        constituents: Vec<Constituent>,
+        quote: impl Fn(&str) -> Option<&'a MDSnapshot>,
+    ) -> Result<()> {
+        let missing = || QAMDError::InvalidMarketData("index constituent without a price".to_string());
+        let level = match self.divisor {
+            Some(divisor) => {
+                let prices = self.prices(&quote).ok_or_else(missing)?;
+                if self.units.is_empty() {
+                    self.units = Self::equal_units(&prices);
+                }
+                Self::aggregate(&self.units, &prices) / divisor
+            }
+            None => self.base_value,
+        };
+
+        let mut next = Self::new(self.weighting, constituents, self.base_value);
+        if next.constituents.is_empty() {
+            return Err(QAMDError::General("an index needs at least one constituent".to_string()));
+        }
+        let prices = next.prices(&quote).ok_or_else(missing)?;
+        if next.units.is_empty() {
+            next.units = Self::equal_units(&prices);
+        }
+        next.divisor = Some(Self::aggregate(&next.units, &prices) / level);
+        *self = next;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn quote(instrument_id: &str, last_price: f64, pre_close: f64) -> MDSnapshot {
+        MDSnapshot::builder(instrument_id, Utc::now())
+            .last_price(last_price)
+            .pre_close(pre_close)
+            .volume(100)
+            .amount(last_price * 100.0)
+            .build()
+    }
+
+    fn quotes(prices: &[(&str, f64, f64)]) -> HashMap<String, MDSnapshot> {
+        prices
+            .iter()
+            .map(|(id, last, pre)| (id.to_string(), quote(id, *last, *pre)))
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {} got {}", expected, actual);
+    }
+
+    #[test]
+    fn test_capitalization_weighted() {
+        let constituents = vec![Constituent::new("SSE.600000", 100.0), Constituent::new("SSE.600036", 300.0)];
+        let mut index = IndexCalculator::new(IndexWeighting::Capitalization, constituents, DEFAULT_BASE_VALUE);
+
+        let base = quotes(&[("SSE.600000", 10.0, 10.0), ("SSE.600036", 20.0, 20.0)]);
+        let value = index.compute(|id| base.get(id)).unwrap();
+        assert_close(value.value, 1000.0);
+        assert_close(index.divisor().unwrap(), 7000.0 / 1000.0);
+        assert_eq!(value.volume, 200);
+
+        // The larger constituent moves the index more
+        let moved = quotes(&[("SSE.600000", 10.0, 10.0), ("SSE.600036", 22.0, 20.0)]);
+        let value = index.compute(|id| moved.get(id)).unwrap();
+        assert_close(value.value, 7600.0 / 7.0);
+        assert_close(value.pre_close, 1000.0);
+    }
+
+    #[test]
+    fn test_equal_weighted() {
+        let constituents = vec![Constituent::new("A", 100.0), Constituent::new("B", 300.0)];
+        let mut index = IndexCalculator::new(IndexWeighting::Equal, constituents, 100.0);
+
+        let base = quotes(&[("A", 10.0, 10.0), ("B", 20.0, 20.0)]);
+        assert_close(index.compute(|id| base.get(id)).unwrap().value, 100.0);
+
+        // +10% on either constituent moves the index by 5% regardless of shares or price
+        let moved = quotes(&[("A", 11.0, 10.0), ("B", 20.0, 20.0)]);
+        assert_close(index.compute(|id| moved.get(id)).unwrap().value, 105.0);
+        let moved = quotes(&[("A", 10.0, 10.0), ("B", 22.0, 20.0)]);
+        assert_close(index.compute(|id| moved.get(id)).unwrap().value, 105.0);
+    }
+
+    #[test]
+    fn test_missing_quote_and_fallback_price() {
+        let constituents = vec![Constituent::new("A", 1.0), Constituent::new("B", 1.0)];
+        let mut index = IndexCalculator::new(IndexWeighting::Capitalization, constituents, 1000.0);
+
+        let partial = quotes(&[("A", 10.0, 10.0)]);
+        assert!(index.compute(|id| partial.get(id)).is_none());
+        assert!(index.divisor().is_none());
+
+        // Before the first trade the previous close is used
+        let opening = quotes(&[("A", 10.0, 10.0), ("B", 0.0, 30.0)]);
+        assert_close(index.compute(|id| opening.get(id)).unwrap().value, 1000.0);
+        assert_close(index.divisor().unwrap(), 0.04);
+    }
+
+    #[test]
+    fn test_rebalance_keeps_level() {
+        let constituents = vec![Constituent::new("A", 100.0), Constituent::new("B", 100.0)];
+        let mut index =
+            IndexCalculator::new(IndexWeighting::Capitalization, constituents, 1000.0).with_divisor(2.0);
+
+        let prices = quotes(&[("A", 10.0, 10.0), ("B", 20.0, 20.0), ("C", 50.0, 50.0)]);
+        assert_close(index.compute(|id| prices.get(id)).unwrap().value, 1500.0);
+
+        // Replace B by C and double the shares of A
+        let next = vec![Constituent::new("A", 200.0), Constituent::new("C", 10.0)];
+        index.rebalance(next, |id| prices.get(id)).unwrap();
+        assert_close(index.compute(|id| prices.get(id)).unwrap().value, 1500.0);
+        assert_close(index.divisor().unwrap(), 2500.0 / 1500.0);
+
+        let missing = vec![Constituent::new("D", 1.0)];
+        assert!(index.rebalance(missing, |id| prices.get(id)).is_err());
+        assert_eq!(index.constituents().len(), 2);
+    }
+}
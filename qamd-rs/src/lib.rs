@@ -10,9 +10,15 @@ pub mod constants;
 pub mod types;
 pub mod daily;
 pub mod minute;
+pub mod orderbook;
+pub mod filter;
+pub mod options;
+pub mod timestamp;
+pub mod resample;
+pub mod index;
 
-pub use snapshot::MDSnapshot;
-pub use tick::Tick;
+pub use snapshot::{InstrumentClass, MDSnapshot, MDSnapshotBuilder};
+pub use tick::{Tick, TradeDirection};
 pub use error::QAMDError;
 pub use types::*;
 pub use daily::{
@@ -24,6 +30,13 @@ pub use minute::{
     MinuteMarketData,
     MinuteBar,
 };
+pub use orderbook::{LevelAction, LevelUpdate, OrderBook, PriceLevel, Side};
+pub use filter::{FilterVerdict, TickFilter, TickFilterStats};
+pub use options::{parse_option_id, Greeks, OptionAnalytics, OptionCode, OptionContract, OptionType, PricingModel};
+pub use timestamp::TimestampNormalizer;
+pub use resample::{resample, resample_daily, BarResampler, Frequency};
+pub use index::{Constituent, IndexCalculator, IndexValue, IndexWeighting};
+pub use constants::exchange::{exchange_of, ExchangeInfo, TradingSession};
 
 #[cfg(test)]
 mod tests {
@@ -95,6 +108,9 @@ mod tests {
             pre_settlement: OptionalF64::String("-".to_string()),
             settlement: OptionalF64::String("-".to_string()),
             iopv: OptionalF64::Null,
+            ins_class: None,
+            underlying_symbol: None,
+            strike_price: None,
         };
 
         assert_eq!(snapshot.instrument_id, "SSE_688286");
@@ -169,6 +185,9 @@ mod tests {
             pre_settlement: OptionalF64::String("-".to_string()),
             settlement: OptionalF64::String("-".to_string()),
             iopv: OptionalF64::Null,
+            ins_class: None,
+            underlying_symbol: None,
+            strike_price: None,
         };
 
         let tick = Tick::from_snapshot(&snapshot);
@@ -243,6 +262,9 @@ mod tests {
             pre_settlement: OptionalF64::String("-".to_string()),
             settlement: OptionalF64::String("-".to_string()),
             iopv: OptionalF64::Null,
+            ins_class: None,
+            underlying_symbol: None,
+            strike_price: None,
         };
 
         let json = serde_json::to_string(&snapshot).unwrap();
@@ -252,4 +274,42 @@ mod tests {
         assert_eq!(deserialized.last_price, snapshot.last_price);
         assert_eq!(deserialized.bid_ask_spread(), snapshot.bid_ask_spread());
     }
+
+    #[test]
+    fn test_snapshot_builder() {
+        let now = Utc::now();
+        let snapshot = MDSnapshot::builder("SSE_510050", now)
+            .last_price(2.85)
+            .volume(120000)
+            .amount(342000.0)
+            .limits(2.57, 3.13)
+            .bid(1, 2.849, 500)
+            .ask(1, 2.851, 800)
+            .ask(10, 2.86, 100)
+            .close(OptionalF64::String("-".to_string()))
+            .iopv(2.8502)
+            .build();
+
+        assert_eq!(snapshot.instrument_id, "SSE_510050");
+        assert_eq!(snapshot.datetime, now);
+        assert_eq!(snapshot.lower_limit, 2.57);
+        assert_eq!(snapshot.upper_limit, 3.13);
+        assert_eq!(snapshot.ask_price10, Some(2.86));
+        assert_eq!(snapshot.ask_volume10, Some(100));
+        assert!(!snapshot.has_level2_depth());
+        assert!(snapshot.is_etf());
+        assert!(!snapshot.is_futures_or_options());
+        assert_eq!(snapshot.close, OptionalF64::String("-".to_string()));
+
+        let default = MDSnapshot::default();
+        assert!(default.instrument_id.is_empty());
+        assert_eq!(default.settlement, OptionalF64::Null);
+        assert_eq!(default.bid_price2, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_snapshot_builder_rejects_invalid_level() {
+        MDSnapshot::builder("SSE_510050", Utc::now()).bid(11, 2.8, 100);
+    }
 }
@@ -0,0 +1,479 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::MDSnapshot;
+
+/// Seconds in a year used to convert time to expiry (365 calendar days)
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0;
+
+/// Volatility search range for implied volatility
+const MIN_VOLATILITY: f64 = 1e-4;
+const MAX_VOLATILITY: f64 = 5.0;
+
+/// Price tolerance and iteration limit of the implied volatility solver
+const IV_TOLERANCE: f64 = 1e-8;
+const IV_MAX_ITERATIONS: usize = 100;
+
+/// Call or put
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum OptionType {
+    /// Right to buy the underlying
+    Call,
+    /// Right to sell the underlying
+    Put,
+}
+
+impl OptionType {
+    /// Parse a call/put flag ("C", "CALL", "P", "PUT", case insensitive)
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.trim().to_ascii_uppercase().as_str() {
+            "C" | "CALL" => Some(OptionType::Call),
+            "P" | "PUT" => Some(OptionType::Put),
+            _ => None,
+        }
+    }
+}
+
+/// Pricing model
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum PricingModel {
+    /// Black-76, for options on futures (underlying is the futures price)
+    Black76,
+    /// Black-Scholes, for options on spot underlyings such as stocks and ETFs
+    BlackScholes,
+}
+
+/// Option sensitivities
+///
+/// `vega` and `rho` are per 1% change of volatility and rate,
+/// `theta` is per calendar day.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Greeks {
+    /// Sensitivity to the underlying price
+    pub delta: f64,
+    /// Sensitivity of delta to the underlying price
+    pub gamma: f64,
+    /// Price change for a 1% increase in volatility
+    pub vega: f64,
+    /// Price change for one calendar day passing
+    pub theta: f64,
+    /// Price change for a 1% increase in the risk-free rate
+    pub rho: f64,
+}
+
+/// Implied volatility and greeks of an option quote
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct OptionAnalytics {
+    /// Annualized implied volatility
+    pub implied_volatility: f64,
+    /// Greeks at the implied volatility
+    pub greeks: Greeks,
+}
+
+/// Standard normal probability density
+pub fn norm_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+/// Standard normal cumulative distribution
+pub fn norm_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+/// Complementary error function (Chebyshev approximation, relative error < 1.2e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let ans = t * poly.exp();
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
+
+/// d1 and d2 terms shared by both models
+fn d1_d2(model: PricingModel, underlying: f64, strike: f64, t: f64, rate: f64, vol: f64) -> (f64, f64) {
+    let vol_sqrt_t = vol * t.sqrt();
+    let drift = match model {
+        PricingModel::Black76 => 0.5 * vol * vol,
+        PricingModel::BlackScholes => rate + 0.5 * vol * vol,
+    };
+    let d1 = ((underlying / strike).ln() + drift * t) / vol_sqrt_t;
+    (d1, d1 - vol_sqrt_t)
+}
+
+/// Theoretical option price
+///
+/// `t` is the time to expiry in years, `rate` and `vol` are annualized.
+/// Returns the discounted intrinsic value when `t` or `vol` is not positive.
+pub fn price(
+    model: PricingModel,
+    option_type: OptionType,
+    underlying: f64,
+    strike: f64,
+    t: f64,
+    rate: f64,
+    vol: f64,
+) -> f64 {
+    let discount = (-rate * t.max(0.0)).exp();
+    if t <= 0.0 || vol <= 0.0 {
+        let forward = match model {
+            PricingModel::Black76 => underlying,
+            PricingModel::BlackScholes => underlying / discount,
+        };
+        let intrinsic = match option_type {
+            OptionType::Call => (forward - strike).max(0.0),
+            OptionType::Put => (strike - forward).max(0.0),
+        };
+        return discount * intrinsic;
+    }
+
+    let (d1, d2) = d1_d2(model, underlying, strike, t, rate, vol);
+    // Black-76 discounts the futures price, Black-Scholes uses the spot price as is
+    let underlying_pv = match model {
+        PricingModel::Black76 => discount * underlying,
+        PricingModel::BlackScholes => underlying,
+    };
+    match option_type {
+        OptionType::Call => underlying_pv * norm_cdf(d1) - discount * strike * norm_cdf(d2),
+        OptionType::Put => discount * strike * norm_cdf(-d2) - underlying_pv * norm_cdf(-d1),
+    }
+}
+
+/// Option greeks, see [`Greeks`] for units
+pub fn greeks(
+    model: PricingModel,
+    option_type: OptionType,
+    underlying: f64,
+    strike: f64,
+    t: f64,
+    rate: f64,
+    vol: f64,
+) -> Greeks {
+    if t <= 0.0 || vol <= 0.0 {
+        return Greeks::default();
+    }
+
+    let (d1, d2) = d1_d2(model, underlying, strike, t, rate, vol);
+    let sqrt_t = t.sqrt();
+    let discount = (-rate * t).exp();
+    let pdf = norm_pdf(d1);
+
+    let (delta, gamma, vega, theta, rho) = match model {
+        PricingModel::Black76 => {
+            let value = price(model, option_type, underlying, strike, t, rate, vol);
+            let delta = match option_type {
+                OptionType::Call => discount * norm_cdf(d1),
+                OptionType::Put => -discount * norm_cdf(-d1),
+            };
+            let gamma = discount * pdf / (underlying * vol * sqrt_t);
+            let vega = discount * underlying * pdf * sqrt_t;
+            let theta = rate * value - discount * underlying * pdf * vol / (2.0 * sqrt_t);
+            // The futures price does not depend on the rate, only the discounting does
+            let rho = -t * value;
+            (delta, gamma, vega, theta, rho)
+        }
+        PricingModel::BlackScholes => {
+            let decay = -underlying * pdf * vol / (2.0 * sqrt_t);
+            let (delta, theta, rho) = match option_type {
+                OptionType::Call => (
+                    norm_cdf(d1),
+                    decay - rate * strike * discount * norm_cdf(d2),
+                    strike * t * discount * norm_cdf(d2),
+                ),
+                OptionType::Put => (
+                    norm_cdf(d1) - 1.0,
+                    decay + rate * strike * discount * norm_cdf(-d2),
+                    -strike * t * discount * norm_cdf(-d2),
+                ),
+            };
+            let gamma = pdf / (underlying * vol * sqrt_t);
+            let vega = underlying * pdf * sqrt_t;
+            (delta, gamma, vega, theta, rho)
+        }
+    };
+
+    Greeks {
+        delta,
+        gamma,
+        vega: vega / 100.0,
+        theta: theta / 365.0,
+        rho: rho / 100.0,
+    }
+}
+
+/// Implied volatility of an option price
+///
+/// Uses Newton's method safeguarded by bisection. Returns `None` when the
+/// inputs are not positive or the price is outside the range reachable
+/// with a volatility between 0.01% and 500%.
+pub fn implied_volatility(
+    model: PricingModel,
+    option_type: OptionType,
+    option_price: f64,
+    underlying: f64,
+    strike: f64,
+    t: f64,
+    rate: f64,
+) -> Option<f64> {
+    if !(option_price > 0.0 && underlying > 0.0 && strike > 0.0 && t > 0.0) {
+        return None;
+    }
+
+    let value = |vol: f64| price(model, option_type, underlying, strike, t, rate, vol);
+    let (mut low, mut high) = (MIN_VOLATILITY, MAX_VOLATILITY);
+    if option_price < value(low) - IV_TOLERANCE || option_price > value(high) + IV_TOLERANCE {
+        return None;
+    }
+
+    let mut vol = 0.3;
+    for _ in 0..IV_MAX_ITERATIONS {
+        let diff = value(vol) - option_price;
+        if diff.abs() < IV_TOLERANCE {
+            return Some(vol);
+        }
+        // Price is increasing in volatility, keep the root bracketed
+        if diff > 0.0 {
+            high = vol;
+        } else {
+            low = vol;
+        }
+
+        // Raw vega (per unit of volatility)
+        let vega = greeks(model, option_type, underlying, strike, t, rate, vol).vega * 100.0;
+        let newton = vol - diff / vega;
+        vol = if vega > f64::EPSILON && newton > low && newton < high {
+            newton
+        } else {
+            0.5 * (low + high)
+        };
+    }
+    Some(vol)
+}
+
+/// Terms of an option contract
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct OptionContract {
+    /// Call or put
+    pub option_type: OptionType,
+    /// Strike price
+    pub strike: f64,
+    /// Expiry time
+    pub expiry: DateTime<Utc>,
+    /// Model used for pricing
+    pub model: PricingModel,
+}
+
+impl OptionContract {
+    /// Create a new contract description
+    pub fn new(option_type: OptionType, strike: f64, expiry: DateTime<Utc>, model: PricingModel) -> Self {
+        Self {
+            option_type,
+            strike,
+            expiry,
+            model,
+        }
+    }
+
+    /// Time to expiry in years, zero once expired
+    pub fn time_to_expiry(&self, now: DateTime<Utc>) -> f64 {
+        ((self.expiry - now).num_milliseconds() as f64 / 1000.0 / SECONDS_PER_YEAR).max(0.0)
+    }
+
+    /// Implied volatility and greeks of an option snapshot
+    ///
+    /// The option is valued at its last price, falling back to the mid of the
+    /// best quotes when it has not traded. Returns `None` for expired contracts
+    /// or when no volatility reproduces the quote.
+    pub fn analyze(&self, snapshot: &MDSnapshot, underlying_price: f64, rate: f64) -> Option<OptionAnalytics> {
+        let option_price = quote_price(snapshot)?;
+        let t = self.time_to_expiry(snapshot.datetime);
+        let vol = implied_volatility(self.model, self.option_type, option_price, underlying_price, self.strike, t, rate)?;
+
+        Some(OptionAnalytics {
+            implied_volatility: vol,
+            greeks: greeks(self.model, self.option_type, underlying_price, self.strike, t, rate, vol),
+        })
+    }
+}
+
+/// CFFEX index option products and the index futures standing in for their underlying index
+const INDEX_OPTION_FUTURES: &[(&str, &str)] = &[("IO", "IF"), ("MO", "IM"), ("HO", "IH")];
+
+/// Terms encoded in a futures option code
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OptionCode {
+    /// Underlying futures contract, with the exchange prefix of the option code if it had one
+    pub underlying: String,
+    /// Call or put
+    pub option_type: OptionType,
+    /// Strike price
+    pub strike: f64,
+}
+
+/// Parse the underlying, call/put flag and strike from a futures option code
+///
+/// Accepts `m2409-C-3000` (DCE, GFEX, CFFEX), `cu2409C70000` (SHFE, INE) and `SR409C5000`
+/// (CZCE), optionally with an exchange prefix such as `DCE.`. CFFEX index options (`IO`, `MO`,
+/// `HO`) map to the index futures of the same month. Stock and ETF options have numeric codes
+/// that carry none of these terms and return `None`, as do futures and spreads.
+pub fn parse_option_id(instrument_id: &str) -> Option<OptionCode> {
+    let (exchange, code) = match instrument_id.split_once('.') {
+        Some((exchange, code)) => (Some(exchange), code),
+        None => (None, instrument_id),
+    };
+    let product_len = code.bytes().take_while(u8::is_ascii_alphabetic).count();
+    let month_len = code[product_len..].bytes().take_while(u8::is_ascii_digit).count();
+    if product_len == 0 || !(3..=4).contains(&month_len) {
+        return None;
+    }
+    let (contract, terms) = code.split_at(product_len + month_len);
+    let terms = terms.strip_prefix('-').unwrap_or(terms);
+    let option_type = match terms.bytes().next()? {
+        b'C' | b'c' => OptionType::Call,
+        b'P' | b'p' => OptionType::Put,
+        _ => return None,
+    };
+    let strike = terms[1..].strip_prefix('-').unwrap_or(&terms[1..]);
+    if strike.is_empty() || !strike.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return None;
+    }
+    let strike: f64 = strike.parse().ok().filter(|strike| *strike > 0.0)?;
+
+    let (product, month) = contract.split_at(product_len);
+    let product = INDEX_OPTION_FUTURES
+        .iter()
+        .find(|(option, _)| *option == product)
+        .map_or(product, |(_, future)| future);
+    let underlying = match exchange {
+        Some(exchange) => format!("{}.{}{}", exchange, product, month),
+        None => format!("{}{}", product, month),
+    };
+    Some(OptionCode {
+        underlying,
+        option_type,
+        strike,
+    })
+}
+
+/// Reference price of a snapshot: last price, else the mid of the best quotes
+pub fn quote_price(snapshot: &MDSnapshot) -> Option<f64> {
+    let valid = |price: f64| price.is_finite() && price > 0.0 && price < f64::MAX / 2.0;
+    if valid(snapshot.last_price) {
+        return Some(snapshot.last_price);
+    }
+    (valid(snapshot.bid_price1) && valid(snapshot.ask_price1))
+        .then(|| (snapshot.bid_price1 + snapshot.ask_price1) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "expected {} got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_black_scholes_reference_values() {
+        let model = PricingModel::BlackScholes;
+        let call = price(model, OptionType::Call, 100.0, 100.0, 1.0, 0.05, 0.2);
+        let put = price(model, OptionType::Put, 100.0, 100.0, 1.0, 0.05, 0.2);
+        assert_close(call, 10.4506, 1e-4);
+        assert_close(put, 5.5735, 1e-4);
+        // Put-call parity
+        assert_close(call - put, 100.0 - 100.0 * (-0.05f64).exp(), 1e-6);
+
+        let g = greeks(model, OptionType::Call, 100.0, 100.0, 1.0, 0.05, 0.2);
+        assert_close(g.delta, 0.6368, 1e-4);
+        assert_close(g.gamma, 0.018762, 1e-5);
+        assert_close(g.vega, 0.37524, 1e-4);
+        assert_close(g.theta, -6.4140 / 365.0, 1e-5);
+        assert_close(g.rho, 0.53232, 1e-4);
+    }
+
+    #[test]
+    fn test_black76_parity_and_greeks() {
+        let model = PricingModel::Black76;
+        let (f, k, t, r, vol) = (3500.0, 3600.0, 0.25, 0.02, 0.25);
+        let call = price(model, OptionType::Call, f, k, t, r, vol);
+        let put = price(model, OptionType::Put, f, k, t, r, vol);
+        assert_close(call - put, (-r * t).exp() * (f - k), 1e-6);
+
+        let call_greeks = greeks(model, OptionType::Call, f, k, t, r, vol);
+        let put_greeks = greeks(model, OptionType::Put, f, k, t, r, vol);
+        assert_close(call_greeks.delta - put_greeks.delta, (-r * t).exp(), 1e-9);
+        assert_close(call_greeks.gamma, put_greeks.gamma, 1e-12);
+        assert!(call_greeks.theta < 0.0);
+    }
+
+    #[test]
+    fn test_implied_volatility_round_trip() {
+        for model in [PricingModel::Black76, PricingModel::BlackScholes] {
+            for option_type in [OptionType::Call, OptionType::Put] {
+                for strike in [80.0, 100.0, 125.0] {
+                    let target = price(model, option_type, 100.0, strike, 0.5, 0.03, 0.35);
+                    let vol = implied_volatility(model, option_type, target, 100.0, strike, 0.5, 0.03).unwrap();
+                    assert_close(vol, 0.35, 1e-5);
+                }
+            }
+        }
+
+        // Below intrinsic value
+        assert!(implied_volatility(PricingModel::Black76, OptionType::Call, 1.0, 120.0, 100.0, 0.5, 0.0).is_none());
+        assert!(implied_volatility(PricingModel::Black76, OptionType::Call, 1.0, 100.0, 100.0, 0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_option_contract_analyze() {
+        let now = Utc::now();
+        let contract = OptionContract::new(OptionType::Put, 3400.0, now + Duration::days(60), PricingModel::Black76);
+        let t = contract.time_to_expiry(now);
+        let premium = price(contract.model, OptionType::Put, 3500.0, 3400.0, t, 0.02, 0.22);
+
+        let snapshot = MDSnapshot::builder("SHFE.rb2410P3400", now)
+            .last_price(premium)
+            .build();
+        let analytics = contract.analyze(&snapshot, 3500.0, 0.02).unwrap();
+        assert_close(analytics.implied_volatility, 0.22, 1e-5);
+        assert!(analytics.greeks.delta < 0.0 && analytics.greeks.delta > -0.5);
+
+        let expired = OptionContract::new(OptionType::Put, 3400.0, now - Duration::days(1), PricingModel::Black76);
+        assert_eq!(expired.time_to_expiry(now), 0.0);
+        assert!(expired.analyze(&snapshot, 3500.0, 0.02).is_none());
+    }
+
+    #[test]
+    fn test_parse_option_id() {
+        let code = parse_option_id("DCE.m2409-C-3000").unwrap();
+        assert_eq!(code.underlying, "DCE.m2409");
+        assert_eq!(code.option_type, OptionType::Call);
+        assert_eq!(code.strike, 3000.0);
+
+        let code = parse_option_id("cu2409P70000").unwrap();
+        assert_eq!((code.underlying.as_str(), code.option_type, code.strike), ("cu2409", OptionType::Put, 70000.0));
+        let code = parse_option_id("CZCE.SR409C5000").unwrap();
+        assert_eq!((code.underlying.as_str(), code.strike), ("CZCE.SR409", 5000.0));
+        // Index options refer to the index futures of the same month
+        assert_eq!(parse_option_id("CFFEX.IO2409-P-3500").unwrap().underlying, "CFFEX.IF2409");
+
+        for not_option in ["SHFE.rb2410", "SSE.10007000", "SP m2409&m2501", "m2409-C-", "cu2409X70000"] {
+            assert!(parse_option_id(not_option).is_none(), "{}", not_option);
+        }
+    }
+}
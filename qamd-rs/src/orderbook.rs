@@ -0,0 +1,402 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use crate::snapshot::MDSnapshot;
+
+/// Maximum number of levels carried by an `MDSnapshot`
+pub const MAX_DEPTH: usize = 10;
+
+/// A single price level in the order book
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PriceLevel {
+    /// Price of the level
+    pub price: f64,
+    /// Resting volume at this price
+    pub volume: i64,
+}
+
+impl PriceLevel {
+    /// Create a new price level
+    pub fn new(price: f64, volume: i64) -> Self {
+        Self { price, volume }
+    }
+
+    /// Notional value of the level (price * volume)
+    pub fn notional(&self) -> f64 {
+        self.price * self.volume as f64
+    }
+}
+
+/// Side of the order book
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Side {
+    /// Bid (buy) side
+    #[serde(rename = "bid", alias = "Bid")]
+    Bid,
+    /// Ask (sell) side
+    #[serde(rename = "ask", alias = "Ask")]
+    Ask,
+}
+
+/// Kind of change of a price level between two books
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelAction {
+    /// A new price level appeared
+    Add,
+    /// The volume resting at an existing price changed
+    Modify,
+    /// The price level is gone
+    Delete,
+}
+
+/// Incremental change of one price level
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct LevelUpdate {
+    /// Side of the level
+    pub side: Side,
+    /// What happened to the level
+    pub action: LevelAction,
+    /// Price of the level
+    pub price: f64,
+    /// Resting volume after the change (0 for deletes)
+    pub volume: i64,
+}
+
+/// Level 2 order book reconstructed from market data snapshots
+///
+/// Bids are ordered from the highest price down, asks from the lowest price up,
+/// so index 0 on each side is always the best level.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OrderBook {
+    /// Unique identifier for the instrument
+    pub instrument_id: String,
+
+    /// Timestamp of the snapshot the book was last updated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub datetime: Option<DateTime<Utc>>,
+
+    /// Bid levels, best first
+    pub bids: Vec<PriceLevel>,
+
+    /// Ask levels, best first
+    pub asks: Vec<PriceLevel>,
+}
+
+impl OrderBook {
+    /// Create an empty order book for an instrument
+    pub fn new(instrument_id: impl Into<String>) -> Self {
+        Self {
+            instrument_id: instrument_id.into(),
+            datetime: None,
+            bids: Vec::new(),
+            asks: Vec::new(),
+        }
+    }
+
+    /// Build an order book from a market data snapshot
+    pub fn from_snapshot(snapshot: &MDSnapshot) -> Self {
+        let mut book = Self::new(snapshot.instrument_id.clone());
+        book.update(snapshot);
+        book
+    }
+
+    /// Replace the book contents with the depth carried by a snapshot
+    pub fn update(&mut self, snapshot: &MDSnapshot) {
+        if self.instrument_id.is_empty() {
+            self.instrument_id = snapshot.instrument_id.clone();
+        }
+        self.datetime = Some(snapshot.datetime);
+        self.bids = snapshot_levels(snapshot, Side::Bid);
+        self.asks = snapshot_levels(snapshot, Side::Ask);
+    }
+
+    /// Level changes turning this book into `next`, matched by price
+    ///
+    /// Deletes come first, so applying the changes in order never holds two
+    /// levels at the same price.
+    pub fn diff(&self, next: &OrderBook) -> Vec<LevelUpdate> {
+        let mut deletes = Vec::new();
+        let mut changes = Vec::new();
+        for side in [Side::Bid, Side::Ask] {
+            let (before, after) = (self.side(side), next.side(side));
+            for level in before {
+                if !after.iter().any(|l| l.price == level.price) {
+                    deletes.push(LevelUpdate { side, action: LevelAction::Delete, price: level.price, volume: 0 });
+                }
+            }
+            for level in after {
+                let action = match before.iter().find(|l| l.price == level.price) {
+                    None => LevelAction::Add,
+                    Some(previous) if previous.volume != level.volume => LevelAction::Modify,
+                    Some(_) => continue,
+                };
+                changes.push(LevelUpdate { side, action, price: level.price, volume: level.volume });
+            }
+        }
+        deletes.extend(changes);
+        deletes
+    }
+
+    /// Apply level changes produced by [`OrderBook::diff`], keeping both sides sorted best first
+    pub fn apply(&mut self, updates: &[LevelUpdate]) {
+        for update in updates {
+            let levels = match update.side {
+                Side::Bid => &mut self.bids,
+                Side::Ask => &mut self.asks,
+            };
+            let position = levels.iter().position(|l| l.price == update.price);
+            match (update.action, position) {
+                (LevelAction::Delete, Some(i)) => {
+                    levels.remove(i);
+                }
+                (LevelAction::Delete, None) => {}
+                (_, Some(i)) => levels[i].volume = update.volume,
+                (_, None) => levels.push(PriceLevel::new(update.price, update.volume)),
+            }
+        }
+        self.bids.sort_by(|a, b| b.price.total_cmp(&a.price));
+        self.asks.sort_by(|a, b| a.price.total_cmp(&b.price));
+    }
+
+    /// Best (highest) bid level
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.first()
+    }
+
+    /// Best (lowest) ask level
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.first()
+    }
+
+    /// Levels of one side, best first
+    pub fn side(&self, side: Side) -> &[PriceLevel] {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    /// Iterate over bid levels, best first
+    pub fn bids(&self) -> impl Iterator<Item = &PriceLevel> {
+        self.bids.iter()
+    }
+
+    /// Iterate over ask levels, best first
+    pub fn asks(&self) -> impl Iterator<Item = &PriceLevel> {
+        self.asks.iter()
+    }
+
+    /// Iterate over (bid, ask) pairs level by level
+    pub fn levels(&self) -> impl Iterator<Item = (Option<&PriceLevel>, Option<&PriceLevel>)> {
+        (0..self.depth()).map(move |i| (self.bids.get(i), self.asks.get(i)))
+    }
+
+    /// Number of levels on the deeper side of the book
+    pub fn depth(&self) -> usize {
+        self.bids.len().max(self.asks.len())
+    }
+
+    /// Check if the book has no levels at all
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /// Mid price between the best bid and the best ask
+    pub fn mid_price(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some((bid.price + ask.price) / 2.0),
+            _ => None,
+        }
+    }
+
+    /// Spread between the best ask and the best bid
+    pub fn spread(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some(ask.price - bid.price),
+            _ => None,
+        }
+    }
+
+    /// Check if the best bid is at or above the best ask
+    pub fn is_crossed(&self) -> bool {
+        matches!(self.spread(), Some(spread) if spread <= 0.0)
+    }
+
+    /// Total resting volume of the top `levels` levels on one side
+    pub fn total_volume(&self, side: Side, levels: usize) -> i64 {
+        self.side(side).iter().take(levels).map(|l| l.volume).sum()
+    }
+
+    /// Volume imbalance of the top `levels` levels, in [-1, 1]
+    ///
+    /// Positive values mean more resting bid volume than ask volume.
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        let bid = self.total_volume(Side::Bid, levels) as f64;
+        let ask = self.total_volume(Side::Ask, levels) as f64;
+        let total = bid + ask;
+        if total > 0.0 {
+            Some((bid - ask) / total)
+        } else {
+            None
+        }
+    }
+
+    /// Volume-weighted mid price using the top of book sizes (microprice)
+    pub fn weighted_mid_price(&self) -> Option<f64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        let total = (bid.volume + ask.volume) as f64;
+        if total > 0.0 {
+            Some((bid.price * ask.volume as f64 + ask.price * bid.volume as f64) / total)
+        } else {
+            None
+        }
+    }
+}
+
+/// Check that a level carries a real quote rather than a missing-value sentinel
+fn is_valid_level(price: f64, volume: i64) -> bool {
+    price.is_finite() && price > 0.0 && price < f64::MAX / 2.0 && volume > 0
+}
+
+/// Extract the populated levels of one side of a snapshot, best first
+fn snapshot_levels(snapshot: &MDSnapshot, side: Side) -> Vec<PriceLevel> {
+    let raw: [(Option<f64>, Option<i64>); MAX_DEPTH] = match side {
+        Side::Bid => [
+            (Some(snapshot.bid_price1), Some(snapshot.bid_volume1)),
+            (snapshot.bid_price2, snapshot.bid_volume2),
+            (snapshot.bid_price3, snapshot.bid_volume3),
+            (snapshot.bid_price4, snapshot.bid_volume4),
+            (snapshot.bid_price5, snapshot.bid_volume5),
+            (snapshot.bid_price6, snapshot.bid_volume6),
+            (snapshot.bid_price7, snapshot.bid_volume7),
+            (snapshot.bid_price8, snapshot.bid_volume8),
+            (snapshot.bid_price9, snapshot.bid_volume9),
+            (snapshot.bid_price10, snapshot.bid_volume10),
+        ],
+        Side::Ask => [
+            (Some(snapshot.ask_price1), Some(snapshot.ask_volume1)),
+            (snapshot.ask_price2, snapshot.ask_volume2),
+            (snapshot.ask_price3, snapshot.ask_volume3),
+            (snapshot.ask_price4, snapshot.ask_volume4),
+            (snapshot.ask_price5, snapshot.ask_volume5),
+            (snapshot.ask_price6, snapshot.ask_volume6),
+            (snapshot.ask_price7, snapshot.ask_volume7),
+            (snapshot.ask_price8, snapshot.ask_volume8),
+            (snapshot.ask_price9, snapshot.ask_volume9),
+            (snapshot.ask_price10, snapshot.ask_volume10),
+        ],
+    };
+
+    let mut levels: Vec<PriceLevel> = raw
+        .iter()
+        .filter_map(|&(price, volume)| match (price, volume) {
+            (Some(p), Some(v)) if is_valid_level(p, v) => Some(PriceLevel::new(p, v)),
+            _ => None,
+        })
+        .collect();
+
+    // Upstream feeds are already sorted, but guard against out-of-order levels
+    match side {
+        Side::Bid => levels.sort_by(|a, b| b.price.total_cmp(&a.price)),
+        Side::Ask => levels.sort_by(|a, b| a.price.total_cmp(&b.price)),
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OptionalF64;
+    use chrono::Utc;
+
+    fn snapshot() -> MDSnapshot {
+        serde_json::from_value(serde_json::json!({
+            "instrument_id": "SHFE.rb2410",
+            "amount": 0.0,
+            "ask_price1": 3501.0, "ask_volume1": 10,
+            "ask_price2": 3502.0, "ask_volume2": 20,
+            "bid_price1": 3500.0, "bid_volume1": 30,
+            "bid_price2": 3499.0, "bid_volume2": 40,
+            "bid_price3": 3498.0, "bid_volume3": 0,
+            "close": null,
+            "datetime": Utc::now(),
+            "highest": 0.0, "last_price": 3500.0, "lower_limit": 0.0, "lowest": 0.0,
+            "open": 0.0, "open_interest": null, "pre_close": 0.0,
+            "pre_open_interest": null, "pre_settlement": null, "settlement": null,
+            "upper_limit": 0.0, "volume": 0, "average": 0.0, "iopv": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_orderbook_from_snapshot() {
+        let book = OrderBook::from_snapshot(&snapshot());
+
+        assert_eq!(book.instrument_id, "SHFE.rb2410");
+        assert_eq!(book.bids.len(), 2, "zero-volume levels are dropped");
+        assert_eq!(book.asks.len(), 2);
+        assert_eq!(book.best_bid(), Some(&PriceLevel::new(3500.0, 30)));
+        assert_eq!(book.best_ask(), Some(&PriceLevel::new(3501.0, 10)));
+        assert_eq!(book.mid_price(), Some(3500.5));
+        assert_eq!(book.spread(), Some(1.0));
+        assert!(!book.is_crossed());
+        assert_eq!(book.levels().count(), 2);
+
+        // (70 - 30) / 100
+        assert_eq!(book.imbalance(MAX_DEPTH), Some(0.4));
+        assert_eq!(book.imbalance(1), Some(0.5));
+
+        let micro = book.weighted_mid_price().unwrap();
+        assert!(micro > 3500.5, "heavier bid pushes microprice toward the ask");
+    }
+
+    #[test]
+    fn test_orderbook_update_and_serde() {
+        let mut snap = snapshot();
+        let mut book = OrderBook::from_snapshot(&snap);
+
+        snap.ask_price1 = 3499.0;
+        snap.close = OptionalF64::Null;
+        book.update(&snap);
+        assert!(book.is_crossed());
+
+        let json = serde_json::to_string(&book).unwrap();
+        let back: OrderBook = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, book);
+
+        assert!(OrderBook::new("empty").mid_price().is_none());
+        assert!(OrderBook::new("empty").imbalance(5).is_none());
+    }
+
+    #[test]
+    fn test_orderbook_diff_and_apply() {
+        let mut snap = snapshot();
+        let before = OrderBook::from_snapshot(&snap);
+        assert!(before.diff(&before).is_empty());
+
+        // Best bid trades away, the ask gains volume and a new ask level appears
+        snap.bid_price1 = 3499.0;
+        snap.bid_volume1 = 40;
+        snap.bid_price2 = Some(3498.0);
+        snap.bid_volume2 = Some(5);
+        snap.ask_volume1 = 12;
+        snap.ask_price3 = Some(3503.0);
+        snap.ask_volume3 = Some(1);
+        let after = OrderBook::from_snapshot(&snap);
+
+        let changes = before.diff(&after);
+        assert_eq!(changes[0], LevelUpdate { side: Side::Bid, action: LevelAction::Delete, price: 3500.0, volume: 0 });
+        assert!(changes.contains(&LevelUpdate { side: Side::Bid, action: LevelAction::Add, price: 3498.0, volume: 5 }));
+        assert!(changes.contains(&LevelUpdate { side: Side::Ask, action: LevelAction::Modify, price: 3501.0, volume: 12 }));
+        assert!(changes.contains(&LevelUpdate { side: Side::Ask, action: LevelAction::Add, price: 3503.0, volume: 1 }));
+        assert_eq!(changes.len(), 4, "unchanged levels are not sent: {:?}", changes);
+
+        let mut book = before.clone();
+        book.apply(&changes);
+        assert_eq!(book.bids, after.bids);
+        assert_eq!(book.asks, after.asks);
+
+        let json = serde_json::to_value(changes[0]).unwrap();
+        assert_eq!(json, serde_json::json!({ "side": "bid", "action": "delete", "price": 3500.0, "volume": 0 }));
+    }
+}
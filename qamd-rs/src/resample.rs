@@ -0,0 +1,350 @@
+//! Minute bar resampling
+//!
+//! [`BarResampler`] aggregates a stream of [`MinuteBar`]s into coarser bars without any
+//! dataframe dependency. Bars of one instrument must arrive in time order, different
+//! instruments may be interleaved.
+//!
+//! An `N`-minute bar covers `N` consecutive minute bars of the same trading date, so
+//! buckets follow the trading sessions rather than the wall clock: 60-minute stock bars
+//! end at 10:30, 11:30, 14:00 and 15:00. A bar is emitted as soon as its last minute
+//! arrives, a partial bar when the instrument's trading date changes or on
+//! [`flush`](BarResampler::flush). Resampled bars carry the datetime of their last
+//! minute bar and always have a trading date.
+//!
+//! Futures minute bars without a trading date are attributed with
+//! [`TimestampNormalizer::trading_date`], which puts night sessions on the next weekday
+//! but knows no holidays; [`BarResampler::with_trading_date`] plugs in a calendar.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::daily::{DailyBar, InstrumentType};
+use crate::error::QAMDError;
+use crate::minute::MinuteBar;
+use crate::timestamp::TimestampNormalizer;
+
+/// Target frequency of a resampler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    /// Bars of this many trading minutes
+    Minutes(u32),
+    /// One bar per trading date
+    Daily,
+}
+
+impl Frequency {
+    /// Minute bars per resampled bar, `None` for daily bars
+    pub fn minutes(&self) -> Option<u32> {
+        match self {
+            Frequency::Minutes(n) => Some((*n).max(1)),
+            Frequency::Daily => None,
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = QAMDError;
+
+    /// Parses `5m`, `15min`, `1h`, `1d`, `day` or `daily`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        if matches!(s.as_str(), "d" | "1d" | "day" | "daily") {
+            return Ok(Frequency::Daily);
+        }
+        let invalid = || QAMDError::General(format!("Invalid bar frequency: {}", s));
+        let (count, minutes_per_unit) = if let Some(count) = s.strip_suffix("min") {
+            (count, 1)
+        } else if let Some(count) = s.strip_suffix('m') {
+            (count, 1)
+        } else if let Some(count) = s.strip_suffix('h') {
+            (count, 60)
+        } else {
+            return Err(invalid());
+        };
+        match count.parse::<u32>() {
+            Ok(count) if count > 0 => Ok(Frequency::Minutes(count * minutes_per_unit)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frequency::Minutes(n) => write!(f, "{}m", n),
+            Frequency::Daily => write!(f, "1d"),
+        }
+    }
+}
+
+/// A resampled bar being built
+struct Bucket {
+    bar: MinuteBar,
+    minutes: u32,
+}
+
+impl Bucket {
+    fn new(bar: &MinuteBar, trading_date: NaiveDate) -> Self {
+        let mut bar = bar.clone();
+        bar.trading_date = Some(trading_date);
+        Self { bar, minutes: 1 }
+    }
+
+    fn add(&mut self, bar: &MinuteBar) {
+        let acc = &mut self.bar;
+        acc.datetime = bar.datetime;
+        acc.high = acc.high.max(bar.high);
+        acc.low = acc.low.min(bar.low);
+        acc.close = bar.close;
+        acc.volume += bar.volume;
+        acc.total_turnover += bar.total_turnover;
+        // Open interest is a level, keep the latest reported one
+        if bar.open_interest.is_some() {
+            acc.open_interest = bar.open_interest;
+        }
+        self.minutes += 1;
+    }
+}
+
+/// Trading date of a bar without one
+type TradingDateFn = Box<dyn Fn(&MinuteBar) -> NaiveDate + Send + Sync>;
+
+/// Default attribution: night sessions move futures to the next weekday, other
+/// instruments trade on their China local calendar day
+fn default_trading_date(bar: &MinuteBar) -> NaiveDate {
+    let normalizer = TimestampNormalizer::china();
+    if bar.instrument_type == InstrumentType::Future {
+        normalizer.trading_date(bar.datetime)
+    } else {
+        bar.datetime.with_timezone(&normalizer.offset()).date_naive()
+    }
+}
+
+/// Streaming aggregation of minute bars into a coarser frequency
+pub struct BarResampler {
+    frequency: Frequency,
+    trading_date: TradingDateFn,
+    // Instrument -> bar being built
+    open: HashMap<String, Bucket>,
+}
+
+impl BarResampler {
+    pub fn new(frequency: Frequency) -> Self {
+        Self {
+            frequency,
+            trading_date: Box::new(default_trading_date),
+            open: HashMap::new(),
+        }
+    }
+
+    /// Attribute bars without a trading date with a calendar, e.g. one that knows holidays
+    pub fn with_trading_date(
+        mut self,
+        trading_date: impl Fn(DateTime<Utc>, InstrumentType) -> NaiveDate + Send + Sync + 'static,
+    ) -> Self {
+        self.trading_date = Box::new(move |bar| trading_date(bar.datetime, bar.instrument_type));
+        self
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    /// Adds a minute bar, returns the bars it completes (at most two when the trading
+    /// date changes)
+    pub fn push(&mut self, bar: &MinuteBar) -> Vec<MinuteBar> {
+        let trading_date = bar.trading_date.unwrap_or_else(|| (self.trading_date)(bar));
+        let mut done = Vec::new();
+
+        match self.open.get_mut(&bar.order_book_id) {
+            Some(bucket) if bucket.bar.trading_date == Some(trading_date) => bucket.add(bar),
+            _ => {
+                let bucket = Bucket::new(bar, trading_date);
+                if let Some(previous) = self.open.insert(bar.order_book_id.clone(), bucket) {
+                    done.push(previous.bar);
+                }
+            }
+        }
+
+        if let Some(minutes) = self.frequency.minutes() {
+            if self.open.get(&bar.order_book_id).is_some_and(|b| b.minutes >= minutes) {
+                if let Some(bucket) = self.open.remove(&bar.order_book_id) {
+                    done.push(bucket.bar);
+                }
+            }
+        }
+        done
+    }
+
+    /// Emits the partial bars of all instruments, ordered by instrument
+    pub fn flush(&mut self) -> Vec<MinuteBar> {
+        let mut bars: Vec<MinuteBar> = self.open.drain().map(|(_, bucket)| bucket.bar).collect();
+        bars.sort_by(|a, b| a.order_book_id.cmp(&b.order_book_id));
+        bars
+    }
+}
+
+/// Resamples a batch of minute bars
+pub fn resample(bars: &[MinuteBar], frequency: Frequency) -> Vec<MinuteBar> {
+    let mut resampler = BarResampler::new(frequency);
+    let mut out: Vec<MinuteBar> = bars.iter().flat_map(|bar| resampler.push(bar)).collect();
+    out.extend(resampler.flush());
+    out
+}
+
+/// Aggregates a batch of minute bars into daily bars
+pub fn resample_daily(bars: &[MinuteBar]) -> Vec<DailyBar> {
+    resample(bars, Frequency::Daily).iter().map(daily_bar).collect()
+}
+
+/// A bar covering a whole trading date as a [`DailyBar`]
+pub fn daily_bar(bar: &MinuteBar) -> DailyBar {
+    let date = bar
+        .trading_date
+        .unwrap_or_else(|| bar.datetime.with_timezone(&TimestampNormalizer::china().offset()).date_naive());
+    let mut daily = DailyBar::new(
+        date,
+        bar.order_book_id.clone(),
+        bar.instrument_type,
+        bar.open,
+        bar.high,
+        bar.low,
+        bar.close,
+        bar.volume,
+        bar.total_turnover,
+    );
+    daily.open_interest = bar.open_interest;
+    daily
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    /// Stock minute bars from a China local time, one per minute
+    fn stock_bars(start: (u32, u32), count: i64) -> Vec<MinuteBar> {
+        let first = Utc.with_ymd_and_hms(2024, 1, 10, start.0 - 8, start.1, 0).unwrap();
+        (0..count)
+            .map(|i| {
+                let price = 10.0 + i as f32 * 0.01;
+                MinuteBar::new_stock(
+                    first + Duration::minutes(i),
+                    "000001.XSHE".to_string(),
+                    price,
+                    price + 0.05,
+                    price - 0.05,
+                    price + 0.01,
+                    100.0,
+                    1000.0,
+                )
+            })
+            .collect()
+    }
+
+    fn future_bar(datetime: DateTime<Utc>, close: f32, open_interest: f32) -> MinuteBar {
+        let mut bar = MinuteBar::new(
+            datetime,
+            "rb2405.SHFE".to_string(),
+            InstrumentType::Future,
+            close,
+            close + 2.0,
+            close - 2.0,
+            close,
+            10.0,
+            close * 10.0,
+        );
+        bar.open_interest = Some(open_interest);
+        bar
+    }
+
+    #[test]
+    fn test_parse_frequency() {
+        assert_eq!("5m".parse::<Frequency>().unwrap(), Frequency::Minutes(5));
+        assert_eq!("15min".parse::<Frequency>().unwrap(), Frequency::Minutes(15));
+        assert_eq!("1H".parse::<Frequency>().unwrap(), Frequency::Minutes(60));
+        assert_eq!("daily".parse::<Frequency>().unwrap(), Frequency::Daily);
+        assert!("0m".parse::<Frequency>().is_err());
+        assert!("5s".parse::<Frequency>().is_err());
+        assert_eq!(Frequency::Minutes(15).to_string(), "15m");
+    }
+
+    #[test]
+    fn test_five_minute_ohlcv() {
+        let bars = stock_bars((9, 31), 12);
+        let out = resample(&bars, Frequency::Minutes(5));
+        assert_eq!(out.len(), 3);
+
+        let first = &out[0];
+        assert_eq!(first.datetime, bars[4].datetime);
+        assert_eq!(first.open, bars[0].open);
+        assert_eq!(first.close, bars[4].close);
+        assert_eq!(first.high, bars[4].high);
+        assert_eq!(first.low, bars[0].low);
+        assert_eq!(first.volume, 500.0);
+        assert_eq!(first.total_turnover, 5000.0);
+        assert_eq!(first.trading_date, NaiveDate::from_ymd_opt(2024, 1, 10));
+        // The partial last bucket is emitted on flush
+        assert_eq!(out[2].datetime, bars[11].datetime);
+        assert_eq!(out[2].volume, 200.0);
+    }
+
+    #[test]
+    fn test_hourly_bars_follow_sessions() {
+        let mut bars = stock_bars((9, 31), 120);
+        bars.extend(stock_bars((13, 1), 120));
+        let ends: Vec<String> = resample(&bars, Frequency::Minutes(60))
+            .iter()
+            .map(|bar| (bar.datetime + Duration::hours(8)).format("%H:%M").to_string())
+            .collect();
+        assert_eq!(ends, vec!["10:30", "11:30", "14:00", "15:00"]);
+    }
+
+    #[test]
+    fn test_night_session_daily_bar() {
+        // Friday night (13:00 UTC is 21:00 local) and Monday day session
+        let friday_night = Utc.with_ymd_and_hms(2024, 1, 5, 13, 0, 0).unwrap();
+        let monday_day = Utc.with_ymd_and_hms(2024, 1, 8, 1, 0, 0).unwrap();
+        let next_night = Utc.with_ymd_and_hms(2024, 1, 8, 13, 0, 0).unwrap();
+        let bars = vec![
+            future_bar(friday_night, 3900.0, 1000.0),
+            future_bar(friday_night + Duration::minutes(1), 3910.0, 1010.0),
+            future_bar(monday_day, 3890.0, 990.0),
+            future_bar(next_night, 3920.0, 995.0),
+        ];
+
+        let daily = resample_daily(&bars);
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily[0].date, NaiveDate::from_ymd_opt(2024, 1, 8).unwrap());
+        assert_eq!(daily[0].open, 3900.0);
+        assert_eq!(daily[0].close, 3890.0);
+        assert_eq!(daily[0].high, 3912.0);
+        assert_eq!(daily[0].low, 3888.0);
+        assert_eq!(daily[0].volume, 30.0);
+        assert_eq!(daily[0].open_interest, Some(990.0));
+        assert_eq!(daily[1].date, NaiveDate::from_ymd_opt(2024, 1, 9).unwrap());
+    }
+
+    #[test]
+    fn test_custom_calendar_and_interleaving() {
+        let holiday_after = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let mut resampler =
+            BarResampler::new(Frequency::Minutes(2)).with_trading_date(move |_, _| holiday_after);
+        let datetime = Utc.with_ymd_and_hms(2024, 1, 11, 13, 0, 0).unwrap();
+        let mut other = future_bar(datetime, 100.0, 1.0);
+        other.order_book_id = "ag2406.SHFE".to_string();
+
+        assert!(resampler.push(&future_bar(datetime, 3900.0, 1.0)).is_empty());
+        assert!(resampler.push(&other).is_empty());
+        let done = resampler.push(&future_bar(datetime + Duration::minutes(1), 3901.0, 2.0));
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].order_book_id, "rb2405.SHFE");
+        assert_eq!(done[0].trading_date, Some(holiday_after));
+
+        let rest = resampler.flush();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].order_book_id, "ag2406.SHFE");
+    }
+}
@@ -1,17 +1,92 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::orderbook::MAX_DEPTH;
 use crate::types::OptionalF64;
 
+/// Alternative field names accepted when deserializing, as `(alias, canonical)`
+///
+/// Snapshots always serialize with the canonical names. The aliases cover the
+/// TradingView quotes of the per-source gateways (`high`/`low`) and
+/// RQData-style records (`order_book_id`, `total_turnover`, `prev_close`, ...).
+pub const FIELD_ALIASES: &[(&str, &str)] = &[
+    ("order_book_id", "instrument_id"),
+    ("turnover", "amount"),
+    ("total_turnover", "amount"),
+    ("high", "highest"),
+    ("low", "lowest"),
+    ("limit_down", "lower_limit"),
+    ("limit_up", "upper_limit"),
+    ("prev_close", "pre_close"),
+    ("prev_settlement", "pre_settlement"),
+];
+
+/// Canonical [`MDSnapshot`] field name for a field name of any dialect
+pub fn canonical_field(name: &str) -> &str {
+    FIELD_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, canonical)| canonical)
+}
+
+/// Class of the instrument a snapshot belongs to, as listed in instrument reference data
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum InstrumentClass {
+    Future,
+    Option,
+    Stock,
+    /// Exchange-traded fund
+    Etf,
+    /// Other listed funds (LOF, closed-end)
+    Fund,
+    Index,
+    Bond,
+    Spot,
+}
+
+impl InstrumentClass {
+    /// Parse a product class code ("FUTURE", "OPTION", "ETF", "LOF", ..., case insensitive)
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.trim().to_ascii_uppercase().as_str() {
+            "FUTURE" | "FUTURES" => Some(InstrumentClass::Future),
+            "OPTION" | "OPTIONS" => Some(InstrumentClass::Option),
+            "STOCK" => Some(InstrumentClass::Stock),
+            "ETF" => Some(InstrumentClass::Etf),
+            "FUND" | "LOF" => Some(InstrumentClass::Fund),
+            "INDEX" => Some(InstrumentClass::Index),
+            "BOND" => Some(InstrumentClass::Bond),
+            "SPOT" => Some(InstrumentClass::Spot),
+            _ => None,
+        }
+    }
+
+    /// Futures and options, which carry settlement and open interest
+    pub fn is_derivative(self) -> bool {
+        matches!(self, InstrumentClass::Future | InstrumentClass::Option)
+    }
+}
+
 /// Market data snapshot with order book and trade information
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// The `Default` snapshot has an empty instrument id, a Unix epoch timestamp,
+/// zeroed prices and volumes, no depth beyond level 1 and `Null` optional fields.
+/// Use [`MDSnapshotBuilder`] to populate only the fields a source provides.
+///
+/// Only `instrument_id` and `datetime` are required when deserializing, missing
+/// fields take their `Default` values and the names in [`FIELD_ALIASES`] are
+/// accepted for their canonical fields.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct MDSnapshot {
     /// Unique identifier for the instrument (e.g., "SSE_688286")
+    #[serde(alias = "order_book_id")]
     pub instrument_id: String,
     
     /// Total turnover value
+    #[serde(default, alias = "turnover", alias = "total_turnover")]
     pub amount: f64,
     
     /// Best ask price (level 1)
+    #[serde(default)]
     pub ask_price1: f64,
     /// Ask price level 2
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -42,6 +117,7 @@ pub struct MDSnapshot {
     pub ask_price10: Option<f64>,
     
     /// Best ask volume (level 1)
+    #[serde(default)]
     pub ask_volume1: i64,
     /// Ask volume level 2
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -72,6 +148,7 @@ pub struct MDSnapshot {
     pub ask_volume10: Option<i64>,
     
     /// Best bid price (level 1)
+    #[serde(default)]
     pub bid_price1: f64,
     /// Bid price level 2
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -102,6 +179,7 @@ pub struct MDSnapshot {
     pub bid_price10: Option<f64>,
     
     /// Best bid volume (level 1)
+    #[serde(default)]
     pub bid_volume1: i64,
     /// Bid volume level 2
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -132,72 +210,486 @@ pub struct MDSnapshot {
     pub bid_volume10: Option<i64>,
     
     /// Closing price for the day, can be "-" before market close
+    #[serde(default)]
     pub close: OptionalF64,
     
     /// Timestamp of the snapshot
     pub datetime: DateTime<Utc>,
     
     /// Highest price of the day
+    #[serde(default, alias = "high")]
     pub highest: f64,
     
     /// Last traded price
+    #[serde(default)]
     pub last_price: f64,
     
     /// Lower limit price for the day
+    #[serde(default, alias = "limit_down")]
     pub lower_limit: f64,
     
     /// Lowest price of the day
+    #[serde(default, alias = "low")]
     pub lowest: f64,
     
     /// Opening price for the day
+    #[serde(default)]
     pub open: f64,
     
     /// Open interest for futures or options, can be "-" for stocks
+    #[serde(default)]
     pub open_interest: OptionalF64,
     
     /// Previous closing price
+    #[serde(default, alias = "prev_close")]
     pub pre_close: f64,
     
     /// Previous day's open interest, can be "-" for stocks
+    #[serde(default)]
     pub pre_open_interest: OptionalF64,
     
     /// Previous settlement price, can be "-" for stocks
+    #[serde(default, alias = "prev_settlement")]
     pub pre_settlement: OptionalF64,
     
     /// Settlement price, can be "-" for stocks or before market close
+    #[serde(default)]
     pub settlement: OptionalF64,
     
     /// Upper limit price for the day
+    #[serde(default, alias = "limit_up")]
     pub upper_limit: f64,
     
     /// Total trading volume for the day
+    #[serde(default)]
     pub volume: i64,
     
     /// Volume-weighted average price
+    #[serde(default)]
     pub average: f64,
     
     /// Indicative Optimized Portfolio Value, used for ETFs, can be "-" for non-ETFs
+    #[serde(default)]
     pub iopv: OptionalF64,
+
+    /// Instrument class from the reference data, absent when unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ins_class: Option<InstrumentClass>,
+
+    /// Underlying instrument of an option
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlying_symbol: Option<String>,
+
+    /// Strike price of an option
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strike_price: Option<f64>,
 }
 
 impl MDSnapshot {
+    /// Start building a snapshot for an instrument at a given time
+    pub fn builder(instrument_id: impl Into<String>, datetime: DateTime<Utc>) -> MDSnapshotBuilder {
+        MDSnapshotBuilder::new(instrument_id, datetime)
+    }
+
     /// Check if the market data includes level 2 depth
     pub fn has_level2_depth(&self) -> bool {
         self.ask_price2.is_some() || self.bid_price2.is_some()
     }
     
-    /// Check if this is futures or options data (has open interest)
+    /// Check if this is futures or options data
+    ///
+    /// Decided by `ins_class` when known, otherwise by the presence of open interest.
     pub fn is_futures_or_options(&self) -> bool {
-        matches!(self.open_interest, OptionalF64::Value(_))
+        match self.ins_class {
+            Some(class) => class.is_derivative(),
+            None => self.open_interest.is_value(),
+        }
     }
     
-    /// Check if this is an ETF (has IOPV)
+    /// Check if this is an ETF
+    ///
+    /// Decided by `ins_class` when known, otherwise by the presence of an IOPV.
     pub fn is_etf(&self) -> bool {
-        matches!(self.iopv, OptionalF64::Value(_))
+        match self.ins_class {
+            Some(class) => class == InstrumentClass::Etf,
+            None => self.iopv.is_value(),
+        }
     }
     
     /// Calculate bid-ask spread
     pub fn bid_ask_spread(&self) -> f64 {
         self.ask_price1 - self.bid_price1
     }
-} 
\ No newline at end of file
+}
+
+/// Builder for [`MDSnapshot`]
+///
+/// The instrument id and timestamp are required; every other field starts
+/// from the `Default` snapshot.
+///
+/// ```
+/// use chrono::Utc;
+/// use qamd_rs::MDSnapshot;
+///
+/// let snapshot = MDSnapshot::builder("SHFE.rb2410", Utc::now())
+///     .last_price(3500.0)
+///     .volume(1200)
+///     .bid(1, 3499.0, 30)
+///     .ask(1, 3501.0, 10)
+///     .bid(2, 3498.0, 40)
+///     .open_interest(180000.0)
+///     .build();
+///
+/// assert!(snapshot.has_level2_depth());
+/// assert!(snapshot.is_futures_or_options());
+/// ```
+#[derive(Debug, Clone)]
+pub struct MDSnapshotBuilder {
+    snapshot: MDSnapshot,
+}
+
+impl MDSnapshotBuilder {
+    /// Create a builder with the required fields
+    pub fn new(instrument_id: impl Into<String>, datetime: DateTime<Utc>) -> Self {
+        Self {
+            snapshot: MDSnapshot {
+                instrument_id: instrument_id.into(),
+                datetime,
+                ..MDSnapshot::default()
+            },
+        }
+    }
+
+    /// Set the last traded price
+    pub fn last_price(mut self, price: f64) -> Self {
+        self.snapshot.last_price = price;
+        self
+    }
+
+    /// Set the total trading volume for the day
+    pub fn volume(mut self, volume: i64) -> Self {
+        self.snapshot.volume = volume;
+        self
+    }
+
+    /// Set the total turnover value
+    pub fn amount(mut self, amount: f64) -> Self {
+        self.snapshot.amount = amount;
+        self
+    }
+
+    /// Set the opening price
+    pub fn open(mut self, price: f64) -> Self {
+        self.snapshot.open = price;
+        self
+    }
+
+    /// Set the highest price of the day
+    pub fn highest(mut self, price: f64) -> Self {
+        self.snapshot.highest = price;
+        self
+    }
+
+    /// Set the lowest price of the day
+    pub fn lowest(mut self, price: f64) -> Self {
+        self.snapshot.lowest = price;
+        self
+    }
+
+    /// Set the previous closing price
+    pub fn pre_close(mut self, price: f64) -> Self {
+        self.snapshot.pre_close = price;
+        self
+    }
+
+    /// Set the lower and upper limit prices for the day
+    pub fn limits(mut self, lower: f64, upper: f64) -> Self {
+        self.snapshot.lower_limit = lower;
+        self.snapshot.upper_limit = upper;
+        self
+    }
+
+    /// Set the volume-weighted average price
+    pub fn average(mut self, price: f64) -> Self {
+        self.snapshot.average = price;
+        self
+    }
+
+    /// Set the closing price
+    pub fn close(mut self, price: impl Into<OptionalF64>) -> Self {
+        self.snapshot.close = price.into();
+        self
+    }
+
+    /// Set the open interest
+    pub fn open_interest(mut self, value: impl Into<OptionalF64>) -> Self {
+        self.snapshot.open_interest = value.into();
+        self
+    }
+
+    /// Set the previous day's open interest
+    pub fn pre_open_interest(mut self, value: impl Into<OptionalF64>) -> Self {
+        self.snapshot.pre_open_interest = value.into();
+        self
+    }
+
+    /// Set the settlement price
+    pub fn settlement(mut self, price: impl Into<OptionalF64>) -> Self {
+        self.snapshot.settlement = price.into();
+        self
+    }
+
+    /// Set the previous settlement price
+    pub fn pre_settlement(mut self, price: impl Into<OptionalF64>) -> Self {
+        self.snapshot.pre_settlement = price.into();
+        self
+    }
+
+    /// Set the indicative optimized portfolio value
+    pub fn iopv(mut self, value: impl Into<OptionalF64>) -> Self {
+        self.snapshot.iopv = value.into();
+        self
+    }
+
+    /// Set the instrument class
+    pub fn ins_class(mut self, class: InstrumentClass) -> Self {
+        self.snapshot.ins_class = Some(class);
+        self
+    }
+
+    /// Set the underlying instrument and strike price of an option
+    pub fn option_terms(mut self, underlying_symbol: impl Into<String>, strike_price: f64) -> Self {
+        self.snapshot.underlying_symbol = Some(underlying_symbol.into());
+        self.snapshot.strike_price = Some(strike_price);
+        self
+    }
+
+    /// Set a bid level (1 is the best bid)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not in `1..=MAX_DEPTH`.
+    pub fn bid(mut self, level: usize, price: f64, volume: i64) -> Self {
+        let s = &mut self.snapshot;
+        let (price_slot, volume_slot) = match level {
+            1 => {
+                s.bid_price1 = price;
+                s.bid_volume1 = volume;
+                return self;
+            }
+            2 => (&mut s.bid_price2, &mut s.bid_volume2),
+            3 => (&mut s.bid_price3, &mut s.bid_volume3),
+            4 => (&mut s.bid_price4, &mut s.bid_volume4),
+            5 => (&mut s.bid_price5, &mut s.bid_volume5),
+            6 => (&mut s.bid_price6, &mut s.bid_volume6),
+            7 => (&mut s.bid_price7, &mut s.bid_volume7),
+            8 => (&mut s.bid_price8, &mut s.bid_volume8),
+            9 => (&mut s.bid_price9, &mut s.bid_volume9),
+            10 => (&mut s.bid_price10, &mut s.bid_volume10),
+            _ => panic!("bid level {} out of range 1..={}", level, MAX_DEPTH),
+        };
+        *price_slot = Some(price);
+        *volume_slot = Some(volume);
+        self
+    }
+
+    /// Set an ask level (1 is the best ask)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not in `1..=MAX_DEPTH`.
+    pub fn ask(mut self, level: usize, price: f64, volume: i64) -> Self {
+        let s = &mut self.snapshot;
+        let (price_slot, volume_slot) = match level {
+            1 => {
+                s.ask_price1 = price;
+                s.ask_volume1 = volume;
+                return self;
+            }
+            2 => (&mut s.ask_price2, &mut s.ask_volume2),
+            3 => (&mut s.ask_price3, &mut s.ask_volume3),
+            4 => (&mut s.ask_price4, &mut s.ask_volume4),
+            5 => (&mut s.ask_price5, &mut s.ask_volume5),
+            6 => (&mut s.ask_price6, &mut s.ask_volume6),
+            7 => (&mut s.ask_price7, &mut s.ask_volume7),
+            8 => (&mut s.ask_price8, &mut s.ask_volume8),
+            9 => (&mut s.ask_price9, &mut s.ask_volume9),
+            10 => (&mut s.ask_price10, &mut s.ask_volume10),
+            _ => panic!("ask level {} out of range 1..={}", level, MAX_DEPTH),
+        };
+        *price_slot = Some(price);
+        *volume_slot = Some(volume);
+        self
+    }
+
+    /// Finish building the snapshot
+    pub fn build(self) -> MDSnapshot {
+        self.snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn datetime() -> DateTime<Utc> {
+        "2024-06-03T01:30:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_canonical_field() {
+        assert_eq!(canonical_field("high"), "highest");
+        assert_eq!(canonical_field("total_turnover"), "amount");
+        assert_eq!(canonical_field("order_book_id"), "instrument_id");
+        assert_eq!(canonical_field("last_price"), "last_price");
+        assert_eq!(canonical_field("unknown"), "unknown");
+    }
+
+    #[test]
+    fn test_every_alias_deserializes_to_its_canonical_field() {
+        for (alias, canonical) in FIELD_ALIASES {
+            let value = if *canonical == "instrument_id" { json!("SSE_600000") } else { json!(12.5) };
+            let mut with_alias = json!({ "datetime": datetime() });
+            if *canonical != "instrument_id" {
+                with_alias["instrument_id"] = json!("SSE_600000");
+            }
+            let mut with_canonical = with_alias.clone();
+            with_alias[*alias] = value.clone();
+            with_canonical[*canonical] = value;
+
+            let from_alias: MDSnapshot = serde_json::from_value(with_alias).unwrap();
+            let from_canonical: MDSnapshot = serde_json::from_value(with_canonical).unwrap();
+            assert_eq!(from_alias, from_canonical, "alias {} of {}", alias, canonical);
+        }
+    }
+
+    #[test]
+    fn test_gateway_tradingview_quote() {
+        // Quote shape published by the ctp/qq/sina gateways
+        let quote = json!({
+            "instrument_id": "SHFE.rb2410",
+            "datetime": datetime().to_rfc3339(),
+            "last_price": 3500.0,
+            "volume": 1200,
+            "amount": 4200000.0,
+            "open": 3480.0,
+            "high": 3510.0,
+            "low": 3470.0,
+            "bid_price1": 3499.0,
+            "bid_volume1": 30,
+            "ask_price1": 3500.0,
+            "ask_volume1": 10,
+            "volume_multiple": 10,
+            "price_tick": 1.0,
+            "open_interest": 150000,
+            "upper_limit": 3800.0,
+            "lower_limit": 3200.0,
+            "pre_close": 3490.0,
+            "pre_settlement": 3488.0,
+            "pre_open_interest": 149000,
+            "close": 0.0,
+            "settlement": 0.0,
+            "average": 3495.0
+        });
+        let snapshot: MDSnapshot = serde_json::from_value(quote).unwrap();
+        assert_eq!(snapshot.highest, 3510.0);
+        assert_eq!(snapshot.lowest, 3470.0);
+        assert_eq!(snapshot.open_interest, OptionalF64::Value(150000.0));
+        assert_eq!(snapshot.pre_open_interest, OptionalF64::Value(149000.0));
+        assert_eq!(snapshot.iopv, OptionalF64::Null);
+        assert!(snapshot.is_futures_or_options());
+    }
+
+    #[test]
+    fn test_rqdata_style_record() {
+        let record = json!({
+            "order_book_id": "SSE_510300",
+            "datetime": datetime(),
+            "last": 3.9,
+            "last_price": 3.901,
+            "high": 3.95,
+            "low": 3.88,
+            "total_turnover": 1.5e9,
+            "volume": 380000000,
+            "prev_close": 3.89,
+            "limit_up": 4.28,
+            "limit_down": 3.5,
+            "prev_settlement": "-",
+            "iopv": 3.902
+        });
+        let snapshot: MDSnapshot = serde_json::from_value(record).unwrap();
+        let expected = MDSnapshot::builder("SSE_510300", datetime())
+            .last_price(3.901)
+            .highest(3.95)
+            .lowest(3.88)
+            .amount(1.5e9)
+            .volume(380000000)
+            .pre_close(3.89)
+            .limits(3.5, 4.28)
+            .pre_settlement(OptionalF64::String("-".to_string()))
+            .iopv(3.902)
+            .build();
+        assert_eq!(snapshot, expected);
+        assert!(snapshot.is_etf());
+    }
+
+    #[test]
+    fn test_serializes_canonical_names_losslessly() {
+        let snapshot = MDSnapshot::builder("SHFE.rb2410", datetime())
+            .last_price(3500.0)
+            .highest(3510.0)
+            .lowest(3470.0)
+            .amount(4.2e6)
+            .open_interest(150000.0)
+            .bid(2, 3498.0, 5)
+            .build();
+        let value = serde_json::to_value(&snapshot).unwrap();
+        for (alias, canonical) in FIELD_ALIASES {
+            assert!(value.get(*alias).is_none(), "serialized alias {}", alias);
+            assert!(value.get(*canonical).is_some(), "missing {}", canonical);
+        }
+        assert_eq!(serde_json::from_value::<MDSnapshot>(value).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_classification_by_instrument_class() {
+        // Before the first trade a future has no open interest and an ETF no IOPV
+        let future = MDSnapshot::builder("SHFE.rb2410", datetime())
+            .ins_class(InstrumentClass::Future)
+            .open_interest(OptionalF64::missing())
+            .build();
+        assert!(future.is_futures_or_options());
+        assert!(!future.is_etf());
+        let etf = MDSnapshot::builder("SSE.510300", datetime())
+            .ins_class(InstrumentClass::Etf)
+            .iopv(OptionalF64::missing())
+            .build();
+        assert!(etf.is_etf());
+        assert!(!etf.is_futures_or_options());
+        // An IOPV does not make a LOF an ETF once the class is known
+        let lof = MDSnapshot::builder("SZSE.161725", datetime())
+            .ins_class(InstrumentClass::from_code("lof").unwrap())
+            .iopv(1.02)
+            .build();
+        assert!(!lof.is_etf());
+
+        let option = MDSnapshot::builder("DCE.m2409-C-3000", datetime())
+            .ins_class(InstrumentClass::Option)
+            .option_terms("DCE.m2409", 3000.0)
+            .build();
+        let value = serde_json::to_value(&option).unwrap();
+        assert_eq!(value["ins_class"], "OPTION");
+        assert_eq!(value["underlying_symbol"], "DCE.m2409");
+        assert_eq!(value["strike_price"], 3000.0);
+        assert_eq!(serde_json::from_value::<MDSnapshot>(value).unwrap(), option);
+        // Unclassified snapshots do not carry the fields
+        assert!(serde_json::to_value(&future).unwrap().get("underlying_symbol").is_none());
+    }
+
+    #[test]
+    fn test_missing_required_fields() {
+        assert!(serde_json::from_value::<MDSnapshot>(json!({ "instrument_id": "SSE_600000" })).is_err());
+        assert!(serde_json::from_value::<MDSnapshot>(json!({ "datetime": datetime() })).is_err());
+    }
+}
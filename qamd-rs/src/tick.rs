@@ -2,32 +2,90 @@ use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use crate::snapshot::MDSnapshot;
 
+/// Aggressor side of a trade, inferred from the quotes around the last price
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeDirection {
+    /// Traded at or above the ask (buyer initiated)
+    Buy,
+    /// Traded at or below the bid (seller initiated)
+    Sell,
+    /// No trade, or the side could not be determined
+    #[default]
+    Neutral,
+}
+
+impl TradeDirection {
+    /// Infer the aggressor side of a trade at `price` against the given level 1 quotes
+    ///
+    /// Trades at or through the ask are buys, at or through the bid sells. Trades
+    /// inside the spread are classified by the side of the mid price, and trades at
+    /// the mid (or without a valid quote) by comparing to the previous trade price.
+    pub fn infer(price: f64, bid: f64, ask: f64, prev_price: Option<f64>) -> Self {
+        if !(price.is_finite() && price > 0.0) {
+            return Self::Neutral;
+        }
+        if bid > 0.0 && ask > 0.0 && ask >= bid {
+            if price >= ask {
+                return Self::Buy;
+            }
+            if price <= bid {
+                return Self::Sell;
+            }
+            let mid = (bid + ask) / 2.0;
+            if price > mid {
+                return Self::Buy;
+            }
+            if price < mid {
+                return Self::Sell;
+            }
+        }
+        match prev_price {
+            Some(prev) if prev > 0.0 && price > prev => Self::Buy,
+            Some(prev) if prev > 0.0 && price < prev => Self::Sell,
+            _ => Self::Neutral,
+        }
+    }
+}
+
 /// Basic tick data representing a single price update
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Tick {
     /// Unique identifier for the instrument (e.g., "SSE_688286")
     pub instrument_id: String,
-    
+
     /// Last traded price
     pub last_price: f64,
-    
+
     /// Total trading volume
     pub volume: i64,
-    
+
     /// Total turnover value
     pub amount: f64,
-    
+
     /// Timestamp of the tick
     pub datetime: DateTime<Utc>,
+
+    /// Inferred aggressor side of the volume traded since the previous snapshot
+    #[serde(default)]
+    pub direction: TradeDirection,
+
+    /// Volume traded since the previous snapshot
+    #[serde(default)]
+    pub delta_volume: i64,
+
+    /// Turnover since the previous snapshot
+    #[serde(default)]
+    pub delta_amount: f64,
 }
 
 impl Tick {
     /// Create a new tick with the given values
     pub fn new(
-        instrument_id: String, 
-        last_price: f64, 
-        volume: i64, 
-        amount: f64, 
+        instrument_id: String,
+        last_price: f64,
+        volume: i64,
+        amount: f64,
         datetime: DateTime<Utc>
     ) -> Self {
         Self {
@@ -36,17 +94,132 @@ impl Tick {
             volume,
             amount,
             datetime,
+            direction: TradeDirection::Neutral,
+            delta_volume: 0,
+            delta_amount: 0.0,
         }
     }
-    
+
     /// Extract a tick from a market data snapshot
+    ///
+    /// Without a previous snapshot the deltas are the session totals and the
+    /// direction is inferred from the snapshot's own quotes.
     pub fn from_snapshot(snapshot: &MDSnapshot) -> Self {
+        let direction = if snapshot.volume > 0 {
+            TradeDirection::infer(snapshot.last_price, snapshot.bid_price1, snapshot.ask_price1, None)
+        } else {
+            TradeDirection::Neutral
+        };
         Self {
             instrument_id: snapshot.instrument_id.clone(),
             last_price: snapshot.last_price,
             volume: snapshot.volume,
             amount: snapshot.amount,
             datetime: snapshot.datetime,
+            direction,
+            delta_volume: snapshot.volume.max(0),
+            delta_amount: snapshot.amount.max(0.0),
+        }
+    }
+
+    /// Build the tick traded between two consecutive snapshots of an instrument
+    ///
+    /// The direction is inferred against the quotes standing before the trade
+    /// (`prev`). When the cumulative volume goes backwards (a new session or an
+    /// upstream reset) `curr` is treated as the first snapshot of the session.
+    pub fn from_snapshot_pair(prev: &MDSnapshot, curr: &MDSnapshot) -> Self {
+        if curr.volume < prev.volume {
+            return Self::from_snapshot(curr);
+        }
+        let delta_volume = curr.volume - prev.volume;
+        let delta_amount = (curr.amount - prev.amount).max(0.0);
+        let direction = if delta_volume > 0 {
+            TradeDirection::infer(
+                curr.last_price,
+                prev.bid_price1,
+                prev.ask_price1,
+                Some(prev.last_price),
+            )
+        } else {
+            TradeDirection::Neutral
+        };
+        Self {
+            instrument_id: curr.instrument_id.clone(),
+            last_price: curr.last_price,
+            volume: curr.volume,
+            amount: curr.amount,
+            datetime: curr.datetime,
+            direction,
+            delta_volume,
+            delta_amount,
         }
     }
-} 
\ No newline at end of file
+
+    /// Whether any volume traded since the previous snapshot
+    pub fn has_trade(&self) -> bool {
+        self.delta_volume > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(last: f64, bid: f64, ask: f64, volume: i64, amount: f64) -> MDSnapshot {
+        MDSnapshot::builder("SHFE_rb2410", Utc::now())
+            .last_price(last)
+            .bid(1, bid, 10)
+            .ask(1, ask, 10)
+            .volume(volume)
+            .amount(amount)
+            .build()
+    }
+
+    #[test]
+    fn test_infer_direction() {
+        assert_eq!(TradeDirection::infer(10.2, 10.0, 10.2, None), TradeDirection::Buy);
+        assert_eq!(TradeDirection::infer(9.9, 10.0, 10.2, None), TradeDirection::Sell);
+        assert_eq!(TradeDirection::infer(10.15, 10.0, 10.2, None), TradeDirection::Buy);
+        assert_eq!(TradeDirection::infer(10.05, 10.0, 10.2, None), TradeDirection::Sell);
+        // At the mid price fall back to the tick rule
+        assert_eq!(TradeDirection::infer(10.1, 10.0, 10.2, Some(10.0)), TradeDirection::Buy);
+        assert_eq!(TradeDirection::infer(10.1, 10.0, 10.2, Some(10.1)), TradeDirection::Neutral);
+        // No quotes (e.g. limit up)
+        assert_eq!(TradeDirection::infer(10.1, 0.0, 0.0, Some(10.2)), TradeDirection::Sell);
+        assert_eq!(TradeDirection::infer(0.0, 10.0, 10.2, None), TradeDirection::Neutral);
+    }
+
+    #[test]
+    fn test_from_snapshot_pair() {
+        let prev = snapshot(3500.0, 3499.0, 3501.0, 1000, 35_000_000.0);
+        let curr = snapshot(3501.0, 3500.0, 3502.0, 1012, 35_042_012.0);
+        let tick = Tick::from_snapshot_pair(&prev, &curr);
+        assert_eq!(tick.direction, TradeDirection::Buy);
+        assert_eq!(tick.delta_volume, 12);
+        assert_eq!(tick.delta_amount, 42_012.0);
+        assert_eq!(tick.volume, 1012);
+        assert!(tick.has_trade());
+
+        // Quote change only
+        let quote = snapshot(3501.0, 3500.0, 3503.0, 1012, 35_042_012.0);
+        let tick = Tick::from_snapshot_pair(&curr, &quote);
+        assert_eq!(tick.direction, TradeDirection::Neutral);
+        assert!(!tick.has_trade());
+
+        // Volume reset at the start of a new session
+        let next = snapshot(3490.0, 3489.0, 3491.0, 5, 174_500.0);
+        let tick = Tick::from_snapshot_pair(&curr, &next);
+        assert_eq!(tick.delta_volume, 5);
+        assert_eq!(tick.delta_amount, 174_500.0);
+    }
+
+    #[test]
+    fn test_deserialize_without_computed_fields() {
+        let json = r#"{"instrument_id":"SSE_600000","last_price":10.0,"volume":100,"amount":1000.0,"datetime":"2024-01-05T02:30:00Z"}"#;
+        let tick: Tick = serde_json::from_str(json).unwrap();
+        assert_eq!(tick.direction, TradeDirection::Neutral);
+        assert_eq!(tick.delta_volume, 0);
+        let json = serde_json::to_string(&Tick { direction: TradeDirection::Sell, ..tick }).unwrap();
+        assert!(json.contains(r#""direction":"sell""#));
+    }
+}
@@ -0,0 +1,253 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc, Weekday};
+
+use crate::error::{QAMDError, Result};
+
+/// Quotes stamped at or after this local time belong to the evening part of a night session
+const NIGHT_SESSION_START: (u32, u32) = (18, 0);
+/// Quotes stamped before this local time belong to the after-midnight part of a night session
+const NIGHT_SESSION_END: (u32, u32) = (6, 0);
+
+/// Converts exchange-local quote timestamps into UTC
+///
+/// CTP style feeds report a quote's time as `ActionDay` (calendar day), `TradingDay`,
+/// `UpdateTime` and `UpdateMillisec`, all in the exchange's local time. Not every
+/// exchange fills `ActionDay` correctly during the night session (DCE reports the
+/// trading day instead, some fronts leave it empty), so when it is missing or equal
+/// to the trading day the calendar day is derived from the trading day:
+///
+/// - evening quotes (after 18:00) belong to the previous weekday
+/// - after-midnight quotes (before 06:00) belong to the day after that
+///
+/// Holidays are not taken into account, night sessions are never held before them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampNormalizer {
+    offset: FixedOffset,
+}
+
+impl Default for TimestampNormalizer {
+    fn default() -> Self {
+        Self::china()
+    }
+}
+
+impl TimestampNormalizer {
+    /// Create a normalizer for an exchange with the given UTC offset
+    pub fn new(offset: FixedOffset) -> Self {
+        Self { offset }
+    }
+
+    /// China Standard Time (UTC+8), used by all mainland exchanges
+    pub fn china() -> Self {
+        Self::new(FixedOffset::east_opt(8 * 3600).expect("valid offset"))
+    }
+
+    /// Normalizer for a known exchange code, `None` for exchanges without a fixed offset
+    pub fn for_exchange(exchange: &str) -> Option<Self> {
+        if let Some(info) = crate::constants::exchange::lookup(exchange) {
+            return FixedOffset::east_opt(info.utc_offset_secs).map(Self::new);
+        }
+        match exchange {
+            "HKEX" => Some(Self::china()),
+            "NSE" => FixedOffset::east_opt(5 * 3600 + 1800).map(Self::new),
+            _ => None,
+        }
+    }
+
+    /// UTC offset of the exchange
+    pub fn offset(&self) -> FixedOffset {
+        self.offset
+    }
+
+    /// Calendar day a quote was produced on
+    pub fn action_date(&self, action_day: Option<NaiveDate>, trading_day: NaiveDate, time: NaiveTime) -> NaiveDate {
+        match action_day {
+            Some(day) if day != trading_day => day,
+            _ => {
+                let evening = NaiveTime::from_hms_opt(NIGHT_SESSION_START.0, NIGHT_SESSION_START.1, 0)
+                    .expect("valid time");
+                let morning = NaiveTime::from_hms_opt(NIGHT_SESSION_END.0, NIGHT_SESSION_END.1, 0)
+                    .expect("valid time");
+                if time >= evening {
+                    previous_weekday(trading_day)
+                } else if time < morning {
+                    previous_weekday(trading_day) + Duration::days(1)
+                } else {
+                    trading_day
+                }
+            }
+        }
+    }
+
+    /// Trading day of a futures quote, the inverse of [`action_date`](Self::action_date):
+    /// evening and after-midnight quotes belong to the next weekday's trading day
+    pub fn trading_date(&self, datetime: DateTime<Utc>) -> NaiveDate {
+        let local = datetime.with_timezone(&self.offset).naive_local();
+        let evening = NaiveTime::from_hms_opt(NIGHT_SESSION_START.0, NIGHT_SESSION_START.1, 0)
+            .expect("valid time");
+        let morning = NaiveTime::from_hms_opt(NIGHT_SESSION_END.0, NIGHT_SESSION_END.1, 0)
+            .expect("valid time");
+        if local.time() >= evening {
+            next_weekday(local.date())
+        } else if local.time() < morning {
+            next_weekday(local.date() - Duration::days(1))
+        } else {
+            local.date()
+        }
+    }
+
+    /// Convert an exchange-local datetime to UTC
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        self.offset
+            .from_local_datetime(&local)
+            .single()
+            .expect("fixed offsets are unambiguous")
+            .with_timezone(&Utc)
+    }
+
+    /// Combine CTP style date/time fields into a UTC timestamp
+    ///
+    /// Days are `YYYYMMDD` (or `YYYY-MM-DD`), `action_day` may be empty. `update_time`
+    /// is `HH:MM:SS` with an optional fractional part, which takes precedence over
+    /// `millisec`.
+    pub fn normalize(
+        &self,
+        action_day: &str,
+        trading_day: &str,
+        update_time: &str,
+        millisec: i32,
+    ) -> Result<DateTime<Utc>> {
+        let trading_day = parse_day(trading_day)?
+            .ok_or_else(|| QAMDError::InvalidMarketData("Missing trading day".to_string()))?;
+        let action_day = parse_day(action_day)?;
+
+        let update_time = update_time.trim();
+        let mut time = NaiveTime::parse_from_str(update_time, "%H:%M:%S%.f")?;
+        if time.nanosecond() == 0 && millisec != 0 {
+            if !(0..1000).contains(&millisec) {
+                return Err(QAMDError::InvalidMarketData(format!(
+                    "Invalid update millisecond: {}",
+                    millisec
+                )));
+            }
+            time = time
+                .with_nanosecond(millisec as u32 * 1_000_000)
+                .expect("valid nanosecond");
+        }
+
+        let date = self.action_date(action_day, trading_day, time);
+        Ok(self.to_utc(date.and_time(time)))
+    }
+
+    /// Same as [`normalize`](Self::normalize), as nanoseconds since the Unix epoch
+    pub fn normalize_nanos(
+        &self,
+        action_day: &str,
+        trading_day: &str,
+        update_time: &str,
+        millisec: i32,
+    ) -> Result<i64> {
+        let datetime = self.normalize(action_day, trading_day, update_time, millisec)?;
+        datetime.timestamp_nanos_opt().ok_or_else(|| {
+            QAMDError::InvalidMarketData(format!("Timestamp out of range: {}", datetime))
+        })
+    }
+}
+
+/// Parse a `YYYYMMDD` or `YYYY-MM-DD` day, empty strings are `None`
+fn parse_day(day: &str) -> Result<Option<NaiveDate>> {
+    let day = day.trim();
+    if day.is_empty() {
+        return Ok(None);
+    }
+    let format = if day.contains('-') { "%Y-%m-%d" } else { "%Y%m%d" };
+    Ok(Some(NaiveDate::parse_from_str(day, format)?))
+}
+
+/// The weekday before `date`
+fn previous_weekday(date: NaiveDate) -> NaiveDate {
+    let mut day = date - Duration::days(1);
+    while matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+        day -= Duration::days(1);
+    }
+    day
+}
+
+/// The weekday after `date`
+fn next_weekday(date: NaiveDate) -> NaiveDate {
+    let mut day = date + Duration::days(1);
+    while matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+        day += Duration::days(1);
+    }
+    day
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_day_session() {
+        let normalizer = TimestampNormalizer::china();
+        let datetime = normalizer.normalize("20240105", "20240105", "10:30:00", 500).unwrap();
+        assert_eq!(datetime, utc("2024-01-05T02:30:00.500Z"));
+        assert_eq!(
+            normalizer.normalize_nanos("", "2024-01-05", "10:30:00.123456789", 500).unwrap(),
+            utc("2024-01-05T02:30:00.123456789Z").timestamp_nanos_opt().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_trading_date() {
+        let normalizer = TimestampNormalizer::china();
+        let day = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        // Friday evening and Saturday early morning belong to Monday
+        assert_eq!(normalizer.trading_date(utc("2024-01-05T13:00:00Z")), day("2024-01-08"));
+        assert_eq!(normalizer.trading_date(utc("2024-01-05T17:30:00Z")), day("2024-01-08"));
+        // Tuesday night after midnight belongs to Wednesday
+        assert_eq!(normalizer.trading_date(utc("2024-01-09T16:15:00Z")), day("2024-01-10"));
+        assert_eq!(normalizer.trading_date(utc("2024-01-10T02:30:00Z")), day("2024-01-10"));
+    }
+
+    #[test]
+    fn test_night_session_rollover() {
+        let normalizer = TimestampNormalizer::china();
+        // Friday night session belongs to Monday's trading day
+        let expected = utc("2024-01-05T13:00:00Z");
+        // ActionDay filled correctly
+        assert_eq!(normalizer.normalize("20240105", "20240108", "21:00:00", 0).unwrap(), expected);
+        // DCE reports the trading day as ActionDay
+        assert_eq!(normalizer.normalize("20240108", "20240108", "21:00:00", 0).unwrap(), expected);
+        // Missing ActionDay
+        assert_eq!(normalizer.normalize("", "20240108", "21:00:00", 0).unwrap(), expected);
+        // After midnight the calendar day is Saturday
+        assert_eq!(
+            normalizer.normalize("20240108", "20240108", "01:30:00", 0).unwrap(),
+            utc("2024-01-05T17:30:00Z")
+        );
+        // Midweek after-midnight quotes share the trading day
+        assert_eq!(
+            normalizer.normalize("", "20240110", "00:15:00", 0).unwrap(),
+            utc("2024-01-09T16:15:00Z")
+        );
+    }
+
+    #[test]
+    fn test_exchange_offsets_and_errors() {
+        assert_eq!(TimestampNormalizer::for_exchange("SHFE"), Some(TimestampNormalizer::china()));
+        assert_eq!(TimestampNormalizer::for_exchange("NYSE"), None);
+        let nse = TimestampNormalizer::for_exchange("NSE").unwrap();
+        assert_eq!(
+            nse.normalize("20240105", "20240105", "09:15:00", 0).unwrap(),
+            utc("2024-01-05T03:45:00Z")
+        );
+
+        let normalizer = TimestampNormalizer::default();
+        assert!(normalizer.normalize("", "", "10:00:00", 0).is_err());
+        assert!(normalizer.normalize("", "20240105", "25:00:00", 0).is_err());
+        assert!(normalizer.normalize("", "20240105", "10:00:00", 1500).is_err());
+    }
+}
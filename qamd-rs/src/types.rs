@@ -1,4 +1,5 @@
 use std::fmt;
+use std::ops;
 use serde::{Serialize, Deserialize};
 
 /// Represents an optional numeric value in market data
@@ -22,6 +23,12 @@ impl<T: Default> Default for OptionalNumeric<T> {
     }
 }
 
+impl<T> From<T> for OptionalNumeric<T> {
+    fn from(value: T) -> Self {
+        OptionalNumeric::Value(value)
+    }
+}
+
 impl<T: fmt::Display> fmt::Display for OptionalNumeric<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -32,8 +39,165 @@ impl<T: fmt::Display> fmt::Display for OptionalNumeric<T> {
     }
 }
 
+impl<T> From<Option<T>> for OptionalNumeric<T> {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => OptionalNumeric::Value(value),
+            None => OptionalNumeric::Null,
+        }
+    }
+}
+
+impl<T> From<OptionalNumeric<T>> for Option<T> {
+    fn from(value: OptionalNumeric<T>) -> Self {
+        match value {
+            OptionalNumeric::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<T> OptionalNumeric<T> {
+    /// The `"-"` placeholder sources use for fields that do not apply
+    pub fn missing() -> Self {
+        OptionalNumeric::String("-".to_string())
+    }
+
+    /// Returns `true` if this holds a numeric value
+    pub fn is_value(&self) -> bool {
+        matches!(self, OptionalNumeric::Value(_))
+    }
+
+    /// Returns `true` if this is an explicit null
+    pub fn is_null(&self) -> bool {
+        matches!(self, OptionalNumeric::Null)
+    }
+
+    /// Returns the numeric value, if any
+    pub fn as_option(&self) -> Option<T>
+    where
+        T: Copy,
+    {
+        match self {
+            OptionalNumeric::Value(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric value or `default` for string and null values
+    pub fn unwrap_or(&self, default: T) -> T
+    where
+        T: Copy,
+    {
+        self.as_option().unwrap_or(default)
+    }
+
+    /// Maps the numeric value, keeping string and null values unchanged
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> OptionalNumeric<U> {
+        match self {
+            OptionalNumeric::Value(value) => OptionalNumeric::Value(f(value)),
+            OptionalNumeric::String(s) => OptionalNumeric::String(s),
+            OptionalNumeric::Null => OptionalNumeric::Null,
+        }
+    }
+
+    /// Combines two values with `f`.
+    ///
+    /// Null takes precedence over strings so that an explicitly missing input
+    /// is never reported as a placeholder; otherwise the first string is kept.
+    fn combine<F: FnOnce(T, T) -> T>(self, rhs: Self, f: F) -> Self {
+        match (self, rhs) {
+            (OptionalNumeric::Value(a), OptionalNumeric::Value(b)) => OptionalNumeric::Value(f(a, b)),
+            (OptionalNumeric::Null, _) | (_, OptionalNumeric::Null) => OptionalNumeric::Null,
+            (OptionalNumeric::String(s), _) | (_, OptionalNumeric::String(s)) => OptionalNumeric::String(s),
+        }
+    }
+}
+
+impl OptionalNumeric<f64> {
+    /// Returns the value as `f64`, if any
+    pub fn as_f64_opt(&self) -> Option<f64> {
+        self.as_option()
+    }
+}
+
+impl OptionalNumeric<i64> {
+    /// Returns the value as `i64`, if any
+    pub fn as_i64_opt(&self) -> Option<i64> {
+        self.as_option()
+    }
+}
+
+macro_rules! impl_optional_op {
+    ($trait:ident, $method:ident) => {
+        impl<T: ops::$trait<Output = T>> ops::$trait for OptionalNumeric<T> {
+            type Output = OptionalNumeric<T>;
+
+            fn $method(self, rhs: Self) -> Self::Output {
+                self.combine(rhs, |a, b| a.$method(b))
+            }
+        }
+
+        impl<T: ops::$trait<Output = T>> ops::$trait<T> for OptionalNumeric<T> {
+            type Output = OptionalNumeric<T>;
+
+            fn $method(self, rhs: T) -> Self::Output {
+                self.map(|a| a.$method(rhs))
+            }
+        }
+    };
+}
+
+impl_optional_op!(Add, add);
+impl_optional_op!(Sub, sub);
+impl_optional_op!(Mul, mul);
+impl_optional_op!(Div, div);
+
+impl<T: ops::Neg<Output = T>> ops::Neg for OptionalNumeric<T> {
+    type Output = OptionalNumeric<T>;
+
+    fn neg(self) -> Self::Output {
+        self.map(|a| -a)
+    }
+}
+
 /// Type alias for optional market data fields (typically price-related)
 pub type OptionalF64 = OptionalNumeric<f64>;
 
 /// Type alias for optional volume fields
-pub type OptionalI64 = OptionalNumeric<i64>; 
\ No newline at end of file
+pub type OptionalI64 = OptionalNumeric<i64>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accessors_and_conversions() {
+        assert_eq!(OptionalF64::from(1.5).as_f64_opt(), Some(1.5));
+        assert_eq!(OptionalF64::from(None), OptionalF64::Null);
+        assert_eq!(OptionalF64::from(Some(2.0)), OptionalF64::Value(2.0));
+        assert_eq!(OptionalF64::missing().as_f64_opt(), None);
+        assert_eq!(OptionalF64::missing().unwrap_or(0.0), 0.0);
+        assert_eq!(OptionalI64::Value(7).as_i64_opt(), Some(7));
+        assert_eq!(Option::<f64>::from(OptionalF64::Null), None);
+        assert!(OptionalF64::Null.is_null());
+        assert!(!OptionalF64::missing().is_value());
+    }
+
+    #[test]
+    fn test_arithmetic_propagates_missing_values() {
+        let a = OptionalF64::Value(3.0);
+        let b = OptionalF64::Value(1.5);
+        assert_eq!(a.clone() + b.clone(), OptionalF64::Value(4.5));
+        assert_eq!(a.clone() - b.clone(), OptionalF64::Value(1.5));
+        assert_eq!(a.clone() * 2.0, OptionalF64::Value(6.0));
+        assert_eq!(a.clone() / b, OptionalF64::Value(2.0));
+        assert_eq!(-a.clone(), OptionalF64::Value(-3.0));
+
+        // Placeholders are kept as-is and Null wins over placeholders
+        assert_eq!(a.clone() + OptionalF64::missing(), OptionalF64::missing());
+        assert_eq!(OptionalF64::missing() * 2.0, OptionalF64::missing());
+        assert_eq!(OptionalF64::missing() + OptionalF64::Null, OptionalF64::Null);
+        assert_eq!(a + OptionalF64::Null, OptionalF64::Null);
+    }
+}
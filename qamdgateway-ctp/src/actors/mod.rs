@@ -1,6 +0,0 @@
-pub mod messages;
-pub mod md_actor;
-pub mod md_connector;
-pub mod md_distributor;
-pub mod qq_md_actor;
-pub mod sina_md_actor; 
\ No newline at end of file
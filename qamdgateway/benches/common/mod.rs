@@ -0,0 +1,137 @@
+//! Shared fixtures of the gateway benchmarks
+
+#![allow(dead_code)]
+
+use chrono::{Duration as ChronoDuration, Utc};
+use ctp_common::CThostFtdcDepthMarketDataField;
+use qamd_rs::{MDSnapshot, OptionalF64};
+
+/// A futures snapshot with one level of quotes, prices move with `round`
+pub fn snapshot(instrument: usize, round: usize) -> MDSnapshot {
+    let price = 3_500.0 + round as f64;
+    MDSnapshot {
+        instrument_id: format!("SHFE.rb{}", 2_400 + instrument),
+        amount: 1_000_000.0 + round as f64,
+        ask_price1: price + 1.0,
+        ask_volume1: 10,
+        bid_price1: price - 1.0,
+        bid_volume1: 12,
+        last_price: price,
+        datetime: Utc::now() + ChronoDuration::milliseconds(round as i64 * 500),
+        highest: price,
+        lowest: 3_400.0,
+        open: 3_450.0,
+        close: OptionalF64::Null,
+        volume: 1_000 + round as i64,
+        pre_close: 3_440.0,
+        lower_limit: 3_100.0,
+        upper_limit: 3_800.0,
+        average: price,
+        ask_price2: None,
+        ask_volume2: None,
+        bid_price2: None,
+        bid_volume2: None,
+        ask_price3: None,
+        ask_volume3: None,
+        bid_price3: None,
+        bid_volume3: None,
+        ask_price4: None,
+        ask_price5: None,
+        ask_price6: None,
+        ask_price7: None,
+        ask_price8: None,
+        ask_price9: None,
+        ask_price10: None,
+        ask_volume4: None,
+        ask_volume5: None,
+        ask_volume6: None,
+        ask_volume7: None,
+        ask_volume8: None,
+        ask_volume9: None,
+        ask_volume10: None,
+        bid_price4: None,
+        bid_price5: None,
+        bid_price6: None,
+        bid_price7: None,
+        bid_price8: None,
+        bid_price9: None,
+        bid_price10: None,
+        bid_volume4: None,
+        bid_volume5: None,
+        bid_volume6: None,
+        bid_volume7: None,
+        bid_volume8: None,
+        bid_volume9: None,
+        bid_volume10: None,
+        open_interest: OptionalF64::Value(100_000.0),
+        pre_open_interest: OptionalF64::Value(99_000.0),
+        pre_settlement: OptionalF64::Value(3_440.0),
+        settlement: OptionalF64::Null,
+        iopv: OptionalF64::Null,
+        ins_class: None,
+        underlying_symbol: None,
+        strike_price: None,
+    }
+}
+
+/// A futures snapshot with five levels of quotes on each side
+pub fn depth_snapshot(instrument: usize, round: usize) -> MDSnapshot {
+    let mut snapshot = snapshot(instrument, round);
+    let (bid, ask) = (snapshot.bid_price1, snapshot.ask_price1);
+    snapshot.bid_price2 = Some(bid - 1.0);
+    snapshot.bid_price3 = Some(bid - 2.0);
+    snapshot.bid_price4 = Some(bid - 3.0);
+    snapshot.bid_price5 = Some(bid - 4.0);
+    snapshot.ask_price2 = Some(ask + 1.0);
+    snapshot.ask_price3 = Some(ask + 2.0);
+    snapshot.ask_price4 = Some(ask + 3.0);
+    snapshot.ask_price5 = Some(ask + 4.0);
+    // Volumes rotate with the round so consecutive books differ on every level
+    let volume = |level: i64| Some(10 + (level + round as i64) % 7);
+    snapshot.bid_volume2 = volume(2);
+    snapshot.bid_volume3 = volume(3);
+    snapshot.bid_volume4 = volume(4);
+    snapshot.bid_volume5 = volume(5);
+    snapshot.ask_volume2 = volume(6);
+    snapshot.ask_volume3 = volume(7);
+    snapshot.ask_volume4 = volume(8);
+    snapshot.ask_volume5 = volume(9);
+    snapshot
+}
+
+fn fill(field: &mut [u8], value: &str) {
+    field[..value.len()].copy_from_slice(value.as_bytes());
+}
+
+/// A raw CTP depth quote as delivered by the market data API
+pub fn depth_field(exchange: &str, instrument: &str) -> CThostFtdcDepthMarketDataField {
+    let mut data = CThostFtdcDepthMarketDataField::default();
+    fill(&mut data.TradingDay, "20240105");
+    fill(&mut data.ActionDay, "20240105");
+    fill(&mut data.UpdateTime, "10:30:00");
+    data.UpdateMillisec = 500;
+    fill(&mut data.ExchangeID, exchange);
+    fill(&mut data.InstrumentID, instrument);
+    data.LastPrice = 3512.0;
+    data.OpenPrice = 3500.0;
+    data.HighestPrice = 3520.0;
+    data.LowestPrice = 3490.0;
+    data.PreClosePrice = 3498.0;
+    data.ClosePrice = f64::MAX;
+    data.SettlementPrice = f64::MAX;
+    data.PreSettlementPrice = 3496.0;
+    data.OpenInterest = 120_000.0;
+    data.PreOpenInterest = 119_000.0;
+    data.Volume = 100_000;
+    data.Turnover = 3_512_000_000.0;
+    data.AveragePrice = 35_110.0;
+    data.UpperLimitPrice = 3_800.0;
+    data.LowerLimitPrice = 3_200.0;
+    data.BidPrice1 = 3511.0;
+    data.BidVolume1 = 5;
+    data.AskPrice1 = 3512.0;
+    data.AskVolume1 = 7;
+    data.BidPrice2 = f64::MAX;
+    data.AskPrice2 = f64::MAX;
+    data
+}
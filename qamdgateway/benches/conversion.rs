@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use qamdgateway::actors::messages::MarketDataSource;
+use qamdgateway::converter::SnapshotConverter;
+use qamdgateway::instruments::{InstrumentInfo, InstrumentRegistry};
+use serde_json::json;
+use std::sync::Arc;
+
+mod common;
+use common::depth_field;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ctp_conversion");
+
+    let future = depth_field("SHFE", "rb2410");
+    let converter = SnapshotConverter::new(MarketDataSource::CTP);
+    group.bench_function("future", |b| {
+        b.iter(|| converter.convert(black_box(&future)).unwrap())
+    });
+
+    // The exchange is inferred from the instrument ID when the front leaves it empty
+    let bare = depth_field("", "rb2410");
+    group.bench_function("inferred_exchange", |b| {
+        b.iter(|| converter.convert(black_box(&bare)).unwrap())
+    });
+
+    let info: InstrumentInfo = serde_json::from_value(json!({
+        "instrument_id": "rb2410",
+        "exchange_id": "SHFE",
+        "price_tick": 1.0,
+        "volume_multiple": 10,
+    }))
+    .unwrap();
+    let mut instruments = InstrumentRegistry::new();
+    instruments.extend([info]);
+    let rounding = SnapshotConverter::new(MarketDataSource::CTP).with_price_rounding(Arc::new(instruments));
+    group.bench_function("price_rounding", |b| {
+        b.iter(|| rounding.convert(black_box(&future)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,178 @@
+use actix::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use qamdgateway::actors::md_distributor::MarketDataDistributor;
+use qamdgateway::actors::messages::*;
+use qamdgateway::quote_encoding::{quote_frame, EncodedQuote};
+use qamdgateway::ws_server::{WsFormat, WsServerMessage};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+mod common;
+use common::snapshot;
+
+// Instruments per batch; matches the distributor's batch size threshold so
+// every round of updates is flushed as one batch
+const INSTRUMENTS: usize = 50;
+const ROUNDS: usize = 10;
+const CLIENT_ARBITERS: usize = 4;
+// Sessions encoding the same batch in the session benchmark
+const SESSIONS: usize = 100;
+
+/// Client stand-in that counts the instrument updates it receives
+struct CountingClient {
+    received: Arc<AtomicUsize>,
+    expected: usize,
+    done: Arc<Notify>,
+}
+
+impl Actor for CountingClient {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // The distributor uses try_send, so never drop updates in the benchmark
+        ctx.set_mailbox_capacity(usize::MAX);
+    }
+}
+
+impl Handler<MarketDataUpdateMessage> for CountingClient {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdateMessage, _: &mut Self::Context) -> Self::Result {
+        let count = msg.instruments.len();
+        if self.received.fetch_add(count, Ordering::SeqCst) + count == self.expected {
+            self.done.notify_one();
+        }
+    }
+}
+
+/// Time to deliver `ROUNDS` batches of updates to each of `clients` clients
+fn fanout(clients: usize, shards: usize) -> Duration {
+    System::new().block_on(async move {
+        let distributor = MarketDataDistributor::new()
+            .with_fanout_shards(shards)
+            .start();
+        let instruments: Vec<String> = (0..INSTRUMENTS)
+            .map(|i| format!("SHFE.rb{}", 2_400 + i))
+            .collect();
+
+        let received = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(Notify::new());
+        let expected = clients * INSTRUMENTS * ROUNDS;
+        let arbiters: Vec<Arbiter> = (0..CLIENT_ARBITERS).map(|_| Arbiter::new()).collect();
+        for client in 0..clients {
+            let (received, done) = (received.clone(), done.clone());
+            let addr = CountingClient::start_in_arbiter(
+                &arbiters[client % CLIENT_ARBITERS].handle(),
+                move |_| CountingClient { received, expected, done },
+            );
+            distributor.do_send(RegisterDataReceiver {
+                client_id: format!("client-{}", client),
+                addr: addr.recipient(),
+                instruments: instruments.clone(),
+                notify: None,
+                remap: None,
+                disconnect: None,
+            });
+        }
+        // Wait until every client is registered
+        let _ = distributor.send(GetAllSubscriptions).await;
+
+        let start = Instant::now();
+        for round in 0..ROUNDS {
+            for instrument in 0..INSTRUMENTS {
+                distributor.do_send(MarketDataUpdate(Arc::new(snapshot(instrument, round)), MarketDataSource::CTP));
+            }
+        }
+        done.notified().await;
+        let elapsed = start.elapsed();
+
+        for arbiter in arbiters {
+            arbiter.stop();
+        }
+        elapsed
+    })
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("distributor_fanout");
+    group.sample_size(10);
+    for clients in [100, 1_000] {
+        for shards in [1, 4] {
+            let id = BenchmarkId::new(format!("shards_{}", shards), clients);
+            group.bench_with_input(id, &clients, |b, &clients| {
+                b.iter_custom(|iters| (0..iters).map(|_| fanout(clients, shards)).sum());
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Encode one batch the way sessions did before pre-encoding: parse the
+/// shared JSON, stamp the session's sequence numbers and serialize again
+fn reparse(format: WsFormat, shared: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut quotes = serde_json::Map::new();
+    for (instrument, json) in shared {
+        let Ok(Value::Object(mut fields)) = serde_json::from_slice::<Value>(json) else {
+            continue;
+        };
+        fields.insert("seq".to_string(), json!(1));
+        quotes.insert(instrument.clone(), Value::Object(fields));
+    }
+    let msg = WsServerMessage::quote_data(quotes);
+    match format {
+        WsFormat::Json => serde_json::to_vec(&msg).unwrap(),
+        WsFormat::MsgPack => rmp_serde::to_vec_named(&msg).unwrap(),
+        WsFormat::Cbor => {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(&msg, &mut bytes).unwrap();
+            bytes
+        }
+    }
+}
+
+/// Per-session cost of turning one distributor batch into a WebSocket frame
+fn session_benchmark(c: &mut Criterion) {
+    let deltas: Vec<(String, Value)> = (0..INSTRUMENTS)
+        .map(|i| {
+            let snapshot = snapshot(i, 0);
+            (snapshot.instrument_id.clone(), serde_json::to_value(&snapshot).unwrap())
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("session_encoding");
+    for (name, format) in [("json", WsFormat::Json), ("msgpack", WsFormat::MsgPack), ("cbor", WsFormat::Cbor)] {
+        // Before: every session parses the shared JSON and encodes the frame itself
+        group.bench_with_input(BenchmarkId::new("reparse", name), &format, |b, &format| {
+            b.iter(|| {
+                let shared: Vec<(String, Vec<u8>)> = deltas
+                    .iter()
+                    .map(|(instrument, delta)| (instrument.clone(), serde_json::to_vec(delta).unwrap()))
+                    .collect();
+                for _ in 0..SESSIONS {
+                    black_box(reparse(format, &shared));
+                }
+            })
+        });
+        // After: each format is encoded once per batch and sessions splice in their sequence numbers
+        group.bench_with_input(BenchmarkId::new("pre_encoded", name), &format, |b, &format| {
+            b.iter(|| {
+                let shared: Vec<(String, EncodedQuote)> = deltas
+                    .iter()
+                    .map(|(instrument, delta)| (instrument.clone(), EncodedQuote::new(delta.clone())))
+                    .collect();
+                for _ in 0..SESSIONS {
+                    let quotes: Vec<(&str, u64, &EncodedQuote)> =
+                        shared.iter().map(|(instrument, quote)| (instrument.as_str(), 1, quote)).collect();
+                    black_box(quote_frame(format, &quotes).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark, session_benchmark);
+criterion_main!(benches);
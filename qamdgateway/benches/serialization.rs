@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use qamd_rs::MDSnapshot;
+use serde::Serialize;
+
+mod common;
+use common::{depth_snapshot, snapshot};
+
+/// One distributor batch of snapshots in a message envelope
+#[derive(Serialize)]
+struct Batch<'a> {
+    aid: &'static str,
+    data: Vec<&'a MDSnapshot>,
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot_serialization");
+    for (name, snapshot) in [("level1", snapshot(0, 0)), ("level5", depth_snapshot(0, 0))] {
+        group.bench_with_input(BenchmarkId::new("json", name), &snapshot, |b, snapshot| {
+            b.iter(|| serde_json::to_vec(black_box(snapshot)).unwrap())
+        });
+        // Named fields, as sent to msgpack WebSocket sessions
+        group.bench_with_input(BenchmarkId::new("msgpack", name), &snapshot, |b, snapshot| {
+            b.iter(|| rmp_serde::to_vec_named(black_box(snapshot)).unwrap())
+        });
+    }
+    group.finish();
+
+    let snapshots: Vec<MDSnapshot> = (0..50).map(|instrument| snapshot(instrument, 0)).collect();
+    let batch = Batch {
+        aid: "rtn_data",
+        data: snapshots.iter().collect(),
+    };
+    let mut group = c.benchmark_group("batch_serialization");
+    group.bench_function("json", |b| b.iter(|| serde_json::to_vec(black_box(&batch)).unwrap()));
+    group.bench_function("msgpack", |b| {
+        b.iter(|| rmp_serde::to_vec_named(black_box(&batch)).unwrap())
+    });
+    group.finish();
+
+    let json = serde_json::to_vec(&snapshot(0, 0)).unwrap();
+    let msgpack = rmp_serde::to_vec_named(&snapshot(0, 0)).unwrap();
+    let mut group = c.benchmark_group("snapshot_deserialization");
+    group.bench_function("json", |b| {
+        b.iter(|| serde_json::from_slice::<MDSnapshot>(black_box(&json)).unwrap())
+    });
+    group.bench_function("msgpack", |b| {
+        b.iter(|| rmp_serde::from_slice::<MDSnapshot>(black_box(&msgpack)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
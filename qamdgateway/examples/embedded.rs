@@ -0,0 +1,38 @@
+use qamdgateway::config::Config;
+use qamdgateway::Gateway;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+/// 在进程内运行网关，通过回调和通道接收行情（不启动WebSocket服务）
+/// 使用方法: QAMDGATEWAY_CONFIG_PATH=config.json cargo run --example embedded [合约...]
+#[actix_rt::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+
+    let instruments: Vec<String> = std::env::args().skip(1).collect();
+    let mut gateway = Gateway::builder()
+        .config(Config::load()?)
+        .subscribe(instruments)
+        .on_snapshot("print", |snapshot, source| {
+            println!("{:?} {} {}", source, snapshot.instrument_id, snapshot.last_price);
+        })
+        .start()?;
+
+    // 通过通道消费行情，60秒后退出
+    let mut updates = gateway.stream("example", 1024);
+    let deadline = tokio::time::sleep(Duration::from_secs(60));
+    tokio::pin!(deadline);
+    let mut received = 0usize;
+    loop {
+        tokio::select! {
+            Some(_) = updates.recv() => received += 1,
+            _ = &mut deadline => break,
+        }
+    }
+    println!("Received {} updates", received);
+
+    gateway.stop();
+    Ok(())
+}
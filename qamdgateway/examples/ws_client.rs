@@ -1,5 +1,4 @@
 use url::Url;
-use env_logger;
 use tokio;
 use tokio_tungstenite;
 use futures::SinkExt;
@@ -12,7 +11,7 @@ use std::env;
 #[tokio::main]
 async fn main() {
     // 设置日志级别
-    env_logger::init();
+    tracing_subscriber::fmt::init();
 
     // 获取命令行参数，决定使用哪种行情源
     // 使用方法: cargo run --example ws_client [ctp|qq|sina]
@@ -0,0 +1,333 @@
+//! 按令牌的合约访问控制
+//!
+//! 令牌携带`exchange:SHFE`、`instrument:rb*`形式的权限范围（`*`表示全部合约），
+//! 订阅时拒绝无权限的合约，推送行情时再次检查，因此通过管理接口修改令牌后
+//! 已连接的会话在下一笔行情时按新的权限生效。
+
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use crate::config::AclConfig;
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::{matches_pattern, InstrumentRegistry};
+
+/// 令牌名称的最大长度
+const MAX_NAME_LEN: usize = 64;
+
+/// 权限范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// 全部合约（`*`）
+    All,
+    /// 某交易所的全部合约（`exchange:SHFE`）
+    Exchange(String),
+    /// 匹配通配符模式的合约（`instrument:rb*`）
+    Instrument(String),
+}
+
+impl FromStr for Scope {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(Scope::All);
+        }
+        match s.split_once(':') {
+            Some(("exchange", exchange)) if !exchange.is_empty() => Ok(Scope::Exchange(exchange.to_uppercase())),
+            Some(("instrument", pattern)) if !pattern.is_empty() => Ok(Scope::Instrument(pattern.to_string())),
+            _ => Err(GatewayError::ConfigError(format!(
+                "invalid scope {:?}, expected \"*\", \"exchange:<id>\" or \"instrument:<pattern>\"",
+                s
+            ))),
+        }
+    }
+}
+
+impl Scope {
+    /// 是否允许该合约（exchange为合约所属交易所）
+    fn allows(&self, instrument: &str, exchange: Option<&str>) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Exchange(id) => exchange.is_some_and(|exchange| exchange.eq_ignore_ascii_case(id)),
+            Scope::Instrument(pattern) => matches_pattern(pattern, instrument),
+        }
+    }
+}
+
+/// 一组权限范围，允许其中任一范围覆盖的合约
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    scopes: Vec<Scope>,
+}
+
+impl Permissions {
+    /// 解析权限范围列表
+    pub fn parse(scopes: &[String]) -> GatewayResult<Self> {
+        let scopes = scopes.iter().map(|scope| scope.parse()).collect::<GatewayResult<_>>()?;
+        Ok(Self { scopes })
+    }
+
+    /// 是否允许该合约，交易所取代码前缀（如`SHFE.rb2410`），否则查合约基础信息
+    pub fn allows(&self, instrument: &str, registry: &InstrumentRegistry) -> bool {
+        let exchange = match instrument.split_once('.') {
+            Some((exchange, _)) => Some(exchange),
+            None => registry.get(instrument).map(|info| info.exchange_id.as_str()),
+        };
+        self.scopes.iter().any(|scope| scope.allows(instrument, exchange))
+    }
+}
+
+/// 令牌及其权限范围
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenGrant {
+    /// 令牌
+    pub token: String,
+    /// 令牌持有者的名称
+    #[serde(default)]
+    pub name: String,
+    /// 权限范围
+    pub scopes: Vec<String>,
+    /// 最后修改时间
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 令牌存储
+///
+/// 配置了文件时每次修改后写入文件；修改后递增版本号，会话据此刷新缓存的权限
+pub struct AclStore {
+    // 持久化文件
+    path: Option<PathBuf>,
+    // 未携带令牌的客户端的权限
+    anonymous: Permissions,
+    // 令牌 -> (令牌信息, 解析后的权限)
+    tokens: RwLock<HashMap<String, (TokenGrant, Permissions)>>,
+    generation: AtomicU64,
+}
+
+impl AclStore {
+    /// 创建只保存在内存中的存储
+    pub fn new(anonymous_scopes: &[String]) -> GatewayResult<Self> {
+        Ok(Self {
+            path: None,
+            anonymous: Permissions::parse(anonymous_scopes)?,
+            tokens: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    /// 按配置创建存储，从令牌文件加载（文件不存在时为空），之后的修改写入该文件
+    pub fn load(config: &AclConfig) -> GatewayResult<Self> {
+        let mut store = Self::new(&config.anonymous_scopes)?;
+        let Some(path) = &config.tokens_file else {
+            return Ok(store);
+        };
+        let path = Path::new(path);
+        if path.exists() {
+            let grants: Vec<TokenGrant> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            let mut tokens = HashMap::with_capacity(grants.len());
+            for grant in grants {
+                let permissions = Permissions::parse(&grant.scopes)?;
+                tokens.insert(grant.token.clone(), (grant, permissions));
+            }
+            store.tokens = RwLock::new(tokens);
+        }
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    /// 令牌数
+    pub fn token_count(&self) -> usize {
+        self.tokens.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// 当前版本号，令牌每次修改后递增
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// 是否为已登记的令牌
+    pub fn contains(&self, token: &str) -> bool {
+        self.tokens.read().unwrap_or_else(|e| e.into_inner()).contains_key(token)
+    }
+
+    /// 令牌持有者的名称
+    pub fn name(&self, token: &str) -> Option<String> {
+        let tokens = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+        tokens.get(token).map(|(grant, _)| grant.name.clone())
+    }
+
+    /// 令牌的权限，未携带或已删除的令牌按匿名客户端处理
+    pub fn permissions(&self, token: Option<&str>) -> Permissions {
+        let tokens = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+        token
+            .and_then(|token| tokens.get(token))
+            .map(|(_, permissions)| permissions.clone())
+            .unwrap_or_else(|| self.anonymous.clone())
+    }
+
+    /// 按名称排序的全部令牌
+    pub fn list(&self) -> Vec<TokenGrant> {
+        let tokens = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+        let mut grants: Vec<TokenGrant> = tokens.values().map(|(grant, _)| grant.clone()).collect();
+        grants.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.token.cmp(&b.token)));
+        grants
+    }
+
+    /// 新增或替换令牌的权限范围
+    pub fn save_token(&self, token: &str, name: &str, scopes: Vec<String>) -> GatewayResult<TokenGrant> {
+        if token.len() < 16 || !token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(GatewayError::ConfigError(
+                "token must be at least 16 letters, digits, '_' or '-'".to_string(),
+            ));
+        }
+        if name.len() > MAX_NAME_LEN {
+            return Err(GatewayError::ConfigError(format!("name is longer than {} characters", MAX_NAME_LEN)));
+        }
+        let permissions = Permissions::parse(&scopes)?;
+        let grant = TokenGrant {
+            token: token.to_string(),
+            name: name.to_string(),
+            scopes,
+            updated_at: Utc::now(),
+        };
+
+        let mut tokens = self.tokens.write().unwrap_or_else(|e| e.into_inner());
+        tokens.insert(token.to_string(), (grant.clone(), permissions));
+        self.save(&tokens);
+        drop(tokens);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        info!("Token {:?} saved with scopes {:?}", grant.name, grant.scopes);
+        Ok(grant)
+    }
+
+    /// 删除令牌，使用该令牌的会话降为匿名权限
+    pub fn delete_token(&self, token: &str) -> bool {
+        let mut tokens = self.tokens.write().unwrap_or_else(|e| e.into_inner());
+        let Some((grant, _)) = tokens.remove(token) else {
+            return false;
+        };
+        self.save(&tokens);
+        drop(tokens);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        info!("Token {:?} deleted", grant.name);
+        true
+    }
+
+    /// 写入文件（先写临时文件再重命名，避免写到一半的文件）
+    fn save(&self, tokens: &HashMap<String, (TokenGrant, Permissions)>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut grants: Vec<&TokenGrant> = tokens.values().map(|(grant, _)| grant).collect();
+        grants.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.token.cmp(&b.token)));
+        let result = serde_json::to_string_pretty(&grants)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            .and_then(|content| {
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, content)?;
+                std::fs::rename(&tmp_path, path)
+            });
+        if let Err(e) = result {
+            error!("Failed to save tokens to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// 请求携带的令牌（`Authorization: Bearer`头或`token`查询参数）
+pub fn request_token(req: &HttpRequest) -> Option<String> {
+    let header = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    header.or_else(|| {
+        web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|params| params.into_inner().remove("token"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruments::InstrumentInfo;
+    use serde_json::json;
+
+    const TOKEN: &str = "desk-token-0123456789";
+
+    fn registry() -> InstrumentRegistry {
+        let info: InstrumentInfo = serde_json::from_value(json!({
+            "instrument_id": "rb2410",
+            "exchange_id": "SHFE",
+            "price_tick": 1.0,
+            "volume_multiple": 10
+        }))
+        .unwrap();
+        let mut registry = InstrumentRegistry::new();
+        registry.extend([info]);
+        registry
+    }
+
+    fn permissions(scopes: &[&str]) -> Permissions {
+        Permissions::parse(&scopes.iter().map(|scope| scope.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn scope_from_str() {
+        assert_eq!(" * ".parse::<Scope>().unwrap(), Scope::All);
+        assert_eq!("exchange:shfe".parse::<Scope>().unwrap(), Scope::Exchange("SHFE".to_string()));
+        assert_eq!("instrument:rb*".parse::<Scope>().unwrap(), Scope::Instrument("rb*".to_string()));
+        assert!("exchange:".parse::<Scope>().is_err());
+        assert!("product:rb".parse::<Scope>().is_err());
+        assert!("SHFE".parse::<Scope>().is_err());
+    }
+
+    #[test]
+    fn exchange_scope_allows_prefixed_and_registered_codes() {
+        let registry = registry();
+        let shfe = permissions(&["exchange:SHFE"]);
+        assert!(shfe.allows("SHFE.rb2410", &registry));
+        // 不带前缀的代码按合约基础信息的交易所判断
+        assert!(shfe.allows("rb2410", &registry));
+        assert!(!shfe.allows("hc2410", &registry));
+        assert!(!shfe.allows("DCE.m2409", &registry));
+    }
+
+    #[test]
+    fn instrument_scope_matches_pattern() {
+        let registry = registry();
+        let rebar = permissions(&["instrument:SHFE.rb*", "instrument:au2412"]);
+        assert!(rebar.allows("SHFE.rb2410", &registry));
+        assert!(!rebar.allows("SHFE.hc2410", &registry));
+        assert!(rebar.allows("au2412", &registry));
+        assert!(!Permissions::default().allows("SHFE.rb2410", &registry));
+        assert!(permissions(&["*"]).allows("anything", &registry));
+    }
+
+    #[test]
+    fn deleted_token_falls_back_to_anonymous() {
+        let registry = registry();
+        let store = AclStore::new(&["exchange:DCE".to_string()]).unwrap();
+        assert!(store.save_token("short", "desk", vec!["*".to_string()]).is_err());
+        store.save_token(TOKEN, "desk", vec!["exchange:SHFE".to_string()]).unwrap();
+        let generation = store.generation();
+        assert!(store.permissions(Some(TOKEN)).allows("SHFE.rb2410", &registry));
+        assert!(!store.permissions(None).allows("SHFE.rb2410", &registry));
+
+        assert!(store.delete_token(TOKEN));
+        assert!(store.generation() > generation);
+        let revoked = store.permissions(Some(TOKEN));
+        assert!(!revoked.allows("SHFE.rb2410", &registry));
+        assert!(revoked.allows("DCE.m2409", &registry));
+        assert!(!store.delete_token(TOKEN));
+    }
+}
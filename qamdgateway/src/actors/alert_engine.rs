@@ -0,0 +1,155 @@
+use actix::prelude::*;
+use hashbrown::HashMap;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::actors::messages::*;
+use crate::alerts::AlertRule;
+use crate::error::{GatewayError, GatewayResult};
+
+/// 每个客户端最多登记的预警数
+const MAX_ALERTS_PER_CLIENT: usize = 100;
+
+/// 单个客户端的预警
+struct ClientAlerts {
+    addr: Recipient<AlertTriggered>,
+    rules: HashMap<String, AlertRule>,
+}
+
+/// 行情预警Actor
+///
+/// 作为行情输出注册到分发器，每条行情到达时检查各客户端登记的预警规则，
+/// 触发时通知对应的客户端会话
+pub struct AlertEngine {
+    // 客户端ID -> 预警
+    clients: HashMap<String, ClientAlerts>,
+    // 有预警规则的合约的上一条行情
+    previous: HashMap<String, Arc<qamd_rs::MDSnapshot>>,
+}
+
+impl Actor for AlertEngine {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        info!("Alert engine started");
+    }
+}
+
+impl Default for AlertEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlertEngine {
+    /// 创建行情预警Actor
+    pub fn new() -> Self {
+        Self {
+            clients: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    /// 是否有规则适用于该合约
+    fn is_watched(&self, instrument_id: &str) -> bool {
+        self.clients
+            .values()
+            .any(|client| client.rules.values().any(|rule| rule.applies_to(instrument_id)))
+    }
+
+    /// 删除不再被任何规则引用的合约行情
+    fn prune_previous(&mut self) {
+        let watched: Vec<String> = self
+            .previous
+            .keys()
+            .filter(|instrument| self.is_watched(instrument))
+            .cloned()
+            .collect();
+        self.previous.retain(|instrument, _| watched.contains(instrument));
+    }
+}
+
+impl Handler<SetAlert> for AlertEngine {
+    type Result = GatewayResult<()>;
+
+    fn handle(&mut self, msg: SetAlert, _: &mut Self::Context) -> Self::Result {
+        let client = self
+            .clients
+            .entry(msg.client_id.clone())
+            .or_insert_with(|| ClientAlerts {
+                addr: msg.addr.clone(),
+                rules: HashMap::new(),
+            });
+        client.addr = msg.addr;
+        if !client.rules.contains_key(&msg.rule.alert_id) && client.rules.len() >= MAX_ALERTS_PER_CLIENT {
+            return Err(GatewayError::AlertRejected(format!(
+                "{}: too many alerts, at most {} per client",
+                msg.rule.alert_id, MAX_ALERTS_PER_CLIENT
+            )));
+        }
+        debug!("Client {} set alert {:?}", msg.client_id, msg.rule);
+        client.rules.insert(msg.rule.alert_id.clone(), msg.rule);
+        Ok(())
+    }
+}
+
+impl Handler<RemoveAlert> for AlertEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: RemoveAlert, _: &mut Self::Context) -> Self::Result {
+        match msg.alert_id {
+            Some(alert_id) => {
+                if let Some(client) = self.clients.get_mut(&msg.client_id) {
+                    client.rules.remove(&alert_id);
+                    if client.rules.is_empty() {
+                        self.clients.remove(&msg.client_id);
+                    }
+                }
+            }
+            None => {
+                self.clients.remove(&msg.client_id);
+            }
+        }
+        self.prune_previous();
+    }
+}
+
+impl Handler<MarketDataUpdate> for AlertEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let snapshot = msg.0;
+        if !self.is_watched(&snapshot.instrument_id) {
+            return;
+        }
+
+        let previous = self.previous.get(&snapshot.instrument_id).map(Arc::as_ref);
+        for client in self.clients.values_mut() {
+            let mut fired = Vec::new();
+            for rule in client.rules.values() {
+                if !rule.applies_to(&snapshot.instrument_id) {
+                    continue;
+                }
+                let Some(value) = rule.condition.evaluate(previous, &snapshot) else {
+                    continue;
+                };
+                client.addr.do_send(AlertTriggered {
+                    alert_id: rule.alert_id.clone(),
+                    instrument_id: snapshot.instrument_id.clone(),
+                    condition: rule.condition.clone(),
+                    value,
+                    datetime: snapshot.datetime,
+                });
+                if !rule.repeat {
+                    fired.push(rule.alert_id.clone());
+                }
+            }
+            for alert_id in fired {
+                client.rules.remove(&alert_id);
+            }
+        }
+        self.clients.retain(|_, client| !client.rules.is_empty());
+
+        self.previous.insert(snapshot.instrument_id.clone(), snapshot);
+    }
+}
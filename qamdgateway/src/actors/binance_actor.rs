@@ -0,0 +1,481 @@
+use actix::prelude::*;
+use awc::error::WsProtocolError;
+use awc::ws;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use hashbrown::{HashMap, HashSet};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+use qamd_rs::{MDSnapshot, MDSnapshotBuilder};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::config::BinanceConfig;
+use crate::journal::RawJournal;
+use crate::latency;
+
+// 单条SUBSCRIBE请求的最大stream数
+const MAX_STREAMS_PER_REQUEST: usize = 200;
+// 连续请求的间隔（Binance限制每个连接每秒最多5条上行消息）
+const REQUEST_INTERVAL: Duration = Duration::from_millis(250);
+// 连接状态检查间隔
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// websocket最大帧长度
+const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// bookTicker推送（合约期货版本带事件时间E，现货版本没有）
+#[derive(Debug, Deserialize)]
+struct BookTicker {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "b")]
+    bid_price: String,
+    #[serde(rename = "B")]
+    bid_quantity: String,
+    #[serde(rename = "a")]
+    ask_price: String,
+    #[serde(rename = "A")]
+    ask_quantity: String,
+    #[serde(rename = "E", default)]
+    event_time: Option<i64>,
+}
+
+/// trade推送
+#[derive(Debug, Deserialize)]
+struct Trade {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p")]
+    price: String,
+    #[serde(rename = "q")]
+    quantity: String,
+    #[serde(rename = "T")]
+    trade_time: i64,
+}
+
+/// 请求的错误信息
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: i64,
+    msg: String,
+}
+
+/// websocket文本消息
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StreamMessage {
+    Trade(Trade),
+    BookTicker(BookTicker),
+    /// SUBSCRIBE/UNSUBSCRIBE的应答
+    Response {
+        id: u64,
+        #[serde(default)]
+        error: Option<ApiError>,
+    },
+}
+
+/// 单个合约的行情状态
+#[derive(Debug, Default)]
+struct QuoteState {
+    datetime: Option<DateTime<Utc>>,
+    // 开高低和成交的统计日（UTC）
+    trading_day: Option<NaiveDate>,
+    last_price: f64,
+    open: f64,
+    highest: f64,
+    lowest: f64,
+    // 当日累计成交数量（未放大）和成交额
+    quantity: f64,
+    amount: f64,
+    bid_price: f64,
+    bid_quantity: f64,
+    ask_price: f64,
+    ask_quantity: f64,
+}
+
+impl QuoteState {
+    /// 推进行情时间，保证同一合约的行情时间不倒退（否则会被分发器当作乱序行情丢弃）
+    fn advance(&mut self, datetime: DateTime<Utc>) -> DateTime<Utc> {
+        let datetime = self.datetime.map_or(datetime, |last| last.max(datetime));
+        self.datetime = Some(datetime);
+        datetime
+    }
+
+    /// 记录一笔成交，跨UTC日时重新统计开高低和成交
+    fn record_trade(&mut self, price: f64, quantity: f64, datetime: DateTime<Utc>) {
+        let day = datetime.date_naive();
+        if self.trading_day != Some(day) {
+            self.trading_day = Some(day);
+            self.open = price;
+            self.highest = price;
+            self.lowest = price;
+            self.quantity = 0.0;
+            self.amount = 0.0;
+        }
+        self.last_price = price;
+        self.highest = self.highest.max(price);
+        self.lowest = self.lowest.min(price);
+        self.quantity += quantity;
+        self.amount += price * quantity;
+    }
+
+    fn snapshot(&self, instrument_id: &str, datetime: DateTime<Utc>, scale: f64) -> MDSnapshot {
+        let scaled = |quantity: f64| (quantity * scale).round() as i64;
+        let mut builder = MDSnapshotBuilder::new(instrument_id, datetime)
+            .last_price(self.last_price)
+            .open(self.open)
+            .highest(self.highest)
+            .lowest(self.lowest)
+            .volume(scaled(self.quantity))
+            .amount(self.amount)
+            .bid(1, self.bid_price, scaled(self.bid_quantity))
+            .ask(1, self.ask_price, scaled(self.ask_quantity));
+        if self.quantity > 0.0 {
+            builder = builder.average(self.amount / self.quantity);
+        }
+        builder.build()
+    }
+}
+
+/// 毫秒时间戳转换为UTC时间
+fn from_millis(millis: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(millis).single()
+}
+
+/// 合约的stream名称（如`btcusdt@bookTicker`、`btcusdt@trade`）
+fn streams(symbol: &str) -> [String; 2] {
+    let symbol = symbol.to_ascii_lowercase();
+    [format!("{}@bookTicker", symbol), format!("{}@trade", symbol)]
+}
+
+/// Binance现货L1行情Actor
+///
+/// 通过一个websocket连接订阅`<symbol>@bookTicker`（买一卖一）和`<symbol>@trade`（逐笔成交），
+/// 合并为MDSnapshot后发送到分发器。合约代码带交易所前缀（如`BINANCE.BTCUSDT`），
+/// 分发器把该前缀合约的订阅和退订直接发给本Actor。
+/// 开高低、成交量和成交额按UTC自然日从连接后收到的成交累计，
+/// 成交量和挂单量按`quantity_decimals`放大后取整。
+/// 断线或长时间没有收到任何消息时按`reconnect_secs`重连，重连后重新订阅全部合约
+pub struct BinanceMarketDataActor {
+    config: BinanceConfig,
+    distributor: Addr<MarketDataDistributor>,
+    // 订阅的合约（Binance代码，大写）
+    symbols: HashSet<String>,
+    // 合约代码 -> 行情状态
+    quotes: HashMap<String, QuoteState>,
+    // 向连接写入消息的通道（未连接时为None）
+    writer: Option<mpsc::UnboundedSender<ws::Message>>,
+    // 读取连接的stream
+    reader: Option<SpawnHandle>,
+    connecting: bool,
+    // 最后一次收到消息的时间
+    last_message: Instant,
+    next_request_id: u64,
+    // 上行请求排队，按REQUEST_INTERVAL发送
+    next_request_at: Instant,
+    // 原始消息日志（解析前写入）
+    journal: Option<Arc<RawJournal>>,
+    // 本连接日志所在的span
+    span: Span,
+}
+
+impl Actor for BinanceMarketDataActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        info!("Binance market data actor started, connecting to {}", self.config.url);
+
+        let instruments = self.config.instruments.clone();
+        self.add_symbols(&instruments);
+
+        let addr = ctx.address();
+        self.distributor.do_send(RegisterExchangeSource {
+            exchange: self.config.exchange.clone(),
+            source: MarketDataSource::Binance,
+            subscribe: addr.clone().recipient(),
+            unsubscribe: addr.recipient(),
+        });
+
+        self.connect(ctx);
+        ctx.run_interval(IDLE_CHECK_INTERVAL, |act, ctx| act.check_idle(ctx));
+    }
+}
+
+impl BinanceMarketDataActor {
+    /// 创建Binance现货行情Actor
+    pub fn new(config: BinanceConfig, distributor: Addr<MarketDataDistributor>) -> Self {
+        Self {
+            span: info_span!("upstream", source = ?MarketDataSource::Binance, front = %config.url),
+            config,
+            distributor,
+            symbols: HashSet::new(),
+            quotes: HashMap::new(),
+            writer: None,
+            reader: None,
+            connecting: false,
+            last_message: Instant::now(),
+            next_request_id: 1,
+            next_request_at: Instant::now(),
+            journal: None,
+        }
+    }
+
+    /// 解析前把原始消息写入日志
+    pub fn with_journal(mut self, journal: Arc<RawJournal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// 合约代码转换为Binance代码，其他交易所的合约返回None
+    fn symbol_of(&self, instrument: &str) -> Option<String> {
+        instrument
+            .split_once('.')
+            .filter(|(exchange, symbol)| *exchange == self.config.exchange && !symbol.is_empty())
+            .map(|(_, symbol)| symbol.to_ascii_uppercase())
+    }
+
+    /// 加入订阅的合约，返回新增的Binance代码
+    fn add_symbols(&mut self, instruments: &[String]) -> Vec<String> {
+        let added: Vec<String> = instruments
+            .iter()
+            .filter_map(|instrument| self.symbol_of(instrument))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter(|symbol| self.symbols.insert(symbol.clone()))
+            .collect();
+        added
+    }
+
+    /// 建立websocket连接
+    fn connect(&mut self, ctx: &mut Context<Self>) {
+        if self.connecting {
+            return;
+        }
+        self.connecting = true;
+
+        let request = awc::Client::new()
+            .ws(self.config.url.as_str())
+            .max_frame_size(MAX_FRAME_SIZE)
+            .connect();
+        async move { request.await.map(|(_, framed)| framed) }
+            .into_actor(self)
+            .map(|res, act, ctx| {
+                let _span = act.span.clone().entered();
+                act.connecting = false;
+                match res {
+                    Ok(framed) => {
+                        info!("Connected to Binance at {}", act.config.url);
+                        let (sink, stream) = framed.split();
+                        let (writer, rx) = mpsc::unbounded();
+                        actix::spawn(
+                            async move {
+                                if let Err(e) = rx.map(Ok).forward(sink).await {
+                                    warn!("Binance websocket write failed: {}", e);
+                                }
+                            }
+                            .instrument(act.span.clone()),
+                        );
+                        act.writer = Some(writer);
+                        act.reader = Some(ctx.add_stream(stream));
+                        act.last_message = Instant::now();
+
+                        let symbols: Vec<String> = act.symbols.iter().cloned().collect();
+                        act.request(ctx, "SUBSCRIBE", &symbols);
+                    }
+                    Err(e) => {
+                        error!("Failed to connect to Binance at {}: {}", act.config.url, e);
+                        act.reconnect_later(ctx);
+                    }
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// 断开当前连接并在reconnect_secs后重连
+    fn reconnect_later(&mut self, ctx: &mut Context<Self>) {
+        if let Some(reader) = self.reader.take() {
+            ctx.cancel_future(reader);
+        }
+        // 关闭写入通道后连接随之关闭
+        self.writer = None;
+        ctx.run_later(Duration::from_secs(self.config.reconnect_secs.max(1)), |act, ctx| {
+            let _span = act.span.clone().entered();
+            act.connect(ctx);
+        });
+    }
+
+    /// 长时间没有收到任何消息（Binance定期发送ping）时重连
+    fn check_idle(&mut self, ctx: &mut Context<Self>) {
+        let timeout = Duration::from_secs(self.config.idle_timeout_secs);
+        if self.writer.is_some() && !timeout.is_zero() && self.last_message.elapsed() > timeout {
+            warn!("No message from Binance for {:?}, reconnecting", self.last_message.elapsed());
+            self.reconnect_later(ctx);
+        }
+    }
+
+    /// 发送SUBSCRIBE/UNSUBSCRIBE请求，stream较多时分批并按间隔发送
+    fn request(&mut self, ctx: &mut Context<Self>, method: &'static str, symbols: &[String]) {
+        if self.writer.is_none() || symbols.is_empty() {
+            return;
+        }
+        let streams: Vec<String> = symbols.iter().flat_map(|symbol| streams(symbol)).collect();
+        for batch in streams.chunks(MAX_STREAMS_PER_REQUEST) {
+            let id = self.next_request_id;
+            self.next_request_id += 1;
+            let message = serde_json::json!({
+                "method": method,
+                "params": batch,
+                "id": id,
+            })
+            .to_string();
+            debug!("Binance {} request {}: {} streams", method, id, batch.len());
+
+            let now = Instant::now();
+            let delay = self.next_request_at.saturating_duration_since(now);
+            self.next_request_at = now.max(self.next_request_at) + REQUEST_INTERVAL;
+            ctx.run_later(delay, move |act, _| act.write(ws::Message::Text(message.into())));
+        }
+    }
+
+    fn write(&mut self, message: ws::Message) {
+        if let Some(writer) = &self.writer {
+            if writer.unbounded_send(message).is_err() {
+                debug!("Binance connection closed, dropping outgoing message");
+            }
+        }
+    }
+
+    /// 解析文本消息，合并到行情状态后发送到分发器
+    fn handle_text(&mut self, text: &str) {
+        if let Some(journal) = &self.journal {
+            if let Err(e) = journal.append_bytes(MarketDataSource::Binance, latency::now_us(), text.as_bytes()) {
+                warn!("Failed to journal Binance message: {}", e);
+            }
+        }
+        let message = match serde_json::from_str::<StreamMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!("Ignoring Binance message {}: {}", text, e);
+                return;
+            }
+        };
+        let scale = 10f64.powi(self.config.quantity_decimals as i32);
+        let price = |value: &str| value.parse::<f64>().unwrap_or(0.0);
+
+        let (symbol, snapshot) = match message {
+            StreamMessage::Trade(trade) => {
+                let Some(datetime) = from_millis(trade.trade_time) else {
+                    return;
+                };
+                let instrument_id = format!("{}.{}", self.config.exchange, trade.symbol);
+                let state = self.quotes.entry_ref(trade.symbol.as_str()).or_default();
+                let datetime = state.advance(datetime);
+                state.record_trade(price(&trade.price), price(&trade.quantity), datetime);
+                (trade.symbol, state.snapshot(&instrument_id, datetime, scale))
+            }
+            StreamMessage::BookTicker(ticker) => {
+                let datetime = ticker.event_time.and_then(from_millis).unwrap_or_else(Utc::now);
+                let instrument_id = format!("{}.{}", self.config.exchange, ticker.symbol);
+                let state = self.quotes.entry_ref(ticker.symbol.as_str()).or_default();
+                let datetime = state.advance(datetime);
+                state.bid_price = price(&ticker.bid_price);
+                state.bid_quantity = price(&ticker.bid_quantity);
+                state.ask_price = price(&ticker.ask_price);
+                state.ask_quantity = price(&ticker.ask_quantity);
+                (ticker.symbol, state.snapshot(&instrument_id, datetime, scale))
+            }
+            StreamMessage::Response { id, error: Some(error) } => {
+                error!("Binance request {} failed: {} ({})", id, error.msg, error.code);
+                return;
+            }
+            StreamMessage::Response { .. } => return,
+        };
+
+        // 退订后仍可能收到少量在途消息
+        if self.symbols.contains(&symbol) {
+            self.distributor.do_send(MarketDataUpdate(Arc::new(snapshot), MarketDataSource::Binance));
+        }
+    }
+}
+
+/// 处理websocket消息
+impl StreamHandler<Result<ws::Frame, WsProtocolError>> for BinanceMarketDataActor {
+    fn handle(&mut self, msg: Result<ws::Frame, WsProtocolError>, ctx: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        self.last_message = Instant::now();
+        match msg {
+            Ok(ws::Frame::Text(bytes)) => match std::str::from_utf8(&bytes) {
+                Ok(text) => self.handle_text(text),
+                Err(e) => warn!("Invalid UTF-8 from Binance: {}", e),
+            },
+            Ok(ws::Frame::Ping(payload)) => self.write(ws::Message::Pong(payload)),
+            Ok(ws::Frame::Close(reason)) => {
+                info!("Binance closed the connection: {:?}", reason);
+                self.reconnect_later(ctx);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("Binance websocket protocol error: {}", e);
+                self.reconnect_later(ctx);
+            }
+        }
+    }
+
+    // 连接断开时重连，不停止Actor
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        if self.reader.take().is_some() {
+            warn!("Binance connection lost");
+            self.reconnect_later(ctx);
+        }
+    }
+}
+
+/// 订阅合约（非本交易所的合约忽略）
+impl Handler<Subscribe> for BinanceMarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Subscribe, ctx: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
+        let added = self.add_symbols(&msg.instruments);
+        if !added.is_empty() {
+            info!("Subscribing {} Binance symbols", added.len());
+            self.request(ctx, "SUBSCRIBE", &added);
+        }
+    }
+}
+
+/// 退订合约（配置中的合约始终保留）
+impl Handler<Unsubscribe> for BinanceMarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, ctx: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
+        let pinned: HashSet<String> = self
+            .config
+            .instruments
+            .iter()
+            .filter_map(|instrument| self.symbol_of(instrument))
+            .collect();
+        let requested: HashSet<String> = msg
+            .instruments
+            .iter()
+            .filter_map(|instrument| self.symbol_of(instrument))
+            .collect();
+        let removed: Vec<String> = requested
+            .into_iter()
+            .filter(|symbol| !pinned.contains(symbol) && self.symbols.remove(symbol))
+            .collect();
+        for symbol in &removed {
+            self.quotes.remove(symbol);
+        }
+        if !removed.is_empty() {
+            info!("Unsubscribing {} Binance symbols", removed.len());
+            self.request(ctx, "UNSUBSCRIBE", &removed);
+        }
+    }
+}
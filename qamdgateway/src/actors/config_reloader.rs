@@ -0,0 +1,255 @@
+use actix::prelude::*;
+use tracing::{info, warn};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+
+use crate::actors::md_connector::MarketDataConnector;
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::config::{ClientQuotaConfig, Config, HeartbeatConfig, SendQueueConfig};
+use crate::instruments::InstrumentRegistry;
+use crate::sinks::{
+    start_ipc_server, start_redis_bridge, start_zmq_publisher, stop_sink, IPC_SINK, REDIS_SINK, ZMQ_SINK,
+};
+
+/// 配置热加载Actor
+///
+/// 收到SIGHUP或`/api/admin/reload`请求时重新读取配置文件，与当前配置比较后：
+/// - 默认订阅的增减立即同步到上游
+/// - 发送队列、客户端限额和心跳设置对新连接生效，重新订阅限速立即下发给所有上游连接
+/// - Redis、ZeroMQ和本机IPC输出按新配置启停或重建
+/// - 默认broker的凭证或前置地址变化时只重连对应的行情Actor
+///
+/// 其余配置项的变化只记录为需要重启。
+pub struct ConfigReloader {
+    // 当前生效的配置
+    config: Config,
+    connector: Addr<MarketDataConnector>,
+    distributor: Addr<MarketDataDistributor>,
+    instruments: Arc<InstrumentRegistry>,
+    // 与WebSocket处理器共享的发送队列设置
+    send_queue: Arc<RwLock<SendQueueConfig>>,
+    // 与WebSocket处理器共享的客户端限额
+    quota: Arc<RwLock<ClientQuotaConfig>>,
+    // 与WebSocket处理器共享的心跳设置
+    heartbeat: Arc<RwLock<HeartbeatConfig>>,
+}
+
+impl Actor for ConfigReloader {
+    type Context = Context<Self>;
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config: Config,
+        connector: Addr<MarketDataConnector>,
+        distributor: Addr<MarketDataDistributor>,
+        instruments: Arc<InstrumentRegistry>,
+        send_queue: Arc<RwLock<SendQueueConfig>>,
+        quota: Arc<RwLock<ClientQuotaConfig>>,
+        heartbeat: Arc<RwLock<HeartbeatConfig>>,
+    ) -> Self {
+        Self {
+            config,
+            connector,
+            distributor,
+            instruments,
+            send_queue,
+            quota,
+            heartbeat,
+        }
+    }
+
+    /// 展开后的默认订阅
+    fn default_instruments(&self, config: &Config) -> Vec<String> {
+        let mut instruments = self.instruments.expand(&config.subscription.default_instruments);
+        for instrument in self.instruments.expand(&config.subscription.auto_subscribe_patterns) {
+            if !instruments.contains(&instrument) {
+                instruments.push(instrument);
+            }
+        }
+        instruments
+    }
+
+    /// 应用可热加载的配置项
+    fn apply(&mut self, config: &Config, summary: &mut ReloadSummary) {
+        let old_instruments = self.default_instruments(&self.config);
+        let new_instruments = self.default_instruments(config);
+        let added: Vec<String> = new_instruments
+            .iter()
+            .filter(|instrument| !old_instruments.contains(instrument))
+            .cloned()
+            .collect();
+        let removed: Vec<String> = old_instruments
+            .into_iter()
+            .filter(|instrument| !new_instruments.contains(instrument))
+            .collect();
+        if !added.is_empty() || !removed.is_empty() {
+            summary.applied.push(format!(
+                "subscription.default_instruments (+{} -{})",
+                added.len(),
+                removed.len()
+            ));
+            self.connector.do_send(UpdateDefaultSubscriptions { added, removed });
+        }
+
+        if config.websocket.send_queue != self.config.websocket.send_queue {
+            *self.send_queue.write().unwrap_or_else(|e| e.into_inner()) = config.websocket.send_queue.clone();
+            summary.applied.push("websocket.send_queue".to_string());
+        }
+        if config.websocket.quota != self.config.websocket.quota {
+            *self.quota.write().unwrap_or_else(|e| e.into_inner()) = config.websocket.quota.clone();
+            summary.applied.push("websocket.quota".to_string());
+        }
+        if config.websocket.heartbeat != self.config.websocket.heartbeat {
+            *self.heartbeat.write().unwrap_or_else(|e| e.into_inner()) = config.websocket.heartbeat.clone();
+            summary.applied.push("websocket.heartbeat".to_string());
+        }
+
+        if config.resubscribe != self.config.resubscribe {
+            self.connector.do_send(UpdateResubscribePolicy {
+                config: config.resubscribe.clone(),
+            });
+            summary.applied.push("resubscribe".to_string());
+        }
+
+        // 输出的配置变化时先停止旧的输出再按新配置启动
+        let old_redis = self.config.redis.clone().filter(|r| r.enabled);
+        let new_redis = config.redis.clone().filter(|r| r.enabled);
+        if old_redis != new_redis {
+            if old_redis.is_some() {
+                stop_sink(REDIS_SINK, &self.distributor);
+            }
+            if let Some(redis_config) = new_redis {
+                start_redis_bridge(redis_config, self.instruments.clone(), &self.distributor);
+            }
+            summary.applied.push("redis".to_string());
+        }
+        let old_zmq = self.config.zmq.clone().filter(|z| z.enabled);
+        let new_zmq = config.zmq.clone().filter(|z| z.enabled);
+        if old_zmq != new_zmq {
+            if old_zmq.is_some() {
+                stop_sink(ZMQ_SINK, &self.distributor);
+            }
+            if let Some(zmq_config) = new_zmq {
+                start_zmq_publisher(zmq_config, &self.distributor);
+            }
+            summary.applied.push("zmq".to_string());
+        }
+        let old_ipc = self.config.ipc.clone().filter(|i| i.enabled);
+        let new_ipc = config.ipc.clone().filter(|i| i.enabled);
+        if old_ipc != new_ipc {
+            if old_ipc.is_some() {
+                stop_sink(IPC_SINK, &self.distributor);
+            }
+            if let Some(ipc_config) = new_ipc {
+                start_ipc_server(ipc_config, &self.distributor);
+            }
+            summary.applied.push("ipc".to_string());
+        }
+
+        // 其余配置项在启动时读取一次
+        let websocket = |config: &Config| {
+            (config.websocket.host.clone(), config.websocket.port, config.websocket.path.clone())
+        };
+        if websocket(config) != websocket(&self.config) {
+            summary.restart_required.push("websocket".to_string());
+        }
+        let sections = [
+            ("subscription.subscription_file", config.subscription.subscription_file != self.config.subscription.subscription_file),
+            ("rest_api", changed(&config.rest_api, &self.config.rest_api)),
+            ("replay", changed(&config.replay, &self.config.replay)),
+            ("mock", changed(&config.mock, &self.config.mock)),
+            ("acl", changed(&config.acl, &self.config.acl)),
+            ("dominant", changed(&config.dominant, &self.config.dominant)),
+            ("circuit_breaker", changed(&config.circuit_breaker, &self.config.circuit_breaker)),
+            ("failover", changed(&config.failover, &self.config.failover)),
+            ("calendar", changed(&config.calendar, &self.config.calendar)),
+            ("options", changed(&config.options, &self.config.options)),
+            ("replication", changed(&config.replication, &self.config.replication)),
+            ("recorder", changed(&config.recorder, &self.config.recorder)),
+            ("history", changed(&config.history, &self.config.history)),
+            ("eod", changed(&config.eod, &self.config.eod)),
+            ("sina_http", changed(&config.sina_http, &self.config.sina_http)),
+            ("converter", changed(&config.converter, &self.config.converter)),
+            ("instrument_query", changed(&config.instrument_query, &self.config.instrument_query)),
+            ("trader_query", changed(&config.trader_query, &self.config.trader_query)),
+            ("warmup", changed(&config.warmup, &self.config.warmup)),
+            ("distributor", changed(&config.distributor, &self.config.distributor)),
+            ("price_check", changed(&config.price_check, &self.config.price_check)),
+            ("data_quality", changed(&config.data_quality, &self.config.data_quality)),
+            ("trading_phase", changed(&config.trading_phase, &self.config.trading_phase)),
+            ("admin", changed(&config.admin, &self.config.admin)),
+            ("socketio", changed(&config.socketio, &self.config.socketio)),
+            ("flow", changed(&config.flow, &self.config.flow)),
+            ("latency", changed(&config.latency, &self.config.latency)),
+            ("journal", changed(&config.journal, &self.config.journal)),
+            ("supervision", changed(&config.supervision, &self.config.supervision)),
+            ("load_sharing", changed(&config.load_sharing, &self.config.load_sharing)),
+            ("symbols", changed(&config.symbols, &self.config.symbols)),
+            ("log_level", config.log_level != self.config.log_level),
+            ("logging", changed(&config.logging, &self.config.logging)),
+        ];
+        summary.restart_required.extend(
+            sections
+                .into_iter()
+                .filter(|(_, changed)| *changed)
+                .map(|(section, _)| section.to_string()),
+        );
+    }
+}
+
+/// 通过序列化结果比较配置项
+fn changed<T: Serialize>(new: &T, old: &T) -> bool {
+    serde_json::to_value(new).ok() != serde_json::to_value(old).ok()
+}
+
+impl Handler<ReloadConfig> for ConfigReloader {
+    type Result = ResponseActFuture<Self, Result<ReloadSummary, String>>;
+
+    fn handle(&mut self, _: ReloadConfig, _: &mut Self::Context) -> Self::Result {
+        // 新配置无效时保留当前配置
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Config reload failed, keeping the current configuration: {}", e);
+                return Box::pin(fut::ready(Err(e.to_string())));
+            }
+        };
+
+        let mut summary = ReloadSummary::default();
+        self.apply(&config, &mut summary);
+
+        // 回放模式下没有上游连接
+        let replay = config.replay.as_ref().is_some_and(|r| r.enabled);
+        let brokers = match config.active_broker_configs() {
+            Ok(brokers) if !replay => brokers,
+            Ok(_) => Vec::new(),
+            Err(e) => {
+                warn!("Skipping broker reload: {}", e);
+                Vec::new()
+            }
+        };
+        self.config = config;
+
+        Box::pin(
+            self.connector
+                .send(ReloadBrokers { configs: brokers })
+                .into_actor(self)
+                .map(move |result, _, _| {
+                    match result {
+                        Ok(reconnected) => summary.reconnected_brokers = reconnected,
+                        Err(e) => warn!("Failed to reload broker configuration: {}", e),
+                    }
+                    info!(
+                        "Configuration reloaded: applied {:?}, reconnected {:?}",
+                        summary.applied, summary.reconnected_brokers
+                    );
+                    if !summary.restart_required.is_empty() {
+                        warn!("Changes to {:?} take effect after a restart", summary.restart_required);
+                    }
+                    Ok(summary)
+                }),
+        )
+    }
+}
@@ -0,0 +1,303 @@
+use actix::prelude::*;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use hashbrown::HashMap;
+use tracing::{debug, info};
+use qamd_rs::MDSnapshot;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::actors::messages::*;
+use crate::calendar::{china_offset, TradingCalendar, TradingPhase};
+use crate::config::DataQualityConfig;
+use crate::instruments::matches_pattern;
+
+/// 清理长时间没有行情的合约（已取消订阅的合约只在下一个时段报告一次无行情）
+const IDLE_TIMEOUT: Duration = Duration::from_secs(12 * 3600);
+
+/// 单个合约的质量状态
+struct InstrumentState {
+    /// 最新行情的时间
+    last_datetime: DateTime<Utc>,
+    /// 最新行情的接收时间
+    last_received: Instant,
+    /// 当前状态，只在进入异常状态时报告一次
+    stale: bool,
+    crossed: bool,
+    invalid_price: bool,
+    issues: QualityCounts,
+    last_issue: Option<QualityEvent>,
+}
+
+/// 单个客户端的质量事件订阅
+struct ClientQuality {
+    addr: Recipient<QualityEvent>,
+    patterns: Vec<String>,
+}
+
+/// 行情质量监控Actor
+///
+/// 作为行情输出注册到分发器，检查交易时段内的行情间隔和无行情的合约、
+/// 零/负价格以及买一价不低于卖一价的交叉盘口，
+/// 质量事件推送给订阅的客户端会话，各合约的问题计数由管理接口查询。
+/// 交易时段按合约代码的交易所前缀判断，未知交易所的合约不做间隔和无行情检查。
+/// 收到交易阶段后，无行情只在交易所连续交易满`stale_secs`后报告（集合竞价、休市等阶段不报告）
+pub struct DataQualityActor {
+    config: DataQualityConfig,
+    calendar: Arc<TradingCalendar>,
+    // 交易所 -> 当前交易阶段及进入的时间
+    phases: HashMap<String, (TradingPhase, Instant)>,
+    // 合约ID -> 质量状态
+    instruments: HashMap<String, InstrumentState>,
+    // 客户端ID -> 质量事件订阅
+    clients: HashMap<String, ClientQuality>,
+}
+
+impl Actor for DataQualityActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Data quality monitor started");
+
+        if self.config.check_interval_secs > 0 {
+            ctx.run_interval(Duration::from_secs(self.config.check_interval_secs), |act, _| {
+                act.check_stale();
+            });
+        }
+    }
+}
+
+impl DataQualityActor {
+    /// 创建行情质量监控Actor
+    pub fn new(config: DataQualityConfig, calendar: Arc<TradingCalendar>) -> Self {
+        Self {
+            config,
+            calendar,
+            phases: HashMap::new(),
+            instruments: HashMap::new(),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// 合约在某一时刻（北京时间）是否处于交易时段
+    fn is_trading_time(&self, instrument_id: &str, datetime: NaiveDateTime) -> bool {
+        instrument_id
+            .split_once('.')
+            .is_some_and(|(exchange, _)| self.calendar.is_trading_time(exchange, datetime))
+    }
+
+    /// 合约的交易所是否已连续交易`elapsed`以上，未收到交易阶段时按交易日历判断
+    fn is_continuous_for(&self, instrument_id: &str, now: NaiveDateTime, elapsed: Duration) -> bool {
+        let Some((exchange, _)) = instrument_id.split_once('.') else {
+            return false;
+        };
+        match self.phases.get(exchange) {
+            Some((phase, since)) => *phase == TradingPhase::Continuous && since.elapsed() >= elapsed,
+            None => {
+                let since = now - chrono::Duration::seconds(elapsed.as_secs() as i64);
+                self.calendar.is_trading_time(exchange, now) && self.calendar.is_trading_time(exchange, since)
+            }
+        }
+    }
+
+    /// 检查交易时段内长时间没有行情的合约
+    fn check_stale(&mut self) {
+        let now = TradingCalendar::now_local();
+        let stale_after = Duration::from_secs(self.config.stale_secs);
+
+        self.instruments
+            .retain(|_, state| state.last_received.elapsed() < IDLE_TIMEOUT);
+        let stale: Vec<(String, Duration)> = self
+            .instruments
+            .iter()
+            .filter(|(instrument, state)| {
+                !state.stale
+                    && state.last_received.elapsed() >= stale_after
+                    // 时段开始不足stale_secs时不报告，避免开盘时把前一时段的最后一条行情当作无行情
+                    && self.is_continuous_for(instrument, now, stale_after)
+            })
+            .map(|(instrument, state)| (instrument.clone(), state.last_received.elapsed()))
+            .collect();
+
+        for (instrument_id, elapsed) in stale {
+            if let Some(state) = self.instruments.get_mut(&instrument_id) {
+                state.stale = true;
+            }
+            self.report(QualityEvent {
+                detail: format!("No ticks for {}s during trading hours", elapsed.as_secs()),
+                instrument_id,
+                issue: QualityIssue::Stale,
+                datetime: Utc::now(),
+            });
+        }
+    }
+
+    /// 检查一条行情，返回新出现的问题
+    fn check_snapshot(&mut self, snapshot: &MDSnapshot) -> Vec<QualityEvent> {
+        let max_gap = chrono::Duration::seconds(self.config.max_gap_secs as i64);
+        let previous = self
+            .instruments
+            .get(&snapshot.instrument_id)
+            .map(|state| state.last_datetime);
+        let event = |issue, detail| QualityEvent {
+            instrument_id: snapshot.instrument_id.clone(),
+            issue,
+            detail,
+            datetime: snapshot.datetime,
+        };
+        let mut events = Vec::new();
+
+        // 间隔的起点加上阈值仍在交易时段内才算断档，午休、收盘等时段间隔不计
+        if let Some(previous) = previous {
+            let gap = snapshot.datetime - previous;
+            let local = |datetime: DateTime<Utc>| datetime.with_timezone(&china_offset()).naive_local();
+            if gap > max_gap
+                && self.is_trading_time(&snapshot.instrument_id, local(previous + max_gap))
+                && self.is_trading_time(&snapshot.instrument_id, local(snapshot.datetime))
+            {
+                events.push(event(
+                    QualityIssue::Gap,
+                    format!("No ticks for {}s since {}", gap.num_seconds(), previous.to_rfc3339()),
+                ));
+            }
+        }
+
+        let state = self
+            .instruments
+            .entry_ref(snapshot.instrument_id.as_str())
+            .or_insert_with(|| InstrumentState {
+                last_datetime: snapshot.datetime,
+                last_received: Instant::now(),
+                stale: false,
+                crossed: false,
+                invalid_price: false,
+                issues: QualityCounts::default(),
+                last_issue: None,
+            });
+        if snapshot.datetime > state.last_datetime {
+            state.last_datetime = snapshot.datetime;
+        }
+        state.last_received = Instant::now();
+        if state.stale {
+            state.stale = false;
+            debug!("Ticks of {} resumed", snapshot.instrument_id);
+        }
+
+        let prices = [snapshot.last_price, snapshot.bid_price1, snapshot.ask_price1];
+        let invalid_price = prices.iter().any(|price| !price.is_finite() || *price < 0.0)
+            || (snapshot.volume > 0 && snapshot.last_price <= 0.0);
+        if invalid_price && !state.invalid_price {
+            events.push(event(
+                QualityIssue::InvalidPrice,
+                format!(
+                    "Invalid price: last {} bid {} ask {}",
+                    snapshot.last_price, snapshot.bid_price1, snapshot.ask_price1
+                ),
+            ));
+        }
+        state.invalid_price = invalid_price;
+
+        let crossed = snapshot.bid_price1 > 0.0
+            && snapshot.ask_price1 > 0.0
+            && snapshot.bid_price1 >= snapshot.ask_price1;
+        if crossed && !state.crossed {
+            events.push(event(
+                QualityIssue::CrossedBook,
+                format!("Crossed book: bid {} >= ask {}", snapshot.bid_price1, snapshot.ask_price1),
+            ));
+        }
+        state.crossed = crossed;
+
+        events
+    }
+
+    /// 记录问题并推送给订阅的客户端
+    fn report(&mut self, event: QualityEvent) {
+        debug!("Data quality {:?} on {}: {}", event.issue, event.instrument_id, event.detail);
+        if let Some(state) = self.instruments.get_mut(&event.instrument_id) {
+            state.issues.record(event.issue);
+            state.last_issue = Some(event.clone());
+        }
+        for client in self.clients.values() {
+            if client
+                .patterns
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &event.instrument_id))
+            {
+                client.addr.do_send(event.clone());
+            }
+        }
+    }
+}
+
+impl Handler<MarketDataUpdate> for DataQualityActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        for event in self.check_snapshot(&msg.0) {
+            self.report(event);
+        }
+    }
+}
+
+impl Handler<PhaseChanged> for DataQualityActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: PhaseChanged, _: &mut Self::Context) -> Self::Result {
+        // 首次推送的是当前阶段，按进入该阶段的时间计算持续时间
+        let since = match msg.previous {
+            Some(_) => Instant::now(),
+            None => {
+                let elapsed = (Utc::now() - msg.datetime).to_std().unwrap_or_default();
+                Instant::now().checked_sub(elapsed).unwrap_or_else(Instant::now)
+            }
+        };
+        self.phases.insert(msg.exchange, (msg.phase, since));
+    }
+}
+
+impl Handler<SubscribeQuality> for DataQualityActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeQuality, _: &mut Self::Context) -> Self::Result {
+        debug!("Client {} data quality subscriptions: {:?}", msg.client_id, msg.patterns);
+        if msg.patterns.is_empty() {
+            self.clients.remove(&msg.client_id);
+        } else {
+            self.clients.insert(
+                msg.client_id,
+                ClientQuality {
+                    addr: msg.addr,
+                    patterns: msg.patterns,
+                },
+            );
+        }
+    }
+}
+
+impl Handler<GetQualityStats> for DataQualityActor {
+    type Result = MessageResult<GetQualityStats>;
+
+    fn handle(&mut self, msg: GetQualityStats, _: &mut Self::Context) -> Self::Result {
+        let mut report: Vec<InstrumentQuality> = self
+            .instruments
+            .iter()
+            .filter(|(instrument, _)| {
+                msg.instrument.as_ref().is_none_or(|wanted| wanted == *instrument)
+            })
+            .map(|(instrument, state)| InstrumentQuality {
+                instrument_id: instrument.clone(),
+                issues: state.issues.clone(),
+                stale: state.stale,
+                crossed: state.crossed,
+                last_issue: state.last_issue.clone(),
+            })
+            .collect();
+        report.sort_by(|a, b| {
+            b.issues
+                .total()
+                .cmp(&a.issues.total())
+                .then_with(|| a.instrument_id.cmp(&b.instrument_id))
+        });
+        MessageResult(report)
+    }
+}
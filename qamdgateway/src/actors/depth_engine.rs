@@ -0,0 +1,182 @@
+use actix::prelude::*;
+use hashbrown::HashMap;
+use tracing::{debug, info};
+use qamd_rs::OrderBook;
+
+use crate::actors::messages::*;
+use crate::instruments::matches_pattern;
+
+/// 单个客户端的盘口订阅
+struct ClientDepth {
+    addr: Recipient<DepthUpdate>,
+    instruments: Vec<String>,
+}
+
+impl ClientDepth {
+    fn watches(&self, instrument_id: &str) -> bool {
+        self.instruments
+            .iter()
+            .any(|instrument| matches_pattern(instrument, instrument_id))
+    }
+}
+
+/// 合约的盘口及最近一帧的序号
+struct DepthBook {
+    book: OrderBook,
+    seq: u64,
+}
+
+impl DepthBook {
+    fn snapshot(&self) -> DepthFrame {
+        DepthFrame::Snapshot {
+            seq: self.seq,
+            book: self.book.clone(),
+        }
+    }
+}
+
+/// 盘口增量Actor
+///
+/// 作为行情输出注册到分发器，为有客户端订阅的合约维护盘口，
+/// 将相邻两笔行情的盘口差异（价位的新增、修改和删除）带序号推送给订阅的客户端会话；
+/// 新订阅和请求重新同步的客户端先收到全量帧。适用于带多档行情的数据源，
+/// 只有一档行情的数据源只推送买一卖一的变化
+pub struct DepthEngine {
+    // 客户端ID -> 盘口订阅
+    clients: HashMap<String, ClientDepth>,
+    // 合约ID -> 盘口（只保留有订阅的合约）
+    books: HashMap<String, DepthBook>,
+}
+
+impl Actor for DepthEngine {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        info!("Depth engine started");
+    }
+}
+
+impl Default for DepthEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DepthEngine {
+    /// 创建盘口增量Actor
+    pub fn new() -> Self {
+        Self {
+            clients: HashMap::new(),
+            books: HashMap::new(),
+        }
+    }
+
+    /// 是否有客户端订阅该合约
+    fn is_watched(&self, instrument_id: &str) -> bool {
+        self.clients.values().any(|client| client.watches(instrument_id))
+    }
+
+    /// 向客户端发送其订阅的合约中满足条件的全量帧
+    fn send_snapshots<F>(&self, client: &ClientDepth, filter: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        let frames: Vec<DepthFrame> = self
+            .books
+            .iter()
+            .filter(|(instrument_id, _)| client.watches(instrument_id) && filter(instrument_id))
+            .map(|(_, book)| book.snapshot())
+            .collect();
+        if !frames.is_empty() {
+            client.addr.do_send(DepthUpdate { frames });
+        }
+    }
+}
+
+impl Handler<SubscribeDepth> for DepthEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeDepth, _: &mut Self::Context) -> Self::Result {
+        debug!("Client {} depth subscriptions: {:?}", msg.client_id, msg.instruments);
+        if msg.instruments.is_empty() {
+            self.clients.remove(&msg.client_id);
+        } else {
+            let client = ClientDepth {
+                addr: msg.addr,
+                instruments: msg.instruments,
+            };
+            // 新订阅的合约先发送全量帧，之前已订阅的合约继续推送增量
+            let previous = self.clients.get(&msg.client_id);
+            self.send_snapshots(&client, |instrument_id| {
+                !previous.is_some_and(|previous| previous.watches(instrument_id))
+            });
+            self.clients.insert(msg.client_id, client);
+        }
+
+        // 删除不再被订阅的合约的盘口
+        let watched: Vec<String> = self
+            .books
+            .keys()
+            .filter(|instrument| self.is_watched(instrument))
+            .cloned()
+            .collect();
+        self.books.retain(|instrument, _| watched.contains(instrument));
+    }
+}
+
+impl Handler<ResyncDepth> for DepthEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: ResyncDepth, _: &mut Self::Context) -> Self::Result {
+        let Some(client) = self.clients.get(&msg.client_id) else {
+            return;
+        };
+        debug!("Client {} requested depth resync of {:?}", msg.client_id, msg.instruments);
+        self.send_snapshots(client, |instrument_id| {
+            msg.instruments.is_empty() || msg.instruments.iter().any(|instrument| instrument == instrument_id)
+        });
+    }
+}
+
+impl Handler<MarketDataUpdate> for DepthEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let snapshot = msg.0;
+        if !self.is_watched(&snapshot.instrument_id) {
+            return;
+        }
+
+        let next = OrderBook::from_snapshot(&snapshot);
+        let frame = match self.books.get_mut(snapshot.instrument_id.as_str()) {
+            Some(depth) => {
+                let changes = depth.book.diff(&next);
+                if changes.is_empty() {
+                    return;
+                }
+                depth.seq += 1;
+                depth.book = next;
+                DepthFrame::Update {
+                    instrument_id: snapshot.instrument_id.clone(),
+                    seq: depth.seq,
+                    datetime: depth.book.datetime,
+                    changes,
+                }
+            }
+            None => {
+                let depth = DepthBook { book: next, seq: 1 };
+                let frame = depth.snapshot();
+                self.books.insert(snapshot.instrument_id.clone(), depth);
+                frame
+            }
+        };
+
+        for client in self.clients.values() {
+            if client.watches(&snapshot.instrument_id) {
+                client.addr.do_send(DepthUpdate {
+                    frames: vec![frame.clone()],
+                });
+            }
+        }
+    }
+}
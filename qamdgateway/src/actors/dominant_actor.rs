@@ -0,0 +1,215 @@
+use actix::prelude::*;
+use hashbrown::{HashMap, HashSet};
+use tracing::{debug, info, warn};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::config::DominantConfig;
+use crate::dominant::{contract_matches, parse_dominant, DominantProduct, DominantTracker};
+use crate::instruments::InstrumentRegistry;
+
+/// 单个主力合约的映射状态
+struct DominantDefinition {
+    tracker: DominantTracker,
+    // 定义时间，预热期过后即使部分合约月份没有行情也选择主力合约
+    defined_at: Instant,
+}
+
+/// 主力合约映射Actor
+///
+/// 以普通客户端的身份向分发器订阅品种的各合约月份，同时注册为行情输出以接收完整的MDSnapshot。
+/// 按持仓量选出主力合约后，把当前主力合约月份的行情改为主力合约代码发回分发器；
+/// 主力合约切换时通知分发器，由分发器告知订阅了该主力合约的客户端。
+pub struct DominantActor {
+    // 在分发器中的客户端ID
+    client_id: String,
+    distributor: Addr<MarketDataDistributor>,
+    registry: Arc<InstrumentRegistry>,
+    config: DominantConfig,
+    // 主力合约代码 -> 映射状态
+    definitions: HashMap<String, DominantDefinition>,
+    // 各合约月份最新行情（合约月份 -> 行情及数据源）
+    quotes: HashMap<String, (Arc<qamd_rs::MDSnapshot>, MarketDataSource)>,
+}
+
+impl Actor for DominantActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Dominant contract engine started");
+
+        let addr = ctx.address();
+        self.distributor.do_send(RegisterDataReceiver {
+            client_id: self.client_id.clone(),
+            addr: addr.clone().recipient(),
+            instruments: Vec::new(),
+            notify: None,
+            remap: None,
+            disconnect: None,
+        });
+        self.distributor.do_send(RegisterSnapshotSink {
+            name: "dominant".to_string(),
+            addr: addr.clone().recipient(),
+        });
+        self.distributor.do_send(RegisterDominantEngine { addr });
+    }
+}
+
+impl DominantActor {
+    /// 创建主力合约映射Actor
+    pub fn new(
+        distributor: Addr<MarketDataDistributor>,
+        registry: Arc<InstrumentRegistry>,
+        config: DominantConfig,
+    ) -> Self {
+        Self {
+            client_id: format!("dominant-{}", uuid::Uuid::new_v4()),
+            distributor,
+            registry,
+            config,
+            definitions: HashMap::new(),
+            quotes: HashMap::new(),
+        }
+    }
+
+    /// 品种的合约月份，按交割先后排列：优先使用配置，否则取合约信息中该品种的期货合约
+    fn contracts_for(&self, product: &DominantProduct) -> Vec<String> {
+        if let Some(contracts) = self.config.contracts.get(&product.key()) {
+            return contracts.clone();
+        }
+        let mut instruments: Vec<_> = self.registry.iter().filter(|info| product.matches(info)).collect();
+        instruments.sort_by(|a, b| {
+            a.expire_date
+                .cmp(&b.expire_date)
+                .then_with(|| a.instrument_id.cmp(&b.instrument_id))
+        });
+        instruments
+            .into_iter()
+            .map(|info| format!("{}.{}", info.exchange_id, info.instrument_id))
+            .collect()
+    }
+
+    /// 向分发器更新合约月份的订阅，并清理不再使用的行情
+    fn update_contract_subscription(&mut self) {
+        let contracts: HashSet<String> = self
+            .definitions
+            .values()
+            .flat_map(|definition| definition.tracker.contracts().iter().cloned())
+            .collect();
+        self.quotes.retain(|contract, _| contracts.contains(contract));
+        self.distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: contracts.into_iter().collect(),
+        });
+    }
+
+    /// 把合约月份的行情以主力合约代码发回分发器
+    fn publish(&self, instrument: &str, snapshot: &qamd_rs::MDSnapshot, source: MarketDataSource) {
+        let mut snapshot = snapshot.clone();
+        snapshot.instrument_id = instrument.to_string();
+        self.distributor.do_send(MarketDataUpdate(Arc::new(snapshot), source));
+    }
+}
+
+impl Handler<DefineDominant> for DominantActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: DefineDominant, _: &mut Self::Context) -> Self::Result {
+        if self.definitions.contains_key(&msg.instrument) {
+            return;
+        }
+        let Some(product) = parse_dominant(&msg.instrument) else {
+            warn!("Invalid dominant contract {}", msg.instrument);
+            return;
+        };
+        let contracts = self.contracts_for(&product);
+        if contracts.is_empty() {
+            warn!("No contract months known for dominant contract {}", msg.instrument);
+            return;
+        }
+        info!("Defined dominant contract {} over {:?}", msg.instrument, contracts);
+        self.definitions.insert(
+            msg.instrument,
+            DominantDefinition {
+                tracker: DominantTracker::new(contracts, self.config.switch_ratio),
+                defined_at: Instant::now(),
+            },
+        );
+        self.update_contract_subscription();
+    }
+}
+
+impl Handler<RemoveDominant> for DominantActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RemoveDominant, _: &mut Self::Context) -> Self::Result {
+        if self.definitions.remove(&msg.instrument).is_some() {
+            info!("Removed dominant contract {}", msg.instrument);
+            self.update_contract_subscription();
+        }
+    }
+}
+
+impl Handler<MarketDataUpdate> for DominantActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let (snapshot, source) = (msg.0, msg.1);
+        let warmup = Duration::from_secs(self.config.warmup_secs);
+
+        let mut remapped = Vec::new();
+        let mut current = Vec::new();
+        for (instrument, definition) in self.definitions.iter_mut() {
+            // 找到该行情对应的合约月份（配置的月份可能不带交易所前缀）
+            let Some(contract) = definition
+                .tracker
+                .contracts()
+                .iter()
+                .find(|contract| contract_matches(contract, &snapshot.instrument_id))
+                .cloned()
+            else {
+                continue;
+            };
+            self.quotes.insert(contract.clone(), (snapshot.clone(), source));
+            if let Some(open_interest) = snapshot.open_interest.as_option() {
+                definition.tracker.update(&contract, open_interest);
+            }
+
+            let tracker = &mut definition.tracker;
+            if !tracker.is_complete() && definition.defined_at.elapsed() < warmup {
+                continue;
+            }
+            let from = tracker.current().map(str::to_string);
+            if let Some(to) = tracker.select().map(str::to_string) {
+                remapped.push((instrument.clone(), from, to));
+            } else if tracker.current() == Some(contract.as_str()) {
+                current.push(instrument.clone());
+            }
+        }
+
+        for instrument in current {
+            self.publish(&instrument, &snapshot, source);
+        }
+        for (instrument, from, to) in remapped {
+            info!("Dominant contract {} switched from {:?} to {}", instrument, from, to);
+            self.distributor.do_send(ContractRemapped {
+                instrument: instrument.clone(),
+                from,
+                to: to.clone(),
+            });
+            match self.quotes.get(&to) {
+                Some((quote, source)) => self.publish(&instrument, quote, *source),
+                None => debug!("No quote yet for {} mapped by {}", to, instrument),
+            }
+        }
+    }
+}
+
+/// 合约月份的行情通过行情输出接收，客户端通道推送的增量JSON不需要处理
+impl Handler<MarketDataUpdateMessage> for DominantActor {
+    type Result = ();
+
+    fn handle(&mut self, _: MarketDataUpdateMessage, _: &mut Self::Context) -> Self::Result {}
+}
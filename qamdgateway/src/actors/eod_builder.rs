@@ -0,0 +1,363 @@
+use actix::prelude::*;
+use chrono::{NaiveDate, NaiveTime};
+use hashbrown::HashMap;
+use tracing::{error, info, warn};
+use qamd_rs::{DailyBar, InstrumentType, MDSnapshot};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::actors::messages::*;
+use crate::actors::tick_recorder::TickRecorderActor;
+use crate::calendar::{china_offset, TradingCalendar, FUTURES_EXCHANGES};
+use crate::config::EodConfig;
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::{InstrumentInfo, InstrumentRegistry};
+
+// 检查是否到达生成时间的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 收盘后日线生成Actor
+///
+/// 每个交易日到达配置的时间后，从日内行情记录中取出当天的行情，
+/// 按合约汇总为日线，写入QALfs目录：期货、期权写入`futureday/future_day_{date}.pq`，
+/// 股票、基金写入`bfqdata/stock_day_bfq_{date}.pq`。
+pub struct EodBarBuilder {
+    base: PathBuf,
+    time: NaiveTime,
+    calendar: Arc<TradingCalendar>,
+    instruments: Arc<InstrumentRegistry>,
+    recorder: Addr<TickRecorderActor>,
+    // 最近一次生成日线的交易日
+    last_run: Option<NaiveDate>,
+}
+
+impl Actor for EodBarBuilder {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // 收盘后才启动时记录的行情不完整，不覆盖当天已有的日线
+        let now = TradingCalendar::now_local();
+        if now.time() >= self.time {
+            self.last_run = Some(now.date());
+        }
+        info!(
+            "EOD bar builder started, building daily bars at {} into {}",
+            self.time.format("%H:%M"),
+            self.base.display()
+        );
+        ctx.run_interval(CHECK_INTERVAL, |act, ctx| act.check(ctx));
+    }
+}
+
+impl EodBarBuilder {
+    pub fn new(
+        config: &EodConfig,
+        calendar: Arc<TradingCalendar>,
+        instruments: Arc<InstrumentRegistry>,
+        recorder: Addr<TickRecorderActor>,
+    ) -> GatewayResult<Self> {
+        let time = NaiveTime::parse_from_str(&config.time, "%H:%M").map_err(|e| {
+            GatewayError::ConfigError(format!("Invalid EOD time {}: {}", config.time, e))
+        })?;
+        Ok(Self {
+            base: PathBuf::from(&config.path),
+            time,
+            calendar,
+            instruments,
+            recorder,
+            last_run: None,
+        })
+    }
+
+    /// 交易日到达生成时间后生成当天的日线
+    fn check(&mut self, ctx: &mut Context<Self>) {
+        let now = TradingCalendar::now_local();
+        let today = now.date();
+        if now.time() < self.time || self.last_run == Some(today) || !self.calendar.is_trading_day(today) {
+            return;
+        }
+        self.last_run = Some(today);
+        ctx.notify(BuildDailyBars { trading_day: today });
+    }
+}
+
+impl Handler<BuildDailyBars> for EodBarBuilder {
+    type Result = ResponseActFuture<Self, GatewayResult<usize>>;
+
+    fn handle(&mut self, msg: BuildDailyBars, _: &mut Self::Context) -> Self::Result {
+        let trading_day = msg.trading_day;
+        Box::pin(
+            self.recorder
+                .send(GetRecordedTicks {
+                    instrument: "*".to_string(),
+                    from: None,
+                    to: None,
+                })
+                .into_actor(self)
+                .map(move |result, act, _| {
+                    let ticks = result.map_err(|e| GatewayError::Other(format!("Tick recorder unavailable: {}", e)))?;
+                    let bars = act.build_bars(trading_day, ticks);
+                    let written = write_daily_bars(&act.base, trading_day, &bars);
+                    match &written {
+                        Ok(count) => info!("Wrote {} daily bars for {}", count, trading_day),
+                        Err(e) => error!("Failed to write daily bars for {}: {}", trading_day, e),
+                    }
+                    written
+                }),
+        )
+    }
+}
+
+impl EodBarBuilder {
+    /// 按合约汇总交易日的行情（记录的行情已按时间排序）
+    fn build_bars(&self, trading_day: NaiveDate, ticks: Vec<MDSnapshot>) -> Vec<DailyBar> {
+        let offset = china_offset();
+        let mut by_instrument: HashMap<String, Vec<MDSnapshot>> = HashMap::new();
+        for tick in ticks {
+            let local = tick.datetime.with_timezone(&offset).naive_local();
+            if self.calendar.trading_day(local) != trading_day {
+                continue;
+            }
+            by_instrument.entry_ref(tick.instrument_id.as_str()).or_default().push(tick);
+        }
+
+        let mut bars: Vec<DailyBar> = by_instrument
+            .iter()
+            .filter_map(|(instrument, ticks)| {
+                build_daily_bar(trading_day, ticks, self.instruments.get(instrument))
+            })
+            .collect();
+        bars.sort_by(|a, b| a.order_book_id.cmp(&b.order_book_id));
+        bars
+    }
+}
+
+/// 合约的品种类型（期权归为Other）
+fn instrument_type(snapshot: &MDSnapshot, info: Option<&InstrumentInfo>) -> InstrumentType {
+    match info.map(|info| info.product_class.as_str()) {
+        Some("FUTURE") => InstrumentType::Future,
+        Some("OPTION") => InstrumentType::Other,
+        Some("ETF") | Some("FUND") | Some("LOF") => InstrumentType::Fund,
+        Some("INDEX") => InstrumentType::Index,
+        Some("STOCK") => InstrumentType::Stock,
+        _ => {
+            let exchange = snapshot.instrument_id.split_once('.').map(|(exchange, _)| exchange);
+            if exchange.is_some_and(|exchange| FUTURES_EXCHANGES.contains(&exchange)) {
+                InstrumentType::Future
+            } else if snapshot.iopv.is_value() {
+                InstrumentType::Fund
+            } else {
+                InstrumentType::Stock
+            }
+        }
+    }
+}
+
+/// 是否为有结算价的期货、期权
+fn is_derivative(instrument_type: InstrumentType) -> bool {
+    matches!(instrument_type, InstrumentType::Future | InstrumentType::Other)
+}
+
+/// 由一个合约一个交易日的行情生成日线
+///
+/// 交易所推送的开高低、成交量和成交额都是当日累计值，以最后一笔为准，
+/// 开高低同时参考逐笔最新价以防个别行情缺少这些字段。
+/// 期货、期权优先使用交易所公布的结算价，尚未公布时按当日成交均价估算（按最小变动价位取整），
+/// 缺少合约乘数时不填写结算价。
+pub fn build_daily_bar(
+    trading_day: NaiveDate,
+    ticks: &[MDSnapshot],
+    info: Option<&InstrumentInfo>,
+) -> Option<DailyBar> {
+    let valid = |price: &f64| *price > 0.0 && price.is_finite();
+    let ticks: Vec<&MDSnapshot> = ticks.iter().filter(|tick| valid(&tick.last_price)).collect();
+    let (first, last) = (ticks.first()?, ticks.last()?);
+    let instrument_type = instrument_type(last, info);
+    let derivative = is_derivative(instrument_type);
+
+    let open = ticks.iter().map(|tick| tick.open).find(valid).unwrap_or(first.last_price);
+    let high = ticks
+        .iter()
+        .flat_map(|tick| [tick.highest, tick.last_price])
+        .filter(valid)
+        .fold(f64::MIN, f64::max);
+    let low = ticks
+        .iter()
+        .flat_map(|tick| [tick.lowest, tick.last_price])
+        .filter(valid)
+        .fold(f64::MAX, f64::min);
+    // 期货收盘后推送的收盘价与最新价可能不同
+    let close = if derivative {
+        last.close.as_f64_opt().filter(valid).unwrap_or(last.last_price)
+    } else {
+        last.last_price
+    };
+
+    let mut bar = DailyBar::new(
+        trading_day,
+        last.instrument_id.clone(),
+        instrument_type,
+        open as f32,
+        high as f32,
+        low as f32,
+        close as f32,
+        last.volume as f32,
+        last.amount as f32,
+    );
+    bar.limit_up = Some(last.upper_limit).filter(valid).map(|price| price as f32);
+    bar.limit_down = Some(last.lower_limit).filter(valid).map(|price| price as f32);
+
+    if derivative {
+        bar.open_interest = last.open_interest.as_f64_opt().map(|value| value as f32);
+        bar.prev_settlement = last.pre_settlement.as_f64_opt().filter(valid).map(|price| price as f32);
+        bar.settlement = last
+            .settlement
+            .as_f64_opt()
+            .filter(valid)
+            .or_else(|| estimated_settlement(last, info?))
+            .map(|price| price as f32);
+    } else if instrument_type == InstrumentType::Fund {
+        bar.iopv = last.iopv.as_f64_opt().map(|value| value as f32);
+    }
+    Some(bar)
+}
+
+/// 按当日成交均价估算结算价
+fn estimated_settlement(last: &MDSnapshot, info: &InstrumentInfo) -> Option<f64> {
+    if last.volume <= 0 || info.volume_multiple <= 0 || last.amount <= 0.0 {
+        return None;
+    }
+    let vwap = last.amount / (last.volume as f64 * info.volume_multiple as f64);
+    if info.price_tick > 0.0 {
+        Some((vwap / info.price_tick).round() * info.price_tick)
+    } else {
+        Some(vwap)
+    }
+}
+
+/// 日线写入QALfs目录，返回写入的日线数
+pub fn write_daily_bars(base: &Path, trading_day: NaiveDate, bars: &[DailyBar]) -> GatewayResult<usize> {
+    let (derivatives, others): (Vec<DailyBar>, Vec<DailyBar>) = bars
+        .iter()
+        .cloned()
+        .partition(|bar| is_derivative(bar.instrument_type));
+    let date = trading_day.format("%Y-%m-%d");
+    let files = [
+        (base.join("futureday").join(format!("future_day_{}.pq", date)), derivatives),
+        (base.join("bfqdata").join(format!("stock_day_bfq_{}.pq", date)), others),
+    ];
+    let mut written = 0;
+    for (path, bars) in files {
+        if bars.is_empty() {
+            continue;
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_parquet(&path, &bars)?;
+        written += bars.len();
+    }
+    if written == 0 {
+        warn!("No recorded ticks for trading day {}", trading_day);
+    }
+    Ok(written)
+}
+
+/// 日线的Parquet结构（列名与QALfs中的日线数据一致）
+#[cfg(feature = "eod-parquet")]
+const DAILY_BAR_SCHEMA: &str = "
+message daily_bar {
+    REQUIRED INT32 date (DATE);
+    REQUIRED BYTE_ARRAY order_book_id (UTF8);
+    REQUIRED FLOAT open;
+    REQUIRED FLOAT high;
+    REQUIRED FLOAT low;
+    REQUIRED FLOAT close;
+    REQUIRED FLOAT volume;
+    REQUIRED FLOAT total_turnover;
+    OPTIONAL FLOAT num_trades;
+    OPTIONAL FLOAT limit_up;
+    OPTIONAL FLOAT limit_down;
+    OPTIONAL FLOAT open_interest;
+    OPTIONAL FLOAT prev_settlement;
+    OPTIONAL FLOAT settlement;
+    OPTIONAL FLOAT iopv;
+}
+";
+
+/// 写入Parquet文件（先写临时文件再改名，读取方不会看到写了一半的文件）
+#[cfg(feature = "eod-parquet")]
+fn write_parquet(path: &Path, bars: &[DailyBar]) -> GatewayResult<()> {
+    use parquet::basic::Compression;
+    use parquet::data_type::{ByteArray, ByteArrayType, FloatType, Int32Type};
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    let parquet_error = |e: ParquetError| GatewayError::Other(format!("Parquet error: {}", e));
+    let required: [fn(&DailyBar) -> f32; 6] = [
+        |bar| bar.open,
+        |bar| bar.high,
+        |bar| bar.low,
+        |bar| bar.close,
+        |bar| bar.volume,
+        |bar| bar.total_turnover,
+    ];
+    let optional: [fn(&DailyBar) -> Option<f32>; 7] = [
+        |bar| bar.num_trades,
+        |bar| bar.limit_up,
+        |bar| bar.limit_down,
+        |bar| bar.open_interest,
+        |bar| bar.prev_settlement,
+        |bar| bar.settlement,
+        |bar| bar.iopv,
+    ];
+
+    let schema = Arc::new(parse_message_type(DAILY_BAR_SCHEMA).map_err(parquet_error)?);
+    let properties = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+    let temp = path.with_extension("pq.tmp");
+    let mut writer = SerializedFileWriter::new(fs::File::create(&temp)?, schema, properties).map_err(parquet_error)?;
+    let mut row_group = writer.next_row_group().map_err(parquet_error)?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column().map_err(parquet_error)? {
+        match index {
+            0 => {
+                let epoch = NaiveDate::default();
+                let dates: Vec<i32> = bars.iter().map(|bar| (bar.date - epoch).num_days() as i32).collect();
+                column.typed::<Int32Type>().write_batch(&dates, None, None)
+            }
+            1 => {
+                let ids: Vec<ByteArray> = bars.iter().map(|bar| bar.order_book_id.as_str().into()).collect();
+                column.typed::<ByteArrayType>().write_batch(&ids, None, None)
+            }
+            2..=7 => {
+                let values: Vec<f32> = bars.iter().map(required[index - 2]).collect();
+                column.typed::<FloatType>().write_batch(&values, None, None)
+            }
+            _ => {
+                let values: Vec<Option<f32>> = bars.iter().map(optional[index - 8]).collect();
+                let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+                let present: Vec<f32> = values.into_iter().flatten().collect();
+                column.typed::<FloatType>().write_batch(&present, Some(&levels), None)
+            }
+        }
+        .map_err(parquet_error)?;
+        column.close().map_err(parquet_error)?;
+        index += 1;
+    }
+    row_group.close().map_err(parquet_error)?;
+    writer.close().map_err(parquet_error)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "eod-parquet"))]
+fn write_parquet(path: &Path, _bars: &[DailyBar]) -> GatewayResult<()> {
+    Err(GatewayError::Other(format!(
+        "Writing daily bars requires the `eod-parquet` feature: {}",
+        path.display()
+    )))
+}
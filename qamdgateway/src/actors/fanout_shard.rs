@@ -0,0 +1,274 @@
+use actix::prelude::*;
+use hashbrown::{HashMap, HashSet};
+use tracing::error;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::actors::md_distributor::project_delta;
+use crate::actors::messages::*;
+use crate::supervision;
+
+/// 合约所属的分片
+pub fn shard_index(instrument: &str, shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    instrument.hash(&mut hasher);
+    (hasher.finish() % shards.max(1) as u64) as usize
+}
+
+/// 分片上的客户端
+struct ShardClient {
+    addr: Recipient<MarketDataUpdateMessage>,
+    settings: ShardClientSettings,
+    // 在本分片订阅的合约
+    instruments: HashSet<String>,
+}
+
+/// 行情分发分片
+///
+/// 分发器按合约哈希把客户端订阅登记到负责该合约的分片，
+/// 每批增量行情构建一次后交给分片，由分片按客户端裁剪字段并推送（未裁剪字段的客户端共用编码结果）。
+/// 各分片运行在独立的Arbiter上，向大量客户端推送的开销因此分摊到多个线程。
+#[derive(Default)]
+pub struct FanoutShard {
+    clients: HashMap<String, ShardClient>,
+    // 合约 -> 订阅的客户端
+    instrument_clients: HashMap<String, HashSet<String>>,
+}
+
+impl Actor for FanoutShard {
+    type Context = Context<Self>;
+}
+
+impl FanoutShard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 移除客户端在本分片的订阅
+    fn release(&mut self, client_id: &str, instruments: impl IntoIterator<Item = String>) {
+        for instrument in instruments {
+            if let Some(clients) = self.instrument_clients.get_mut(&instrument) {
+                clients.remove(client_id);
+                if clients.is_empty() {
+                    self.instrument_clients.remove(&instrument);
+                }
+            }
+        }
+    }
+}
+
+impl Handler<ShardSubscribe> for FanoutShard {
+    type Result = ();
+
+    fn handle(&mut self, msg: ShardSubscribe, _: &mut Self::Context) -> Self::Result {
+        let client = self
+            .clients
+            .entry(msg.client_id.clone())
+            .or_insert_with(|| ShardClient {
+                addr: msg.addr.clone(),
+                settings: ShardClientSettings::default(),
+                instruments: HashSet::new(),
+            });
+        client.addr = msg.addr;
+        client.settings = msg.settings;
+        for instrument in msg.instruments {
+            self.instrument_clients
+                .entry(instrument.clone())
+                .or_default()
+                .insert(msg.client_id.clone());
+            client.instruments.insert(instrument);
+        }
+    }
+}
+
+impl Handler<ShardUnsubscribe> for FanoutShard {
+    type Result = ();
+
+    fn handle(&mut self, msg: ShardUnsubscribe, _: &mut Self::Context) -> Self::Result {
+        let Some(client) = self.clients.get_mut(&msg.client_id) else {
+            return;
+        };
+        for instrument in &msg.instruments {
+            client.instruments.remove(instrument);
+        }
+        if client.instruments.is_empty() {
+            self.clients.remove(&msg.client_id);
+        }
+        self.release(&msg.client_id, msg.instruments);
+    }
+}
+
+impl Handler<ShardUnregister> for FanoutShard {
+    type Result = ();
+
+    fn handle(&mut self, msg: ShardUnregister, _: &mut Self::Context) -> Self::Result {
+        if let Some(client) = self.clients.remove(&msg.client_id) {
+            self.release(&msg.client_id, client.instruments);
+        }
+    }
+}
+
+impl Handler<ShardUpdateClient> for FanoutShard {
+    type Result = ();
+
+    fn handle(&mut self, msg: ShardUpdateClient, _: &mut Self::Context) -> Self::Result {
+        if let Some(client) = self.clients.get_mut(&msg.client_id) {
+            client.settings = msg.settings;
+        }
+    }
+}
+
+impl Handler<ShardFanout> for FanoutShard {
+    type Result = ();
+
+    fn handle(&mut self, msg: ShardFanout, _: &mut Self::Context) -> Self::Result {
+        // panic时只丢弃这一批，分片继续运行
+        let _ = supervision::catch_panic("fanout_shard", None, || self.fanout(msg));
+    }
+}
+
+impl FanoutShard {
+    /// 把一批增量行情推送给订阅的客户端
+    fn fanout(&self, msg: ShardFanout) {
+        // 按客户端合并本批次的合约，每个客户端只发送一条消息
+        let mut client_updates: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, (instrument, _)) in msg.updates.iter().enumerate() {
+            let Some(clients) = self.instrument_clients.get(instrument) else {
+                continue;
+            };
+            for client_id in clients {
+                client_updates.entry(client_id.as_str()).or_default().push(index);
+            }
+        }
+
+        for (client_id, indices) in client_updates {
+            let Some(client) = self.clients.get(client_id) else {
+                continue;
+            };
+            if client.settings.deferred {
+                continue;
+            }
+            let mut data = HashMap::new();
+            let mut instruments = Vec::new();
+            for index in indices {
+                let (instrument, delta) = &msg.updates[index];
+                if let Some(payload) = project_delta(client.settings.fields.as_ref(), delta) {
+                    data.insert(instrument.clone(), payload);
+                    instruments.push(instrument.clone());
+                }
+            }
+            if instruments.is_empty() {
+                continue;
+            }
+            if let Err(e) = client.addr.try_send(MarketDataUpdateMessage { instruments, data }) {
+                error!("Failed to send batch update to client {}: {}", client_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quote_encoding::EncodedQuote;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    const REBAR: &str = "SHFE.rb2410";
+    const GOLD: &str = "SHFE.au2412";
+
+    /// 记录收到的行情的客户端
+    struct Recorder(Arc<Mutex<Vec<MarketDataUpdateMessage>>>);
+
+    impl Actor for Recorder {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<MarketDataUpdateMessage> for Recorder {
+        type Result = ();
+
+        fn handle(&mut self, msg: MarketDataUpdateMessage, _: &mut Self::Context) -> Self::Result {
+            self.0.lock().unwrap().push(msg);
+        }
+    }
+
+    fn subscribe(
+        shard: &mut FanoutShard,
+        addr: Recipient<MarketDataUpdateMessage>,
+        settings: ShardClientSettings,
+        instruments: &[&str],
+    ) {
+        let msg = ShardSubscribe {
+            client_id: "client".to_string(),
+            addr,
+            settings,
+            instruments: instruments.iter().map(|instrument| instrument.to_string()).collect(),
+        };
+        shard.handle(msg, &mut Context::new());
+    }
+
+    fn delta(instrument: &str, fields: serde_json::Value) -> (String, Arc<EncodedQuote>) {
+        (instrument.to_string(), Arc::new(EncodedQuote::new(fields)))
+    }
+
+    /// 推送一批行情并取回客户端收到的消息
+    async fn fan_out(shard: &FanoutShard, received: &Mutex<Vec<MarketDataUpdateMessage>>) -> Vec<MarketDataUpdateMessage> {
+        let updates = vec![
+            delta(REBAR, json!({"instrument_id": REBAR, "last_price": 3500.0})),
+            delta(GOLD, json!({"instrument_id": GOLD, "volume": 12})),
+        ];
+        shard.fanout(ShardFanout { updates });
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+        std::mem::take(&mut *received.lock().unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn fanout_reaches_subscribed_instruments_only() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut shard = FanoutShard::new();
+        subscribe(&mut shard, Recorder(received.clone()).start().recipient(), ShardClientSettings::default(), &[REBAR]);
+
+        let messages = fan_out(&shard, &received).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].instruments, vec![REBAR.to_string()]);
+    }
+
+    #[actix_rt::test]
+    async fn unsubscribe_releases_client() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut shard = FanoutShard::new();
+        subscribe(&mut shard, Recorder(received.clone()).start().recipient(), ShardClientSettings::default(), &[REBAR, GOLD]);
+        assert_eq!(fan_out(&shard, &received).await[0].instruments.len(), 2);
+
+        let unsubscribe = |instrument: &str| ShardUnsubscribe {
+            client_id: "client".to_string(),
+            instruments: vec![instrument.to_string()],
+        };
+        shard.handle(unsubscribe(REBAR), &mut Context::new());
+        assert_eq!(fan_out(&shard, &received).await[0].instruments, vec![GOLD.to_string()]);
+
+        // 最后一个合约退订后客户端从分片移除
+        shard.handle(unsubscribe(GOLD), &mut Context::new());
+        assert!(shard.clients.is_empty());
+        assert!(shard.instrument_clients.is_empty());
+        assert!(fan_out(&shard, &received).await.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn fanout_projects_client_fields() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut shard = FanoutShard::new();
+        let settings = ShardClientSettings {
+            fields: Some(["last_price".to_string()].into_iter().collect()),
+            deferred: false,
+        };
+        subscribe(&mut shard, Recorder(received.clone()).start().recipient(), settings, &[REBAR, GOLD]);
+
+        // GOLD只有客户端不关注的字段变化，不推送
+        let messages = fan_out(&shard, &received).await;
+        assert_eq!(messages[0].instruments, vec![REBAR.to_string()]);
+        let fields = messages[0].data[REBAR].fields();
+        assert_eq!(fields.keys().collect::<Vec<_>>(), vec!["instrument_id", "last_price"]);
+    }
+}
@@ -0,0 +1,154 @@
+use actix::prelude::*;
+use hashbrown::HashMap;
+use tracing::{debug, info};
+
+use crate::actors::messages::*;
+use crate::analytics::{InstrumentAnalytics, KlineBuilder};
+use crate::instruments::matches_pattern;
+
+/// 单个客户端的指标或K线订阅
+struct ClientSubscription<M: Message<Result = ()> + Send> {
+    addr: Recipient<M>,
+    instruments: Vec<String>,
+}
+
+impl<M: Message<Result = ()> + Send> ClientSubscription<M> {
+    /// 是否订阅了该合约（不带交易所前缀的订阅匹配任意交易所）
+    fn watches(&self, instrument_id: &str) -> bool {
+        self.instruments.iter().any(|instrument| matches_pattern(instrument, instrument_id))
+    }
+}
+
+/// 衍生指标Actor
+///
+/// 作为行情输出注册到分发器，为有客户端订阅的合约逐笔计算订单流不平衡、
+/// microprice、滚动VWAP和已实现波动率，以及合成1分钟K线，推送给订阅的客户端会话
+pub struct IndicatorEngine {
+    // 客户端ID -> 指标订阅
+    clients: HashMap<String, ClientSubscription<IndicatorUpdate>>,
+    // 合约ID -> 计算状态（只保留有订阅的合约）
+    analytics: HashMap<String, InstrumentAnalytics>,
+    // 客户端ID -> K线订阅
+    kline_clients: HashMap<String, ClientSubscription<KlineUpdate>>,
+    // 合约ID -> K线合成状态（只保留有订阅的合约）
+    klines: HashMap<String, KlineBuilder>,
+}
+
+impl Actor for IndicatorEngine {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        info!("Indicator engine started");
+    }
+}
+
+impl Default for IndicatorEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndicatorEngine {
+    /// 创建衍生指标Actor
+    pub fn new() -> Self {
+        Self {
+            clients: HashMap::new(),
+            analytics: HashMap::new(),
+            kline_clients: HashMap::new(),
+            klines: HashMap::new(),
+        }
+    }
+
+    /// 是否有客户端订阅该合约的指标
+    fn is_watched(&self, instrument_id: &str) -> bool {
+        self.clients.values().any(|client| client.watches(instrument_id))
+    }
+
+    /// 是否有客户端订阅该合约的K线
+    fn is_kline_watched(&self, instrument_id: &str) -> bool {
+        self.kline_clients.values().any(|client| client.watches(instrument_id))
+    }
+}
+
+impl Handler<SubscribeIndicators> for IndicatorEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeIndicators, _: &mut Self::Context) -> Self::Result {
+        debug!("Client {} indicator subscriptions: {:?}", msg.client_id, msg.instruments);
+        if msg.instruments.is_empty() {
+            self.clients.remove(&msg.client_id);
+        } else {
+            self.clients.insert(
+                msg.client_id,
+                ClientSubscription {
+                    addr: msg.addr,
+                    instruments: msg.instruments,
+                },
+            );
+        }
+
+        // 删除不再被订阅的合约的计算状态
+        let watched: Vec<String> = self
+            .analytics
+            .keys()
+            .filter(|instrument| self.is_watched(instrument))
+            .cloned()
+            .collect();
+        self.analytics.retain(|instrument, _| watched.contains(instrument));
+    }
+}
+
+impl Handler<SubscribeKlines> for IndicatorEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeKlines, _: &mut Self::Context) -> Self::Result {
+        debug!("Client {} kline subscriptions: {:?}", msg.client_id, msg.instruments);
+        if msg.instruments.is_empty() {
+            self.kline_clients.remove(&msg.client_id);
+        } else {
+            self.kline_clients.insert(
+                msg.client_id,
+                ClientSubscription {
+                    addr: msg.addr,
+                    instruments: msg.instruments,
+                },
+            );
+        }
+
+        // 删除不再被订阅的合约的K线
+        let watched: Vec<String> = self
+            .klines
+            .keys()
+            .filter(|instrument| self.is_kline_watched(instrument))
+            .cloned()
+            .collect();
+        self.klines.retain(|instrument, _| watched.contains(instrument));
+    }
+}
+
+impl Handler<MarketDataUpdate> for IndicatorEngine {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let snapshot = msg.0;
+        if self.is_kline_watched(&snapshot.instrument_id) {
+            if let Some(kline) = self.klines.entry_ref(snapshot.instrument_id.as_str()).or_default().update(&snapshot) {
+                for client in self.kline_clients.values().filter(|client| client.watches(&snapshot.instrument_id)) {
+                    client.addr.do_send(KlineUpdate(kline.clone()));
+                }
+            }
+        }
+        if !self.is_watched(&snapshot.instrument_id) {
+            return;
+        }
+
+        let indicators = self
+            .analytics
+            .entry_ref(snapshot.instrument_id.as_str())
+            .or_default()
+            .update(&snapshot);
+        for client in self.clients.values().filter(|client| client.watches(&snapshot.instrument_id)) {
+            client.addr.do_send(IndicatorUpdate(indicators.clone()));
+        }
+    }
+}
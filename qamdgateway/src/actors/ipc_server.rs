@@ -0,0 +1,222 @@
+use actix::prelude::*;
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::actors::messages::*;
+use crate::config::IpcConfig;
+
+/// 推送给消费者的一帧：4字节大端长度 + msgpack编码的MDSnapshot
+type Frame = Arc<Vec<u8>>;
+
+/// 本机IPC输出Actor
+///
+/// 在Unix域套接字（Windows上为命名管道）上监听本机消费者，将分发器接受的每条行情
+/// 编码为带长度前缀的msgpack帧推送给所有已连接的消费者，不经过HTTP/WebSocket。
+/// 每个消费者有独立的发送缓冲，缓冲满时丢弃该消费者的行情，不影响其他消费者。
+pub struct IpcServerActor {
+    config: IpcConfig,
+    // 监听任务送来的新连接
+    incoming: mpsc::UnboundedReceiver<mpsc::Sender<Frame>>,
+    incoming_tx: mpsc::UnboundedSender<mpsc::Sender<Frame>>,
+    clients: Vec<IpcClient>,
+    next_client_id: u64,
+    listener: Option<actix_rt::task::JoinHandle<()>>,
+}
+
+/// 已连接的消费者
+struct IpcClient {
+    id: u64,
+    frames: mpsc::Sender<Frame>,
+    // 因缓冲已满丢弃的行情数
+    dropped: u64,
+}
+
+impl Actor for IpcServerActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        match spawn_listener(&self.config, self.incoming_tx.clone()) {
+            Ok(listener) => {
+                info!("IPC transport listening on {}", self.config.path);
+                self.listener = Some(listener);
+            }
+            Err(e) => error!("Failed to listen for IPC consumers on {}: {}", self.config.path, e),
+        }
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        // 套接字文件留给下次启动替换，重新加载配置时新的监听可能已经绑定在同一路径
+        if let Some(listener) = self.listener.take() {
+            listener.abort();
+        }
+        info!("IPC transport on {} stopped", self.config.path);
+    }
+}
+
+impl IpcServerActor {
+    pub fn new(config: IpcConfig) -> Self {
+        let (incoming_tx, incoming) = mpsc::unbounded_channel();
+        Self {
+            config,
+            incoming,
+            incoming_tx,
+            clients: Vec::new(),
+            next_client_id: 1,
+            listener: None,
+        }
+    }
+
+    /// 接收监听任务送来的新连接
+    fn accept_clients(&mut self) {
+        while let Ok(frames) = self.incoming.try_recv() {
+            let id = self.next_client_id;
+            self.next_client_id += 1;
+            info!("IPC consumer {} connected to {}", id, self.config.path);
+            self.clients.push(IpcClient { id, frames, dropped: 0 });
+        }
+    }
+}
+
+impl Handler<MarketDataUpdate> for IpcServerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        self.accept_clients();
+        if self.clients.is_empty() {
+            return;
+        }
+        let snapshot = msg.0;
+        let payload = match rmp_serde::to_vec_named(snapshot.as_ref()) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to encode {} for IPC: {}", snapshot.instrument_id, e);
+                return;
+            }
+        };
+        let mut frame = Vec::with_capacity(4 + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        let frame = Arc::new(frame);
+
+        self.clients.retain_mut(|client| match client.frames.try_send(frame.clone()) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                client.dropped += 1;
+                if client.dropped == 1 || client.dropped % 10_000 == 0 {
+                    warn!("IPC consumer {} is too slow, {} snapshots dropped", client.id, client.dropped);
+                }
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                info!("IPC consumer {} disconnected", client.id);
+                false
+            }
+        });
+    }
+}
+
+/// 向一个消费者写出行情帧，写入失败（消费者断开）时结束
+async fn serve<W: AsyncWrite + Unpin>(mut writer: W, mut frames: mpsc::Receiver<Frame>) {
+    while let Some(frame) = frames.recv().await {
+        if let Err(e) = writer.write_all(&frame).await {
+            debug!("IPC consumer write failed: {}", e);
+            break;
+        }
+    }
+}
+
+/// 新连接的发送缓冲交给Actor，写出任务独立运行
+fn adopt<W: AsyncWrite + Unpin + 'static>(
+    writer: W,
+    buffer: usize,
+    incoming: &mpsc::UnboundedSender<mpsc::Sender<Frame>>,
+) -> bool {
+    let (frames_tx, frames) = mpsc::channel(buffer.max(1));
+    if incoming.send(frames_tx).is_err() {
+        return false;
+    }
+    actix_rt::spawn(serve(writer, frames));
+    true
+}
+
+/// 绑定Unix域套接字（替换上次运行遗留的套接字文件）并接受连接
+#[cfg(unix)]
+fn spawn_listener(
+    config: &IpcConfig,
+    incoming: mpsc::UnboundedSender<mpsc::Sender<Frame>>,
+) -> std::io::Result<actix_rt::task::JoinHandle<()>> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(&config.path) {
+        if !metadata.file_type().is_socket() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "path exists and is not a socket",
+            ));
+        }
+        std::fs::remove_file(&config.path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(&config.path)?;
+    let buffer = config.buffer;
+    Ok(actix_rt::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    if !adopt(stream, buffer, &incoming) {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to accept IPC consumer: {}", e);
+                    actix_rt::time::sleep(std::time::Duration::from_millis(100)).await;
+                }
+            }
+        }
+    }))
+}
+
+/// 创建命名管道并接受连接，每个连接使用一个管道实例
+#[cfg(windows)]
+fn spawn_listener(
+    config: &IpcConfig,
+    incoming: mpsc::UnboundedSender<mpsc::Sender<Frame>>,
+) -> std::io::Result<actix_rt::task::JoinHandle<()>> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let path = config.path.clone();
+    let mut server = ServerOptions::new().first_pipe_instance(true).create(&path)?;
+    let buffer = config.buffer;
+    Ok(actix_rt::spawn(async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                warn!("Failed to accept IPC consumer: {}", e);
+                actix_rt::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+            let connected = server;
+            server = match ServerOptions::new().create(&path) {
+                Ok(server) => server,
+                Err(e) => {
+                    error!("Failed to create named pipe {}: {}", path, e);
+                    break;
+                }
+            };
+            if !adopt(connected, buffer, &incoming) {
+                break;
+            }
+        }
+    }))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn spawn_listener(
+    _config: &IpcConfig,
+    _incoming: mpsc::UnboundedSender<mpsc::Sender<Frame>>,
+) -> std::io::Result<actix_rt::task::JoinHandle<()>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "IPC transport is not supported on this platform",
+    ))
+}
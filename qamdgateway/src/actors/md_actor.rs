@@ -1,515 +1,433 @@
 use actix::prelude::*;
-use ctp_common::{CThostFtdcDepthMarketDataField, CThostFtdcReqUserLoginField, CThostFtdcSpecificInstrumentField};
-use log::{debug, error, info, warn};
-use std::collections::HashSet;
-use std::ffi::CString;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use chrono::Utc;
+use tracing::{debug, error, info, info_span, warn, Level, Span};
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 
 // 统一导入消息类型
 use crate::actors::messages::*;
-use crate::config::BrokerConfig;
-use crate::converter::convert_ctp_to_md_snapshot;
-use crate::error::GatewayResult;
-
-// 特性标志条件导入
-#[cfg(feature = "ctp")]
-use ctp_md::{DisconnectionReason, MdApi, MdSpi, RspResult, GenericMdApi};
-#[cfg(feature = "qq")]
-use ctp_md_qq::{DisconnectionReason, MdApi, MdSpi, RspResult, GenericMdApi};
-#[cfg(feature = "sina")]
-use ctp_md_sina::{DisconnectionReason, MdApi, MdSpi, RspResult, GenericMdApi};
-
-// 统一的SPI实现，用于回调处理
-struct MarketDataSpiImpl {
-    // 使用actor的地址将消息从CTP回调发送回actor
-    actor_addr: Addr<MarketDataActor>,
-    subscribed_instruments: Arc<Mutex<HashSet<String>>>,
+use crate::calendar::{TradingCalendar, TradingPhase, FUTURES_EXCHANGES};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::{BrokerConfig, CircuitBreakerConfig, ResubscribeConfig};
+use crate::converter::SnapshotConverter;
+use crate::error::{GatewayError, GatewayResult};
+use crate::journal::RawJournal;
+use crate::latency::{self, LatencyTrace};
+use crate::logging::TickLogSampler;
+use crate::sources::{FlowDir, MarketDataSourceAdapter};
+use crate::supervision::{self, CrashEvent};
+
+/// 断线重连后正在进行的重新订阅
+struct ResubscribeTask {
+    // 尚未订阅的合约
+    pending: VecDeque<String>,
+    total: usize,
+    subscribed: usize,
+    // 连续失败次数
+    failures: u32,
+    // 下一批请求的定时任务
+    handle: Option<SpawnHandle>,
 }
 
-// SPI接口实现，处理所有回调
-impl MdSpi for MarketDataSpiImpl {
-    fn on_front_connected(&mut self) {
-        info!("MD Front connected");
-        self.actor_addr.do_send(MarketDataEvent::Connected);
-    }
-
-    fn on_front_disconnected(&mut self, reason: DisconnectionReason) {
-        warn!("MD Front disconnected: {:?}", reason);
-        self.actor_addr.do_send(MarketDataEvent::Disconnected);
-    }
-
-    fn on_rsp_user_login(
-        &mut self,
-        rsp_user_login: Option<&ctp_common::CThostFtdcRspUserLoginField>,
-        result: RspResult,
-        request_id: i32,
-        is_last: bool,
-    ) {
-        info!("Login response: RequestID={}, IsLast={}", request_id, is_last);
-        
-        if let Some(login_info) = rsp_user_login {
-            let trading_day = String::from_utf8_lossy(&login_info.TradingDay);
-            let login_time = String::from_utf8_lossy(&login_info.LoginTime);
-            let broker_id = String::from_utf8_lossy(&login_info.BrokerID);
-            let user_id = String::from_utf8_lossy(&login_info.UserID);
-            
-            info!(
-                "MD Logged in: Trading Day = {}, Login Time = {}, Broker ID = {}, User ID = {}",
-                trading_day, login_time, broker_id, user_id
-            );
-            
-            self.actor_addr.do_send(MarketDataEvent::LoggedIn);
-        } else if let Some(error) = result.err() {
-            let error_msg = format!(
-                "MD Login failed: Error = {}",
-                error
-            );
-            error!("{}", error_msg);
-            self.actor_addr.do_send(MarketDataEvent::Error(error_msg));
-        }
-    }
-
-    fn on_rsp_sub_market_data(
-        &mut self,
-        specific_instrument: Option<&CThostFtdcSpecificInstrumentField>,
-        result: RspResult,
-        request_id: i32,
-        is_last: bool,
-    ) {
-        info!("Subscribe response: RequestID={}, IsLast={}", request_id, is_last);
-        
-        if let Some(instrument) = specific_instrument {
-            let instrument_id = String::from_utf8_lossy(&instrument.InstrumentID)
-                .trim_end_matches('\0')
-                .to_string();
-
-            if result.is_ok() {
-                info!("Subscribed to market data for {}", instrument_id);
-                
-                // 保存订阅信息
-                if let Ok(mut subscribed) = self.subscribed_instruments.lock() {
-                    subscribed.insert(instrument_id.clone());
-                }
-                
-                self.actor_addr.do_send(MarketDataEvent::SubscriptionSuccess(instrument_id));
-            } else if let Some(error) = result.err() {
-                let error_msg = format!(
-                    "Failed to subscribe to market data for {}: Error = {}",
-                    instrument_id,
-                    error
-                );
-                error!("{}", error_msg);
-                self.actor_addr.do_send(MarketDataEvent::SubscriptionFailure(instrument_id, error_msg));
-            }
-        }
-    }
-
-    fn on_rtn_depth_market_data(
-        &mut self,
-        depth_market_data: Option<&CThostFtdcDepthMarketDataField>,
-    ) {
-        if let Some(market_data) = depth_market_data {
-            // 将数据克隆后发送给actor
-            let market_data_owned = *market_data;
-            self.actor_addr.do_send(MarketDataEvent::MarketData(market_data_owned));
-        }
-    }
-
-    fn on_rsp_un_sub_market_data(
-        &mut self,
-        specific_instrument: Option<&CThostFtdcSpecificInstrumentField>,
-        result: RspResult,
-        request_id: i32,
-        is_last: bool,
-    ) {
-        info!("Unsubscribe response: RequestID={}, IsLast={}", request_id, is_last);
-        
-        if let Some(instrument) = specific_instrument {
-            let instrument_id = String::from_utf8_lossy(&instrument.InstrumentID)
-                .trim_end_matches('\0')
-                .to_string();
-
-            if result.is_ok() {
-                info!("Unsubscribed from market data for {}", instrument_id);
-                
-                // 移除订阅信息
-                if let Ok(mut subscribed) = self.subscribed_instruments.lock() {
-                    subscribed.remove(&instrument_id);
-                }
-            } else if let Some(error) = result.err() {
-                error!(
-                    "Failed to unsubscribe from market data for {}: Error = {}",
-                    instrument_id,
-                    error
-                );
-            }
-        }
-    }
-
-    fn on_rsp_error(
-        &mut self,
-        result: RspResult,
-        request_id: i32,
-        is_last: bool,
-    ) {
-        if let Some(error) = result.err() {
-            let error_msg = format!(
-                "MD error: Request ID = {}, Is Last = {}, Error = {}",
-                request_id, is_last, error
-            );
-            error!("{}", error_msg);
-            self.actor_addr.do_send(MarketDataEvent::Error(error_msg));
-        }
-    }
-}
-
-// 统一的MarketDataActor结构，通过feature flags选择实际的API实现
+/// 上游行情连接Actor
+///
+/// 通过行情源插件连接前置、登录和订阅，负责断线重连、分批重新订阅以及把行情转换为MDSnapshot。
+/// 重连由熔断器控制：失败后指数退避，连续失败的前置暂停使用，并在配置的多个前置之间轮换。
+/// 处理行情或SPI回调时发生panic后释放连接并停止，通知监督者（连接器）重新创建
 pub struct MarketDataActor {
-    #[cfg(feature = "ctp")]
-    md_api: Option<ctp_md::MdApi>,
-    #[cfg(feature = "qq")]
-    md_api: Option<ctp_md_qq::MdApi>,
-    #[cfg(feature = "sina")]
-    md_api: Option<ctp_md_sina::MdApi>,
-    #[cfg(not(any(feature = "ctp", feature = "qq", feature = "sina")))]
-    md_api: Option<()>, // 当没有特性被启用时的占位符
-    
-    subscribed_instruments: Arc<Mutex<HashSet<String>>>,
+    // 行情源插件（CTP、QQ、新浪等）
+    adapter: Box<dyn MarketDataSourceAdapter>,
+    // 独占的流文件目录（Actor释放时解除占用）
+    flow_dir: Option<FlowDir>,
+    // 已订阅成功的合约
+    subscribed_instruments: HashSet<String>,
     broker_config: BrokerConfig,
     distributor: Option<Addr<crate::actors::md_distributor::MarketDataDistributor>>,
+    // 崩溃后负责重启的监督者
+    supervisor: Option<Recipient<SourceCrashed>>,
+    // 交易日历（设置后非交易时间不尝试重连）
+    calendar: Option<(Arc<TradingCalendar>, chrono::Duration)>,
+    // 期货交易所的交易阶段（收到后代替交易日历判断是否允许重连）
+    phases: HashMap<String, TradingPhase>,
+    // 行情转换（按数据源处理无效值，可选按最小变动价位取整）
+    converter: SnapshotConverter,
+    // 原始行情日志（转换前写入）
+    journal: Option<Arc<RawJournal>>,
+    // 断线重连后的分批重新订阅策略
+    resubscribe: ResubscribeConfig,
+    // 重新订阅进度的发布通道
+    resubscribe_events: Option<broadcast::Sender<ResubscribeProgress>>,
+    // 正在进行的重新订阅
+    resubscribe_task: Option<ResubscribeTask>,
+    // 各前置的熔断状态
+    breaker: CircuitBreaker,
+    // 已安排的重连
+    reconnect_handle: Option<SpawnHandle>,
+    // 等待连接结果的超时检查
+    connect_timeout_handle: Option<SpawnHandle>,
     front_addr: String,
-    user_id: String,
-    password: String,
     broker_id: String,
     is_connected: bool,
     is_logged_in: bool,
-    
-    // 数据源类型(便于标识)
-    #[cfg(feature = "ctp")]
-    source_type: MarketDataSource,
-    #[cfg(feature = "qq")]
-    source_type: MarketDataSource,
-    #[cfg(feature = "sina")]
-    source_type: MarketDataSource,
-    #[cfg(not(any(feature = "ctp", feature = "qq", feature = "sina")))]
-    source_type: MarketDataSource,
+    // 最近一次断开
+    last_disconnect: Option<DisconnectInfo>,
+    // 本连接日志所在的span
+    span: Span,
+    // 逐笔行情日志采样
+    tick_log: TickLogSampler,
 }
 
 impl Actor for MarketDataActor {
     type Context = Context<Self>;
 
-    fn started(&mut self, ctx: &mut Self::Context) {
-        info!("MarketDataActor started");
-        
-        // 调度心跳以检查连接状态
-        ctx.run_interval(Duration::from_secs(30), |act, ctx| {
-            if !act.is_connected {
-                info!("MarketDataActor heartbeat: Not connected, attempting to reconnect");
-                act.init_md_api(ctx);
-            }
-        });
+    fn started(&mut self, _: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        info!("MarketDataActor started ({:?})", self.adapter.source());
     }
 
     fn stopped(&mut self, _: &mut Self::Context) {
+        let _span = self.span.clone().entered();
         info!("MarketDataActor stopped");
     }
 }
 
 impl MarketDataActor {
-    // 创建新的市场数据Actor，可根据编译时特性决定具体行为
-    #[cfg(feature = "ctp")]
-    pub fn new(config: BrokerConfig) -> Self {
-        let front_addr = config.front_addr.clone();
-        let user_id = config.user_id.clone();
-        let password = config.password.clone();
-        let broker_id = config.broker_id.clone();
-        
+    /// 创建使用指定行情源插件的市场数据Actor
+    pub fn new(config: BrokerConfig, adapter: Box<dyn MarketDataSourceAdapter>) -> Self {
+        let span = info_span!(
+            "upstream",
+            broker_id = %config.broker_id,
+            source = ?adapter.source(),
+            front = %config.front_addr
+        );
         Self {
-            md_api: None,
-            subscribed_instruments: Arc::new(Mutex::new(HashSet::new())),
+            converter: SnapshotConverter::new(adapter.source()),
+            adapter,
+            flow_dir: None,
+            journal: None,
+            subscribed_instruments: HashSet::new(),
+            breaker: CircuitBreaker::new(config.fronts(), CircuitBreakerConfig::default()),
+            reconnect_handle: None,
+            connect_timeout_handle: None,
+            front_addr: config.front_addr.clone(),
+            broker_id: config.broker_id.clone(),
             broker_config: config,
             distributor: None,
-            front_addr,
-            user_id,
-            password,
-            broker_id,
+            supervisor: None,
+            calendar: None,
+            phases: HashMap::new(),
+            resubscribe: ResubscribeConfig::default(),
+            resubscribe_events: None,
+            resubscribe_task: None,
             is_connected: false,
             is_logged_in: false,
-            source_type: MarketDataSource::CTP,
+            last_disconnect: None,
+            span,
+            tick_log: TickLogSampler::new(),
         }
     }
 
-    #[cfg(feature = "qq")]
-    pub fn new(config: BrokerConfig) -> Self {
-        let front_addr = config.front_addr.clone();
-        let user_id = config.user_id.clone();
-        let password = config.password.clone();
-        let broker_id = config.broker_id.clone();
-        
-        Self {
-            md_api: None,
-            subscribed_instruments: Arc::new(Mutex::new(HashSet::new())),
-            broker_config: config,
-            distributor: None,
-            front_addr,
-            user_id,
-            password,
-            broker_id,
-            is_connected: false,
-            is_logged_in: false,
-            source_type: MarketDataSource::QQ,
+    /// 使用独占的流文件目录
+    pub fn with_flow_dir(mut self, flow_dir: FlowDir) -> Self {
+        self.flow_dir = Some(flow_dir);
+        self
+    }
+
+    /// 转换前把原始行情写入日志
+    pub fn with_journal(mut self, journal: Arc<RawJournal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// 崩溃后通知监督者
+    pub fn with_supervisor(mut self, supervisor: Recipient<SourceCrashed>) -> Self {
+        self.supervisor = Some(supervisor);
+        self
+    }
+
+    /// 设置重连的熔断策略
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.breaker = CircuitBreaker::new(self.broker_config.fronts(), config);
+        self
+    }
+
+    // 是否允许重连（未设置交易日历时总是允许），任一期货交易所不在收盘阶段时允许
+    fn is_reconnect_allowed(&self) -> bool {
+        if !self.phases.is_empty() {
+            return self.phases.values().any(|phase| *phase != TradingPhase::Closed);
+        }
+        match &self.calendar {
+            Some((calendar, lead)) => calendar.is_futures_session_near(chrono::Utc::now(), *lead),
+            None => true,
         }
     }
 
-    #[cfg(feature = "sina")]
-    pub fn new(config: BrokerConfig) -> Self {
-        let front_addr = config.front_addr.clone();
-        let user_id = config.user_id.clone();
-        let password = config.password.clone();
-        let broker_id = config.broker_id.clone();
-        
-        Self {
-            md_api: None,
-            subscribed_instruments: Arc::new(Mutex::new(HashSet::new())),
-            broker_config: config,
-            distributor: None,
-            front_addr,
-            user_id,
-            password,
-            broker_id,
-            is_connected: false,
-            is_logged_in: false,
-            source_type: MarketDataSource::Sina,
+    // 取消重连和超时检查，释放行情API
+    fn release(&mut self, ctx: &mut Context<Self>) {
+        self.cancel_resubscribe(ctx);
+        for handle in [self.reconnect_handle.take(), self.connect_timeout_handle.take()].into_iter().flatten() {
+            ctx.cancel_future(handle);
         }
+        self.adapter.disconnect();
+        self.is_connected = false;
+        self.is_logged_in = false;
     }
 
-    #[cfg(not(any(feature = "ctp", feature = "qq", feature = "sina")))]
-    pub fn new(config: BrokerConfig) -> Self {
-        let front_addr = config.front_addr.clone();
-        let user_id = config.user_id.clone();
-        let password = config.password.clone();
-        let broker_id = config.broker_id.clone();
-        
-        Self {
-            md_api: None,
-            subscribed_instruments: Arc::new(Mutex::new(HashSet::new())),
-            broker_config: config,
-            distributor: None,
-            front_addr,
-            user_id,
-            password,
-            broker_id,
-            is_connected: false,
-            is_logged_in: false,
-            source_type: MarketDataSource::CTP, // 默认值
+    // 崩溃后释放连接并停止，由监督者决定是否重启
+    fn crash(&mut self, ctx: &mut Context<Self>, event: CrashEvent) {
+        error!("Market data actor for broker {} crashed, stopping", self.broker_id);
+        // 释放连接时再次panic也要停止Actor
+        let _ = supervision::catch_panic("md_actor", Some(&self.broker_id.clone()), || self.release(ctx));
+        if let Some(supervisor) = &self.supervisor {
+            supervisor.do_send(SourceCrashed {
+                broker_id: self.broker_id.clone(),
+                addr: ctx.address(),
+                event,
+            });
         }
+        ctx.stop();
     }
 
-    // 初始化市场数据API，根据编译时特性选择不同实现
+    // 通过行情源插件连接熔断器选择的前置，超时未连接时记为失败
     fn init_md_api(&mut self, ctx: &mut Context<Self>) {
-        let flow_path = CString::new("").unwrap();
-        
-        #[cfg(feature = "ctp")]
-        {
-            // 创建CTP的MdApi
-            let mut md_api = ctp_md::MdApi::new(flow_path, false, false);
-            
-            // 创建SPI并注册
-            let addr = ctx.address();
-            let subscribed_instruments = self.subscribed_instruments.clone();
-            let spi = Box::new(MarketDataSpiImpl {
-                actor_addr: addr,
-                subscribed_instruments,
-            });
-            
-            md_api.register_spi(spi);
-            
-            // 连接
-            let front_addr = CString::new(self.front_addr.clone()).unwrap();
-            md_api.register_front(front_addr);
-            
-            // 初始化API
-            md_api.init();
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            
-            // 保存API
-            self.md_api = Some(md_api);
+        if let Some(handle) = self.reconnect_handle.take() {
+            ctx.cancel_future(handle);
         }
-        
-        #[cfg(feature = "qq")]
-        {
-            // 创建QQ的MdApi
-            let mut md_api = ctp_md_qq::MdApi::new(flow_path, false, false);
-            
-            // 创建SPI并注册
-            let addr = ctx.address();
-            let subscribed_instruments = self.subscribed_instruments.clone();
-            let spi = Box::new(MarketDataSpiImpl {
-                actor_addr: addr,
-                subscribed_instruments,
-            });
-            
-            md_api.register_spi(spi);
-            
-            // 连接
-            let front_addr = CString::new(self.front_addr.clone()).unwrap();
-            md_api.register_front(front_addr);
-            
-            // 初始化API
-            md_api.init();
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            
-            // 保存API
-            self.md_api = Some(md_api);
+        let now = Instant::now();
+        let wait = self.breaker.plan(now);
+        if !wait.is_zero() {
+            self.schedule_reconnect(ctx);
+            return;
         }
-        
-        #[cfg(feature = "sina")]
-        {
-            // 创建Sina的MdApi
-            let mut md_api = ctp_md_sina::MdApi::new(flow_path, false, false);
-            
-            // 创建SPI并注册
-            let addr = ctx.address();
-            let subscribed_instruments = self.subscribed_instruments.clone();
-            let spi = Box::new(MarketDataSpiImpl {
-                actor_addr: addr,
-                subscribed_instruments,
-            });
-            
-            md_api.register_spi(spi);
-            
-            // 连接
-            let front_addr = CString::new(self.front_addr.clone()).unwrap();
-            md_api.register_front(front_addr);
-            
-            // 初始化API
-            md_api.init();
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            
-            // 保存API
-            self.md_api = Some(md_api);
+        self.breaker.record_attempt(now);
+        self.front_addr = self.breaker.current_front().to_string();
+        self.span.record("front", self.front_addr.as_str());
+        info!("Connecting broker {} to front {}", self.broker_id, self.front_addr);
+
+        // 释放之前的API，避免其在后台继续重试旧的前置
+        self.adapter.disconnect();
+        let events = ctx.address().recipient();
+        let flow_dir = self.flow_dir.as_ref().map(FlowDir::path);
+        if let Err(e) = self.adapter.connect(&self.front_addr, flow_dir, events) {
+            error!("Failed to connect broker {}: {}", self.broker_id, e);
+            self.on_connect_failed(ctx);
+            return;
         }
-    }
 
-    // 登录方法，根据编译时特性选择不同实现
-    fn login(&mut self) -> Result<(), String> {
-        #[cfg(any(feature = "ctp", feature = "qq", feature = "sina"))]
-        if let Some(ref mut md_api) = self.md_api {
-            let mut req = CThostFtdcReqUserLoginField::default();
-            
-            // 填充登录请求
-            if !self.broker_id.is_empty() {
-                let broker_bytes = self.broker_id.as_bytes();
-                let copy_len = std::cmp::min(broker_bytes.len(), req.BrokerID.len() - 1);
-                req.BrokerID[..copy_len].copy_from_slice(&broker_bytes[..copy_len]);
-                req.BrokerID[copy_len] = 0; // 空终止符
-            }
-            
-            if !self.user_id.is_empty() {
-                let user_bytes = self.user_id.as_bytes();
-                let copy_len = std::cmp::min(user_bytes.len(), req.UserID.len() - 1);
-                req.UserID[..copy_len].copy_from_slice(&user_bytes[..copy_len]);
-                req.UserID[copy_len] = 0;
+        if let Some(handle) = self.connect_timeout_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.connect_timeout_handle = Some(ctx.run_later(self.breaker.connect_timeout(), |act, ctx| {
+            let _span = act.span.clone().entered();
+            act.connect_timeout_handle = None;
+            if !act.is_connected {
+                warn!("Broker {} timed out connecting to {}", act.broker_id, act.front_addr);
+                act.last_disconnect = Some(DisconnectInfo {
+                    time: Utc::now(),
+                    reason: format!("Timed out connecting to {}", act.front_addr),
+                });
+                act.on_connect_failed(ctx);
             }
-            
-            if !self.password.is_empty() {
-                let pass_bytes = self.password.as_bytes();
-                let copy_len = std::cmp::min(pass_bytes.len(), req.Password.len() - 1);
-                req.Password[..copy_len].copy_from_slice(&pass_bytes[..copy_len]);
-                req.Password[copy_len] = 0;
+        }));
+    }
+
+    // 连接失败：释放API，计入熔断器并安排下一次重连
+    fn on_connect_failed(&mut self, ctx: &mut Context<Self>) {
+        self.adapter.disconnect();
+        if self.breaker.on_failure(Instant::now()) {
+            warn!("Circuit opened for front {} of broker {}", self.front_addr, self.broker_id);
+        }
+        self.schedule_reconnect(ctx);
+    }
+
+    // 按熔断器给出的等待时间安排重连（已安排时不重复安排）
+    fn schedule_reconnect(&mut self, ctx: &mut Context<Self>) {
+        if self.reconnect_handle.is_some() {
+            return;
+        }
+        let wait = self.breaker.plan(Instant::now());
+        info!(
+            "Broker {} reconnecting to {} in {}ms",
+            self.broker_id,
+            self.breaker.current_front(),
+            wait.as_millis()
+        );
+        self.reconnect_handle = Some(ctx.run_later(wait, |act, ctx| {
+            let _span = act.span.clone().entered();
+            act.reconnect_handle = None;
+            if act.is_connected {
+                return;
             }
-            
-            // 执行登录
-            let result = md_api.req_user_login(&req, 1);
-            
-            match result {
-                Ok(_) => {
-                    std::thread::sleep(std::time::Duration::from_secs(1));
-                    Ok(())
-                },
-                Err(e) => {
-                    let error_msg = format!("Failed to send login request: {:?}", e);
-                    error!("{}", error_msg);
-                    Err(error_msg)
-                }
+            // 非交易时间推迟重连，不消耗连接次数
+            if !act.is_reconnect_allowed() {
+                act.reconnect_handle = Some(ctx.run_later(Duration::from_secs(30), |act, ctx| {
+                    let _span = act.span.clone().entered();
+                    act.reconnect_handle = None;
+                    act.schedule_reconnect(ctx);
+                }));
+                return;
             }
-        } else {
-            Err("Market data API not initialized".to_string())
-        }
+            act.init_md_api(ctx);
+        }));
+    }
 
-        #[cfg(not(any(feature = "ctp", feature = "qq", feature = "sina")))]
-        Err("No market data provider enabled".to_string())
+    // 登录
+    fn login(&mut self) -> GatewayResult<()> {
+        self.adapter.login(&self.broker_config)
     }
 
     // 订阅合约方法
-    fn subscribe_instruments(&mut self, instruments: &[String]) -> Result<(), String> {
-        if !self.is_logged_in {
-            return Err("Not logged in".to_string());
-        }
+    fn subscribe_instruments(&mut self, instruments: &[String]) -> GatewayResult<()> {
+        self.request_subscribe(instruments)?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        Ok(())
+    }
 
-        #[cfg(any(feature = "ctp", feature = "qq", feature = "sina"))]
-        if let Some(ref mut md_api) = self.md_api {
-            // 将合约ID转换为CString
-            let instrument_cstrings: Vec<CString> = instruments
-                .iter()
-                .map(|s| {
-                    // 股票代码可能不含交易所前缀，需要处理
-                    let instrument_code = s.split('.').last().unwrap_or(s);
-                    let code = instrument_code.to_string();
-                    info!("Subscribing to instrument: {}", code);
-                    CString::new(code).unwrap()
-                })
-                .collect();
-                
-            // 执行订阅
-            let result = md_api.subscribe_market_data(&instrument_cstrings);
-            
-            match result {
-                Ok(_) => {
-                    std::thread::sleep(std::time::Duration::from_secs(1));
-                    Ok(())
-                },
-                Err(e) => Err(format!("Failed to subscribe to instruments, error: {:?}", e))
-            }
-        } else {
-            Err("MD API not initialized".to_string())
+    /// 向分发器报告合约的订阅结果（error为None表示成功）
+    fn report_subscription(&self, instrument: String, error: Option<String>) {
+        if let Some(distributor) = &self.distributor {
+            distributor.do_send(UpstreamSubscribed {
+                source: self.adapter.source(),
+                instrument,
+                error,
+            });
         }
+    }
 
-        #[cfg(not(any(feature = "ctp", feature = "qq", feature = "sina")))]
-        Err("No market data provider enabled".to_string())
+    // 发送订阅请求（不等待）
+    fn request_subscribe(&mut self, instruments: &[String]) -> GatewayResult<()> {
+        if !self.is_logged_in {
+            return Err(GatewayError::NotLoggedIn);
+        }
+        self.adapter.subscribe(instruments)
     }
 
     // 取消订阅合约方法
-    fn unsubscribe_instruments(&mut self, instruments: &[String]) -> Result<(), String> {
+    fn unsubscribe_instruments(&mut self, instruments: &[String]) -> GatewayResult<()> {
         if !self.is_logged_in {
-            return Err("Not logged in".to_string());
+            return Err(GatewayError::NotLoggedIn);
         }
+        self.adapter.unsubscribe(instruments)
+    }
+}
+
+impl MarketDataActor {
+    /// 登录后分批重新订阅合约，避免一次性订阅触发CTP流控
+    fn start_resubscribe(&mut self, ctx: &mut Context<Self>, instruments: Vec<String>) {
+        self.cancel_resubscribe(ctx);
+        if instruments.is_empty() {
+            return;
+        }
+        info!(
+            "Resubscribing {} instruments for broker {} in batches of {}",
+            instruments.len(),
+            self.broker_id,
+            self.resubscribe.batch_size.max(1)
+        );
+        self.resubscribe_task = Some(ResubscribeTask {
+            total: instruments.len(),
+            pending: instruments.into(),
+            subscribed: 0,
+            failures: 0,
+            handle: None,
+        });
+        self.resubscribe_next_batch(ctx);
+    }
 
-        #[cfg(any(feature = "ctp", feature = "qq", feature = "sina"))]
-        if let Some(ref mut md_api) = self.md_api {
-            // 将合约ID转换为CString
-            let instrument_cstrings: Vec<CString> = instruments
-                .iter()
-                .map(|s| {
-                    let instrument_code = s.split('.').last().unwrap_or(s);
-                    CString::new(instrument_code.to_string()).unwrap()
-                })
-                .collect();
-            
-            // 执行取消订阅
-            let result = md_api.unsubscribe_market_data(&instrument_cstrings);
-            
-            match result {
-                Ok(_) => Ok(()),
-                Err(e) => Err(format!("Failed to unsubscribe from instruments, error: {:?}", e))
+    /// 取消正在进行的重新订阅（断线后由下次登录重新开始）
+    fn cancel_resubscribe(&mut self, ctx: &mut Context<Self>) {
+        if let Some(task) = self.resubscribe_task.take() {
+            if let Some(handle) = task.handle {
+                ctx.cancel_future(handle);
             }
-        } else {
-            Err("MD API not initialized".to_string())
+            info!(
+                "Resubscribe for broker {} cancelled at {}/{}",
+                self.broker_id, task.subscribed, task.total
+            );
+        }
+    }
+
+    /// 订阅下一批合约并安排下一次请求
+    fn resubscribe_next_batch(&mut self, ctx: &mut Context<Self>) {
+        let Some(mut task) = self.resubscribe_task.take() else {
+            return;
+        };
+        let count = self.resubscribe.batch_size.max(1).min(task.pending.len());
+        let batch: Vec<String> = task.pending.drain(..count).collect();
+
+        let delay = match self.request_subscribe(&batch) {
+            Ok(()) => {
+                task.subscribed += batch.len();
+                task.failures = 0;
+                Duration::from_millis(self.resubscribe.batch_interval_ms)
+            }
+            Err(e) => {
+                // 未登录时放弃，重新登录后会再次发起
+                if matches!(e, GatewayError::NotLoggedIn) {
+                    warn!("Resubscribe for broker {} stopped: {}", self.broker_id, e);
+                    return;
+                }
+                task.failures += 1;
+                let backoff = self
+                    .resubscribe
+                    .backoff_initial_ms
+                    .saturating_mul(1 << (task.failures - 1).min(16))
+                    .min(self.resubscribe.backoff_max_ms);
+                warn!(
+                    "Resubscribe batch of {} rejected for broker {} ({} failures), retrying in {}ms: {}",
+                    batch.len(),
+                    self.broker_id,
+                    task.failures,
+                    backoff,
+                    e
+                );
+                for instrument in batch.into_iter().rev() {
+                    task.pending.push_front(instrument);
+                }
+                Duration::from_millis(backoff)
+            }
+        };
+        let done = task.pending.is_empty();
+        let delay = if done { Duration::ZERO } else { delay + self.resubscribe_jitter() };
+        self.publish_resubscribe_progress(&task, delay, done);
+
+        if done {
+            info!("Resubscribed {} instruments for broker {}", task.subscribed, self.broker_id);
+            return;
         }
+        task.handle = Some(ctx.run_later(delay, |act, ctx| {
+            let _span = act.span.clone().entered();
+            act.resubscribe_next_batch(ctx);
+        }));
+        self.resubscribe_task = Some(task);
+    }
 
-        #[cfg(not(any(feature = "ctp", feature = "qq", feature = "sina")))]
-        Err("No market data provider enabled".to_string())
+    /// 随机附加的等待时间，避免多个连接同时发送请求
+    fn resubscribe_jitter(&self) -> Duration {
+        if self.resubscribe.jitter_ms == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(rand::thread_rng().gen_range(0..=self.resubscribe.jitter_ms))
+    }
+
+    /// 发布重新订阅进度
+    fn publish_resubscribe_progress(&self, task: &ResubscribeTask, delay: Duration, done: bool) {
+        let Some(events) = &self.resubscribe_events else {
+            return;
+        };
+        // 没有订阅者时发送失败，忽略即可
+        let _ = events.send(ResubscribeProgress {
+            broker_id: self.broker_id.clone(),
+            total: task.total,
+            subscribed: task.subscribed,
+            failures: task.failures,
+            next_delay_ms: delay.as_millis() as u64,
+            done,
+            timestamp: chrono::Utc::now(),
+        });
     }
 }
 
@@ -518,14 +436,16 @@ impl Handler<InitMarketDataSource> for MarketDataActor {
     type Result = ();
 
     fn handle(&mut self, _: InitMarketDataSource, ctx: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
         self.init_md_api(ctx);
     }
 }
 
 impl Handler<LoginMarketDataSource> for MarketDataActor {
-    type Result = Result<(), String>;
+    type Result = GatewayResult<()>;
 
     fn handle(&mut self, _: LoginMarketDataSource, _: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
         self.login()
     }
 }
@@ -534,8 +454,12 @@ impl Handler<Subscribe> for MarketDataActor {
     type Result = ();
 
     fn handle(&mut self, msg: Subscribe, _: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
         if let Err(e) = self.subscribe_instruments(&msg.instruments) {
             error!("Failed to subscribe to instruments: {}", e);
+            for instrument in msg.instruments {
+                self.report_subscription(instrument, Some(e.to_string()));
+            }
         }
     }
 }
@@ -544,6 +468,7 @@ impl Handler<Unsubscribe> for MarketDataActor {
     type Result = ();
 
     fn handle(&mut self, msg: Unsubscribe, _: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
         if let Err(e) = self.unsubscribe_instruments(&msg.instruments) {
             error!("Failed to unsubscribe from instruments: {}", e);
         }
@@ -553,78 +478,103 @@ impl Handler<Unsubscribe> for MarketDataActor {
 impl Handler<GetSubscriptions> for MarketDataActor {
     type Result = Vec<String>;
 
-    fn handle(&mut self, msg: GetSubscriptions, _: &mut Self::Context) -> Self::Result {
-        let subscriptions = if let Ok(subscribed) = self.subscribed_instruments.lock() {
-            subscribed.iter().cloned().collect()
-        } else {
-            Vec::new()
-        };
-        
-        // 如果提供了回调，则执行回调
-        if let Some(callback) = msg.callback {
-            callback(subscriptions.clone());
-        }
-        
-        subscriptions
+    fn handle(&mut self, _: GetSubscriptions, _: &mut Self::Context) -> Self::Result {
+        self.subscribed_instruments.iter().cloned().collect()
     }
 }
 
 impl Handler<MarketDataEvent> for MarketDataActor {
     type Result = ();
 
-    fn handle(&mut self, msg: MarketDataEvent, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: MarketDataEvent, ctx: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
+        let crash = match msg {
+            MarketDataEvent::Panicked(message) => {
+                Some(supervision::record_crash("spi", Some(&self.broker_id), message))
+            }
+            msg => {
+                let broker_id = self.broker_id.clone();
+                supervision::catch_panic("md_actor", Some(&broker_id), || self.on_event(msg, ctx)).err()
+            }
+        };
+        if let Some(event) = crash {
+            self.crash(ctx, event);
+        }
+    }
+}
+
+impl MarketDataActor {
+    // 处理行情源事件
+    fn on_event(&mut self, msg: MarketDataEvent, ctx: &mut Context<Self>) {
         match msg {
             MarketDataEvent::Connected => {
-                info!("Market data source connected");
+                info!("Market data source connected to {}", self.front_addr);
                 self.is_connected = true;
+                self.breaker.on_success(Instant::now());
+                if let Some(handle) = self.connect_timeout_handle.take() {
+                    ctx.cancel_future(handle);
+                }
                 
                 // 连接后自动登录
                 if let Err(e) = self.login() {
                     error!("Failed to login: {}", e);
                 }
             },
-            MarketDataEvent::Disconnected => {
-                warn!("Market data source disconnected");
+            MarketDataEvent::Disconnected(reason) => {
+                warn!("Market data source disconnected from {}: {}", self.front_addr, reason);
+                self.last_disconnect = Some(DisconnectInfo {
+                    time: Utc::now(),
+                    reason,
+                });
+                // 未连接成功时的断开由连接超时处理
+                let was_connected = self.is_connected;
                 self.is_connected = false;
                 self.is_logged_in = false;
+                self.cancel_resubscribe(ctx);
+                if was_connected {
+                    // 断线计入前置的健康分，重连时可能换到更健康的前置
+                    self.breaker.on_disconnect(Instant::now());
+                    self.schedule_reconnect(ctx);
+                }
             },
             MarketDataEvent::LoggedIn => {
                 info!("Market data source logged in");
                 self.is_logged_in = true;
                 
                 // 重新订阅所有合约
-                let instruments = {
-                    if let Ok(subscribed) = self.subscribed_instruments.lock() {
-                        subscribed.iter().cloned().collect::<Vec<_>>()
-                    } else {
-                        Vec::new()
-                    }
-                };
-                
-                if !instruments.is_empty() {
-                    if let Err(e) = self.subscribe_instruments(&instruments) {
-                        error!("Failed to resubscribe to instruments: {}", e);
+                let instruments = self.subscribed_instruments.iter().cloned().collect();
+                self.start_resubscribe(ctx, instruments);
+            },
+            MarketDataEvent::MarketData(md, received) => {
+                // 先记录原始行情，转换出错时可以离线复现
+                if let Some(journal) = &self.journal {
+                    if let Err(e) = journal.append_depth(self.adapter.source(), received, &md) {
+                        warn!("Failed to journal market data: {}", e);
                     }
                 }
-            },
-            MarketDataEvent::MarketData(md) => {
+                
                 // 转换为MDSnapshot
-                match convert_ctp_to_md_snapshot(&md) {
+                match self.converter.convert(&md) {
                     Ok(snapshot) => {
-                        debug!("Received market data for {}", snapshot.instrument_id);
-                        // 转发给distributor
+                        if tracing::enabled!(Level::DEBUG) {
+                            if let Some(ticks) = self.tick_log.sample(&snapshot.instrument_id) {
+                                debug!(
+                                    instrument = %snapshot.instrument_id,
+                                    last_price = snapshot.last_price,
+                                    volume = snapshot.volume,
+                                    ticks,
+                                    "Received market data"
+                                );
+                            }
+                        }
+                        // 转发给distributor（开启延迟追踪时带上各跳时间戳）
                         if let Some(distributor) = &self.distributor {
-                            #[cfg(feature = "ctp")]
-                            distributor.do_send(MarketDataUpdate(snapshot, MarketDataSource::CTP));
-                            
-                            #[cfg(feature = "qq")]
-                            distributor.do_send(MarketDataUpdate(snapshot, MarketDataSource::QQ));
-                            
-                            #[cfg(feature = "sina")]
-                            distributor.do_send(MarketDataUpdate(snapshot, MarketDataSource::Sina));
-                            
-                            #[cfg(not(any(feature = "ctp", feature = "qq", feature = "sina")))]
-                            distributor.do_send(MarketDataUpdate(snapshot, MarketDataSource::CTP));
+                            let source = self.adapter.source();
+                            if latency::is_enabled() {
+                                distributor.do_send(TracedMarketDataUpdate(Arc::new(snapshot), source, LatencyTrace::new(received)));
+                            } else {
+                                distributor.do_send(MarketDataUpdate(Arc::new(snapshot), source));
+                            }
                         }
                     },
                     Err(e) => {
@@ -634,13 +584,22 @@ impl Handler<MarketDataEvent> for MarketDataActor {
             },
             MarketDataEvent::SubscriptionSuccess(instrument) => {
                 info!("Successfully subscribed to {}", instrument);
+                self.report_subscription(instrument.clone(), None);
+                self.subscribed_instruments.insert(instrument);
+            },
+            MarketDataEvent::Unsubscribed(instrument) => {
+                info!("Unsubscribed from market data for {}", instrument);
+                self.subscribed_instruments.remove(&instrument);
             },
             MarketDataEvent::SubscriptionFailure(instrument, error) => {
                 error!("Failed to subscribe to {}: {}", instrument, error);
+                self.report_subscription(instrument, Some(error));
             },
             MarketDataEvent::Error(error) => {
                 error!("Market data error: {}", error);
             },
+            // 已在handle中转为崩溃
+            MarketDataEvent::Panicked(_) => {},
         }
     }
 }
@@ -658,8 +617,9 @@ impl Handler<StartMarketData> for MarketDataActor {
     type Result = ();
 
     fn handle(&mut self, msg: StartMarketData, ctx: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
         // 如果API未初始化，则初始化
-        if self.md_api.is_none() {
+        if !self.adapter.is_connected() {
             self.init_md_api(ctx);
         }
         
@@ -676,15 +636,9 @@ impl Handler<StopMarketData> for MarketDataActor {
     type Result = ();
 
     fn handle(&mut self, _: StopMarketData, _: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
         // 取消订阅所有合约
-        let instruments = {
-            if let Ok(subscribed) = self.subscribed_instruments.lock() {
-                subscribed.iter().cloned().collect::<Vec<_>>()
-            } else {
-                Vec::new()
-            }
-        };
-        
+        let instruments: Vec<String> = self.subscribed_instruments.iter().cloned().collect();
         if !instruments.is_empty() {
             if let Err(e) = self.unsubscribe_instruments(&instruments) {
                 error!("Failed to unsubscribe from instruments: {}", e);
@@ -697,19 +651,111 @@ impl Handler<RestartActor> for MarketDataActor {
     type Result = ();
 
     fn handle(&mut self, _: RestartActor, ctx: &mut Self::Context) -> Self::Result {
-        // 只有未连接或未登录时才重启
-        if !self.is_connected || !self.is_logged_in {
-            info!("Restarting market data actor for broker {}", self.broker_id);
-            
-            // 重新初始化
-            if self.md_api.is_none() {
-                self.init_md_api(ctx);
-            }
-            
-            // 尝试重新登录
+        let _span = self.span.clone().entered();
+        // 非交易时间不重启，避免前置关闭期间反复重连
+        if !self.is_reconnect_allowed() {
+            debug!("Outside trading hours, skip restarting broker {}", self.broker_id);
+            return;
+        }
+        
+        // 未连接时由熔断器安排重连，已连接但未登录时重新登录
+        if !self.is_connected {
+            self.schedule_reconnect(ctx);
+        } else if !self.is_logged_in {
+            info!("Restarting login for broker {}", self.broker_id);
             if let Err(e) = self.login() {
                 error!("Failed to login during restart: {}", e);
             }
         }
     }
-} 
\ No newline at end of file
+}
+
+impl Handler<SetTradingCalendar> for MarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetTradingCalendar, _: &mut Self::Context) -> Self::Result {
+        self.calendar = Some((msg.calendar, msg.lead));
+    }
+}
+
+impl Handler<PhaseChanged> for MarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: PhaseChanged, _: &mut Self::Context) -> Self::Result {
+        if FUTURES_EXCHANGES.contains(&msg.exchange.as_str()) {
+            self.phases.insert(msg.exchange, msg.phase);
+        }
+    }
+}
+
+impl Handler<SetInstrumentRegistry> for MarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetInstrumentRegistry, _: &mut Self::Context) -> Self::Result {
+        self.converter = if msg.round_prices {
+            self.converter.clone().with_price_rounding(msg.instruments)
+        } else {
+            self.converter.clone().with_instruments(msg.instruments)
+        };
+    }
+}
+
+impl Handler<SetTolerantParsing> for MarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetTolerantParsing, _: &mut Self::Context) -> Self::Result {
+        self.converter = self.converter.clone().with_tolerant_parsing(msg.dead_letter);
+    }
+}
+
+impl Handler<SetResubscribePolicy> for MarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetResubscribePolicy, _: &mut Self::Context) -> Self::Result {
+        self.resubscribe = msg.config;
+        self.resubscribe_events = Some(msg.events);
+    }
+}
+
+impl Handler<GetConnectionState> for MarketDataActor {
+    type Result = MessageResult<GetConnectionState>;
+
+    fn handle(&mut self, _: GetConnectionState, _: &mut Self::Context) -> Self::Result {
+        MessageResult(BrokerState {
+            broker_id: self.broker_id.clone(),
+            front_addr: self.front_addr.clone(),
+            fronts: self.breaker.status(),
+            connected: self.is_connected,
+            logged_in: self.is_logged_in,
+            subscribed: self.subscribed_instruments.len(),
+            resubscribing: self.resubscribe_task.is_some(),
+            // 由连接器填写
+            crashes: 0,
+            restarts: 0,
+            source: self.adapter.source(),
+            last_disconnect: self.last_disconnect.clone(),
+        })
+    }
+}
+
+impl Handler<StopActor> for MarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, _: StopActor, ctx: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
+        info!("Stopping market data actor for broker {}", self.broker_id);
+        
+        // 退订所有合约
+        let instruments: Vec<String> = self.subscribed_instruments.iter().cloned().collect();
+        if !instruments.is_empty() {
+            if let Err(e) = self.unsubscribe_instruments(&instruments) {
+                error!("Failed to unsubscribe from instruments: {}", e);
+            }
+        }
+        
+        // 释放行情API，断开前置连接
+        self.release(ctx);
+        
+        ctx.stop();
+    }
+}
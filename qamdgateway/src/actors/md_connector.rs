@@ -1,41 +1,32 @@
 use actix::prelude::*;
-use log::{info, error, warn};
+use tracing::{debug, info, error, warn};
 use hashbrown::{HashMap, HashSet};
 use uuid::Uuid;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::any::Any;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::broadcast;
 
 use crate::actors::prelude::*;
 use crate::actors::messages::*;
 use crate::actors::md_actor::MarketDataActor;
 use crate::actors::md_distributor::MarketDataDistributor;
-use crate::config::BrokerConfig;
-
-
-
-
-// Conditionally import feature-specific types
-#[cfg(feature = "qq")]
-use crate::actors::qq_md_actor;
-
-#[cfg(feature = "sina")]
-use crate::actors::sina_md_actor;
-
-
-/// 数据源类型枚举
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum MarketDataSourceType {
-    CTP,
-    QQ,
-    Sina,
-    // 后续可以添加更多的数据源类型
-}
+use crate::actors::trading_phase::TradingPhaseActor;
+use crate::calendar::{TradingCalendar, FUTURES_EXCHANGES};
+use crate::config::{BrokerConfig, CircuitBreakerConfig, FlowConfig, LoadSharingConfig, ResubscribeConfig, SupervisionConfig};
+use crate::converter::DeadLetterLog;
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::InstrumentRegistry;
+use crate::journal::RawJournal;
+use crate::load_sharing::LoadSharing;
+use crate::sources::{self, FlowDir};
+use crate::supervision::{self, RestartBudget};
 
 /// Market data connector that manages connections to market data sources
 pub struct MarketDataConnector {
-    /// Market data sources by ID (CTP行情源)
+    /// Market data sources by broker ID (CTP, QQ and Sina brokers side by side)
     md_sources: HashMap<String, Addr<MarketDataActor>>,
-    /// QQ行情源
 
     /// Market data distributor
     distributor: Addr<MarketDataDistributor>,
@@ -43,8 +34,48 @@ pub struct MarketDataConnector {
     broker_configs: Vec<BrokerConfig>,
     /// Default subscriptions
     default_subscriptions: Vec<String>,
+    /// Watched subscription file, its expanded instruments and the watcher keeping it alive
+    subscription_file: Option<(PathBuf, Arc<InstrumentRegistry>)>,
+    file_subscriptions: Vec<String>,
+    file_watcher: Option<notify::RecommendedWatcher>,
+    /// Whether a debounced reload of the subscription file is scheduled
+    file_reload_pending: bool,
     /// Connected clients
     clients: HashMap<Uuid, Recipient<MarketDataUpdate>>,
+    /// Trading calendar used to suppress reconnects outside trading hours
+    calendar: Option<(Arc<TradingCalendar>, chrono::Duration)>,
+    /// Trading phase tracker that replaces the calendar check once it reports phases
+    trading_phases: Option<Addr<TradingPhaseActor>>,
+    /// Instrument reference data used to classify quotes
+    instruments: Option<Arc<InstrumentRegistry>>,
+    /// Round prices to the price tick of known instruments
+    round_prices: bool,
+    // 容错解析及其死信文件
+    tolerant_parsing: bool,
+    dead_letter: Option<Arc<DeadLetterLog>>,
+    /// Batched resubscribe policy and progress channel applied to every source
+    resubscribe: Option<(ResubscribeConfig, broadcast::Sender<ResubscribeProgress>)>,
+    /// Flow file directories of the upstream APIs
+    flow: FlowConfig,
+    /// Reconnect circuit breaker applied to every source
+    circuit_breaker: CircuitBreakerConfig,
+    /// Raw upstream message journal shared by all sources
+    journal: Option<Arc<RawJournal>>,
+    /// Restart policy of sources that crashed with a panic
+    supervision: SupervisionConfig,
+    /// Crash restarts of each broker within the supervision window
+    restart_budgets: HashMap<String, RestartBudget>,
+    /// Assignment of instruments to the accounts sharing the subscriptions
+    load_sharing: Option<LoadSharing>,
+    load_sharing_config: LoadSharingConfig,
+    /// Load sharing accounts that are logged out, and since when
+    accounts_down: HashMap<String, Instant>,
+}
+
+/// Active subscriptions of the distributor and the current subscriptions of each source
+struct SubscriptionSnapshot {
+    active: Vec<String>,
+    sources: Vec<(String, Addr<MarketDataActor>, Vec<String>)>,
 }
 
 impl Actor for MarketDataConnector {
@@ -58,8 +89,20 @@ impl Actor for MarketDataConnector {
             act.check_connections();
         });
         
+        // Re-sync upstream subscriptions when the subscription file changes
+        self.watch_subscription_file(ctx);
+        
         // Initialize market data sources
         self.init_market_data_sources(ctx);
+        
+        // Move the instruments of accounts that stay logged out to the other accounts
+        if self.load_sharing.is_some() {
+            self.publish_load_sharing();
+            let interval = Duration::from_secs(self.load_sharing_config.check_interval_secs.max(1));
+            ctx.run_interval(interval, |act, ctx| {
+                act.check_accounts(ctx);
+            });
+        }
     }
 }
 
@@ -74,35 +117,267 @@ impl MarketDataConnector {
             distributor,
             broker_configs,
             default_subscriptions,
+            subscription_file: None,
+            file_subscriptions: Vec::new(),
+            file_watcher: None,
+            file_reload_pending: false,
             clients: HashMap::new(),
+            calendar: None,
+            trading_phases: None,
+            instruments: None,
+            round_prices: false,
+            tolerant_parsing: false,
+            dead_letter: None,
+            resubscribe: None,
+            flow: FlowConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            journal: None,
+            supervision: SupervisionConfig::default(),
+            restart_budgets: HashMap::new(),
+            load_sharing: None,
+            load_sharing_config: LoadSharingConfig::default(),
+            accounts_down: HashMap::new(),
         }
     }
+
+    /// Set where the upstream APIs keep their flow files
+    pub fn with_flow(mut self, flow: FlowConfig) -> Self {
+        self.flow = flow;
+        self
+    }
+
+    /// Back off, budget and rotate the reconnects of every source
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = config;
+        self
+    }
+
+    /// Restart sources that crash with a panic within a restart budget
+    pub fn with_supervision(mut self, config: SupervisionConfig) -> Self {
+        self.supervision = config;
+        self
+    }
+
+    /// Subscribe each instrument on one of several accounts chosen by consistent hashing
+    ///
+    /// Without configured accounts every CTP broker shares the load.
+    pub fn with_load_sharing(mut self, config: LoadSharingConfig) -> Self {
+        let accounts = if config.accounts.is_empty() {
+            self.broker_configs
+                .iter()
+                .filter(|config| {
+                    matches!(sources::resolve_source(config.source_type.as_deref()), Ok(MarketDataSource::CTP))
+                })
+                .map(|config| config.broker_id.clone())
+                .collect()
+        } else {
+            config.accounts.clone()
+        };
+        info!("Sharing subscriptions across accounts {:?}", accounts);
+        self.load_sharing = Some(LoadSharing::new(accounts, config.virtual_nodes));
+        self.load_sharing_config = config;
+        self
+    }
+
+    /// Journal the raw quotes of every source before conversion
+    pub fn with_journal(mut self, journal: Arc<RawJournal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Suppress upstream reconnects outside trading hours
+    pub fn with_calendar(mut self, calendar: Arc<TradingCalendar>, lead: chrono::Duration) -> Self {
+        self.calendar = Some((calendar, lead));
+        self
+    }
+
+    /// Suppress upstream reconnects while every futures exchange is closed
+    pub fn with_trading_phases(mut self, phases: Addr<TradingPhaseActor>) -> Self {
+        self.trading_phases = Some(phases);
+        self
+    }
     
-    fn init_market_data_sources(&mut self, ctx: &mut Context<Self>) {
-        info!("Initializing market data sources");
-        
-        // Create a market data actor for each broker
-        println!("broker_configs: {:?}", self.broker_configs);
-        for broker_config in &self.broker_configs {
-            let broker_id = broker_config.broker_id.clone();
-            info!("Creating market data source for broker {}", broker_id);
-            
-            // Create the actor
-            let md_actor = MarketDataActor::new(broker_config.clone()).start();
-            
-            // Store the actor
-            self.md_sources.insert(broker_id, md_actor);
+    /// Classify upstream quotes and fill in option terms from the instrument reference data
+    pub fn with_instruments(mut self, instruments: Arc<InstrumentRegistry>) -> Self {
+        self.instruments = Some(instruments);
+        self
+    }
+    
+    /// Round upstream prices to the price tick of known instruments
+    pub fn with_price_rounding(mut self, instruments: Arc<InstrumentRegistry>) -> Self {
+        self.round_prices = true;
+        self.with_instruments(instruments)
+    }
+    
+    /// Publish partial snapshots from malformed quotes, capturing them in a dead-letter file
+    pub fn with_tolerant_parsing(mut self, dead_letter: Option<Arc<DeadLetterLog>>) -> Self {
+        self.tolerant_parsing = true;
+        self.dead_letter = dead_letter;
+        self
+    }
+    
+    /// Resubscribe in jittered batches after reconnects and publish progress
+    pub fn with_resubscribe(
+        mut self,
+        config: ResubscribeConfig,
+        events: broadcast::Sender<ResubscribeProgress>,
+    ) -> Self {
+        self.resubscribe = Some((config, events));
+        self
+    }
+    
+    /// Also subscribe the instruments listed in a file, re-syncing upstream subscriptions when it changes
+    ///
+    /// The file lists one instrument or instrument group per line; `#` starts a comment.
+    /// A file that cannot be read at startup is treated as empty until it is written.
+    pub fn with_subscription_file(mut self, path: impl Into<PathBuf>, instruments: Arc<InstrumentRegistry>) -> Self {
+        let path = path.into();
+        match read_subscription_file(&path) {
+            Ok(patterns) => {
+                self.file_subscriptions = instruments.expand(&patterns);
+                info!(
+                    "Loaded {} instruments from subscription file {}",
+                    self.file_subscriptions.len(),
+                    path.display()
+                );
+            }
+            Err(e) => warn!("{}", e),
+        }
+        self.subscription_file = Some((path, instruments));
+        self
+    }
+    
+    /// Default subscriptions from the configuration and the subscription file
+    fn bootstrap_subscriptions(&self) -> Vec<String> {
+        let mut instruments = self.default_subscriptions.clone();
+        instruments.extend(missing_from(&self.file_subscriptions, &self.default_subscriptions));
+        instruments
+    }
+    
+    fn watch_subscription_file(&mut self, ctx: &mut Context<Self>) {
+        let Some((path, _)) = &self.subscription_file else {
+            return;
+        };
+        // Watch the directory so that editors replacing the file are noticed as well
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = path.file_name().map(|name| name.to_os_string());
+        let addr = ctx.address();
+        let watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| match result {
+            Ok(event) => {
+                let relevant = !event.kind.is_access()
+                    && event.paths.iter().any(|path| path.file_name() == file_name.as_deref());
+                if relevant {
+                    addr.do_send(SubscriptionFileChanged);
+                }
+            }
+            Err(e) => warn!("Subscription file watch error: {}", e),
+        });
+        let watcher = watcher.and_then(|mut watcher| {
+            notify::Watcher::watch(&mut watcher, &directory, notify::RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+        match watcher {
+            Ok(watcher) => {
+                info!("Watching subscription file {}", path.display());
+                self.file_watcher = Some(watcher);
+            }
+            Err(e) => error!("Failed to watch subscription file {}: {}", path.display(), e),
+        }
+    }
+    
+    /// Re-read the subscription file and sync the instruments that were added or removed
+    fn reload_subscription_file(&mut self, ctx: &mut Context<Self>) {
+        let Some((path, instruments)) = &self.subscription_file else {
+            return;
+        };
+        let file_subscriptions = match read_subscription_file(path) {
+            Ok(patterns) => instruments.expand(&patterns),
+            Err(e) => {
+                warn!("{}, keeping the current subscriptions", e);
+                return;
+            }
+        };
+        let added = missing_from(&file_subscriptions, &self.file_subscriptions);
+        let removed = missing_from(&self.file_subscriptions, &file_subscriptions);
+        self.file_subscriptions = file_subscriptions;
+        if added.is_empty() && removed.is_empty() {
+            return;
         }
+        info!(
+            "Subscription file {} changed (+{} -{})",
+            path.display(),
+            added.len(),
+            removed.len()
+        );
         
-        // Initialize the market data sources
-        for (broker_id, md_actor) in &self.md_sources {
-            info!("Initializing market data source for broker {}", broker_id);
-            md_actor.do_send(InitMarketDataSource);
+        // 配置中的默认订阅不受订阅文件影响
+        let added = missing_from(&added, &self.default_subscriptions);
+        let removed = missing_from(&removed, &self.default_subscriptions);
+        self.sync_default_changes(added, removed, ctx);
+    }
+    
+    /// Subscribe added default instruments on every source and unsubscribe removed ones no client still uses
+    fn sync_default_changes(&self, added: Vec<String>, removed: Vec<String>, ctx: &mut Context<Self>) {
+        if !added.is_empty() {
+            info!("Subscribing {} new default instruments", added.len());
+            for (broker_id, md_actor) in &self.md_sources {
+                let instruments = assigned_instruments(self.load_sharing.as_ref(), broker_id, &added);
+                if !instruments.is_empty() {
+                    md_actor.do_send(Subscribe {
+                        id: Uuid::nil(),
+                        instruments,
+                    });
+                }
+            }
+        }
+        if removed.is_empty() {
+            return;
+        }
         
-            info!("Registering distributor with market data source for broker {}", broker_id);
-            md_actor.do_send(RegisterDistributor {
-                addr: self.distributor.clone(),
+        // 仍有客户端订阅的合约保留上游订阅
+        let md_sources: Vec<Addr<MarketDataActor>> = self.md_sources.values().cloned().collect();
+        let future = self.distributor
+            .send(GetAllSubscriptions)
+            .into_actor(self)
+            .map(move |result, _act, _ctx| {
+                let active = match result {
+                    Ok(active) => active,
+                    Err(e) => {
+                        error!("Failed to get active subscriptions: {}", e);
+                        return;
+                    }
+                };
+                let unused: Vec<String> = removed
+                    .into_iter()
+                    .filter(|instrument| !active.contains(instrument))
+                    .collect();
+                if unused.is_empty() {
+                    return;
+                }
+                info!("Unsubscribing {} removed default instruments", unused.len());
+                for md_actor in md_sources {
+                    md_actor.do_send(Unsubscribe {
+                        id: Uuid::nil(),
+                        instruments: unused.clone(),
+                    });
+                }
             });
+        ctx.spawn(future);
+    }
+    
+    fn init_market_data_sources(&mut self, ctx: &mut Context<Self>) {
+        info!("Initializing market data sources");
+        
+        // Create a market data actor for each broker
+        debug!(
+            "Brokers: {:?}",
+            self.broker_configs.iter().map(|config| &config.broker_id).collect::<Vec<_>>()
+        );
+        for broker_config in self.broker_configs.clone() {
+            self.spawn_market_data_source(broker_config, ctx);
         }
         
         // Set up periodic synchronization of subscriptions
@@ -112,15 +387,16 @@ impl MarketDataConnector {
     }
     
     fn start_market_data(&self) {
-        if !self.default_subscriptions.is_empty() {
-            info!("Starting market data with default subscriptions: {:?}", self.default_subscriptions);
+        let default_subscriptions = self.bootstrap_subscriptions();
+        if !default_subscriptions.is_empty() {
+            info!("Starting market data with default subscriptions: {:?}", default_subscriptions);
             
             // Start all market data sources with default subscriptions
             for (broker_id, md_actor) in &self.md_sources {
                 info!("Starting market data for broker {}", broker_id);
                 
                 md_actor.do_send(StartMarketData {
-                    instruments: self.default_subscriptions.clone(),
+                    instruments: default_subscriptions.clone(),
                 });
             }
         }
@@ -137,84 +413,489 @@ impl MarketDataConnector {
 
     }
     
-    // Sync broker subscriptions with client subscriptions
-    fn sync_subscriptions(&self, ctx: &mut Context<Self>) {
-        // Get all market data sources
+    /// Query the distributor's active subscriptions together with the current subscriptions of every source
+    fn snapshot_subscriptions(&self) -> impl std::future::Future<Output = Result<SubscriptionSnapshot, MailboxError>> + 'static {
+        let active = self.distributor.send(GetAllSubscriptions);
         let md_sources: Vec<(String, Addr<MarketDataActor>)> = self.md_sources
             .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
+            .map(|(broker_id, md_actor)| (broker_id.clone(), md_actor.clone()))
             .collect();
-            
-        // Clone the distributor address for use in futures
-        let distributor = self.distributor.clone();
         
-        // First get all active subscriptions from distributor
-        let future = distributor
-            .send(GetAllSubscriptions {})
+        async move {
+            let active = active.await?;
+            let requests = md_sources.iter().map(|(_, md_actor)| md_actor.send(GetSubscriptions));
+            let current = futures::future::join_all(requests).await;
+            // Sources stopped in the meantime are left out
+            let sources = md_sources
+                .into_iter()
+                .zip(current)
+                .filter_map(|((broker_id, md_actor), current)| current.ok().map(|current| (broker_id, md_actor, current)))
+                .collect();
+            Ok(SubscriptionSnapshot { active, sources })
+        }
+    }
+    
+    // Sync broker subscriptions with client subscriptions
+    fn sync_subscriptions(&self, ctx: &mut Context<Self>) {
+        let future = self
+            .snapshot_subscriptions()
             .into_actor(self)
-            .map(move |result, _act, _ctx| {
-                if let Ok(active_subscriptions) = result {
-                    // Process each market data source
-                    for (broker_id, md_actor) in md_sources {
-                        // Clone active_subscriptions for this iteration
-                        let active_subs = active_subscriptions.clone();
-                        let broker_id_clone = broker_id.clone();
-                        let md_actor_clone = md_actor.clone();
-                        
-                        // Using do_send instead of send+wait to avoid blocking
-                        md_actor.do_send(GetSubscriptions { 
+            .map(|result, act, _ctx| {
+                let snapshot = match result {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => {
+                        error!("Failed to query subscriptions: {}", e);
+                        return;
+                    }
+                };
+                for (broker_id, md_actor, current) in snapshot.sources {
+                    // Only the share of this broker when it is a load sharing account
+                    let assigned = assigned_instruments(act.load_sharing.as_ref(), &broker_id, &snapshot.active);
+                    
+                    let to_subscribe = missing_from(&assigned, &current);
+                    if !to_subscribe.is_empty() {
+                        info!("Synchronizing subscriptions for broker {}: subscribing to {} instruments",
+                            broker_id, to_subscribe.len());
+                        md_actor.do_send(Subscribe {
                             id: Uuid::new_v4(),
-                            // Process the result in another message
-                            callback: Some(Box::new(move |current_subscriptions| {
-                                // Find instruments that need to be subscribed
-                                let to_subscribe: Vec<String> = active_subs
-                                    .iter()
-                                    .filter(|inst| !current_subscriptions.contains(*inst))
-                                    .cloned()
-                                    .collect();
-                                
-                                // Subscribe to new instruments
-                                if !to_subscribe.is_empty() {
-                                    info!("Synchronizing subscriptions for broker {}: subscribing to {} instruments", 
-                                        broker_id_clone, to_subscribe.len());
-                                    md_actor_clone.do_send(Subscribe {
-                                        id: Uuid::new_v4(),
-                                        instruments: to_subscribe,
-                                    });
-                                }
-                                
-                                // Find instruments that need to be unsubscribed
-                                let to_unsubscribe: Vec<String> = current_subscriptions
-                                    .iter()
-                                    .filter(|inst| !active_subs.contains(*inst))
-                                    .cloned()
-                                    .collect();
-                                
-                                // Unsubscribe from old instruments
-                                if !to_unsubscribe.is_empty() {
-                                    info!("Synchronizing subscriptions for broker {}: unsubscribing from {} instruments", 
-                                        broker_id_clone, to_unsubscribe.len());
-                                    md_actor_clone.do_send(Unsubscribe {
-                                        id: Uuid::new_v4(),
-                                        instruments: to_unsubscribe,
-                                    });
-                                }
-                            }))
+                            instruments: to_subscribe,
+                        });
+                    }
+                    
+                    let to_unsubscribe = missing_from(&current, &assigned);
+                    if !to_unsubscribe.is_empty() {
+                        info!("Synchronizing subscriptions for broker {}: unsubscribing from {} instruments",
+                            broker_id, to_unsubscribe.len());
+                        md_actor.do_send(Unsubscribe {
+                            id: Uuid::new_v4(),
+                            instruments: to_unsubscribe,
                         });
                     }
                 }
             });
         
-        // Execute the future
         ctx.spawn(future);
     }
 
+    /// 启动一个上游行情连接并注册到分发器，行情源按`source_type`选择
+    fn spawn_market_data_source(&mut self, broker_config: BrokerConfig, ctx: &mut Context<Self>) -> Option<Addr<MarketDataActor>> {
+        let broker_id = broker_config.broker_id.clone();
+        let adapter = sources::resolve_source(broker_config.source_type.as_deref())
+            .and_then(sources::create_adapter);
+        let adapter = match adapter {
+            Ok(adapter) => adapter,
+            Err(e) => {
+                error!("Cannot create market data source for broker {}: {}", broker_id, e);
+                return None;
+            }
+        };
+        let source_type = adapter.source();
+        info!("Creating {:?} market data source for broker {}", source_type, broker_id);
+        
+        // Every broker keeps its flow files in a directory of its own
+        let owner = format!("{:?} broker {}", source_type, broker_config.name);
+        let flow_path = self.flow.broker_dir(&broker_config, source_type);
+        let flow_dir = match FlowDir::acquire(&flow_path, &owner, self.flow.max_size_mb * 1024 * 1024) {
+            Ok(flow_dir) => flow_dir,
+            Err(e) => {
+                error!("Cannot use flow directory for broker {}: {}", broker_id, e);
+                return None;
+            }
+        };
+        
+        let mut md_actor = MarketDataActor::new(broker_config, adapter)
+            .with_flow_dir(flow_dir)
+            .with_circuit_breaker(self.circuit_breaker.clone())
+            .with_supervisor(ctx.address().recipient());
+        if let Some(journal) = &self.journal {
+            md_actor = md_actor.with_journal(journal.clone());
+        }
+        let md_actor = md_actor.start();
+        md_actor.do_send(InitMarketDataSource);
+        self.distributor.do_send(RegisterMdActor {
+            broker_id: broker_id.clone(),
+            addr: md_actor.clone(),
+            source_type,
+        });
+        if let Some((calendar, lead)) = &self.calendar {
+            md_actor.do_send(SetTradingCalendar {
+                calendar: calendar.clone(),
+                lead: *lead,
+            });
+        }
+        if let Some(phases) = &self.trading_phases {
+            phases.do_send(SubscribePhase {
+                client_id: format!("md_actor:{}", broker_id),
+                addr: md_actor.clone().recipient(),
+                session_end: None,
+                exchanges: FUTURES_EXCHANGES.iter().map(|exchange| exchange.to_string()).collect(),
+            });
+        }
+        if let Some(instruments) = &self.instruments {
+            md_actor.do_send(SetInstrumentRegistry {
+                instruments: instruments.clone(),
+                round_prices: self.round_prices,
+            });
+        }
+        if self.tolerant_parsing {
+            md_actor.do_send(SetTolerantParsing {
+                dead_letter: self.dead_letter.clone(),
+            });
+        }
+        if let Some((config, events)) = &self.resubscribe {
+            md_actor.do_send(SetResubscribePolicy {
+                config: config.clone(),
+                events: events.clone(),
+            });
+        }
+        
+        self.md_sources.insert(broker_id, md_actor.clone());
+        Some(md_actor)
+    }
+
+    /// 启动上游连接，订阅当前所有活跃合约以及默认合约
+    fn start_broker(&mut self, config: BrokerConfig, ctx: &mut Context<Self>) {
+        self.broker_configs.push(config.clone());
+        self.connect_broker(config, ctx);
+    }
+
+    /// 创建已登记配置的上游连接并订阅
+    fn connect_broker(&mut self, config: BrokerConfig, ctx: &mut Context<Self>) {
+        let broker_id = config.broker_id.clone();
+        let Some(md_actor) = self.spawn_market_data_source(config, ctx) else {
+            return;
+        };
+        
+        let default_subscriptions = self.bootstrap_subscriptions();
+        let future = self.distributor
+            .send(GetAllSubscriptions)
+            .into_actor(self)
+            .map(move |result, act, _ctx| {
+                let mut instruments: HashSet<String> = default_subscriptions.into_iter().collect();
+                match result {
+                    Ok(active) => instruments.extend(active),
+                    Err(e) => error!("Failed to get active subscriptions for new broker: {}", e),
+                }
+                let instruments: Vec<String> = instruments.into_iter().collect();
+                let instruments = assigned_instruments(act.load_sharing.as_ref(), &broker_id, &instruments);
+                
+                info!("Starting broker {} with {} instruments", broker_id, instruments.len());
+                md_actor.do_send(StartMarketData { instruments });
+            });
+        ctx.spawn(future);
+    }
+
+    /// 停止上游连接
+    fn stop_broker(&mut self, broker_id: &str) -> Result<(), String> {
+        let md_actor = self.md_sources
+            .remove(broker_id)
+            .ok_or_else(|| format!("Broker {} not found", broker_id))?;
+        
+        md_actor.do_send(StopActor);
+        self.distributor.do_send(UnregisterMdActor {
+            broker_id: broker_id.to_string(),
+        });
+        self.broker_configs.retain(|config| config.broker_id != broker_id);
+        self.restart_budgets.remove(broker_id);
+        self.accounts_down.remove(broker_id);
+        Ok(())
+    }
+
+    /// Tell the distributor which account an instrument is subscribed on
+    fn publish_load_sharing(&self) {
+        self.distributor.do_send(SetLoadSharing {
+            sharing: self.load_sharing.clone().map(Arc::new),
+        });
+    }
+
+    /// Query the login state of the load sharing accounts, then rebalance
+    fn check_accounts(&self, ctx: &mut Context<Self>) {
+        let Some(sharing) = &self.load_sharing else {
+            return;
+        };
+        let requests: Vec<_> = sharing
+            .accounts()
+            .iter()
+            .filter_map(|broker_id| self.md_sources.get(broker_id))
+            .map(|md_actor| md_actor.send(GetConnectionState))
+            .collect();
+        let future = futures::future::join_all(requests)
+            .into_actor(self)
+            .map(|results, act, ctx| {
+                let now = Instant::now();
+                for state in results.into_iter().filter_map(Result::ok) {
+                    if state.logged_in {
+                        act.accounts_down.remove(&state.broker_id);
+                    } else {
+                        act.accounts_down.entry(state.broker_id).or_insert(now);
+                    }
+                }
+                act.rebalance(ctx);
+            });
+        ctx.spawn(future);
+    }
+
+    /// Keep the accounts that are connected and not logged out past the grace period on the hash ring,
+    /// resyncing the upstream subscriptions when that set changes
+    fn rebalance(&mut self, ctx: &mut Context<Self>) {
+        let grace = Duration::from_secs(self.load_sharing_config.down_grace_secs);
+        let Some(sharing) = &mut self.load_sharing else {
+            return;
+        };
+        let live: Vec<String> = sharing
+            .accounts()
+            .iter()
+            .filter(|broker_id| self.md_sources.contains_key(*broker_id))
+            .filter(|broker_id| self.accounts_down.get(*broker_id).is_none_or(|since| since.elapsed() < grace))
+            .cloned()
+            .collect();
+        if !sharing.set_live(&live) {
+            return;
+        }
+        if live.is_empty() {
+            warn!("No load sharing account is available, keeping the assignment to all accounts");
+        } else {
+            info!("Rebalancing subscriptions across accounts {:?}", live);
+        }
+        self.publish_load_sharing();
+        self.sync_subscriptions(ctx);
+    }
+
     // 添加获取分发器的方法
     pub fn get_distributor(&self) -> Addr<MarketDataDistributor> {
         self.distributor.clone()
     }
 }
 
+impl Handler<AddBroker> for MarketDataConnector {
+    type Result = Result<(), String>;
+
+    fn handle(&mut self, msg: AddBroker, ctx: &mut Self::Context) -> Self::Result {
+        let broker_id = msg.config.broker_id.clone();
+        if broker_id.is_empty() {
+            return Err("broker_id must not be empty".to_string());
+        }
+        if self.md_sources.contains_key(&broker_id) {
+            return Err(format!("Broker {} is already connected", broker_id));
+        }
+        
+        self.start_broker(msg.config, ctx);
+        Ok(())
+    }
+}
+
+impl Handler<RemoveBroker> for MarketDataConnector {
+    type Result = Result<(), String>;
+
+    fn handle(&mut self, msg: RemoveBroker, ctx: &mut Self::Context) -> Self::Result {
+        self.stop_broker(&msg.broker_id)?;
+        // 剩余的连接立即补订被移除连接上的合约（负载分担时按新的账户集合重新分配）
+        if self.load_sharing.is_some() {
+            self.rebalance(ctx);
+        } else {
+            self.sync_subscriptions(ctx);
+        }
+        
+        if self.md_sources.is_empty() {
+            warn!("Broker {} removed, no market data sources left", msg.broker_id);
+        } else {
+            info!("Broker {} removed, {} market data sources left", msg.broker_id, self.md_sources.len());
+        }
+        Ok(())
+    }
+}
+
+impl Handler<ReloadBrokers> for MarketDataConnector {
+    type Result = Vec<String>;
+
+    fn handle(&mut self, msg: ReloadBrokers, ctx: &mut Self::Context) -> Self::Result {
+        let mut reconnected = Vec::new();
+        for config in msg.configs {
+            let changed = match self.broker_configs.iter().find(|c| c.broker_id == config.broker_id) {
+                Some(current) => *current != config,
+                None => {
+                    warn!("Broker {} is not connected, add it via /api/brokers", config.broker_id);
+                    false
+                }
+            };
+            // 只重建配置变化的连接，其他连接不受影响
+            if changed && self.stop_broker(&config.broker_id).is_ok() {
+                info!("Broker {} configuration changed, reconnecting", config.broker_id);
+                reconnected.push(config.broker_id.clone());
+                self.start_broker(config, ctx);
+            }
+        }
+        reconnected
+    }
+}
+
+impl Handler<UpdateDefaultSubscriptions> for MarketDataConnector {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateDefaultSubscriptions, ctx: &mut Self::Context) -> Self::Result {
+        self.default_subscriptions.retain(|instrument| !msg.removed.contains(instrument));
+        for instrument in &msg.added {
+            if !self.default_subscriptions.contains(instrument) {
+                self.default_subscriptions.push(instrument.clone());
+            }
+        }
+        
+        // 订阅文件中的合约已经订阅，也不随配置移除
+        let added = missing_from(&msg.added, &self.file_subscriptions);
+        let removed = missing_from(&msg.removed, &self.file_subscriptions);
+        self.sync_default_changes(added, removed, ctx);
+    }
+}
+
+impl Handler<SubscriptionFileChanged> for MarketDataConnector {
+    type Result = ();
+
+    fn handle(&mut self, _: SubscriptionFileChanged, ctx: &mut Self::Context) -> Self::Result {
+        // 编辑器保存文件时会产生多个事件，合并后再重新读取
+        if self.file_reload_pending {
+            return;
+        }
+        self.file_reload_pending = true;
+        ctx.run_later(Duration::from_millis(500), |act, ctx| {
+            act.file_reload_pending = false;
+            act.reload_subscription_file(ctx);
+        });
+    }
+}
+
+impl Handler<UpdateResubscribePolicy> for MarketDataConnector {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateResubscribePolicy, _: &mut Self::Context) -> Self::Result {
+        if let Some((config, events)) = &mut self.resubscribe {
+            *config = msg.config;
+            for md_actor in self.md_sources.values() {
+                md_actor.do_send(SetResubscribePolicy {
+                    config: config.clone(),
+                    events: events.clone(),
+                });
+            }
+        }
+    }
+}
+
+impl Handler<ListBrokers> for MarketDataConnector {
+    type Result = Vec<BrokerConfig>;
+
+    fn handle(&mut self, _: ListBrokers, _: &mut Self::Context) -> Self::Result {
+        self.broker_configs
+            .iter()
+            .filter(|config| self.md_sources.contains_key(&config.broker_id))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Handler<GetBrokerStates> for MarketDataConnector {
+    type Result = ResponseFuture<Vec<BrokerState>>;
+
+    fn handle(&mut self, _: GetBrokerStates, _: &mut Self::Context) -> Self::Result {
+        let restarts: HashMap<String, usize> = self
+            .restart_budgets
+            .iter()
+            .map(|(broker_id, budget)| (broker_id.clone(), budget.restarts()))
+            .collect();
+        let requests: Vec<_> = self
+            .md_sources
+            .values()
+            .map(|md_actor| md_actor.send(GetConnectionState))
+            .collect();
+        Box::pin(async move {
+            let mut states: Vec<BrokerState> = futures::future::join_all(requests)
+                .await
+                .into_iter()
+                .filter_map(Result::ok)
+                .collect();
+            for state in &mut states {
+                state.crashes = supervision::metrics().broker_crashes(&state.broker_id);
+                state.restarts = restarts.get(&state.broker_id).copied().unwrap_or(0);
+            }
+            states.sort_by(|a, b| a.broker_id.cmp(&b.broker_id));
+            states
+        })
+    }
+}
+
+impl Handler<GetLoadSharing> for MarketDataConnector {
+    type Result = ResponseFuture<Option<Vec<crate::load_sharing::LoadSharingAccount>>>;
+
+    fn handle(&mut self, _: GetLoadSharing, _: &mut Self::Context) -> Self::Result {
+        let Some(sharing) = self.load_sharing.clone() else {
+            return Box::pin(async { None });
+        };
+        let active = self.distributor.send(GetAllSubscriptions);
+        Box::pin(async move {
+            let active = active.await.unwrap_or_default();
+            Some(sharing.status(&active))
+        })
+    }
+}
+
+impl Handler<SourceCrashed> for MarketDataConnector {
+    type Result = ();
+
+    fn handle(&mut self, msg: SourceCrashed, ctx: &mut Self::Context) -> Self::Result {
+        // 连接已被移除或重建时忽略旧Actor的崩溃
+        if self.md_sources.get(&msg.broker_id) != Some(&msg.addr) {
+            return;
+        }
+        self.md_sources.remove(&msg.broker_id);
+        self.distributor.do_send(UnregisterMdActor {
+            broker_id: msg.broker_id.clone(),
+        });
+        self.rebalance(ctx);
+        
+        let budget = self.restart_budgets.entry(msg.broker_id.clone()).or_default();
+        let Some(delay) = budget.next_delay(&self.supervision, Instant::now()) else {
+            error!(
+                "Broker {} crashed {} times within {}s, giving up (last panic in {}: {}); add it again via /api/brokers",
+                msg.broker_id, self.supervision.max_restarts, self.supervision.window_secs,
+                msg.event.component, msg.event.message
+            );
+            self.broker_configs.retain(|config| config.broker_id != msg.broker_id);
+            self.restart_budgets.remove(&msg.broker_id);
+            return;
+        };
+        
+        // 等待旧Actor释放流文件目录后再重建
+        warn!("Broker {} crashed ({}), restarting in {:?}", msg.broker_id, msg.event.message, delay);
+        let broker_id = msg.broker_id;
+        ctx.run_later(delay, move |act, ctx| {
+            // 等待期间被移除或重建的连接不再重启
+            if act.md_sources.contains_key(&broker_id) {
+                return;
+            }
+            let Some(config) = act.broker_configs.iter().find(|config| config.broker_id == broker_id).cloned() else {
+                return;
+            };
+            info!("Restarting crashed broker {}", broker_id);
+            act.connect_broker(config, ctx);
+        });
+    }
+}
+
+impl Handler<ReconnectBroker> for MarketDataConnector {
+    type Result = Result<(), String>;
+
+    fn handle(&mut self, msg: ReconnectBroker, ctx: &mut Self::Context) -> Self::Result {
+        let config = self.broker_configs
+            .iter()
+            .find(|config| config.broker_id == msg.broker_id && self.md_sources.contains_key(&config.broker_id))
+            .cloned()
+            .ok_or_else(|| format!("Broker {} not found", msg.broker_id))?;
+        
+        info!("Reconnecting broker {}", msg.broker_id);
+        self.stop_broker(&msg.broker_id)?;
+        self.start_broker(config, ctx);
+        Ok(())
+    }
+}
+
 impl Handler<Subscribe> for MarketDataConnector {
     type Result = ();
 
@@ -224,12 +905,16 @@ impl Handler<Subscribe> for MarketDataConnector {
             msg.id, msg.instruments
         );
         
-        // Forward subscription to all market data sources
+        // Forward subscription to all market data sources, or to the accounts owning the instruments
         for (broker_id, md_actor) in &self.md_sources {
+            let instruments = assigned_instruments(self.load_sharing.as_ref(), broker_id, &msg.instruments);
+            if instruments.is_empty() {
+                continue;
+            }
             info!("Subscribing broker {} to instruments", broker_id);
             md_actor.do_send(Subscribe {
                 id: msg.id,
-                instruments: msg.instruments.clone(),
+                instruments,
             });
         }
         
@@ -260,50 +945,28 @@ impl Handler<Unsubscribe> for MarketDataConnector {
             });
         }
         
-        // Get all market data sources
-        let md_sources: Vec<(String, Addr<MarketDataActor>)> = self.md_sources
-            .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
-            
-        // Check if any instruments no longer have subscribers
-        let distributor = self.distributor.clone();
-        
-        // Create a future that processes all market data sources
-        let future = distributor
-            .send(GetAllSubscriptions {})
+        // Unsubscribe every source from instruments that no longer have subscribers
+        let future = self
+            .snapshot_subscriptions()
             .into_actor(self)
-            .map(move |result, _act, _ctx| {
-                if let Ok(active_subscriptions) = result {
-                    for (broker_id, md_actor) in md_sources {
-                        // Create a separate future for each market data source with its own copy of active_subscriptions
-                        let active_subs = active_subscriptions.clone();
-                        let broker_id_clone = broker_id.clone();
-                        let md_actor_clone = md_actor.clone();
-                        
-                        // Using do_send instead of send+wait to avoid blocking
-                        md_actor.do_send(GetSubscriptions { 
+            .map(|result, _act, _ctx| {
+                let snapshot = match result {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => {
+                        error!("Failed to query subscriptions: {}", e);
+                        return;
+                    }
+                };
+                for (broker_id, md_actor, current) in snapshot.sources {
+                    let to_unsubscribe = missing_from(&current, &snapshot.active);
+                    if !to_unsubscribe.is_empty() {
+                        info!(
+                            "Unsubscribing broker {} from unused instruments: {:?}",
+                            broker_id, to_unsubscribe
+                        );
+                        md_actor.do_send(Unsubscribe {
                             id: Uuid::nil(),
-                            // Process the result in another message
-                            callback: Some(Box::new(move |curr_subs| {
-                                // Find instruments to unsubscribe from
-                                let to_unsubscribe: Vec<String> = curr_subs
-                                    .into_iter()
-                                    .filter(|inst| !active_subs.contains(inst))
-                                    .collect();
-                                
-                                if !to_unsubscribe.is_empty() {
-                                    info!(
-                                        "Unsubscribing broker {} from unused instruments: {:?}",
-                                        broker_id_clone, to_unsubscribe
-                                    );
-                                    
-                                    md_actor_clone.do_send(Unsubscribe {
-                                        id: Uuid::nil(),
-                                        instruments: to_unsubscribe,
-                                    });
-                                }
-                            }))
+                            instruments: to_unsubscribe,
                         });
                     }
                 }
@@ -316,27 +979,24 @@ impl Handler<Unsubscribe> for MarketDataConnector {
 impl Handler<GetSubscriptions> for MarketDataConnector {
     type Result = ResponseFuture<Vec<String>>;
 
-    fn handle(&mut self, msg: GetSubscriptions, _: &mut Self::Context) -> Self::Result {
-        // Get unique subscriptions from all market data sources
-        // For simplicity, we'll just use the first market data source
-        if let Some((_, md_actor)) = self.md_sources.iter().next() {
-            let fut = md_actor.send(GetSubscriptions { 
-                id: msg.id,
-                callback: None  // We're using the future result instead
-            });
-            
-            Box::pin(async move {
-                match fut.await {
-                    Ok(subscriptions) => subscriptions,
-                    Err(e) => {
-                        error!("Failed to get subscriptions: {}", e);
-                        Vec::new()
-                    }
-                }
-            })
-        } else {
-            Box::pin(async { Vec::new() })
-        }
+    fn handle(&mut self, _: GetSubscriptions, _: &mut Self::Context) -> Self::Result {
+        // Union of the subscriptions of all market data sources
+        let requests: Vec<_> = self
+            .md_sources
+            .values()
+            .map(|md_actor| md_actor.send(GetSubscriptions))
+            .collect();
+        Box::pin(async move {
+            let instruments: HashSet<String> = futures::future::join_all(requests)
+                .await
+                .into_iter()
+                .filter_map(Result::ok)
+                .flatten()
+                .collect();
+            let mut instruments: Vec<String> = instruments.into_iter().collect();
+            instruments.sort();
+            instruments
+        })
     }
 }
 
@@ -352,6 +1012,9 @@ impl Handler<WebSocketConnect> for MarketDataConnector {
             client_id: client_id.to_string(),
             addr: msg.addr,
             instruments: Vec::new(),
+            notify: None,
+            remap: None,
+            disconnect: None,
         });
         
         info!("Client {} connected and registered with distributor", client_id);
@@ -403,3 +1066,36 @@ impl Handler<MarketDataUpdate> for MarketDataConnector {
     }
 }
 
+/// Instruments a broker subscribes: all of them, or its share when it is a load sharing account
+fn assigned_instruments(sharing: Option<&LoadSharing>, broker_id: &str, instruments: &[String]) -> Vec<String> {
+    instruments
+        .iter()
+        .filter(|instrument| sharing.is_none_or(|sharing| sharing.owns(broker_id, instrument)))
+        .cloned()
+        .collect()
+}
+
+/// Read a subscription file: one instrument or instrument group per line, `#` starts a comment
+fn read_subscription_file(path: &Path) -> GatewayResult<Vec<String>> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        GatewayError::ConfigError(format!("Failed to read subscription file {}: {}", path.display(), e))
+    })?;
+    let mut instruments: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if !line.is_empty() && !instruments.iter().any(|instrument| instrument == line) {
+            instruments.push(line.to_string());
+        }
+    }
+    Ok(instruments)
+}
+
+/// Instruments of `wanted` missing from `present`
+fn missing_from(wanted: &[String], present: &[String]) -> Vec<String> {
+    let present: HashSet<&String> = present.iter().collect();
+    wanted
+        .iter()
+        .filter(|instrument| !present.contains(instrument))
+        .cloned()
+        .collect()
+}
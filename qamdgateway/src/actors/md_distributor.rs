@@ -1,12 +1,31 @@
 use actix::prelude::*;
 use hashbrown::{HashMap, HashSet};
-use log::{debug, error, info, warn};
+use tracing::{debug, error, info, warn, Level};
 use serde_json::json;
+use serde::{Deserialize, Serialize};
 use uuid;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::actors::fanout_shard::{shard_index, FanoutShard};
 use crate::actors::messages::*;
-use qamd_rs::{MDSnapshot, OptionalF64};
+use crate::clock_skew::ClockSkewEstimator;
+use crate::config::{ClockSkewConfig, FailoverConfig, OptionsConfig, PriceCheckConfig};
+use crate::error::GatewayResult;
+use crate::instruments::{matches_pattern, InstrumentRegistry};
+use crate::latency::{self, LatencyTrace, LATENCY_FIELD};
+use crate::load_sharing::LoadSharing;
+use crate::logging::TickLogSampler;
+use crate::price_check::PriceChecker;
+use crate::ws_server::Channel;
+use crate::quote_encoding::EncodedQuote;
+use crate::supervision;
+use crate::dominant::is_dominant;
+use crate::synthetic::is_synthetic;
+use qamd_rs::options::quote_price;
+use qamd_rs::{OptionAnalytics, TickFilter};
 
 /// 市场数据分发器
 /// 
@@ -19,28 +38,31 @@ pub struct MarketDataDistributor {
     // 保存合约订阅关系 (合约ID -> 订阅客户端集合)
     instrument_subscribers: HashMap<String, HashSet<String>>,
     
-    // 保存不同市场数据源的Actor地址
-    #[cfg(feature = "ctp")]
-    ctp_actors: HashMap<String, Addr<crate::actors::md_actor::MarketDataActor>>,
-    
-    #[cfg(feature = "qq")]
-    qq_actors: HashMap<String, Addr<crate::actors::md_actor::MarketDataActor>>,
-    
-    #[cfg(feature = "sina")]
-    sina_actors: HashMap<String, Addr<crate::actors::md_actor::MarketDataActor>>,
+    // 保存不同市场数据源的Actor地址 (数据源 -> 经纪商ID -> Actor)
+    md_actors: HashMap<MarketDataSource, HashMap<String, Addr<crate::actors::md_actor::MarketDataActor>>>,
+    // 多账户负载分担时合约到账户的分配
+    load_sharing: Option<Arc<LoadSharing>>,
     
     // 最新的市场数据缓存 (合约ID -> 行情数据)
-    market_data_cache: HashMap<String, qamd_rs::MDSnapshot>,
+    market_data_cache: HashMap<String, Arc<qamd_rs::MDSnapshot>>,
+    // 最近的若干笔行情 (合约ID -> 从旧到新)，供订阅时回放
+    recent_ticks: HashMap<String, VecDeque<Arc<qamd_rs::MDSnapshot>>>,
+    // 每个合约保留的笔数（0为不保留）
+    replay_depth: usize,
     
     // 来源标记 (合约ID -> 市场数据源)
     source_map: HashMap<String, MarketDataSource>,
+    // 行情源对订阅请求的应答 ((数据源, 合约代码) -> 失败原因，None为成功)
+    upstream_acks: HashMap<(MarketDataSource, String), Option<String>>,
 
     // 增量更新相关字段
     // 每个客户端最后的行情数据快照
-    client_snapshots: HashMap<String, HashMap<String, qamd_rs::MDSnapshot>>,
+    client_snapshots: HashMap<String, HashMap<String, Arc<qamd_rs::MDSnapshot>>>,
     
     // 批量更新累积缓存
     batch_updates: HashMap<String, HashMap<String, serde_json::Value>>,
+    // 批量更新中带延迟追踪的合约
+    batch_traces: HashMap<String, LatencyTrace>,
     
     // 上次批量发送时间
     last_batch_send: Instant,
@@ -48,6 +70,126 @@ pub struct MarketDataDistributor {
     // 批量更新配置
     batch_interval: Duration,
     batch_size_threshold: usize,
+    
+    // 已断开、可在保留期内恢复的会话（客户端ID -> 会话状态），
+    // 包括从持久化状态恢复、尚未重新连接的客户端
+    detached_sessions: HashMap<String, DetachedSession>,
+    // 断开的会话的保留时长（为0时断开即释放订阅）
+    resume_grace: Duration,
+    
+    // 最后一个客户端退订后，合约在上游保留订阅的时长（为0时立即退订）
+    unsubscribe_linger: Duration,
+    // 无人订阅、等待上游退订的合约（合约ID -> 退订时间）
+    lingering: HashMap<String, Instant>,
+    // 上游退订后保留行情缓存的时长（None表示一直保留）
+    cache_retention: Option<Duration>,
+    // 上游已退订、等待清除缓存的合约（合约ID -> 清除时间）
+    cache_expiry: HashMap<String, Instant>,
+    
+    // 多数据源故障切换配置
+    failover: FailoverConfig,
+    // 每个合约各数据源最后一次收到行情的时间
+    source_last_tick: HashMap<String, HashMap<MarketDataSource, Instant>>,
+    
+    // 重复/乱序行情过滤器
+    tick_filter: TickFilter,
+    // 丢弃行情的日志采样
+    dropped_tick_log: TickLogSampler,
+    
+    // 各数据源的时钟偏差估计
+    clock_skew: ClockSkewEstimator,
+    // 发布前的价格校验
+    price_checker: PriceChecker,
+    
+    // 行情输出（名称 -> 接收者）
+    snapshot_sinks: HashMap<String, Recipient<MarketDataUpdate>>,
+    // 逐笔成交输出（名称 -> 接收者）
+    #[cfg(feature = "zmq-pub")]
+    tick_sinks: HashMap<String, Recipient<TickUpdate>>,
+    
+    // 期权分析配置
+    options: OptionsConfig,
+    // 合约基础信息（期权条款和标的）
+    instruments: Arc<InstrumentRegistry>,
+    // 期权最新的隐含波动率和希腊字母
+    option_analytics: HashMap<String, OptionAnalytics>,
+    // 标的合约 -> 已计算过的期权合约
+    underlying_options: HashMap<String, HashSet<String>>,
+    
+    // 通配符模式订阅索引 (模式 -> 订阅客户端集合)
+    pattern_subscribers: HashMap<String, HashSet<String>>,
+    // 单个模式最多展开的合约数
+    max_pattern_matches: usize,
+    
+    // 补丁模式客户端每个合约发送全量帧的间隔
+    patch_refresh: Duration,
+    
+    // 合成合约计算Actor
+    synthetic_engine: Option<Addr<crate::actors::synthetic_actor::SyntheticActor>>,
+    
+    // 主力合约映射Actor
+    dominant_engine: Option<Addr<crate::actors::dominant_actor::DominantActor>>,
+    // 主力合约 -> 当前映射的合约月份
+    dominant_contracts: HashMap<String, String>,
+    
+    // 按交易所前缀接收订阅的行情源（交易所 -> 行情源）
+    exchange_sources: HashMap<String, ExchangeSource>,
+    
+    // 行情分发分片（为空时由分发器直接推送）
+    shards: Vec<Addr<FanoutShard>>,
+    // 分片所在的Arbiter，随分发器一起释放
+    _arbiters: Vec<Arbiter>,
+}
+
+/// 分发器订阅状态（用于重启后恢复订阅）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DistributorState {
+    /// 合约ID -> 订阅客户端ID
+    pub instrument_subscribers: HashMap<String, Vec<String>>,
+    /// 合约ID -> 市场数据源
+    pub source_map: HashMap<String, MarketDataSource>,
+    /// 保存时间
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl DistributorState {
+    /// 从文件加载状态，文件不存在时返回None
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// 保存状态到文件（先写临时文件再重命名，避免写到一半的文件）
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, path)
+    }
+}
+
+/// 期权分析推送给客户端的字段
+fn option_fields(analytics: &OptionAnalytics) -> HashMap<String, serde_json::Value> {
+    let greeks = &analytics.greeks;
+    [
+        ("implied_volatility", analytics.implied_volatility),
+        ("delta", greeks.delta),
+        ("gamma", greeks.gamma),
+        ("vega", greeks.vega),
+        ("theta", greeks.theta),
+        ("rho", greeks.rho),
+    ]
+    .into_iter()
+    .map(|(field, value)| (field.to_string(), json!(value)))
+    .collect()
 }
 
 /// 订阅者信息
@@ -56,6 +198,169 @@ struct Subscriber {
     addr: Recipient<MarketDataUpdateMessage>,
     // 订阅的合约集合
     instruments: HashSet<String>,
+    // 限速间隔（None表示实时推送）
+    throttle: Option<Duration>,
+    // 限速模式下上次推送时间
+    last_flush: Instant,
+    // 限速模式下等待推送的合约（同一合约只保留最新行情）
+    pending: HashSet<String>,
+    // 数据源切换通知的接收者
+    notify: Option<Recipient<SourceChanged>>,
+    // 主力合约切换通知的接收者
+    remap: Option<Recipient<ContractRemapped>>,
+    // 管理接口强制断开时通知的会话
+    disconnect: Option<Recipient<ForceDisconnect>>,
+    // 通配符模式 -> 经该模式订阅的合约
+    patterns: HashMap<String, HashSet<String>>,
+    // 推送的行情字段（None表示全部字段）
+    fields: Option<HashSet<String>>,
+    // 补丁模式状态（None表示推送增量字段）
+    patch: Option<ClientPatch>,
+    // 客户端取消订阅的通道
+    muted: HashSet<Channel>,
+}
+
+/// 补丁模式客户端的状态
+struct ClientPatch {
+    // 接收行情帧的会话
+    addr: Recipient<QuotePatchUpdate>,
+    // 合约 -> 上次发送给客户端的行情字段
+    sent: HashMap<String, serde_json::Map<String, serde_json::Value>>,
+    // 合约 -> 上次发送全量帧的时间
+    last_full: HashMap<String, Instant>,
+}
+
+/// 按交易所前缀接收订阅的行情源
+struct ExchangeSource {
+    source: MarketDataSource,
+    subscribe: Recipient<Subscribe>,
+    unsubscribe: Recipient<Unsubscribe>,
+}
+
+/// 合约代码的交易所前缀（如`BINANCE.BTCUSDT` -> `BINANCE`）
+fn exchange_prefix(instrument: &str) -> Option<&str> {
+    instrument.split_once('.').map(|(exchange, _)| exchange)
+}
+
+/// 行情源应答订阅时使用的合约代码（去掉交易所前缀，如`SHFE.rb2410` -> `rb2410`）
+fn upstream_code(instrument: &str) -> &str {
+    instrument.rsplit('.').next().unwrap_or(instrument)
+}
+
+/// 已断开的会话，保留期内其订阅的合约不会从行情源退订
+struct DetachedSession {
+    instruments: HashSet<String>,
+    patterns: HashMap<String, HashSet<String>>,
+    fields: Option<HashSet<String>>,
+    throttle: Option<Duration>,
+    deadline: Instant,
+}
+
+impl Subscriber {
+    fn new(
+        addr: Recipient<MarketDataUpdateMessage>,
+        notify: Option<Recipient<SourceChanged>>,
+        remap: Option<Recipient<ContractRemapped>>,
+        disconnect: Option<Recipient<ForceDisconnect>>,
+    ) -> Self {
+        Self {
+            addr,
+            instruments: HashSet::new(),
+            throttle: None,
+            last_flush: Instant::now(),
+            pending: HashSet::new(),
+            notify,
+            remap,
+            disconnect,
+            patterns: HashMap::new(),
+            fields: None,
+            patch: None,
+            muted: HashSet::new(),
+        }
+    }
+
+    /// 行情由分发器合并推送（限速或补丁模式），不经过批量推送和分片
+    fn is_deferred(&self) -> bool {
+        self.throttle.is_some() || self.patch.is_some()
+    }
+
+    /// 是否向客户端推送该通道的事件
+    fn routes(&self, channel: Channel) -> bool {
+        !self.muted.contains(&channel)
+    }
+
+    /// 合约是否经某个通配符模式订阅
+    fn is_pattern_match(&self, instrument: &str) -> bool {
+        self.patterns.values().any(|matched| matched.contains(instrument))
+    }
+
+    /// 按客户端设置的字段裁剪行情JSON
+    fn project(&self, value: serde_json::Value) -> Option<serde_json::Value> {
+        project_fields(self.fields.as_ref(), value)
+    }
+
+    /// 分发分片需要的推送设置
+    fn shard_settings(&self) -> ShardClientSettings {
+        ShardClientSettings {
+            fields: self.fields.clone(),
+            deferred: self.is_deferred(),
+        }
+    }
+}
+
+/// 按字段裁剪行情JSON（instrument_id和延迟追踪始终保留）
+///
+/// 裁剪后没有任何行情字段时返回None，增量更新此时不需要推送
+pub(crate) fn project_fields(
+    fields: Option<&HashSet<String>>,
+    mut value: serde_json::Value,
+) -> Option<serde_json::Value> {
+    if let (Some(fields), Some(object)) = (fields, value.as_object_mut()) {
+        object.retain(|field, _| field == "instrument_id" || field == LATENCY_FIELD || fields.contains(field));
+    }
+    let has_fields = value
+        .as_object()
+        .is_some_and(|object| object.keys().any(|field| field != "instrument_id" && field != LATENCY_FIELD));
+    has_fields.then_some(value)
+}
+
+/// 按客户端设置的字段裁剪共用的增量（None表示推送全部字段，直接共用）
+pub(crate) fn project_delta(fields: Option<&HashSet<String>>, delta: &Arc<EncodedQuote>) -> Option<Arc<EncodedQuote>> {
+    match fields {
+        Some(fields) => project_fields(Some(fields), serde_json::Value::Object(delta.fields().clone()))
+            .map(|value| Arc::new(EncodedQuote::new(value))),
+        None => Some(delta.clone()),
+    }
+}
+
+/// 比较客户端上次收到的行情字段和最新字段，生成补丁操作
+pub(crate) fn diff_fields(
+    previous: &serde_json::Map<String, serde_json::Value>,
+    current: &serde_json::Map<String, serde_json::Value>,
+) -> Vec<PatchOp> {
+    let mut ops: Vec<PatchOp> = current
+        .iter()
+        .filter_map(|(field, value)| match previous.get(field) {
+            Some(old) if old == value => None,
+            Some(_) => Some(PatchOp::Replace {
+                path: format!("/{}", field),
+                value: value.clone(),
+            }),
+            None => Some(PatchOp::Add {
+                path: format!("/{}", field),
+                value: value.clone(),
+            }),
+        })
+        .collect();
+    ops.extend(
+        previous
+            .keys()
+            .filter(|field| !current.contains_key(*field))
+            .map(|field| PatchOp::Remove {
+                path: format!("/{}", field),
+            }),
+    );
+    ops
 }
 
 impl Actor for MarketDataDistributor {
@@ -69,7 +374,20 @@ impl Actor for MarketDataDistributor {
             if !act.batch_updates.is_empty() {
                 act.send_batch_updates();
             }
+            act.flush_throttled_clients();
+            act.expire_detached_sessions();
+            act.expire_lingering();
         });
+        
+        if self.failover.enabled {
+            info!(
+                "Source failover enabled: priority {:?}, stale after {}s",
+                self.failover.priority, self.failover.stale_secs
+            );
+            ctx.run_interval(Duration::from_secs(1), |act, _| {
+                act.check_source_failover();
+            });
+        }
     }
 
     fn stopped(&mut self, _: &mut Self::Context) {
@@ -89,19 +407,328 @@ impl MarketDataDistributor {
         Self {
             subscribers: HashMap::new(),
             instrument_subscribers: HashMap::new(),
-            #[cfg(feature = "ctp")]
-            ctp_actors: HashMap::new(),
-            #[cfg(feature = "qq")]
-            qq_actors: HashMap::new(),
-            #[cfg(feature = "sina")]
-            sina_actors: HashMap::new(),
+            md_actors: HashMap::new(),
+            load_sharing: None,
             market_data_cache: HashMap::new(),
+            recent_ticks: HashMap::new(),
+            replay_depth: 0,
             source_map: HashMap::new(),
+            upstream_acks: HashMap::new(),
             client_snapshots: HashMap::new(),
             batch_updates: HashMap::new(),
+            batch_traces: HashMap::new(),
             last_batch_send: Instant::now(),
             batch_interval: Duration::from_millis(100),
             batch_size_threshold: 50,
+            detached_sessions: HashMap::new(),
+            resume_grace: Duration::ZERO,
+            unsubscribe_linger: Duration::ZERO,
+            lingering: HashMap::new(),
+            cache_retention: None,
+            cache_expiry: HashMap::new(),
+            failover: FailoverConfig::default(),
+            source_last_tick: HashMap::new(),
+            tick_filter: TickFilter::new(),
+            dropped_tick_log: TickLogSampler::new(),
+            snapshot_sinks: HashMap::new(),
+            #[cfg(feature = "zmq-pub")]
+            tick_sinks: HashMap::new(),
+            options: OptionsConfig::default(),
+            instruments: Arc::new(InstrumentRegistry::new()),
+            option_analytics: HashMap::new(),
+            underlying_options: HashMap::new(),
+            pattern_subscribers: HashMap::new(),
+            max_pattern_matches: 500,
+            patch_refresh: Duration::from_secs(30),
+            clock_skew: ClockSkewEstimator::new(ClockSkewConfig::default()),
+            price_checker: PriceChecker::new(PriceCheckConfig::default()),
+            synthetic_engine: None,
+            dominant_engine: None,
+            dominant_contracts: HashMap::new(),
+            exchange_sources: HashMap::new(),
+            shards: Vec::new(),
+            _arbiters: Vec::new(),
+        }
+    }
+
+    /// 设置多数据源故障切换策略
+    pub fn with_failover(mut self, failover: FailoverConfig) -> Self {
+        self.failover = failover;
+        self
+    }
+
+    /// 为期权行情附加隐含波动率和希腊字母
+    pub fn with_options(mut self, options: OptionsConfig, instruments: Arc<InstrumentRegistry>) -> Self {
+        self.options = options;
+        self.instruments = instruments;
+        self
+    }
+
+    /// 设置断开的会话可恢复的保留时长
+    pub fn with_resume_grace(mut self, resume_grace: Duration) -> Self {
+        self.resume_grace = resume_grace;
+        self
+    }
+
+    /// 设置无人订阅的合约在上游保留订阅的时长，以及上游退订后保留行情缓存的时长
+    pub fn with_unsubscribe_linger(mut self, linger: Duration, cache_retention: Option<Duration>) -> Self {
+        self.unsubscribe_linger = linger;
+        self.cache_retention = cache_retention;
+        self
+    }
+
+    /// 设置单个通配符模式最多展开的合约数
+    pub fn with_max_pattern_matches(mut self, max_pattern_matches: usize) -> Self {
+        self.max_pattern_matches = max_pattern_matches;
+        self
+    }
+
+    /// 设置每个合约保留的最近行情笔数，客户端订阅时可要求回放（0为不保留）
+    pub fn with_replay_depth(mut self, replay_depth: usize) -> Self {
+        self.replay_depth = replay_depth;
+        self
+    }
+
+    /// 设置补丁模式客户端每个合约发送全量帧的间隔
+    pub fn with_patch_refresh(mut self, patch_refresh: Duration) -> Self {
+        self.patch_refresh = patch_refresh;
+        self
+    }
+
+    /// 设置时钟偏差估计和需要修正行情时间的数据源
+    pub fn with_clock_skew(mut self, config: ClockSkewConfig) -> Self {
+        self.clock_skew = ClockSkewEstimator::new(config);
+        self
+    }
+
+    /// 设置发布前的价格校验（最小变动价位和涨跌停板）
+    pub fn with_price_check(mut self, config: PriceCheckConfig) -> Self {
+        self.price_checker = PriceChecker::new(config);
+        self
+    }
+
+    /// 把向客户端推送增量行情的工作分摊到多个分片
+    ///
+    /// 每个分片运行在独立的Arbiter上，按合约哈希负责一部分合约；
+    /// 分片数不大于1时由分发器直接推送
+    pub fn with_fanout_shards(mut self, shards: usize) -> Self {
+        if shards > 1 {
+            for _ in 0..shards {
+                let arbiter = Arbiter::new();
+                self.shards.push(FanoutShard::start_in_arbiter(&arbiter.handle(), |_| FanoutShard::new()));
+                self._arbiters.push(arbiter);
+            }
+            info!("Distributor fan-out sharded across {} arbiters", shards);
+        }
+        self
+    }
+
+    /// 按负责的分片对合约分组
+    fn group_by_shard<'a>(&self, instruments: impl IntoIterator<Item = &'a String>) -> HashMap<usize, Vec<String>> {
+        let mut groups: HashMap<usize, Vec<String>> = HashMap::new();
+        for instrument in instruments {
+            groups
+                .entry(shard_index(instrument, self.shards.len()))
+                .or_default()
+                .push(instrument.clone());
+        }
+        groups
+    }
+
+    /// 把客户端新增的订阅登记到对应的分片
+    fn shard_subscribe(&self, client_id: &str, instruments: &[String]) {
+        let Some(subscriber) = self.subscribers.get(client_id) else {
+            return;
+        };
+        if self.shards.is_empty() || instruments.is_empty() {
+            return;
+        }
+        for (index, instruments) in self.group_by_shard(instruments) {
+            self.shards[index].do_send(ShardSubscribe {
+                client_id: client_id.to_string(),
+                addr: subscriber.addr.clone(),
+                settings: subscriber.shard_settings(),
+                instruments,
+            });
+        }
+    }
+
+    /// 同步客户端推送设置到所有分片
+    fn shard_update_client(&self, client_id: &str) {
+        let Some(subscriber) = self.subscribers.get(client_id) else {
+            return;
+        };
+        for shard in &self.shards {
+            shard.do_send(ShardUpdateClient {
+                client_id: client_id.to_string(),
+                settings: subscriber.shard_settings(),
+            });
+        }
+    }
+
+    /// 数据源的优先级（越小越优先，不在列表中的数据源排在最后）
+    fn source_rank(&self, source: MarketDataSource) -> usize {
+        self.failover
+            .priority
+            .iter()
+            .position(|s| *s == source)
+            .unwrap_or(self.failover.priority.len())
+    }
+
+    /// 数据源是否在过期时间内收到过该合约的行情
+    fn is_source_fresh(&self, instrument: &str, source: MarketDataSource, now: Instant) -> bool {
+        let stale_after = Duration::from_secs(self.failover.stale_secs);
+        self.source_last_tick
+            .get(instrument)
+            .and_then(|ticks| ticks.get(&source))
+            .is_some_and(|last| now.duration_since(*last) < stale_after)
+    }
+
+    /// 根据故障切换策略判断是否采用该数据源的行情
+    ///
+    /// 当前数据源之外的行情只在优先级更高时采用（主数据源恢复），
+    /// 否则仅记录收到时间，供故障切换检查使用
+    fn accept_tick_from(&mut self, instrument: &str, source: MarketDataSource) -> bool {
+        self.source_last_tick
+            .entry_ref(instrument)
+            .or_default()
+            .insert(source, Instant::now());
+        
+        let Some(&active) = self.source_map.get(instrument) else {
+            return true;
+        };
+        if active == source {
+            return true;
+        }
+        if self.source_rank(source) < self.source_rank(active) {
+            info!("Source {:?} recovered for {}, switching back from {:?}", source, instrument, active);
+            self.switch_source(instrument, active, source);
+            return true;
+        }
+        false
+    }
+
+    /// 检查订阅合约的当前数据源是否停止推送，必要时切换到备用数据源
+    fn check_source_failover(&mut self) {
+        let now = Instant::now();
+        let mut switches = Vec::new();
+        
+        for instrument in self.instrument_subscribers.keys() {
+            let Some(&active) = self.source_map.get(instrument) else {
+                continue;
+            };
+            if self.is_source_fresh(instrument, active, now) {
+                continue;
+            }
+            
+            // 优先切换到正在推送的数据源；备用数据源平时不订阅该合约，
+            // 没有时切换到优先级低于当前数据源的下一个已注册数据源，由switch_source订阅
+            let backup = self.failover
+                .priority
+                .iter()
+                .copied()
+                .filter(|source| *source != active)
+                .find(|source| self.is_source_fresh(instrument, *source, now))
+                .or_else(|| {
+                    self.failover
+                        .priority
+                        .iter()
+                        .copied()
+                        .filter(|source| self.source_rank(*source) > self.source_rank(active))
+                        .find(|source| self.actor_for_source(*source, instrument).is_some())
+                });
+            if let Some(backup) = backup {
+                switches.push((instrument.clone(), active, backup));
+            }
+        }
+        
+        for (instrument, from, to) in switches {
+            warn!(
+                "Source {:?} stale for {} (>{}s), failing over to {:?}",
+                from, instrument, self.failover.stale_secs, to
+            );
+            // 新订阅的数据源有一个过期周期的时间开始推送，之后仍无行情再切换到下一个
+            if !self.is_source_fresh(&instrument, to, now) {
+                self.source_last_tick.entry_ref(instrument.as_str()).or_default().insert(to, now);
+            }
+            self.switch_source(&instrument, from, to);
+        }
+    }
+
+    /// 切换合约的数据源并通知订阅该合约的客户端
+    fn switch_source(&mut self, instrument: &str, from: MarketDataSource, to: MarketDataSource) {
+        self.source_map.insert(instrument.to_string(), to);
+        // 不同数据源的时间戳不可比较，切换后重新开始过滤
+        self.tick_filter.reset(instrument);
+        
+        // 注册了备用数据源Actor时确保其已订阅该合约
+        if let Some(actor) = self.actor_for_source(to, instrument) {
+            actor.do_send(Subscribe {
+                id: uuid::Uuid::nil(),
+                instruments: vec![instrument.to_string()],
+            });
+        }
+        
+        let Some(client_ids) = self.instrument_subscribers.get(instrument) else {
+            return;
+        };
+        let notification = SourceChanged {
+            instrument: instrument.to_string(),
+            from,
+            to,
+        };
+        for client_id in client_ids {
+            let subscriber = self.subscribers.get(client_id).filter(|s| s.routes(Channel::Status));
+            if let Some(notify) = subscriber.and_then(|s| s.notify.as_ref()) {
+                if let Err(e) = notify.try_send(notification.clone()) {
+                    error!("Failed to send source change to client {}: {}", client_id, e);
+                }
+            }
+        }
+    }
+
+    /// 获取指定数据源中负责该合约的Actor（负载分担时为分配到的账户，否则为第一个Actor）
+    fn actor_for_source(&self, source: MarketDataSource, instrument: &str) -> Option<Addr<crate::actors::md_actor::MarketDataActor>> {
+        let actors = self.md_actors.get(&source)?;
+        self.load_sharing
+            .as_ref()
+            .and_then(|sharing| sharing.owner(instrument))
+            .and_then(|owner| actors.get(owner))
+            .or_else(|| actors.values().next())
+            .cloned()
+    }
+
+    /// 导出当前订阅状态
+    fn export_state(&self) -> DistributorState {
+        DistributorState {
+            instrument_subscribers: self.instrument_subscribers
+                .iter()
+                .map(|(instrument, clients)| (instrument.clone(), clients.iter().cloned().collect()))
+                .collect(),
+            source_map: self.source_map
+                .iter()
+                .filter(|(instrument, _)| self.instrument_subscribers.contains_key(*instrument))
+                .map(|(instrument, source)| (instrument.clone(), *source))
+                .collect(),
+            saved_at: chrono::Utc::now(),
+        }
+    }
+
+    /// 释放保留期内没有恢复的会话的订阅
+    fn expire_detached_sessions(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self.detached_sessions
+            .iter()
+            .filter(|(_, session)| now >= session.deadline)
+            .map(|(client_id, _)| client_id.clone())
+            .collect();
+        
+        for client_id in expired {
+            if let Some(session) = self.detached_sessions.remove(&client_id) {
+                let instruments: Vec<String> = session.instruments.into_iter().collect();
+                debug!("Session {} was not resumed, releasing {} instruments", client_id, instruments.len());
+                self.remove_subscription(&client_id, &instruments);
+            }
         }
     }
 
@@ -112,6 +739,8 @@ impl MarketDataDistributor {
         
         // Collect instruments with cached data for later use
         let mut instruments_with_data = Vec::new();
+        // 新订阅的合约（同步到分片）
+        let mut new_instruments = Vec::new();
         
         if let Some(subscriber) = self.subscribers.get_mut(client_id) {
             // 更新现有订阅者的订阅
@@ -129,6 +758,15 @@ impl MarketDataDistributor {
                 
                 // 如果是新订阅的合约，需要发送全量数据
                 if is_new_subscription {
+                    // 已映射的主力合约先告知客户端当前的合约月份
+                    if let (Some(remap), Some(contract)) = (&subscriber.remap, self.dominant_contracts.get(instrument)) {
+                        let _ = remap.try_send(ContractRemapped {
+                            instrument: instrument.clone(),
+                            from: None,
+                            to: contract.clone(),
+                        });
+                    }
+                    new_instruments.push(instrument.clone());
                     if let Some(data) = self.market_data_cache.get(instrument) {
                         instruments_with_data.push((instrument.clone(), data.clone()));
                     }
@@ -136,6 +774,15 @@ impl MarketDataDistributor {
             }
         }
         
+        self.shard_subscribe(client_id, &new_instruments);
+        
+        // 补丁模式客户端的新合约以全量帧推送
+        if let Some(subscriber) = self.subscribers.get_mut(client_id).filter(|s| s.patch.is_some()) {
+            subscriber.pending.extend(instruments_with_data.into_iter().map(|(instrument, _)| instrument));
+            self.flush_client_pending(client_id);
+            return;
+        }
+        
         // 为新订阅的合约发送全量数据
         if !instruments_with_data.is_empty() {
             if let Some(subscriber) = self.subscribers.get(client_id) {
@@ -144,9 +791,10 @@ impl MarketDataDistributor {
                 
                 // 构建全量数据
                 for (instrument, data) in &instruments_with_data {
-                    let json_data = self.snapshot_to_json(data);
-                    data_map.insert(instrument.clone(), json_data.to_string());
-                    update_instruments.push(instrument.clone());
+                    if let Some(json_data) = subscriber.project(self.snapshot_to_json(data)) {
+                        data_map.insert(instrument.clone(), Arc::new(EncodedQuote::new(json_data)));
+                        update_instruments.push(instrument.clone());
+                    }
                     
                     // 更新客户端快照
                     self.client_snapshots
@@ -172,61 +820,142 @@ impl MarketDataDistributor {
 
     /// 删除订阅
     fn remove_subscription(&mut self, client_id: &str, instruments: &[String]) {
-        if let Some(subscriber) = self.subscribers.get_mut(client_id) {
-            // 从订阅者中移除订阅
-            for instrument in instruments {
+        if !self.shards.is_empty() {
+            for (index, instruments) in self.group_by_shard(instruments) {
+                self.shards[index].do_send(ShardUnsubscribe {
+                    client_id: client_id.to_string(),
+                    instruments,
+                });
+            }
+        }
+        
+        // 从订阅者中移除订阅（已断开的客户端没有订阅者，只释放合约）
+        for instrument in instruments {
+            if let Some(subscriber) = self.subscribers.get_mut(client_id) {
                 subscriber.instruments.remove(instrument);
+                subscriber.pending.remove(instrument);
+                if let Some(patch) = &mut subscriber.patch {
+                    patch.sent.remove(instrument);
+                    patch.last_full.remove(instrument);
+                }
+            }
+            
+            // 更新合约订阅关系
+            if let Some(subscribers) = self.instrument_subscribers.get_mut(instrument) {
+                subscribers.remove(client_id);
                 
-                // 更新合约订阅关系
-                if let Some(subscribers) = self.instrument_subscribers.get_mut(instrument) {
-                    subscribers.remove(client_id);
+                // 如果没有订阅者了，则考虑取消订阅该合约（从行情源）
+                if subscribers.is_empty() {
+                    self.instrument_subscribers.remove(instrument);
+                    self.source_last_tick.remove(instrument);
                     
-                    // 如果没有订阅者了，则考虑取消订阅该合约（从行情源）
-                    if subscribers.is_empty() {
-                        self.instrument_subscribers.remove(instrument);
-                        
-                        // 根据数据来源取消订阅合约
-                        if let Some(source) = self.source_map.get(instrument) {
-                            match source {
-                                #[cfg(feature = "ctp")]
-                                MarketDataSource::CTP => {
-                                    for (_, actor) in &self.ctp_actors {
-                                        actor.do_send(Unsubscribe {
-                                            id: uuid::Uuid::nil(),
-                                            instruments: vec![instrument.clone()],
-                                        });
-                                    }
-                                },
-                                #[cfg(feature = "qq")]
-                                MarketDataSource::QQ => {
-                                    for (_, actor) in &self.qq_actors {
-                                        actor.do_send(Unsubscribe {
-                                            id: uuid::Uuid::nil(),
-                                            instruments: vec![instrument.clone()],
-                                        });
-                                    }
-                                },
-                                #[cfg(feature = "sina")]
-                                MarketDataSource::Sina => {
-                                    for (_, actor) in &self.sina_actors {
-                                        actor.do_send(Unsubscribe {
-                                            id: uuid::Uuid::nil(),
-                                            instruments: vec![instrument.clone()],
-                                        });
-                                    }
-                                },
-                                #[allow(unreachable_patterns)]
-                                _ => {
-                                    warn!("Unknown market data source for instrument {}", instrument);
-                                }
-                            }
-                        }
+                    // 短时间内可能被重新订阅，暂不退订上游
+                    if !self.unsubscribe_linger.is_zero() {
+                        self.lingering.insert(instrument.clone(), Instant::now() + self.unsubscribe_linger);
+                        continue;
+                    }
+                    self.unsubscribe_upstream(instrument);
+                }
+            }
+        }
+    }
+
+    /// 向行情源退订无人订阅的合约，并按保留策略处理其行情缓存
+    fn unsubscribe_upstream(&mut self, instrument: &str) {
+        // 退订期间的行情缺失，再次订阅后从新的行情开始保留
+        self.recent_ticks.remove(instrument);
+        match self.cache_retention {
+            Some(retention) if retention.is_zero() => {
+                self.market_data_cache.remove(instrument);
+            }
+            Some(retention) => {
+                self.cache_expiry.insert(instrument.to_string(), Instant::now() + retention);
+            }
+            None => {}
+        }
+        
+        if is_synthetic(instrument) {
+            if let Some(engine) = &self.synthetic_engine {
+                engine.do_send(RemoveSynthetic {
+                    instrument: instrument.to_string(),
+                });
+            }
+            return;
+        }
+        
+        if is_dominant(instrument) {
+            self.dominant_contracts.remove(instrument);
+            if let Some(engine) = &self.dominant_engine {
+                engine.do_send(RemoveDominant {
+                    instrument: instrument.to_string(),
+                });
+            }
+            return;
+        }
+        
+        if let Some(source) = exchange_prefix(instrument).and_then(|exchange| self.exchange_sources.get(exchange)) {
+            source.unsubscribe.do_send(Unsubscribe {
+                id: uuid::Uuid::nil(),
+                instruments: vec![instrument.to_string()],
+            });
+            return;
+        }
+        
+        // 根据数据来源取消订阅合约
+        if let Some(source) = self.source_map.get(instrument) {
+            self.upstream_acks.remove(&(*source, upstream_code(instrument).to_string()));
+            match source {
+                MarketDataSource::CTP | MarketDataSource::QQ | MarketDataSource::Sina => {
+                    for actor in self.md_actors.get(source).into_iter().flat_map(|actors| actors.values()) {
+                        actor.do_send(Unsubscribe {
+                            id: uuid::Uuid::nil(),
+                            instruments: vec![instrument.to_string()],
+                        });
                     }
+                },
+                // 轮询数据源按当前订阅拉取行情，无需取消订阅
+                MarketDataSource::SinaHttp => {}
+                // 交易所行情源未注册（已在上面处理注册的情况）
+                MarketDataSource::Binance => {}
+                MarketDataSource::Replay => {
+                    warn!("Unknown market data source for instrument {}", instrument);
                 }
             }
         }
     }
 
+    /// 退订保留期已过的合约，清除保留期已过的行情缓存
+    fn expire_lingering(&mut self) {
+        if self.lingering.is_empty() && self.cache_expiry.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .lingering
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(instrument, _)| instrument.clone())
+            .collect();
+        for instrument in expired {
+            self.lingering.remove(&instrument);
+            debug!("Unsubscribing {} upstream, no subscriber after {:?}", instrument, self.unsubscribe_linger);
+            self.unsubscribe_upstream(&instrument);
+        }
+        
+        let subscribed = &self.instrument_subscribers;
+        let cache = &mut self.market_data_cache;
+        self.cache_expiry.retain(|instrument, deadline| {
+            if subscribed.contains_key(instrument) {
+                return false;
+            }
+            if *deadline <= now {
+                cache.remove(instrument);
+                return false;
+            }
+            true
+        });
+    }
+
     /// 向客户端发送市场数据
     fn send_market_data_to_client(&self, client_id: &str, instrument: &str, data: &qamd_rs::MDSnapshot) {
         if let Some(subscriber) = self.subscribers.get(client_id) {
@@ -266,7 +995,7 @@ impl MarketDataDistributor {
                 
                 // 构建市场数据更新消息
                 let mut data_map = HashMap::new();
-                data_map.insert(instrument.to_string(), data_json.to_string());
+                data_map.insert(instrument.to_string(), Arc::new(EncodedQuote::new(data_json)));
                 
                 let message = MarketDataUpdateMessage {
                     instruments: vec![instrument.to_string()],
@@ -295,48 +1024,20 @@ impl MarketDataDistributor {
     }
 
     /// 查找合适的Actor处理订阅请求
+    ///
+    /// 优先使用合约当前的数据源，否则按故障切换配置的优先级选择
     fn find_actor_for_instrument(&self, instrument: &str) -> Option<(Addr<crate::actors::md_actor::MarketDataActor>, MarketDataSource)> {
         // 首先检查该合约是否已经有数据源
-        if let Some(source) = self.source_map.get(instrument) {
-            match source {
-                #[cfg(feature = "ctp")]
-                MarketDataSource::CTP => {
-                    if let Some((_, actor)) = self.ctp_actors.iter().next() {
-                        return Some((actor.clone(), MarketDataSource::CTP));
-                    }
-                },
-                #[cfg(feature = "qq")]
-                MarketDataSource::QQ => {
-                    if let Some((_, actor)) = self.qq_actors.iter().next() {
-                        return Some((actor.clone(), MarketDataSource::QQ));
-                    }
-                },
-                #[cfg(feature = "sina")]
-                MarketDataSource::Sina => {
-                    if let Some((_, actor)) = self.sina_actors.iter().next() {
-                        return Some((actor.clone(), MarketDataSource::Sina));
-                    }
-                },
-                #[allow(unreachable_patterns)]
-                _ => {}
+        if let Some(&source) = self.source_map.get(instrument) {
+            if let Some(actor) = self.actor_for_source(source, instrument) {
+                return Some((actor, source));
             }
         }
         
-        // 简化：由于构建时只会启用一个feature，直接返回对应类型的第一个actor即可
-        
-        #[cfg(feature = "ctp")]
-        if let Some((_, actor)) = self.ctp_actors.iter().next() {
-            return Some((actor.clone(), MarketDataSource::CTP));
-        }
-        
-        #[cfg(feature = "qq")]
-        if let Some((_, actor)) = self.qq_actors.iter().next() {
-            return Some((actor.clone(), MarketDataSource::QQ));
-        }
-        
-        #[cfg(feature = "sina")]
-        if let Some((_, actor)) = self.sina_actors.iter().next() {
-            return Some((actor.clone(), MarketDataSource::Sina));
+        for &source in &self.failover.priority {
+            if let Some(actor) = self.actor_for_source(source, instrument) {
+                return Some((actor, source));
+            }
         }
         
         // 没有找到合适的数据源
@@ -344,25 +1045,171 @@ impl MarketDataDistributor {
         None
     }
 
-    /// 检查两个快照之间的字段变化，返回变化的字段及值
-    fn compare_snapshot(&self, old_data: &qamd_rs::MDSnapshot, new_data: &qamd_rs::MDSnapshot) -> HashMap<String, serde_json::Value> {
-        let mut changes = HashMap::new();
-        
-        // 检查所有字段是否有变化
-        if old_data.last_price != new_data.last_price {
-            changes.insert("last_price".to_string(), json!(new_data.last_price));
+    /// 向行情源订阅合约（同一数据源的合约合并为一次订阅请求）
+    fn subscribe_upstream(&mut self, instruments: &[String]) {
+        let mut requests: HashMap<MarketDataSource, (Addr<crate::actors::md_actor::MarketDataActor>, Vec<String>)> = HashMap::new();
+        let mut exchange_requests: HashMap<String, Vec<String>> = HashMap::new();
+        for instrument in instruments {
+            // 等待退订的合约仍在上游订阅中
+            if self.lingering.remove(instrument).is_some() {
+                debug!("Instrument {} resubscribed while lingering", instrument);
+                continue;
+            }
+            
+            // 合成合约由合成合约计算Actor订阅各腿
+            if is_synthetic(instrument) {
+                match &self.synthetic_engine {
+                    Some(engine) => engine.do_send(DefineSynthetic {
+                        instrument: instrument.clone(),
+                    }),
+                    None => warn!("Synthetic instrument engine not running, cannot subscribe {}", instrument),
+                }
+                continue;
+            }
+            
+            // 主力合约由主力合约映射Actor订阅各合约月份
+            if is_dominant(instrument) {
+                match &self.dominant_engine {
+                    Some(engine) => engine.do_send(DefineDominant {
+                        instrument: instrument.clone(),
+                    }),
+                    None => warn!("Dominant contract engine not running, cannot subscribe {}", instrument),
+                }
+                continue;
+            }
+            
+            // 交易所行情源（如Binance）直接接收该交易所合约的订阅
+            if let Some((exchange, source)) = exchange_prefix(instrument)
+                .and_then(|exchange| self.exchange_sources.get_key_value(exchange))
+                .map(|(exchange, source)| (exchange.clone(), source.source))
+            {
+                self.source_map.insert(instrument.clone(), source);
+                exchange_requests.entry(exchange).or_default().push(instrument.clone());
+                continue;
+            }
+            
+            match self.find_actor_for_instrument(instrument) {
+                Some((actor, source)) => {
+                    // 之前的应答作废，等待本次订阅的结果
+                    self.upstream_acks.remove(&(source, upstream_code(instrument).to_string()));
+                    self.source_map.insert(instrument.clone(), source);
+                    requests.entry(source).or_insert_with(|| (actor, Vec::new())).1.push(instrument.clone());
+                }
+                None => warn!("No suitable market data actor found for instrument {}", instrument),
+            }
         }
         
-        if old_data.pre_settlement != new_data.pre_settlement {
-            changes.insert("pre_settlement".to_string(), json!(new_data.pre_settlement));
+        for (actor, instruments) in requests.into_values() {
+            actor.do_send(Subscribe {
+                id: uuid::Uuid::nil(),
+                instruments,
+            });
         }
-        
-        if old_data.pre_close != new_data.pre_close {
-            changes.insert("pre_close".to_string(), json!(new_data.pre_close));
+        for (exchange, instruments) in exchange_requests {
+            if let Some(source) = self.exchange_sources.get(&exchange) {
+                source.subscribe.do_send(Subscribe {
+                    id: uuid::Uuid::nil(),
+                    instruments,
+                });
+            }
         }
-        
-        if old_data.pre_open_interest != new_data.pre_open_interest {
-            changes.insert("pre_open_interest".to_string(), json!(new_data.pre_open_interest));
+    }
+
+    /// 首次收到行情的合约加入匹配的通配符模式订阅
+    fn match_pattern_subscriptions(&mut self, instrument: &str) {
+        let mut matched_clients = Vec::new();
+        for (pattern, clients) in &self.pattern_subscribers {
+            if !matches_pattern(pattern, instrument) {
+                continue;
+            }
+            for client_id in clients {
+                let Some(subscriber) = self.subscribers.get_mut(client_id) else {
+                    continue;
+                };
+                if subscriber.instruments.contains(instrument) {
+                    continue;
+                }
+                let Some(matched) = subscriber.patterns.get_mut(pattern) else {
+                    continue;
+                };
+                if matched.len() >= self.max_pattern_matches {
+                    debug!("Pattern {} of client {} reached {} instruments, skipping {}", pattern, client_id, self.max_pattern_matches, instrument);
+                    continue;
+                }
+                matched.insert(instrument.to_string());
+                matched_clients.push(client_id.clone());
+            }
+        }
+        
+        for client_id in matched_clients {
+            debug!("Instrument {} matched a pattern subscription of client {}", instrument, client_id);
+            self.add_subscription(&client_id, &[instrument.to_string()]);
+        }
+    }
+
+    /// 重新计算受该合约行情影响的期权分析（期权本身或以其为标的的期权）
+    ///
+    /// 返回结果有变化的期权及需要推送的字段
+    fn refresh_option_analytics(&mut self, instrument: &str) -> Vec<(String, HashMap<String, serde_json::Value>)> {
+        let mut targets: Vec<String> = self.underlying_options
+            .get(instrument)
+            .map(|options| options.iter().cloned().collect())
+            .unwrap_or_default();
+        targets.push(instrument.to_string());
+        
+        let mut updates = Vec::new();
+        for option in targets {
+            let Some((contract, underlying)) = self.instruments.option_contract(&option) else {
+                continue;
+            };
+            let Some(snapshot) = self.market_data_cache.get(&option) else {
+                continue;
+            };
+            
+            // 行情中的合约代码可能带交易所前缀，标的按相同规则查找
+            let underlying = match option.split_once('.') {
+                Some((exchange, _)) if !underlying.contains('.') => format!("{}.{}", exchange, underlying),
+                _ => underlying,
+            };
+            let Some(underlying_price) = self.market_data_cache.get(&underlying).map(Arc::as_ref).and_then(quote_price) else {
+                self.underlying_options.entry(underlying).or_default().insert(option);
+                continue;
+            };
+            
+            let analytics = contract.analyze(snapshot, underlying_price, self.options.risk_free_rate);
+            self.underlying_options.entry(underlying).or_default().insert(option.clone());
+            let Some(analytics) = analytics else {
+                continue;
+            };
+            if self.option_analytics.get(&option) == Some(&analytics) {
+                continue;
+            }
+            
+            updates.push((option.clone(), option_fields(&analytics)));
+            self.option_analytics.insert(option, analytics);
+        }
+        updates
+    }
+
+    /// 检查两个快照之间的字段变化，返回变化的字段及值
+    fn compare_snapshot(&self, old_data: &qamd_rs::MDSnapshot, new_data: &qamd_rs::MDSnapshot) -> HashMap<String, serde_json::Value> {
+        let mut changes = HashMap::new();
+        
+        // 检查所有字段是否有变化
+        if old_data.last_price != new_data.last_price {
+            changes.insert("last_price".to_string(), json!(new_data.last_price));
+        }
+        
+        if old_data.pre_settlement != new_data.pre_settlement {
+            changes.insert("pre_settlement".to_string(), json!(new_data.pre_settlement));
+        }
+        
+        if old_data.pre_close != new_data.pre_close {
+            changes.insert("pre_close".to_string(), json!(new_data.pre_close));
+        }
+        
+        if old_data.pre_open_interest != new_data.pre_open_interest {
+            changes.insert("pre_open_interest".to_string(), json!(new_data.pre_open_interest));
         }
         
         if old_data.open != new_data.open {
@@ -489,6 +1336,10 @@ impl MarketDataDistributor {
             changes.insert("average".to_string(), json!(new_data.average));
         }
         
+        if old_data.iopv != new_data.iopv {
+            changes.insert("iopv".to_string(), json!(new_data.iopv));
+        }
+        
         if old_data.datetime != new_data.datetime {
             changes.insert("datetime".to_string(), json!(new_data.datetime.clone()));
         }
@@ -498,7 +1349,7 @@ impl MarketDataDistributor {
     
     /// 将数据转换为完整的JSON
     fn snapshot_to_json(&self, data: &qamd_rs::MDSnapshot) -> serde_json::Value {
-        json!({
+        let mut value = json!({
             "instrument_id": data.instrument_id.clone(),
             "last_price": data.last_price,
             "pre_settlement": data.pre_settlement,
@@ -536,7 +1387,26 @@ impl MarketDataDistributor {
             "ask_volume5": data.ask_volume5,
             "average": data.average,
             "datetime": data.datetime.clone()
-        })
+        });
+        // ETF和期权的字段只在适用时推送
+        if let Some(fields) = value.as_object_mut() {
+            if !data.iopv.is_null() {
+                fields.insert("iopv".to_string(), json!(data.iopv));
+            }
+            if let Some(class) = data.ins_class {
+                fields.insert("ins_class".to_string(), json!(class));
+            }
+            if let Some(underlying) = &data.underlying_symbol {
+                fields.insert("underlying_symbol".to_string(), json!(underlying));
+            }
+            if let Some(strike) = data.strike_price {
+                fields.insert("strike_price".to_string(), json!(strike));
+            }
+        }
+        if let Some(analytics) = self.option_analytics.get(&data.instrument_id) {
+            self.apply_changes_to_json(&mut value, &option_fields(analytics));
+        }
+        value
     }
 
     /// 应用增量更新到JSON数据
@@ -548,6 +1418,52 @@ impl MarketDataDistributor {
         }
     }
 
+    /// 本批次有变化且有客户端订阅的合约的增量（包含instrument_id，延迟追踪时附带推送时间）
+    fn batch_deltas(&self, traces: &HashMap<String, serde_json::Value>) -> Vec<(String, Arc<EncodedQuote>)> {
+        let mut deltas = Vec::new();
+        for (instrument, changes) in &self.batch_updates {
+            if changes.is_empty() || !self.instrument_subscribers.contains_key(instrument) {
+                continue;
+            }
+            let mut instrument_data = serde_json::Value::Object(serde_json::Map::new());
+            
+            // 确保instrument_id字段始终存在
+            if !changes.contains_key("instrument_id") {
+                instrument_data["instrument_id"] = json!(instrument);
+            }
+            
+            // 应用所有变化
+            self.apply_changes_to_json(&mut instrument_data, changes);
+            if let Some(trace) = traces.get(instrument) {
+                instrument_data[LATENCY_FIELD] = trace.clone();
+            }
+            if let Some(delta) = project_fields(None, instrument_data) {
+                deltas.push((instrument.clone(), Arc::new(EncodedQuote::new(delta))));
+            }
+        }
+        deltas
+    }
+    
+    /// 记录随本批次推送给客户端的快照（限速和补丁模式客户端在推送时记录）
+    fn record_client_snapshots(&mut self) {
+        for (client_id, subscriber) in &self.subscribers {
+            if subscriber.is_deferred() {
+                continue;
+            }
+            for (instrument, changes) in &self.batch_updates {
+                if changes.is_empty() || !subscriber.instruments.contains(instrument) {
+                    continue;
+                }
+                if let Some(market_data) = self.market_data_cache.get(instrument) {
+                    self.client_snapshots
+                        .entry_ref(client_id.as_str())
+                        .or_default()
+                        .insert(instrument.clone(), market_data.clone());
+                }
+            }
+        }
+    }
+    
     /// 是否满足批量发送的条件
     fn should_send_batch(&self) -> bool {
         Instant::now().duration_since(self.last_batch_send) > self.batch_interval ||
@@ -563,14 +1479,60 @@ impl MarketDataDistributor {
         // 获取所有有更新的合约
         let instruments_with_updates: HashSet<String> = self.batch_updates.keys().cloned().collect();
         
+        // 延迟追踪记录推送时间，随增量JSON传给客户端会话
+        let traces: HashMap<String, serde_json::Value> = self.batch_traces
+            .drain()
+            .map(|(instrument, mut trace)| {
+                trace.record_dispatch();
+                (instrument, trace.to_value())
+            })
+            .collect();
+        
+        // 限速和补丁模式客户端只记录待推送合约，由分发器合并发送
+        for subscriber in self.subscribers.values_mut() {
+            if subscriber.is_deferred() {
+                let updated: Vec<String> = subscriber.instruments
+                    .intersection(&instruments_with_updates)
+                    .cloned()
+                    .collect();
+                subscriber.pending.extend(updated);
+            }
+        }
+        
+        // 未限速的补丁模式客户端随本批次推送，限速的由flush_throttled_clients推送
+        let patch_clients: Vec<String> = self.subscribers
+            .iter()
+            .filter(|(_, s)| s.patch.is_some() && s.throttle.is_none() && !s.pending.is_empty())
+            .map(|(client_id, _)| client_id.clone())
+            .collect();
+        for client_id in patch_clients {
+            self.flush_client_pending(&client_id);
+        }
+        
+        // 每个合约只构建一次增量，各客户端共用其编码结果
+        let deltas = self.batch_deltas(&traces);
+        self.record_client_snapshots();
+        
+        // 分片模式下由分片推送给客户端
+        if !self.shards.is_empty() {
+            let mut shard_updates: Vec<Vec<(String, Arc<EncodedQuote>)>> = vec![Vec::new(); self.shards.len()];
+            for (instrument, delta) in deltas {
+                shard_updates[shard_index(&instrument, self.shards.len())].push((instrument, delta));
+            }
+            for (shard, updates) in self.shards.iter().zip(shard_updates) {
+                if !updates.is_empty() {
+                    shard.do_send(ShardFanout { updates });
+                }
+            }
+            self.batch_updates.clear();
+            self.last_batch_send = Instant::now();
+            return;
+        }
+        let deltas: HashMap<String, Arc<EncodedQuote>> = deltas.into_iter().collect();
+        
         // 遍历所有客户端，发送订阅的更新
         for (client_id, subscriber) in &self.subscribers {
-            // 找出该客户端订阅的且有更新的合约
-            let client_instruments: HashSet<_> = subscriber.instruments
-                .intersection(&instruments_with_updates)
-                .collect();
-                
-            if client_instruments.is_empty() {
+            if subscriber.is_deferred() {
                 continue;
             }
             
@@ -578,34 +1540,15 @@ impl MarketDataDistributor {
             let mut data_map = HashMap::new();
             let mut update_instruments = Vec::new();
             
-            for &instrument in &client_instruments {
-                if let Some(changes) = self.batch_updates.get(instrument) {
-                    if changes.is_empty() {
-                        continue;
-                    }
-                    
-                    // 为每个合约构造增量JSON
-                    let mut instrument_data = serde_json::Value::Object(serde_json::Map::new());
-                    
-                    // 确保instrument_id字段始终存在
-                    if !changes.contains_key("instrument_id") {
-                        instrument_data["instrument_id"] = json!(instrument);
-                    }
-                    
-                    // 应用所有变化
-                    self.apply_changes_to_json(&mut instrument_data, changes);
-                    
-                    // 添加到数据映射
-                    data_map.insert(instrument.to_string(), instrument_data.to_string());
-                    update_instruments.push(instrument.to_string());
-                    
-                    // 更新客户端快照
-                    if let Some(market_data) = self.market_data_cache.get(instrument) {
-                        self.client_snapshots
-                            .entry(client_id.clone())
-                            .or_insert_with(HashMap::new)
-                            .insert(instrument.to_string(), market_data.clone());
-                    }
+            for instrument in &subscriber.instruments {
+                let Some(delta) = deltas.get(instrument.as_str()) else {
+                    continue;
+                };
+                
+                // 添加到数据映射（客户端不关注的字段变化不推送）
+                if let Some(payload) = project_delta(subscriber.fields.as_ref(), delta) {
+                    data_map.insert(instrument.clone(), payload);
+                    update_instruments.push(instrument.clone());
                 }
             }
             
@@ -628,6 +1571,208 @@ impl MarketDataDistributor {
         self.batch_updates.clear();
         self.last_batch_send = Instant::now();
     }
+
+    /// 向到达推送时间的限速客户端发送合并后的行情
+    fn flush_throttled_clients(&mut self) {
+        let now = Instant::now();
+        let due_clients: Vec<String> = self.subscribers
+            .iter()
+            .filter(|(_, s)| {
+                !s.pending.is_empty()
+                    && s.throttle.is_some_and(|interval| now.duration_since(s.last_flush) >= interval)
+            })
+            .map(|(client_id, _)| client_id.clone())
+            .collect();
+        
+        for client_id in due_clients {
+            self.flush_client_pending(&client_id);
+        }
+    }
+    
+    /// 发送客户端积压的合约行情
+    ///
+    /// 每个合约只发送相对客户端上次收到的快照的变化字段，
+    /// 因此中间被合并掉的行情不会丢失最终状态
+    fn flush_client_pending(&mut self, client_id: &str) {
+        let pending: Vec<String> = match self.subscribers.get_mut(client_id) {
+            Some(subscriber) => {
+                subscriber.last_flush = Instant::now();
+                subscriber.pending.drain().collect()
+            }
+            None => return,
+        };
+        if self.subscribers.get(client_id).is_some_and(|s| s.patch.is_some()) {
+            self.send_patch_frames(client_id, pending);
+            return;
+        }
+        
+        let mut data_map = HashMap::new();
+        let mut update_instruments = Vec::new();
+        let Some(subscriber) = self.subscribers.get(client_id) else {
+            return;
+        };
+        
+        for instrument in pending {
+            let Some(latest) = self.market_data_cache.get(&instrument) else {
+                continue;
+            };
+            
+            let previous = self.client_snapshots
+                .get(client_id)
+                .and_then(|snapshots| snapshots.get(&instrument));
+            let json_data = match previous {
+                Some(old_snapshot) => {
+                    let mut changes = self.compare_snapshot(old_snapshot, latest);
+                    if let Some(analytics) = self.option_analytics.get(&instrument) {
+                        changes.extend(option_fields(analytics));
+                    }
+                    if changes.is_empty() {
+                        continue;
+                    }
+                    let mut json_data = serde_json::Value::Object(serde_json::Map::new());
+                    json_data["instrument_id"] = json!(instrument);
+                    self.apply_changes_to_json(&mut json_data, &changes);
+                    json_data
+                }
+                None => self.snapshot_to_json(latest),
+            };
+            
+            if let Some(json_data) = subscriber.project(json_data) {
+                data_map.insert(instrument.clone(), Arc::new(EncodedQuote::new(json_data)));
+                update_instruments.push(instrument.clone());
+            }
+            
+            let latest = latest.clone();
+            self.client_snapshots
+                .entry(client_id.to_string())
+                .or_default()
+                .insert(instrument, latest);
+        }
+        
+        if update_instruments.is_empty() {
+            return;
+        }
+        
+        let message = MarketDataUpdateMessage {
+            instruments: update_instruments,
+            data: data_map,
+        };
+        if let Err(e) = subscriber.addr.try_send(message) {
+            error!("Failed to send conflated update to client {}: {}", client_id, e);
+        }
+    }
+    
+    /// 向补丁模式客户端发送合约行情帧
+    ///
+    /// 每个合约与上次发送给客户端的字段比较，只发送变化的字段；
+    /// 首次推送或距上次全量帧超过刷新间隔时发送全量帧，使客户端丢帧后也能恢复一致
+    fn send_patch_frames(&mut self, client_id: &str, instruments: Vec<String>) {
+        let Some(subscriber) = self.subscribers.get(client_id) else {
+            return;
+        };
+        let quotes: Vec<(String, serde_json::Map<String, serde_json::Value>)> = instruments
+            .into_iter()
+            .filter_map(|instrument| {
+                let latest = self.market_data_cache.get(&instrument)?;
+                match subscriber.project(self.snapshot_to_json(latest))? {
+                    serde_json::Value::Object(quote) => Some((instrument, quote)),
+                    _ => None,
+                }
+            })
+            .collect();
+        
+        let refresh = self.patch_refresh;
+        let Some(patch) = self.subscribers.get_mut(client_id).and_then(|s| s.patch.as_mut()) else {
+            return;
+        };
+        let now = Instant::now();
+        let mut frames = Vec::new();
+        for (instrument, quote) in quotes {
+            let refresh_due = patch
+                .last_full
+                .get(&instrument)
+                .is_none_or(|last| now.duration_since(*last) >= refresh);
+            let frame = match patch.sent.get(&instrument) {
+                Some(previous) if !refresh_due => {
+                    let ops = diff_fields(previous, &quote);
+                    if ops.is_empty() {
+                        continue;
+                    }
+                    QuoteFrame::Patch {
+                        instrument_id: instrument.clone(),
+                        seq: 0,
+                        ops,
+                    }
+                }
+                _ => {
+                    patch.last_full.insert(instrument.clone(), now);
+                    QuoteFrame::Full {
+                        instrument_id: instrument.clone(),
+                        seq: 0,
+                        quote: quote.clone(),
+                    }
+                }
+            };
+            patch.sent.insert(instrument, quote);
+            frames.push(frame);
+        }
+        
+        if frames.is_empty() {
+            return;
+        }
+        if let Err(e) = patch.addr.try_send(QuotePatchUpdate { frames }) {
+            // 客户端没有收到这些帧，各合约的下一帧改为全量帧
+            error!("Failed to send quote frames to client {}: {}", client_id, e);
+            patch.sent.clear();
+        }
+    }
+    
+    /// 重新推送客户端已订阅合约的全量数据（instruments为空表示全部已订阅合约）
+    fn resend_full_snapshots(&mut self, client_id: &str, instruments: &[String]) {
+        let Some(subscriber) = self.subscribers.get_mut(client_id) else {
+            return;
+        };
+        let resent: Vec<String> = subscriber
+            .instruments
+            .iter()
+            .filter(|instrument| instruments.is_empty() || instruments.contains(instrument))
+            .cloned()
+            .collect();
+        if let Some(patch) = &mut subscriber.patch {
+            // 清空已发送的字段，各合约以全量帧推送
+            for instrument in &resent {
+                patch.sent.remove(instrument);
+            }
+            subscriber.pending.extend(resent);
+            self.flush_client_pending(client_id);
+            return;
+        }
+        
+        let Some(subscriber) = self.subscribers.get(client_id) else {
+            return;
+        };
+        let mut data_map = HashMap::new();
+        let mut update_instruments = Vec::new();
+        for instrument in resent {
+            let Some(data) = self.market_data_cache.get(&instrument) else {
+                continue;
+            };
+            if let Some(json_data) = subscriber.project(self.snapshot_to_json(data)) {
+                data_map.insert(instrument.clone(), Arc::new(EncodedQuote::new(json_data)));
+                update_instruments.push(instrument);
+            }
+        }
+        if update_instruments.is_empty() {
+            return;
+        }
+        let message = MarketDataUpdateMessage {
+            instruments: update_instruments,
+            data: data_map,
+        };
+        if let Err(e) = subscriber.addr.try_send(message) {
+            error!("Failed to send full snapshot to client {}: {}", client_id, e);
+        }
+    }
 }
 
 // 处理市场数据更新消息
@@ -635,9 +1780,66 @@ impl Handler<MarketDataUpdate> for MarketDataDistributor {
     type Result = ();
 
     fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
-        let (data, source) = (msg.0, msg.1);
+        self.on_market_data_guarded(msg.0, msg.1, None);
+    }
+}
+
+// 处理带延迟追踪的市场数据更新消息
+impl Handler<TracedMarketDataUpdate> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: TracedMarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let mut trace = msg.2;
+        trace.dist = latency::now_us();
+        self.on_market_data_guarded(msg.0, msg.1, Some(trace));
+    }
+}
+
+impl MarketDataDistributor {
+    /// 处理一条行情，panic时记为崩溃并丢弃这条行情，分发器继续运行
+    fn on_market_data_guarded(&mut self, data: Arc<qamd_rs::MDSnapshot>, source: MarketDataSource, trace: Option<LatencyTrace>) {
+        let instrument = data.instrument_id.clone();
+        if let Err(event) = supervision::catch_panic("distributor", None, || self.on_market_data(data, source, trace)) {
+            warn!("Dropped market data for {} after panic: {}", instrument, event.message);
+        }
+    }
+
+
+    /// 处理一条行情：过滤、计算增量并加入批量更新
+    fn on_market_data(&mut self, mut data: Arc<qamd_rs::MDSnapshot>, source: MarketDataSource, trace: Option<LatencyTrace>) {
         let instrument = data.instrument_id.clone();
         
+        // 所有数据源的行情都参与时钟偏差估计
+        self.clock_skew.observe(source, data.datetime, chrono::Utc::now());
+        
+        // 故障切换模式下只转发当前数据源的行情
+        if self.failover.enabled && !self.accept_tick_from(&instrument, source) {
+            return;
+        }
+        
+        // 丢弃重连后重复推送或乱序的行情
+        let verdict = self.tick_filter.check(&data);
+        if !verdict.is_accepted() {
+            if tracing::enabled!(Level::DEBUG) {
+                if let Some(ticks) = self.dropped_tick_log.sample(&instrument) {
+                    debug!(%instrument, ?source, ticks, "Dropped {:?} tick", verdict);
+                }
+            }
+            return;
+        }
+        
+        // 价格不是最小变动价位的整数倍或超出涨跌停板时按配置计数、修正或丢弃
+        if self.price_checker.enabled() && !self.price_checker.check(&mut data, &self.instruments) {
+            debug!(%instrument, ?source, "Dropped tick with invalid prices");
+            return;
+        }
+        
+        // 按估计的偏差把行情时间修正到本地时钟
+        let corrected = self.clock_skew.correct(source, data.datetime);
+        if corrected != data.datetime {
+            Arc::make_mut(&mut data).datetime = corrected;
+        }
+        
         // 检查是否需要计算增量更新
         let mut changes = HashMap::new();
         if let Some(old_data) = self.market_data_cache.get(&instrument) {
@@ -648,7 +1850,25 @@ impl Handler<MarketDataUpdate> for MarketDataDistributor {
             if changes.is_empty() {
                 return;
             }
+            
+            // 与上一笔行情之间有成交时推送逐笔成交
+            #[cfg(feature = "zmq-pub")]
+            if !self.tick_sinks.is_empty() {
+                let tick = qamd_rs::Tick::from_snapshot_pair(old_data, &data);
+                if tick.has_trade() {
+                    for (name, sink) in &self.tick_sinks {
+                        if let Err(e) = sink.try_send(TickUpdate(tick.clone())) {
+                            warn!("Failed to forward {} to tick sink {}: {}", instrument, name, e);
+                        }
+                    }
+                }
+            }
         } else {
+            // 新合约加入匹配的通配符模式订阅（随本次行情推送全量数据）
+            if !self.pattern_subscribers.is_empty() {
+                self.match_pattern_subscriptions(&instrument);
+            }
+            
             // 新合约，所有字段都是变化的
             changes = self.snapshot_to_json(&data)
                 .as_object()
@@ -658,12 +1878,36 @@ impl Handler<MarketDataUpdate> for MarketDataDistributor {
                 .collect();
         }
         
+        // 转发给行情输出
+        for (name, sink) in &self.snapshot_sinks {
+            if let Err(e) = sink.try_send(MarketDataUpdate(data.clone(), source)) {
+                warn!("Failed to forward {} to snapshot sink {}: {}", instrument, name, e);
+            }
+        }
+        
         // 更新缓存
+        if self.replay_depth > 0 {
+            let ticks = self.recent_ticks.entry_ref(instrument.as_str()).or_default();
+            if ticks.len() >= self.replay_depth {
+                ticks.pop_front();
+            }
+            ticks.push_back(data.clone());
+        }
         self.market_data_cache.insert(instrument.clone(), data.clone());
         self.source_map.insert(instrument.clone(), source);
         
-        // 添加到批量更新缓存
+        // 添加到批量更新缓存（同一批次内保留最新一笔行情的追踪）
         self.batch_updates.insert(instrument.clone(), changes);
+        if let Some(trace) = trace {
+            self.batch_traces.insert(instrument.clone(), trace);
+        }
+        
+        // 期权及以该合约为标的的期权附加隐含波动率和希腊字母
+        if self.options.enabled {
+            for (option, fields) in self.refresh_option_analytics(&instrument) {
+                self.batch_updates.entry(option).or_default().extend(fields);
+            }
+        }
         
         // 检查是否应立即发送批量更新
         if self.should_send_batch() {
@@ -680,10 +1924,7 @@ impl Handler<RegisterDataReceiver> for MarketDataDistributor {
         let client_id = msg.client_id.clone();
         
         // 创建新的订阅者
-        let subscriber = Subscriber {
-            addr: msg.addr,
-            instruments: HashSet::new(),
-        };
+        let subscriber = Subscriber::new(msg.addr, msg.notify, msg.remap, msg.disconnect);
         
         // 保存订阅者信息
         self.subscribers.insert(client_id.clone(), subscriber);
@@ -694,23 +1935,7 @@ impl Handler<RegisterDataReceiver> for MarketDataDistributor {
         // 添加订阅
         if !msg.instruments.is_empty() {
             self.add_subscription(&client_id, &msg.instruments);
-            
-            // 处理每个合约的订阅
-            for instrument in &msg.instruments {
-                // 查找合适的Actor处理订阅请求
-                if let Some((actor, source)) = self.find_actor_for_instrument(instrument) {
-                    // 记录数据源
-                    self.source_map.insert(instrument.clone(), source);
-                    
-                    // 发送订阅请求
-                    actor.do_send(Subscribe {
-                        id: uuid::Uuid::nil(),
-                        instruments: vec![instrument.clone()],
-                    });
-                } else {
-                    warn!("No suitable market data actor found for instrument {}", instrument);
-                }
-            }
+            self.subscribe_upstream(&msg.instruments);
         }
     }
 }
@@ -721,6 +1946,38 @@ impl Handler<UnregisterDataReceiver> for MarketDataDistributor {
 
     fn handle(&mut self, msg: UnregisterDataReceiver, _: &mut Self::Context) -> Self::Result {
         if let Some(subscriber) = self.subscribers.remove(&msg.client_id) {
+            // 客户端已离线，分片不再向其推送
+            for shard in &self.shards {
+                shard.do_send(ShardUnregister {
+                    client_id: msg.client_id.clone(),
+                });
+            }
+            
+            // 清理通配符模式索引
+            for pattern in subscriber.patterns.keys() {
+                if let Some(clients) = self.pattern_subscribers.get_mut(pattern) {
+                    clients.remove(&msg.client_id);
+                    if clients.is_empty() {
+                        self.pattern_subscribers.remove(pattern);
+                    }
+                }
+            }
+            
+            // 清理客户端快照
+            self.client_snapshots.remove(&msg.client_id);
+            
+            // 保留会话的订阅，客户端在保留期内可以带会话ID重新连接
+            if !self.resume_grace.is_zero() && !subscriber.instruments.is_empty() {
+                self.detached_sessions.insert(msg.client_id, DetachedSession {
+                    instruments: subscriber.instruments,
+                    patterns: subscriber.patterns,
+                    fields: subscriber.fields,
+                    throttle: subscriber.throttle,
+                    deadline: Instant::now() + self.resume_grace,
+                });
+                return;
+            }
+            
             // 获取客户端订阅的所有合约
             let instruments: Vec<String> = subscriber.instruments.into_iter().collect();
             
@@ -728,14 +1985,80 @@ impl Handler<UnregisterDataReceiver> for MarketDataDistributor {
             if !instruments.is_empty() {
                 self.remove_subscription(&msg.client_id, &instruments);
             }
-            
-            // 清理客户端快照
-            self.client_snapshots.remove(&msg.client_id);
         }
     }
 }
 
+// 处理会话恢复消息
+impl Handler<ResumeSession> for MarketDataDistributor {
+    type Result = Option<ResumedSession>;
+
+    fn handle(&mut self, msg: ResumeSession, _: &mut Self::Context) -> Self::Result {
+        // 会话仍在线时不允许另一个连接接管
+        if self.subscribers.contains_key(&msg.client_id) {
+            warn!("Session {} is still connected, refusing to resume it", msg.client_id);
+            return None;
+        }
+        
+        // 断线的会话，或从持久化状态恢复、尚未重新连接的客户端
+        let session = self.detached_sessions.remove(&msg.client_id)?;
+        
+        let mut subscriber = Subscriber::new(msg.addr, msg.notify, msg.remap, msg.disconnect);
+        subscriber.fields = session.fields.clone();
+        subscriber.throttle = session.throttle;
+        subscriber.patterns = session.patterns;
+        for pattern in subscriber.patterns.keys() {
+            self.pattern_subscribers
+                .entry(pattern.clone())
+                .or_default()
+                .insert(msg.client_id.clone());
+        }
+        let patterns: Vec<String> = subscriber.patterns.keys().cloned().collect();
+        self.subscribers.insert(msg.client_id.clone(), subscriber);
+        self.client_snapshots.entry(msg.client_id.clone()).or_default();
+        
+        // 重新建立订阅并推送各合约最新的缓存行情
+        let instruments: Vec<String> = session.instruments.into_iter().collect();
+        self.add_subscription(&msg.client_id, &instruments);
+        info!(
+            "Resumed session {} with {} instruments and {} patterns",
+            msg.client_id, instruments.len(), patterns.len()
+        );
+        
+        Some(ResumedSession {
+            instruments,
+            patterns,
+            fields: session.fields,
+            throttle: session.throttle,
+        })
+    }
+}
+
 // 处理订阅更新消息
+impl Handler<GetRecentTicks> for MarketDataDistributor {
+    type Result = MessageResult<GetRecentTicks>;
+
+    fn handle(&mut self, msg: GetRecentTicks, _: &mut Self::Context) -> Self::Result {
+        let Some(subscriber) = self.subscribers.get(&msg.client_id) else {
+            return MessageResult(Vec::new());
+        };
+        let replay = msg
+            .instruments
+            .into_iter()
+            .filter_map(|instrument| {
+                let ticks = self.recent_ticks.get(&instrument)?;
+                let ticks: Vec<serde_json::Value> = ticks
+                    .iter()
+                    .skip(ticks.len().saturating_sub(msg.depth))
+                    .filter_map(|tick| subscriber.project(self.snapshot_to_json(tick)))
+                    .collect();
+                (!ticks.is_empty()).then_some((instrument, ticks))
+            })
+            .collect();
+        MessageResult(replay)
+    }
+}
+
 impl Handler<UpdateSubscription> for MarketDataDistributor {
     type Result = ();
 
@@ -751,32 +2074,17 @@ impl Handler<UpdateSubscription> for MarketDataDistributor {
                 .cloned()
                 .collect();
             
-            // 计算需要移除的合约
+            // 计算需要移除的合约（经通配符模式订阅的合约由UnsubscribePattern移除）
             let to_remove: Vec<String> = current_instruments
                 .difference(&new_instruments)
+                .filter(|instrument| !subscriber.is_pattern_match(instrument))
                 .cloned()
                 .collect();
             
             // 添加新订阅
             if !to_add.is_empty() {
                 self.add_subscription(&msg.client_id, &to_add);
-                
-                // 处理每个合约的订阅
-                for instrument in &to_add {
-                    // 查找合适的Actor处理订阅请求
-                    if let Some((actor, source)) = self.find_actor_for_instrument(instrument) {
-                        // 记录数据源
-                        self.source_map.insert(instrument.clone(), source);
-                        
-                        // 发送订阅请求
-                        actor.do_send(Subscribe {
-                            id: uuid::Uuid::nil(),
-                            instruments: vec![instrument.clone()],
-                        });
-                    } else {
-                        warn!("No suitable market data actor found for instrument {}", instrument);
-                    }
-                }
+                self.subscribe_upstream(&to_add);
             }
             
             // 移除旧订阅
@@ -787,73 +2095,372 @@ impl Handler<UpdateSubscription> for MarketDataDistributor {
     }
 }
 
-// 处理订阅查询消息
-impl Handler<QuerySubscription> for MarketDataDistributor {
-    type Result = Vec<String>;
+// 处理合成合约计算Actor注册消息
+impl Handler<RegisterSyntheticEngine> for MarketDataDistributor {
+    type Result = ();
 
-    fn handle(&mut self, msg: QuerySubscription, _: &mut Self::Context) -> Self::Result {
-        if let Some(subscriber) = self.subscribers.get(&msg.client_id) {
-            subscriber.instruments.iter().cloned().collect()
-        } else {
+    fn handle(&mut self, msg: RegisterSyntheticEngine, _: &mut Self::Context) -> Self::Result {
+        // 恢复的订阅中可能已有合成合约
+        for instrument in self.instrument_subscribers.keys().filter(|instrument| is_synthetic(instrument)) {
+            msg.addr.do_send(DefineSynthetic {
+                instrument: instrument.clone(),
+            });
+        }
+        self.synthetic_engine = Some(msg.addr);
+    }
+}
+
+// 处理主力合约映射Actor注册消息
+impl Handler<RegisterDominantEngine> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RegisterDominantEngine, _: &mut Self::Context) -> Self::Result {
+        // 恢复的订阅中可能已有主力合约
+        for instrument in self.instrument_subscribers.keys().filter(|instrument| is_dominant(instrument)) {
+            msg.addr.do_send(DefineDominant {
+                instrument: instrument.clone(),
+            });
+        }
+        self.dominant_engine = Some(msg.addr);
+    }
+}
+
+// 处理主力合约切换消息，通知订阅了该主力合约的客户端
+impl Handler<ContractRemapped> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ContractRemapped, _: &mut Self::Context) -> Self::Result {
+        info!("Dominant contract {} mapped from {:?} to {}", msg.instrument, msg.from, msg.to);
+        self.dominant_contracts.insert(msg.instrument.clone(), msg.to.clone());
+        // 新合约月份的最新行情可能早于旧合约月份的最后一笔
+        self.tick_filter.reset(&msg.instrument);
+        
+        let Some(client_ids) = self.instrument_subscribers.get(&msg.instrument) else {
+            return;
+        };
+        for client_id in client_ids {
+            if let Some(remap) = self.subscribers.get(client_id).and_then(|s| s.remap.as_ref()) {
+                if let Err(e) = remap.try_send(msg.clone()) {
+                    error!("Failed to send contract remap to client {}: {}", client_id, e);
+                }
+            }
+        }
+    }
+}
+
+// 处理交易所行情源注册消息
+impl Handler<RegisterExchangeSource> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RegisterExchangeSource, _: &mut Self::Context) -> Self::Result {
+        info!("Registered {:?} as the market data source of exchange {}", msg.source, msg.exchange);
+        
+        // 恢复的订阅中可能已有该交易所的合约
+        let instruments: Vec<String> = self.instrument_subscribers
+            .keys()
+            .filter(|instrument| exchange_prefix(instrument) == Some(msg.exchange.as_str()))
+            .cloned()
+            .collect();
+        for instrument in &instruments {
+            self.source_map.insert(instrument.clone(), msg.source);
+        }
+        if !instruments.is_empty() {
+            msg.subscribe.do_send(Subscribe {
+                id: uuid::Uuid::nil(),
+                instruments,
+            });
+        }
+        
+        self.exchange_sources.insert(msg.exchange, ExchangeSource {
+            source: msg.source,
+            subscribe: msg.subscribe,
+            unsubscribe: msg.unsubscribe,
+        });
+    }
+}
+
+// 处理通配符模式订阅消息
+impl Handler<SubscribePattern> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribePattern, _: &mut Self::Context) -> Self::Result {
+        for pattern in msg.patterns {
+            let Some(subscriber) = self.subscribers.get(&msg.client_id) else {
+                return;
+            };
+            if subscriber.patterns.contains_key(&pattern) {
+                continue;
+            }
+            
+            // 已知的匹配合约：合约信息中的合约和已经收到过行情的合约
+            let mut matched: Vec<String> = self.instruments.match_pattern(&pattern);
+            matched.extend(
+                self.market_data_cache
+                    .keys()
+                    .filter(|instrument| matches_pattern(&pattern, instrument))
+                    .cloned(),
+            );
+            matched.sort();
+            matched.dedup();
+            if matched.len() > self.max_pattern_matches {
+                warn!(
+                    "Pattern {} of client {} matches {} instruments, only the first {} are subscribed",
+                    pattern, msg.client_id, matched.len(), self.max_pattern_matches
+                );
+                matched.truncate(self.max_pattern_matches);
+            }
+            info!("Client {} subscribed to pattern {} ({} instruments)", msg.client_id, pattern, matched.len());
+            
+            let to_add: Vec<String> = matched
+                .iter()
+                .filter(|instrument| !subscriber.instruments.contains(*instrument))
+                .cloned()
+                .collect();
+            if let Some(subscriber) = self.subscribers.get_mut(&msg.client_id) {
+                subscriber.patterns.insert(pattern.clone(), matched.into_iter().collect());
+            }
+            self.pattern_subscribers
+                .entry(pattern)
+                .or_default()
+                .insert(msg.client_id.clone());
+            
+            if !to_add.is_empty() {
+                self.add_subscription(&msg.client_id, &to_add);
+                self.subscribe_upstream(&to_add);
+            }
+        }
+    }
+}
+
+// 处理取消通配符模式订阅消息
+impl Handler<UnsubscribePattern> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnsubscribePattern, _: &mut Self::Context) -> Self::Result {
+        for pattern in msg.patterns {
+            let Some(subscriber) = self.subscribers.get_mut(&msg.client_id) else {
+                return;
+            };
+            let Some(matched) = subscriber.patterns.remove(&pattern) else {
+                continue;
+            };
+            
+            if let Some(clients) = self.pattern_subscribers.get_mut(&pattern) {
+                clients.remove(&msg.client_id);
+                if clients.is_empty() {
+                    self.pattern_subscribers.remove(&pattern);
+                }
+            }
+            
+            // 仍被其他模式匹配的合约保留
+            let to_remove: Vec<String> = matched
+                .into_iter()
+                .filter(|instrument| !subscriber.is_pattern_match(instrument))
+                .collect();
+            if !to_remove.is_empty() {
+                self.remove_subscription(&msg.client_id, &to_remove);
+            }
+        }
+    }
+}
+
+// 处理客户端限速设置消息
+impl Handler<SetClientThrottle> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetClientThrottle, _: &mut Self::Context) -> Self::Result {
+        if let Some(subscriber) = self.subscribers.get_mut(&msg.client_id) {
+            subscriber.throttle = msg.interval;
+            subscriber.last_flush = Instant::now();
+            info!("Client {} throttle set to {:?}", msg.client_id, msg.interval);
+        }
+        self.shard_update_client(&msg.client_id);
+        
+        // 取消限速时立即推送积压的行情
+        if msg.interval.is_none() {
+            self.flush_client_pending(&msg.client_id);
+        }
+    }
+}
+
+// 处理客户端推送字段设置消息
+impl Handler<SetClientFields> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetClientFields, _: &mut Self::Context) -> Self::Result {
+        let Some(subscriber) = self.subscribers.get_mut(&msg.client_id) else {
+            return;
+        };
+        if subscriber.fields == msg.fields {
+            return;
+        }
+        info!("Client {} fields set to {:?}", msg.client_id, msg.fields);
+        subscriber.fields = msg.fields;
+        self.shard_update_client(&msg.client_id);
+        
+        // 字段变化后重新推送已订阅合约的全量数据，使客户端获得新增的字段
+        self.resend_full_snapshots(&msg.client_id, &[]);
+    }
+}
+
+// 处理客户端补丁模式设置消息
+impl Handler<SetClientPatch> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetClientPatch, _: &mut Self::Context) -> Self::Result {
+        let Some(subscriber) = self.subscribers.get_mut(&msg.client_id) else {
+            return;
+        };
+        if subscriber.patch.is_some() == msg.addr.is_some() {
+            return;
+        }
+        info!(
+            "Client {} patch mode {}",
+            msg.client_id,
+            if msg.addr.is_some() { "enabled" } else { "disabled" }
+        );
+        subscriber.patch = msg.addr.map(|addr| ClientPatch {
+            addr,
+            sent: HashMap::new(),
+            last_full: HashMap::new(),
+        });
+        if subscriber.throttle.is_none() {
+            subscriber.pending.clear();
+        }
+        self.shard_update_client(&msg.client_id);
+        
+        // 切换后推送全量数据，客户端按新的格式重建行情状态
+        self.resend_full_snapshots(&msg.client_id, &[]);
+    }
+}
+
+// 处理客户端通道订阅设置消息
+impl Handler<SetClientChannels> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetClientChannels, _: &mut Self::Context) -> Self::Result {
+        let Some(subscriber) = self.subscribers.get_mut(&msg.client_id) else {
+            return;
+        };
+        debug!("Client {} muted channels {:?}", msg.client_id, msg.muted);
+        subscriber.muted = msg.muted;
+    }
+}
+
+// 处理客户端重新推送全量行情的请求
+impl Handler<ResendSnapshots> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ResendSnapshots, _: &mut Self::Context) -> Self::Result {
+        debug!("Client {} requested a resend of {} instruments", msg.client_id, msg.instruments.len());
+        self.resend_full_snapshots(&msg.client_id, &msg.instruments);
+    }
+}
+
+// 处理订阅查询消息
+impl Handler<QuerySubscription> for MarketDataDistributor {
+    type Result = Vec<String>;
+
+    fn handle(&mut self, msg: QuerySubscription, _: &mut Self::Context) -> Self::Result {
+        if let Some(subscriber) = self.subscribers.get(&msg.client_id) {
+            subscriber.instruments.iter().cloned().collect()
+        } else {
             Vec::new()
         }
     }
 }
 
+// 处理行情源的订阅应答
+impl Handler<UpstreamSubscribed> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpstreamSubscribed, _: &mut Self::Context) -> Self::Result {
+        let code = upstream_code(&msg.instrument).to_string();
+        self.upstream_acks.insert((msg.source, code), msg.error);
+    }
+}
+
+// 处理上游订阅状态查询
+impl Handler<QueryUpstreamStatus> for MarketDataDistributor {
+    type Result = MessageResult<QueryUpstreamStatus>;
+
+    fn handle(&mut self, msg: QueryUpstreamStatus, _: &mut Self::Context) -> Self::Result {
+        let statuses = msg
+            .instruments
+            .into_iter()
+            .map(|instrument| {
+                let status = self.upstream_status(&instrument);
+                (instrument, status)
+            })
+            .collect();
+        MessageResult(statuses)
+    }
+}
+
+impl MarketDataDistributor {
+    /// 合约的上游订阅状态：收到过行情或行情源确认即为成功，
+    /// 没有行情源应答的合约（合成合约、主力合约、交易所行情源等）在收到行情前一直等待
+    fn upstream_status(&self, instrument: &str) -> UpstreamStatus {
+        if self.market_data_cache.contains_key(instrument) {
+            return UpstreamStatus::Subscribed;
+        }
+        let Some(source) = self.source_map.get(instrument) else {
+            let routed = is_synthetic(instrument)
+                || is_dominant(instrument)
+                || exchange_prefix(instrument).is_some_and(|exchange| self.exchange_sources.contains_key(exchange));
+            return if routed {
+                UpstreamStatus::Pending
+            } else {
+                UpstreamStatus::Failed("no market data source for instrument".to_string())
+            };
+        };
+        match self.upstream_acks.get(&(*source, upstream_code(instrument).to_string())) {
+            Some(None) => UpstreamStatus::Subscribed,
+            Some(Some(error)) => UpstreamStatus::Failed(error.clone()),
+            None => UpstreamStatus::Pending,
+        }
+    }
+
+    /// 注册上游行情连接Actor，并把分发器地址注册到Actor
+    fn register_md_actor(
+        &mut self,
+        source: MarketDataSource,
+        broker_id: String,
+        addr: Addr<crate::actors::md_actor::MarketDataActor>,
+        ctx: &mut Context<Self>,
+    ) {
+        info!("Registered {:?} market data actor for broker {}", source, broker_id);
+        addr.do_send(RegisterDistributor {
+            addr: ctx.address(),
+        });
+        self.md_actors.entry(source).or_default().insert(broker_id, addr);
+    }
+}
+
 // 处理CTP市场数据Actor注册消息
-#[cfg(feature = "ctp")]
 impl Handler<RegisterCTPMdActor> for MarketDataDistributor {
     type Result = ();
 
     fn handle(&mut self, msg: RegisterCTPMdActor, ctx: &mut Self::Context) -> Self::Result {
-        // 注册CTP市场数据Actor
-        let broker_id = msg.broker_id.clone();
-        self.ctp_actors.insert(broker_id.clone(), msg.addr.clone());
-        
-        // 将分发器地址注册到Actor
-        msg.addr.do_send(RegisterDistributor {
-            addr: ctx.address(),
-        });
-        
-        info!("Registered CTP market data actor for broker {}", broker_id);
+        self.register_md_actor(MarketDataSource::CTP, msg.broker_id, msg.addr, ctx);
     }
 }
 
 // 处理QQ市场数据Actor注册消息
-#[cfg(feature = "qq")]
 impl Handler<RegisterQQMdActor> for MarketDataDistributor {
     type Result = ();
 
     fn handle(&mut self, msg: RegisterQQMdActor, ctx: &mut Self::Context) -> Self::Result {
-        // 注册QQ市场数据Actor
-        let broker_id = msg.broker_id.clone();
-        self.qq_actors.insert(broker_id.clone(), msg.addr.clone());
-        
-        // 将分发器地址注册到Actor
-        msg.addr.do_send(RegisterDistributor {
-            addr: ctx.address(),
-        });
-        
-        info!("Registered QQ market data actor for broker {}", broker_id);
+        self.register_md_actor(MarketDataSource::QQ, msg.broker_id, msg.addr, ctx);
     }
 }
 
 // 处理Sina市场数据Actor注册消息
-#[cfg(feature = "sina")]
 impl Handler<RegisterSinaMdActor> for MarketDataDistributor {
     type Result = ();
 
     fn handle(&mut self, msg: RegisterSinaMdActor, ctx: &mut Self::Context) -> Self::Result {
-        // 注册Sina市场数据Actor
-        let broker_id = msg.broker_id.clone();
-        self.sina_actors.insert(broker_id.clone(), msg.addr.clone());
-        
-        // 将分发器地址注册到Actor
-        msg.addr.do_send(RegisterDistributor {
-            addr: ctx.address(),
-        });
-        
-        info!("Registered Sina market data actor for broker {}", broker_id);
+        self.register_md_actor(MarketDataSource::Sina, msg.broker_id, msg.addr, ctx);
     }
 }
 
@@ -862,34 +2469,30 @@ impl Handler<RegisterMdActor> for MarketDataDistributor {
     type Result = ();
 
     fn handle(&mut self, msg: RegisterMdActor, ctx: &mut Self::Context) -> Self::Result {
-        // 根据数据源类型注册到不同的集合
-        let broker_id = msg.broker_id.clone();
-        match msg.source_type {
-            #[cfg(feature = "ctp")]
-            MarketDataSource::CTP => {
-                self.ctp_actors.insert(broker_id.clone(), msg.addr.clone());
-                info!("Registered CTP market data actor for broker {}", broker_id);
-            },
-            #[cfg(feature = "qq")]
-            MarketDataSource::QQ => {
-                self.qq_actors.insert(broker_id.clone(), msg.addr.clone());
-                info!("Registered QQ market data actor for broker {}", broker_id);
-            },
-            #[cfg(feature = "sina")]
-            MarketDataSource::Sina => {
-                self.sina_actors.insert(broker_id.clone(), msg.addr.clone());
-                info!("Registered Sina market data actor for broker {}", broker_id);
-            },
-            #[allow(unreachable_patterns)]
-            _ => {
-                warn!("Unknown market data source type {:?}", msg.source_type);
-            }
-        }
-        
-        // 将分发器地址注册到Actor
-        msg.addr.do_send(RegisterDistributor {
-            addr: ctx.address(),
-        });
+        self.register_md_actor(msg.source_type, msg.broker_id, msg.addr, ctx);
+    }
+}
+
+// 处理负载分担设置消息
+impl Handler<SetLoadSharing> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetLoadSharing, _: &mut Self::Context) -> Self::Result {
+        self.load_sharing = msg.sharing;
+    }
+}
+
+// 处理市场数据Actor注销消息
+impl Handler<UnregisterMdActor> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnregisterMdActor, _: &mut Self::Context) -> Self::Result {
+        for actors in self.md_actors.values_mut() {
+            if actors.remove(&msg.broker_id).is_some() {
+                info!("Unregistered market data actor for broker {}", msg.broker_id);
+            }
+        }
+        self.md_actors.retain(|_, actors| !actors.is_empty());
     }
 }
 
@@ -910,6 +2513,198 @@ impl Handler<GetAllSubscriptions> for MarketDataDistributor {
     }
 }
 
+// 处理行情输出注册消息
+impl Handler<RegisterSnapshotSink> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RegisterSnapshotSink, _: &mut Self::Context) -> Self::Result {
+        info!("Registered snapshot sink {}", msg.name);
+        self.snapshot_sinks.insert(msg.name, msg.addr);
+    }
+}
+
+// 处理逐笔成交输出注册消息
+#[cfg(feature = "zmq-pub")]
+impl Handler<RegisterTickSink> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RegisterTickSink, _: &mut Self::Context) -> Self::Result {
+        info!("Registered tick sink {}", msg.name);
+        self.tick_sinks.insert(msg.name, msg.addr);
+    }
+}
+
+// 处理注销行情输出消息
+impl Handler<UnregisterSnapshotSink> for MarketDataDistributor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnregisterSnapshotSink, _: &mut Self::Context) -> Self::Result {
+        if self.snapshot_sinks.remove(&msg.name).is_some() {
+            info!("Unregistered snapshot sink {}", msg.name);
+        }
+        #[cfg(feature = "zmq-pub")]
+        if self.tick_sinks.remove(&msg.name).is_some() {
+            info!("Unregistered tick sink {}", msg.name);
+        }
+    }
+}
+
+// 处理订阅状态查询消息
+impl Handler<GetDistributorState> for MarketDataDistributor {
+    type Result = MessageResult<GetDistributorState>;
+
+    fn handle(&mut self, _: GetDistributorState, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.export_state())
+    }
+}
+
+// 处理行情缓存查询消息
+impl Handler<GetSnapshotCache> for MarketDataDistributor {
+    type Result = MessageResult<GetSnapshotCache>;
+
+    fn handle(&mut self, _: GetSnapshotCache, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.market_data_cache.values().map(|snapshot| (**snapshot).clone()).collect())
+    }
+}
+
+// 处理行情过滤统计查询消息
+impl Handler<GetTickFilterStats> for MarketDataDistributor {
+    type Result = MessageResult<GetTickFilterStats>;
+
+    fn handle(&mut self, _: GetTickFilterStats, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.tick_filter.stats())
+    }
+}
+
+// 处理价格校验统计查询消息
+impl Handler<GetPriceCheckStats> for MarketDataDistributor {
+    type Result = MessageResult<GetPriceCheckStats>;
+
+    fn handle(&mut self, _: GetPriceCheckStats, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.price_checker.report())
+    }
+}
+
+// 处理时钟偏差查询消息
+impl Handler<GetClockSkew> for MarketDataDistributor {
+    type Result = MessageResult<GetClockSkew>;
+
+    fn handle(&mut self, _: GetClockSkew, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.clock_skew.report())
+    }
+}
+
+// 处理客户端数量查询消息
+impl Handler<GetClientCounts> for MarketDataDistributor {
+    type Result = MessageResult<GetClientCounts>;
+
+    fn handle(&mut self, _: GetClientCounts, _: &mut Self::Context) -> Self::Result {
+        let sessions = self
+            .subscribers
+            .values()
+            .filter(|subscriber| subscriber.disconnect.is_some())
+            .count();
+        MessageResult(ClientCounts {
+            sessions,
+            internal: self.subscribers.len() - sessions,
+            detached: self.detached_sessions.len(),
+            instruments: self.instrument_subscribers.len(),
+        })
+    }
+}
+
+// 处理管理接口强制断开客户端消息，返回客户端是否在线
+impl Handler<DisconnectClient> for MarketDataDistributor {
+    type Result = bool;
+
+    fn handle(&mut self, msg: DisconnectClient, _: &mut Self::Context) -> Self::Result {
+        let Some(disconnect) = self
+            .subscribers
+            .get(&msg.client_id)
+            .and_then(|subscriber| subscriber.disconnect.as_ref())
+        else {
+            return false;
+        };
+        info!("Disconnecting client {}: {}", msg.client_id, msg.reason);
+        disconnect.do_send(ForceDisconnect { reason: msg.reason });
+        true
+    }
+}
+
+// 处理保存订阅状态消息
+impl Handler<SaveDistributorState> for MarketDataDistributor {
+    type Result = GatewayResult<()>;
+
+    fn handle(&mut self, msg: SaveDistributorState, _: &mut Self::Context) -> Self::Result {
+        let state = self.export_state();
+        state.save(&msg.path)?;
+        info!(
+            "Saved subscription state for {} instruments to {}",
+            state.instrument_subscribers.len(),
+            msg.path.display()
+        );
+        Ok(())
+    }
+}
+
+// 处理恢复订阅状态消息
+impl Handler<RestoreDistributorState> for MarketDataDistributor {
+    type Result = Vec<String>;
+
+    fn handle(&mut self, msg: RestoreDistributorState, _: &mut Self::Context) -> Self::Result {
+        let state = msg.state;
+        
+        // 客户端以会话ID（rtn_session中的session_id）重新连接时恢复订阅，保留期内未恢复的由expire_detached_sessions释放
+        let deadline = Instant::now() + msg.grace;
+        let mut restored = 0;
+        for (instrument, clients) in state.instrument_subscribers {
+            let entry = self.instrument_subscribers.entry(instrument.clone()).or_default();
+            for client_id in clients {
+                if !self.subscribers.contains_key(&client_id) {
+                    let session = self.detached_sessions.entry(client_id.clone()).or_insert_with(|| {
+                        restored += 1;
+                        DetachedSession {
+                            instruments: HashSet::new(),
+                            patterns: HashMap::new(),
+                            fields: None,
+                            throttle: None,
+                            deadline,
+                        }
+                    });
+                    session.instruments.insert(instrument.clone());
+                }
+                entry.insert(client_id);
+            }
+        }
+        for (instrument, source) in state.source_map {
+            self.source_map.entry(instrument).or_insert(source);
+        }
+
+        let instruments: Vec<String> = self.instrument_subscribers.keys().cloned().collect();
+        if let Some(engine) = &self.synthetic_engine {
+            for instrument in instruments.iter().filter(|instrument| is_synthetic(instrument)) {
+                engine.do_send(DefineSynthetic {
+                    instrument: instrument.clone(),
+                });
+            }
+        }
+        if let Some(engine) = &self.dominant_engine {
+            for instrument in instruments.iter().filter(|instrument| is_dominant(instrument)) {
+                engine.do_send(DefineDominant {
+                    instrument: instrument.clone(),
+                });
+            }
+        }
+        info!(
+            "Restored subscription state saved at {}: {} instruments, {} clients",
+            state.saved_at,
+            instruments.len(),
+            restored
+        );
+        instruments
+    }
+}
+
 // 处理添加单个订阅消息
 impl Handler<AddSubscription> for MarketDataDistributor {
     type Result = ();
@@ -929,3 +2724,353 @@ impl Handler<RemoveSubscription> for MarketDataDistributor {
         self.remove_subscription(&msg.client_id.to_string(), &[msg.instrument.clone()]);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::md_actor::MarketDataActor;
+    use crate::config::BrokerConfig;
+    use crate::sources::MarketDataSourceAdapter;
+
+    const INSTRUMENT: &str = "SHFE.rb2410";
+
+    /// 不连接前置的行情源
+    struct IdleAdapter(MarketDataSource);
+
+    impl MarketDataSourceAdapter for IdleAdapter {
+        fn source(&self) -> MarketDataSource {
+            self.0
+        }
+
+        fn connect(&mut self, _: &str, _: Option<&Path>, _: Recipient<MarketDataEvent>) -> GatewayResult<()> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            false
+        }
+
+        fn login(&mut self, _: &BrokerConfig) -> GatewayResult<()> {
+            Ok(())
+        }
+
+        fn subscribe(&mut self, _: &[String]) -> GatewayResult<()> {
+            Ok(())
+        }
+
+        fn unsubscribe(&mut self, _: &[String]) -> GatewayResult<()> {
+            Ok(())
+        }
+
+        fn disconnect(&mut self) {}
+    }
+
+    fn md_actor(source: MarketDataSource) -> Addr<MarketDataActor> {
+        let config: BrokerConfig =
+            serde_json::from_value(json!({"name": "test", "front_addr": "tcp://127.0.0.1:1"})).unwrap();
+        MarketDataActor::new(config, Box::new(IdleAdapter(source))).start()
+    }
+
+    /// 订阅了INSTRUMENT、当前数据源为CTP且CTP的行情已过期的分发器
+    fn stale_primary() -> MarketDataDistributor {
+        let mut distributor = MarketDataDistributor::new().with_failover(FailoverConfig {
+            enabled: true,
+            stale_secs: 1,
+            priority: vec![MarketDataSource::CTP, MarketDataSource::QQ],
+        });
+        distributor
+            .instrument_subscribers
+            .insert(INSTRUMENT.to_string(), HashSet::from(["client".to_string()]));
+        distributor.source_map.insert(INSTRUMENT.to_string(), MarketDataSource::CTP);
+        distributor
+            .source_last_tick
+            .entry_ref(INSTRUMENT)
+            .or_default()
+            .insert(MarketDataSource::CTP, Instant::now() - Duration::from_secs(5));
+        distributor
+    }
+
+    #[actix_rt::test]
+    async fn stale_primary_fails_over_to_unsubscribed_backup() {
+        let mut distributor = stale_primary();
+        distributor
+            .md_actors
+            .entry(MarketDataSource::QQ)
+            .or_default()
+            .insert("backup".to_string(), md_actor(MarketDataSource::QQ));
+
+        // 备用数据源没有收到过行情，仍切换过去并订阅
+        distributor.check_source_failover();
+        assert_eq!(distributor.source_map.get(INSTRUMENT), Some(&MarketDataSource::QQ));
+
+        // 备用数据源在一个过期周期内保持不变
+        distributor.check_source_failover();
+        assert_eq!(distributor.source_map.get(INSTRUMENT), Some(&MarketDataSource::QQ));
+
+        // 主数据源恢复后切回
+        assert!(distributor.accept_tick_from(INSTRUMENT, MarketDataSource::CTP));
+        assert_eq!(distributor.source_map.get(INSTRUMENT), Some(&MarketDataSource::CTP));
+    }
+
+    /// 丢弃行情的客户端
+    struct Sink;
+
+    impl Actor for Sink {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<MarketDataUpdateMessage> for Sink {
+        type Result = ();
+
+        fn handle(&mut self, _: MarketDataUpdateMessage, _: &mut Self::Context) -> Self::Result {}
+    }
+
+    fn restore(distributor: &mut MarketDataDistributor, grace: Duration) {
+        let state = DistributorState {
+            instrument_subscribers: HashMap::from([(INSTRUMENT.to_string(), vec!["saved-session".to_string()])]),
+            source_map: HashMap::new(),
+            saved_at: chrono::Utc::now(),
+        };
+        let instruments = distributor.handle(RestoreDistributorState { state, grace }, &mut Context::new());
+        assert_eq!(instruments, vec![INSTRUMENT.to_string()]);
+    }
+
+    #[actix_rt::test]
+    async fn restored_client_resumes_with_saved_session_id() {
+        let mut distributor = MarketDataDistributor::new();
+        restore(&mut distributor, Duration::from_secs(60));
+        let resume = || ResumeSession {
+            client_id: "saved-session".to_string(),
+            addr: Sink.start().recipient(),
+            notify: None,
+            remap: None,
+            disconnect: None,
+        };
+
+        let resumed = distributor.handle(resume(), &mut Context::new()).unwrap();
+        assert_eq!(resumed.instruments, vec![INSTRUMENT.to_string()]);
+        assert!(distributor.subscribers.contains_key("saved-session"));
+        // 会话在线时不能再次恢复
+        assert!(distributor.handle(resume(), &mut Context::new()).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn restored_client_released_after_grace() {
+        let mut distributor = MarketDataDistributor::new();
+        restore(&mut distributor, Duration::ZERO);
+        distributor.expire_detached_sessions();
+        assert!(!distributor.instrument_subscribers.contains_key(INSTRUMENT));
+        assert!(distributor.detached_sessions.is_empty());
+    }
+
+    #[test]
+    fn stale_primary_stays_without_registered_backup() {
+        let mut distributor = stale_primary();
+        distributor.check_source_failover();
+        assert_eq!(distributor.source_map.get(INSTRUMENT), Some(&MarketDataSource::CTP));
+    }
+
+    /// 记录数据源切换通知次数的客户端
+    struct SourceWatcher(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Actor for SourceWatcher {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<SourceChanged> for SourceWatcher {
+        type Result = ();
+
+        fn handle(&mut self, _: SourceChanged, _: &mut Self::Context) -> Self::Result {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn muted_status_channel_skips_source_change() {
+        let notified = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut distributor = MarketDataDistributor::new();
+        let register = RegisterDataReceiver {
+            client_id: "client".to_string(),
+            addr: Sink.start().recipient(),
+            instruments: vec![INSTRUMENT.to_string()],
+            notify: Some(SourceWatcher(notified.clone()).start().recipient()),
+            remap: None,
+            disconnect: None,
+        };
+        distributor.handle(register, &mut Context::new());
+
+        distributor.switch_source(INSTRUMENT, MarketDataSource::CTP, MarketDataSource::QQ);
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(notified.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let mute = SetClientChannels {
+            client_id: "client".to_string(),
+            muted: HashSet::from([Channel::Status]),
+        };
+        distributor.handle(mute, &mut Context::new());
+        distributor.switch_source(INSTRUMENT, MarketDataSource::QQ, MarketDataSource::CTP);
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(notified.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[actix_rt::test]
+    async fn sharded_batch_records_client_snapshots() {
+        let mut distributor = MarketDataDistributor::new().with_fanout_shards(2);
+        let register = RegisterDataReceiver {
+            client_id: "client".to_string(),
+            addr: Sink.start().recipient(),
+            instruments: vec![INSTRUMENT.to_string()],
+            notify: None,
+            remap: None,
+            disconnect: None,
+        };
+        distributor.handle(register, &mut Context::new());
+        let snapshot = Arc::new(qamd_rs::MDSnapshot {
+            instrument_id: INSTRUMENT.to_string(),
+            last_price: 3500.0,
+            ..Default::default()
+        });
+        distributor.market_data_cache.insert(INSTRUMENT.to_string(), snapshot.clone());
+        distributor
+            .batch_updates
+            .insert(INSTRUMENT.to_string(), HashMap::from([("last_price".to_string(), json!(3500.0))]));
+
+        distributor.send_batch_updates();
+        assert_eq!(distributor.client_snapshots["client"].get(INSTRUMENT), Some(&snapshot));
+        assert!(distributor.batch_updates.is_empty());
+    }
+
+    fn register(distributor: &mut MarketDataDistributor, client_id: &str) {
+        let register = RegisterDataReceiver {
+            client_id: client_id.to_string(),
+            addr: Sink.start().recipient(),
+            instruments: vec![INSTRUMENT.to_string()],
+            notify: None,
+            remap: None,
+            disconnect: None,
+        };
+        distributor.handle(register, &mut Context::new());
+    }
+
+    fn resume(client_id: &str) -> ResumeSession {
+        ResumeSession {
+            client_id: client_id.to_string(),
+            addr: Sink.start().recipient(),
+            notify: None,
+            remap: None,
+            disconnect: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn detached_session_resumes_with_its_settings() {
+        let mut distributor = MarketDataDistributor::new().with_resume_grace(Duration::from_secs(60));
+        distributor
+            .market_data_cache
+            .insert("DCE.m2409".to_string(), Arc::new(qamd_rs::MDSnapshot::default()));
+        register(&mut distributor, "client");
+        let pattern = SubscribePattern {
+            client_id: "client".to_string(),
+            patterns: vec!["DCE.*".to_string()],
+        };
+        distributor.handle(pattern, &mut Context::new());
+        let fields = HashSet::from(["last_price".to_string()]);
+        let set_fields = SetClientFields {
+            client_id: "client".to_string(),
+            fields: Some(fields.clone()),
+        };
+        distributor.handle(set_fields, &mut Context::new());
+        let throttle = SetClientThrottle {
+            client_id: "client".to_string(),
+            interval: Some(Duration::from_millis(500)),
+        };
+        distributor.handle(throttle, &mut Context::new());
+
+        distributor.handle(UnregisterDataReceiver { client_id: "client".to_string() }, &mut Context::new());
+        assert!(!distributor.subscribers.contains_key("client"));
+        assert!(!distributor.pattern_subscribers.contains_key("DCE.*"));
+        // 保留期内合约仍保持订阅
+        assert!(distributor.instrument_subscribers[INSTRUMENT].contains("client"));
+        distributor.expire_detached_sessions();
+        assert_eq!(distributor.detached_sessions.len(), 1);
+
+        let mut resumed = distributor.handle(resume("client"), &mut Context::new()).unwrap();
+        resumed.instruments.sort();
+        assert_eq!(resumed.instruments, ["DCE.m2409", INSTRUMENT]);
+        assert_eq!(resumed.patterns, ["DCE.*"]);
+        assert_eq!(resumed.fields, Some(fields));
+        assert_eq!(resumed.throttle, Some(Duration::from_millis(500)));
+        assert!(distributor.pattern_subscribers["DCE.*"].contains("client"));
+        assert!(distributor.detached_sessions.is_empty());
+        // 已恢复的会话不能再次恢复
+        assert!(distributor.handle(resume("client"), &mut Context::new()).is_none());
+        assert!(distributor.handle(resume("unknown"), &mut Context::new()).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn unregister_without_grace_releases_instruments() {
+        let mut distributor = MarketDataDistributor::new();
+        register(&mut distributor, "client");
+        distributor.handle(UnregisterDataReceiver { client_id: "client".to_string() }, &mut Context::new());
+        assert!(distributor.detached_sessions.is_empty());
+        assert!(!distributor.instrument_subscribers.contains_key(INSTRUMENT));
+        assert!(distributor.handle(resume("client"), &mut Context::new()).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn detached_session_released_after_deadline() {
+        let mut distributor = MarketDataDistributor::new().with_resume_grace(Duration::from_secs(60));
+        register(&mut distributor, "client");
+        distributor.handle(UnregisterDataReceiver { client_id: "client".to_string() }, &mut Context::new());
+        distributor.detached_sessions.get_mut("client").unwrap().deadline = Instant::now();
+        distributor.expire_detached_sessions();
+        assert!(distributor.detached_sessions.is_empty());
+        assert!(!distributor.instrument_subscribers.contains_key(INSTRUMENT));
+        assert!(distributor.handle(resume("client"), &mut Context::new()).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn pattern_expansion_is_capped() {
+        let mut distributor = MarketDataDistributor::new().with_max_pattern_matches(2);
+        for instrument in ["DCE.m2409", "DCE.m2501", "DCE.m2505"] {
+            distributor
+                .market_data_cache
+                .insert(instrument.to_string(), Arc::new(qamd_rs::MDSnapshot::default()));
+        }
+        register(&mut distributor, "client");
+        let pattern = SubscribePattern {
+            client_id: "client".to_string(),
+            patterns: vec!["DCE.m*".to_string()],
+        };
+        distributor.handle(pattern, &mut Context::new());
+        // 超过上限的合约不订阅，按代码顺序保留前面的合约
+        let matched = &distributor.subscribers["client"].patterns["DCE.m*"];
+        assert_eq!(matched.len(), 2);
+        assert!(matched.contains("DCE.m2409") && matched.contains("DCE.m2501"));
+        assert!(!distributor.instrument_subscribers.contains_key("DCE.m2505"));
+
+        // 之后首次收到行情的合约也不超过上限
+        distributor.match_pattern_subscriptions("DCE.m2509");
+        assert_eq!(distributor.subscribers["client"].patterns["DCE.m*"].len(), 2);
+        assert!(!distributor.instrument_subscribers.contains_key("DCE.m2509"));
+        assert_eq!(distributor.subscribers["client"].instruments.len(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn new_instrument_joins_pattern_below_cap() {
+        let mut distributor = MarketDataDistributor::new().with_max_pattern_matches(2);
+        register(&mut distributor, "client");
+        let pattern = SubscribePattern {
+            client_id: "client".to_string(),
+            patterns: vec!["DCE.m*".to_string()],
+        };
+        distributor.handle(pattern, &mut Context::new());
+        assert!(distributor.subscribers["client"].patterns["DCE.m*"].is_empty());
+
+        distributor.match_pattern_subscriptions("DCE.m2409");
+        distributor.match_pattern_subscriptions("SHFE.ag2412");
+        assert!(distributor.instrument_subscribers["DCE.m2409"].contains("client"));
+        assert!(!distributor.instrument_subscribers.contains_key("SHFE.ag2412"));
+        assert_eq!(distributor.subscribers["client"].patterns["DCE.m*"].len(), 1);
+    }
+}
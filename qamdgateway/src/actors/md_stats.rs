@@ -0,0 +1,168 @@
+use actix::prelude::*;
+use chrono::{DateTime, Utc};
+use hashbrown::HashMap;
+use tracing::info;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::actors::messages::*;
+
+/// 速率统计的最长窗口
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// 清理长时间没有行情的合约
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// 单个合约的行情统计
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentStats {
+    /// 合约代码
+    pub instrument_id: String,
+    /// 最近1秒的行情数（条/秒）
+    pub tick_rate_1s: f64,
+    /// 最近1分钟的平均行情速率（条/秒）
+    pub tick_rate_1m: f64,
+    /// 最新行情的时间戳
+    pub last_update: DateTime<Utc>,
+    /// 最新行情的接收时间
+    pub last_received: DateTime<Utc>,
+    /// 当前数据源
+    pub source: MarketDataSource,
+    /// 买卖价差
+    pub bid_ask_spread: f64,
+    /// 累计行情数
+    pub total_ticks: u64,
+}
+
+/// 合约的滚动统计状态
+struct RollingStats {
+    // 最近一分钟内每条行情的到达时间
+    arrivals: VecDeque<Instant>,
+    last_update: DateTime<Utc>,
+    last_received: DateTime<Utc>,
+    last_received_at: Instant,
+    source: MarketDataSource,
+    bid_ask_spread: f64,
+    total_ticks: u64,
+}
+
+impl RollingStats {
+    /// 丢弃统计窗口之外的到达记录
+    fn prune(&mut self, now: Instant) {
+        while let Some(&oldest) = self.arrivals.front() {
+            if now.duration_since(oldest) <= RATE_WINDOW {
+                break;
+            }
+            self.arrivals.pop_front();
+        }
+    }
+
+    /// 窗口内的平均速率（条/秒）
+    fn rate(&self, now: Instant, window: Duration) -> f64 {
+        let count = self
+            .arrivals
+            .iter()
+            .rev()
+            .take_while(|&&arrival| now.duration_since(arrival) <= window)
+            .count();
+        count as f64 / window.as_secs_f64()
+    }
+}
+
+/// 行情统计Actor
+///
+/// 作为行情输出注册到分发器，统计每个合约的行情到达速率、
+/// 最新行情时间、当前数据源和买卖价差
+pub struct MarketDataStatsActor {
+    stats: HashMap<String, RollingStats>,
+}
+
+impl Actor for MarketDataStatsActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Market data stats collector started");
+
+        // 定期清理不再推送行情的合约
+        ctx.run_interval(RATE_WINDOW, |act, _| {
+            let now = Instant::now();
+            act.stats
+                .retain(|_, stats| now.duration_since(stats.last_received_at) < IDLE_TIMEOUT);
+        });
+    }
+}
+
+impl Default for MarketDataStatsActor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarketDataStatsActor {
+    /// 创建行情统计Actor
+    pub fn new() -> Self {
+        Self {
+            stats: HashMap::new(),
+        }
+    }
+}
+
+impl Handler<MarketDataUpdate> for MarketDataStatsActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let (snapshot, source) = (msg.0, msg.1);
+        let now = Instant::now();
+
+        let stats = self
+            .stats
+            .entry_ref(snapshot.instrument_id.as_str())
+            .or_insert_with(|| RollingStats {
+                arrivals: VecDeque::new(),
+                last_update: snapshot.datetime,
+                last_received: Utc::now(),
+                last_received_at: now,
+                source,
+                bid_ask_spread: 0.0,
+                total_ticks: 0,
+            });
+        stats.arrivals.push_back(now);
+        stats.prune(now);
+        stats.last_update = snapshot.datetime;
+        stats.last_received = Utc::now();
+        stats.last_received_at = now;
+        stats.source = source;
+        stats.bid_ask_spread = snapshot.bid_ask_spread();
+        stats.total_ticks += 1;
+    }
+}
+
+impl Handler<GetMarketDataStats> for MarketDataStatsActor {
+    type Result = MessageResult<GetMarketDataStats>;
+
+    fn handle(&mut self, msg: GetMarketDataStats, _: &mut Self::Context) -> Self::Result {
+        let now = Instant::now();
+        let mut report: Vec<InstrumentStats> = self
+            .stats
+            .iter_mut()
+            .filter(|(instrument, _)| {
+                msg.instrument.as_ref().is_none_or(|wanted| wanted == *instrument)
+            })
+            .map(|(instrument, stats)| {
+                stats.prune(now);
+                InstrumentStats {
+                    instrument_id: instrument.clone(),
+                    tick_rate_1s: stats.rate(now, Duration::from_secs(1)),
+                    tick_rate_1m: stats.rate(now, RATE_WINDOW),
+                    last_update: stats.last_update,
+                    last_received: stats.last_received,
+                    source: stats.source,
+                    bid_ask_spread: stats.bid_ask_spread,
+                    total_ticks: stats.total_ticks,
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| a.instrument_id.cmp(&b.instrument_id));
+        MessageResult(report)
+    }
+}
@@ -3,21 +3,25 @@ use qamd_rs::MDSnapshot;
 use ctp_common::CThostFtdcDepthMarketDataField;
 
 use uuid::Uuid;
-use crate::actors::md_distributor::MarketDataDistributor;
+use serde::{Deserialize, Serialize};
+use crate::actors::md_distributor::{DistributorState, MarketDataDistributor};
+use crate::config::BrokerConfig;
+use crate::error::GatewayResult;
+use crate::quote_encoding::EncodedQuote;
+use crate::ws_server::Channel;
 use hashbrown::{HashMap, HashSet};
 
-// Message type forward declarations for feature-dependent types
-#[cfg(feature = "qq")]
-pub use crate::actors::md_actor as qq_md_actor;
-#[cfg(feature = "sina")]
-pub use crate::actors::md_actor as sina_md_actor;
-
 /// 市场数据源类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MarketDataSource {
     CTP,
     QQ,
     Sina,
+    /// 新浪HTTP行情轮询
+    SinaHttp,
+    Replay,
+    /// Binance现货行情（websocket）
+    Binance,
 }
 
 //
@@ -31,7 +35,7 @@ pub struct InitMarketDataSource;
 
 /// 登录市场数据源
 #[derive(Message)]
-#[rtype(result = "Result<(), String>")]
+#[rtype(result = "GatewayResult<()>")]
 pub struct LoginMarketDataSource;
 
 /// 启动市场数据流
@@ -69,12 +73,7 @@ pub struct Unsubscribe {
 /// 获取当前订阅的合约列表
 #[derive(Message)]
 #[rtype(result = "Vec<String>")]
-pub struct GetSubscriptions {
-    /// 客户端ID
-    pub id: uuid::Uuid,
-    /// 订阅回调（可选）
-    pub callback: Option<Box<dyn Fn(Vec<String>) + Send>>,
-}
+pub struct GetSubscriptions;
 
 /// 注册市场数据分发器
 #[derive(Message)]
@@ -88,12 +87,18 @@ pub struct RegisterDistributor {
 #[rtype(result = "()")]
 pub enum MarketDataEvent {
     Connected,
-    Disconnected,
+    /// 前置断开（断开原因）
+    Disconnected(String),
     LoggedIn,
-    MarketData(CThostFtdcDepthMarketDataField),
+    /// 行情（SPI回调时刻，Unix微秒）
+    MarketData(CThostFtdcDepthMarketDataField, i64),
     SubscriptionSuccess(String),
     SubscriptionFailure(String, String),
+    /// 退订成功
+    Unsubscribed(String),
     Error(String),
+    /// SPI回调中发生panic（panic信息）
+    Panicked(String),
 }
 
 /// 重启 Actor
@@ -101,6 +106,198 @@ pub enum MarketDataEvent {
 #[rtype(result = "()")]
 pub struct RestartActor;
 
+/// 设置交易日历（非交易时间不尝试重连）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetTradingCalendar {
+    pub calendar: std::sync::Arc<crate::calendar::TradingCalendar>,
+    /// 开盘前多久恢复重连
+    pub lead: chrono::Duration,
+}
+
+/// 按合约基础信息对行情分类、补充期权条款，round_prices时按最小变动价位对价格取整
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetInstrumentRegistry {
+    pub instruments: std::sync::Arc<crate::instruments::InstrumentRegistry>,
+    pub round_prices: bool,
+}
+
+/// 容错解析行情：字段缺失或编码错误时发布部分快照
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetTolerantParsing {
+    /// 原始数据死信文件（可选）
+    pub dead_letter: Option<std::sync::Arc<crate::converter::DeadLetterLog>>,
+}
+
+/// 设置断线重连后的分批重新订阅策略
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetResubscribePolicy {
+    pub config: crate::config::ResubscribeConfig,
+    /// 重新订阅进度的发布通道
+    pub events: tokio::sync::broadcast::Sender<ResubscribeProgress>,
+}
+
+/// 断线重连后的重新订阅进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResubscribeProgress {
+    pub broker_id: String,
+    /// 需要重新订阅的合约总数
+    pub total: usize,
+    /// 已成功订阅的合约数
+    pub subscribed: usize,
+    /// 连续失败次数，大于0时正在退避重试
+    pub failures: u32,
+    /// 距下一批请求的等待时间（毫秒）
+    pub next_delay_ms: u64,
+    /// 是否已全部完成
+    pub done: bool,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// 停止 Actor（退订所有合约并释放行情API）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StopActor;
+
+/// 行情Actor因panic停止，由连接器按重启策略重新创建
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SourceCrashed {
+    pub broker_id: String,
+    /// 崩溃的Actor（连接已重建时忽略旧Actor的崩溃）
+    pub addr: Addr<crate::actors::md_actor::MarketDataActor>,
+    pub event: crate::supervision::CrashEvent,
+}
+
+//
+// 行情连接管理消息
+//
+
+/// 运行时添加上游行情连接
+#[derive(Message)]
+#[rtype(result = "Result<(), String>")]
+pub struct AddBroker {
+    pub config: BrokerConfig,
+}
+
+/// 运行时移除上游行情连接
+#[derive(Message)]
+#[rtype(result = "Result<(), String>")]
+pub struct RemoveBroker {
+    pub broker_id: String,
+}
+
+/// 从配置文件重新加载配置
+#[derive(Message)]
+#[rtype(result = "Result<ReloadSummary, String>")]
+pub struct ReloadConfig;
+
+/// 配置重新加载的结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadSummary {
+    /// 已生效的变更
+    pub applied: Vec<String>,
+    /// 需要重启网关才能生效的配置项
+    pub restart_required: Vec<String>,
+    /// 因凭证变化而重连的上游连接
+    pub reconnected_brokers: Vec<String>,
+}
+
+/// 重新加载配置后更新上游连接，配置有变化的连接会被重建，返回重连的broker_id
+#[derive(Message)]
+#[rtype(result = "Vec<String>")]
+pub struct ReloadBrokers {
+    pub configs: Vec<BrokerConfig>,
+}
+
+/// 重新加载配置后更新默认订阅
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdateDefaultSubscriptions {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// 订阅文件发生变化
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscriptionFileChanged;
+
+/// 重新加载配置后更新所有上游连接的重新订阅策略
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdateResubscribePolicy {
+    pub config: crate::config::ResubscribeConfig,
+}
+
+/// 获取当前的上游行情连接配置
+#[derive(Message)]
+#[rtype(result = "Vec<BrokerConfig>")]
+pub struct ListBrokers;
+
+/// 强制重建一个上游行情连接
+#[derive(Message)]
+#[rtype(result = "Result<(), String>")]
+pub struct ReconnectBroker {
+    pub broker_id: String,
+}
+
+/// 设置合约在多个行情账户之间的分配（None时关闭负载分担）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetLoadSharing {
+    pub sharing: Option<std::sync::Arc<crate::load_sharing::LoadSharing>>,
+}
+
+/// 获取各行情账户的负载分担状态（未开启时为None）
+#[derive(Message)]
+#[rtype(result = "Option<Vec<crate::load_sharing::LoadSharingAccount>>")]
+pub struct GetLoadSharing;
+
+/// 获取所有上游行情连接的状态
+#[derive(Message)]
+#[rtype(result = "Vec<BrokerState>")]
+pub struct GetBrokerStates;
+
+/// 获取单个行情Actor的连接状态
+#[derive(Message)]
+#[rtype(result = "BrokerState")]
+pub struct GetConnectionState;
+
+/// 上游行情连接的状态
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokerState {
+    pub broker_id: String,
+    /// 当前使用的前置
+    pub front_addr: String,
+    /// 各前置的熔断状态
+    pub fronts: Vec<crate::circuit_breaker::FrontStatus>,
+    pub connected: bool,
+    pub logged_in: bool,
+    /// 已订阅的合约数
+    pub subscribed: usize,
+    /// 是否正在断线重连后分批重新订阅
+    pub resubscribing: bool,
+    /// 因panic崩溃的次数
+    pub crashes: u64,
+    /// 统计窗口内崩溃后自动重启的次数
+    pub restarts: usize,
+    /// 数据源类型
+    pub source: MarketDataSource,
+    /// 最近一次断开（时间和原因）
+    pub last_disconnect: Option<DisconnectInfo>,
+}
+
+/// 上游连接断开的时间和原因
+#[derive(Debug, Clone, Serialize)]
+pub struct DisconnectInfo {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub reason: String,
+}
+
 //
 // WebSocket 服务器消息
 //
@@ -143,6 +340,219 @@ pub struct RegisterDataReceiver {
     pub client_id: String,
     pub addr: Recipient<MarketDataUpdateMessage>,
     pub instruments: Vec<String>,
+    /// 数据源切换通知的接收者（可选）
+    pub notify: Option<Recipient<SourceChanged>>,
+    /// 主力合约切换通知的接收者（可选）
+    pub remap: Option<Recipient<ContractRemapped>>,
+    /// 管理接口强制断开时通知的会话（内部接收者为None）
+    pub disconnect: Option<Recipient<ForceDisconnect>>,
+}
+
+/// 登记行情预警（同一预警ID会替换之前的规则）
+#[derive(Message)]
+#[rtype(result = "GatewayResult<()>")]
+pub struct SetAlert {
+    pub client_id: String,
+    pub addr: Recipient<AlertTriggered>,
+    pub rule: crate::alerts::AlertRule,
+}
+
+/// 删除行情预警（alert_id为None时删除客户端的全部预警）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RemoveAlert {
+    pub client_id: String,
+    pub alert_id: Option<String>,
+}
+
+/// 行情预警触发通知
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct AlertTriggered {
+    pub alert_id: String,
+    pub instrument_id: String,
+    pub condition: crate::alerts::AlertCondition,
+    /// 触发时的观测值（价格或成交量增量）
+    pub value: f64,
+    /// 触发行情的时间
+    pub datetime: chrono::DateTime<chrono::Utc>,
+}
+
+/// 获取全部自选列表
+#[derive(Message)]
+#[rtype(result = "Vec<crate::actors::watchlist_store::Watchlist>")]
+pub struct ListWatchlists;
+
+/// 获取自选列表
+#[derive(Message)]
+#[rtype(result = "Option<crate::actors::watchlist_store::Watchlist>")]
+pub struct GetWatchlist {
+    pub name: String,
+}
+
+/// 创建或替换自选列表
+#[derive(Message)]
+#[rtype(result = "GatewayResult<crate::actors::watchlist_store::Watchlist>")]
+pub struct SaveWatchlist {
+    pub name: String,
+    pub instruments: Vec<String>,
+}
+
+/// 删除自选列表
+#[derive(Message)]
+#[rtype(result = "GatewayResult<()>")]
+pub struct DeleteWatchlist {
+    pub name: String,
+}
+
+/// 会话订阅自选列表，返回当前列表，之后列表的修改通过WatchlistChanged通知
+#[derive(Message)]
+#[rtype(result = "GatewayResult<crate::actors::watchlist_store::Watchlist>")]
+pub struct SubscribeWatchlist {
+    pub name: String,
+    pub client_id: String,
+    pub addr: Recipient<WatchlistChanged>,
+}
+
+/// 会话取消订阅自选列表（name为None时取消全部）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnsubscribeWatchlist {
+    pub name: Option<String>,
+    pub client_id: String,
+}
+
+/// 自选列表修改通知（instruments为None表示列表已删除）
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct WatchlistChanged {
+    pub name: String,
+    pub instruments: Option<Vec<String>>,
+}
+
+/// 读取客户端配置
+#[derive(Message)]
+#[rtype(result = "Option<crate::actors::profile_store::ClientProfile>")]
+pub struct LoadProfile {
+    pub name: String,
+}
+
+/// 保存客户端配置（替换同名配置）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SaveProfile(pub crate::actors::profile_store::ClientProfile);
+
+/// 设置客户端订阅衍生指标的合约（替换之前的列表，空列表表示取消全部）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribeIndicators {
+    pub client_id: String,
+    pub addr: Recipient<IndicatorUpdate>,
+    pub instruments: Vec<String>,
+}
+
+/// 衍生指标推送
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct IndicatorUpdate(pub crate::analytics::Indicators);
+
+/// 设置客户端订阅K线的合约（替换之前的列表，空列表表示取消全部）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribeKlines {
+    pub client_id: String,
+    pub addr: Recipient<KlineUpdate>,
+    pub instruments: Vec<String>,
+}
+
+/// K线推送（当前K线，每笔行情更新一次）
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct KlineUpdate(pub crate::analytics::Kline);
+
+/// 设置客户端订阅盘口增量的合约（替换之前的列表，空列表表示取消全部）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribeDepth {
+    pub client_id: String,
+    pub addr: Recipient<DepthUpdate>,
+    pub instruments: Vec<String>,
+}
+
+/// 客户端发现序号不连续时请求重新发送盘口全量（instruments为空表示全部已订阅合约）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ResyncDepth {
+    pub client_id: String,
+    pub instruments: Vec<String>,
+}
+
+/// 推送给客户端的一批盘口帧
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct DepthUpdate {
+    pub frames: Vec<DepthFrame>,
+}
+
+/// 盘口帧，seq按合约递增，客户端收到的增量帧序号应比上一帧大1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DepthFrame {
+    /// 全量帧，客户端用bids/asks替换该合约的盘口
+    Snapshot {
+        seq: u64,
+        #[serde(flatten)]
+        book: qamd_rs::OrderBook,
+    },
+    /// 增量帧，按顺序应用价位的新增、修改和删除
+    Update {
+        instrument_id: String,
+        seq: u64,
+        datetime: Option<chrono::DateTime<chrono::Utc>>,
+        changes: Vec<qamd_rs::LevelUpdate>,
+    },
+}
+
+impl DepthFrame {
+    pub fn instrument_id(&self) -> &str {
+        match self {
+            DepthFrame::Snapshot { book, .. } => &book.instrument_id,
+            DepthFrame::Update { instrument_id, .. } => instrument_id,
+        }
+    }
+}
+
+/// 恢复断线前的会话（会话仍在线或已过期时返回None）
+#[derive(Message)]
+#[rtype(result = "Option<ResumedSession>")]
+pub struct ResumeSession {
+    pub client_id: String,
+    pub addr: Recipient<MarketDataUpdateMessage>,
+    pub notify: Option<Recipient<SourceChanged>>,
+    pub remap: Option<Recipient<ContractRemapped>>,
+    pub disconnect: Option<Recipient<ForceDisconnect>>,
+}
+
+/// 恢复的会话状态
+#[derive(Debug, Clone, Default)]
+pub struct ResumedSession {
+    /// 订阅的合约（包括经通配符模式订阅的合约）
+    pub instruments: Vec<String>,
+    /// 订阅的通配符模式
+    pub patterns: Vec<String>,
+    /// 推送的行情字段
+    pub fields: Option<HashSet<String>>,
+    /// 限速间隔
+    pub throttle: Option<std::time::Duration>,
+}
+
+/// 合约行情数据源切换通知
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct SourceChanged {
+    pub instrument: String,
+    pub from: MarketDataSource,
+    pub to: MarketDataSource,
 }
 
 /// 取消注册市场数据接收者
@@ -160,6 +570,166 @@ pub struct UpdateSubscription {
     pub instruments: Vec<String>,
 }
 
+/// 定义合成合约（合约代码即公式，如`SPREAD:rb2410-rb2501`）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DefineSynthetic {
+    pub instrument: String,
+}
+
+/// 删除不再有客户端订阅的合成合约
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RemoveSynthetic {
+    pub instrument: String,
+}
+
+/// 向分发器注册合成合约计算Actor
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterSyntheticEngine {
+    pub addr: Addr<crate::actors::synthetic_actor::SyntheticActor>,
+}
+
+/// 定义主力合约（如`SHFE.rb.HOT`）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct DefineDominant {
+    pub instrument: String,
+}
+
+/// 删除不再有客户端订阅的主力合约
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RemoveDominant {
+    pub instrument: String,
+}
+
+/// 向分发器注册主力合约映射Actor
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterDominantEngine {
+    pub addr: Addr<crate::actors::dominant_actor::DominantActor>,
+}
+
+/// 主力合约映射到新的合约月份（from为None表示首次映射），
+/// 由主力合约映射Actor发给分发器，再转发给订阅了该主力合约的客户端
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct ContractRemapped {
+    pub instrument: String,
+    pub from: Option<String>,
+    pub to: String,
+}
+
+/// 按通配符模式订阅合约（如`au*`、`SHFE.*`、`*.rb2410`）
+///
+/// 已知的匹配合约立即订阅，之后首次推送行情的匹配合约自动加入订阅
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribePattern {
+    pub client_id: String,
+    pub patterns: Vec<String>,
+}
+
+/// 取消通配符模式订阅（同时取消经该模式订阅的合约）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnsubscribePattern {
+    pub client_id: String,
+    pub patterns: Vec<String>,
+}
+
+/// 设置客户端的最大推送频率（None表示不限速）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetClientThrottle {
+    pub client_id: String,
+    pub interval: Option<std::time::Duration>,
+}
+
+/// 设置客户端推送的行情字段（None表示推送全部字段）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetClientFields {
+    pub client_id: String,
+    pub fields: Option<HashSet<String>>,
+}
+
+/// 设置客户端的补丁模式（addr为None表示关闭，行情恢复为增量字段推送）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetClientPatch {
+    pub client_id: String,
+    pub addr: Option<Recipient<QuotePatchUpdate>>,
+}
+
+/// 设置客户端取消订阅的通道（分发器不再向其推送这些通道的事件）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetClientChannels {
+    pub client_id: String,
+    pub muted: HashSet<Channel>,
+}
+
+/// 客户端发现行情序号不连续时请求重新推送全量行情（instruments为空表示全部已订阅合约）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ResendSnapshots {
+    pub client_id: String,
+    pub instruments: Vec<String>,
+}
+
+/// 补丁模式下推送给客户端的一批行情帧
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QuotePatchUpdate {
+    pub frames: Vec<QuoteFrame>,
+}
+
+/// 补丁模式的行情帧，seq由会话在推送时按合约编号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuoteFrame {
+    /// 全量帧，客户端用quote替换该合约的全部字段
+    Full {
+        instrument_id: String,
+        #[serde(default)]
+        seq: u64,
+        quote: serde_json::Map<String, serde_json::Value>,
+    },
+    /// 补丁帧，相对客户端上一次收到的帧变化的字段
+    Patch {
+        instrument_id: String,
+        #[serde(default)]
+        seq: u64,
+        ops: Vec<PatchOp>,
+    },
+}
+
+impl QuoteFrame {
+    pub fn instrument_id(&self) -> &str {
+        match self {
+            QuoteFrame::Full { instrument_id, .. } | QuoteFrame::Patch { instrument_id, .. } => instrument_id,
+        }
+    }
+
+    pub fn set_seq(&mut self, value: u64) {
+        match self {
+            QuoteFrame::Full { seq, .. } | QuoteFrame::Patch { seq, .. } => *seq = value,
+        }
+    }
+}
+
+/// 类似JSON Patch（RFC 6902）的字段操作，path为"/字段名"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PatchOp {
+    Add { path: String, value: serde_json::Value },
+    Replace { path: String, value: serde_json::Value },
+    Remove { path: String },
+}
+
 /// 查询当前订阅
 #[derive(Message)]
 #[rtype(result = "Vec<String>")]
@@ -167,30 +737,526 @@ pub struct QuerySubscription {
     pub client_id: String,
 }
 
+/// 合约的上游订阅状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpstreamStatus {
+    /// 行情源已确认订阅或已收到行情
+    Subscribed,
+    /// 订阅失败（原因）
+    Failed(String),
+    /// 尚未应答
+    Pending,
+}
+
+/// 查询合约的上游订阅状态
+#[derive(Message)]
+#[rtype(result = "Vec<(String, UpstreamStatus)>")]
+pub struct QueryUpstreamStatus {
+    pub instruments: Vec<String>,
+}
+
+/// 行情源对订阅请求的应答（合约为行情源使用的代码，error为None表示订阅成功）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpstreamSubscribed {
+    pub source: MarketDataSource,
+    pub instrument: String,
+    pub error: Option<String>,
+}
+
 /// 市场数据更新消息传递给客户端
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct MarketDataUpdateMessage {
     pub instruments: Vec<String>,
-    pub data: HashMap<String, String>, // 使用JSON字符串表示行情数据
+    /// 合约 -> 行情增量（同一批次内内容相同的增量在客户端之间共享，每种格式只编码一次）
+    pub data: HashMap<String, std::sync::Arc<EncodedQuote>>,
 }
 
 /// 市场数据更新消息传递给分发器
+///
+/// 行情以Arc共享，分发器缓存和各行情输出持有同一份快照
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
-pub struct MarketDataUpdate(pub qamd_rs::MDSnapshot, pub MarketDataSource);
+pub struct MarketDataUpdate(pub std::sync::Arc<qamd_rs::MDSnapshot>, pub MarketDataSource);
+
+/// 带延迟追踪的市场数据更新（开启延迟追踪时代替MarketDataUpdate）
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct TracedMarketDataUpdate(
+    pub std::sync::Arc<qamd_rs::MDSnapshot>,
+    pub MarketDataSource,
+    pub crate::latency::LatencyTrace,
+);
+
+/// 在分发分片上登记客户端订阅的合约（同时更新客户端的推送设置）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ShardSubscribe {
+    pub client_id: String,
+    pub addr: Recipient<MarketDataUpdateMessage>,
+    pub settings: ShardClientSettings,
+    pub instruments: Vec<String>,
+}
+
+/// 在分发分片上取消客户端订阅的合约
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ShardUnsubscribe {
+    pub client_id: String,
+    pub instruments: Vec<String>,
+}
+
+/// 从分发分片上移除客户端
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ShardUnregister {
+    pub client_id: String,
+}
+
+/// 更新分发分片上客户端的推送设置
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ShardUpdateClient {
+    pub client_id: String,
+    pub settings: ShardClientSettings,
+}
+
+/// 分发分片需要的客户端推送设置
+#[derive(Debug, Clone, Default)]
+pub struct ShardClientSettings {
+    /// 推送的行情字段（None表示全部字段）
+    pub fields: Option<HashSet<String>>,
+    /// 限速和补丁模式的客户端由分发器推送，分片跳过
+    pub deferred: bool,
+}
+
+/// 分发分片负责的合约的一批增量行情（已包含instrument_id）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ShardFanout {
+    pub updates: Vec<(String, std::sync::Arc<EncodedQuote>)>,
+}
 
 /// 获取所有订阅列表消息
 #[derive(Message)]
 #[rtype(result = "Vec<String>")]
-pub struct GetAllSubscriptions {}
+pub struct GetAllSubscriptions;
+
+/// 注册行情输出（接收分发器接受的每条行情，如Redis桥接）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterSnapshotSink {
+    pub name: String,
+    pub addr: Recipient<MarketDataUpdate>,
+}
+
+/// 两次行情之间的成交（含推断的主动买卖方向和成交增量）
+#[cfg(feature = "zmq-pub")]
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct TickUpdate(pub qamd_rs::Tick);
+
+/// 注册逐笔成交输出（只接收有成交量变化的行情）
+#[cfg(feature = "zmq-pub")]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterTickSink {
+    pub name: String,
+    pub addr: Recipient<TickUpdate>,
+}
+
+/// 注销行情输出
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnregisterSnapshotSink {
+    pub name: String,
+}
+
+/// 获取分发器当前的订阅状态
+#[derive(Message)]
+#[rtype(result = "DistributorState")]
+pub struct GetDistributorState;
+
+/// 获取分发器缓存的全部最新行情
+#[derive(Message)]
+#[rtype(result = "Vec<qamd_rs::MDSnapshot>")]
+pub struct GetSnapshotCache;
+
+/// 获取重复/乱序行情过滤统计
+#[derive(Message)]
+#[rtype(result = "qamd_rs::TickFilterStats")]
+pub struct GetTickFilterStats;
+
+/// 获取发布前价格校验的统计
+#[derive(Message)]
+#[rtype(result = "crate::price_check::PriceCheckReport")]
+pub struct GetPriceCheckStats;
+
+/// 获取各数据源的时钟偏差估计
+#[derive(Message)]
+#[rtype(result = "Vec<crate::clock_skew::SourceClockSkew>")]
+pub struct GetClockSkew;
+
+/// 获取分发器的客户端数量
+#[derive(Message)]
+#[rtype(result = "ClientCounts")]
+pub struct GetClientCounts;
+
+/// 分发器的客户端数量
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClientCounts {
+    /// 在线的WebSocket会话
+    pub sessions: usize,
+    /// 内部接收者（连接器、预热订阅、合成合约等）
+    pub internal: usize,
+    /// 已断开、保留期内可恢复的会话
+    pub detached: usize,
+    /// 有订阅的合约数
+    pub instruments: usize,
+}
+
+/// 获取每个合约的行情统计（可按合约过滤）
+#[derive(Message)]
+#[rtype(result = "Vec<crate::actors::md_stats::InstrumentStats>")]
+pub struct GetMarketDataStats {
+    pub instrument: Option<String>,
+}
+
+/// 获取记录的日内行情（按北京时间的时段过滤，起始晚于结束时表示跨午夜）
+#[derive(Message)]
+#[rtype(result = "Vec<MDSnapshot>")]
+pub struct GetRecordedTicks {
+    /// 合约代码，不带交易所前缀时匹配任意交易所，支持通配符
+    pub instrument: String,
+    pub from: Option<chrono::NaiveTime>,
+    pub to: Option<chrono::NaiveTime>,
+}
+
+/// 汇总交易日的行情生成日线并写入QALfs目录，返回写入的日线数
+#[derive(Message)]
+#[rtype(result = "GatewayResult<usize>")]
+pub struct BuildDailyBars {
+    pub trading_day: chrono::NaiveDate,
+}
+
+/// 获取合约最近一段时间的行情（以该合约最新一笔行情的时间为基准）
+#[derive(Message)]
+#[rtype(result = "Vec<MDSnapshot>")]
+pub struct GetTickHistory {
+    /// 合约代码，不带交易所前缀时匹配任意交易所
+    pub instrument: String,
+    pub seconds: u64,
+}
+
+/// 取合约最近的若干笔行情，用于订阅时回放
+///
+/// 每个合约最多返回depth笔（不超过分发器保留的笔数），从旧到新，按客户端的字段设置裁剪
+#[derive(Message)]
+#[rtype(result = "Vec<(String, Vec<serde_json::Value>)>")]
+pub struct GetRecentTicks {
+    pub client_id: String,
+    pub instruments: Vec<String>,
+    pub depth: usize,
+}
+
+/// 将分发器的订阅状态保存到文件
+#[derive(Message)]
+#[rtype(result = "GatewayResult<()>")]
+pub struct SaveDistributorState {
+    pub path: std::path::PathBuf,
+}
+
+/// 恢复分发器的订阅状态，返回需要重新订阅的合约
+#[derive(Message)]
+#[rtype(result = "Vec<String>")]
+pub struct RestoreDistributorState {
+    pub state: DistributorState,
+    /// 恢复的客户端订阅保留时长，超时未重连的客户端订阅将被清除
+    pub grace: std::time::Duration,
+}
+
+//
+// 客户端限额消息
+//
+
+/// 客户端超出的限额
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaKind {
+    /// 订阅合约数
+    Instruments,
+    /// 每分钟订阅请求数
+    SubscribeRate,
+    /// 每秒上行消息数
+    MessageRate,
+}
+
+/// WebSocket会话上报的超限事件
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QuotaViolation {
+    pub client_id: String,
+    /// 客户端地址
+    pub remote_addr: Option<String>,
+    pub kind: QuotaKind,
+    /// 超限详情（同时发送给客户端）
+    pub detail: String,
+    /// 强制断开时通知的会话
+    pub addr: Recipient<ForceDisconnect>,
+}
+
+/// 客户端断开后清除其超限记录
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ForgetClient {
+    pub client_id: String,
+}
+
+/// 查询超限的在线客户端（按超限次数从多到少排列）
+#[derive(Message)]
+#[rtype(result = "Vec<OffenderInfo>")]
+pub struct GetOffenders;
+
+/// 各类限额的超限次数
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ViolationCounts {
+    pub instruments: u64,
+    pub subscribe_rate: u64,
+    pub message_rate: u64,
+}
+
+impl ViolationCounts {
+    /// 记录一次超限
+    pub fn record(&mut self, kind: QuotaKind) {
+        match kind {
+            QuotaKind::Instruments => self.instruments += 1,
+            QuotaKind::SubscribeRate => self.subscribe_rate += 1,
+            QuotaKind::MessageRate => self.message_rate += 1,
+        }
+    }
+
+    /// 总超限次数
+    pub fn total(&self) -> u64 {
+        self.instruments + self.subscribe_rate + self.message_rate
+    }
+}
+
+/// 超限客户端信息
+#[derive(Debug, Clone, Serialize)]
+pub struct OffenderInfo {
+    pub client_id: String,
+    pub remote_addr: Option<String>,
+    pub violations: ViolationCounts,
+    pub first_violation: chrono::DateTime<chrono::Utc>,
+    pub last_violation: chrono::DateTime<chrono::Utc>,
+    /// 最近一次超限的详情
+    pub last_detail: String,
+}
+
+/// 强制断开客户端，返回客户端是否在线
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct DisconnectClient {
+    pub client_id: String,
+    pub reason: String,
+}
+
+/// 通知WebSocket会话关闭连接
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ForceDisconnect {
+    pub reason: String,
+}
+
+/// 客户端心跳往返时间（毫秒）
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientRttInfo {
+    pub client_id: String,
+    pub remote_addr: Option<String>,
+    /// 最近一次往返时间
+    pub last_rtt_ms: f64,
+    /// 平滑往返时间
+    pub srtt_ms: f64,
+    pub min_rtt_ms: f64,
+    pub max_rtt_ms: f64,
+    /// 往返时间抖动
+    pub jitter_ms: f64,
+    /// 收到的带时间戳的pong数
+    pub pongs: u64,
+    /// 连续超出阈值的pong数
+    pub violations: u32,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// WebSocket会话每收到一次pong上报往返时间
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ClientRtt {
+    pub info: ClientRttInfo,
+}
+
+/// 查询在线客户端的往返时间（按平滑往返时间从大到小排列）
+#[derive(Message)]
+#[rtype(result = "Vec<ClientRttInfo>")]
+pub struct GetClientRtt;
+
+//
+// 行情质量消息
+//
+
+/// 行情质量问题
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityIssue {
+    /// 交易时段内相邻两条行情的间隔过长
+    Gap,
+    /// 交易时段内长时间没有行情
+    Stale,
+    /// 最新价为零/负数或价格不是有限值
+    InvalidPrice,
+    /// 买一价不低于卖一价
+    CrossedBook,
+}
+
+/// 行情质量事件
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct QualityEvent {
+    pub instrument_id: String,
+    pub issue: QualityIssue,
+    /// 问题详情
+    pub detail: String,
+    /// 发现问题的时间（行情时间或检查时间）
+    pub datetime: chrono::DateTime<chrono::Utc>,
+}
+
+/// 设置客户端订阅行情质量事件的合约模式（替换之前的列表，空列表表示取消订阅）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribeQuality {
+    pub client_id: String,
+    pub addr: Recipient<QualityEvent>,
+    pub patterns: Vec<String>,
+}
+
+/// 查询各合约的行情质量计数（按问题总数从多到少排列）
+#[derive(Message)]
+#[rtype(result = "Vec<InstrumentQuality>")]
+pub struct GetQualityStats {
+    /// 只返回该合约
+    pub instrument: Option<String>,
+}
+
+/// 各类行情质量问题的次数
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QualityCounts {
+    pub gap: u64,
+    pub stale: u64,
+    pub invalid_price: u64,
+    pub crossed_book: u64,
+}
+
+impl QualityCounts {
+    /// 记录一次问题
+    pub fn record(&mut self, issue: QualityIssue) {
+        match issue {
+            QualityIssue::Gap => self.gap += 1,
+            QualityIssue::Stale => self.stale += 1,
+            QualityIssue::InvalidPrice => self.invalid_price += 1,
+            QualityIssue::CrossedBook => self.crossed_book += 1,
+        }
+    }
+
+    /// 问题总数
+    pub fn total(&self) -> u64 {
+        self.gap + self.stale + self.invalid_price + self.crossed_book
+    }
+}
+
+/// 单个合约的行情质量
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentQuality {
+    pub instrument_id: String,
+    pub issues: QualityCounts,
+    /// 当前是否处于无行情状态
+    pub stale: bool,
+    /// 当前盘口是否交叉
+    pub crossed: bool,
+    /// 最近一次问题
+    pub last_issue: Option<QualityEvent>,
+}
+
+//
+// 交易阶段消息
+//
+
+/// 交易阶段的判断依据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseSource {
+    /// 交易日历和交易时段
+    Calendar,
+    /// 交易时段之外仍有成交的行情
+    Quotes,
+}
+
+/// 交易所的交易阶段变化（订阅时先推送一次当前阶段，previous为None）
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct PhaseChanged {
+    pub exchange: String,
+    pub phase: crate::calendar::TradingPhase,
+    pub previous: Option<crate::calendar::TradingPhase>,
+    pub source: PhaseSource,
+    /// 进入该阶段的时间
+    pub datetime: chrono::DateTime<chrono::Utc>,
+}
+
+/// 设置订阅交易阶段变化的交易所（支持通配符，替换之前的列表，空列表表示取消订阅）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribePhase {
+    pub client_id: String,
+    pub addr: Recipient<PhaseChanged>,
+    /// 交易日结束通知的接收者（可选）
+    pub session_end: Option<Recipient<SessionEnd>>,
+    pub exchanges: Vec<String>,
+}
+
+/// 交易所一个交易日的行情结束
+///
+/// 日盘收盘后等到各合约的结算价（或等待超时）时发出，之后不会再有该交易日的行情
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct SessionEnd {
+    pub exchange: String,
+    pub trading_day: chrono::NaiveDate,
+    /// 当天有行情的合约数
+    pub instruments: usize,
+    /// 其中收到结算价的合约数
+    pub settled: usize,
+    pub datetime: chrono::DateTime<chrono::Utc>,
+    /// 各合约的最终行情（含收盘价和收到的结算价）
+    #[serde(skip)]
+    pub snapshots: std::sync::Arc<Vec<MDSnapshot>>,
+}
+
+/// 查询各交易所最近一次交易日结束
+#[derive(Message)]
+#[rtype(result = "Vec<SessionEnd>")]
+pub struct GetSessionEnds;
+
+/// 查询各交易所当前的交易阶段
+#[derive(Message)]
+#[rtype(result = "Vec<PhaseChanged>")]
+pub struct GetTradingPhases;
 
 //
 // 针对特定市场数据源的注册消息
 //
 
 /// 注册CTP市场数据Actor
-#[cfg(feature = "ctp")]
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct RegisterCTPMdActor {
@@ -199,7 +1265,6 @@ pub struct RegisterCTPMdActor {
 }
 
 /// 注册QQ市场数据Actor 
-#[cfg(feature = "qq")]
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct RegisterQQMdActor {
@@ -208,7 +1273,6 @@ pub struct RegisterQQMdActor {
 }
 
 /// 注册新浪市场数据Actor
-#[cfg(feature = "sina")]
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct RegisterSinaMdActor {
@@ -225,6 +1289,25 @@ pub struct RegisterMdActor {
     pub source_type: MarketDataSource,
 }
 
+/// 注销市场数据Actor（上游连接停止时）
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnregisterMdActor {
+    pub broker_id: String,
+}
+
+/// 注册按合约代码的交易所前缀接收订阅的行情源（如`BINANCE.BTCUSDT`）
+///
+/// 分发器把该交易所合约的订阅和退订直接发给行情源，不经过CTP等柜台数据源
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterExchangeSource {
+    pub exchange: String,
+    pub source: MarketDataSource,
+    pub subscribe: Recipient<Subscribe>,
+    pub unsubscribe: Recipient<Unsubscribe>,
+}
+
 /// 添加单个订阅消息
 #[derive(Message)]
 #[rtype(result = "()")]
@@ -262,3 +1345,24 @@ pub struct WebSocketDisconnect {
     /// 客户端ID
     pub id: uuid::Uuid,
 }
+
+//
+// 历史回放消息
+//
+
+/// 开始（或继续）历史回放
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StartReplay;
+
+/// 暂停历史回放
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StopReplay;
+
+/// 调整回放速度（"1x", "10x", "max"）
+#[derive(Message)]
+#[rtype(result = "Result<(), String>")]
+pub struct SetReplaySpeed {
+    pub speed: String,
+}
@@ -0,0 +1,149 @@
+use actix::prelude::*;
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+use qamd_rs::MDSnapshot;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+use std::sync::Arc;
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::config::MockConfig;
+
+// 生成间隔的下限，更高的频率按1毫秒生成
+const MIN_TICK_INTERVAL: Duration = Duration::from_millis(1);
+
+/// 单个合约的随机游走状态
+struct MockInstrument {
+    instrument_id: String,
+    price: f64,
+    highest: f64,
+    lowest: f64,
+    volume: i64,
+    amount: f64,
+    open_interest: f64,
+}
+
+/// 确定性的模拟行情生成器
+///
+/// 价格按随机游走变化，每次变动不超过`max_step`个最小变动价位；
+/// 相同的种子和合约列表总是生成相同的价格序列
+pub struct MockTickGenerator {
+    config: MockConfig,
+    rng: StdRng,
+    instruments: Vec<MockInstrument>,
+}
+
+impl MockTickGenerator {
+    pub fn new(config: &MockConfig) -> Self {
+        let instruments = config
+            .instruments
+            .iter()
+            .map(|instrument_id| MockInstrument {
+                instrument_id: instrument_id.clone(),
+                price: config.start_price,
+                highest: config.start_price,
+                lowest: config.start_price,
+                volume: 0,
+                amount: 0.0,
+                open_interest: config.open_interest,
+            })
+            .collect();
+
+        Self {
+            config: config.clone(),
+            rng: StdRng::seed_from_u64(config.seed),
+            instruments,
+        }
+    }
+
+    /// 为每个合约生成一笔行情（按配置中的合约顺序）
+    pub fn next_ticks(&mut self, datetime: DateTime<Utc>) -> Vec<MDSnapshot> {
+        let tick = self.config.price_tick;
+        let max_step = self.config.max_step as i64;
+        let mut snapshots = Vec::with_capacity(self.instruments.len());
+        for state in &mut self.instruments {
+            let step = self.rng.gen_range(-max_step..=max_step) as f64 * tick;
+            // 价格不低于一个最小变动价位
+            state.price = (state.price + step).max(tick);
+            state.highest = state.highest.max(state.price);
+            state.lowest = state.lowest.min(state.price);
+            let traded = self.rng.gen_range(1..=10);
+            state.volume += traded;
+            state.amount += traded as f64 * state.price;
+            // 持仓量随成交增减
+            state.open_interest = (state.open_interest + self.rng.gen_range(-traded..=traded) as f64).max(0.0);
+
+            snapshots.push(
+                MDSnapshot::builder(state.instrument_id.clone(), datetime)
+                    .last_price(state.price)
+                    .volume(state.volume)
+                    .amount(state.amount)
+                    .open(self.config.start_price)
+                    .pre_close(self.config.start_price)
+                    .highest(state.highest)
+                    .lowest(state.lowest)
+                    .average(state.amount / state.volume as f64)
+                    .open_interest(state.open_interest)
+                    .bid(1, state.price - tick, self.rng.gen_range(1..=50))
+                    .ask(1, state.price + tick, self.rng.gen_range(1..=50))
+                    .build(),
+            );
+        }
+        snapshots
+    }
+}
+
+/// 模拟行情源Actor
+///
+/// 以CTP行情源的身份按配置的频率发布随机游走行情，
+/// 无需真实的期货账户即可运行和测试整个网关
+pub struct MockMarketDataActor {
+    generator: MockTickGenerator,
+    interval: Option<Duration>,
+    distributor: Addr<MarketDataDistributor>,
+    published: u64,
+}
+
+impl Actor for MockMarketDataActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let Some(interval) = self.interval else {
+            warn!("Mock market data rate must be positive, no ticks will be generated");
+            return;
+        };
+        info!(
+            "MockMarketDataActor started, {} instruments every {:?}",
+            self.generator.instruments.len(),
+            interval
+        );
+        ctx.run_interval(interval, |act, _| act.publish());
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        info!("MockMarketDataActor stopped, {} snapshots published", self.published);
+    }
+}
+
+impl MockMarketDataActor {
+    pub fn new(config: MockConfig, distributor: Addr<MarketDataDistributor>) -> Self {
+        let interval = (config.ticks_per_sec > 0.0 && config.ticks_per_sec.is_finite())
+            .then(|| Duration::from_secs_f64(1.0 / config.ticks_per_sec).max(MIN_TICK_INTERVAL));
+
+        Self {
+            generator: MockTickGenerator::new(&config),
+            interval,
+            distributor,
+            published: 0,
+        }
+    }
+
+    fn publish(&mut self) {
+        for snapshot in self.generator.next_ticks(Utc::now()) {
+            self.distributor.do_send(MarketDataUpdate(Arc::new(snapshot), MarketDataSource::CTP));
+            self.published += 1;
+        }
+    }
+}
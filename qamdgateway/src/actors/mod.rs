@@ -1,16 +1,36 @@
+pub mod alert_engine;
+pub mod binance_actor;
+pub mod config_reloader;
+pub mod data_quality;
+pub mod depth_engine;
+pub mod dominant_actor;
+pub mod eod_builder;
+pub mod fanout_shard;
+pub mod indicator_engine;
+pub mod ipc_server;
 pub mod md_actor;
 pub mod md_connector;
 pub mod md_distributor;
+pub mod md_stats;
 pub mod messages;
-
-#[cfg(feature = "ctp")]
-pub use md_actor as ctp_md_actor;
-
-#[cfg(feature = "qq")]
-pub use md_actor as qq_md_actor;
-
-#[cfg(feature = "sina")]
-pub use md_actor as sina_md_actor;
+pub mod mock_actor;
+pub mod profile_store;
+pub mod quota_monitor;
+pub mod replay_actor;
+pub mod replication;
+pub mod sina_http_poller;
+pub mod synthetic_actor;
+pub mod tick_history;
+pub mod tick_recorder;
+pub mod trading_phase;
+pub mod warmup_scheduler;
+pub mod watchlist_store;
+#[cfg(feature = "redis-bridge")]
+pub mod redis_bridge;
+#[cfg(feature = "zmq-pub")]
+pub mod zmq_publisher;
+#[cfg(feature = "ctp-trader-query")]
+pub mod trader_query;
 
 // 预导入常用类型和消息
 pub mod prelude {
@@ -0,0 +1,155 @@
+use actix::prelude::*;
+use chrono::{DateTime, Utc};
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::actors::messages::*;
+use crate::config::QueuePolicy;
+
+/// 客户端名称的最大长度
+const MAX_NAME_LEN: usize = 64;
+/// 修改后延迟写入文件的时间，合并短时间内的多次修改
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// 客户端名称是否有效（1-64个字母、数字、`_`、`-`或`.`）
+pub fn is_valid_client_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// 客户端配置：以`client_name`连接的会话最后的订阅和限速设置，重连时恢复
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientProfile {
+    /// 客户端名称
+    pub name: String,
+    /// 订阅的合约（不含通配符模式和自选列表带来的合约）
+    #[serde(default)]
+    pub instruments: Vec<String>,
+    /// 订阅的通配符模式
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// 订阅的自选列表
+    #[serde(default)]
+    pub watchlists: Vec<String>,
+    /// 限速间隔（毫秒，0表示不限速）
+    #[serde(default)]
+    pub throttle_ms: u64,
+    /// 批量发送窗口（毫秒，0表示不使用）
+    #[serde(default)]
+    pub batch_window_ms: u64,
+    /// 发送队列溢出策略
+    #[serde(default)]
+    pub queue_policy: Option<QueuePolicy>,
+    /// 最后修改时间
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 客户端配置存储Actor
+///
+/// 按客户端名称保存会话最后的订阅和限速设置，配置了文件时修改后写入文件，
+/// 网关重启后以同一名称连接的客户端仍可恢复
+pub struct ProfileStore {
+    // 持久化文件
+    path: Option<PathBuf>,
+    // 名称 -> 客户端配置
+    profiles: HashMap<String, ClientProfile>,
+    // 是否已安排写入文件
+    save_scheduled: bool,
+}
+
+impl Actor for ProfileStore {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        info!("Profile store started with {} client profiles", self.profiles.len());
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        if self.save_scheduled {
+            self.save();
+        }
+    }
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProfileStore {
+    /// 创建只保存在内存中的存储
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            profiles: HashMap::new(),
+            save_scheduled: false,
+        }
+    }
+
+    /// 从文件加载客户端配置，文件不存在时创建空存储，之后的修改写入该文件
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let profiles = if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let profiles: Vec<ClientProfile> = serde_json::from_str(&content)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            profiles
+                .into_iter()
+                .map(|profile| (profile.name.clone(), profile))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            profiles,
+            save_scheduled: false,
+        })
+    }
+
+    /// 写入文件（先写临时文件再重命名，避免写到一半的文件）
+    fn save(&mut self) {
+        self.save_scheduled = false;
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut profiles: Vec<&ClientProfile> = self.profiles.values().collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        let result = serde_json::to_string_pretty(&profiles)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            .and_then(|content| {
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, content)?;
+                std::fs::rename(&tmp_path, path)
+            });
+        if let Err(e) = result {
+            error!("Failed to save client profiles to {}: {}", path.display(), e);
+        }
+    }
+}
+
+impl Handler<LoadProfile> for ProfileStore {
+    type Result = MessageResult<LoadProfile>;
+
+    fn handle(&mut self, msg: LoadProfile, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.profiles.get(&msg.name).cloned())
+    }
+}
+
+impl Handler<SaveProfile> for ProfileStore {
+    type Result = ();
+
+    fn handle(&mut self, msg: SaveProfile, ctx: &mut Self::Context) -> Self::Result {
+        self.profiles.insert(msg.0.name.clone(), msg.0);
+        if self.path.is_none() || self.save_scheduled {
+            return;
+        }
+        self.save_scheduled = true;
+        ctx.run_later(SAVE_DELAY, |act, _| act.save());
+    }
+}
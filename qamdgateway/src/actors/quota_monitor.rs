@@ -0,0 +1,125 @@
+use actix::prelude::*;
+use chrono::Utc;
+use hashbrown::HashMap;
+use tracing::{info, warn};
+
+use crate::actors::messages::*;
+
+/// 超限客户端记录
+struct Offender {
+    addr: Recipient<ForceDisconnect>,
+    info: OffenderInfo,
+}
+
+/// 客户端限额监控Actor
+///
+/// WebSocket会话在客户端超出订阅数或消息频率限额时上报，
+/// 管理接口据此列出超限的在线客户端并可强制断开。
+/// 会话同时上报心跳往返时间，供管理接口查询。
+#[derive(Default)]
+pub struct QuotaMonitor {
+    // 客户端ID -> 超限记录
+    offenders: HashMap<String, Offender>,
+    // 客户端ID -> 心跳往返时间
+    rtt: HashMap<String, ClientRttInfo>,
+}
+
+impl Actor for QuotaMonitor {
+    type Context = Context<Self>;
+}
+
+impl QuotaMonitor {
+    /// 创建限额监控Actor
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Handler<QuotaViolation> for QuotaMonitor {
+    type Result = ();
+
+    fn handle(&mut self, msg: QuotaViolation, _: &mut Self::Context) {
+        let now = Utc::now();
+        let offender = self
+            .offenders
+            .entry(msg.client_id.clone())
+            .or_insert_with(|| Offender {
+                addr: msg.addr,
+                info: OffenderInfo {
+                    client_id: msg.client_id.clone(),
+                    remote_addr: msg.remote_addr,
+                    violations: ViolationCounts::default(),
+                    first_violation: now,
+                    last_violation: now,
+                    last_detail: String::new(),
+                },
+            });
+        offender.info.violations.record(msg.kind);
+        offender.info.last_violation = now;
+        offender.info.last_detail = msg.detail;
+
+        // 首次超限及此后每100次记录一次日志，避免刷屏
+        let total = offender.info.violations.total();
+        if total == 1 || total % 100 == 0 {
+            warn!(
+                "Client {} ({}) exceeded its quota {} times: {}",
+                msg.client_id,
+                offender.info.remote_addr.as_deref().unwrap_or("unknown"),
+                total,
+                offender.info.last_detail
+            );
+        }
+    }
+}
+
+impl Handler<ForgetClient> for QuotaMonitor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ForgetClient, _: &mut Self::Context) {
+        self.offenders.remove(&msg.client_id);
+        self.rtt.remove(&msg.client_id);
+    }
+}
+
+impl Handler<ClientRtt> for QuotaMonitor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ClientRtt, _: &mut Self::Context) {
+        self.rtt.insert(msg.info.client_id.clone(), msg.info);
+    }
+}
+
+impl Handler<GetClientRtt> for QuotaMonitor {
+    type Result = MessageResult<GetClientRtt>;
+
+    fn handle(&mut self, _: GetClientRtt, _: &mut Self::Context) -> Self::Result {
+        let mut clients: Vec<ClientRttInfo> = self.rtt.values().cloned().collect();
+        clients.sort_by(|a, b| b.srtt_ms.total_cmp(&a.srtt_ms));
+        MessageResult(clients)
+    }
+}
+
+impl Handler<GetOffenders> for QuotaMonitor {
+    type Result = MessageResult<GetOffenders>;
+
+    fn handle(&mut self, _: GetOffenders, _: &mut Self::Context) -> Self::Result {
+        let mut offenders: Vec<OffenderInfo> =
+            self.offenders.values().map(|offender| offender.info.clone()).collect();
+        offenders.sort_by_key(|offender| std::cmp::Reverse(offender.violations.total()));
+        MessageResult(offenders)
+    }
+}
+
+impl Handler<DisconnectClient> for QuotaMonitor {
+    type Result = bool;
+
+    fn handle(&mut self, msg: DisconnectClient, _: &mut Self::Context) -> bool {
+        // 会话关闭时会发送ForgetClient，这里直接移除记录
+        let Some(offender) = self.offenders.remove(&msg.client_id) else {
+            return false;
+        };
+        info!("Disconnecting client {}: {}", msg.client_id, msg.reason);
+        offender.addr.do_send(ForceDisconnect { reason: msg.reason });
+        true
+    }
+}
@@ -0,0 +1,103 @@
+use actix::prelude::*;
+use tracing::{error, info, warn};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::actors::messages::*;
+use crate::config::RedisConfig;
+use crate::instruments::InstrumentRegistry;
+
+/// Redis桥接Actor
+///
+/// 将分发器接受的每条行情PUBLISH到`{prefix}:{exchange}:{instrument}`频道，
+/// 并在`snapshot_key`哈希表中保存每个合约的最新快照，供不支持WebSocket协议的程序使用。
+/// Redis客户端是阻塞的，因此运行在SyncArbiter线程中。
+pub struct RedisBridgeActor {
+    config: RedisConfig,
+    client: redis::Client,
+    connection: Option<redis::Connection>,
+    // 连接失败后暂停发布直到该时间
+    retry_after: Option<Instant>,
+    instruments: Arc<InstrumentRegistry>,
+}
+
+// 连接失败后的重试间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+impl Actor for RedisBridgeActor {
+    type Context = SyncContext<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        info!("Redis bridge started, publishing to {}", self.config.url);
+    }
+}
+
+impl RedisBridgeActor {
+    /// 创建Redis桥接Actor（第一条行情到达时才连接）
+    pub fn new(config: RedisConfig, client: redis::Client, instruments: Arc<InstrumentRegistry>) -> Self {
+        Self {
+            config,
+            client,
+            connection: None,
+            retry_after: None,
+            instruments,
+        }
+    }
+
+    /// 行情所属的交易所（优先使用合约前缀，其次查询合约信息）
+    fn exchange_of(&self, instrument_id: &str) -> String {
+        if let Some((exchange, _)) = instrument_id.split_once('.') {
+            return exchange.to_string();
+        }
+        self.instruments
+            .get(instrument_id)
+            .map(|info| info.exchange_id.clone())
+            .unwrap_or_else(|| "UNKNOWN".to_string())
+    }
+
+    /// 发布行情并更新最新快照
+    fn publish(&mut self, snapshot: &qamd_rs::MDSnapshot) -> redis::RedisResult<()> {
+        if self.connection.is_none() {
+            self.connection = Some(self.client.get_connection()?);
+        }
+
+        let payload = serde_json::to_string(snapshot).map_err(|e| {
+            redis::RedisError::from((redis::ErrorKind::TypeError, "serialize snapshot", e.to_string()))
+        })?;
+        let channel = format!(
+            "{}:{}:{}",
+            self.config.channel_prefix,
+            self.exchange_of(&snapshot.instrument_id),
+            snapshot.instrument_id
+        );
+
+        let connection = self.connection.as_mut().expect("connection initialized");
+        redis::pipe()
+            .cmd("PUBLISH").arg(&channel).arg(&payload).ignore()
+            .cmd("HSET").arg(&self.config.snapshot_key).arg(&snapshot.instrument_id).arg(&payload).ignore()
+            .query::<()>(connection)
+    }
+}
+
+impl Handler<MarketDataUpdate> for RedisBridgeActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        if self.retry_after.is_some_and(|retry_after| Instant::now() < retry_after) {
+            return;
+        }
+        
+        match self.publish(&msg.0) {
+            Ok(()) => self.retry_after = None,
+            Err(e) => {
+                // 丢弃行情直到重连间隔结束，避免Redis不可用时每条行情都尝试连接
+                if self.connection.take().is_some() {
+                    warn!("Redis connection lost: {}", e);
+                } else {
+                    error!("Failed to publish {} to Redis: {}", msg.0.instrument_id, e);
+                }
+                self.retry_after = Some(Instant::now() + RECONNECT_INTERVAL);
+            }
+        }
+    }
+}
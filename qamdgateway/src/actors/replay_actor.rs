@@ -0,0 +1,799 @@
+use actix::prelude::*;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use tracing::{error, info, warn};
+use qamd_rs::snapshot::canonical_field;
+use qamd_rs::MDSnapshot;
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::config::ReplayConfig;
+use crate::error::{GatewayError, GatewayResult};
+use crate::tick_archive::{open_tick_file, ArchiveIndex, INDEX_FILE};
+
+// 最大倍速模式下每轮发送的记录数，避免长时间阻塞Actor
+const MAX_SPEED_BATCH: usize = 1000;
+// 行情时间间隔超过该值（如午休、夜盘结束）时直接跳过等待
+const MAX_DATA_GAP: chrono::Duration = chrono::Duration::seconds(60);
+
+/// 回放速度
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// 按行情时间的倍数回放（1.0 为实时）
+    Multiplier(f64),
+    /// 尽可能快地回放
+    Max,
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s == "max" {
+            return Ok(ReplaySpeed::Max);
+        }
+        let factor = s.trim_end_matches('x');
+        match factor.parse::<f64>() {
+            Ok(v) if v > 0.0 && v.is_finite() => Ok(ReplaySpeed::Multiplier(v)),
+            _ => Err(format!("Invalid replay speed: {}", s)),
+        }
+    }
+}
+
+/// 历史行情回放Actor
+///
+/// 读取按QALfs目录结构存放的逐笔快照文件（CSV / JSON Lines / Parquet）或行情记录目录，
+/// 按配置的速度通过MarketDataDistributor发布，与实盘行情走同一条管道
+pub struct ReplayMarketDataActor {
+    config: ReplayConfig,
+    distributor: Addr<MarketDataDistributor>,
+    speed: ReplaySpeed,
+    // 待加载的文件
+    pending_files: VecDeque<PathBuf>,
+    // 当前文件中的快照（已按时间排序）
+    buffer: Vec<MDSnapshot>,
+    cursor: usize,
+    // 本轮（循环回放时从头开始后）是否加载到过数据
+    pass_loaded: bool,
+    // 回放时钟锚点 (真实时间, 行情时间)
+    anchor: Option<(Instant, DateTime<Utc>)>,
+    last_data_time: Option<DateTime<Utc>>,
+    running: bool,
+    published: u64,
+}
+
+impl Actor for ReplayMarketDataActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("ReplayMarketDataActor started, speed = {:?}", self.speed);
+
+        match resolve_replay_files(&self.config) {
+            Ok(files) if !files.is_empty() => {
+                info!("Replay will read {} file(s) from {}", files.len(), self.config.path);
+                self.pending_files = files.into();
+                self.running = true;
+                ctx.notify(ReplayStep);
+            }
+            Ok(_) => warn!("No replay files found under {}", self.config.path),
+            Err(e) => error!("Failed to resolve replay files: {}", e),
+        }
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        info!("ReplayMarketDataActor stopped, {} snapshots published", self.published);
+    }
+}
+
+impl ReplayMarketDataActor {
+    pub fn new(config: ReplayConfig, distributor: Addr<MarketDataDistributor>) -> Self {
+        let speed = config.speed.parse().unwrap_or_else(|e| {
+            warn!("{}, falling back to 1x", e);
+            ReplaySpeed::Multiplier(1.0)
+        });
+
+        Self {
+            config,
+            distributor,
+            speed,
+            pending_files: VecDeque::new(),
+            buffer: Vec::new(),
+            cursor: 0,
+            pass_loaded: false,
+            anchor: None,
+            last_data_time: None,
+            running: false,
+            published: 0,
+        }
+    }
+
+    /// 确保缓冲区中有待发送的数据，必要时加载下一个文件
+    fn fill_buffer(&mut self) -> bool {
+        while self.cursor >= self.buffer.len() {
+            let path = match self.pending_files.pop_front() {
+                Some(path) => path,
+                None => {
+                    if !self.config.loop_replay {
+                        return false;
+                    }
+                    // 整轮都没有数据（文件都加载失败或被合约过滤掉）时停止，否则会一直重新加载
+                    if !self.pass_loaded {
+                        error!("No snapshots loaded from {} in a full pass, stopping replay", self.config.path);
+                        return false;
+                    }
+                    match resolve_replay_files(&self.config) {
+                        Ok(files) if !files.is_empty() => {
+                            info!("Replay finished, restarting from the beginning");
+                            self.pending_files = files.into();
+                            self.pass_loaded = false;
+                            self.anchor = None;
+                            self.last_data_time = None;
+                            continue;
+                        }
+                        _ => return false,
+                    }
+                }
+            };
+
+            match load_replay_file(&path) {
+                Ok(mut snapshots) => {
+                    if !self.config.instruments.is_empty() {
+                        snapshots.retain(|s| accepts(&self.config, &s.instrument_id));
+                    }
+                    snapshots.sort_by_key(|s| s.datetime);
+                    info!("Loaded {} snapshots from {}", snapshots.len(), path.display());
+                    self.pass_loaded |= !snapshots.is_empty();
+                    self.buffer = snapshots;
+                    self.cursor = 0;
+                }
+                Err(e) => {
+                    error!("Failed to load replay file {}: {}", path.display(), e);
+                }
+            }
+        }
+        true
+    }
+
+    fn publish_current(&mut self) {
+        let snapshot = self.buffer[self.cursor].clone();
+        self.cursor += 1;
+        self.last_data_time = Some(snapshot.datetime);
+        self.published += 1;
+        self.distributor
+            .do_send(MarketDataUpdate(Arc::new(snapshot), MarketDataSource::Replay));
+    }
+
+    /// 发送所有已到期的快照，并安排下一次执行
+    fn step(&mut self, ctx: &mut Context<Self>) {
+        let mut sent = 0;
+
+        while self.running {
+            if !self.fill_buffer() {
+                info!("Replay complete, {} snapshots published", self.published);
+                self.running = false;
+                return;
+            }
+
+            match self.speed {
+                ReplaySpeed::Max => {
+                    self.publish_current();
+                    sent += 1;
+                    if sent >= MAX_SPEED_BATCH {
+                        ctx.notify(ReplayStep);
+                        return;
+                    }
+                }
+                ReplaySpeed::Multiplier(factor) => {
+                    let data_time = self.buffer[self.cursor].datetime;
+
+                    // 行情中断过长时重新对齐时钟，不做无意义的等待
+                    let gap_too_large = self
+                        .last_data_time
+                        .map(|last| data_time - last > MAX_DATA_GAP)
+                        .unwrap_or(false);
+                    if self.anchor.is_none() || gap_too_large {
+                        self.anchor = Some((Instant::now(), data_time));
+                    }
+
+                    let (real_start, data_start) = self.anchor.unwrap();
+                    let offset = (data_time - data_start).to_std().unwrap_or_default();
+                    let due = offset.div_f64(factor);
+                    let elapsed = real_start.elapsed();
+
+                    if due <= elapsed {
+                        self.publish_current();
+                    } else {
+                        ctx.notify_later(ReplayStep, due - elapsed);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 内部驱动消息
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ReplayStep;
+
+impl Handler<ReplayStep> for ReplayMarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, _: ReplayStep, ctx: &mut Self::Context) -> Self::Result {
+        self.step(ctx);
+    }
+}
+
+impl Handler<StartReplay> for ReplayMarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, _: StartReplay, ctx: &mut Self::Context) -> Self::Result {
+        if !self.running {
+            info!("Resuming replay");
+            self.running = true;
+            self.anchor = None;
+            ctx.notify(ReplayStep);
+        }
+    }
+}
+
+impl Handler<StopReplay> for ReplayMarketDataActor {
+    type Result = ();
+
+    fn handle(&mut self, _: StopReplay, _: &mut Self::Context) -> Self::Result {
+        info!("Pausing replay");
+        self.running = false;
+    }
+}
+
+impl Handler<SetReplaySpeed> for ReplayMarketDataActor {
+    type Result = Result<(), String>;
+
+    fn handle(&mut self, msg: SetReplaySpeed, ctx: &mut Self::Context) -> Self::Result {
+        self.speed = msg.speed.parse()?;
+        self.anchor = None;
+        info!("Replay speed set to {:?}", self.speed);
+        if self.running {
+            ctx.notify(ReplayStep);
+        }
+        Ok(())
+    }
+}
+
+/// 检查合约是否在回放过滤列表中（列表为空时回放所有合约）
+fn accepts(config: &ReplayConfig, instrument_id: &str) -> bool {
+    let code = instrument_id.rsplit('.').next().unwrap_or(instrument_id);
+    config.instruments.is_empty()
+        || config
+            .instruments
+            .iter()
+            .any(|i| i == instrument_id || i == code)
+}
+
+/// 根据配置找出需要回放的文件
+///
+/// `path`可以是单个文件；可以是行情记录目录（含`index.json`），此时按索引只读取
+/// 交易日落在`start..=end`内、且包含过滤合约的文件；也可以是QALfs根目录：
+/// 此时读取`{path}/{dataset}/`下文件名以`_{YYYY-MM-DD}`结尾、且日期落在`start..=end`内的文件
+pub fn resolve_replay_files(config: &ReplayConfig) -> GatewayResult<Vec<PathBuf>> {
+    let base = Path::new(&config.path);
+    if base.is_file() {
+        return Ok(vec![base.to_path_buf()]);
+    }
+
+    let parse_bound = |s: &Option<String>| -> GatewayResult<Option<NaiveDate>> {
+        s.as_deref()
+            .map(|d| {
+                NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                    .map_err(|e| GatewayError::ConfigError(format!("Invalid replay date {}: {}", d, e)))
+            })
+            .transpose()
+    };
+    let start = parse_bound(&config.start)?;
+    let end = parse_bound(&config.end)?;
+
+    if base.join(INDEX_FILE).is_file() {
+        let index = ArchiveIndex::load(base)?;
+        let files = index.select(start, end, |instrument| accepts(config, instrument));
+        return Ok(files.into_iter().map(|f| base.join(&f.file)).collect());
+    }
+
+    let dir = base.join(&config.dataset);
+    let mut files: Vec<(NaiveDate, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if !path.is_file() || ReplayFormat::from_path(&path).is_none() {
+            continue;
+        }
+        let date = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.split('.').next())
+            .and_then(|s| s.rsplit('_').next())
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        let Some(date) = date else {
+            continue;
+        };
+        if start.is_none_or(|s| date >= s) && end.is_none_or(|e| date <= e) {
+            files.push((date, path));
+        }
+    }
+
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// 回放文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplayFormat {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+impl ReplayFormat {
+    /// 根据扩展名判断格式，CSV和JSON Lines文件可以用zstd压缩（`.zst`）
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let (name, compressed) = match name.strip_suffix(".zst") {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        match (name.rsplit_once('.')?.1, compressed) {
+            ("csv", _) => Some(ReplayFormat::Csv),
+            ("jsonl" | "json", _) => Some(ReplayFormat::JsonLines),
+            ("pq" | "parquet", false) => Some(ReplayFormat::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// 读取单个回放文件
+pub fn load_replay_file(path: &Path) -> GatewayResult<Vec<MDSnapshot>> {
+    let format = ReplayFormat::from_path(path).ok_or_else(|| {
+        GatewayError::Other(format!("Unsupported replay file: {}", path.display()))
+    })?;
+
+    let records = match format {
+        ReplayFormat::Csv => read_csv_records(path)?,
+        ReplayFormat::JsonLines => read_jsonl_records(path)?,
+        ReplayFormat::Parquet => read_parquet_records(path)?,
+    };
+
+    let mut snapshots = Vec::with_capacity(records.len());
+    for record in records {
+        match record_to_snapshot(record) {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(e) => warn!("Skipping malformed record in {}: {}", path.display(), e),
+        }
+    }
+    Ok(snapshots)
+}
+
+fn read_csv_records(path: &Path) -> GatewayResult<Vec<Map<String, Value>>> {
+    let mut reader = csv::Reader::from_reader(open_tick_file(path)?);
+    let headers = reader
+        .headers()
+        .map_err(|e| GatewayError::Other(format!("CSV error: {}", e)))?
+        .clone();
+
+    let mut records = Vec::new();
+    for row in reader.records() {
+        let row = row.map_err(|e| GatewayError::Other(format!("CSV error: {}", e)))?;
+        let mut map = Map::new();
+        for (key, value) in headers.iter().zip(row.iter()) {
+            map.insert(key.to_string(), Value::String(value.to_string()));
+        }
+        records.push(map);
+    }
+    Ok(records)
+}
+
+fn read_jsonl_records(path: &Path) -> GatewayResult<Vec<Map<String, Value>>> {
+    let reader = BufReader::new(open_tick_file(path)?);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Value::Object(map) = serde_json::from_str::<Value>(&line)? {
+            records.push(map);
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "replay-parquet")]
+fn read_parquet_records(path: &Path) -> GatewayResult<Vec<Map<String, Value>>> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let reader = SerializedFileReader::new(fs::File::open(path)?)
+        .map_err(|e| GatewayError::Other(format!("Parquet error: {}", e)))?;
+    let rows = reader
+        .get_row_iter(None)
+        .map_err(|e| GatewayError::Other(format!("Parquet error: {}", e)))?;
+
+    let mut records = Vec::new();
+    for row in rows {
+        let row = row.map_err(|e| GatewayError::Other(format!("Parquet error: {}", e)))?;
+        if let Value::Object(map) = row.to_json_value() {
+            records.push(map);
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(not(feature = "replay-parquet"))]
+fn read_parquet_records(path: &Path) -> GatewayResult<Vec<Map<String, Value>>> {
+    Err(GatewayError::Other(format!(
+        "Parquet replay requires the `replay-parquet` feature: {}",
+        path.display()
+    )))
+}
+
+/// 将一行记录转换为MDSnapshot
+///
+/// 字段名与MDSnapshot一致，同时兼容QALfs中常见的
+/// `order_book_id` / `high` / `low` / `total_turnover` 等列名（见`qamd_rs::snapshot::FIELD_ALIASES`）
+fn record_to_snapshot(record: Map<String, Value>) -> GatewayResult<MDSnapshot> {
+    let mut snapshot = json!({
+        "instrument_id": "",
+        "amount": 0.0,
+        "ask_price1": 0.0,
+        "ask_volume1": 0,
+        "bid_price1": 0.0,
+        "bid_volume1": 0,
+        "close": null,
+        "highest": 0.0,
+        "last_price": 0.0,
+        "lower_limit": 0.0,
+        "lowest": 0.0,
+        "open": 0.0,
+        "open_interest": null,
+        "pre_close": 0.0,
+        "pre_open_interest": null,
+        "pre_settlement": null,
+        "settlement": null,
+        "upper_limit": 0.0,
+        "volume": 0,
+        "average": 0.0,
+        "iopv": null,
+    });
+    let fields = snapshot.as_object_mut().unwrap();
+
+    for (key, value) in record {
+        let key = canonical_field(&key).to_string();
+
+        let value = match key.as_str() {
+            "instrument_id" => match value {
+                Value::String(s) => Value::String(s),
+                other => Value::String(other.to_string()),
+            },
+            "datetime" => Value::String(parse_record_datetime(&value)?.to_rfc3339()),
+            k if k.contains("volume") => match to_number(value) {
+                Some(v) => json!(v.round() as i64),
+                None => continue,
+            },
+            _ => match value {
+                Value::String(s) if s.trim().is_empty() => continue,
+                Value::String(s) => match s.trim().parse::<f64>() {
+                    Ok(v) => json!(v),
+                    // 保留 "-" 等占位符，由OptionalF64处理
+                    Err(_) => Value::String(s),
+                },
+                other => other,
+            },
+        };
+        fields.insert(key, value);
+    }
+
+    if !fields.contains_key("datetime") {
+        return Err(GatewayError::ConversionError("Record has no datetime".to_string()));
+    }
+
+    serde_json::from_value(snapshot).map_err(GatewayError::from)
+}
+
+fn to_number(value: Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+/// 解析记录中的时间字段
+///
+/// 支持RFC3339字符串、带时区的字符串、无时区的本地时间（按北京时间处理）
+/// 以及秒/毫秒/微秒/纳秒的Unix时间戳
+fn parse_record_datetime(value: &Value) -> GatewayResult<DateTime<Utc>> {
+    let invalid = || GatewayError::ConversionError(format!("Invalid datetime: {}", value));
+
+    match value {
+        Value::Number(n) => {
+            let ts = n.as_i64().ok_or_else(invalid)?;
+            let dt = if ts > 100_000_000_000_000_000 {
+                Utc.timestamp_nanos(ts)
+            } else if ts > 100_000_000_000_000 {
+                Utc.timestamp_micros(ts).single().ok_or_else(invalid)?
+            } else if ts > 100_000_000_000 {
+                Utc.timestamp_millis_opt(ts).single().ok_or_else(invalid)?
+            } else {
+                Utc.timestamp_opt(ts, 0).single().ok_or_else(invalid)?
+            };
+            Ok(dt)
+        }
+        Value::String(s) => {
+            let s = s.trim();
+            if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+                return Ok(dt.with_timezone(&Utc));
+            }
+            if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f %:z") {
+                return Ok(dt.with_timezone(&Utc));
+            }
+            let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+                .map_err(|_| invalid())?;
+            let beijing = FixedOffset::east_opt(8 * 3600).unwrap();
+            beijing
+                .from_local_datetime(&naive)
+                .single()
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok_or_else(invalid)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qamd_rs::types::OptionalF64;
+
+    /// 测试用的临时目录
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qamd-replay-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn replay_config(path: &Path) -> ReplayConfig {
+        ReplayConfig {
+            enabled: true,
+            path: path.display().to_string(),
+            dataset: "tickdata".to_string(),
+            start: None,
+            end: None,
+            speed: "max".to_string(),
+            instruments: Vec::new(),
+            loop_replay: false,
+        }
+    }
+
+    fn record(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    const CSV_TICKS: &str = "order_book_id,datetime,last_price,high,low,volume,open_interest\n\
+        SHFE.rb2410,2024-07-01 09:00:01.500,3500,3510,3490,100.0,2000\n\
+        DCE.m2409,2024-07-01 09:00:00,3100,3110,3090,50,\n";
+
+    #[test]
+    fn record_to_snapshot_maps_aliases_and_numbers() {
+        let snapshot = record_to_snapshot(record(json!({
+            "order_book_id": "SHFE.rb2410",
+            "datetime": "2024-07-01T01:00:00Z",
+            "last_price": "3500.0",
+            "high": 3510.0,
+            "low": "3490",
+            "volume": "100.6",
+            "total_turnover": 3.5e6,
+            "open_interest": "",
+            "settlement": "-",
+        })))
+        .unwrap();
+        assert_eq!(snapshot.instrument_id, "SHFE.rb2410");
+        assert_eq!(snapshot.datetime, Utc.with_ymd_and_hms(2024, 7, 1, 1, 0, 0).unwrap());
+        assert_eq!(snapshot.last_price, 3500.0);
+        assert_eq!(snapshot.highest, 3510.0);
+        assert_eq!(snapshot.lowest, 3490.0);
+        assert_eq!(snapshot.volume, 101);
+        assert_eq!(snapshot.amount, 3.5e6);
+        assert_eq!(snapshot.open_interest, OptionalF64::Null);
+    }
+
+    #[test]
+    fn record_without_datetime_is_rejected() {
+        let result = record_to_snapshot(record(json!({ "instrument_id": "SHFE.rb2410", "last_price": 3500.0 })));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parse_record_datetime_formats() {
+        let expected = Utc.with_ymd_and_hms(2024, 7, 1, 1, 0, 0).unwrap();
+        for value in [
+            json!("2024-07-01T01:00:00Z"),
+            json!("2024-07-01T09:00:00+08:00"),
+            json!("2024-07-01 09:00:00 +08:00"),
+            // 无时区的时间按北京时间处理
+            json!("2024-07-01 09:00:00"),
+            json!(expected.timestamp()),
+            json!(expected.timestamp_millis()),
+            json!(expected.timestamp_micros()),
+            json!(expected.timestamp_nanos_opt().unwrap()),
+        ] {
+            assert_eq!(parse_record_datetime(&value).unwrap(), expected, "{}", value);
+        }
+        assert_eq!(
+            parse_record_datetime(&json!("2024-07-01 09:00:00.250")).unwrap(),
+            expected + chrono::Duration::milliseconds(250)
+        );
+        assert!(parse_record_datetime(&json!("yesterday")).is_err());
+        assert!(parse_record_datetime(&json!(true)).is_err());
+    }
+
+    #[test]
+    fn load_csv_file() {
+        let dir = temp_dir("csv");
+        let path = dir.join("ticks_2024-07-01.csv");
+        fs::write(&path, CSV_TICKS).unwrap();
+
+        let snapshots = load_replay_file(&path).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].instrument_id, "SHFE.rb2410");
+        assert_eq!(snapshots[0].highest, 3510.0);
+        assert_eq!(snapshots[0].volume, 100);
+        assert_eq!(snapshots[0].open_interest, OptionalF64::Value(2000.0));
+        assert_eq!(snapshots[1].instrument_id, "DCE.m2409");
+        assert_eq!(snapshots[1].open_interest, OptionalF64::Null);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unsupported_file_is_rejected() {
+        assert!(load_replay_file(Path::new("ticks_2024-07-01.txt")).is_err());
+        assert!(load_replay_file(Path::new("ticks_2024-07-01.pq.zst")).is_err());
+    }
+
+    #[cfg(feature = "replay-parquet")]
+    #[test]
+    fn load_parquet_file() {
+        use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+
+        let schema = "message ticks {
+            REQUIRED BYTE_ARRAY order_book_id (UTF8);
+            REQUIRED INT64 datetime;
+            REQUIRED DOUBLE last_price;
+            REQUIRED INT64 volume;
+        }";
+        let dir = temp_dir("parquet");
+        let path = dir.join("ticks_2024-07-01.pq");
+        let schema = std::sync::Arc::new(parse_message_type(schema).unwrap());
+        let mut writer =
+            SerializedFileWriter::new(fs::File::create(&path).unwrap(), schema, Default::default()).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column().unwrap() {
+            match index {
+                0 => {
+                    let ids: Vec<ByteArray> = vec!["SHFE.rb2410".into(), "SHFE.rb2410".into()];
+                    column.typed::<ByteArrayType>().write_batch(&ids, None, None)
+                }
+                1 => column.typed::<Int64Type>().write_batch(&[1719795600000, 1719795601000], None, None),
+                2 => column.typed::<DoubleType>().write_batch(&[3500.0, 3501.0], None, None),
+                _ => column.typed::<Int64Type>().write_batch(&[100, 102], None, None),
+            }
+            .unwrap();
+            column.close().unwrap();
+            index += 1;
+        }
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let snapshots = load_replay_file(&path).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].instrument_id, "SHFE.rb2410");
+        assert_eq!(snapshots[0].datetime, Utc.with_ymd_and_hms(2024, 7, 1, 1, 0, 0).unwrap());
+        assert_eq!(snapshots[1].last_price, 3501.0);
+        assert_eq!(snapshots[1].volume, 102);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn accepts_full_ids_and_codes() {
+        let mut config = replay_config(Path::new("."));
+        assert!(accepts(&config, "SHFE.rb2410"));
+        config.instruments = vec!["rb2410".to_string(), "DCE.m2409".to_string()];
+        assert!(accepts(&config, "SHFE.rb2410"));
+        assert!(accepts(&config, "rb2410"));
+        assert!(accepts(&config, "DCE.m2409"));
+        assert!(!accepts(&config, "m2409"));
+        assert!(!accepts(&config, "SHFE.hc2410"));
+    }
+
+    #[test]
+    fn resolve_files_by_date_in_order() {
+        let dir = temp_dir("resolve");
+        let dataset = dir.join("tickdata");
+        fs::create_dir_all(&dataset).unwrap();
+        for name in [
+            "future_tick_2024-07-03.csv",
+            "future_tick_2024-07-01.jsonl",
+            "future_tick_2024-07-02.csv.zst",
+            "future_tick_2024-07-04.pq",
+            "notes_2024-07-02.txt",
+            "future_tick_latest.csv",
+        ] {
+            fs::write(dataset.join(name), "").unwrap();
+        }
+        let names = |config: &ReplayConfig| -> Vec<String> {
+            resolve_replay_files(config)
+                .unwrap()
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let mut config = replay_config(&dir);
+        assert_eq!(
+            names(&config),
+            [
+                "future_tick_2024-07-01.jsonl",
+                "future_tick_2024-07-02.csv.zst",
+                "future_tick_2024-07-03.csv",
+                "future_tick_2024-07-04.pq",
+            ]
+        );
+
+        config.start = Some("2024-07-02".to_string());
+        config.end = Some("2024-07-03".to_string());
+        assert_eq!(names(&config), ["future_tick_2024-07-02.csv.zst", "future_tick_2024-07-03.csv"]);
+
+        config.start = Some("07/02/2024".to_string());
+        assert!(resolve_replay_files(&config).is_err());
+
+        // 单个文件忽略日期范围
+        let single = replay_config(&dataset.join("future_tick_2024-07-03.csv"));
+        assert_eq!(names(&single), ["future_tick_2024-07-03.csv"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn loop_replay_stops_when_a_pass_loads_nothing() {
+        let dir = temp_dir("loop");
+        let path = dir.join("ticks_2024-07-01.csv");
+        fs::write(&path, CSV_TICKS).unwrap();
+
+        let mut config = replay_config(&path);
+        config.loop_replay = true;
+        config.instruments = vec!["SHFE.hc2410".to_string()];
+        let distributor = MarketDataDistributor::new().start();
+        let mut actor = ReplayMarketDataActor::new(config.clone(), distributor.clone());
+        actor.pending_files = resolve_replay_files(&config).unwrap().into();
+        assert!(!actor.fill_buffer());
+
+        // 有数据时循环回放从头开始
+        config.instruments = vec!["rb2410".to_string()];
+        let mut actor = ReplayMarketDataActor::new(config.clone(), distributor);
+        actor.pending_files = resolve_replay_files(&config).unwrap().into();
+        assert!(actor.fill_buffer());
+        actor.cursor = actor.buffer.len();
+        assert!(actor.fill_buffer());
+        assert_eq!(actor.buffer.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
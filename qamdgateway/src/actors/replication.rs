@@ -0,0 +1,364 @@
+//! 双网关主备复制
+//!
+//! 主网关在`replication.path`上提供WebSocket复制通道，向备网关推送：
+//! - 连接建立时的完整订阅状态和行情缓存
+//! - 之后的每条行情、定期的订阅状态和心跳
+//!
+//! 备网关连接主网关并将收到的行情写入本地分发器缓存。
+//! 超过`takeover_secs`没有收到主网关的任何消息时，备网关接管：
+//! 恢复主网关的订阅状态并向上游订阅相应合约。接管后不再回切，主网关恢复后需人工处理。
+
+use actix::prelude::*;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use futures_util::StreamExt;
+use tracing::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+
+use crate::actors::md_connector::MarketDataConnector;
+use crate::actors::md_distributor::{DistributorState, MarketDataDistributor};
+use crate::actors::messages::*;
+use crate::config::ReplicationConfig;
+use crate::synthetic::is_synthetic;
+
+// 复制会话的邮箱容量，行情输出使用try_send投递，容量过小会丢行情
+const SESSION_MAILBOX_CAPACITY: usize = 65536;
+// 备网关检查主网关状态及重连的间隔
+const STANDBY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 复制通道消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReplicationMessage {
+    /// 完整订阅状态
+    State { state: DistributorState },
+    /// 行情缓存
+    Snapshots { snapshots: Vec<qamd_rs::MDSnapshot> },
+    /// 单条行情
+    Snapshot {
+        snapshot: Box<qamd_rs::MDSnapshot>,
+        source: MarketDataSource,
+    },
+    /// 心跳
+    Heartbeat { ts: i64 },
+}
+
+/// 主网关侧的复制会话
+pub struct ReplicationSession {
+    // 在分发器中注册的行情输出名称
+    sink_name: String,
+    config: ReplicationConfig,
+    distributor: Addr<MarketDataDistributor>,
+}
+
+impl ReplicationSession {
+    /// 创建复制会话
+    pub fn new(config: ReplicationConfig, distributor: Addr<MarketDataDistributor>) -> Self {
+        Self {
+            sink_name: format!("replication-{}", uuid::Uuid::new_v4()),
+            config,
+            distributor,
+        }
+    }
+
+    /// 发送复制消息
+    fn send(ctx: &mut ws::WebsocketContext<Self>, msg: &ReplicationMessage) {
+        match serde_json::to_string(msg) {
+            Ok(text) => ctx.text(text),
+            Err(e) => error!("Failed to serialize replication message: {}", e),
+        }
+    }
+
+    /// 向备网关推送当前订阅状态
+    fn send_state(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        self.distributor
+            .send(GetDistributorState)
+            .into_actor(self)
+            .map(|res, _, ctx| match res {
+                Ok(state) => Self::send(ctx, &ReplicationMessage::State { state }),
+                Err(e) => error!("Failed to get distributor state: {}", e),
+            })
+            .wait(ctx);
+    }
+}
+
+impl Actor for ReplicationSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Standby gateway connected, replicating as {}", self.sink_name);
+        ctx.set_mailbox_capacity(SESSION_MAILBOX_CAPACITY);
+
+        // 先发送完整状态和行情缓存，再注册为行情输出接收增量
+        self.send_state(ctx);
+        self.distributor
+            .send(GetSnapshotCache)
+            .into_actor(self)
+            .map(|res, act, ctx| {
+                match res {
+                    Ok(snapshots) => Self::send(ctx, &ReplicationMessage::Snapshots { snapshots }),
+                    Err(e) => error!("Failed to get snapshot cache: {}", e),
+                }
+                act.distributor.do_send(RegisterSnapshotSink {
+                    name: act.sink_name.clone(),
+                    addr: ctx.address().recipient(),
+                });
+            })
+            .wait(ctx);
+
+        ctx.run_interval(Duration::from_secs(self.config.heartbeat_secs.max(1)), |_, ctx| {
+            let ts = chrono::Utc::now().timestamp_millis();
+            Self::send(ctx, &ReplicationMessage::Heartbeat { ts });
+        });
+        ctx.run_interval(Duration::from_secs(self.config.state_secs.max(1)), |act, ctx| {
+            act.send_state(ctx);
+        });
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        info!("Standby gateway disconnected ({})", self.sink_name);
+        self.distributor.do_send(UnregisterSnapshotSink {
+            name: self.sink_name.clone(),
+        });
+        Running::Stop
+    }
+}
+
+impl Handler<MarketDataUpdate> for ReplicationSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, ctx: &mut Self::Context) -> Self::Result {
+        Self::send(
+            ctx,
+            &ReplicationMessage::Snapshot {
+                snapshot: Box::new(Arc::unwrap_or_clone(msg.0)),
+                source: msg.1,
+            },
+        );
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ReplicationSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!("Replication connection error: {}", e);
+                ctx.stop();
+            }
+        }
+    }
+}
+
+/// 主网关复制通道的WebSocket处理函数
+pub async fn replication_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    md_distributor: web::Data<Addr<MarketDataDistributor>>,
+    config: web::Data<ReplicationConfig>,
+) -> Result<HttpResponse, Error> {
+    let session = ReplicationSession::new(config.get_ref().clone(), md_distributor.get_ref().clone());
+    ws::start(session, &req, stream)
+}
+
+/// 从主网关收到的复制消息
+#[derive(Message)]
+#[rtype(result = "()")]
+struct PeerMessage(ReplicationMessage);
+
+/// 与主网关的连接断开
+#[derive(Message)]
+#[rtype(result = "()")]
+struct PeerDisconnected;
+
+/// 备网关复制Actor
+///
+/// 连接主网关的复制通道，镜像行情缓存和订阅状态，主网关失联后接管订阅
+pub struct StandbyActor {
+    config: ReplicationConfig,
+    distributor: Addr<MarketDataDistributor>,
+    connector: Addr<MarketDataConnector>,
+    // 接管时恢复订阅的客户端宽限期
+    restore_grace: Duration,
+    // 接管时向上游订阅使用的ID
+    client_id: uuid::Uuid,
+    // 最近一次收到主网关消息的时间
+    last_heard: Instant,
+    // 最近一次收到的订阅状态
+    state: Option<DistributorState>,
+    // 当前连接任务
+    connection: Option<SpawnHandle>,
+    // 是否已接管
+    active: bool,
+}
+
+impl Actor for StandbyActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!(
+            "Standby mode: replicating from {}, takeover after {}s of silence",
+            self.config.peer_url.as_deref().unwrap_or("-"),
+            self.config.takeover_secs
+        );
+        // 启动时视为刚收到主网关消息，给首次连接留出时间
+        self.last_heard = Instant::now();
+        self.connect(ctx);
+
+        ctx.run_interval(STANDBY_CHECK_INTERVAL, |act, ctx| {
+            if act.active {
+                return;
+            }
+            if act.last_heard.elapsed() >= Duration::from_secs(act.config.takeover_secs) {
+                act.takeover(ctx);
+            } else if act.connection.is_none() {
+                act.connect(ctx);
+            }
+        });
+    }
+}
+
+impl StandbyActor {
+    /// 创建备网关复制Actor
+    pub fn new(
+        config: ReplicationConfig,
+        distributor: Addr<MarketDataDistributor>,
+        connector: Addr<MarketDataConnector>,
+        restore_grace: Duration,
+    ) -> Self {
+        Self {
+            config,
+            distributor,
+            connector,
+            restore_grace,
+            client_id: uuid::Uuid::new_v4(),
+            last_heard: Instant::now(),
+            state: None,
+            connection: None,
+            active: false,
+        }
+    }
+
+    /// 连接主网关复制通道
+    fn connect(&mut self, ctx: &mut Context<Self>) {
+        let Some(url) = self.config.peer_url.clone() else {
+            return;
+        };
+        let addr = ctx.address();
+        let task = async move {
+            match tokio_tungstenite::connect_async(url.as_str()).await {
+                Ok((mut stream, _)) => {
+                    info!("Connected to primary gateway at {}", url);
+                    while let Some(msg) = stream.next().await {
+                        match msg {
+                            Ok(tokio_tungstenite::tungstenite::Message::Text(text)) => {
+                                match serde_json::from_str::<ReplicationMessage>(&text) {
+                                    Ok(msg) => addr.do_send(PeerMessage(msg)),
+                                    Err(e) => warn!("Invalid replication message: {}", e),
+                                }
+                            }
+                            Ok(tokio_tungstenite::tungstenite::Message::Close(_)) => break,
+                            Ok(_) => {}
+                            Err(e) => {
+                                warn!("Replication connection to {} failed: {}", url, e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to connect to primary gateway at {}: {}", url, e),
+            }
+            addr.do_send(PeerDisconnected);
+        };
+        self.connection = Some(ctx.spawn(task.into_actor(self)));
+    }
+
+    /// 接管主网关的订阅
+    fn takeover(&mut self, ctx: &mut Context<Self>) {
+        warn!(
+            "No message from primary gateway for {}s, taking over subscriptions",
+            self.config.takeover_secs
+        );
+        self.active = true;
+        if let Some(handle) = self.connection.take() {
+            ctx.cancel_future(handle);
+        }
+
+        let Some(state) = self.state.take() else {
+            warn!("No subscription state replicated from primary, nothing to take over");
+            return;
+        };
+        let connector = self.connector.clone();
+        let client_id = self.client_id;
+        self.distributor
+            .send(RestoreDistributorState {
+                state,
+                grace: self.restore_grace,
+            })
+            .into_actor(self)
+            .map(move |res, _, _| match res {
+                Ok(restored) => {
+                    // 合成合约由各腿计算，不向上游订阅
+                    let instruments: Vec<String> = restored
+                        .into_iter()
+                        .filter(|instrument| !is_synthetic(instrument))
+                        .collect();
+                    info!("Took over {} instruments from primary gateway", instruments.len());
+                    if !instruments.is_empty() {
+                        connector.do_send(Subscribe {
+                            id: client_id,
+                            instruments,
+                        });
+                    }
+                }
+                Err(e) => error!("Failed to restore replicated subscriptions: {}", e),
+            })
+            .spawn(ctx);
+    }
+}
+
+impl Handler<PeerMessage> for StandbyActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: PeerMessage, _: &mut Self::Context) -> Self::Result {
+        if self.active {
+            return;
+        }
+        self.last_heard = Instant::now();
+
+        match msg.0 {
+            ReplicationMessage::State { state } => self.state = Some(state),
+            ReplicationMessage::Snapshots { snapshots } => {
+                info!("Received {} cached snapshots from primary gateway", snapshots.len());
+                for snapshot in snapshots {
+                    let source = self
+                        .state
+                        .as_ref()
+                        .and_then(|state| state.source_map.get(&snapshot.instrument_id).copied())
+                        .unwrap_or(MarketDataSource::CTP);
+                    self.distributor.do_send(MarketDataUpdate(Arc::new(snapshot), source));
+                }
+            }
+            ReplicationMessage::Snapshot { snapshot, source } => {
+                self.distributor.do_send(MarketDataUpdate(Arc::new(*snapshot), source));
+            }
+            ReplicationMessage::Heartbeat { .. } => {}
+        }
+    }
+}
+
+impl Handler<PeerDisconnected> for StandbyActor {
+    type Result = ();
+
+    fn handle(&mut self, _: PeerDisconnected, _: &mut Self::Context) -> Self::Result {
+        // 由定时检查负责重连或接管
+        self.connection = None;
+    }
+}
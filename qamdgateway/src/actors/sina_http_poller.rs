@@ -0,0 +1,256 @@
+//! 新浪HTTP行情轮询
+//!
+//! 新浪前置（TDX风格）不可用时的备用数据源：按固定间隔请求`hq.sinajs.cn`，
+//! 解析GBK编码的响应并转换为MDSnapshot。每次轮询拉取分发器当前订阅的合约
+//! 以及配置中的合约，只支持沪深北A股/基金/债券代码。
+//!
+//! 响应格式（每个代码一行）：
+//! `var hq_str_sh600000="名称,今开,昨收,最新价,最高,最低,买一,卖一,成交量,成交额,
+//! 买一量,买一价,...,买五量,买五价,卖一量,卖一价,...,卖五量,卖五价,日期,时间,状态";`
+
+use actix::prelude::*;
+use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+use hashbrown::HashMap;
+use tracing::{debug, error, info, warn};
+use qamd_rs::{MDSnapshot, MDSnapshotBuilder};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::calendar::china_offset;
+use crate::config::SinaHttpConfig;
+use crate::journal::RawJournal;
+use crate::latency;
+
+// 新浪行情接口校验Referer，缺少时返回403
+const SINA_REFERER: &str = "https://finance.sina.com.cn";
+// 单次响应的最大字节数
+const MAX_RESPONSE_BYTES: usize = 4 * 1024 * 1024;
+// 行情字段数（不含名称之后可能追加的字段）
+const MIN_QUOTE_FIELDS: usize = 32;
+
+/// 合约代码转换为新浪行情代码（如`SSE.600000` -> `sh600000`）
+///
+/// 不带交易所前缀的6位代码按首位数字推断交易所，期货等其他合约返回None
+pub fn to_sina_code(instrument_id: &str) -> Option<String> {
+    let (prefix, code) = match instrument_id.split_once('.') {
+        Some((exchange, code)) => {
+            let prefix = match exchange {
+                "SSE" => "sh",
+                "SZSE" => "sz",
+                "BSE" => "bj",
+                _ => return None,
+            };
+            (prefix, code)
+        }
+        None => {
+            let prefix = match instrument_id.chars().next()? {
+                '5' | '6' | '9' => "sh",
+                '0' | '1' | '2' | '3' => "sz",
+                '4' | '8' => "bj",
+                _ => return None,
+            };
+            (prefix, instrument_id)
+        }
+    };
+    if code.len() != 6 || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}{}", prefix, code))
+}
+
+/// 拆分一行响应，返回新浪代码和行情字段
+fn parse_line(line: &str) -> Option<(&str, Vec<&str>)> {
+    let rest = line.trim().strip_prefix("var hq_str_")?;
+    let (code, rest) = rest.split_once('=')?;
+    let body = rest.trim_end_matches(';').trim_matches('"');
+    Some((code, body.split(',').collect()))
+}
+
+/// 将一行行情字段转换为MDSnapshot
+///
+/// 停牌或代码无效时新浪返回空字符串，此时返回None
+pub fn parse_quote(instrument_id: &str, fields: &[&str]) -> Option<MDSnapshot> {
+    if fields.len() < MIN_QUOTE_FIELDS {
+        return None;
+    }
+    let price = |i: usize| fields[i].parse::<f64>().unwrap_or(0.0);
+    // 成交量、挂单量以股为单位，部分代码返回带小数的值
+    let volume = |i: usize| fields[i].parse::<f64>().map(|v| v as i64).unwrap_or(0);
+
+    let date = NaiveDate::parse_from_str(fields[30], "%Y-%m-%d").ok()?;
+    let time = NaiveTime::parse_from_str(fields[31], "%H:%M:%S").ok()?;
+    let datetime = china_offset()
+        .from_local_datetime(&date.and_time(time))
+        .single()?
+        .with_timezone(&Utc);
+
+    let mut builder = MDSnapshotBuilder::new(instrument_id, datetime)
+        .open(price(1))
+        .pre_close(price(2))
+        .last_price(price(3))
+        .highest(price(4))
+        .lowest(price(5))
+        .volume(volume(8))
+        .amount(price(9));
+    for level in 1..=5 {
+        let bid = 10 + (level - 1) * 2;
+        let ask = 20 + (level - 1) * 2;
+        builder = builder
+            .bid(level, price(bid + 1), volume(bid))
+            .ask(level, price(ask + 1), volume(ask));
+    }
+    Some(builder.build())
+}
+
+/// 新浪HTTP行情轮询Actor
+///
+/// 轮询得到的行情以`MarketDataSource::SinaHttp`发送到分发器，
+/// 开启故障切换时排在优先级末尾，仅在其他数据源停止推送时采用
+pub struct SinaHttpPollerActor {
+    config: SinaHttpConfig,
+    distributor: Addr<MarketDataDistributor>,
+    client: awc::Client,
+    // 尚未完成的请求数，上一轮未完成时跳过本轮
+    in_flight: usize,
+    // 原始响应日志（解析前写入）
+    journal: Option<Arc<RawJournal>>,
+}
+
+impl Actor for SinaHttpPollerActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!(
+            "Sina HTTP poller started, polling {} every {}ms",
+            self.config.url, self.config.interval_ms
+        );
+        ctx.run_interval(Duration::from_millis(self.config.interval_ms.max(100)), |act, ctx| {
+            act.poll(ctx);
+        });
+    }
+}
+
+impl SinaHttpPollerActor {
+    /// 创建新浪HTTP行情轮询Actor
+    pub fn new(config: SinaHttpConfig, distributor: Addr<MarketDataDistributor>) -> Self {
+        let client = awc::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .finish();
+        Self {
+            config,
+            distributor,
+            client,
+            in_flight: 0,
+            journal: None,
+        }
+    }
+
+    /// 解析前把原始响应写入日志
+    pub fn with_journal(mut self, journal: Arc<RawJournal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// 获取当前订阅的合约并发起一轮轮询
+    fn poll(&mut self, ctx: &mut Context<Self>) {
+        if self.in_flight > 0 {
+            debug!("Previous Sina HTTP poll still running, skipping");
+            return;
+        }
+        self.distributor
+            .send(GetAllSubscriptions)
+            .into_actor(self)
+            .map(|res, act, ctx| {
+                let mut instruments = act.config.instruments.clone();
+                match res {
+                    Ok(active) => instruments.extend(active),
+                    Err(e) => error!("Failed to get active subscriptions for Sina HTTP poller: {}", e),
+                }
+                act.fetch(ctx, instruments);
+            })
+            .spawn(ctx);
+    }
+
+    /// 按批次请求行情
+    fn fetch(&mut self, ctx: &mut Context<Self>, instruments: Vec<String>) {
+        // 新浪代码 -> 订阅该代码的合约ID（带/不带交易所前缀可能同时订阅）
+        let mut codes: HashMap<String, Vec<String>> = HashMap::new();
+        for instrument in instruments {
+            if let Some(code) = to_sina_code(&instrument) {
+                let ids = codes.entry(code).or_default();
+                if !ids.contains(&instrument) {
+                    ids.push(instrument);
+                }
+            }
+        }
+        if codes.is_empty() {
+            return;
+        }
+
+        let codes: Vec<(String, Vec<String>)> = codes.into_iter().collect();
+        for batch in codes.chunks(self.config.batch_size.max(1)) {
+            let list: Vec<&str> = batch.iter().map(|(code, _)| code.as_str()).collect();
+            let url = format!("{}{}", self.config.url, list.join(","));
+            let request = self
+                .client
+                .get(url)
+                .insert_header(("Referer", SINA_REFERER))
+                .send();
+            let ids: HashMap<String, Vec<String>> = batch.iter().cloned().collect();
+
+            self.in_flight += 1;
+            async move {
+                let mut response = request.await.map_err(|e| e.to_string())?;
+                if !response.status().is_success() {
+                    return Err(format!("HTTP {}", response.status()));
+                }
+                response
+                    .body()
+                    .limit(MAX_RESPONSE_BYTES)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            .into_actor(self)
+            .map(move |res, act, _| {
+                act.in_flight -= 1;
+                match res {
+                    Ok(body) => act.publish(&body, &ids),
+                    Err(e) => warn!("Sina HTTP quote request failed: {}", e),
+                }
+            })
+            .spawn(ctx);
+        }
+    }
+
+    /// 解析响应并发送行情到分发器
+    fn publish(&self, body: &[u8], ids: &HashMap<String, Vec<String>>) {
+        if let Some(journal) = &self.journal {
+            if let Err(e) = journal.append_bytes(MarketDataSource::SinaHttp, latency::now_us(), body) {
+                warn!("Failed to journal Sina HTTP response: {}", e);
+            }
+        }
+        let (text, _, malformed) = encoding_rs::GBK.decode(body);
+        if malformed {
+            debug!("Sina HTTP response contains invalid GBK sequences");
+        }
+
+        for line in text.lines() {
+            let Some((code, fields)) = parse_line(line) else {
+                continue;
+            };
+            let Some(instrument_ids) = ids.get(code) else {
+                continue;
+            };
+            for instrument_id in instrument_ids {
+                match parse_quote(instrument_id, &fields) {
+                    Some(snapshot) => self
+                        .distributor
+                        .do_send(MarketDataUpdate(Arc::new(snapshot), MarketDataSource::SinaHttp)),
+                    None => debug!("No Sina HTTP quote for {}", instrument_id),
+                }
+            }
+        }
+    }
+}
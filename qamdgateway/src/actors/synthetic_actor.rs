@@ -0,0 +1,145 @@
+use actix::prelude::*;
+use hashbrown::{HashMap, HashSet};
+use tracing::{debug, info, warn};
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::synthetic::SyntheticInstrument;
+
+/// 合成合约计算Actor
+///
+/// 以普通客户端的身份向分发器订阅各腿合约，同时注册为行情输出以接收完整的MDSnapshot。
+/// 任一腿有新行情时重新计算引用该腿的合成合约，并把结果作为普通行情发回分发器，
+/// 由分发器推送给订阅了合成合约的客户端。
+pub struct SyntheticActor {
+    // 在分发器中的客户端ID
+    client_id: String,
+    distributor: Addr<MarketDataDistributor>,
+    // 合成合约代码 -> 定义
+    definitions: HashMap<String, SyntheticInstrument>,
+    // 各腿最新行情（腿代码 -> 行情）
+    quotes: HashMap<String, qamd_rs::MDSnapshot>,
+}
+
+impl Actor for SyntheticActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Synthetic instrument engine started");
+
+        let addr = ctx.address();
+        self.distributor.do_send(RegisterDataReceiver {
+            client_id: self.client_id.clone(),
+            addr: addr.clone().recipient(),
+            instruments: Vec::new(),
+            notify: None,
+            remap: None,
+            disconnect: None,
+        });
+        self.distributor.do_send(RegisterSnapshotSink {
+            name: "synthetic".to_string(),
+            addr: addr.clone().recipient(),
+        });
+        self.distributor.do_send(RegisterSyntheticEngine { addr });
+    }
+}
+
+impl SyntheticActor {
+    /// 创建合成合约计算Actor
+    pub fn new(distributor: Addr<MarketDataDistributor>) -> Self {
+        Self {
+            client_id: format!("synthetic-{}", uuid::Uuid::new_v4()),
+            distributor,
+            definitions: HashMap::new(),
+            quotes: HashMap::new(),
+        }
+    }
+
+    /// 全部合成合约引用的腿
+    fn legs(&self) -> HashSet<String> {
+        self.definitions
+            .values()
+            .flat_map(|definition| definition.legs.iter().map(|leg| leg.instrument.clone()))
+            .collect()
+    }
+
+    /// 向分发器更新腿合约的订阅，并清理不再使用的腿行情
+    fn update_leg_subscription(&mut self) {
+        let legs = self.legs();
+        self.quotes.retain(|leg, _| legs.contains(leg));
+        self.distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: legs.into_iter().collect(),
+        });
+    }
+}
+
+impl Handler<DefineSynthetic> for SyntheticActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: DefineSynthetic, _: &mut Self::Context) -> Self::Result {
+        if self.definitions.contains_key(&msg.instrument) {
+            return;
+        }
+        match SyntheticInstrument::parse(&msg.instrument) {
+            Ok(definition) => {
+                info!(
+                    "Defined synthetic instrument {} ({:?}, {} legs)",
+                    msg.instrument,
+                    definition.kind,
+                    definition.legs.len()
+                );
+                self.definitions.insert(msg.instrument, definition);
+                self.update_leg_subscription();
+            }
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+impl Handler<RemoveSynthetic> for SyntheticActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RemoveSynthetic, _: &mut Self::Context) -> Self::Result {
+        if self.definitions.remove(&msg.instrument).is_some() {
+            info!("Removed synthetic instrument {}", msg.instrument);
+            self.update_leg_subscription();
+        }
+    }
+}
+
+impl Handler<MarketDataUpdate> for SyntheticActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let (snapshot, source) = (msg.0, msg.1);
+
+        // 找到该行情对应的腿（腿可能不带交易所前缀）
+        let mut affected = Vec::new();
+        for (instrument, definition) in &self.definitions {
+            for leg in &definition.legs {
+                if leg.matches(&snapshot.instrument_id) {
+                    self.quotes.insert(leg.instrument.clone(), (*snapshot).clone());
+                    affected.push(instrument.clone());
+                }
+            }
+        }
+
+        for instrument in affected {
+            let Some(definition) = self.definitions.get_mut(&instrument) else {
+                continue;
+            };
+            if let Some(synthetic) = definition.compute(&self.quotes) {
+                debug!("Synthetic {} = {}", instrument, synthetic.last_price);
+                self.distributor.do_send(MarketDataUpdate(std::sync::Arc::new(synthetic), source));
+            }
+        }
+    }
+}
+
+/// 腿合约的行情通过行情输出接收，客户端通道推送的增量JSON不需要处理
+impl Handler<MarketDataUpdateMessage> for SyntheticActor {
+    type Result = ();
+
+    fn handle(&mut self, _: MarketDataUpdateMessage, _: &mut Self::Context) -> Self::Result {}
+}
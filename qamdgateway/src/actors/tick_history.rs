@@ -0,0 +1,89 @@
+use actix::prelude::*;
+use chrono::Duration as ChronoDuration;
+use hashbrown::HashMap;
+use tracing::info;
+use qamd_rs::MDSnapshot;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::actors::messages::*;
+use crate::config::HistoryConfig;
+use crate::instruments::matches_pattern;
+
+/// 近期行情缓存Actor
+///
+/// 作为行情输出注册到分发器，为每个合约保留最近`retention_minutes`分钟的行情，
+/// 供图表客户端初始化时回填，无需单独的历史数据库。
+/// 过期按行情时间而非本地时间判断，回放模式下同样适用。
+pub struct TickHistoryActor {
+    retention: ChronoDuration,
+    max_ticks: usize,
+    ticks: HashMap<String, VecDeque<MDSnapshot>>,
+}
+
+impl Actor for TickHistoryActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        info!(
+            "Tick history started, keeping {} minutes of ticks per instrument",
+            self.retention.num_minutes()
+        );
+    }
+}
+
+impl TickHistoryActor {
+    /// 创建近期行情缓存Actor
+    pub fn new(config: &HistoryConfig) -> Self {
+        Self {
+            retention: ChronoDuration::minutes(config.retention_minutes as i64),
+            max_ticks: config.max_ticks_per_instrument.max(1),
+            ticks: HashMap::new(),
+        }
+    }
+}
+
+impl Handler<MarketDataUpdate> for TickHistoryActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let snapshot = msg.0;
+        let cutoff = snapshot.datetime - self.retention;
+        let ticks = self
+            .ticks
+            .entry_ref(snapshot.instrument_id.as_str())
+            .or_default();
+        // 丢弃过期行情，超出数量上限时丢弃最旧的
+        while ticks
+            .front()
+            .is_some_and(|tick| tick.datetime < cutoff || ticks.len() >= self.max_ticks)
+        {
+            ticks.pop_front();
+        }
+        ticks.push_back(Arc::unwrap_or_clone(snapshot));
+    }
+}
+
+impl Handler<GetTickHistory> for TickHistoryActor {
+    type Result = MessageResult<GetTickHistory>;
+
+    fn handle(&mut self, msg: GetTickHistory, _: &mut Self::Context) -> Self::Result {
+        // 查询窗口不超过缓存的时长
+        let retention = self.retention.num_seconds().max(0) as u64;
+        let window = ChronoDuration::seconds(msg.seconds.min(retention) as i64);
+        let mut ticks: Vec<MDSnapshot> = self
+            .ticks
+            .iter()
+            .filter(|(instrument, _)| matches_pattern(&msg.instrument, instrument))
+            .flat_map(|(_, ticks)| {
+                let cutoff = ticks.back().map(|latest| latest.datetime - window);
+                ticks
+                    .iter()
+                    .filter(move |tick| cutoff.is_some_and(|cutoff| tick.datetime >= cutoff))
+            })
+            .cloned()
+            .collect();
+        ticks.sort_by_key(|tick| tick.datetime);
+        MessageResult(ticks)
+    }
+}
@@ -0,0 +1,207 @@
+use actix::prelude::*;
+use chrono::NaiveTime;
+use hashbrown::HashMap;
+use tracing::{error, info, warn};
+use qamd_rs::MDSnapshot;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::actors::messages::*;
+use crate::calendar::china_offset;
+use crate::config::RecorderConfig;
+use crate::instruments::matches_pattern;
+use crate::tick_archive::{compress_file, TickArchive};
+
+/// 检查行情文件是否需要轮换的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 导出为CSV时的一行（固定列，只含一档行情）
+#[derive(Debug, Clone, Serialize)]
+pub struct TickRow {
+    /// 北京时间
+    pub datetime: String,
+    pub instrument_id: String,
+    pub last_price: f64,
+    pub volume: i64,
+    pub amount: f64,
+    pub open_interest: Option<f64>,
+    pub bid_price1: f64,
+    pub bid_volume1: i64,
+    pub ask_price1: f64,
+    pub ask_volume1: i64,
+    pub open: f64,
+    pub highest: f64,
+    pub lowest: f64,
+}
+
+impl From<&MDSnapshot> for TickRow {
+    fn from(snapshot: &MDSnapshot) -> Self {
+        Self {
+            datetime: snapshot
+                .datetime
+                .with_timezone(&china_offset())
+                .format("%Y-%m-%d %H:%M:%S%.3f")
+                .to_string(),
+            instrument_id: snapshot.instrument_id.clone(),
+            last_price: snapshot.last_price,
+            volume: snapshot.volume,
+            amount: snapshot.amount,
+            open_interest: snapshot.open_interest.as_f64_opt(),
+            bid_price1: snapshot.bid_price1,
+            bid_volume1: snapshot.bid_volume1,
+            ask_price1: snapshot.ask_price1,
+            ask_volume1: snapshot.ask_volume1,
+            open: snapshot.open,
+            highest: snapshot.highest,
+            lowest: snapshot.lowest,
+        }
+    }
+}
+
+/// 行情时间（北京时间）是否在时段内，起始晚于结束时表示跨午夜（夜盘）
+fn in_window(time: NaiveTime, from: Option<NaiveTime>, to: Option<NaiveTime>) -> bool {
+    match (from, to) {
+        (Some(from), Some(to)) if from > to => time >= from || time <= to,
+        (from, to) => from.is_none_or(|from| time >= from) && to.is_none_or(|to| time <= to),
+    }
+}
+
+/// 日内行情记录Actor
+///
+/// 作为行情输出注册到分发器，为每个合约在内存中保留最近的行情（环形缓冲），
+/// 供导出接口做临时分析；配置了目录时同时写入按交易日轮换的行情文件
+pub struct TickRecorderActor {
+    config: RecorderConfig,
+    ticks: HashMap<String, VecDeque<MDSnapshot>>,
+    // 行情文件
+    archive: Option<TickArchive>,
+}
+
+impl Actor for TickRecorderActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!(
+            "Tick recorder started, keeping up to {} ticks per instrument",
+            self.config.max_ticks_per_instrument
+        );
+        if let Some(archive) = &mut self.archive {
+            // 清理过期文件，压缩上次运行结束时未压缩的文件
+            archive.remove_expired();
+            let files = if self.config.compress {
+                archive.uncompressed_files()
+            } else {
+                Vec::new()
+            };
+            for path in files {
+                self.compress(path, ctx);
+            }
+            ctx.run_interval(CHECK_INTERVAL, |act, ctx| {
+                let Some(archive) = &mut act.archive else {
+                    return;
+                };
+                match archive.check() {
+                    Ok(finished) => act.finished(finished, ctx),
+                    Err(e) => warn!("Failed to rotate tick file: {}", e),
+                }
+            });
+        }
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        // 停止时不等待压缩，下次启动时再压缩
+        if let Some(archive) = &mut self.archive {
+            if let Err(e) = archive.finish() {
+                error!("Failed to close tick file: {}", e);
+            }
+        }
+    }
+}
+
+impl TickRecorderActor {
+    /// 创建日内行情记录Actor
+    pub fn new(config: RecorderConfig) -> Self {
+        Self {
+            config,
+            ticks: HashMap::new(),
+            archive: None,
+        }
+    }
+
+    /// 同时写入行情文件
+    pub fn with_archive(mut self, archive: TickArchive) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// 文件轮换后压缩写完的文件
+    fn finished(&mut self, path: Option<PathBuf>, ctx: &mut Context<Self>) {
+        if let Some(path) = path.filter(|_| self.config.compress) {
+            self.compress(path, ctx);
+        }
+    }
+
+    /// 在阻塞线程中压缩文件，完成后更新索引
+    fn compress(&mut self, path: PathBuf, ctx: &mut Context<Self>) {
+        let level = self.config.compression_level;
+        let task = tokio::task::spawn_blocking(move || {
+            let compressed = compress_file(&path, level);
+            (path, compressed)
+        });
+        ctx.spawn(task.into_actor(self).map(|result, act, _| match result {
+            Ok((path, Ok(compressed))) => {
+                if let Some(archive) = &mut act.archive {
+                    if let Err(e) = archive.compressed(&path, &compressed) {
+                        error!("Failed to update tick index: {}", e);
+                    }
+                }
+            }
+            Ok((path, Err(e))) => error!("Failed to compress tick file {}: {}", path.display(), e),
+            Err(e) => error!("Tick file compression panicked: {}", e),
+        }));
+    }
+}
+
+impl Handler<MarketDataUpdate> for TickRecorderActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, ctx: &mut Self::Context) -> Self::Result {
+        let snapshot = msg.0;
+        if let Some(archive) = &mut self.archive {
+            match archive.append(&snapshot) {
+                Ok(finished) => self.finished(finished, ctx),
+                Err(e) => warn!("Failed to write tick file: {}", e),
+            }
+        }
+        let capacity = self.config.max_ticks_per_instrument.max(1);
+        let ticks = self
+            .ticks
+            .entry_ref(snapshot.instrument_id.as_str())
+            .or_default();
+        if ticks.len() >= capacity {
+            ticks.pop_front();
+        }
+        ticks.push_back(Arc::unwrap_or_clone(snapshot));
+    }
+}
+
+impl Handler<GetRecordedTicks> for TickRecorderActor {
+    type Result = MessageResult<GetRecordedTicks>;
+
+    fn handle(&mut self, msg: GetRecordedTicks, _: &mut Self::Context) -> Self::Result {
+        let offset = china_offset();
+        let mut ticks: Vec<MDSnapshot> = self
+            .ticks
+            .iter()
+            .filter(|(instrument, _)| matches_pattern(&msg.instrument, instrument))
+            .flat_map(|(_, ticks)| ticks.iter())
+            .filter(|tick| in_window(tick.datetime.with_timezone(&offset).time(), msg.from, msg.to))
+            .cloned()
+            .collect();
+        ticks.sort_by_key(|tick| tick.datetime);
+        MessageResult(ticks)
+    }
+}
@@ -0,0 +1,298 @@
+//! 只读的CTP交易前置查询
+//!
+//! 登录交易前置只为查询合约、保证金率和手续费率，不报单也不查询资金持仓。
+//! 查询结果写入合约注册表，行情推送的margin和commission字段据此计算。
+//! 需要启用`ctp-trader-query`特性。
+
+use actix::prelude::*;
+use ctp_common::{
+    ApiResult, CThostFtdcInstrumentCommissionRateField, CThostFtdcInstrumentMarginRateField,
+    CThostFtdcQryInstrumentCommissionRateField, CThostFtdcQryInstrumentMarginRateField, normalize_double,
+    THOST_FTDC_HF_Speculation,
+};
+use ctp_trader::{GenericTraderApi, TraderApi, TraderSpiOutput};
+use hashbrown::HashMap;
+use tracing::{debug, error, info, warn};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config::TraderQueryConfig;
+use crate::error::{GatewayError, GatewayResult};
+use crate::instrument_query::{
+    check_rsp, connect, fill_cstr, instrument_info, recv_until, request_instruments, wait_login,
+};
+use crate::instruments::{matches_pattern, CommissionRate, InstrumentInfo, InstrumentRegistry, MarginRate, TradingRates};
+
+// 查询请求被流控时的重试间隔
+const THROTTLE_RETRY: Duration = Duration::from_secs(1);
+
+/// 定期查询账户费率的Actor，每轮查询在独立线程中进行
+pub struct TraderQueryActor {
+    config: TraderQueryConfig,
+    registry: Arc<InstrumentRegistry>,
+    // 正在进行的一轮查询
+    running: Option<JoinHandle<()>>,
+}
+
+impl TraderQueryActor {
+    pub fn new(config: TraderQueryConfig, registry: Arc<InstrumentRegistry>) -> Self {
+        Self {
+            config,
+            registry,
+            running: None,
+        }
+    }
+
+    /// 开始一轮查询，上一轮尚未结束时跳过
+    fn start_round(&mut self) {
+        if self.running.as_ref().is_some_and(|round| !round.is_finished()) {
+            warn!("Previous CTP rate query is still running, skipping this round");
+            return;
+        }
+        if let Err(e) = std::fs::create_dir_all(&self.config.flow_path) {
+            warn!("Failed to create CTP flow directory {}: {}", self.config.flow_path, e);
+        }
+        let config = self.config.clone();
+        let registry = self.registry.clone();
+        self.running = Some(std::thread::spawn(move || match query_rates(&config, &registry) {
+            Ok(count) => info!("Updated trading rates of {} instruments from CTP", count),
+            Err(e) => error!("Failed to query trading rates from CTP: {}", e),
+        }));
+    }
+}
+
+impl Actor for TraderQueryActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("CTP rate query started on trader front {}", self.config.login.front_addr);
+        self.start_round();
+        if self.config.refresh_secs > 0 {
+            ctx.run_interval(Duration::from_secs(self.config.refresh_secs), |act, _| act.start_round());
+        }
+    }
+}
+
+/// 一轮查询：登录后查询合约，再按品种查询手续费率、按合约查询保证金率
+///
+/// 收到的费率立即写入注册表，中途失败时已写入的费率保留
+fn query_rates(config: &TraderQueryConfig, registry: &InstrumentRegistry) -> GatewayResult<usize> {
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let (mut api, receiver) = connect(&config.login, &config.flow_path)?;
+    wait_login(&mut api, &receiver, &config.login, Instant::now() + timeout)?;
+
+    let instruments = query_instruments(&mut api, &receiver, timeout)?;
+    let mut instruments: Vec<InstrumentInfo> = instruments
+        .into_iter()
+        .filter(|info| info.product_class == "FUTURE")
+        .filter(|info| {
+            let instrument = format!("{}.{}", info.exchange_id, info.instrument_id);
+            config.instruments.is_empty()
+                || config.instruments.iter().any(|pattern| matches_pattern(pattern, &instrument))
+        })
+        .collect();
+    instruments.sort_by(|a, b| a.instrument_id.cmp(&b.instrument_id));
+    info!("Querying trading rates of {} instruments from CTP", instruments.len());
+
+    // 先以交易所保证金率填充，账户费率到达后覆盖
+    let mut rates: HashMap<String, TradingRates> = instruments
+        .iter()
+        .map(|info| {
+            let margin = info.long_margin_ratio.or(info.short_margin_ratio).map(|_| MarginRate {
+                long_ratio: info.long_margin_ratio.unwrap_or_default(),
+                short_ratio: info.short_margin_ratio.unwrap_or_default(),
+                ..MarginRate::default()
+            });
+            let rates = TradingRates {
+                volume_multiple: info.volume_multiple,
+                margin,
+                commission: None,
+            };
+            (info.instrument_id.clone(), rates)
+        })
+        .collect();
+    registry.update_rates(rates.iter().map(|(instrument, rates)| (instrument.clone(), *rates)));
+
+    // 手续费率通常按品种设置，每个品种只查询一个合约
+    let mut products: HashMap<&str, Vec<&str>> = HashMap::new();
+    for info in &instruments {
+        products.entry(info.product_id.as_str()).or_default().push(info.instrument_id.as_str());
+    }
+    let interval = Duration::from_millis(config.query_interval_ms);
+    for (product, members) in &products {
+        std::thread::sleep(interval);
+        let mut req = CThostFtdcQryInstrumentCommissionRateField::default();
+        fill_cstr(&mut req.BrokerID, &config.login.broker_id);
+        fill_cstr(&mut req.InvestorID, &config.login.user_id);
+        fill_cstr(&mut req.InstrumentID, members[0]);
+        let deadline = Instant::now() + timeout;
+        send_query("commission rate", deadline, || api.req_qry_instrument_commission_rate(&req, 4))?;
+        let Some(commission) = receive_commission_rate(&receiver, deadline)? else {
+            debug!("No commission rate for product {}", product);
+            continue;
+        };
+        let updated: Vec<(String, TradingRates)> = members
+            .iter()
+            .filter_map(|instrument| {
+                let entry = rates.get_mut(*instrument)?;
+                entry.commission = Some(commission);
+                Some((instrument.to_string(), *entry))
+            })
+            .collect();
+        registry.update_rates(updated);
+    }
+
+    for info in &instruments {
+        std::thread::sleep(interval);
+        let mut req = CThostFtdcQryInstrumentMarginRateField::default();
+        fill_cstr(&mut req.BrokerID, &config.login.broker_id);
+        fill_cstr(&mut req.InvestorID, &config.login.user_id);
+        fill_cstr(&mut req.InstrumentID, &info.instrument_id);
+        req.HedgeFlag = THOST_FTDC_HF_Speculation;
+        let deadline = Instant::now() + timeout;
+        send_query("margin rate", deadline, || api.req_qry_instrument_margin_rate(&req, 5))?;
+        let Some(field) = receive_margin_rate(&receiver, deadline)? else {
+            debug!("No margin rate for {}", info.instrument_id);
+            continue;
+        };
+        let Some(entry) = rates.get_mut(&info.instrument_id) else {
+            continue;
+        };
+        entry.margin = Some(margin_rate(&field, info));
+        registry.update_rates([(info.instrument_id.clone(), *entry)]);
+    }
+    Ok(rates.len())
+}
+
+/// 查询全部合约信息
+fn query_instruments(
+    api: &mut TraderApi,
+    receiver: &mpsc::Receiver<TraderSpiOutput>,
+    timeout: Duration,
+) -> GatewayResult<Vec<InstrumentInfo>> {
+    let deadline = Instant::now() + timeout;
+    request_instruments(api, deadline)?;
+    let mut instruments = Vec::new();
+    loop {
+        match receive(receiver, deadline, "instrument query")? {
+            TraderSpiOutput::RspQryInstrument(rsp) => {
+                check_rsp("instrument query", &rsp.result)?;
+                instruments.extend(rsp.instrument.as_ref().and_then(instrument_info));
+                if rsp.is_last {
+                    return Ok(instruments);
+                }
+            }
+            TraderSpiOutput::RspError(rsp) => check_rsp("instrument query", &rsp.result)?,
+            _ => {}
+        }
+    }
+}
+
+/// 接收手续费率查询的应答，没有费率记录时返回None
+fn receive_commission_rate(
+    receiver: &mpsc::Receiver<TraderSpiOutput>,
+    deadline: Instant,
+) -> GatewayResult<Option<CommissionRate>> {
+    let mut commission = None;
+    loop {
+        match receive(receiver, deadline, "commission rate query")? {
+            TraderSpiOutput::RspQryInstrumentCommissionRate(rsp) => {
+                check_rsp("commission rate query", &rsp.result)?;
+                if let Some(field) = &rsp.instrument_commission_rate {
+                    commission = Some(commission_rate(field));
+                }
+                if rsp.is_last {
+                    return Ok(commission);
+                }
+            }
+            TraderSpiOutput::RspError(rsp) => check_rsp("commission rate query", &rsp.result)?,
+            _ => {}
+        }
+    }
+}
+
+/// 接收保证金率查询的应答，没有费率记录时返回None
+fn receive_margin_rate(
+    receiver: &mpsc::Receiver<TraderSpiOutput>,
+    deadline: Instant,
+) -> GatewayResult<Option<CThostFtdcInstrumentMarginRateField>> {
+    let mut margin = None;
+    loop {
+        match receive(receiver, deadline, "margin rate query")? {
+            TraderSpiOutput::RspQryInstrumentMarginRate(rsp) => {
+                check_rsp("margin rate query", &rsp.result)?;
+                if rsp.instrument_margin_rate.is_some() {
+                    margin = rsp.instrument_margin_rate;
+                }
+                if rsp.is_last {
+                    return Ok(margin);
+                }
+            }
+            TraderSpiOutput::RspError(rsp) => check_rsp("margin rate query", &rsp.result)?,
+            _ => {}
+        }
+    }
+}
+
+/// 接收下一条回报，前置断开时结束本轮查询
+fn receive(
+    receiver: &mpsc::Receiver<TraderSpiOutput>,
+    deadline: Instant,
+    request: &str,
+) -> GatewayResult<TraderSpiOutput> {
+    match recv_until(receiver, deadline) {
+        Ok(TraderSpiOutput::FrontDisconnected(disconnected)) => Err(GatewayError::UpstreamUnavailable(format!(
+            "CTP trader front disconnected during {}: {:?}",
+            request, disconnected.reason
+        ))),
+        Ok(output) => Ok(output),
+        Err(_) => Err(GatewayError::UpstreamUnavailable(format!("CTP {} timed out", request))),
+    }
+}
+
+/// 发送查询请求，被流控时等待后重试
+fn send_query(request: &str, deadline: Instant, mut send: impl FnMut() -> ApiResult) -> GatewayResult<()> {
+    loop {
+        match send() {
+            Ok(()) => return Ok(()),
+            Err(e) if Instant::now() + THROTTLE_RETRY < deadline => {
+                debug!("CTP {} rejected ({:?}), retrying", request, e);
+                std::thread::sleep(THROTTLE_RETRY);
+            }
+            Err(e) => return Err(GatewayError::CtpError(format!("Failed to send {}: {:?}", request, e))),
+        }
+    }
+}
+
+/// CTP以DBL_MAX表示无效值
+fn rate(value: f64) -> f64 {
+    normalize_double(value).filter(|v| v.is_finite() && *v >= 0.0).unwrap_or_default()
+}
+
+fn commission_rate(field: &CThostFtdcInstrumentCommissionRateField) -> CommissionRate {
+    CommissionRate {
+        open_ratio: rate(field.OpenRatioByMoney),
+        open_per_lot: rate(field.OpenRatioByVolume),
+        close_ratio: rate(field.CloseRatioByMoney),
+        close_per_lot: rate(field.CloseRatioByVolume),
+        close_today_ratio: rate(field.CloseTodayRatioByMoney),
+        close_today_per_lot: rate(field.CloseTodayRatioByVolume),
+    }
+}
+
+/// 转换账户保证金率，相对交易所收取时加上交易所保证金率
+fn margin_rate(field: &CThostFtdcInstrumentMarginRateField, info: &InstrumentInfo) -> MarginRate {
+    let (exchange_long, exchange_short) = if field.IsRelative != 0 {
+        (info.long_margin_ratio.unwrap_or_default(), info.short_margin_ratio.unwrap_or_default())
+    } else {
+        (0.0, 0.0)
+    };
+    MarginRate {
+        long_ratio: exchange_long + rate(field.LongMarginRatioByMoney),
+        long_per_lot: rate(field.LongMarginRatioByVolume),
+        short_ratio: exchange_short + rate(field.ShortMarginRatioByMoney),
+        short_per_lot: rate(field.ShortMarginRatioByVolume),
+    }
+}
@@ -0,0 +1,319 @@
+use actix::prelude::*;
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use hashbrown::HashMap;
+use qamd_rs::MDSnapshot;
+use tracing::{debug, info};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::actors::messages::*;
+use crate::calendar::{china_offset, TradingCalendar, TradingPhase, FUTURES_EXCHANGES};
+use crate::config::TradingPhaseConfig;
+use crate::instruments::matches_pattern;
+
+/// 单个交易所的当前阶段
+struct ExchangePhase {
+    phase: TradingPhase,
+    source: PhaseSource,
+    since: DateTime<Utc>,
+}
+
+/// 等待结算价的交易日结束
+struct PendingEnd {
+    trading_day: NaiveDate,
+    deadline: Instant,
+}
+
+/// 单个客户端的交易阶段订阅
+struct ClientPhase {
+    addr: Recipient<PhaseChanged>,
+    session_end: Option<Recipient<SessionEnd>>,
+    exchanges: Vec<String>,
+}
+
+impl ClientPhase {
+    fn watches(&self, exchange: &str) -> bool {
+        self.exchanges
+            .iter()
+            .any(|pattern| matches_pattern(pattern, exchange))
+    }
+}
+
+/// 交易阶段Actor
+///
+/// 按交易日历为每个交易所推算交易阶段（盘前、集合竞价、连续交易、休市、收盘），
+/// 作为行情输出注册到分发器：交易时段之外收到成交量增加的行情时，
+/// 在`activity_secs`内把该交易所视为连续交易（例如临时调整的交易时段）。
+/// 阶段变化推送给订阅的客户端会话以及据此抑制无行情报告和重连的内部Actor。
+///
+/// 交易所日盘收盘后，期货交易所等到当天有行情的合约都收到结算价（最多`settlement_wait_secs`），
+/// 其他交易所在收盘时，向订阅者发出带各合约最终行情的交易日结束通知
+pub struct TradingPhaseActor {
+    config: TradingPhaseConfig,
+    calendar: Arc<TradingCalendar>,
+    // 交易所 -> 当前阶段
+    phases: HashMap<String, ExchangePhase>,
+    // 合约ID -> 最新行情
+    latest: HashMap<String, Arc<MDSnapshot>>,
+    // 交易所 -> 等待结算价的交易日结束
+    pending_ends: HashMap<String, PendingEnd>,
+    // 交易所 -> 最近一次交易日结束
+    ends: HashMap<String, SessionEnd>,
+    // 交易所 -> 最近一次成交量增加的时间
+    activity: HashMap<String, Instant>,
+    // 客户端ID -> 订阅
+    clients: HashMap<String, ClientPhase>,
+}
+
+impl Actor for TradingPhaseActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Trading phase tracker started");
+
+        self.update();
+        ctx.run_interval(Duration::from_secs(self.config.check_interval_secs.max(1)), |act, _| {
+            act.update();
+        });
+    }
+}
+
+impl TradingPhaseActor {
+    /// 创建交易阶段Actor
+    pub fn new(config: TradingPhaseConfig, calendar: Arc<TradingCalendar>) -> Self {
+        Self {
+            config,
+            calendar,
+            phases: HashMap::new(),
+            latest: HashMap::new(),
+            pending_ends: HashMap::new(),
+            ends: HashMap::new(),
+            activity: HashMap::new(),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// 按交易日历和最近的成交推算交易所当前的阶段
+    fn evaluate(&self, exchange: &str) -> (TradingPhase, PhaseSource) {
+        let phase = self.calendar.phase(
+            exchange,
+            TradingCalendar::now_local(),
+            ChronoDuration::minutes(self.config.pre_open_minutes),
+            ChronoDuration::minutes(self.config.auction_minutes),
+        );
+        let active = self
+            .activity
+            .get(exchange)
+            .is_some_and(|last| last.elapsed() < Duration::from_secs(self.config.activity_secs));
+        if active && !phase.is_trading() {
+            (TradingPhase::Continuous, PhaseSource::Quotes)
+        } else {
+            (phase, PhaseSource::Calendar)
+        }
+    }
+
+    /// 重新推算各交易所的阶段，推送发生的变化
+    fn update(&mut self) {
+        let now = Utc::now();
+        let local = TradingCalendar::now_local();
+        for info in qamd_rs::constants::exchange::EXCHANGES.iter() {
+            let (phase, source) = self.evaluate(info.code);
+            let previous = self.phases.get(info.code).map(|current| current.phase);
+            if previous == Some(phase) {
+                continue;
+            }
+            debug!("{} trading phase {:?} -> {:?} ({:?})", info.code, previous, phase, source);
+            self.phases.insert(
+                info.code.to_string(),
+                ExchangePhase { phase, source, since: now },
+            );
+            if previous.is_some() {
+                self.publish(PhaseChanged {
+                    exchange: info.code.to_string(),
+                    phase,
+                    previous,
+                    source,
+                    datetime: now,
+                });
+            }
+            if previous.is_some() && phase == TradingPhase::Closed && self.calendar.is_after_day_close(info.code, local) {
+                self.begin_session_end(info.code, local.date());
+            }
+        }
+
+        // 等待结算价超时的交易所直接结束
+        let expired: Vec<String> = self
+            .pending_ends
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= Instant::now())
+            .map(|(exchange, _)| exchange.clone())
+            .collect();
+        for exchange in expired {
+            self.end_session(&exchange);
+        }
+    }
+
+    /// 日盘收盘：期货交易所等待结算价，其他交易所立即结束交易日
+    fn begin_session_end(&mut self, exchange: &str, trading_day: NaiveDate) {
+        let ended = self.ends.get(exchange).is_some_and(|end| end.trading_day == trading_day);
+        if ended || self.pending_ends.contains_key(exchange) {
+            return;
+        }
+        let wait = Duration::from_secs(self.config.settlement_wait_secs);
+        self.pending_ends.insert(
+            exchange.to_string(),
+            PendingEnd {
+                trading_day,
+                deadline: Instant::now() + wait,
+            },
+        );
+        if wait.is_zero() || !FUTURES_EXCHANGES.contains(&exchange) || self.is_settled(exchange, trading_day) {
+            self.end_session(exchange);
+        } else {
+            info!("{} closed, waiting up to {}s for settlement prices", exchange, wait.as_secs());
+        }
+    }
+
+    /// 交易所当天有行情的合约的最新行情
+    fn session_snapshots<'a>(
+        &'a self,
+        exchange: &'a str,
+        trading_day: NaiveDate,
+    ) -> impl Iterator<Item = &'a MDSnapshot> + 'a {
+        let offset = china_offset();
+        self.latest.values().map(Arc::as_ref).filter(move |snapshot| {
+            snapshot
+                .instrument_id
+                .split_once('.')
+                .is_some_and(|(prefix, _)| prefix.eq_ignore_ascii_case(exchange))
+                && self.calendar.trading_day(snapshot.datetime.with_timezone(&offset).naive_local()) == trading_day
+        })
+    }
+
+    /// 当天有行情的合约是否都已收到结算价
+    fn is_settled(&self, exchange: &str, trading_day: NaiveDate) -> bool {
+        let mut snapshots = self.session_snapshots(exchange, trading_day).peekable();
+        snapshots.peek().is_some() && snapshots.all(|snapshot| snapshot.settlement.as_f64_opt().is_some())
+    }
+
+    /// 结束交易所的交易日，向订阅者发出带最终行情的通知
+    fn end_session(&mut self, exchange: &str) {
+        let Some(pending) = self.pending_ends.remove(exchange) else {
+            return;
+        };
+        let mut snapshots: Vec<MDSnapshot> = self.session_snapshots(exchange, pending.trading_day).cloned().collect();
+        snapshots.sort_by(|a, b| a.instrument_id.cmp(&b.instrument_id));
+        let event = SessionEnd {
+            exchange: exchange.to_string(),
+            trading_day: pending.trading_day,
+            instruments: snapshots.len(),
+            settled: snapshots.iter().filter(|snapshot| snapshot.settlement.as_f64_opt().is_some()).count(),
+            datetime: Utc::now(),
+            snapshots: Arc::new(snapshots),
+        };
+        info!(
+            "{} trading day {} ended, {} of {} instruments settled",
+            exchange, event.trading_day, event.settled, event.instruments
+        );
+        self.clients.retain(|_, client| client.addr.connected());
+        for client in self.clients.values().filter(|client| client.watches(exchange)) {
+            if let Some(session_end) = &client.session_end {
+                session_end.do_send(event.clone());
+            }
+        }
+        self.ends.insert(exchange.to_string(), event);
+    }
+
+    /// 推送给订阅该交易所的客户端，清理已经断开的订阅
+    fn publish(&mut self, event: PhaseChanged) {
+        self.clients.retain(|_, client| client.addr.connected());
+        for client in self.clients.values() {
+            if client.watches(&event.exchange) {
+                client.addr.do_send(event.clone());
+            }
+        }
+    }
+
+    /// 各交易所的当前阶段
+    fn current(&self) -> impl Iterator<Item = PhaseChanged> + '_ {
+        self.phases.iter().map(|(exchange, current)| PhaseChanged {
+            exchange: exchange.clone(),
+            phase: current.phase,
+            previous: None,
+            source: current.source,
+            datetime: current.since,
+        })
+    }
+}
+
+impl Handler<MarketDataUpdate> for TradingPhaseActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let snapshot = msg.0;
+        let Some((exchange, _)) = snapshot.instrument_id.split_once('.') else {
+            return;
+        };
+        let exchange = exchange.to_uppercase();
+        let volume = snapshot.volume;
+        let previous = self.latest.insert(snapshot.instrument_id.clone(), snapshot).map(|previous| previous.volume);
+        // 收盘后等待结算价的交易所在全部合约收到结算价后结束交易日
+        if let Some(pending) = self.pending_ends.get(&exchange) {
+            if self.is_settled(&exchange, pending.trading_day) {
+                self.end_session(&exchange);
+            }
+        }
+        // 只有成交量增加才算交易，收盘后的结算价等行情不改变成交量
+        if previous.is_some_and(|previous| volume > previous) {
+            let traded = self
+                .phases
+                .get(&exchange)
+                .is_some_and(|current| current.phase.is_trading());
+            self.activity.insert(exchange, Instant::now());
+            if !traded {
+                self.update();
+            }
+        }
+    }
+}
+
+impl Handler<SubscribePhase> for TradingPhaseActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribePhase, _: &mut Self::Context) -> Self::Result {
+        debug!("Client {} trading phase subscriptions: {:?}", msg.client_id, msg.exchanges);
+        if msg.exchanges.is_empty() {
+            self.clients.remove(&msg.client_id);
+            return;
+        }
+        let client = ClientPhase {
+            addr: msg.addr,
+            session_end: msg.session_end,
+            exchanges: msg.exchanges,
+        };
+        for event in self.current().filter(|event| client.watches(&event.exchange)) {
+            client.addr.do_send(event);
+        }
+        self.clients.insert(msg.client_id, client);
+    }
+}
+
+impl Handler<GetTradingPhases> for TradingPhaseActor {
+    type Result = MessageResult<GetTradingPhases>;
+
+    fn handle(&mut self, _: GetTradingPhases, _: &mut Self::Context) -> Self::Result {
+        let mut phases: Vec<PhaseChanged> = self.current().collect();
+        phases.sort_by(|a, b| a.exchange.cmp(&b.exchange));
+        MessageResult(phases)
+    }
+}
+
+impl Handler<GetSessionEnds> for TradingPhaseActor {
+    type Result = MessageResult<GetSessionEnds>;
+
+    fn handle(&mut self, _: GetSessionEnds, _: &mut Self::Context) -> Self::Result {
+        let mut ends: Vec<SessionEnd> = self.ends.values().cloned().collect();
+        ends.sort_by(|a, b| a.exchange.cmp(&b.exchange));
+        MessageResult(ends)
+    }
+}
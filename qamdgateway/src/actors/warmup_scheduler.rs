@@ -0,0 +1,180 @@
+use actix::prelude::*;
+use chrono::Duration as ChronoDuration;
+use hashbrown::HashSet;
+use tracing::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::calendar::{exchange_sessions, SessionSchedule, TradingCalendar};
+use crate::config::{WarmupConfig, WarmupGroup};
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::InstrumentRegistry;
+
+/// 预热的合约组
+struct ScheduledGroup {
+    name: String,
+    instruments: Vec<String>,
+    schedules: Vec<SessionSchedule>,
+}
+
+impl ScheduledGroup {
+    /// 解析合约组的时段（自定义时段优先，否则使用交易所时段）并展开合约组
+    fn new(group: &WarmupGroup, registry: &InstrumentRegistry) -> GatewayResult<Self> {
+        let schedules = if !group.sessions.is_empty() {
+            group
+                .sessions
+                .iter()
+                .map(|spec| SessionSchedule::parse(spec))
+                .collect::<GatewayResult<Vec<_>>>()?
+        } else {
+            let exchange = group.exchange.as_deref().ok_or_else(|| {
+                GatewayError::ConfigError(format!(
+                    "Warm-up group {} needs either sessions or an exchange",
+                    group.name
+                ))
+            })?;
+            exchange_sessions(exchange)
+                .ok_or_else(|| {
+                    GatewayError::ConfigError(format!(
+                        "Warm-up group {}: no sessions known for exchange {}",
+                        group.name, exchange
+                    ))
+                })?
+                .iter()
+                .copied()
+                .map(SessionSchedule::from_session)
+                .collect()
+        };
+        Ok(Self {
+            name: group.name.clone(),
+            instruments: registry.expand(&group.instruments),
+            schedules,
+        })
+    }
+}
+
+/// 开盘预热订阅Actor
+///
+/// 以普通客户端的身份向分发器订阅：在合约组的时段开始前`lead_minutes`订阅，
+/// 收盘后`linger_minutes`取消订阅。真实客户端仍在订阅的合约由分发器继续保留。
+pub struct WarmupScheduler {
+    // 在分发器中的客户端ID
+    client_id: String,
+    distributor: Addr<MarketDataDistributor>,
+    calendar: Arc<TradingCalendar>,
+    groups: Vec<ScheduledGroup>,
+    lead: ChronoDuration,
+    linger: ChronoDuration,
+    check_interval: Duration,
+    // 当前处于预热窗口的合约组
+    active: HashSet<String>,
+}
+
+impl Actor for WarmupScheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!(
+            "Warm-up scheduler started with {} groups, subscribing {} minutes before session open",
+            self.groups.len(),
+            self.lead.num_minutes()
+        );
+        self.distributor.do_send(RegisterDataReceiver {
+            client_id: self.client_id.clone(),
+            addr: ctx.address().recipient(),
+            instruments: Vec::new(),
+            notify: None,
+            remap: None,
+            disconnect: None,
+        });
+        self.check();
+        ctx.run_interval(self.check_interval, |act, _| act.check());
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        self.distributor.do_send(UnregisterDataReceiver {
+            client_id: self.client_id.clone(),
+        });
+        Running::Stop
+    }
+}
+
+impl WarmupScheduler {
+    /// 创建预热调度Actor，时段定义无效时返回错误
+    pub fn new(
+        config: &WarmupConfig,
+        calendar: Arc<TradingCalendar>,
+        registry: &InstrumentRegistry,
+        distributor: Addr<MarketDataDistributor>,
+    ) -> GatewayResult<Self> {
+        let groups = config
+            .groups
+            .iter()
+            .map(|group| ScheduledGroup::new(group, registry))
+            .collect::<GatewayResult<Vec<_>>>()?;
+        Ok(Self {
+            client_id: format!("warmup-{}", uuid::Uuid::new_v4()),
+            distributor,
+            calendar,
+            groups,
+            lead: ChronoDuration::minutes(config.lead_minutes),
+            linger: ChronoDuration::minutes(config.linger_minutes),
+            check_interval: Duration::from_secs(config.check_interval_secs.max(1)),
+            active: HashSet::new(),
+        })
+    }
+
+    /// 检查各合约组是否处于预热窗口，有变化时更新订阅
+    fn check(&mut self) {
+        let now = TradingCalendar::now_local();
+        let active: HashSet<String> = self
+            .groups
+            .iter()
+            .filter(|group| {
+                group.schedules.iter().any(|schedule| {
+                    self.calendar
+                        .is_in_session_window(schedule, now, self.lead, self.linger)
+                })
+            })
+            .map(|group| group.name.clone())
+            .collect();
+        if active == self.active {
+            return;
+        }
+
+        for group in &self.groups {
+            match (self.active.contains(&group.name), active.contains(&group.name)) {
+                (false, true) => info!(
+                    "Warming up {} instruments of group {}",
+                    group.instruments.len(),
+                    group.name
+                ),
+                (true, false) => info!("Releasing warm-up subscriptions of group {}", group.name),
+                _ => {}
+            }
+        }
+
+        let instruments: HashSet<String> = self
+            .groups
+            .iter()
+            .filter(|group| active.contains(&group.name))
+            .flat_map(|group| group.instruments.iter().cloned())
+            .collect();
+        if instruments.is_empty() && !active.is_empty() {
+            warn!("Active warm-up groups {:?} contain no known instruments", active);
+        }
+        self.distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: instruments.into_iter().collect(),
+        });
+        self.active = active;
+    }
+}
+
+impl Handler<MarketDataUpdateMessage> for WarmupScheduler {
+    type Result = ();
+
+    fn handle(&mut self, _: MarketDataUpdateMessage, _: &mut Self::Context) -> Self::Result {}
+}
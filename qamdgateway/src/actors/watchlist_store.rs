@@ -0,0 +1,241 @@
+use actix::prelude::*;
+use chrono::{DateTime, Utc};
+use hashbrown::HashMap;
+use tracing::{error, info};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::actors::messages::*;
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::is_pattern;
+
+/// 自选列表名称的最大长度
+const MAX_NAME_LEN: usize = 64;
+/// 单个自选列表最多包含的合约数
+const MAX_WATCHLIST_INSTRUMENTS: usize = 2000;
+
+/// 自选列表（命名的合约集合，可包含合约组如`ALL.SHFE`）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Watchlist {
+    /// 名称
+    pub name: String,
+    /// 合约列表
+    pub instruments: Vec<String>,
+    /// 最后修改时间
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 检查自选列表名称和合约
+fn validate(name: &str, instruments: &[String]) -> GatewayResult<()> {
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(GatewayError::InvalidWatchlist(format!(
+            "{:?}: name must be 1-{} letters, digits, '_' or '-'",
+            name, MAX_NAME_LEN
+        )));
+    }
+    if instruments.is_empty() {
+        return Err(GatewayError::InvalidWatchlist(format!("{}: no instruments", name)));
+    }
+    if instruments.len() > MAX_WATCHLIST_INSTRUMENTS {
+        return Err(GatewayError::InvalidWatchlist(format!(
+            "{}: at most {} instruments",
+            name, MAX_WATCHLIST_INSTRUMENTS
+        )));
+    }
+    if let Some(pattern) = instruments.iter().find(|instrument| is_pattern(instrument)) {
+        return Err(GatewayError::InvalidWatchlist(format!(
+            "{}: wildcard pattern {} is not allowed",
+            name, pattern
+        )));
+    }
+    Ok(())
+}
+
+/// 自选列表存储Actor
+///
+/// 保存客户端共享的自选列表（配置了文件时每次修改后写入文件），
+/// 并在列表修改或删除时通知订阅了该列表的会话
+pub struct WatchlistStore {
+    // 持久化文件
+    path: Option<PathBuf>,
+    // 名称 -> 自选列表
+    watchlists: HashMap<String, Watchlist>,
+    // 名称 -> 订阅该列表的会话（客户端ID -> 接收者）
+    subscribers: HashMap<String, HashMap<String, Recipient<WatchlistChanged>>>,
+}
+
+impl Actor for WatchlistStore {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        info!("Watchlist store started with {} watchlists", self.watchlists.len());
+    }
+}
+
+impl Default for WatchlistStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WatchlistStore {
+    /// 创建只保存在内存中的存储
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            watchlists: HashMap::new(),
+            subscribers: HashMap::new(),
+        }
+    }
+
+    /// 从文件加载自选列表，文件不存在时创建空存储，之后的修改写入该文件
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let watchlists = if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let watchlists: Vec<Watchlist> = serde_json::from_str(&content)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            watchlists
+                .into_iter()
+                .map(|watchlist| (watchlist.name.clone(), watchlist))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            watchlists,
+            subscribers: HashMap::new(),
+        })
+    }
+
+    /// 按名称排序的全部自选列表
+    fn sorted(&self) -> Vec<Watchlist> {
+        let mut watchlists: Vec<Watchlist> = self.watchlists.values().cloned().collect();
+        watchlists.sort_by(|a, b| a.name.cmp(&b.name));
+        watchlists
+    }
+
+    /// 写入文件（先写临时文件再重命名，避免写到一半的文件）
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(&self.sorted())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            .and_then(|content| {
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, content)?;
+                std::fs::rename(&tmp_path, path)
+            });
+        if let Err(e) = result {
+            error!("Failed to save watchlists to {}: {}", path.display(), e);
+        }
+    }
+
+    /// 通知订阅了该列表的会话（instruments为None表示列表已删除）
+    fn notify(&mut self, name: &str, instruments: Option<Vec<String>>) {
+        let Some(subscribers) = self.subscribers.get_mut(name) else {
+            return;
+        };
+        subscribers.retain(|_, addr| addr.connected());
+        for addr in subscribers.values() {
+            addr.do_send(WatchlistChanged {
+                name: name.to_string(),
+                instruments: instruments.clone(),
+            });
+        }
+        if instruments.is_none() || subscribers.is_empty() {
+            self.subscribers.remove(name);
+        }
+    }
+}
+
+impl Handler<ListWatchlists> for WatchlistStore {
+    type Result = MessageResult<ListWatchlists>;
+
+    fn handle(&mut self, _: ListWatchlists, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.sorted())
+    }
+}
+
+impl Handler<GetWatchlist> for WatchlistStore {
+    type Result = MessageResult<GetWatchlist>;
+
+    fn handle(&mut self, msg: GetWatchlist, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.watchlists.get(&msg.name).cloned())
+    }
+}
+
+impl Handler<SaveWatchlist> for WatchlistStore {
+    type Result = GatewayResult<Watchlist>;
+
+    fn handle(&mut self, msg: SaveWatchlist, _: &mut Self::Context) -> Self::Result {
+        let mut instruments = Vec::with_capacity(msg.instruments.len());
+        for instrument in msg.instruments {
+            let instrument = instrument.trim().to_string();
+            if !instrument.is_empty() && !instruments.contains(&instrument) {
+                instruments.push(instrument);
+            }
+        }
+        validate(&msg.name, &instruments)?;
+
+        let watchlist = Watchlist {
+            name: msg.name.clone(),
+            instruments,
+            updated_at: Utc::now(),
+        };
+        info!("Watchlist {} saved with {} instruments", watchlist.name, watchlist.instruments.len());
+        self.watchlists.insert(msg.name.clone(), watchlist.clone());
+        self.save();
+        self.notify(&msg.name, Some(watchlist.instruments.clone()));
+        Ok(watchlist)
+    }
+}
+
+impl Handler<DeleteWatchlist> for WatchlistStore {
+    type Result = GatewayResult<()>;
+
+    fn handle(&mut self, msg: DeleteWatchlist, _: &mut Self::Context) -> Self::Result {
+        if self.watchlists.remove(&msg.name).is_none() {
+            return Err(GatewayError::UnknownWatchlist(msg.name));
+        }
+        info!("Watchlist {} deleted", msg.name);
+        self.save();
+        self.notify(&msg.name, None);
+        Ok(())
+    }
+}
+
+impl Handler<SubscribeWatchlist> for WatchlistStore {
+    type Result = GatewayResult<Watchlist>;
+
+    fn handle(&mut self, msg: SubscribeWatchlist, _: &mut Self::Context) -> Self::Result {
+        let watchlist = self
+            .watchlists
+            .get(&msg.name)
+            .cloned()
+            .ok_or_else(|| GatewayError::UnknownWatchlist(msg.name.clone()))?;
+        self.subscribers
+            .entry(msg.name)
+            .or_default()
+            .insert(msg.client_id, msg.addr);
+        Ok(watchlist)
+    }
+}
+
+impl Handler<UnsubscribeWatchlist> for WatchlistStore {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnsubscribeWatchlist, _: &mut Self::Context) -> Self::Result {
+        self.subscribers.retain(|name, subscribers| {
+            if msg.name.as_ref().is_none_or(|wanted| wanted == name) {
+                subscribers.remove(&msg.client_id);
+            }
+            !subscribers.is_empty()
+        });
+    }
+}
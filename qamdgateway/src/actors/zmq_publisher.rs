@@ -0,0 +1,109 @@
+use actix::prelude::*;
+use tracing::{error, info, warn};
+
+use crate::actors::messages::*;
+use crate::config::{ZmqConfig, ZmqFormat};
+
+/// ZeroMQ发布Actor
+///
+/// 绑定PUB套接字，将分发器接受的每条行情以`[合约代码, 行情]`两帧消息发布，
+/// 订阅方按合约代码前缀过滤。开启`ticks`时逐笔成交以`[tick.合约代码, 成交]`发布。ZeroMQ套接字不能跨线程共享，因此运行在SyncArbiter线程中，
+/// 并在该线程内创建套接字。
+pub struct ZmqPublisherActor {
+    config: ZmqConfig,
+    // 需要与套接字一起保留
+    _context: zmq::Context,
+    socket: Option<zmq::Socket>,
+}
+
+impl Actor for ZmqPublisherActor {
+    type Context = SyncContext<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        if self.socket.is_some() {
+            info!("ZeroMQ publisher bound to {}", self.config.bind);
+        }
+    }
+}
+
+impl ZmqPublisherActor {
+    /// 创建ZeroMQ发布Actor并绑定套接字，绑定失败时丢弃所有行情
+    pub fn new(config: ZmqConfig) -> Self {
+        let context = zmq::Context::new();
+        let socket = match Self::bind(&context, &config) {
+            Ok(socket) => Some(socket),
+            Err(e) => {
+                error!("Failed to bind ZeroMQ publisher to {}: {}", config.bind, e);
+                None
+            }
+        };
+        Self {
+            config,
+            _context: context,
+            socket,
+        }
+    }
+
+    fn bind(context: &zmq::Context, config: &ZmqConfig) -> zmq::Result<zmq::Socket> {
+        let socket = context.socket(zmq::PUB)?;
+        socket.set_sndhwm(config.send_hwm)?;
+        socket.bind(&config.bind)?;
+        Ok(socket)
+    }
+
+    /// 按配置的格式编码行情
+    fn encode<T: serde::Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self.config.format {
+            ZmqFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            ZmqFormat::Msgpack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl Handler<MarketDataUpdate> for ZmqPublisherActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        let snapshot = msg.0;
+        let payload = match self.encode(snapshot.as_ref()) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to encode {} for ZeroMQ: {}", snapshot.instrument_id, e);
+                return;
+            }
+        };
+
+        // PUB套接字达到高水位时丢弃消息而不是阻塞
+        let frames = [snapshot.instrument_id.as_bytes(), payload.as_slice()];
+        if let Err(e) = socket.send_multipart(frames, zmq::DONTWAIT) {
+            warn!("Failed to publish {} to ZeroMQ: {}", snapshot.instrument_id, e);
+        }
+    }
+}
+
+impl Handler<TickUpdate> for ZmqPublisherActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: TickUpdate, _: &mut Self::Context) -> Self::Result {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        let tick = msg.0;
+        let payload = match self.encode(&tick) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to encode tick {} for ZeroMQ: {}", tick.instrument_id, e);
+                return;
+            }
+        };
+
+        let topic = format!("tick.{}", tick.instrument_id);
+        let frames = [topic.as_bytes(), payload.as_slice()];
+        if let Err(e) = socket.send_multipart(frames, zmq::DONTWAIT) {
+            warn!("Failed to publish tick {} to ZeroMQ: {}", tick.instrument_id, e);
+        }
+    }
+}
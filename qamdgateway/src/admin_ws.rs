@@ -0,0 +1,333 @@
+//! 管理WebSocket通道
+//!
+//! 需要`admin.token`认证，定期推送各上游连接的状态、客户端数量和行情速率最高的合约，
+//! 实时转发断线重连后的重新订阅进度，并接受强制重连上游、断开客户端等管理命令。
+
+use actix::{Actor, ActorContext, ActorFutureExt, AsyncContext, ContextFutureSpawner, StreamHandler, WrapFuture};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use tracing::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::acl::request_token;
+use crate::actors::md_connector::MarketDataConnector;
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::md_stats::{InstrumentStats, MarketDataStatsActor};
+use crate::actors::messages::*;
+use crate::api::AppState;
+use crate::config::{AdminConfig, HeartbeatConfig};
+use crate::error::GatewayError;
+use crate::supervision::{self, CrashReport};
+
+// 状态推送的最短间隔
+const MIN_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 管理客户端命令
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AdminCommand {
+    /// 断开客户端
+    #[serde(rename_all = "snake_case")]
+    DropClient {
+        aid: String,
+        client_id: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// 强制重连上游
+    #[serde(rename_all = "snake_case")]
+    ReconnectBroker {
+        aid: String,
+        broker_id: String,
+    },
+    /// 无参数命令（立即推送状态）
+    #[serde(rename_all = "snake_case")]
+    Command {
+        aid: String,
+    },
+}
+
+/// 管理通道推送的消息
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum AdminMessage {
+    /// 网关状态
+    Status {
+        aid: String,
+        uptime_secs: u64,
+        brokers: Vec<BrokerState>,
+        clients: ClientCounts,
+        /// 按最近1分钟行情速率从高到低排列
+        top_instruments: Vec<InstrumentStats>,
+        /// 各组件被捕获的panic
+        crashes: CrashReport,
+    },
+    /// 重新订阅进度
+    Resubscribe {
+        aid: String,
+        data: ResubscribeProgress,
+    },
+    /// 命令执行结果
+    CommandResponse {
+        aid: String,
+        ok: bool,
+        message: String,
+    },
+}
+
+/// 管理WebSocket会话
+pub struct AdminSession {
+    heartbeat: Instant,
+    /// 心跳间隔和超时（按管理通道路径取`websocket.heartbeat`）
+    heartbeat_config: HeartbeatConfig,
+    config: AdminConfig,
+    start_time: Instant,
+    md_connector: actix::Addr<MarketDataConnector>,
+    md_distributor: actix::Addr<MarketDataDistributor>,
+    md_stats: actix::Addr<MarketDataStatsActor>,
+    resubscribe_events: broadcast::Sender<ResubscribeProgress>,
+}
+
+impl Actor for AdminSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Admin session started");
+
+        let interval = Duration::from_secs(self.heartbeat_config.interval_secs.max(1));
+        let timeout = Duration::from_secs(self.heartbeat_config.timeout_secs);
+        ctx.run_interval(interval, move |act, ctx| {
+            if act.heartbeat.elapsed() > timeout {
+                info!("Admin session heartbeat failed, disconnecting");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+
+        let interval = Duration::from_secs(self.config.status_interval_secs).max(MIN_STATUS_INTERVAL);
+        ctx.run_interval(interval, |act, ctx| act.push_status(ctx));
+        self.push_status(ctx);
+
+        // 落后太多的管理客户端跳过错过的进度事件
+        let events = futures::stream::unfold(self.resubscribe_events.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(progress) => return Some((progress, rx)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Admin session lagged, skipped {} resubscribe events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        ctx.add_stream(events);
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        info!("Admin session stopped");
+    }
+}
+
+impl AdminSession {
+    /// 收集各Actor的状态并推送
+    fn push_status(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let brokers = self.md_connector.send(GetBrokerStates);
+        let clients = self.md_distributor.send(GetClientCounts);
+        let stats = self.md_stats.send(GetMarketDataStats { instrument: None });
+        let top = self.config.top_instruments;
+
+        async move { futures::join!(brokers, clients, stats) }
+            .into_actor(self)
+            .map(move |(brokers, clients, stats), act, ctx| {
+                let mut instruments = stats.unwrap_or_default();
+                instruments.sort_by(|a, b| b.tick_rate_1m.total_cmp(&a.tick_rate_1m));
+                instruments.truncate(top);
+                act.send(
+                    ctx,
+                    &AdminMessage::Status {
+                        aid: "rtn_status".to_string(),
+                        uptime_secs: act.start_time.elapsed().as_secs(),
+                        brokers: brokers.unwrap_or_default(),
+                        clients: clients.unwrap_or_default(),
+                        top_instruments: instruments,
+                        crashes: supervision::metrics().report(),
+                    },
+                );
+            })
+            .spawn(ctx);
+    }
+
+    /// 执行管理命令，结果以rsp_<命令>返回
+    fn handle_command(&mut self, ctx: &mut ws::WebsocketContext<Self>, command: AdminCommand) {
+        match command {
+            AdminCommand::DropClient { aid, client_id, reason } if aid == "drop_client" => {
+                let reason = reason.unwrap_or_else(|| "disconnected by admin".to_string());
+                info!("Admin command: drop client {} ({})", client_id, reason);
+                self.md_distributor
+                    .send(DisconnectClient {
+                        client_id: client_id.clone(),
+                        reason,
+                    })
+                    .into_actor(self)
+                    .map(move |result, act, ctx| {
+                        let (ok, message) = match result {
+                            Ok(true) => (true, format!("Client {} disconnected", client_id)),
+                            Ok(false) => (false, format!("Client {} not found", client_id)),
+                            Err(e) => (false, format!("Failed to reach distributor: {}", e)),
+                        };
+                        act.respond(ctx, "drop_client", ok, message);
+                    })
+                    .spawn(ctx);
+            }
+            AdminCommand::ReconnectBroker { aid, broker_id } if aid == "reconnect_broker" => {
+                info!("Admin command: reconnect broker {}", broker_id);
+                self.md_connector
+                    .send(ReconnectBroker {
+                        broker_id: broker_id.clone(),
+                    })
+                    .into_actor(self)
+                    .map(move |result, act, ctx| {
+                        let (ok, message) = match result {
+                            Ok(Ok(())) => (true, format!("Broker {} reconnecting", broker_id)),
+                            Ok(Err(e)) => (false, e),
+                            Err(e) => (false, format!("Failed to reach connector: {}", e)),
+                        };
+                        act.respond(ctx, "reconnect_broker", ok, message);
+                    })
+                    .spawn(ctx);
+            }
+            AdminCommand::Command { aid } if aid == "get_status" => self.push_status(ctx),
+            command => {
+                warn!("Unknown admin command: {:?}", command);
+                self.send_error(ctx, &GatewayError::UnknownMessage);
+            }
+        }
+    }
+
+    fn respond(&self, ctx: &mut ws::WebsocketContext<Self>, command: &str, ok: bool, message: String) {
+        let msg = AdminMessage::CommandResponse {
+            aid: format!("rsp_{}", command),
+            ok,
+            message,
+        };
+        self.send(ctx, &msg);
+    }
+
+    fn send<T: Serialize>(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &T) {
+        match serde_json::to_string(msg) {
+            Ok(json) => ctx.text(json),
+            Err(e) => error!("Failed to encode admin message: {}", e),
+        }
+    }
+
+    fn send_error(&self, ctx: &mut ws::WebsocketContext<Self>, error: &GatewayError) {
+        let msg = json!({
+            "aid": "rtn_error",
+            "code": error.code(),
+            "category": error.category(),
+            "message": error.to_string(),
+        });
+        self.send(ctx, &msg);
+    }
+}
+
+/// 转发重新订阅进度
+impl StreamHandler<ResubscribeProgress> for AdminSession {
+    fn handle(&mut self, progress: ResubscribeProgress, ctx: &mut Self::Context) {
+        let msg = AdminMessage::Resubscribe {
+            aid: "rtn_resubscribe".to_string(),
+            data: progress,
+        };
+        self.send(ctx, &msg);
+    }
+
+    // 进度通道关闭（网关退出）时不关闭管理会话
+    fn finished(&mut self, _: &mut Self::Context) {}
+}
+
+/// 处理管理客户端消息
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for AdminSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.heartbeat = Instant::now();
+            }
+            Ok(ws::Message::Text(text)) => match serde_json::from_str::<AdminCommand>(&text) {
+                Ok(command) => self.handle_command(ctx, command),
+                Err(e) => {
+                    warn!("Invalid admin message: {}", e);
+                    self.send_error(ctx, &GatewayError::InvalidMessage(e.to_string()));
+                }
+            },
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("Admin WebSocket protocol error: {}", e);
+                ctx.stop();
+            }
+        }
+    }
+}
+
+/// 按固定时间比较令牌，避免通过响应时间猜测令牌
+pub(crate) fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// 创建管理WebSocket处理器
+#[allow(clippy::too_many_arguments)]
+pub async fn admin_ws_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    config: web::Data<AdminConfig>,
+    heartbeat: web::Data<RwLock<HeartbeatConfig>>,
+    app_state: web::Data<AppState>,
+    md_distributor: web::Data<actix::Addr<MarketDataDistributor>>,
+    md_stats: web::Data<actix::Addr<MarketDataStatsActor>>,
+    resubscribe_events: web::Data<broadcast::Sender<ResubscribeProgress>>,
+) -> Result<HttpResponse, Error> {
+    let authorized = match (config.token(), request_token(&req)) {
+        (Some(expected), Some(given)) => token_matches(&given, expected),
+        _ => false,
+    };
+    if !authorized {
+        warn!(
+            "Rejected admin connection from {}",
+            req.connection_info().realip_remote_addr().unwrap_or("unknown")
+        );
+        let error = GatewayError::AuthError("invalid admin token".to_string());
+        return Ok(HttpResponse::Unauthorized().json(json!({
+            "code": error.code(),
+            "error": error.to_string(),
+        })));
+    }
+
+    let session = AdminSession {
+        heartbeat: Instant::now(),
+        heartbeat_config: heartbeat.read().unwrap_or_else(|e| e.into_inner()).for_route(req.path()),
+        config: config.get_ref().clone(),
+        start_time: app_state.start_time,
+        md_connector: app_state.md_connector.clone(),
+        md_distributor: md_distributor.get_ref().clone(),
+        md_stats: md_stats.get_ref().clone(),
+        resubscribe_events: resubscribe_events.get_ref().clone(),
+    };
+    ws::start(session, &req, stream)
+}
@@ -0,0 +1,147 @@
+//! 行情预警规则
+//!
+//! 客户端通过`set_alert`登记预警条件，每条行情到达时与该合约的上一条行情比较：
+//! - `price_above` / `price_below`：最新价向上/向下穿越阈值
+//! - `volume_delta_above`：两条行情之间的成交量增量超过阈值
+//! - `new_high` / `new_low`：刷新当日最高/最低价
+
+use qamd_rs::MDSnapshot;
+use serde::{Deserialize, Serialize};
+
+use crate::instruments::matches_pattern;
+
+/// 预警条件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// 最新价 >= value
+    PriceAbove { value: f64 },
+    /// 最新价 <= value
+    PriceBelow { value: f64 },
+    /// 相邻两条行情的成交量增量 > value
+    VolumeDeltaAbove { value: i64 },
+    /// 刷新当日最高价
+    NewHigh,
+    /// 刷新当日最低价
+    NewLow,
+}
+
+impl AlertCondition {
+    /// 判断本条行情是否触发条件，触发时返回观测值
+    ///
+    /// 价格条件只在穿越阈值时触发（上一条行情未满足条件），
+    /// 避免价格停留在阈值之外时每条行情都触发
+    pub fn evaluate(&self, previous: Option<&MDSnapshot>, current: &MDSnapshot) -> Option<f64> {
+        match *self {
+            AlertCondition::PriceAbove { value } => (current.last_price >= value
+                && previous.is_none_or(|previous| previous.last_price < value))
+            .then_some(current.last_price),
+            AlertCondition::PriceBelow { value } => (current.last_price <= value
+                && previous.is_none_or(|previous| previous.last_price > value))
+            .then_some(current.last_price),
+            AlertCondition::VolumeDeltaAbove { value } => {
+                let delta = current.volume - previous?.volume;
+                (delta > value).then_some(delta as f64)
+            }
+            AlertCondition::NewHigh => {
+                (current.highest > previous?.highest).then_some(current.highest)
+            }
+            AlertCondition::NewLow => {
+                let previous = previous?;
+                // 最低价为0表示当日尚未成交
+                (previous.lowest > 0.0 && current.lowest < previous.lowest).then_some(current.lowest)
+            }
+        }
+    }
+}
+
+/// 预警规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    /// 客户端指定的预警ID，同一客户端内唯一
+    pub alert_id: String,
+    /// 合约代码，不带交易所前缀时匹配任意交易所，支持通配符
+    pub instrument_id: String,
+    /// 预警条件
+    pub condition: AlertCondition,
+    /// 触发后是否保留（默认触发一次后删除）
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+impl AlertRule {
+    /// 规则是否适用于该合约
+    pub fn applies_to(&self, instrument_id: &str) -> bool {
+        matches_pattern(&self.instrument_id, instrument_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn snapshot(last_price: f64, volume: i64, highest: f64, lowest: f64) -> MDSnapshot {
+        MDSnapshot::builder("SHFE.rb2410", Utc::now())
+            .last_price(last_price)
+            .volume(volume)
+            .highest(highest)
+            .lowest(lowest)
+            .build()
+    }
+
+    #[test]
+    fn test_price_conditions_fire_on_crossing() {
+        let above = AlertCondition::PriceAbove { value: 3500.0 };
+        let below_threshold = snapshot(3490.0, 0, 0.0, 0.0);
+        let at_threshold = snapshot(3500.0, 0, 0.0, 0.0);
+        let beyond = snapshot(3510.0, 0, 0.0, 0.0);
+        assert_eq!(above.evaluate(None, &at_threshold), Some(3500.0));
+        assert_eq!(above.evaluate(Some(&below_threshold), &at_threshold), Some(3500.0));
+        // 已在阈值之上时不再触发
+        assert_eq!(above.evaluate(Some(&at_threshold), &beyond), None);
+        assert_eq!(above.evaluate(Some(&beyond), &below_threshold), None);
+
+        let below = AlertCondition::PriceBelow { value: 3500.0 };
+        assert_eq!(below.evaluate(Some(&beyond), &at_threshold), Some(3500.0));
+        assert_eq!(below.evaluate(Some(&at_threshold), &below_threshold), None);
+    }
+
+    #[test]
+    fn test_volume_and_range_conditions_need_previous() {
+        let previous = snapshot(3500.0, 100, 3520.0, 3480.0);
+        let volume = AlertCondition::VolumeDeltaAbove { value: 50 };
+        assert_eq!(volume.evaluate(None, &snapshot(3500.0, 200, 3520.0, 3480.0)), None);
+        assert_eq!(volume.evaluate(Some(&previous), &snapshot(3500.0, 150, 3520.0, 3480.0)), None);
+        assert_eq!(volume.evaluate(Some(&previous), &snapshot(3500.0, 151, 3520.0, 3480.0)), Some(51.0));
+
+        let current = snapshot(3530.0, 100, 3530.0, 3470.0);
+        assert_eq!(AlertCondition::NewHigh.evaluate(None, &current), None);
+        assert_eq!(AlertCondition::NewHigh.evaluate(Some(&previous), &current), Some(3530.0));
+        assert_eq!(AlertCondition::NewLow.evaluate(Some(&previous), &current), Some(3470.0));
+        // 上一条行情最低价为0（尚未成交）时不算刷新最低价
+        let untraded = snapshot(0.0, 0, 0.0, 0.0);
+        assert_eq!(AlertCondition::NewLow.evaluate(Some(&untraded), &current), None);
+    }
+
+    #[test]
+    fn test_rule_deserializes_and_matches_instruments() {
+        let rule: AlertRule = serde_json::from_str(
+            r#"{"alert_id": "a1", "instrument_id": "rb*", "condition": {"type": "price_above", "value": 3500}}"#,
+        )
+        .unwrap();
+        assert_eq!(rule.condition, AlertCondition::PriceAbove { value: 3500.0 });
+        assert!(!rule.repeat);
+        assert!(rule.applies_to("SHFE.rb2410"));
+        assert!(rule.applies_to("rb2501"));
+        assert!(!rule.applies_to("SHFE.ag2412"));
+
+        let rule: AlertRule = serde_json::from_str(
+            r#"{"alert_id": "a2", "instrument_id": "SHFE.rb2410", "condition": {"type": "new_high"}, "repeat": true}"#,
+        )
+        .unwrap();
+        assert_eq!(rule.condition, AlertCondition::NewHigh);
+        assert!(rule.repeat);
+        assert!(!rule.applies_to("INE.rb2410"));
+    }
+}
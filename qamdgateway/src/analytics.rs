@@ -0,0 +1,240 @@
+//! 行情衍生指标
+//!
+//! 由同一合约的相邻两条行情计算，客户端通过`subscribe_indicator`订阅：
+//! - `microprice`：按买一/卖一挂单量加权的中间价
+//! - `ofi`：订单流不平衡（Cont, Kukanov & Stoikov），买一侧增量减卖一侧增量，
+//!   `ofi_1m`为最近1分钟的累计值
+//! - `vwap_1m` / `vwap_5m`：最近1/5分钟以最新价按成交量增量加权的均价
+//! - `volatility_1m` / `volatility_5m`：最近1/5分钟逐笔对数收益率的已实现波动率（未年化）
+//!
+//! 同一模块由逐笔行情合成1分钟K线，客户端通过`subscribe_kline`订阅，每笔行情推送当前K线
+
+use chrono::{DateTime, Duration, Utc};
+use qamd_rs::MDSnapshot;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// 短窗口（1分钟）
+const SHORT_WINDOW: Duration = Duration::minutes(1);
+/// 长窗口（5分钟），样本保留的最长时间
+const LONG_WINDOW: Duration = Duration::minutes(5);
+
+/// 一条行情的指标
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Indicators {
+    pub instrument_id: String,
+    pub datetime: DateTime<Utc>,
+    pub microprice: Option<f64>,
+    pub ofi: f64,
+    pub ofi_1m: f64,
+    pub vwap_1m: Option<f64>,
+    pub vwap_5m: Option<f64>,
+    pub volatility_1m: Option<f64>,
+    pub volatility_5m: Option<f64>,
+}
+
+/// 相邻两条行情之间的增量
+struct Sample {
+    datetime: DateTime<Utc>,
+    ofi: f64,
+    /// 成交量增量
+    volume: f64,
+    /// 最新价 × 成交量增量
+    turnover: f64,
+    /// 最新价的对数收益率
+    log_return: Option<f64>,
+}
+
+/// 单个合约的指标计算状态
+#[derive(Default)]
+pub struct InstrumentAnalytics {
+    previous: Option<MDSnapshot>,
+    // 最近5分钟的样本，按时间先后排列
+    samples: VecDeque<Sample>,
+}
+
+impl InstrumentAnalytics {
+    /// 计入一条行情并返回最新指标
+    pub fn update(&mut self, snapshot: &MDSnapshot) -> Indicators {
+        // 成交量回落说明进入了新的交易日，之前的样本不再连续
+        if self
+            .previous
+            .as_ref()
+            .is_some_and(|previous| snapshot.volume < previous.volume || snapshot.datetime < previous.datetime)
+        {
+            self.previous = None;
+            self.samples.clear();
+        }
+
+        let mut ofi = 0.0;
+        if let Some(previous) = &self.previous {
+            ofi = order_flow_imbalance(previous, snapshot);
+            let volume = (snapshot.volume - previous.volume) as f64;
+            let log_return = (previous.last_price > 0.0 && snapshot.last_price > 0.0)
+                .then(|| (snapshot.last_price / previous.last_price).ln());
+            self.samples.push_back(Sample {
+                datetime: snapshot.datetime,
+                ofi,
+                volume,
+                turnover: snapshot.last_price * volume,
+                log_return,
+            });
+        }
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| snapshot.datetime - sample.datetime > LONG_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        self.previous = Some(snapshot.clone());
+
+        let short_start = snapshot.datetime - SHORT_WINDOW;
+        let short = || self.samples.iter().filter(move |sample| sample.datetime >= short_start);
+        Indicators {
+            instrument_id: snapshot.instrument_id.clone(),
+            datetime: snapshot.datetime,
+            microprice: microprice(snapshot),
+            ofi,
+            ofi_1m: short().fold(0.0, |sum, sample| sum + sample.ofi),
+            vwap_1m: vwap(short()),
+            vwap_5m: vwap(self.samples.iter()),
+            volatility_1m: realized_volatility(short()),
+            volatility_5m: realized_volatility(self.samples.iter()),
+        }
+    }
+}
+
+/// K线周期（1分钟）
+const KLINE_PERIOD_SECS: i64 = 60;
+
+/// 由逐笔行情合成的K线
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Kline {
+    pub instrument_id: String,
+    /// K线开始时间
+    pub datetime: DateTime<Utc>,
+    /// K线周期（秒）
+    pub duration: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// K线内的成交量
+    pub volume: i64,
+    /// K线内的成交额
+    pub amount: f64,
+    pub open_interest: Option<f64>,
+}
+
+/// 单个合约的K线合成状态
+#[derive(Default)]
+pub struct KlineBuilder {
+    // 当前K线
+    bar: Option<Kline>,
+    // 上一笔行情的累计成交量和成交额
+    previous: Option<(i64, f64)>,
+}
+
+impl KlineBuilder {
+    /// 计入一条行情并返回当前K线，没有成交价或早于当前K线的行情返回None
+    pub fn update(&mut self, snapshot: &MDSnapshot) -> Option<Kline> {
+        if snapshot.last_price <= 0.0 {
+            return None;
+        }
+        let timestamp = snapshot.datetime.timestamp();
+        let start = DateTime::from_timestamp(timestamp - timestamp.rem_euclid(KLINE_PERIOD_SECS), 0)?;
+        if self.bar.as_ref().is_some_and(|bar| start < bar.datetime) {
+            return None;
+        }
+
+        // 累计成交量回落说明进入了新的交易日，第一笔行情不计增量
+        let (volume, amount) = match self.previous {
+            Some((volume, amount)) if snapshot.volume >= volume => {
+                (snapshot.volume - volume, (snapshot.amount - amount).max(0.0))
+            }
+            _ => (0, 0.0),
+        };
+        self.previous = Some((snapshot.volume, snapshot.amount));
+
+        let open_interest = snapshot.open_interest.as_f64_opt();
+        match &mut self.bar {
+            Some(bar) if bar.datetime == start => {
+                bar.high = bar.high.max(snapshot.last_price);
+                bar.low = bar.low.min(snapshot.last_price);
+                bar.close = snapshot.last_price;
+                bar.volume += volume;
+                bar.amount += amount;
+                bar.open_interest = open_interest.or(bar.open_interest);
+            }
+            bar => {
+                *bar = Some(Kline {
+                    instrument_id: snapshot.instrument_id.clone(),
+                    datetime: start,
+                    duration: KLINE_PERIOD_SECS,
+                    open: snapshot.last_price,
+                    high: snapshot.last_price,
+                    low: snapshot.last_price,
+                    close: snapshot.last_price,
+                    volume,
+                    amount,
+                    open_interest,
+                });
+            }
+        }
+        self.bar.clone()
+    }
+}
+
+/// 按对手方挂单量加权的中间价，买一或卖一为空时返回None
+pub fn microprice(snapshot: &MDSnapshot) -> Option<f64> {
+    let (bid, ask) = (snapshot.bid_price1, snapshot.ask_price1);
+    let (bid_volume, ask_volume) = (snapshot.bid_volume1 as f64, snapshot.ask_volume1 as f64);
+    if !(bid > 0.0 && ask > 0.0 && bid_volume + ask_volume > 0.0) {
+        return None;
+    }
+    Some((bid * ask_volume + ask * bid_volume) / (bid_volume + ask_volume))
+}
+
+/// 相邻两条行情之间买一/卖一的订单流不平衡，盘口不完整时为0
+///
+/// 买价上移（或不变时挂单增加）计为买方力量，卖价下移（或不变时挂单增加）计为卖方力量
+pub fn order_flow_imbalance(previous: &MDSnapshot, current: &MDSnapshot) -> f64 {
+    let quoted = |s: &MDSnapshot| s.bid_price1 > 0.0 && s.ask_price1 > 0.0;
+    if !quoted(previous) || !quoted(current) {
+        return 0.0;
+    }
+
+    let mut bid_flow = 0.0;
+    if current.bid_price1 >= previous.bid_price1 {
+        bid_flow += current.bid_volume1 as f64;
+    }
+    if current.bid_price1 <= previous.bid_price1 {
+        bid_flow -= previous.bid_volume1 as f64;
+    }
+    let mut ask_flow = 0.0;
+    if current.ask_price1 <= previous.ask_price1 {
+        ask_flow += current.ask_volume1 as f64;
+    }
+    if current.ask_price1 >= previous.ask_price1 {
+        ask_flow -= previous.ask_volume1 as f64;
+    }
+    bid_flow - ask_flow
+}
+
+fn vwap<'a>(samples: impl Iterator<Item = &'a Sample>) -> Option<f64> {
+    let (turnover, volume) = samples.fold((0.0, 0.0), |(turnover, volume), sample| {
+        (turnover + sample.turnover, volume + sample.volume)
+    });
+    (volume > 0.0).then(|| turnover / volume)
+}
+
+fn realized_volatility<'a>(samples: impl Iterator<Item = &'a Sample>) -> Option<f64> {
+    let mut count = 0;
+    let mut sum_squares = 0.0;
+    for log_return in samples.filter_map(|sample| sample.log_return) {
+        count += 1;
+        sum_squares += log_return * log_return;
+    }
+    (count > 0).then(|| sum_squares.sqrt())
+}
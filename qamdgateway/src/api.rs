@@ -1,13 +1,33 @@
 use actix::Addr;
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::time::{Duration, Instant};
-use log::{info, error};
+use tracing::{info, error};
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
+use crate::acl::{request_token, AclStore};
+use crate::actors::config_reloader::ConfigReloader;
+use crate::admin_ws::token_matches;
 use crate::actors::md_connector::MarketDataConnector;
-use crate::actors::messages::{Subscribe, Unsubscribe, GetSubscriptions};
-use crate::error::GatewayResult;
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::data_quality::DataQualityActor;
+use crate::actors::md_stats::MarketDataStatsActor;
+use crate::actors::quota_monitor::QuotaMonitor;
+use crate::actors::replay_actor::ReplayMarketDataActor;
+use crate::actors::watchlist_store::WatchlistStore;
+use crate::actors::messages::{AddBroker, BrokerState, GetBrokerStates, GetClientCounts, GetLoadSharing, GetTradingPhases, GetSessionEnds, GetClockSkew, DisconnectClient, GetClientRtt, GetMarketDataStats, GetOffenders, GetQualityStats, QualityCounts, GetRecordedTicks, GetSubscriptions, GetTickFilterStats, GetTickHistory, GetPriceCheckStats, ListBrokers, ListWatchlists, GetWatchlist, SaveWatchlist, DeleteWatchlist, ReloadConfig, RemoveBroker, ResubscribeProgress, SetReplaySpeed, StartReplay, StopReplay, Subscribe, Unsubscribe};
+use crate::actors::tick_history::TickHistoryActor;
+use crate::actors::trading_phase::TradingPhaseActor;
+use crate::actors::tick_recorder::{TickRecorderActor, TickRow};
+use crate::calendar::{exchange_sessions, parse_date, TradingCalendar};
+use crate::config::{AdminConfig, BrokerConfig};
+use crate::instruments::InstrumentRegistry;
+use crate::latency;
+use crate::supervision;
+use crate::symbols::{normalize_exchange, SymbolTable};
+use crate::error::{GatewayError, GatewayResult};
 use serde_json::{json, Value};
 
 /// Request for subscription management
@@ -26,9 +46,67 @@ pub struct SubscriptionsResponse {
 #[derive(Serialize)]
 pub struct StatusResponse {
     pub status: String,
+    /// Gateway version
+    pub version: &'static str,
     pub uptime: u64,
+    /// Connected WebSocket sessions
     pub connected_clients: usize,
+    /// Disconnected sessions that can still be resumed
+    pub detached_clients: usize,
+    /// Instruments subscribed by at least one client
     pub active_subscriptions: usize,
+    /// Instruments subscribed upstream, per source type
+    pub subscriptions_by_source: BTreeMap<String, usize>,
+    /// Memory used by the process (Linux only)
+    pub memory: Option<MemoryUsage>,
+    /// Upstream connections, including the current front, per-front health
+    /// and the last disconnect
+    pub brokers: Vec<BrokerState>,
+}
+
+/// Memory used by the process
+#[derive(Serialize)]
+pub struct MemoryUsage {
+    /// Resident set size in bytes
+    pub rss_bytes: u64,
+    /// Virtual memory size in bytes
+    pub virtual_bytes: u64,
+}
+
+impl MemoryUsage {
+    /// Read the memory usage of this process from `/proc/self/status`
+    fn current() -> Option<Self> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let field = |name: &str| -> Option<u64> {
+            let line = status.lines().find(|line| line.starts_with(name))?;
+            let kb: u64 = line[name.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+            Some(kb * 1024)
+        };
+        Some(Self {
+            rss_bytes: field("VmRSS:")?,
+            virtual_bytes: field("VmSize:")?,
+        })
+    }
+}
+
+/// Upstream broker connection info (credentials are never returned)
+#[derive(Serialize)]
+pub struct BrokerInfo {
+    pub broker_id: String,
+    pub name: String,
+    pub front_addr: String,
+    pub source_type: Option<String>,
+}
+
+impl From<BrokerConfig> for BrokerInfo {
+    fn from(config: BrokerConfig) -> Self {
+        Self {
+            broker_id: config.broker_id,
+            name: config.name,
+            front_addr: config.front_addr,
+            source_type: config.source_type,
+        }
+    }
 }
 
 /// Error response
@@ -54,7 +132,7 @@ async fn health_check() -> impl Responder {
 /// Get all subscribed instruments
 #[get("/api/subscriptions")]
 async fn get_subscriptions(data: web::Data<AppState>) -> impl Responder {
-    let result = data.md_connector.send(GetSubscriptions { id: Uuid::nil(), callback: None }).await;
+    let result = data.md_connector.send(GetSubscriptions).await;
     
     match result {
         Ok(instruments) => {
@@ -95,7 +173,7 @@ async fn subscribe(
     }
     
     // Get updated subscriptions
-    let result = data.md_connector.send(GetSubscriptions { id: Uuid::nil(), callback: None }).await;
+    let result = data.md_connector.send(GetSubscriptions).await;
     
     match result {
         Ok(instruments) => {
@@ -136,7 +214,7 @@ async fn unsubscribe(
     }
     
     // Get updated subscriptions
-    let result = data.md_connector.send(GetSubscriptions { id: Uuid::nil(), callback: None }).await;
+    let result = data.md_connector.send(GetSubscriptions).await;
     
     match result {
         Ok(instruments) => {
@@ -153,19 +231,994 @@ async fn unsubscribe(
 
 /// Get gateway status
 #[get("/api/status")]
-async fn get_status(data: web::Data<AppState>) -> impl Responder {
-    // TODO: Implement status check via actor messages
-    // For now, just return basic info
+async fn get_status(
+    data: web::Data<AppState>,
+    distributor: web::Data<Addr<MarketDataDistributor>>,
+) -> impl Responder {
+    let (brokers, clients) = futures::join!(
+        data.md_connector.send(GetBrokerStates),
+        distributor.send(GetClientCounts)
+    );
+    let brokers = brokers.unwrap_or_else(|e| {
+        error!("Failed to get broker states: {}", e);
+        Vec::new()
+    });
+    let clients = clients.unwrap_or_else(|e| {
+        error!("Failed to get client counts: {}", e);
+        Default::default()
+    });
+    // "degraded" while only some upstream connections are logged in
+    let logged_in = brokers.iter().filter(|broker| broker.logged_in).count();
+    let status = if logged_in == brokers.len() {
+        "connected"
+    } else if logged_in > 0 {
+        "degraded"
+    } else {
+        "disconnected"
+    };
+
+    let mut subscriptions_by_source = BTreeMap::new();
+    for broker in &brokers {
+        *subscriptions_by_source.entry(format!("{:?}", broker.source)).or_default() += broker.subscribed;
+    }
+
     let response = StatusResponse {
-        status: "connected".to_string(), // Default to connected
+        status: status.to_string(),
+        version: env!("CARGO_PKG_VERSION"),
         uptime: data.start_time.elapsed().as_secs(),
-        connected_clients: 0, // Will be implemented later
-        active_subscriptions: 0, // Will be implemented later
+        connected_clients: clients.sessions,
+        detached_clients: clients.detached,
+        active_subscriptions: clients.instruments,
+        subscriptions_by_source,
+        memory: MemoryUsage::current(),
+        brokers,
     };
     
     HttpResponse::Ok().json(response)
 }
 
+/// Get duplicate/out-of-order tick filter counters
+#[get("/api/stats/tick_filter")]
+async fn get_tick_filter_stats(distributor: web::Data<Addr<MarketDataDistributor>>) -> impl Responder {
+    match distributor.send(GetTickFilterStats).await {
+        Ok(stats) => HttpResponse::Ok().json(json!({
+            "accepted": stats.accepted,
+            "duplicates": stats.duplicates,
+            "out_of_order": stats.out_of_order,
+            "dropped": stats.dropped(),
+        })),
+        Err(e) => {
+            error!("Failed to get tick filter stats: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get tick filter stats: {}", e)
+            }))
+        }
+    }
+}
+
+/// Get the price tick and limit price violation counters
+#[get("/api/stats/price_check")]
+async fn get_price_check_stats(distributor: web::Data<Addr<MarketDataDistributor>>) -> impl Responder {
+    match distributor.send(GetPriceCheckStats).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            error!("Failed to get price check stats: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get price check stats: {}", e)
+            }))
+        }
+    }
+}
+
+/// Get the estimated clock skew of each market data source
+#[get("/api/stats/clock_skew")]
+async fn get_clock_skew(distributor: web::Data<Addr<MarketDataDistributor>>) -> impl Responder {
+    match distributor.send(GetClockSkew).await {
+        Ok(sources) => HttpResponse::Ok().json(json!({
+            "sources": sources,
+        })),
+        Err(e) => {
+            error!("Failed to get clock skew: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get clock skew: {}", e)
+            }))
+        }
+    }
+}
+
+/// Get the per-hop latency histograms of traced market data
+#[get("/api/stats/latency")]
+async fn get_latency() -> impl Responder {
+    HttpResponse::Ok().json(latency::metrics().report())
+}
+
+/// Get the panics recovered per component and the most recent crash events
+#[get("/api/stats/crashes")]
+async fn get_crashes() -> impl Responder {
+    HttpResponse::Ok().json(supervision::metrics().report())
+}
+
+/// Query parameters for `/api/md/stats`
+#[derive(Debug, Deserialize)]
+struct MdStatsQuery {
+    instrument: Option<String>,
+}
+
+/// Get per-instrument tick rate, last update, source and spread
+#[get("/api/md/stats")]
+async fn get_md_stats(
+    stats: web::Data<Addr<MarketDataStatsActor>>,
+    query: web::Query<MdStatsQuery>,
+) -> impl Responder {
+    let instrument = query.into_inner().instrument;
+    match stats.send(GetMarketDataStats { instrument }).await {
+        Ok(instruments) => HttpResponse::Ok().json(json!({
+            "count": instruments.len(),
+            "instruments": instruments,
+        })),
+        Err(e) => {
+            error!("Failed to get market data stats: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get market data stats: {}", e)
+            }))
+        }
+    }
+}
+
+/// Get per-instrument gap, staleness, invalid price and crossed book counters
+#[get("/api/md/quality")]
+async fn get_md_quality(
+    quality: web::Data<Addr<DataQualityActor>>,
+    query: web::Query<MdStatsQuery>,
+) -> impl Responder {
+    let instrument = query.into_inner().instrument;
+    match quality.send(GetQualityStats { instrument }).await {
+        Ok(instruments) => {
+            let mut totals = QualityCounts::default();
+            for instrument in &instruments {
+                totals.gap += instrument.issues.gap;
+                totals.stale += instrument.issues.stale;
+                totals.invalid_price += instrument.issues.invalid_price;
+                totals.crossed_book += instrument.issues.crossed_book;
+            }
+            HttpResponse::Ok().json(json!({
+                "count": instruments.len(),
+                "stale": instruments.iter().filter(|instrument| instrument.stale).count(),
+                "crossed": instruments.iter().filter(|instrument| instrument.crossed).count(),
+                "totals": totals,
+                "instruments": instruments,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to get data quality stats: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get data quality stats: {}", e)
+            }))
+        }
+    }
+}
+
+/// Stream upstream resubscribe progress as Server-Sent Events
+///
+/// One `data:` event per resubscribe batch; events missed by a slow client are skipped.
+#[get("/api/md/resubscribe/events")]
+async fn resubscribe_events(
+    events: web::Data<broadcast::Sender<ResubscribeProgress>>,
+) -> impl Responder {
+    let stream = futures::stream::unfold(events.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(progress) => {
+                    let data = serde_json::to_string(&progress).unwrap_or_default();
+                    let event = web::Bytes::from(format!("data: {}\n\n", data));
+                    return Some((Ok::<_, actix_web::Error>(event), rx));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    info!("Resubscribe event stream lagged, skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
+}
+
+/// Number of ticks serialized per response chunk in `/api/md/export`
+const EXPORT_CHUNK_TICKS: usize = 1000;
+
+/// Query parameters for `/api/md/export`
+#[derive(Debug, Deserialize)]
+struct MdExportQuery {
+    instrument: String,
+    #[serde(default)]
+    format: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// Parse an `HH:MM` or `HH:MM:SS` time of day
+fn parse_time_of_day(value: &str) -> Option<chrono::NaiveTime> {
+    chrono::NaiveTime::parse_from_str(value, "%H:%M:%S")
+        .or_else(|_| chrono::NaiveTime::parse_from_str(value, "%H:%M"))
+        .ok()
+}
+
+/// Serialize a chunk of ticks as CSV rows, with the header on the first chunk
+fn ticks_to_csv(ticks: &[qamd_rs::MDSnapshot], header: bool) -> Result<web::Bytes, actix_web::Error> {
+    let mut writer = csv::WriterBuilder::new().has_headers(header).from_writer(Vec::new());
+    for tick in ticks {
+        writer
+            .serialize(TickRow::from(tick))
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+    }
+    writer
+        .into_inner()
+        .map(web::Bytes::from)
+        .map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Serialize a chunk of ticks as JSON Lines
+fn ticks_to_jsonl(ticks: &[qamd_rs::MDSnapshot]) -> Result<web::Bytes, actix_web::Error> {
+    let mut body = Vec::new();
+    for tick in ticks {
+        serde_json::to_writer(&mut body, tick).map_err(actix_web::error::ErrorInternalServerError)?;
+        body.push(b'\n');
+    }
+    Ok(web::Bytes::from(body))
+}
+
+/// Export recorded intraday ticks as CSV or JSON Lines
+///
+/// `from` and `to` are Beijing times of day; `from` later than `to` selects a
+/// window across midnight (night session). The body is sent in chunks.
+#[get("/api/md/export")]
+async fn export_ticks(
+    recorder: Option<web::Data<Addr<TickRecorderActor>>>,
+    query: web::Query<MdExportQuery>,
+) -> impl Responder {
+    let Some(recorder) = recorder else {
+        return HttpResponse::ServiceUnavailable().json(json!({
+            "error": "Tick recording is disabled"
+        }));
+    };
+    let query = query.into_inner();
+
+    let csv = match query.format.as_deref().unwrap_or("csv") {
+        "csv" => true,
+        "jsonl" | "ndjson" => false,
+        other => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!("Unsupported export format: {}", other)
+            }))
+        }
+    };
+    let mut window = [None, None];
+    for (bound, value) in window.iter_mut().zip([&query.from, &query.to]) {
+        if let Some(value) = value {
+            match parse_time_of_day(value) {
+                Some(time) => *bound = Some(time),
+                None => {
+                    return HttpResponse::BadRequest().json(json!({
+                        "error": format!("Invalid time of day: {}", value)
+                    }))
+                }
+            }
+        }
+    }
+
+    let ticks = match recorder
+        .send(GetRecordedTicks {
+            instrument: query.instrument.clone(),
+            from: window[0],
+            to: window[1],
+        })
+        .await
+    {
+        Ok(ticks) => ticks,
+        Err(e) => {
+            error!("Failed to get recorded ticks: {}", e);
+            return HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get recorded ticks: {}", e)
+            }));
+        }
+    };
+
+    let (content_type, extension) = if csv {
+        ("text/csv; charset=utf-8", "csv")
+    } else {
+        ("application/x-ndjson", "jsonl")
+    };
+    let filename = format!("{}.{}", query.instrument.replace(['*', '?'], "_"), extension);
+
+    let chunk_count = ticks.len().div_ceil(EXPORT_CHUNK_TICKS);
+    let body = futures::stream::iter((0..chunk_count).map(move |index| {
+        let start = index * EXPORT_CHUNK_TICKS;
+        let chunk = &ticks[start..ticks.len().min(start + EXPORT_CHUNK_TICKS)];
+        if csv {
+            ticks_to_csv(chunk, index == 0)
+        } else {
+            ticks_to_jsonl(chunk)
+        }
+    }));
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            actix_web::http::header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .streaming(body)
+}
+
+/// List upstream broker connections
+#[get("/api/brokers")]
+async fn list_brokers(data: web::Data<AppState>) -> impl Responder {
+    match data.md_connector.send(ListBrokers).await {
+        Ok(brokers) => {
+            let brokers: Vec<BrokerInfo> = brokers.into_iter().map(BrokerInfo::from).collect();
+            HttpResponse::Ok().json(brokers)
+        },
+        Err(e) => {
+            error!("Failed to list brokers: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to list brokers: {}", e)
+            }))
+        }
+    }
+}
+
+/// Connect a new upstream broker front at runtime
+#[post("/api/brokers")]
+async fn add_broker(
+    data: web::Data<AppState>,
+    req: web::Json<BrokerConfig>,
+) -> impl Responder {
+    let config = req.into_inner();
+    let info = BrokerInfo::from(config.clone());
+    
+    match data.md_connector.send(AddBroker { config }).await {
+        Ok(Ok(())) => {
+            info!("Broker {} added via API", info.broker_id);
+            HttpResponse::Created().json(info)
+        },
+        Ok(Err(e)) => HttpResponse::BadRequest().json(ErrorResponse { error: e }),
+        Err(e) => {
+            error!("Failed to add broker: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to add broker: {}", e)
+            }))
+        }
+    }
+}
+
+/// Show how the subscribed instruments are shared across the market data accounts
+#[get("/api/brokers/load_sharing")]
+async fn get_load_sharing(data: web::Data<AppState>) -> impl Responder {
+    match data.md_connector.send(GetLoadSharing).await {
+        Ok(Some(accounts)) => HttpResponse::Ok().json(json!({
+            "enabled": true,
+            "accounts": accounts,
+        })),
+        Ok(None) => HttpResponse::Ok().json(json!({
+            "enabled": false,
+            "accounts": [],
+        })),
+        Err(e) => {
+            error!("Failed to get load sharing: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get load sharing: {}", e)
+            }))
+        }
+    }
+}
+
+/// Disconnect an upstream broker at runtime
+#[delete("/api/brokers/{broker_id}")]
+async fn remove_broker(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let broker_id = path.into_inner();
+    
+    match data.md_connector.send(RemoveBroker { broker_id: broker_id.clone() }).await {
+        Ok(Ok(())) => {
+            info!("Broker {} removed via API", broker_id);
+            HttpResponse::NoContent().finish()
+        },
+        Ok(Err(e)) => HttpResponse::NotFound().json(ErrorResponse { error: e }),
+        Err(e) => {
+            error!("Failed to remove broker: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to remove broker: {}", e)
+            }))
+        }
+    }
+}
+
+/// Request to change the replay speed
+#[derive(Deserialize)]
+pub struct ReplaySpeedRequest {
+    /// "1x", "10x" or "max"
+    pub speed: String,
+}
+
+fn replay_disabled() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().json(json!({
+        "error": "Replay mode is disabled"
+    }))
+}
+
+/// Resume a paused replay
+#[post("/api/replay/start")]
+async fn start_replay(replay: Option<web::Data<Addr<ReplayMarketDataActor>>>) -> impl Responder {
+    let Some(replay) = replay else {
+        return replay_disabled();
+    };
+    match replay.send(StartReplay).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!("Failed to start replay: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to start replay: {}", e)
+            }))
+        }
+    }
+}
+
+/// Pause the replay
+#[post("/api/replay/stop")]
+async fn stop_replay(replay: Option<web::Data<Addr<ReplayMarketDataActor>>>) -> impl Responder {
+    let Some(replay) = replay else {
+        return replay_disabled();
+    };
+    match replay.send(StopReplay).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!("Failed to stop replay: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to stop replay: {}", e)
+            }))
+        }
+    }
+}
+
+/// Change the replay speed
+#[put("/api/replay/speed")]
+async fn set_replay_speed(
+    replay: Option<web::Data<Addr<ReplayMarketDataActor>>>,
+    req: web::Json<ReplaySpeedRequest>,
+) -> impl Responder {
+    let Some(replay) = replay else {
+        return replay_disabled();
+    };
+    match replay.send(SetReplaySpeed { speed: req.into_inner().speed }).await {
+        Ok(Ok(())) => HttpResponse::NoContent().finish(),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(ErrorResponse { error: e }),
+        Err(e) => {
+            error!("Failed to set replay speed: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to set replay speed: {}", e)
+            }))
+        }
+    }
+}
+
+/// Query for instrument reference data
+#[derive(Deserialize)]
+pub struct InstrumentQuery {
+    /// Instrument group, e.g. "ALL.SHFE" or "FUTURE.*"
+    pub group: Option<String>,
+}
+
+/// List instrument reference data, optionally restricted to a group
+#[get("/api/instruments")]
+async fn list_instruments(
+    registry: web::Data<InstrumentRegistry>,
+    query: web::Query<InstrumentQuery>,
+) -> impl Responder {
+    match &query.group {
+        Some(group) if !InstrumentRegistry::is_group(group) => {
+            HttpResponse::BadRequest().json(ErrorResponse {
+                error: format!("Invalid instrument group: {}", group),
+            })
+        }
+        Some(group) => {
+            let instruments: Vec<_> = registry
+                .expand_group(group)
+                .iter()
+                .filter_map(|id| registry.get(id))
+                .cloned()
+                .collect();
+            HttpResponse::Ok().json(instruments)
+        }
+        None => {
+            let instruments: Vec<_> = registry.iter().cloned().collect();
+            HttpResponse::Ok().json(instruments)
+        }
+    }
+}
+
+/// Query of the symbol search endpoint
+#[derive(Deserialize)]
+struct SymbolQuery {
+    /// Code ("000001", "sz000001", "000001.SZ"), name or pinyin initials
+    #[serde(default)]
+    query: String,
+    /// Exchange ("SSE", "SZSE", "BSE" or "SH", "SZ", "BJ")
+    exchange: Option<String>,
+    #[serde(default = "default_symbol_limit")]
+    limit: usize,
+}
+
+fn default_symbol_limit() -> usize {
+    20
+}
+
+/// Search the equity symbol table by code, name or pinyin initials
+#[get("/api/symbols")]
+async fn search_symbols(symbols: web::Data<SymbolTable>, query: web::Query<SymbolQuery>) -> impl Responder {
+    let exchange = match query.exchange.as_deref().filter(|exchange| !exchange.is_empty()) {
+        Some(exchange) => match normalize_exchange(exchange) {
+            Some(exchange) => Some(exchange),
+            None => {
+                return HttpResponse::BadRequest().json(ErrorResponse {
+                    error: format!("Unknown exchange: {}", exchange),
+                })
+            }
+        },
+        None => None,
+    };
+    HttpResponse::Ok().json(symbols.search(&query.query, exchange, query.limit))
+}
+
+/// Get reference data of a single instrument ("rb2410" or "SHFE.rb2410")
+#[get("/api/instruments/{instrument_id}")]
+async fn get_instrument(
+    registry: web::Data<InstrumentRegistry>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let instrument_id = path.into_inner();
+    match registry.get(&instrument_id) {
+        Some(info) => HttpResponse::Ok().json(info),
+        None => HttpResponse::NotFound().json(ErrorResponse {
+            error: format!("Unknown instrument: {}", instrument_id),
+        }),
+    }
+}
+
+/// Request to create or replace a watchlist
+#[derive(Deserialize)]
+pub struct WatchlistRequest {
+    pub name: String,
+    pub instruments: Vec<String>,
+}
+
+/// List the shared watchlists
+#[get("/api/watchlists")]
+async fn list_watchlists(store: web::Data<Addr<WatchlistStore>>) -> impl Responder {
+    match store.send(ListWatchlists).await {
+        Ok(watchlists) => HttpResponse::Ok().json(json!({
+            "count": watchlists.len(),
+            "watchlists": watchlists,
+        })),
+        Err(e) => {
+            error!("Failed to list watchlists: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to list watchlists: {}", e)
+            }))
+        }
+    }
+}
+
+/// Get a single watchlist
+#[get("/api/watchlists/{name}")]
+async fn get_watchlist(
+    store: web::Data<Addr<WatchlistStore>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let name = path.into_inner();
+    match store.send(GetWatchlist { name: name.clone() }).await {
+        Ok(Some(watchlist)) => HttpResponse::Ok().json(watchlist),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            error: GatewayError::UnknownWatchlist(name).to_string(),
+        }),
+        Err(e) => {
+            error!("Failed to get watchlist {}: {}", name, e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get watchlist: {}", e)
+            }))
+        }
+    }
+}
+
+/// Create or replace a watchlist, sessions subscribed to it follow the change
+#[post("/api/watchlists")]
+async fn save_watchlist(
+    store: web::Data<Addr<WatchlistStore>>,
+    req: web::Json<WatchlistRequest>,
+) -> impl Responder {
+    let WatchlistRequest { name, instruments } = req.into_inner();
+    match store.send(SaveWatchlist { name, instruments }).await {
+        Ok(Ok(watchlist)) => HttpResponse::Ok().json(watchlist),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(ErrorResponse { error: e.to_string() }),
+        Err(e) => {
+            error!("Failed to save watchlist: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to save watchlist: {}", e)
+            }))
+        }
+    }
+}
+
+/// Delete a watchlist, sessions subscribed to it drop its instruments
+#[delete("/api/watchlists/{name}")]
+async fn delete_watchlist(
+    store: web::Data<Addr<WatchlistStore>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let name = path.into_inner();
+    match store.send(DeleteWatchlist { name: name.clone() }).await {
+        Ok(Ok(())) => HttpResponse::NoContent().finish(),
+        Ok(Err(e)) => HttpResponse::NotFound().json(ErrorResponse { error: e.to_string() }),
+        Err(e) => {
+            error!("Failed to delete watchlist {}: {}", name, e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to delete watchlist: {}", e)
+            }))
+        }
+    }
+}
+
+/// Query for trading day lookups
+#[derive(Deserialize)]
+pub struct TradingDayQuery {
+    /// Date ("YYYY-MM-DD"), defaults to today (Beijing time)
+    pub date: Option<String>,
+}
+
+/// Query for trading time checks
+#[derive(Deserialize)]
+pub struct TradingTimeQuery {
+    /// Exchange code, e.g. "SHFE"
+    pub exchange: String,
+    /// Datetime ("YYYY-MM-DD HH:MM:SS", Beijing time), defaults to now
+    pub datetime: Option<String>,
+}
+
+/// Get the next trading day after a date
+#[get("/api/calendar/next_trading_day")]
+async fn next_trading_day(
+    calendar: web::Data<TradingCalendar>,
+    query: web::Query<TradingDayQuery>,
+) -> impl Responder {
+    let date = match &query.date {
+        Some(date) => match parse_date(date) {
+            Ok(date) => date,
+            Err(e) => return HttpResponse::BadRequest().json(ErrorResponse { error: e.to_string() }),
+        },
+        None => TradingCalendar::now_local().date(),
+    };
+    
+    HttpResponse::Ok().json(json!({
+        "date": date.to_string(),
+        "is_trading_day": calendar.is_trading_day(date),
+        "next_trading_day": calendar.next_trading_day(date).to_string(),
+        "previous_trading_day": calendar.previous_trading_day(date).to_string(),
+    }))
+}
+
+/// Check whether an exchange is in a trading session
+#[get("/api/calendar/is_trading_time")]
+async fn is_trading_time(
+    calendar: web::Data<TradingCalendar>,
+    query: web::Query<TradingTimeQuery>,
+) -> impl Responder {
+    if exchange_sessions(&query.exchange).is_none() {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Unknown exchange: {}", query.exchange),
+        });
+    }
+    
+    let datetime = match &query.datetime {
+        Some(datetime) => match chrono::NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S") {
+            Ok(datetime) => datetime,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(ErrorResponse {
+                    error: format!("Invalid datetime {}: {}", datetime, e),
+                })
+            }
+        },
+        None => TradingCalendar::now_local(),
+    };
+    
+    HttpResponse::Ok().json(json!({
+        "exchange": query.exchange.to_uppercase(),
+        "datetime": datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "is_trading_time": calendar.is_trading_time(&query.exchange, datetime),
+    }))
+}
+
+/// Get the current trading phase of every exchange
+#[get("/api/calendar/phases")]
+async fn get_trading_phases(phases: web::Data<Addr<TradingPhaseActor>>) -> impl Responder {
+    match phases.send(GetTradingPhases).await {
+        Ok(phases) => HttpResponse::Ok().json(phases),
+        Err(e) => {
+            error!("Failed to get trading phases: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get trading phases: {}", e)
+            }))
+        }
+    }
+}
+
+/// Get the latest end-of-session event of each exchange
+#[get("/api/calendar/session_end")]
+async fn get_session_ends(phases: web::Data<Addr<TradingPhaseActor>>) -> impl Responder {
+    match phases.send(GetSessionEnds).await {
+        Ok(ends) => HttpResponse::Ok().json(ends),
+        Err(e) => {
+            error!("Failed to get session ends: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to get session ends: {}", e)
+            }))
+        }
+    }
+}
+
+/// Query parameters of the tick history endpoint
+#[derive(Deserialize)]
+struct MdHistoryQuery {
+    /// Seconds of history before the instrument's latest tick
+    #[serde(default = "default_history_seconds")]
+    seconds: u64,
+}
+
+fn default_history_seconds() -> u64 {
+    300
+}
+
+/// Recent ticks of an instrument for chart backfill
+#[get("/api/md/history/{instrument_id}")]
+async fn get_tick_history(
+    history: Option<web::Data<Addr<TickHistoryActor>>>,
+    path: web::Path<String>,
+    query: web::Query<MdHistoryQuery>,
+) -> impl Responder {
+    let Some(history) = history else {
+        return HttpResponse::ServiceUnavailable().json(json!({
+            "error": "Tick history is disabled"
+        }));
+    };
+    let instrument_id = path.into_inner();
+
+    match history
+        .send(GetTickHistory {
+            instrument: instrument_id.clone(),
+            seconds: query.seconds,
+        })
+        .await
+    {
+        Ok(ticks) => HttpResponse::Ok().json(json!({
+            "instrument_id": instrument_id,
+            "seconds": query.seconds,
+            "ticks": ticks,
+        })),
+        Err(e) => {
+            error!("Failed to query tick history: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to query tick history: {}", e)
+            }))
+        }
+    }
+}
+
+/// Reload config.json and apply the changes that do not need a restart
+#[post("/api/admin/reload")]
+async fn reload_config(reloader: web::Data<Addr<ConfigReloader>>) -> impl Responder {
+    match reloader.send(ReloadConfig).await {
+        Ok(Ok(summary)) => HttpResponse::Ok().json(summary),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(ErrorResponse { error: e }),
+        Err(e) => {
+            error!("Failed to reload configuration: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to reload configuration: {}", e)
+            }))
+        }
+    }
+}
+
+/// Clients that exceeded their quotas and are still connected, worst first
+#[get("/api/admin/offenders")]
+async fn list_offenders(monitor: web::Data<Addr<QuotaMonitor>>) -> impl Responder {
+    match monitor.send(GetOffenders).await {
+        Ok(offenders) => HttpResponse::Ok().json(offenders),
+        Err(e) => {
+            error!("Failed to list offending clients: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to list offending clients: {}", e)
+            }))
+        }
+    }
+}
+
+/// Heartbeat round-trip times of connected clients, slowest first
+#[get("/api/admin/clients/rtt")]
+async fn list_client_rtt(monitor: web::Data<Addr<QuotaMonitor>>) -> impl Responder {
+    match monitor.send(GetClientRtt).await {
+        Ok(clients) => HttpResponse::Ok().json(clients),
+        Err(e) => {
+            error!("Failed to list client round-trip times: {}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to list client round-trip times: {}", e)
+            }))
+        }
+    }
+}
+
+/// Query for a forced disconnect
+#[derive(Deserialize)]
+pub struct DisconnectQuery {
+    /// Close reason sent to the client
+    #[serde(default = "default_disconnect_reason")]
+    pub reason: String,
+}
+
+fn default_disconnect_reason() -> String {
+    "quota exceeded".to_string()
+}
+
+/// Force-disconnect an offending client
+#[post("/api/admin/offenders/{client_id}/disconnect")]
+async fn disconnect_offender(
+    monitor: web::Data<Addr<QuotaMonitor>>,
+    path: web::Path<String>,
+    query: web::Query<DisconnectQuery>,
+) -> impl Responder {
+    let client_id = path.into_inner();
+
+    match monitor
+        .send(DisconnectClient {
+            client_id: client_id.clone(),
+            reason: query.into_inner().reason,
+        })
+        .await
+    {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().json(ErrorResponse {
+            error: format!("No offending client {}", client_id),
+        }),
+        Err(e) => {
+            error!("Failed to disconnect client {}: {}", client_id, e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to disconnect client: {}", e)
+            }))
+        }
+    }
+}
+
+/// Request to create a token or replace its scopes
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    #[serde(default)]
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+/// Check the admin token and that access control is enabled before managing tokens
+fn token_admin<'a>(
+    req: &HttpRequest,
+    admin: &Option<web::Data<AdminConfig>>,
+    acl: &'a Option<web::Data<AclStore>>,
+) -> GatewayResult<&'a AclStore> {
+    let authorized = match (admin.as_ref().and_then(|admin| admin.token()), request_token(req)) {
+        (Some(expected), Some(given)) => token_matches(&given, expected),
+        _ => false,
+    };
+    if !authorized {
+        return Err(GatewayError::AuthError("invalid admin token".to_string()));
+    }
+    acl.as_ref()
+        .map(|acl| acl.get_ref())
+        .ok_or_else(|| GatewayError::ConfigError("access control is not enabled".to_string()))
+}
+
+/// Response to a rejected token management request
+fn token_admin_error(error: GatewayError) -> HttpResponse {
+    let body = json!({
+        "code": error.code(),
+        "error": error.to_string(),
+    });
+    match error {
+        GatewayError::AuthError(_) => HttpResponse::Unauthorized().json(body),
+        _ => HttpResponse::NotFound().json(body),
+    }
+}
+
+/// List the client tokens and their scopes
+#[get("/api/admin/tokens")]
+async fn list_tokens(
+    req: HttpRequest,
+    admin: Option<web::Data<AdminConfig>>,
+    acl: Option<web::Data<AclStore>>,
+) -> impl Responder {
+    match token_admin(&req, &admin, &acl) {
+        Ok(acl) => {
+            let tokens = acl.list();
+            HttpResponse::Ok().json(json!({
+                "count": tokens.len(),
+                "tokens": tokens,
+            }))
+        }
+        Err(e) => token_admin_error(e),
+    }
+}
+
+/// Create a token with a generated value
+#[post("/api/admin/tokens")]
+async fn create_token(
+    req: HttpRequest,
+    admin: Option<web::Data<AdminConfig>>,
+    acl: Option<web::Data<AclStore>>,
+    body: web::Json<TokenRequest>,
+) -> impl Responder {
+    let acl = match token_admin(&req, &admin, &acl) {
+        Ok(acl) => acl,
+        Err(e) => return token_admin_error(e),
+    };
+    let TokenRequest { name, scopes } = body.into_inner();
+    let token = Uuid::new_v4().simple().to_string();
+    match acl.save_token(&token, &name, scopes) {
+        Ok(grant) => HttpResponse::Created().json(grant),
+        Err(e) => HttpResponse::BadRequest().json(ErrorResponse { error: e.to_string() }),
+    }
+}
+
+/// Create a token with a chosen value or replace the scopes of a token,
+/// connected clients using it follow the change
+#[put("/api/admin/tokens/{token}")]
+async fn save_token(
+    req: HttpRequest,
+    admin: Option<web::Data<AdminConfig>>,
+    acl: Option<web::Data<AclStore>>,
+    path: web::Path<String>,
+    body: web::Json<TokenRequest>,
+) -> impl Responder {
+    let acl = match token_admin(&req, &admin, &acl) {
+        Ok(acl) => acl,
+        Err(e) => return token_admin_error(e),
+    };
+    let TokenRequest { name, scopes } = body.into_inner();
+    match acl.save_token(&path.into_inner(), &name, scopes) {
+        Ok(grant) => HttpResponse::Ok().json(grant),
+        Err(e) => HttpResponse::BadRequest().json(ErrorResponse { error: e.to_string() }),
+    }
+}
+
+/// Delete a token, connected clients using it fall back to the anonymous scopes
+#[delete("/api/admin/tokens/{token}")]
+async fn delete_token(
+    req: HttpRequest,
+    admin: Option<web::Data<AdminConfig>>,
+    acl: Option<web::Data<AclStore>>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let acl = match token_admin(&req, &admin, &acl) {
+        Ok(acl) => acl,
+        Err(e) => return token_admin_error(e),
+    };
+    if acl.delete_token(&path.into_inner()) {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::NotFound().json(ErrorResponse {
+            error: "Unknown token".to_string(),
+        })
+    }
+}
+
 /// Configure API routes
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -174,6 +1227,42 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
             .service(get_subscriptions)
             .service(subscribe)
             .service(unsubscribe)
-            .service(get_status),
+            .service(get_status)
+            .service(get_tick_filter_stats)
+            .service(get_price_check_stats)
+            .service(get_clock_skew)
+            .service(get_latency)
+            .service(get_crashes)
+            .service(get_md_stats)
+            .service(get_md_quality)
+            .service(resubscribe_events)
+            .service(export_ticks)
+            .service(get_tick_history)
+            .service(list_instruments)
+            .service(get_instrument)
+            .service(search_symbols)
+            .service(list_watchlists)
+            .service(get_watchlist)
+            .service(save_watchlist)
+            .service(delete_watchlist)
+            .service(next_trading_day)
+            .service(is_trading_time)
+            .service(get_trading_phases)
+            .service(get_session_ends)
+            .service(list_brokers)
+            .service(get_load_sharing)
+            .service(add_broker)
+            .service(remove_broker)
+            .service(start_replay)
+            .service(stop_replay)
+            .service(set_replay_speed)
+            .service(reload_config)
+            .service(list_offenders)
+            .service(disconnect_offender)
+            .service(list_client_rtt)
+            .service(list_tokens)
+            .service(create_token)
+            .service(save_token)
+            .service(delete_token),
     );
 }
\ No newline at end of file
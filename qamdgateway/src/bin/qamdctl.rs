@@ -0,0 +1,647 @@
+//! Operates a running gateway through its REST and WebSocket APIs
+//!
+//! ```text
+//! qamdctl [--url URL] [--ws-path PATH] [--token TOKEN] <command> [args]
+//!
+//!   status                                   print the gateway status
+//!   subscribe <instrument>...                subscribe the gateway upstream
+//!   unsubscribe <instrument>...              unsubscribe the gateway upstream
+//!   tail [instrument...]                     print quotes as JSON lines
+//!   record --out FILE [instrument...]        write quotes to a .jsonl, .csv or .parquet file
+//! ```
+//!
+//! `tail` and `record` subscribe over the WebSocket API and stop on Ctrl-C, after `--limit N`
+//! quotes or after `--duration SECS`. Without instruments they follow everything the gateway
+//! is subscribed to. Each update is merged into the instrument's latest quote, so every line
+//! or row holds the full quote rather than the changed fields only.
+
+use awc::ws;
+use futures_util::{SinkExt, StreamExt};
+use hashbrown::HashMap;
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "Usage: qamdctl [--url URL] [--ws-path PATH] [--token TOKEN] <command> [args]
+
+Commands:
+  status                                   print the gateway status
+  subscribe <instrument>...                subscribe the gateway upstream
+  unsubscribe <instrument>...              unsubscribe the gateway upstream
+  tail [instrument...]                     print quotes as JSON lines
+  record --out FILE [instrument...]        write quotes to a .jsonl, .csv or .parquet file
+
+tail and record accept --limit N (quotes) and --duration SECS";
+
+/// Largest WebSocket frame accepted, a full `rtn_data` of many instruments can be large
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Columns written to CSV and Parquet files besides `instrument_id` and `datetime`,
+/// named like the snapshot fields so that replay mode reads the files back
+const PRICE_COLUMNS: [&str; 24] = [
+    "last_price",
+    "open",
+    "highest",
+    "lowest",
+    "close",
+    "average",
+    "amount",
+    "open_interest",
+    "pre_close",
+    "pre_settlement",
+    "pre_open_interest",
+    "settlement",
+    "upper_limit",
+    "lower_limit",
+    "bid_price1",
+    "bid_price2",
+    "bid_price3",
+    "bid_price4",
+    "bid_price5",
+    "ask_price1",
+    "ask_price2",
+    "ask_price3",
+    "ask_price4",
+    "ask_price5",
+];
+const VOLUME_COLUMNS: [&str; 11] = [
+    "volume",
+    "bid_volume1",
+    "bid_volume2",
+    "bid_volume3",
+    "bid_volume4",
+    "bid_volume5",
+    "ask_volume1",
+    "ask_volume2",
+    "ask_volume3",
+    "ask_volume4",
+    "ask_volume5",
+];
+
+enum Command {
+    Status,
+    Subscribe,
+    Unsubscribe,
+    Tail,
+    Record(PathBuf),
+}
+
+/// Command line options
+struct Options {
+    /// Base URL of the REST API, the WebSocket URL is derived from it
+    url: String,
+    ws_path: String,
+    token: Option<String>,
+    command: Command,
+    instruments: Vec<String>,
+    limit: Option<usize>,
+    duration: Option<Duration>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut url = "http://127.0.0.1:8080".to_string();
+    let mut ws_path = "/ws/market".to_string();
+    let mut token = None;
+    let mut out = None;
+    let mut limit = None;
+    let mut duration = None;
+    let mut positional = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--url" => url = value("--url")?,
+            "--ws-path" => ws_path = value("--ws-path")?,
+            "--token" => token = Some(value("--token")?),
+            "--out" => out = Some(PathBuf::from(value("--out")?)),
+            "--limit" => {
+                let value = value("--limit")?;
+                limit = Some(value.parse().map_err(|_| format!("Invalid limit {}", value))?);
+            }
+            "--duration" => {
+                let value = value("--duration")?;
+                let secs: u64 = value.parse().map_err(|_| format!("Invalid duration {}", value))?;
+                duration = Some(Duration::from_secs(secs));
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match (positional.next().as_deref(), out) {
+        (Some("record"), Some(out)) => Command::Record(out),
+        (Some("record"), None) => return Err("record needs --out FILE".to_string()),
+        (_, Some(_)) => return Err("--out is only used by record".to_string()),
+        (Some("status"), None) => Command::Status,
+        (Some("subscribe"), None) => Command::Subscribe,
+        (Some("unsubscribe"), None) => Command::Unsubscribe,
+        (Some("tail"), None) => Command::Tail,
+        (Some(other), None) => return Err(format!("Unknown command {}\n{}", other, USAGE)),
+        (None, None) => return Err(USAGE.to_string()),
+    };
+    // "rb2410,hc2410" and "rb2410 hc2410" are both accepted
+    let instruments: Vec<String> = positional
+        .flat_map(|arg| arg.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect::<Vec<_>>())
+        .collect();
+    if matches!(command, Command::Subscribe | Command::Unsubscribe) && instruments.is_empty() {
+        return Err(format!("No instruments given\n{}", USAGE));
+    }
+
+    Ok(Options {
+        url: url.trim_end_matches('/').to_string(),
+        ws_path,
+        token,
+        command,
+        instruments,
+        limit,
+        duration,
+    })
+}
+
+/// REST and WebSocket access to the gateway
+struct Gateway {
+    client: awc::Client,
+    url: String,
+    ws_path: String,
+    token: Option<String>,
+}
+
+impl Gateway {
+    fn new(options: &Options) -> Self {
+        Self {
+            client: awc::Client::builder().timeout(Duration::from_secs(10)).finish(),
+            url: options.url.clone(),
+            ws_path: options.ws_path.clone(),
+            token: options.token.clone(),
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<(bool, Value), String> {
+        let mut request = self.client.get(format!("{}{}", self.url, path));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await;
+        Self::read(path, response).await
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> Result<(bool, Value), String> {
+        let mut request = self.client.post(format!("{}{}", self.url, path));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send_json(body).await;
+        Self::read(path, response).await
+    }
+
+    /// Decodes a JSON response, returns whether the request succeeded
+    async fn read<S>(
+        path: &str,
+        response: Result<awc::ClientResponse<S>, awc::error::SendRequestError>,
+    ) -> Result<(bool, Value), String>
+    where
+        S: futures_util::Stream<Item = Result<actix_web::web::Bytes, awc::error::PayloadError>> + Unpin,
+    {
+        let mut response = response.map_err(|e| format!("{} failed: {}", path, e))?;
+        let body = response
+            .body()
+            .limit(64 * 1024 * 1024)
+            .await
+            .map_err(|e| format!("{} failed: {}", path, e))?;
+        let value = serde_json::from_slice(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+        Ok((response.status().is_success(), value))
+    }
+
+    /// Like `get`, failing on error responses
+    async fn fetch(&self, path: &str) -> Result<Value, String> {
+        match self.get(path).await? {
+            (true, value) => Ok(value),
+            (false, value) => Err(format!("{} failed: {}", path, error_text(&value))),
+        }
+    }
+
+    /// WebSocket URL on the same host as the REST API
+    fn ws_url(&self) -> String {
+        let url = match self.url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}", rest),
+            Some((_, rest)) => format!("ws://{}", rest),
+            None => format!("ws://{}", self.url),
+        };
+        match &self.token {
+            Some(token) => format!("{}{}?token={}", url, self.ws_path, token),
+            None => format!("{}{}", url, self.ws_path),
+        }
+    }
+
+    /// Instruments to stream: the ones given, or everything the gateway is subscribed to.
+    /// Codes without an exchange ("rb2410") get the exchange from the instrument registry.
+    async fn stream_instruments(&self, instruments: &[String]) -> Result<Vec<String>, String> {
+        let instruments = if instruments.is_empty() {
+            let subscriptions = self.fetch("/api/subscriptions").await?;
+            let instruments: Vec<String> = subscriptions["instruments"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|instrument| instrument.as_str().map(str::to_string))
+                .collect();
+            if instruments.is_empty() {
+                return Err("No instruments given and the gateway has no subscriptions".to_string());
+            }
+            instruments
+        } else {
+            instruments.to_vec()
+        };
+
+        let mut resolved = Vec::with_capacity(instruments.len());
+        for instrument in instruments {
+            if instrument.contains('.') || instrument.contains('*') {
+                resolved.push(instrument);
+                continue;
+            }
+            // Unknown to the registry (or no registry configured): subscribe as given
+            match self.get(&format!("/api/instruments/{}", instrument)).await? {
+                (true, info) => match (info["exchange_id"].as_str(), info["instrument_id"].as_str()) {
+                    (Some(exchange), Some(id)) => resolved.push(format!("{}.{}", exchange, id)),
+                    _ => resolved.push(instrument),
+                },
+                (false, _) => resolved.push(instrument),
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+/// Error message of an error response
+fn error_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        _ => value["error"].as_str().map(str::to_string).unwrap_or_else(|| value.to_string()),
+    }
+}
+
+/// Subscribes the instruments over the WebSocket API and hands the merged quote of every
+/// update to `on_quote`. Returns on Ctrl-C, once `deadline` has passed or `on_quote` returns false.
+async fn stream_quotes(
+    gateway: &Gateway,
+    instruments: &[String],
+    deadline: Option<Duration>,
+    mut on_quote: impl FnMut(&Map<String, Value>) -> Result<bool, String>,
+) -> Result<(), String> {
+    let url = gateway.ws_url();
+    let (_, mut framed) = gateway
+        .client
+        .ws(url.as_str())
+        .max_frame_size(MAX_FRAME_SIZE)
+        .connect()
+        .await
+        .map_err(|e| format!("Cannot connect to {}: {}", url, e))?;
+    let request = json!({ "aid": "subscribe_quote", "ins_list": instruments.join(",") }).to_string();
+    framed
+        .send(ws::Message::Text(request.into()))
+        .await
+        .map_err(|e| format!("Cannot send subscribe_quote: {}", e))?;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    let timeout = tokio::time::sleep(deadline.unwrap_or(Duration::MAX / 4));
+    tokio::pin!(ctrl_c, timeout);
+    let mut quotes: HashMap<String, Map<String, Value>> = HashMap::new();
+    loop {
+        let frame = tokio::select! {
+            _ = &mut ctrl_c => return Ok(()),
+            _ = &mut timeout => return Ok(()),
+            frame = framed.next() => frame,
+        };
+        let text = match frame {
+            Some(Ok(ws::Frame::Text(text))) => text,
+            Some(Ok(ws::Frame::Ping(data))) => {
+                framed
+                    .send(ws::Message::Pong(data))
+                    .await
+                    .map_err(|e| format!("Cannot answer ping: {}", e))?;
+                continue;
+            }
+            Some(Ok(ws::Frame::Close(reason))) => {
+                return Err(format!("Gateway closed the connection: {:?}", reason));
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(format!("WebSocket error: {}", e)),
+            None => return Err("Gateway closed the connection".to_string()),
+        };
+        let message: Value = match serde_json::from_slice(&text) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Ignoring malformed message: {}", e);
+                continue;
+            }
+        };
+
+        match message["aid"].as_str() {
+            Some("rtn_data") => {
+                let updates = message["data"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|item| item["quotes"].as_object());
+                for (instrument, fields) in updates.flatten() {
+                    let Some(fields) = fields.as_object() else {
+                        continue;
+                    };
+                    let quote = quotes.entry_ref(instrument.as_str()).or_default();
+                    quote
+                        .entry("instrument_id")
+                        .or_insert_with(|| Value::String(instrument.clone()));
+                    for (field, value) in fields {
+                        if value.is_null() {
+                            quote.remove(field);
+                        } else {
+                            quote.insert(field.clone(), value.clone());
+                        }
+                    }
+                    if !on_quote(quote)? {
+                        return Ok(());
+                    }
+                }
+            }
+            Some("rsp_subscribe_quote") => {
+                eprintln!("Subscribed {}", message["ins_list"].as_str().unwrap_or_default());
+            }
+            // Errors and notices go to stderr, stdout only carries quotes
+            Some(aid) if aid.starts_with("rtn_error") || aid.starts_with("rsp_") => eprintln!("{}", message),
+            _ => {}
+        }
+    }
+}
+
+/// Destination of `record`, chosen by the file extension
+enum TickWriter {
+    JsonLines(BufWriter<File>),
+    Csv(Box<csv::Writer<File>>),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<parquet_ticks::ParquetTicks>),
+}
+
+impl TickWriter {
+    fn create(path: &Path) -> Result<Self, String> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        let create = |path: &Path| File::create(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e));
+        match extension {
+            "jsonl" | "json" => Ok(TickWriter::JsonLines(BufWriter::new(create(path)?))),
+            "csv" => {
+                let mut writer = csv::Writer::from_writer(create(path)?);
+                let header = ["instrument_id", "datetime"].into_iter().chain(PRICE_COLUMNS).chain(VOLUME_COLUMNS);
+                writer.write_record(header).map_err(|e| format!("CSV error: {}", e))?;
+                Ok(TickWriter::Csv(Box::new(writer)))
+            }
+            #[cfg(feature = "parquet")]
+            "parquet" | "pq" => {
+                parquet_ticks::ParquetTicks::create(path).map(|writer| TickWriter::Parquet(Box::new(writer)))
+            }
+            #[cfg(not(feature = "parquet"))]
+            "parquet" | "pq" => Err(format!(
+                "Recording to Parquet requires the `replay-parquet` or `eod-parquet` feature: {}",
+                path.display()
+            )),
+            _ => Err(format!(
+                "Unsupported output file {}, use .jsonl, .csv or .parquet",
+                path.display()
+            )),
+        }
+    }
+
+    fn write(&mut self, quote: &Map<String, Value>) -> Result<(), String> {
+        match self {
+            TickWriter::JsonLines(writer) => {
+                serde_json::to_writer(&mut *writer, quote).map_err(|e| e.to_string())?;
+                writer.write_all(b"\n").map_err(|e| e.to_string())
+            }
+            TickWriter::Csv(writer) => {
+                let text = |field: &str| match quote.get(field) {
+                    Some(Value::String(text)) => text.clone(),
+                    Some(Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                };
+                let row = ["instrument_id", "datetime"]
+                    .into_iter()
+                    .chain(PRICE_COLUMNS)
+                    .chain(VOLUME_COLUMNS)
+                    .map(text);
+                writer.write_record(row).map_err(|e| format!("CSV error: {}", e))
+            }
+            #[cfg(feature = "parquet")]
+            TickWriter::Parquet(writer) => writer.write(quote),
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            TickWriter::JsonLines(mut writer) => writer.flush().map_err(|e| e.to_string()),
+            TickWriter::Csv(mut writer) => writer.flush().map_err(|e| e.to_string()),
+            #[cfg(feature = "parquet")]
+            TickWriter::Parquet(writer) => writer.finish(),
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_ticks {
+    use super::{PRICE_COLUMNS, VOLUME_COLUMNS};
+    use parquet::basic::Compression;
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use serde_json::{Map, Value};
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    /// Quotes buffered before they are written as one row group
+    const ROW_GROUP_ROWS: usize = 65536;
+
+    fn parquet_error(e: ParquetError) -> String {
+        format!("Parquet error: {}", e)
+    }
+
+    /// Parquet tick file, written to a temporary file that is renamed once complete
+    pub struct ParquetTicks {
+        writer: SerializedFileWriter<File>,
+        rows: Vec<Map<String, Value>>,
+        path: PathBuf,
+        temp: PathBuf,
+    }
+
+    impl ParquetTicks {
+        pub fn create(path: &Path) -> Result<Self, String> {
+            let mut schema = String::from("message ticks {\n");
+            schema.push_str("  REQUIRED BYTE_ARRAY instrument_id (UTF8);\n");
+            schema.push_str("  REQUIRED BYTE_ARRAY datetime (UTF8);\n");
+            for column in PRICE_COLUMNS {
+                schema.push_str(&format!("  OPTIONAL DOUBLE {};\n", column));
+            }
+            for column in VOLUME_COLUMNS {
+                schema.push_str(&format!("  OPTIONAL INT64 {};\n", column));
+            }
+            schema.push('}');
+
+            let schema = Arc::new(parse_message_type(&schema).map_err(parquet_error)?);
+            let properties = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+            let temp = path.with_extension("parquet.tmp");
+            let file = File::create(&temp).map_err(|e| format!("Cannot create {}: {}", temp.display(), e))?;
+            let writer = SerializedFileWriter::new(file, schema, properties).map_err(parquet_error)?;
+            Ok(Self {
+                writer,
+                rows: Vec::new(),
+                path: path.to_path_buf(),
+                temp,
+            })
+        }
+
+        pub fn write(&mut self, quote: &Map<String, Value>) -> Result<(), String> {
+            self.rows.push(quote.clone());
+            if self.rows.len() >= ROW_GROUP_ROWS {
+                self.flush().map_err(parquet_error)?;
+            }
+            Ok(())
+        }
+
+        pub fn finish(mut self) -> Result<(), String> {
+            self.flush().map_err(parquet_error)?;
+            self.writer.close().map_err(parquet_error)?;
+            fs::rename(&self.temp, &self.path).map_err(|e| format!("Cannot write {}: {}", self.path.display(), e))
+        }
+
+        /// Writes the buffered quotes as a row group
+        fn flush(&mut self) -> Result<(), ParquetError> {
+            if self.rows.is_empty() {
+                return Ok(());
+            }
+            let rows = std::mem::take(&mut self.rows);
+            let mut row_group = self.writer.next_row_group()?;
+            let mut index = 0;
+            while let Some(mut column) = row_group.next_column()? {
+                match index {
+                    0 | 1 => {
+                        let field = ["instrument_id", "datetime"][index];
+                        let values: Vec<ByteArray> =
+                            rows.iter().map(|row| row[field].as_str().unwrap_or_default().into()).collect();
+                        column.typed::<ByteArrayType>().write_batch(&values, None, None)
+                    }
+                    _ if index < 2 + PRICE_COLUMNS.len() => {
+                        let field = PRICE_COLUMNS[index - 2];
+                        let values: Vec<Option<f64>> =
+                            rows.iter().map(|row| row.get(field).and_then(Value::as_f64)).collect();
+                        let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+                        let present: Vec<f64> = values.into_iter().flatten().collect();
+                        column.typed::<DoubleType>().write_batch(&present, Some(&levels), None)
+                    }
+                    _ => {
+                        let field = VOLUME_COLUMNS[index - 2 - PRICE_COLUMNS.len()];
+                        let values: Vec<Option<i64>> =
+                            rows.iter().map(|row| row.get(field).and_then(Value::as_i64)).collect();
+                        let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+                        let present: Vec<i64> = values.into_iter().flatten().collect();
+                        column.typed::<Int64Type>().write_batch(&present, Some(&levels), None)
+                    }
+                }?;
+                column.close()?;
+                index += 1;
+            }
+            row_group.close()?;
+            Ok(())
+        }
+    }
+}
+
+/// Prints a line to stdout, returns false once stdout is closed (e.g. piped into `head`)
+fn print_line(line: impl std::fmt::Display) -> bool {
+    writeln!(std::io::stdout(), "{}", line).is_ok()
+}
+
+async fn run(options: Options) -> Result<(), String> {
+    let gateway = Gateway::new(&options);
+    match &options.command {
+        Command::Status => {
+            let status = gateway.fetch("/api/status").await?;
+            print_line(serde_json::to_string_pretty(&status).map_err(|e| e.to_string())?);
+        }
+        Command::Subscribe | Command::Unsubscribe => {
+            let path = match options.command {
+                Command::Subscribe => "/api/subscriptions",
+                _ => "/api/unsubscribe",
+            };
+            match gateway.post(path, &json!({ "instruments": options.instruments })).await? {
+                (true, response) => {
+                    // The response lists all upstream subscriptions after the change
+                    let instruments: Vec<&str> = response["instruments"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Value::as_str)
+                        .collect();
+                    eprintln!("The gateway is subscribed to {} instruments", instruments.len());
+                    for instrument in instruments {
+                        print_line(instrument);
+                    }
+                }
+                (false, response) => return Err(format!("{} failed: {}", path, error_text(&response))),
+            }
+        }
+        Command::Tail => {
+            let instruments = gateway.stream_instruments(&options.instruments).await?;
+            let mut count = 0;
+            stream_quotes(&gateway, &instruments, options.duration, |quote| {
+                count += 1;
+                Ok(print_line(Value::Object(quote.clone())) && options.limit.is_none_or(|limit| count < limit))
+            })
+            .await?;
+        }
+        Command::Record(out) => {
+            let instruments = gateway.stream_instruments(&options.instruments).await?;
+            let mut writer = TickWriter::create(out)?;
+            let mut count = 0;
+            let streamed = stream_quotes(&gateway, &instruments, options.duration, |quote| {
+                writer.write(quote)?;
+                count += 1;
+                Ok(options.limit.is_none_or(|limit| count < limit))
+            })
+            .await;
+            // Keep what was recorded even when the connection dropped
+            writer.finish()?;
+            eprintln!("Recorded {} quotes to {}", count, out.display());
+            streamed?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    // Logs go to stderr, stdout carries the command output
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")))
+        .with_writer(std::io::stderr)
+        .init();
+
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match actix_rt::System::new().block_on(run(options)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Replays a raw upstream message journal through the snapshot converter
+//!
+//! ```text
+//! qamdjournal [--source CTP|QQ|Sina] [--instrument ID] [--tolerant] [--limit N] <file or dir>...
+//! ```
+//!
+//! Prints one JSON line per record: the converted snapshot, the conversion error, or
+//! for provider payloads (Binance, Sina HTTP) the raw text. Directories are replayed
+//! file by file, oldest first.
+
+use chrono::DateTime;
+use qamdgateway::actors::messages::MarketDataSource;
+use qamdgateway::converter::SnapshotConverter;
+use qamdgateway::journal::{journal_files, JournalPayload, JournalReader, JournalRecord};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str =
+    "Usage: qamdjournal [--source CTP|QQ|Sina] [--instrument ID] [--tolerant] [--limit N] <file or dir>...";
+
+/// Command line options
+struct Options {
+    /// Convert as if received from this source instead of the recorded one
+    source: Option<MarketDataSource>,
+    instrument: Option<String>,
+    tolerant: bool,
+    limit: Option<usize>,
+    paths: Vec<PathBuf>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        source: None,
+        instrument: None,
+        tolerant: false,
+        limit: None,
+        paths: Vec::new(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--source" => {
+                let source = value("--source")?;
+                options.source = Some(
+                    serde_json::from_value(Value::String(source.clone()))
+                        .map_err(|_| format!("Unknown source {}", source))?,
+                );
+            }
+            "--instrument" => options.instrument = Some(value("--instrument")?),
+            "--tolerant" => options.tolerant = true,
+            "--limit" => {
+                let limit = value("--limit")?;
+                options.limit = Some(limit.parse().map_err(|_| format!("Invalid limit {}", limit))?);
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}\n{}", arg, USAGE)),
+            _ => options.paths.push(PathBuf::from(arg)),
+        }
+    }
+    if options.paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+/// Journal files named on the command line, directories expanded oldest first
+fn input_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(journal_files(path).map_err(|e| format!("Cannot list {}: {}", path.display(), e))?);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// NUL-terminated text field, lossily decoded
+fn text(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).trim().to_string()
+}
+
+/// Replays the records, returns (records, failed conversions)
+fn replay(options: &Options, files: &[PathBuf]) -> Result<(usize, usize), String> {
+    let (mut records, mut failed) = (0, 0);
+    for file in files {
+        let reader = JournalReader::open(file).map_err(|e| format!("Cannot open {}: {}", file.display(), e))?;
+        for record in reader {
+            let JournalRecord {
+                received_us,
+                source,
+                payload,
+            } = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    eprintln!("{}: {}", file.display(), e);
+                    break;
+                }
+            };
+            let source = options.source.unwrap_or(source);
+            let received_at = DateTime::from_timestamp_micros(received_us).map(|t| t.to_rfc3339());
+
+            let line = match payload {
+                JournalPayload::CtpDepth(data) => {
+                    let instrument = text(&data.InstrumentID);
+                    if options.instrument.as_ref().is_some_and(|wanted| *wanted != instrument) {
+                        continue;
+                    }
+                    let mut converter = SnapshotConverter::new(source);
+                    if options.tolerant {
+                        converter = converter.with_tolerant_parsing(None);
+                    }
+                    match converter.convert(&data) {
+                        Ok(snapshot) => json!({
+                            "received_at": received_at,
+                            "source": source,
+                            "snapshot": snapshot,
+                        }),
+                        Err(e) => {
+                            failed += 1;
+                            json!({
+                                "received_at": received_at,
+                                "source": source,
+                                "instrument": instrument,
+                                "exchange": text(&data.ExchangeID),
+                                "error": e.to_string(),
+                            })
+                        }
+                    }
+                }
+                JournalPayload::Bytes(bytes) => {
+                    // Sina responses are GBK encoded
+                    let payload = match source {
+                        MarketDataSource::SinaHttp => encoding_rs::GBK.decode(&bytes).0.into_owned(),
+                        _ => String::from_utf8_lossy(&bytes).into_owned(),
+                    };
+                    if options.instrument.as_ref().is_some_and(|wanted| !payload.contains(wanted.as_str())) {
+                        continue;
+                    }
+                    json!({
+                        "received_at": received_at,
+                        "source": source,
+                        "payload": payload,
+                    })
+                }
+            };
+            println!("{}", line);
+            records += 1;
+            if options.limit.is_some_and(|limit| records >= limit) {
+                return Ok((records, failed));
+            }
+        }
+    }
+    Ok((records, failed))
+}
+
+fn main() -> ExitCode {
+    // Logs go to stderr, stdout carries the replayed records
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")))
+        .with_writer(std::io::stderr)
+        .init();
+
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = input_files(&options.paths).and_then(|files| replay(&options, &files));
+    match result {
+        Ok((records, failed)) => {
+            eprintln!("Replayed {} records, {} failed to convert", records, failed);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
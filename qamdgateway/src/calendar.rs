@@ -0,0 +1,455 @@
+//! 交易日历与交易时段
+//!
+//! 交易日 = 工作日 - 节假日。节假日来自内置数据（见`trade_dates`）和配置（或节假日文件），
+//! 各交易所的交易时段（含夜盘、午休）来自qamd-rs的交易所注册表，
+//! 供网关组件判断当前是否处于交易时间（例如非交易时间抑制重连）。
+//!
+//! 也可以用类似cron的写法自定义时段：`[星期] HH:MM-HH:MM`，
+//! 星期为`*`、`mon-fri`或`mon,wed,fri`，省略时为周一至周五，例如：
+//! - `09:00-15:00`
+//! - `mon-fri 21:00-02:30`：开始时间在18:00之后的时段按夜盘处理
+//!
+//! 交易阶段（盘前、集合竞价、连续交易、休市、收盘）同样由交易时段推算：
+//! 开盘时段（日盘第一个时段和夜盘）之前依次为盘前和集合竞价，
+//! 日盘各时段之间为休市（小节休息和午休），其余时间为收盘。
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use qamd_rs::InstrumentType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+
+use crate::config::CalendarConfig;
+use crate::error::{GatewayError, GatewayResult};
+use crate::trade_dates;
+
+// 交易时段和期货交易所由qamd-rs的交易所注册表统一定义（有夜盘的品种按交易所最晚收盘时间计算）
+pub use qamd_rs::constants::exchange::{TradingSession, FUTURES_EXCHANGES};
+
+const fn hm(hour: u32, min: u32) -> NaiveTime {
+    match NaiveTime::from_hms_opt(hour, min, 0) {
+        Some(time) => time,
+        None => panic!("invalid session time"),
+    }
+}
+
+/// 获取交易所的交易时段，未知交易所返回None
+pub fn exchange_sessions(exchange: &str) -> Option<&'static [TradingSession]> {
+    qamd_rs::constants::exchange::lookup(exchange).map(|info| info.sessions)
+}
+
+/// 夜盘开始时间的下限，用于判断自定义时段是否为夜盘
+const NIGHT_SESSION_START: NaiveTime = hm(18, 0);
+
+/// 早于此时间的行情属于前一天晚上的夜盘
+const MORNING_START: NaiveTime = hm(6, 0);
+
+/// 周一至周五
+const WEEKDAYS: u8 = 0b001_1111;
+
+/// 股票交易所的开盘时段从集合竞价开始（09:15-09:30）
+const STOCK_AUCTION_MINUTES: i64 = 15;
+
+/// 交易阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradingPhase {
+    /// 收盘（含夜盘结束到盘前之间）
+    Closed,
+    /// 盘前
+    PreOpen,
+    /// 开盘集合竞价
+    CallAuction,
+    /// 连续交易
+    Continuous,
+    /// 日盘时段之间的休市（小节休息、午休）
+    Break,
+}
+
+impl TradingPhase {
+    /// 是否处于交易时段（集合竞价或连续交易）
+    pub fn is_trading(&self) -> bool {
+        matches!(self, TradingPhase::CallAuction | TradingPhase::Continuous)
+    }
+}
+
+/// 按星期定义的交易时段
+///
+/// 日盘只在交易日生效，夜盘只在有夜盘的晚上（下一交易日不在长假之后）生效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionSchedule {
+    /// 生效的星期（第0位为周一）
+    days: u8,
+    session: TradingSession,
+}
+
+impl SessionSchedule {
+    /// 周一至周五的交易所时段
+    pub fn from_session(session: TradingSession) -> Self {
+        Self { days: WEEKDAYS, session }
+    }
+
+    /// 解析`[星期] HH:MM-HH:MM`格式的时段
+    pub fn parse(spec: &str) -> GatewayResult<Self> {
+        let invalid = |reason: &str| GatewayError::ConfigError(format!("Invalid session {:?}: {}", spec, reason));
+        let tokens: Vec<&str> = spec.split_whitespace().collect();
+        let (days, times) = match tokens.as_slice() {
+            [times] => (WEEKDAYS, *times),
+            [days, times] => (parse_days(days).ok_or_else(|| invalid("unknown weekday"))?, *times),
+            _ => return Err(invalid("expected `[days] HH:MM-HH:MM`")),
+        };
+        let (start, end) = times.split_once('-').ok_or_else(|| invalid("missing time range"))?;
+        let parse_time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| invalid("invalid time"));
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            return Err(invalid("empty time range"));
+        }
+        let session = if start >= NIGHT_SESSION_START {
+            TradingSession::night(start, end)
+        } else {
+            TradingSession::day(start, end)
+        };
+        Ok(Self { days, session })
+    }
+
+    /// 时段
+    pub fn session(&self) -> TradingSession {
+        self.session
+    }
+
+    fn runs_on_weekday(&self, weekday: Weekday) -> bool {
+        self.days & (1 << weekday.num_days_from_monday()) != 0
+    }
+}
+
+/// 解析星期：`*`、`mon-fri`、`mon,wed,fri`
+fn parse_days(spec: &str) -> Option<u8> {
+    if spec == "*" {
+        return Some(0b111_1111);
+    }
+    let day = |name: &str| name.parse::<Weekday>().ok().map(|day| day.num_days_from_monday());
+    let mut days = 0u8;
+    for part in spec.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (day(from)?, day(to)?);
+                if from > to {
+                    return None;
+                }
+                for day in from..=to {
+                    days |= 1 << day;
+                }
+            }
+            None => days |= 1 << day(part)?,
+        }
+    }
+    Some(days)
+}
+
+/// 中国标准时间（UTC+8）
+pub fn china_offset() -> FixedOffset {
+    FixedOffset::east_opt(8 * 3600).expect("valid offset")
+}
+
+/// 交易日历
+#[derive(Debug, Clone)]
+pub struct TradingCalendar {
+    holidays: BTreeSet<NaiveDate>,
+}
+
+impl Default for TradingCalendar {
+    /// 只包含内置节假日的交易日历
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+
+impl TradingCalendar {
+    /// 使用节假日列表创建交易日历（与内置节假日合并）
+    pub fn new(holidays: impl IntoIterator<Item = NaiveDate>) -> Self {
+        Self {
+            holidays: trade_dates::holidays().chain(holidays).collect(),
+        }
+    }
+
+    /// 根据配置创建交易日历（合并配置中的节假日和节假日文件）
+    pub fn from_config(config: &CalendarConfig) -> GatewayResult<Self> {
+        let mut holidays = config
+            .holidays
+            .iter()
+            .map(|date| parse_date(date))
+            .collect::<GatewayResult<BTreeSet<_>>>()?;
+
+        if let Some(path) = &config.holidays_file {
+            let content = fs::read_to_string(path)?;
+            for line in content.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                holidays.insert(parse_date(line)?);
+            }
+        }
+
+        Ok(Self::new(holidays))
+    }
+
+    /// 是否为交易日
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+
+    /// 下一个交易日（不含当天）
+    pub fn next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut next = date + Duration::days(1);
+        while !self.is_trading_day(next) {
+            next += Duration::days(1);
+        }
+        next
+    }
+
+    /// 上一个交易日（不含当天）
+    pub fn previous_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut prev = date - Duration::days(1);
+        while !self.is_trading_day(prev) {
+            prev -= Duration::days(1);
+        }
+        prev
+    }
+
+    /// 当天晚上是否有夜盘
+    ///
+    /// 夜盘属于下一个交易日，节假日前最后一个交易日的晚上没有夜盘
+    pub fn has_night_session(&self, date: NaiveDate) -> bool {
+        self.is_trading_day(date) && self.next_trading_day(date) - date <= Duration::days(3)
+    }
+
+    /// 某一时刻（北京时间）的行情所属的交易日
+    ///
+    /// 夜盘（含午夜后的部分）属于下一个交易日
+    pub fn trading_day(&self, datetime: NaiveDateTime) -> NaiveDate {
+        let (date, time) = (datetime.date(), datetime.time());
+        if time >= NIGHT_SESSION_START {
+            self.next_trading_day(date)
+        } else if time < MORNING_START {
+            self.next_trading_day(date - Duration::days(1))
+        } else {
+            date
+        }
+    }
+
+    /// 指定交易所在某一时刻（北京时间）是否处于交易时段
+    pub fn is_trading_time(&self, exchange: &str, datetime: NaiveDateTime) -> bool {
+        let Some(sessions) = exchange_sessions(exchange) else {
+            return false;
+        };
+        let (date, time) = (datetime.date(), datetime.time());
+
+        sessions.iter().any(|session| {
+            if !session.night {
+                return self.is_trading_day(date) && session.start <= time && time < session.end;
+            }
+            if session.crosses_midnight() {
+                // 午夜前属于当天晚上，午夜后属于前一天晚上的夜盘
+                (time >= session.start && self.has_night_session(date))
+                    || (time < session.end && self.has_night_session(date - Duration::days(1)))
+            } else {
+                session.start <= time && time < session.end && self.has_night_session(date)
+            }
+        })
+    }
+
+    /// 任一期货交易所在`lead`时间内是否处于或即将进入交易时段
+    pub fn is_futures_session_near(&self, now: DateTime<Utc>, lead: Duration) -> bool {
+        let local = now.with_timezone(&china_offset()).naive_local();
+        FUTURES_EXCHANGES.iter().any(|exchange| {
+            self.is_trading_time(exchange, local) || self.is_trading_time(exchange, local + lead)
+        })
+    }
+
+    /// 某一时刻（北京时间）是否处于时段开始前`lead`至结束后`linger`的窗口内
+    pub fn is_in_session_window(
+        &self,
+        schedule: &SessionSchedule,
+        datetime: NaiveDateTime,
+        lead: Duration,
+        linger: Duration,
+    ) -> bool {
+        let session = schedule.session();
+        // 提前量或跨午夜的时段可能属于前后一天
+        [-1, 0, 1].into_iter().any(|offset| {
+            let date = datetime.date() + Duration::days(offset);
+            let runs = schedule.runs_on_weekday(date.weekday())
+                && if session.night {
+                    self.has_night_session(date)
+                } else {
+                    self.is_trading_day(date)
+                };
+            if !runs {
+                return false;
+            }
+            let open = date.and_time(session.start) - lead;
+            let close_date = if session.crosses_midnight() { date + Duration::days(1) } else { date };
+            let close = close_date.and_time(session.end) + linger;
+            open <= datetime && datetime < close
+        })
+    }
+
+    /// 指定交易所在某一时刻（北京时间）的交易阶段
+    ///
+    /// 期货交易所的集合竞价在开盘时段之前的`auction`内，盘前为再往前的`pre_open`内；
+    /// 股票交易所的开盘时段本身包含集合竞价。未知交易所总是收盘
+    pub fn phase(&self, exchange: &str, datetime: NaiveDateTime, pre_open: Duration, auction: Duration) -> TradingPhase {
+        let Some(info) = qamd_rs::constants::exchange::lookup(exchange) else {
+            return TradingPhase::Closed;
+        };
+        let zero = Duration::zero();
+        let in_window = |session: TradingSession, datetime: NaiveDateTime, lead: Duration| {
+            self.is_in_session_window(&SessionSchedule::from_session(session), datetime, lead, zero)
+        };
+        let day_sessions = || info.sessions.iter().filter(|session| !session.night);
+        // 开盘时段：日盘第一个时段和夜盘，之前没有紧邻的时段
+        let first_day_start = day_sessions().map(|session| session.start).min();
+        let is_opening = |session: &TradingSession| session.night || Some(session.start) == first_day_start;
+
+        if self.is_trading_time(exchange, datetime) {
+            let auction_end = datetime - Duration::minutes(STOCK_AUCTION_MINUTES);
+            let in_auction = info.kind == InstrumentType::Stock
+                && info
+                    .sessions
+                    .iter()
+                    .filter(|session| is_opening(session))
+                    .any(|session| in_window(*session, datetime, zero) && !in_window(*session, auction_end, zero));
+            return if in_auction {
+                TradingPhase::CallAuction
+            } else {
+                TradingPhase::Continuous
+            };
+        }
+
+        let last_day_end = day_sessions().map(|session| session.end).max();
+        if let (Some(start), Some(end)) = (first_day_start, last_day_end) {
+            let time = datetime.time();
+            if self.is_trading_day(datetime.date()) && start <= time && time < end {
+                return TradingPhase::Break;
+            }
+        }
+
+        // 股票交易所的集合竞价已包含在开盘时段内
+        let auction = if info.kind == InstrumentType::Stock { zero } else { auction };
+        let openings = || info.sessions.iter().copied().filter(is_opening);
+        if openings().any(|session| in_window(session, datetime, auction) && !in_window(session, datetime, zero)) {
+            TradingPhase::CallAuction
+        } else if openings().any(|session| in_window(session, datetime, auction + pre_open)) {
+            TradingPhase::PreOpen
+        } else {
+            TradingPhase::Closed
+        }
+    }
+
+    /// 某一时刻（北京时间）交易所当天的日盘是否已经结束（夜盘开始之前）
+    ///
+    /// 日盘结束即交易日结束，夜盘收盘后交易日仍在继续。未知交易所返回false
+    pub fn is_after_day_close(&self, exchange: &str, datetime: NaiveDateTime) -> bool {
+        let Some(close) = exchange_sessions(exchange)
+            .and_then(|sessions| sessions.iter().filter(|session| !session.night).map(|session| session.end).max())
+        else {
+            return false;
+        };
+        let time = datetime.time();
+        self.is_trading_day(datetime.date()) && close <= time && time < NIGHT_SESSION_START
+    }
+
+    /// 当前北京时间
+    pub fn now_local() -> NaiveDateTime {
+        china_offset().from_utc_datetime(&Utc::now().naive_utc()).naive_local()
+    }
+}
+
+/// 解析"YYYY-MM-DD"或"YYYYMMDD"格式的日期
+pub fn parse_date(date: &str) -> GatewayResult<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y%m%d"))
+        .map_err(|e| GatewayError::ConfigError(format!("Invalid date {}: {}", date, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    fn at(date_str: &str, time: &str) -> NaiveDateTime {
+        date(date_str).and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    /// 2024年国庆：10月1日（周二）至10月7日（周一）休市
+    fn national_day() -> TradingCalendar {
+        TradingCalendar::new((1..=7).map(|day| NaiveDate::from_ymd_opt(2024, 10, day).unwrap()))
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(date("2024-10-01"), NaiveDate::from_ymd_opt(2024, 10, 1).unwrap());
+        assert_eq!(date("20241001"), NaiveDate::from_ymd_opt(2024, 10, 1).unwrap());
+        assert!(parse_date("2024/10/01").is_err());
+        assert!(parse_date("20241301").is_err());
+    }
+
+    #[test]
+    fn test_friday_night_session() {
+        let calendar = TradingCalendar::default();
+        // 2024-09-06为周五，夜盘持续到周六02:30，属于下周一的交易日
+        assert!(calendar.has_night_session(date("2024-09-06")));
+        assert!(calendar.is_trading_time("SHFE", at("2024-09-06", "21:30")));
+        assert!(calendar.is_trading_time("SHFE", at("2024-09-07", "01:00")));
+        assert!(!calendar.is_trading_time("SHFE", at("2024-09-07", "02:30")));
+        assert!(!calendar.is_trading_time("DCE", at("2024-09-07", "01:00")));
+        assert_eq!(calendar.trading_day(at("2024-09-07", "01:00")), date("2024-09-09"));
+        // 周六晚上没有夜盘
+        assert!(!calendar.is_trading_time("SHFE", at("2024-09-07", "21:30")));
+    }
+
+    #[test]
+    fn test_no_night_session_before_holiday() {
+        let calendar = national_day();
+        assert_eq!(calendar.next_trading_day(date("2024-09-30")), date("2024-10-08"));
+        assert!(!calendar.has_night_session(date("2024-09-30")));
+        assert!(!calendar.is_trading_time("SHFE", at("2024-09-30", "21:30")));
+        assert!(!calendar.is_trading_time("SHFE", at("2024-10-01", "01:00")));
+        // 节前最后一个交易日的日盘照常
+        assert!(calendar.is_trading_time("SHFE", at("2024-09-30", "10:00")));
+        // 节前的周五晚上仍有夜盘
+        assert!(calendar.is_trading_time("SHFE", at("2024-09-28", "01:00")));
+        // 节后第一天的前一晚（周一）不是交易日
+        assert!(!calendar.is_trading_time("SHFE", at("2024-10-07", "21:30")));
+        assert!(calendar.is_trading_time("SHFE", at("2024-10-08", "21:30")));
+    }
+
+    #[test]
+    fn test_builtin_holidays() {
+        let calendar = TradingCalendar::default();
+        assert!(!calendar.is_trading_day(date("2024-10-01")));
+        assert!(!calendar.is_trading_day(date("2024-09-16")));
+        assert_eq!(calendar.next_trading_day(date("2024-09-30")), date("2024-10-08"));
+        assert!(!calendar.has_night_session(date("2024-09-30")));
+        // 调休的周末仍按非交易日处理
+        assert!(!calendar.is_trading_day(date("2024-09-29")));
+        assert!(calendar.is_trading_day(date("2024-12-31")));
+        // 内置数据之后的节假日来自配置
+        assert!(calendar.is_trading_day(date("2025-01-01")));
+        assert!(!TradingCalendar::new([date("2025-01-01")]).is_trading_day(date("2025-01-01")));
+    }
+
+    #[test]
+    fn test_cffex_close() {
+        let calendar = TradingCalendar::default();
+        assert!(calendar.is_trading_time("CFFEX", at("2024-09-06", "15:10")));
+        assert!(!calendar.is_trading_time("CFFEX", at("2024-09-06", "15:15")));
+        assert!(!calendar.is_trading_time("CFFEX", at("2024-09-06", "21:30")));
+        assert!(!calendar.is_after_day_close("CFFEX", at("2024-09-06", "15:10")));
+        assert!(calendar.is_after_day_close("CFFEX", at("2024-09-06", "15:15")));
+        assert!(calendar.is_after_day_close("SHFE", at("2024-09-06", "15:10")));
+    }
+}
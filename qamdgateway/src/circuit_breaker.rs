@@ -0,0 +1,357 @@
+//! 上游前置的重连熔断
+//!
+//! 每个前置地址各自维护熔断状态：
+//! - 关闭（Closed）：连接失败后按指数退避重试
+//! - 打开（Open）：连续失败达到阈值后停止尝试，等待`open_secs`
+//! - 半开（HalfOpen）：等待结束后允许一次探测连接，成功则关闭，失败则重新打开
+//!
+//! 每个前置每小时的连接次数受`max_attempts_per_hour`限制。
+//! 当前前置不可用时轮换到最早可以尝试的其他前置；同时可以尝试的前置按健康分
+//! （连接耗时、最近一小时的断线次数和连续失败次数）选择，分数相同时按配置顺序轮换。
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::config::CircuitBreakerConfig;
+
+/// 连接次数预算和断线次数的统计窗口
+const BUDGET_WINDOW: Duration = Duration::from_secs(3600);
+
+/// 连接耗时的平滑系数
+const LATENCY_ALPHA: f64 = 0.3;
+
+/// 熔断状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// 单个前置的熔断状态
+#[derive(Debug, Clone)]
+struct FrontCircuit {
+    addr: String,
+    state: CircuitState,
+    // 连续失败次数
+    failures: u32,
+    last_failure: Option<Instant>,
+    opened_at: Option<Instant>,
+    // 统计窗口内的连接时间
+    attempts: VecDeque<Instant>,
+    // 统计窗口内连接成功后的断线时间
+    disconnects: VecDeque<Instant>,
+    // 平滑后的连接耗时（毫秒，未连接成功过时为None）
+    connect_latency_ms: Option<f64>,
+}
+
+impl FrontCircuit {
+    fn new(addr: String) -> Self {
+        Self {
+            addr,
+            state: CircuitState::Closed,
+            failures: 0,
+            last_failure: None,
+            opened_at: None,
+            attempts: VecDeque::new(),
+            disconnects: VecDeque::new(),
+            connect_latency_ms: None,
+        }
+    }
+
+    /// 移出统计窗口之外的记录
+    fn expire(&mut self, now: Instant) {
+        for events in [&mut self.attempts, &mut self.disconnects] {
+            while events
+                .front()
+                .is_some_and(|event| now.duration_since(*event) >= BUDGET_WINDOW)
+            {
+                events.pop_front();
+            }
+        }
+    }
+
+    /// 健康分（0-100，越高越好），没有连接过的前置不扣连接耗时的分
+    fn score(&self) -> f64 {
+        let latency = self.connect_latency_ms.map_or(0.0, |ms| (ms / 100.0).min(30.0));
+        let disconnects = (self.disconnects.len() as f64 * 10.0).min(50.0);
+        let failures = (self.failures as f64 * 5.0).min(20.0);
+        (100.0 - latency - disconnects - failures).max(0.0)
+    }
+
+    /// 最早可以尝试连接的时间
+    fn ready_at(&mut self, config: &CircuitBreakerConfig, now: Instant) -> Instant {
+        self.expire(now);
+
+        let mut ready = now;
+        // 超出每小时的连接次数时等待最早的一次移出窗口
+        if config.max_attempts_per_hour > 0 && self.attempts.len() >= config.max_attempts_per_hour as usize {
+            if let Some(oldest) = self.attempts.front() {
+                ready = ready.max(*oldest + BUDGET_WINDOW);
+            }
+        }
+        match (self.state, self.opened_at, self.last_failure) {
+            (CircuitState::Open, Some(opened_at), _) => {
+                ready = ready.max(opened_at + Duration::from_secs(config.open_secs));
+            }
+            (_, _, Some(last_failure)) if self.failures > 0 => {
+                ready = ready.max(last_failure + backoff(config, self.failures));
+            }
+            _ => {}
+        }
+        ready
+    }
+}
+
+/// 第n次连续失败后的退避时间
+fn backoff(config: &CircuitBreakerConfig, failures: u32) -> Duration {
+    let ms = config
+        .backoff_initial_ms
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(config.backoff_max_ms);
+    Duration::from_millis(ms)
+}
+
+/// 前置的熔断状态（用于状态接口）
+#[derive(Debug, Clone, Serialize)]
+pub struct FrontStatus {
+    pub addr: String,
+    pub state: CircuitState,
+    /// 连续失败次数
+    pub failures: u32,
+    /// 最近一小时的连接次数
+    pub attempts_last_hour: usize,
+    /// 最近一小时连接成功后的断线次数
+    pub disconnects_last_hour: usize,
+    /// 平滑后的连接耗时
+    pub connect_latency_ms: Option<u64>,
+    /// 健康分（0-100）
+    pub score: f64,
+}
+
+/// 一个broker全部前置的熔断器
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    fronts: Vec<FrontCircuit>,
+    // 当前使用的前置
+    current: usize,
+}
+
+impl CircuitBreaker {
+    /// fronts按优先顺序排列，不能为空
+    pub fn new(fronts: Vec<String>, config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            fronts: fronts.into_iter().map(FrontCircuit::new).collect(),
+            current: 0,
+        }
+    }
+
+    /// 当前使用的前置地址
+    pub fn current_front(&self) -> &str {
+        &self.fronts[self.current].addr
+    }
+
+    /// 连接的超时时间
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.connect_timeout_secs)
+    }
+
+    /// 选择下一次连接的前置，返回需要等待的时间
+    ///
+    /// 从当前前置开始依次比较，取最早可以尝试的前置，同时可以尝试时取健康分最高的前置
+    pub fn plan(&mut self, now: Instant) -> Duration {
+        let count = self.fronts.len();
+        let mut best: Option<(usize, Instant, f64)> = None;
+        for offset in 0..count {
+            let index = (self.current + offset) % count;
+            let ready = self.fronts[index].ready_at(&self.config, now);
+            let score = self.fronts[index].score();
+            let better = best.is_none_or(|(_, best_ready, best_score)| {
+                ready < best_ready || (ready == best_ready && score > best_score)
+            });
+            if better {
+                best = Some((index, ready, score));
+            }
+        }
+        let (index, ready, _) = best.unwrap_or((self.current, now, 0.0));
+        self.current = index;
+        ready.saturating_duration_since(now)
+    }
+
+    /// 记录一次对当前前置的连接，打开状态的前置转为半开
+    pub fn record_attempt(&mut self, now: Instant) {
+        let front = &mut self.fronts[self.current];
+        if front.state == CircuitState::Open {
+            front.state = CircuitState::HalfOpen;
+        }
+        front.attempts.push_back(now);
+    }
+
+    /// 当前前置连接成功，记录从最近一次连接开始的耗时
+    pub fn on_success(&mut self, now: Instant) {
+        let front = &mut self.fronts[self.current];
+        if let Some(attempt) = front.attempts.back() {
+            let ms = now.duration_since(*attempt).as_secs_f64() * 1000.0;
+            front.connect_latency_ms = Some(match front.connect_latency_ms {
+                Some(latency) => latency + LATENCY_ALPHA * (ms - latency),
+                None => ms,
+            });
+        }
+        front.state = CircuitState::Closed;
+        front.failures = 0;
+        front.opened_at = None;
+    }
+
+    /// 当前前置连接失败，返回熔断是否因此打开
+    pub fn on_failure(&mut self, now: Instant) -> bool {
+        let threshold = self.config.failure_threshold.max(1);
+        let front = &mut self.fronts[self.current];
+        front.failures += 1;
+        front.last_failure = Some(now);
+        let open = front.state == CircuitState::HalfOpen || front.failures >= threshold;
+        if open {
+            front.state = CircuitState::Open;
+            front.opened_at = Some(now);
+        }
+        open
+    }
+
+    /// 当前前置在连接成功后断开
+    pub fn on_disconnect(&mut self, now: Instant) {
+        self.fronts[self.current].disconnects.push_back(now);
+    }
+
+    /// 全部前置的熔断状态和健康分
+    pub fn status(&mut self) -> Vec<FrontStatus> {
+        let now = Instant::now();
+        self.fronts
+            .iter_mut()
+            .map(|front| {
+                front.expire(now);
+                FrontStatus {
+                    addr: front.addr.clone(),
+                    state: front.state,
+                    failures: front.failures,
+                    attempts_last_hour: front.attempts.len(),
+                    disconnects_last_hour: front.disconnects.len(),
+                    connect_latency_ms: front.connect_latency_ms.map(|ms| ms.round() as u64),
+                    score: front.score(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: 3,
+            backoff_initial_ms: 1000,
+            backoff_max_ms: 8000,
+            open_secs: 60,
+            max_attempts_per_hour: 0,
+            connect_timeout_secs: 10,
+        }
+    }
+
+    fn breaker(config: CircuitBreakerConfig) -> CircuitBreaker {
+        CircuitBreaker::new(vec!["tcp://127.0.0.1:41213".to_string()], config)
+    }
+
+    /// 在`now`连接一次并失败，返回熔断是否打开
+    fn fail(breaker: &mut CircuitBreaker, now: Instant) -> bool {
+        breaker.record_attempt(now);
+        breaker.on_failure(now)
+    }
+
+    #[test]
+    fn test_threshold_opens_circuit() {
+        let mut breaker = breaker(config());
+        let start = Instant::now();
+        assert_eq!(breaker.plan(start), Duration::ZERO);
+
+        assert!(!fail(&mut breaker, start));
+        assert_eq!(breaker.plan(start), Duration::from_millis(1000));
+        assert!(!fail(&mut breaker, start + Duration::from_secs(1)));
+        assert_eq!(breaker.plan(start + Duration::from_secs(1)), Duration::from_millis(2000));
+        assert_eq!(breaker.fronts[0].state, CircuitState::Closed);
+
+        let opened = start + Duration::from_secs(3);
+        assert!(fail(&mut breaker, opened));
+        assert_eq!(breaker.fronts[0].state, CircuitState::Open);
+        assert_eq!(breaker.plan(opened), Duration::from_secs(60));
+        assert_eq!(breaker.plan(opened + Duration::from_secs(60)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_half_open_probe() {
+        let mut breaker = breaker(config());
+        let start = Instant::now();
+        for _ in 0..3 {
+            fail(&mut breaker, start);
+        }
+        assert_eq!(breaker.fronts[0].state, CircuitState::Open);
+
+        // 探测失败重新打开，并重新等待open_secs
+        let probe = start + Duration::from_secs(60);
+        breaker.record_attempt(probe);
+        assert_eq!(breaker.fronts[0].state, CircuitState::HalfOpen);
+        assert!(breaker.on_failure(probe));
+        assert_eq!(breaker.fronts[0].state, CircuitState::Open);
+        assert_eq!(breaker.plan(probe), Duration::from_secs(60));
+
+        // 探测成功关闭熔断
+        let probe = probe + Duration::from_secs(60);
+        breaker.record_attempt(probe);
+        breaker.on_success(probe + Duration::from_millis(200));
+        assert_eq!(breaker.fronts[0].state, CircuitState::Closed);
+        assert_eq!(breaker.fronts[0].failures, 0);
+        assert_eq!(breaker.fronts[0].connect_latency_ms, Some(200.0));
+        assert_eq!(breaker.plan(probe + Duration::from_secs(1)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 100,
+            ..config()
+        };
+        let delays: Vec<u64> = [1, 2, 3, 4, 5, 40].iter().map(|n| backoff(&config, *n).as_millis() as u64).collect();
+        assert_eq!(delays, [1000, 2000, 4000, 8000, 8000, 8000]);
+
+        let mut breaker = breaker(config);
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(!fail(&mut breaker, now));
+        }
+        assert_eq!(breaker.plan(now), Duration::from_millis(8000));
+    }
+
+    #[test]
+    fn test_attempt_budget_delays_next_attempt() {
+        let mut breaker = breaker(CircuitBreakerConfig {
+            max_attempts_per_hour: 3,
+            ..config()
+        });
+        let start = Instant::now();
+        for second in 0..3 {
+            let now = start + Duration::from_secs(second);
+            assert_eq!(breaker.plan(now), Duration::ZERO);
+            breaker.record_attempt(now);
+            breaker.on_success(now);
+            breaker.on_disconnect(now);
+        }
+
+        // 等到最早的一次连接移出一小时的窗口
+        let now = start + Duration::from_secs(10);
+        assert_eq!(breaker.plan(now), BUDGET_WINDOW - Duration::from_secs(10));
+        assert_eq!(breaker.plan(start + BUDGET_WINDOW), Duration::ZERO);
+    }
+}
@@ -0,0 +1,212 @@
+//! 行情源时钟偏差估计
+//!
+//! CTP行情的时间戳来自交易所，QQ和新浪行情的时间戳来自行情商，彼此可能相差数秒。
+//! 每个数据源保留最近若干条行情的偏差样本（本地接收时间减行情时间戳），
+//! 以中位数作为该数据源的时钟偏差，不受个别延迟行情的影响。
+//! 偏差超过上限的样本视为过时快照（如登录后推送的上一笔行情），不参与估计。
+
+use chrono::{DateTime, Duration, Utc};
+use hashbrown::HashMap;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::actors::messages::MarketDataSource;
+use crate::config::ClockSkewConfig;
+
+/// 偏差估计的最短更新间隔，避免每条行情都重新计算中位数
+const ESTIMATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// 数据源的时钟偏差统计
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceClockSkew {
+    /// 数据源
+    pub source: MarketDataSource,
+    /// 估计的偏差（毫秒，正数表示行情时间戳落后于本地时钟），样本不足时为None
+    pub skew_ms: Option<i64>,
+    /// 窗口内的样本数
+    pub samples: usize,
+    /// 因偏差过大丢弃的样本数
+    pub discarded: u64,
+    /// 最近一次采样的本地时间
+    pub last_sample: DateTime<Utc>,
+    /// 是否按偏差修正该数据源的行情时间
+    pub corrected: bool,
+}
+
+/// 单个数据源的偏差样本
+struct SourceSkew {
+    // 最近的偏差样本（毫秒）
+    samples: VecDeque<i64>,
+    // 因偏差过大丢弃的样本数
+    discarded: u64,
+    last_sample: DateTime<Utc>,
+    // 当前的偏差估计（毫秒）
+    estimate: Option<i64>,
+    estimated_at: Option<Instant>,
+}
+
+impl SourceSkew {
+    /// 窗口内样本的中位数
+    fn median(&self) -> i64 {
+        let mut samples: Vec<i64> = self.samples.iter().copied().collect();
+        let middle = samples.len() / 2;
+        *samples.select_nth_unstable(middle).1
+    }
+}
+
+/// 各数据源的时钟偏差估计器
+pub struct ClockSkewEstimator {
+    config: ClockSkewConfig,
+    sources: HashMap<MarketDataSource, SourceSkew>,
+}
+
+impl ClockSkewEstimator {
+    /// 创建估计器
+    pub fn new(config: ClockSkewConfig) -> Self {
+        Self {
+            config,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// 记录一条行情的时间戳和本地接收时间（回放行情不参与估计）
+    pub fn observe(&mut self, source: MarketDataSource, datetime: DateTime<Utc>, received: DateTime<Utc>) {
+        if source == MarketDataSource::Replay || self.config.window == 0 {
+            return;
+        }
+
+        let skew = self.sources.entry(source).or_insert_with(|| SourceSkew {
+            samples: VecDeque::with_capacity(self.config.window),
+            discarded: 0,
+            last_sample: received,
+            estimate: None,
+            estimated_at: None,
+        });
+        skew.last_sample = received;
+
+        let offset = (received - datetime).num_milliseconds();
+        if offset.abs() > self.config.max_skew_ms {
+            skew.discarded += 1;
+            return;
+        }
+        if skew.samples.len() == self.config.window {
+            skew.samples.pop_front();
+        }
+        skew.samples.push_back(offset);
+
+        if skew.samples.len() >= self.config.min_samples.max(1)
+            && skew.estimated_at.is_none_or(|at| at.elapsed() >= ESTIMATE_INTERVAL)
+        {
+            skew.estimate = Some(skew.median());
+            skew.estimated_at = Some(Instant::now());
+        }
+    }
+
+    /// 数据源当前的偏差估计
+    pub fn skew(&self, source: MarketDataSource) -> Option<Duration> {
+        self.sources
+            .get(&source)
+            .and_then(|skew| skew.estimate)
+            .map(Duration::milliseconds)
+    }
+
+    /// 是否修正该数据源的行情时间
+    pub fn corrects(&self, source: MarketDataSource) -> bool {
+        self.config.correct.contains(&source)
+    }
+
+    /// 按偏差估计修正行情时间，未配置修正或样本不足时原样返回
+    pub fn correct(&self, source: MarketDataSource, datetime: DateTime<Utc>) -> DateTime<Utc> {
+        if !self.corrects(source) {
+            return datetime;
+        }
+        match self.skew(source) {
+            Some(skew) => datetime + skew,
+            None => datetime,
+        }
+    }
+
+    /// 各数据源的偏差统计
+    pub fn report(&self) -> Vec<SourceClockSkew> {
+        let mut report: Vec<SourceClockSkew> = self
+            .sources
+            .iter()
+            .map(|(&source, skew)| SourceClockSkew {
+                source,
+                skew_ms: skew.estimate,
+                samples: skew.samples.len(),
+                discarded: skew.discarded,
+                last_sample: skew.last_sample,
+                corrected: self.corrects(source),
+            })
+            .collect();
+        report.sort_by_key(|skew| format!("{:?}", skew.source));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimator(min_samples: usize, correct: Vec<MarketDataSource>) -> ClockSkewEstimator {
+        ClockSkewEstimator::new(ClockSkewConfig {
+            window: 5,
+            min_samples,
+            max_skew_ms: 10_000,
+            correct,
+        })
+    }
+
+    #[test]
+    fn test_median_skew_after_min_samples() {
+        let mut estimator = estimator(3, vec![MarketDataSource::QQ]);
+        let received = Utc::now();
+        estimator.observe(MarketDataSource::QQ, received - Duration::milliseconds(1000), received);
+        estimator.observe(MarketDataSource::QQ, received - Duration::milliseconds(9000), received);
+        assert_eq!(estimator.skew(MarketDataSource::QQ), None);
+        assert_eq!(estimator.correct(MarketDataSource::QQ, received), received);
+
+        // 中位数不受个别延迟行情的影响
+        estimator.observe(MarketDataSource::QQ, received - Duration::milliseconds(1200), received);
+        assert_eq!(estimator.skew(MarketDataSource::QQ), Some(Duration::milliseconds(1200)));
+        assert_eq!(
+            estimator.correct(MarketDataSource::QQ, received),
+            received + Duration::milliseconds(1200)
+        );
+    }
+
+    #[test]
+    fn test_stale_and_replay_samples_are_ignored() {
+        let mut estimator = estimator(1, vec![]);
+        let received = Utc::now();
+        estimator.observe(MarketDataSource::Sina, received - Duration::seconds(30), received);
+        estimator.observe(MarketDataSource::Replay, received - Duration::seconds(1), received);
+        assert_eq!(estimator.skew(MarketDataSource::Sina), None);
+        assert_eq!(estimator.skew(MarketDataSource::Replay), None);
+
+        estimator.observe(MarketDataSource::Sina, received - Duration::milliseconds(500), received);
+        assert_eq!(estimator.skew(MarketDataSource::Sina), Some(Duration::milliseconds(500)));
+        // 未配置修正的数据源不修正行情时间
+        assert_eq!(estimator.correct(MarketDataSource::Sina, received), received);
+
+        let report = estimator.report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].source, MarketDataSource::Sina);
+        assert_eq!(report[0].samples, 1);
+        assert_eq!(report[0].discarded, 1);
+        assert_eq!(report[0].skew_ms, Some(500));
+        assert!(!report[0].corrected);
+    }
+
+    #[test]
+    fn test_window_keeps_recent_samples() {
+        let mut estimator = estimator(1, vec![]);
+        let received = Utc::now();
+        for offset in 0..8 {
+            estimator.observe(MarketDataSource::CTP, received - Duration::milliseconds(offset), received);
+        }
+        assert_eq!(estimator.report()[0].samples, 5);
+    }
+}
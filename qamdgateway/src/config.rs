@@ -1,18 +1,22 @@
+use crate::actors::messages::MarketDataSource;
 use crate::error::{GatewayError, GatewayResult};
 use serde::{Deserialize, Serialize};
 use hashbrown::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// CTP Broker configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BrokerConfig {
     /// Broker name
     pub name: String,
     /// Front address (e.g., "tcp://180.168.146.187:10131")
     pub front_addr: String,
+    /// Further front addresses, rotated to when `front_addr` is unreachable
+    #[serde(default)]
+    pub backup_fronts: Vec<String>,
     /// User ID
     #[serde(default)]
     pub user_id: String,
@@ -28,8 +32,21 @@ pub struct BrokerConfig {
     /// Auth code
     #[serde(default)]
     pub auth_code: String,
-    /// Source type
+    /// Market data source of this broker: `ctp`, `qq` or `sina`
+    /// (defaults to the first source compiled in)
     pub source_type: Option<String>,
+    /// Flow file directory of this broker (defaults to `{flow.root}/{source}/{name}`)
+    #[serde(default)]
+    pub flow_dir: Option<String>,
+}
+
+impl BrokerConfig {
+    /// `front_addr` followed by the backup fronts
+    pub fn fronts(&self) -> Vec<String> {
+        std::iter::once(self.front_addr.clone())
+            .chain(self.backup_fronts.iter().cloned())
+            .collect()
+    }
 }
 
 /// WebSocket server configuration
@@ -41,6 +58,305 @@ pub struct WebSocketConfig {
     pub port: u16,
     /// Path for the WebSocket endpoint
     pub path: String,
+    /// Per-client send queue settings
+    #[serde(default)]
+    pub send_queue: SendQueueConfig,
+    /// Seconds a disconnected session can be resumed with `?session_id=` (0 disables resumption)
+    #[serde(default = "default_resume_grace_secs")]
+    pub resume_grace_secs: u64,
+    /// Per-client subscription and message rate limits
+    #[serde(default)]
+    pub quota: ClientQuotaConfig,
+    /// Ping interval, timeout and RTT limits
+    #[serde(default)]
+    pub heartbeat: HeartbeatConfig,
+}
+
+fn default_resume_grace_secs() -> u64 {
+    60
+}
+
+/// Per-client limits enforced by WebSocket sessions, 0 disables a limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientQuotaConfig {
+    /// Maximum subscribed instruments, each wildcard pattern counts as one
+    #[serde(default = "default_max_instruments")]
+    pub max_instruments: usize,
+    /// Maximum subscribe requests per minute
+    #[serde(default = "default_max_subscribe_per_minute")]
+    pub max_subscribe_per_minute: u32,
+    /// Maximum inbound messages per second
+    #[serde(default = "default_max_messages_per_second")]
+    pub max_messages_per_second: u32,
+}
+
+fn default_max_instruments() -> usize {
+    2000
+}
+
+fn default_max_subscribe_per_minute() -> u32 {
+    60
+}
+
+fn default_max_messages_per_second() -> u32 {
+    50
+}
+
+impl Default for ClientQuotaConfig {
+    fn default() -> Self {
+        Self {
+            max_instruments: default_max_instruments(),
+            max_subscribe_per_minute: default_max_subscribe_per_minute(),
+            max_messages_per_second: default_max_messages_per_second(),
+        }
+    }
+}
+
+/// WebSocket heartbeat settings, 0 disables an RTT limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeartbeatConfig {
+    /// Seconds between pings
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub interval_secs: u64,
+    /// Disconnect clients that send nothing for this many seconds
+    #[serde(default = "default_heartbeat_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Maximum smoothed round-trip time in milliseconds
+    #[serde(default)]
+    pub max_rtt_ms: u64,
+    /// Maximum round-trip time jitter in milliseconds
+    #[serde(default)]
+    pub max_jitter_ms: u64,
+    /// Consecutive pongs over a limit before the client is disconnected
+    #[serde(default = "default_heartbeat_max_violations")]
+    pub max_violations: u32,
+    /// Unsubscribe market data clients that send no request for this many seconds, 0 disables
+    #[serde(default)]
+    pub idle_unsubscribe_secs: u64,
+    /// Overrides for WebSocket routes by path, e.g. a listener's `ws_path` or `admin.path`
+    #[serde(default)]
+    pub routes: HashMap<String, HeartbeatOverride>,
+}
+
+/// Heartbeat settings of one route, unset fields keep the `websocket.heartbeat` value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeartbeatOverride {
+    pub interval_secs: Option<u64>,
+    pub timeout_secs: Option<u64>,
+    pub max_rtt_ms: Option<u64>,
+    pub max_jitter_ms: Option<u64>,
+    pub max_violations: Option<u32>,
+    pub idle_unsubscribe_secs: Option<u64>,
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    10
+}
+
+fn default_heartbeat_timeout_secs() -> u64 {
+    30
+}
+
+fn default_heartbeat_max_violations() -> u32 {
+    3
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_heartbeat_interval_secs(),
+            timeout_secs: default_heartbeat_timeout_secs(),
+            max_rtt_ms: 0,
+            max_jitter_ms: 0,
+            max_violations: default_heartbeat_max_violations(),
+            idle_unsubscribe_secs: 0,
+            routes: HashMap::new(),
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    /// Settings for a WebSocket route with its override applied
+    pub fn for_route(&self, path: &str) -> HeartbeatConfig {
+        let Some(route) = self.routes.get(path) else {
+            return HeartbeatConfig {
+                routes: HashMap::new(),
+                ..self.clone()
+            };
+        };
+        HeartbeatConfig {
+            interval_secs: route.interval_secs.unwrap_or(self.interval_secs),
+            timeout_secs: route.timeout_secs.unwrap_or(self.timeout_secs),
+            max_rtt_ms: route.max_rtt_ms.unwrap_or(self.max_rtt_ms),
+            max_jitter_ms: route.max_jitter_ms.unwrap_or(self.max_jitter_ms),
+            max_violations: route.max_violations.unwrap_or(self.max_violations),
+            idle_unsubscribe_secs: route.idle_unsubscribe_secs.unwrap_or(self.idle_unsubscribe_secs),
+            routes: HashMap::new(),
+        }
+    }
+}
+
+/// What to do when a client's send queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    /// Drop the oldest queued update (its instrument is resent in full once the queue drains)
+    DropOldest,
+    /// Merge into the queued update of the same instrument, else drop the oldest
+    Conflate,
+    /// Close the connection
+    Disconnect,
+}
+
+/// Market data message protocols sent to a client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageOutput {
+    /// TradingView style `rtn_data` messages
+    #[default]
+    Tv,
+    /// Legacy `market_data` messages
+    Legacy,
+    /// Both, each update is sent twice
+    Both,
+}
+
+impl MessageOutput {
+    /// Parse a name from a query parameter or `set_output` request
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "tv" => Some(MessageOutput::Tv),
+            "legacy" => Some(MessageOutput::Legacy),
+            "both" => Some(MessageOutput::Both),
+            _ => None,
+        }
+    }
+
+    pub fn includes_tv(self) -> bool {
+        matches!(self, MessageOutput::Tv | MessageOutput::Both)
+    }
+
+    pub fn includes_legacy(self) -> bool {
+        matches!(self, MessageOutput::Legacy | MessageOutput::Both)
+    }
+}
+
+/// Per-client send queue settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SendQueueConfig {
+    /// Maximum number of queued instrument updates
+    #[serde(default = "default_queue_capacity")]
+    pub capacity: usize,
+    /// Default overflow policy, clients may change it
+    #[serde(default = "default_queue_policy")]
+    pub policy: QueuePolicy,
+    /// Interval between queue flushes in milliseconds
+    #[serde(default = "default_queue_drain_interval_ms")]
+    pub drain_interval_ms: u64,
+    /// Maximum number of instrument updates sent per flush
+    #[serde(default = "default_queue_drain_batch")]
+    pub drain_batch: usize,
+    /// Default message protocols of queued updates, clients may change it
+    #[serde(default)]
+    pub output: MessageOutput,
+    /// Default window in milliseconds collecting all pending updates into one frame
+    /// (0 flushes every drain interval), clients may change it
+    #[serde(default)]
+    pub batch_window_ms: u64,
+    /// Downgrade of clients that cannot keep up to conflated updates
+    #[serde(default)]
+    pub slow_consumer: SlowConsumerConfig,
+}
+
+fn default_queue_capacity() -> usize {
+    1024
+}
+
+fn default_queue_policy() -> QueuePolicy {
+    QueuePolicy::Conflate
+}
+
+fn default_queue_drain_interval_ms() -> u64 {
+    10
+}
+
+fn default_queue_drain_batch() -> usize {
+    64
+}
+
+impl Default for SendQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_queue_capacity(),
+            policy: default_queue_policy(),
+            drain_interval_ms: default_queue_drain_interval_ms(),
+            drain_batch: default_queue_drain_batch(),
+            output: MessageOutput::default(),
+            batch_window_ms: 0,
+            slow_consumer: SlowConsumerConfig::default(),
+        }
+    }
+}
+
+/// Slow consumer detection: a client whose updates take too long to encode and send, or whose
+/// send queue keeps growing, is switched to conflated updates until it catches up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowConsumerConfig {
+    #[serde(default = "default_slow_consumer_enabled")]
+    pub enabled: bool,
+    /// Drain lag in milliseconds above which a second counts as slow: how much longer than the
+    /// fastest heartbeat round trip a ping sent behind the session's updates takes to be answered
+    #[serde(default = "default_slow_consumer_max_drain_ms", alias = "max_send_ms")]
+    pub max_drain_ms: u64,
+    /// Send queue fill in percent of `capacity` above which a second counts as slow unless the queue shrinks
+    #[serde(default = "default_slow_consumer_queue_pct")]
+    pub queue_pct: u8,
+    /// Consecutive slow seconds before the client is downgraded
+    #[serde(default = "default_slow_consumer_slow_secs")]
+    pub slow_secs: u32,
+    /// Interval of the conflated updates sent to a downgraded client
+    #[serde(default = "default_slow_consumer_conflate_ms")]
+    pub conflate_ms: u64,
+    /// Consecutive idle seconds (empty queue, under a quarter of `max_drain_ms`) before full rate is restored
+    #[serde(default = "default_slow_consumer_recover_secs")]
+    pub recover_secs: u32,
+}
+
+fn default_slow_consumer_enabled() -> bool {
+    true
+}
+
+fn default_slow_consumer_max_drain_ms() -> u64 {
+    500
+}
+
+fn default_slow_consumer_queue_pct() -> u8 {
+    50
+}
+
+fn default_slow_consumer_slow_secs() -> u32 {
+    5
+}
+
+fn default_slow_consumer_conflate_ms() -> u64 {
+    1000
+}
+
+fn default_slow_consumer_recover_secs() -> u32 {
+    30
+}
+
+impl Default for SlowConsumerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_slow_consumer_enabled(),
+            max_drain_ms: default_slow_consumer_max_drain_ms(),
+            queue_pct: default_slow_consumer_queue_pct(),
+            slow_secs: default_slow_consumer_slow_secs(),
+            conflate_ms: default_slow_consumer_conflate_ms(),
+            recover_secs: default_slow_consumer_recover_secs(),
+        }
+    }
 }
 
 /// REST API configuration
@@ -53,6 +369,41 @@ pub struct RestApiConfig {
     /// CORS settings
     #[serde(default)]
     pub cors: CorsConfig,
+    /// Listeners to bind, replacing host/port when not empty
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+}
+
+/// A single HTTP/WebSocket listener
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenerConfig {
+    /// "host:port", "[::]:port" or "unix:/path/to/socket"
+    pub address: String,
+    /// WebSocket path on this listener, defaults to websocket.path
+    #[serde(default)]
+    pub ws_path: Option<String>,
+    /// Let an IPv6 listener also accept IPv4 connections
+    #[serde(default)]
+    pub dual_stack: bool,
+    /// Serve HTTPS/WSS with these certificates (requires the `tls` feature)
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// TLS certificate settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert_file: String,
+    /// PEM private key
+    pub key_file: String,
+    /// Seconds between checks for renewed certificate files, 0 disables reloading
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    30
 }
 
 /// CORS configuration
@@ -86,62 +437,1654 @@ pub struct SubscriptionConfig {
     #[serde(default)]
     pub default_instruments: Vec<String>,
     /// Auto-subscribe to certain instruments based on patterns
+    /// (instrument groups such as "ALL.SHFE" or "FUTURE.*")
     #[serde(default)]
     pub auto_subscribe_patterns: Vec<String>,
+    /// Instrument reference data file (JSON array or CSV)
+    #[serde(default)]
+    pub instruments_file: Option<String>,
+    /// File used to persist subscription state across restarts
+    #[serde(default)]
+    pub state_file: Option<String>,
+    /// Seconds restored sessions can be resumed with `?session_id=` before their subscriptions are dropped
+    #[serde(default = "default_restore_grace_secs")]
+    pub restore_grace_secs: u64,
+    /// Maximum number of instruments a single wildcard pattern may expand to
+    #[serde(default = "default_max_pattern_matches")]
+    pub max_pattern_matches: usize,
+    /// File storing the shared named watchlists (kept in memory only if unset)
+    #[serde(default)]
+    pub watchlists_file: Option<String>,
+    /// Watched file listing further default instruments, one per line (`#` starts a comment)
+    #[serde(default)]
+    pub subscription_file: Option<String>,
+    /// File storing the subscriptions and throttling preferences of named clients
+    /// (kept in memory only if unset)
+    #[serde(default)]
+    pub profiles_file: Option<String>,
 }
 
-/// Gateway configuration
+fn default_restore_grace_secs() -> u64 {
+    300
+}
+
+fn default_max_pattern_matches() -> usize {
+    500
+}
+
+/// Historical replay settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    /// List of available brokers
-    pub brokers: HashMap<String, BrokerConfig>,
-    /// Default broker to use
-    pub default_broker: String,
-    /// WebSocket server configuration
-    pub websocket: WebSocketConfig,
-    /// REST API configuration
-    pub rest_api: RestApiConfig,
-    /// Subscription settings
+pub struct ReplayConfig {
+    /// Enable replay mode (upstream brokers are not connected)
     #[serde(default)]
-    pub subscription: SubscriptionConfig,
-    /// Log level
-    #[serde(default = "default_log_level")]
-    pub log_level: String,
+    pub enabled: bool,
+    /// Base directory using the QALfs layout, or a single tick file
+    pub path: String,
+    /// Dataset directory under `path` (e.g. "tickdata")
+    #[serde(default = "default_replay_dataset")]
+    pub dataset: String,
+    /// First trade date to replay ("YYYY-MM-DD"), ignored for single files
+    #[serde(default)]
+    pub start: Option<String>,
+    /// Last trade date to replay ("YYYY-MM-DD"), ignored for single files
+    #[serde(default)]
+    pub end: Option<String>,
+    /// Replay speed: "1x", "10x", "max"
+    #[serde(default = "default_replay_speed")]
+    pub speed: String,
+    /// Only replay these instruments (empty means all)
+    #[serde(default)]
+    pub instruments: Vec<String>,
+    /// Restart from the beginning when the data is exhausted
+    #[serde(default)]
+    pub loop_replay: bool,
 }
 
-fn default_log_level() -> String {
-    "info".to_string()
+fn default_replay_dataset() -> String {
+    "tickdata".to_string()
 }
 
-impl Default for SubscriptionConfig {
+fn default_replay_speed() -> String {
+    "1x".to_string()
+}
+
+/// Synthetic market data for running the gateway without an upstream account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockConfig {
+    /// Enable the mock source (upstream brokers are not connected)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Instruments to generate ticks for
+    #[serde(default)]
+    pub instruments: Vec<String>,
+    /// Ticks per second generated for each instrument
+    #[serde(default = "default_mock_ticks_per_sec")]
+    pub ticks_per_sec: f64,
+    /// Random seed, the same seed always produces the same price paths
+    #[serde(default = "default_mock_seed")]
+    pub seed: u64,
+    /// First price of every instrument
+    #[serde(default = "default_mock_start_price")]
+    pub start_price: f64,
+    /// Price tick of the random walk
+    #[serde(default = "default_mock_price_tick")]
+    pub price_tick: f64,
+    /// Largest move of one tick, in price ticks
+    #[serde(default = "default_mock_max_step")]
+    pub max_step: u32,
+    /// First open interest of every instrument
+    #[serde(default = "default_mock_open_interest")]
+    pub open_interest: f64,
+}
+
+fn default_mock_ticks_per_sec() -> f64 {
+    2.0
+}
+
+fn default_mock_seed() -> u64 {
+    42
+}
+
+fn default_mock_start_price() -> f64 {
+    3500.0
+}
+
+fn default_mock_price_tick() -> f64 {
+    1.0
+}
+
+fn default_mock_max_step() -> u32 {
+    2
+}
+
+fn default_mock_open_interest() -> f64 {
+    100_000.0
+}
+
+impl Default for MockConfig {
     fn default() -> Self {
         Self {
-            default_instruments: vec![],
-            auto_subscribe_patterns: vec![],
+            enabled: false,
+            instruments: Vec::new(),
+            ticks_per_sec: default_mock_ticks_per_sec(),
+            seed: default_mock_seed(),
+            start_price: default_mock_start_price(),
+            price_tick: default_mock_price_tick(),
+            max_step: default_mock_max_step(),
+            open_interest: default_mock_open_interest(),
         }
     }
 }
 
-impl Config {
-    /// Load configuration from a file
-    pub fn from_file<P: AsRef<Path>>(path: P) -> GatewayResult<Self> {
-        let mut file = File::open(path).map_err(GatewayError::IoError)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .map_err(GatewayError::IoError)?;
+/// Multi-source failover settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailoverConfig {
+    /// Enable switching instruments to a backup source when the primary goes quiet
+    #[serde(default)]
+    pub enabled: bool,
+    /// Seconds without ticks before a source is considered stale
+    #[serde(default = "default_stale_secs")]
+    pub stale_secs: u64,
+    /// Sources in order of preference, the first one is the primary
+    #[serde(default = "default_source_priority")]
+    pub priority: Vec<MarketDataSource>,
+}
 
-        let config: Config = serde_json::from_str(&contents).map_err(|e| {
-            GatewayError::ConfigError(format!("Failed to parse config file: {}", e))
-        })?;
-        Ok(config)
+fn default_stale_secs() -> u64 {
+    10
+}
+
+fn default_source_priority() -> Vec<MarketDataSource> {
+    vec![
+        MarketDataSource::CTP,
+        MarketDataSource::QQ,
+        MarketDataSource::Sina,
+        MarketDataSource::SinaHttp,
+    ]
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stale_secs: default_stale_secs(),
+            priority: default_source_priority(),
+        }
     }
+}
 
-    /// Get broker configuration by name
-    pub fn get_broker(&self, broker_name: Option<&str>) -> GatewayResult<&BrokerConfig> {
-        let broker_name = broker_name.unwrap_or(&self.default_broker);
-        self.brokers.get(broker_name).ok_or_else(|| {
-            GatewayError::ConfigError(format!("Broker config not found: {}", broker_name))
-        })
+/// Redis pub/sub bridge settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedisConfig {
+    /// Enable the bridge (requires the `redis-bridge` feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Redis connection URL
+    #[serde(default = "default_redis_url")]
+    pub url: String,
+    /// Channel prefix, snapshots are published to `{prefix}:{exchange}:{instrument}`
+    #[serde(default = "default_redis_channel_prefix")]
+    pub channel_prefix: String,
+    /// Hash holding the latest snapshot of every instrument
+    #[serde(default = "default_redis_snapshot_key")]
+    pub snapshot_key: String,
+}
+
+fn default_redis_url() -> String {
+    "redis://127.0.0.1:6379".to_string()
+}
+
+fn default_redis_channel_prefix() -> String {
+    "md".to_string()
+}
+
+fn default_redis_snapshot_key() -> String {
+    "md:snapshot".to_string()
+}
+
+/// Payload encoding of the ZeroMQ publisher
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZmqFormat {
+    /// JSON text
+    #[default]
+    Json,
+    /// MessagePack with field names
+    Msgpack,
+}
+
+/// ZeroMQ PUB socket output settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZmqConfig {
+    /// Enable the publisher (requires the `zmq-pub` feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Endpoint the PUB socket binds to
+    #[serde(default = "default_zmq_bind")]
+    pub bind: String,
+    /// Payload encoding
+    #[serde(default)]
+    pub format: ZmqFormat,
+    /// Send high water mark, messages beyond it are dropped for slow subscribers
+    #[serde(default = "default_zmq_send_hwm")]
+    pub send_hwm: i32,
+    /// Also publish trades between snapshots under `tick.<instrument>` topics
+    #[serde(default)]
+    pub ticks: bool,
+}
+
+fn default_zmq_bind() -> String {
+    "tcp://*:5556".to_string()
+}
+
+fn default_zmq_send_hwm() -> i32 {
+    10_000
+}
+
+/// Local IPC transport settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IpcConfig {
+    /// Enable the transport
+    #[serde(default)]
+    pub enabled: bool,
+    /// Unix domain socket path, or the named pipe (`\\.\pipe\<name>`) on Windows
+    #[serde(default = "default_ipc_path")]
+    pub path: String,
+    /// Frames queued per consumer, further snapshots are dropped for a consumer that falls behind
+    #[serde(default = "default_ipc_buffer")]
+    pub buffer: usize,
+}
+
+#[cfg(windows)]
+fn default_ipc_path() -> String {
+    r"\\.\pipe\qamdgateway".to_string()
+}
+
+#[cfg(not(windows))]
+fn default_ipc_path() -> String {
+    "/tmp/qamdgateway.sock".to_string()
+}
+
+fn default_ipc_buffer() -> usize {
+    4096
+}
+
+/// Socket.IO compatible endpoint for legacy web clients
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SocketIoConfig {
+    /// Enable the endpoint (requires the `socketio` feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Path of the endpoint, the Socket.IO client default is "/socket.io/"
+    #[serde(default = "default_socketio_path")]
+    pub path: String,
+    /// Event name of the pushed quotes
+    #[serde(default = "default_socketio_event")]
+    pub event: String,
+    /// Engine.IO ping interval
+    #[serde(default = "default_socketio_ping_interval_ms")]
+    pub ping_interval_ms: u64,
+    /// Time to wait for the pong before the connection is closed
+    #[serde(default = "default_socketio_ping_timeout_ms")]
+    pub ping_timeout_ms: u64,
+}
+
+fn default_socketio_path() -> String {
+    "/socket.io/".to_string()
+}
+
+fn default_socketio_event() -> String {
+    "quote".to_string()
+}
+
+fn default_socketio_ping_interval_ms() -> u64 {
+    25_000
+}
+
+fn default_socketio_ping_timeout_ms() -> u64 {
+    20_000
+}
+
+/// Trading calendar settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CalendarConfig {
+    /// Exchange holidays on weekdays ("YYYY-MM-DD" or "YYYYMMDD"), merged with the built-in
+    /// holidays up to 2024-12-31
+    #[serde(default)]
+    pub holidays: Vec<String>,
+    /// File with one holiday per line, merged with `holidays`
+    #[serde(default)]
+    pub holidays_file: Option<String>,
+    /// Skip upstream reconnect attempts outside futures trading hours
+    #[serde(default)]
+    pub suppress_reconnect: bool,
+    /// Minutes before a session opens from which reconnects are allowed again
+    #[serde(default = "default_reconnect_lead_minutes")]
+    pub reconnect_lead_minutes: i64,
+}
+
+fn default_reconnect_lead_minutes() -> i64 {
+    15
+}
+
+/// Subscriptions opened ahead of the session open so the first client gets data immediately
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupConfig {
+    /// Enable the warm-up scheduler
+    #[serde(default)]
+    pub enabled: bool,
+    /// Minutes before a session opens to subscribe
+    #[serde(default = "default_warmup_lead_minutes")]
+    pub lead_minutes: i64,
+    /// Minutes after a session closes to keep the subscription
+    #[serde(default)]
+    pub linger_minutes: i64,
+    /// Seconds between schedule checks
+    #[serde(default = "default_warmup_check_interval_secs")]
+    pub check_interval_secs: u64,
+    /// Instrument universes and their sessions
+    #[serde(default)]
+    pub groups: Vec<WarmupGroup>,
+}
+
+fn default_warmup_lead_minutes() -> i64 {
+    5
+}
+
+fn default_warmup_check_interval_secs() -> u64 {
+    30
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lead_minutes: default_warmup_lead_minutes(),
+            linger_minutes: 0,
+            check_interval_secs: default_warmup_check_interval_secs(),
+            groups: Vec::new(),
+        }
+    }
+}
+
+/// An instrument universe warmed up around its sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupGroup {
+    /// Group name used in logs
+    pub name: String,
+    /// Instruments or instrument groups (e.g. "ALL.SHFE")
+    pub instruments: Vec<String>,
+    /// Use the built-in sessions of this exchange
+    #[serde(default)]
+    pub exchange: Option<String>,
+    /// Session definitions such as "mon-fri 21:00-02:30", used instead of the exchange sessions
+    #[serde(default)]
+    pub sessions: Vec<String>,
+}
+
+/// Role of this instance in an active/standby pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicationRole {
+    /// Serves clients and streams its state to the standby
+    Primary,
+    /// Mirrors the primary and takes over when it stops responding
+    Standby,
+}
+
+/// Active/standby replication settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    /// Enable replication
+    #[serde(default)]
+    pub enabled: bool,
+    /// Role of this instance
+    pub role: ReplicationRole,
+    /// WebSocket path the primary serves replication on
+    #[serde(default = "default_replication_path")]
+    pub path: String,
+    /// Replication URL of the primary, used by the standby (e.g. "ws://10.0.0.1:8081/replication")
+    #[serde(default)]
+    pub peer_url: Option<String>,
+    /// Seconds between heartbeats sent by the primary
+    #[serde(default = "default_replication_heartbeat_secs")]
+    pub heartbeat_secs: u64,
+    /// Seconds between full subscription state updates sent by the primary
+    #[serde(default = "default_replication_state_secs")]
+    pub state_secs: u64,
+    /// Seconds without hearing from the primary before the standby takes over
+    #[serde(default = "default_replication_takeover_secs")]
+    pub takeover_secs: u64,
+}
+
+fn default_replication_path() -> String {
+    "/replication".to_string()
+}
+
+fn default_replication_heartbeat_secs() -> u64 {
+    1
+}
+
+fn default_replication_state_secs() -> u64 {
+    5
+}
+
+fn default_replication_takeover_secs() -> u64 {
+    10
+}
+
+/// Option analytics settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionsConfig {
+    /// Attach implied volatility and greeks to option quotes
+    #[serde(default)]
+    pub enabled: bool,
+    /// Annualized risk-free rate used for pricing
+    #[serde(default = "default_risk_free_rate")]
+    pub risk_free_rate: f64,
+}
+
+fn default_risk_free_rate() -> f64 {
+    0.02
+}
+
+impl Default for OptionsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            risk_free_rate: default_risk_free_rate(),
+        }
+    }
+}
+
+/// Intraday tick recording settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecorderConfig {
+    /// Keep recent ticks in memory for `/api/md/export`
+    #[serde(default)]
+    pub enabled: bool,
+    /// Maximum number of ticks kept per instrument
+    #[serde(default = "default_recorder_max_ticks")]
+    pub max_ticks_per_instrument: usize,
+    /// Also write every recorded tick to JSON Lines files in this directory,
+    /// starting a new file for every trading day
+    #[serde(default)]
+    pub dir: Option<String>,
+    /// Start a new file once the current one exceeds this size (0 disables size rotation)
+    #[serde(default = "default_recorder_max_file_mb")]
+    pub max_file_mb: u64,
+    /// Start a new file after this many minutes (0 disables time rotation)
+    #[serde(default)]
+    pub rotate_minutes: u64,
+    /// Compress finished files with zstd
+    #[serde(default = "default_recorder_compress")]
+    pub compress: bool,
+    /// zstd compression level
+    #[serde(default = "default_recorder_compression_level")]
+    pub compression_level: i32,
+    /// Days of files kept, files of older trading days are deleted (0 keeps all)
+    #[serde(default)]
+    pub retention_days: u32,
+}
+
+fn default_recorder_max_ticks() -> usize {
+    50_000
+}
+
+fn default_recorder_max_file_mb() -> u64 {
+    512
+}
+
+fn default_recorder_compress() -> bool {
+    true
+}
+
+fn default_recorder_compression_level() -> i32 {
+    3
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_ticks_per_instrument: default_recorder_max_ticks(),
+            dir: None,
+            max_file_mb: default_recorder_max_file_mb(),
+            rotate_minutes: 0,
+            compress: default_recorder_compress(),
+            compression_level: default_recorder_compression_level(),
+            retention_days: 0,
+        }
+    }
+}
+
+/// End-of-day daily bars built from the tick recorder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EodConfig {
+    /// Build daily bars after the close on every trading day (requires `recorder.enabled`)
+    #[serde(default)]
+    pub enabled: bool,
+    /// QALfs base directory; bars are written to `futureday/future_day_{date}.pq`
+    /// and `bfqdata/stock_day_bfq_{date}.pq` below it
+    #[serde(default = "default_eod_path")]
+    pub path: String,
+    /// Beijing time (HH:MM) after which the day's bars are built
+    #[serde(default = "default_eod_time")]
+    pub time: String,
+}
+
+fn default_eod_path() -> String {
+    "/opt/cache/data".to_string()
+}
+
+fn default_eod_time() -> String {
+    "15:30".to_string()
+}
+
+impl Default for EodConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_eod_path(),
+            time: default_eod_time(),
+        }
+    }
+}
+
+/// Recent tick history served to charting clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Keep recent ticks in memory for `/api/md/history/{instrument_id}`
+    #[serde(default)]
+    pub enabled: bool,
+    /// Minutes of ticks kept per instrument
+    #[serde(default = "default_history_retention_minutes")]
+    pub retention_minutes: u64,
+    /// Upper bound of ticks kept per instrument regardless of the retention
+    #[serde(default = "default_history_max_ticks")]
+    pub max_ticks_per_instrument: usize,
+}
+
+fn default_history_retention_minutes() -> u64 {
+    10
+}
+
+fn default_history_max_ticks() -> usize {
+    20_000
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention_minutes: default_history_retention_minutes(),
+            max_ticks_per_instrument: default_history_max_ticks(),
+        }
+    }
+}
+
+/// Market data distributor settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistributorConfig {
+    /// Number of fan-out shards pushing updates to clients, each on its own
+    /// arbiter thread; 1 keeps fan-out on the distributor actor
+    #[serde(default = "default_fanout_shards")]
+    pub fanout_shards: usize,
+    /// Seconds between full refresh frames of an instrument for clients in
+    /// patch mode; 0 sends every frame in full
+    #[serde(default = "default_patch_refresh_secs")]
+    pub patch_refresh_secs: u64,
+    /// Seconds an instrument stays subscribed upstream after its last client
+    /// unsubscribes, so a quick resubscription skips the upstream round trip;
+    /// 0 unsubscribes immediately
+    #[serde(default = "default_unsubscribe_linger_secs")]
+    pub unsubscribe_linger_secs: u64,
+    /// Seconds the last snapshot of an instrument unsubscribed upstream is kept
+    /// and sent to the next subscriber; unset keeps it until restart
+    #[serde(default)]
+    pub cache_retention_secs: Option<u64>,
+    /// Recent ticks kept per instrument and replayed to clients subscribing
+    /// with a `depth`; 0 disables the replay
+    #[serde(default)]
+    pub replay_depth: usize,
+}
+
+fn default_fanout_shards() -> usize {
+    1
+}
+
+fn default_patch_refresh_secs() -> u64 {
+    30
+}
+
+fn default_unsubscribe_linger_secs() -> u64 {
+    10
+}
+
+impl Default for DistributorConfig {
+    fn default() -> Self {
+        Self {
+            fanout_shards: default_fanout_shards(),
+            patch_refresh_secs: default_patch_refresh_secs(),
+            unsubscribe_linger_secs: default_unsubscribe_linger_secs(),
+            cache_retention_secs: None,
+            replay_depth: 0,
+        }
+    }
+}
+
+/// Flow file directories of the market data APIs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowConfig {
+    /// Root of the per-broker flow directories
+    #[serde(default = "default_flow_root")]
+    pub root: String,
+    /// Stale `.con` files are removed oldest first when a broker connects while its
+    /// flow directory is larger than this many megabytes (0 disables the cleanup)
+    #[serde(default = "default_flow_max_size_mb")]
+    pub max_size_mb: u64,
+}
+
+fn default_flow_root() -> String {
+    "./flow/md".to_string()
+}
+
+fn default_flow_max_size_mb() -> u64 {
+    64
+}
+
+impl Default for FlowConfig {
+    fn default() -> Self {
+        Self {
+            root: default_flow_root(),
+            max_size_mb: default_flow_max_size_mb(),
+        }
+    }
+}
+
+impl FlowConfig {
+    /// Flow directory of a broker, its own `flow_dir` or one derived from the source and name
+    pub fn broker_dir(&self, broker: &BrokerConfig, source: MarketDataSource) -> PathBuf {
+        if let Some(flow_dir) = &broker.flow_dir {
+            return PathBuf::from(flow_dir);
+        }
+        let name: String = broker
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        Path::new(&self.root)
+            .join(format!("{:?}", source).to_ascii_lowercase())
+            .join(name)
+    }
+}
+
+/// Per-source clock skew estimation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockSkewConfig {
+    /// Number of recent ticks per source the skew is estimated from
+    #[serde(default = "default_skew_window")]
+    pub window: usize,
+    /// Samples required before a skew is reported or applied
+    #[serde(default = "default_skew_min_samples")]
+    pub min_samples: usize,
+    /// Offsets beyond this many milliseconds are stale snapshots rather than skew
+    #[serde(default = "default_max_skew_ms")]
+    pub max_skew_ms: i64,
+    /// Sources whose snapshot datetimes are shifted by their estimated skew
+    #[serde(default)]
+    pub correct: Vec<MarketDataSource>,
+}
+
+fn default_skew_window() -> usize {
+    500
+}
+
+fn default_skew_min_samples() -> usize {
+    20
+}
+
+fn default_max_skew_ms() -> i64 {
+    60_000
+}
+
+impl Default for ClockSkewConfig {
+    fn default() -> Self {
+        Self {
+            window: default_skew_window(),
+            min_samples: default_skew_min_samples(),
+            max_skew_ms: default_max_skew_ms(),
+            correct: Vec::new(),
+        }
+    }
+}
+
+/// What the distributor does with a quote whose prices fail validation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceCheckMode {
+    /// Publish the quote unchanged and count the violation
+    #[default]
+    Flag,
+    /// Round prices to the price tick and clamp them to the limit prices
+    Correct,
+    /// Drop the quote
+    Drop,
+}
+
+/// Validation of outbound prices against the price tick and the limit prices
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceCheckConfig {
+    /// Check the prices of every quote before it is published
+    #[serde(default)]
+    pub enabled: bool,
+    /// Handling of quotes with prices off the price tick or outside the limits
+    #[serde(default)]
+    pub mode: PriceCheckMode,
+    /// Price tick by exchange (e.g. `"SSE": 0.01`) for instruments missing from
+    /// the instrument reference data
+    #[serde(default)]
+    pub exchange_ticks: HashMap<String, f64>,
+}
+
+/// Write-ahead journal of raw upstream messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalConfig {
+    /// Append every raw upstream message to the journal before conversion
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory of the journal files
+    #[serde(default = "default_journal_dir")]
+    pub dir: String,
+    /// Start a new file once the current one reaches this size (0 never rotates)
+    #[serde(default = "default_journal_max_file_mb")]
+    pub max_file_mb: u64,
+    /// Journal files kept, the oldest are deleted on rotation (0 keeps all)
+    #[serde(default = "default_journal_max_files")]
+    pub max_files: usize,
+}
+
+fn default_journal_dir() -> String {
+    "./journal".to_string()
+}
+
+fn default_journal_max_file_mb() -> u64 {
+    256
+}
+
+fn default_journal_max_files() -> usize {
+    20
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_journal_dir(),
+            max_file_mb: default_journal_max_file_mb(),
+            max_files: default_journal_max_files(),
+        }
+    }
+}
+
+/// Per-hop latency tracing of CTP market data
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyConfig {
+    /// Stamp each snapshot at the SPI callback, distributor dispatch and client
+    /// serialization and collect per-hop histograms
+    #[serde(default)]
+    pub enabled: bool,
+    /// Keep the `lat` timestamps in the messages sent to clients
+    #[serde(default)]
+    pub embed: bool,
+}
+
+/// Market data quality monitoring
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataQualityConfig {
+    /// Report a gap when consecutive ticks during trading hours are further apart
+    #[serde(default = "default_quality_max_gap_secs")]
+    pub max_gap_secs: u64,
+    /// Report an instrument as stale after this long without ticks during trading hours
+    #[serde(default = "default_quality_stale_secs")]
+    pub stale_secs: u64,
+    /// How often instruments are checked for staleness
+    #[serde(default = "default_quality_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+fn default_quality_max_gap_secs() -> u64 {
+    30
+}
+
+fn default_quality_stale_secs() -> u64 {
+    60
+}
+
+fn default_quality_check_interval_secs() -> u64 {
+    5
+}
+
+impl Default for DataQualityConfig {
+    fn default() -> Self {
+        Self {
+            max_gap_secs: default_quality_max_gap_secs(),
+            stale_secs: default_quality_stale_secs(),
+            check_interval_secs: default_quality_check_interval_secs(),
+        }
+    }
+}
+
+/// Per-exchange trading phases derived from the calendar and quote activity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradingPhaseConfig {
+    /// Minutes of pre-open before the call auction of an opening session
+    #[serde(default = "default_pre_open_minutes")]
+    pub pre_open_minutes: i64,
+    /// Minutes of call auction before a futures opening session
+    #[serde(default = "default_auction_minutes")]
+    pub auction_minutes: i64,
+    /// Treat an exchange as trading for this long after a tick with new volume outside its sessions
+    #[serde(default = "default_phase_activity_secs")]
+    pub activity_secs: u64,
+    /// How often the phases are re-evaluated
+    #[serde(default = "default_phase_check_interval_secs")]
+    pub check_interval_secs: u64,
+    /// How long after the last day session of a futures exchange closes to wait for
+    /// settlement prices before ending its trading day; 0 ends it at the close
+    #[serde(default = "default_settlement_wait_secs")]
+    pub settlement_wait_secs: u64,
+}
+
+fn default_pre_open_minutes() -> i64 {
+    15
+}
+
+fn default_auction_minutes() -> i64 {
+    5
+}
+
+fn default_phase_activity_secs() -> u64 {
+    60
+}
+
+fn default_phase_check_interval_secs() -> u64 {
+    1
+}
+
+fn default_settlement_wait_secs() -> u64 {
+    1800
+}
+
+impl Default for TradingPhaseConfig {
+    fn default() -> Self {
+        Self {
+            pre_open_minutes: default_pre_open_minutes(),
+            auction_minutes: default_auction_minutes(),
+            activity_secs: default_phase_activity_secs(),
+            check_interval_secs: default_phase_check_interval_secs(),
+            settlement_wait_secs: default_settlement_wait_secs(),
+        }
+    }
+}
+
+/// Spread upstream subscriptions over several accounts of the same source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoadSharingConfig {
+    /// Subscribe each instrument on a single account chosen by consistent hashing
+    #[serde(default)]
+    pub enabled: bool,
+    /// Broker IDs sharing the load (defaults to every CTP broker)
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Points per account on the hash ring
+    #[serde(default = "default_virtual_nodes")]
+    pub virtual_nodes: usize,
+    /// How long an account may stay logged out before its instruments move to the others
+    #[serde(default = "default_account_down_grace_secs")]
+    pub down_grace_secs: u64,
+    /// How often the accounts' login state is checked
+    #[serde(default = "default_account_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+fn default_virtual_nodes() -> usize {
+    160
+}
+
+fn default_account_down_grace_secs() -> u64 {
+    30
+}
+
+fn default_account_check_interval_secs() -> u64 {
+    5
+}
+
+/// Equity symbol table behind `GET /api/symbols`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolsConfig {
+    /// CSV file (`code,exchange,name,abbr,kind`) added to the bundled table
+    #[serde(default)]
+    pub file: Option<String>,
+    /// CSV symbol table downloaded at startup and added to the table
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Seconds between downloads of `url`, 0 downloads it only once
+    #[serde(default = "default_symbols_refresh_secs")]
+    pub refresh_secs: u64,
+    /// Download timeout in milliseconds
+    #[serde(default = "default_symbols_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_symbols_refresh_secs() -> u64 {
+    86400
+}
+
+fn default_symbols_timeout_ms() -> u64 {
+    10000
+}
+
+impl Default for SymbolsConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            url: None,
+            refresh_secs: default_symbols_refresh_secs(),
+            timeout_ms: default_symbols_timeout_ms(),
+        }
+    }
+}
+
+/// Restart policy of upstream sources that crashed with a panic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupervisionConfig {
+    /// Restarts allowed per broker within `window_secs` before the broker is given up
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: usize,
+    /// Window over which restarts are counted
+    #[serde(default = "default_restart_window_secs")]
+    pub window_secs: u64,
+    /// Delay before the first restart, doubled for each further restart within the window
+    #[serde(default = "default_restart_delay_ms")]
+    pub restart_delay_ms: u64,
+    /// Upper bound of the restart delay
+    #[serde(default = "default_max_restart_delay_ms")]
+    pub max_restart_delay_ms: u64,
+}
+
+fn default_max_restarts() -> usize {
+    5
+}
+
+fn default_restart_window_secs() -> u64 {
+    600
+}
+
+fn default_restart_delay_ms() -> u64 {
+    1000
+}
+
+fn default_max_restart_delay_ms() -> u64 {
+    30000
+}
+
+impl Default for SupervisionConfig {
+    fn default() -> Self {
+        Self {
+            max_restarts: default_max_restarts(),
+            window_secs: default_restart_window_secs(),
+            restart_delay_ms: default_restart_delay_ms(),
+            max_restart_delay_ms: default_max_restart_delay_ms(),
+        }
+    }
+}
+
+/// Log line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines prefixed with the enclosing spans
+    #[default]
+    Text,
+    /// One JSON object per line including the fields of the enclosing spans
+    Json,
+}
+
+/// Log output and tick-level log sampling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Log line format
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Log one in this many ticks of each instrument at debug level, 0 disables tick logs
+    #[serde(default = "default_tick_sample_rate")]
+    pub tick_sample_rate: u64,
+}
+
+fn default_tick_sample_rate() -> u64 {
+    1000
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            tick_sample_rate: default_tick_sample_rate(),
+        }
+    }
+}
+
+/// Admin WebSocket channel for gateway introspection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Bearer token required by the admin channel, which is disabled when unset
+    #[serde(default)]
+    pub token: Option<String>,
+    /// WebSocket path of the admin channel
+    #[serde(default = "default_admin_path")]
+    pub path: String,
+    /// Interval between status pushes
+    #[serde(default = "default_admin_status_interval_secs")]
+    pub status_interval_secs: u64,
+    /// Number of instruments listed by tick rate in each status push
+    #[serde(default = "default_admin_top_instruments")]
+    pub top_instruments: usize,
+}
+
+fn default_admin_path() -> String {
+    "/ws/admin".to_string()
+}
+
+fn default_admin_status_interval_secs() -> u64 {
+    5
+}
+
+fn default_admin_top_instruments() -> usize {
+    10
+}
+
+impl AdminConfig {
+    /// The configured token, None when the admin channel is disabled
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref().filter(|token| !token.is_empty())
+    }
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            path: default_admin_path(),
+            status_interval_secs: default_admin_status_interval_secs(),
+            top_instruments: default_admin_top_instruments(),
+        }
+    }
+}
+
+/// Per-token instrument permissions of WebSocket clients
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AclConfig {
+    /// Restrict subscriptions and market data to the scopes of the client's token
+    #[serde(default)]
+    pub enabled: bool,
+    /// File storing the tokens and their scopes (kept in memory only if unset)
+    #[serde(default)]
+    pub tokens_file: Option<String>,
+    /// Scopes of clients connecting without a token (none by default)
+    #[serde(default)]
+    pub anonymous_scopes: Vec<String>,
+}
+
+/// Mapping of continuous contracts (`SHFE.rb.HOT`, `SHFE.rb888`) to the dominant contract month
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DominantConfig {
+    /// Switch to a later month once its open interest exceeds the current one's by this factor
+    #[serde(default = "default_dominant_switch_ratio")]
+    pub switch_ratio: f64,
+    /// Seconds to collect open interest of every month before the first mapping
+    #[serde(default = "default_dominant_warmup_secs")]
+    pub warmup_secs: u64,
+    /// Contract months per product (e.g. `"SHFE.rb": ["SHFE.rb2410", "SHFE.rb2501"]`) in
+    /// delivery order, used instead of the instruments file
+    #[serde(default)]
+    pub contracts: HashMap<String, Vec<String>>,
+}
+
+fn default_dominant_switch_ratio() -> f64 {
+    1.1
+}
+
+fn default_dominant_warmup_secs() -> u64 {
+    5
+}
+
+impl Default for DominantConfig {
+    fn default() -> Self {
+        Self {
+            switch_ratio: default_dominant_switch_ratio(),
+            warmup_secs: default_dominant_warmup_secs(),
+            contracts: HashMap::new(),
+        }
+    }
+}
+
+/// Login to a CTP trader front that is only used for queries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraderLoginConfig {
+    /// Trader front address (not the market data front)
+    pub front_addr: String,
+    /// Broker ID
+    #[serde(default)]
+    pub broker_id: String,
+    /// User ID
+    #[serde(default)]
+    pub user_id: String,
+    /// Password
+    #[serde(default)]
+    pub password: String,
+    /// App ID, authentication is skipped when empty
+    #[serde(default)]
+    pub app_id: String,
+    /// Auth code
+    #[serde(default)]
+    pub auth_code: String,
+}
+
+/// Instrument reference data queried from a CTP trader front at startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstrumentQueryConfig {
+    /// Query instruments on startup (requires the `ctp-instruments` feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Trader front and account
+    #[serde(flatten)]
+    pub login: TraderLoginConfig,
+    /// Directory for the trader API flow files
+    #[serde(default = "default_instrument_query_flow_path")]
+    pub flow_path: String,
+    /// Seconds to wait for the query to complete
+    #[serde(default = "default_instrument_query_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_instrument_query_flow_path() -> String {
+    "./flow/instruments/".to_string()
+}
+
+fn default_instrument_query_timeout_secs() -> u64 {
+    60
+}
+
+/// Margin and commission rates of the account, queried from a CTP trader front
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraderQueryConfig {
+    /// Query rates in the background (requires the `ctp-trader-query` feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Trader front and account
+    #[serde(flatten)]
+    pub login: TraderLoginConfig,
+    /// Directory for the trader API flow files
+    #[serde(default = "default_trader_query_flow_path")]
+    pub flow_path: String,
+    /// Instruments or patterns (e.g. `SHFE.*`, `au*`) whose rates are queried, all futures when empty
+    #[serde(default)]
+    pub instruments: Vec<String>,
+    /// Milliseconds between two requests, CTP accepts about one query per second
+    #[serde(default = "default_trader_query_interval_ms")]
+    pub query_interval_ms: u64,
+    /// Seconds between two rounds of queries (0 queries once)
+    #[serde(default = "default_trader_query_refresh_secs")]
+    pub refresh_secs: u64,
+    /// Seconds to wait for a response before the round is abandoned
+    #[serde(default = "default_trader_query_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_trader_query_flow_path() -> String {
+    "./flow/trader_query/".to_string()
+}
+
+fn default_trader_query_interval_ms() -> u64 {
+    1100
+}
+
+fn default_trader_query_refresh_secs() -> u64 {
+    86400
+}
+
+fn default_trader_query_timeout_secs() -> u64 {
+    30
+}
+
+/// Upstream resubscription settings after a front reconnect
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResubscribeConfig {
+    /// Instruments per subscribe request
+    #[serde(default = "default_resubscribe_batch_size")]
+    pub batch_size: usize,
+    /// Delay between successful batches in milliseconds
+    #[serde(default = "default_resubscribe_batch_interval_ms")]
+    pub batch_interval_ms: u64,
+    /// Random extra delay added to every wait, up to this many milliseconds
+    #[serde(default = "default_resubscribe_jitter_ms")]
+    pub jitter_ms: u64,
+    /// First retry delay after a rejected batch in milliseconds, doubled on every failure
+    #[serde(default = "default_resubscribe_backoff_initial_ms")]
+    pub backoff_initial_ms: u64,
+    /// Upper bound of the retry delay in milliseconds
+    #[serde(default = "default_resubscribe_backoff_max_ms")]
+    pub backoff_max_ms: u64,
+}
+
+fn default_resubscribe_batch_size() -> usize {
+    100
+}
+
+fn default_resubscribe_batch_interval_ms() -> u64 {
+    1000
+}
+
+fn default_resubscribe_jitter_ms() -> u64 {
+    500
+}
+
+fn default_resubscribe_backoff_initial_ms() -> u64 {
+    1000
+}
+
+fn default_resubscribe_backoff_max_ms() -> u64 {
+    60_000
+}
+
+/// Reconnect circuit breaker of the upstream fronts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failed connects after which a front's circuit opens
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub failure_threshold: u32,
+    /// First reconnect delay after a failed connect in milliseconds, doubled on every failure
+    #[serde(default = "default_circuit_backoff_initial_ms")]
+    pub backoff_initial_ms: u64,
+    /// Upper bound of the reconnect delay in milliseconds
+    #[serde(default = "default_circuit_backoff_max_ms")]
+    pub backoff_max_ms: u64,
+    /// Seconds an open circuit waits before a half-open probe
+    #[serde(default = "default_circuit_open_secs")]
+    pub open_secs: u64,
+    /// Connects allowed per front and hour (0 for unlimited)
+    #[serde(default = "default_circuit_max_attempts_per_hour")]
+    pub max_attempts_per_hour: u32,
+    /// Seconds to wait for a connect before counting it as failed
+    #[serde(default = "default_circuit_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    3
+}
+
+fn default_circuit_backoff_initial_ms() -> u64 {
+    1000
+}
+
+fn default_circuit_backoff_max_ms() -> u64 {
+    60_000
+}
+
+fn default_circuit_open_secs() -> u64 {
+    300
+}
+
+fn default_circuit_max_attempts_per_hour() -> u32 {
+    60
+}
+
+fn default_circuit_connect_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_circuit_failure_threshold(),
+            backoff_initial_ms: default_circuit_backoff_initial_ms(),
+            backoff_max_ms: default_circuit_backoff_max_ms(),
+            open_secs: default_circuit_open_secs(),
+            max_attempts_per_hour: default_circuit_max_attempts_per_hour(),
+            connect_timeout_secs: default_circuit_connect_timeout_secs(),
+        }
+    }
+}
+
+impl Default for ResubscribeConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: default_resubscribe_batch_size(),
+            batch_interval_ms: default_resubscribe_batch_interval_ms(),
+            jitter_ms: default_resubscribe_jitter_ms(),
+            backoff_initial_ms: default_resubscribe_backoff_initial_ms(),
+            backoff_max_ms: default_resubscribe_backoff_max_ms(),
+        }
+    }
+}
+
+/// Market data conversion settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConverterConfig {
+    /// Round upstream prices to the price tick from `subscription.instruments_file`
+    #[serde(default)]
+    pub round_to_price_tick: bool,
+    /// Publish partial snapshots from truncated or GBK-encoded quotes (seen on the
+    /// QQ source) instead of dropping them
+    #[serde(default)]
+    pub tolerant_parsing: bool,
+    /// JSON lines file receiving the raw fields of quotes that failed to parse cleanly
+    #[serde(default)]
+    pub dead_letter_file: Option<String>,
+}
+
+/// Sina HTTP quote polling settings
+///
+/// A fallback for stock quotes when the Sina market data front is unreachable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SinaHttpConfig {
+    /// Poll the HTTP quote API
+    #[serde(default)]
+    pub enabled: bool,
+    /// Quote API endpoint, the comma separated codes are appended
+    #[serde(default = "default_sina_http_url")]
+    pub url: String,
+    /// Polling interval in milliseconds
+    #[serde(default = "default_sina_http_interval_ms")]
+    pub interval_ms: u64,
+    /// Maximum number of codes per request
+    #[serde(default = "default_sina_http_batch_size")]
+    pub batch_size: usize,
+    /// Request timeout in milliseconds
+    #[serde(default = "default_sina_http_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Instruments polled in addition to the active subscriptions
+    #[serde(default)]
+    pub instruments: Vec<String>,
+}
+
+fn default_sina_http_url() -> String {
+    "http://hq.sinajs.cn/list=".to_string()
+}
+
+fn default_sina_http_interval_ms() -> u64 {
+    3000
+}
+
+fn default_sina_http_batch_size() -> usize {
+    200
+}
+
+fn default_sina_http_timeout_ms() -> u64 {
+    5000
+}
+
+impl Default for SinaHttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: default_sina_http_url(),
+            interval_ms: default_sina_http_interval_ms(),
+            batch_size: default_sina_http_batch_size(),
+            timeout_ms: default_sina_http_timeout_ms(),
+            instruments: vec![],
+        }
+    }
+}
+
+/// Binance spot L1 feed settings
+///
+/// Streams `bookTicker` and `trade` for instruments prefixed with `exchange`
+/// (e.g. `BINANCE.BTCUSDT`) over a single websocket connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinanceConfig {
+    /// Connect to Binance
+    #[serde(default)]
+    pub enabled: bool,
+    /// Raw stream websocket endpoint
+    #[serde(default = "default_binance_url")]
+    pub url: String,
+    /// Exchange prefix of the instrument IDs routed to Binance
+    #[serde(default = "default_binance_exchange")]
+    pub exchange: String,
+    /// Snapshot volumes are integers, so quantities are multiplied by
+    /// 10^quantity_decimals (8 keeps 1 satoshi of BTC)
+    #[serde(default = "default_binance_quantity_decimals")]
+    pub quantity_decimals: u32,
+    /// Seconds to wait before reconnecting
+    #[serde(default = "default_binance_reconnect_secs")]
+    pub reconnect_secs: u64,
+    /// Reconnect when nothing, not even a ping, arrives for this many seconds
+    #[serde(default = "default_binance_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// Instruments streamed in addition to the client subscriptions
+    #[serde(default)]
+    pub instruments: Vec<String>,
+}
+
+fn default_binance_url() -> String {
+    "wss://stream.binance.com:9443/ws".to_string()
+}
+
+fn default_binance_exchange() -> String {
+    "BINANCE".to_string()
+}
+
+fn default_binance_quantity_decimals() -> u32 {
+    8
+}
+
+fn default_binance_reconnect_secs() -> u64 {
+    5
+}
+
+fn default_binance_idle_timeout_secs() -> u64 {
+    60
+}
+
+impl Default for BinanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: default_binance_url(),
+            exchange: default_binance_exchange(),
+            quantity_decimals: default_binance_quantity_decimals(),
+            reconnect_secs: default_binance_reconnect_secs(),
+            idle_timeout_secs: default_binance_idle_timeout_secs(),
+            instruments: vec![],
+        }
+    }
+}
+
+/// Gateway configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// List of available brokers
+    pub brokers: HashMap<String, BrokerConfig>,
+    /// Default broker to use
+    pub default_broker: String,
+    /// Brokers connected at startup, possibly of different sources
+    /// (defaults to `default_broker` alone)
+    #[serde(default)]
+    pub active_brokers: Vec<String>,
+    /// WebSocket server configuration
+    pub websocket: WebSocketConfig,
+    /// REST API configuration
+    pub rest_api: RestApiConfig,
+    /// Subscription settings
+    #[serde(default)]
+    pub subscription: SubscriptionConfig,
+    /// Log level, overridden by `RUST_LOG`
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Log format and tick-level log sampling
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Historical replay settings
+    #[serde(default)]
+    pub replay: Option<ReplayConfig>,
+    /// Synthetic random-walk market data
+    #[serde(default)]
+    pub mock: Option<MockConfig>,
+    /// Multi-source failover settings
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    /// Trading calendar settings
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+    /// Redis pub/sub bridge settings
+    #[serde(default)]
+    pub redis: Option<RedisConfig>,
+    /// Option analytics settings
+    #[serde(default)]
+    pub options: OptionsConfig,
+    /// Active/standby replication settings
+    #[serde(default)]
+    pub replication: Option<ReplicationConfig>,
+    /// Intraday tick recording settings
+    #[serde(default)]
+    pub recorder: RecorderConfig,
+    /// Recent tick history for chart backfill
+    #[serde(default)]
+    pub history: HistoryConfig,
+    /// End-of-day daily bar export
+    #[serde(default)]
+    pub eod: EodConfig,
+    /// ZeroMQ PUB socket output settings
+    #[serde(default)]
+    pub zmq: Option<ZmqConfig>,
+    /// Unix domain socket / named pipe output for local consumers
+    #[serde(default)]
+    pub ipc: Option<IpcConfig>,
+    /// Socket.IO compatible endpoint for legacy web clients
+    #[serde(default)]
+    pub socketio: Option<SocketIoConfig>,
+    /// Sina HTTP quote polling settings
+    #[serde(default)]
+    pub sina_http: Option<SinaHttpConfig>,
+    /// Binance spot L1 feed settings
+    #[serde(default)]
+    pub binance: Option<BinanceConfig>,
+    /// Market data conversion settings
+    #[serde(default)]
+    pub converter: ConverterConfig,
+    /// Upstream resubscription settings after a front reconnect
+    #[serde(default)]
+    pub resubscribe: ResubscribeConfig,
+    /// Reconnect backoff, retry budget and front rotation of the upstream connections
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Instrument reference data queried from a CTP trader front
+    #[serde(default)]
+    pub instrument_query: Option<InstrumentQueryConfig>,
+    /// Margin and commission rates queried from a CTP trader front
+    #[serde(default)]
+    pub trader_query: Option<TraderQueryConfig>,
+    /// Scheduled warm-up subscriptions around session opens
+    #[serde(default)]
+    pub warmup: WarmupConfig,
+    /// Distributor fan-out settings
+    #[serde(default)]
+    pub distributor: DistributorConfig,
+    /// Gap, staleness, price and crossed book checks
+    #[serde(default)]
+    pub data_quality: DataQualityConfig,
+    /// Per-exchange trading phase tracking
+    #[serde(default)]
+    pub trading_phase: TradingPhaseConfig,
+    /// Per-token instrument permissions
+    #[serde(default)]
+    pub acl: AclConfig,
+    /// Dominant contract mapping of continuous contracts
+    #[serde(default)]
+    pub dominant: DominantConfig,
+    /// Authenticated admin WebSocket channel
+    #[serde(default)]
+    pub admin: AdminConfig,
+    /// Per-source clock skew estimation and correction
+    #[serde(default)]
+    pub clock_skew: ClockSkewConfig,
+    /// Price tick and limit price validation of outbound quotes
+    #[serde(default)]
+    pub price_check: PriceCheckConfig,
+    /// Flow file directories of the market data APIs
+    #[serde(default)]
+    pub flow: FlowConfig,
+    /// Per-hop latency tracing
+    #[serde(default)]
+    pub latency: LatencyConfig,
+    /// Raw upstream message journal
+    #[serde(default)]
+    pub journal: JournalConfig,
+    /// Restart policy of crashed upstream sources
+    #[serde(default)]
+    pub supervision: SupervisionConfig,
+    /// Instrument sharding across several market data accounts
+    #[serde(default)]
+    pub load_sharing: LoadSharingConfig,
+    /// Equity symbol search table
+    #[serde(default)]
+    pub symbols: SymbolsConfig,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            default_instruments: vec![],
+            auto_subscribe_patterns: vec![],
+            instruments_file: None,
+            state_file: None,
+            restore_grace_secs: default_restore_grace_secs(),
+            max_pattern_matches: default_max_pattern_matches(),
+            watchlists_file: None,
+            subscription_file: None,
+            profiles_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load configuration from a file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> GatewayResult<Self> {
+        let mut file = File::open(path).map_err(GatewayError::IoError)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .map_err(GatewayError::IoError)?;
+
+        let config: Config = serde_json::from_str(&contents).map_err(|e| {
+            GatewayError::ConfigError(format!("Failed to parse config file: {}", e))
+        })?;
+        Ok(config)
+    }
+
+    /// Get broker configuration by name
+    pub fn get_broker(&self, broker_name: Option<&str>) -> GatewayResult<&BrokerConfig> {
+        let broker_name = broker_name.unwrap_or(&self.default_broker);
+        self.brokers.get(broker_name).ok_or_else(|| {
+            GatewayError::ConfigError(format!("Broker config not found: {}", broker_name))
+        })
+    }
+
+    /// Configurations of the brokers connected at startup
+    pub fn active_broker_configs(&self) -> GatewayResult<Vec<BrokerConfig>> {
+        if self.active_brokers.is_empty() {
+            return Ok(vec![self.get_broker(None)?.clone()]);
+        }
+        self.active_brokers
+            .iter()
+            .map(|name| self.get_broker(Some(name)).cloned())
+            .collect()
     }
 
     /// Load configuration from environment or file
@@ -1,248 +1,629 @@
-use chrono::{DateTime, Local, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use ctp_common::CThostFtdcDepthMarketDataField;
-use qamd_rs::{MDSnapshot, OptionalF64};
-use std::str::FromStr;
-use log::warn;
+use qamd_rs::constants::exchange;
+use qamd_rs::{exchange_of, parse_option_id, InstrumentClass, MDSnapshot, OptionalF64, TimestampNormalizer};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
 
+use crate::actors::messages::MarketDataSource;
 use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::{InstrumentInfo, InstrumentRegistry};
 
-/// Converts CTP market data to QAMD MDSnapshot
-pub fn convert_ctp_to_md_snapshot(
-    ctp_data: &CThostFtdcDepthMarketDataField,
-) -> GatewayResult<MDSnapshot> {
-    // Parse the update time from CTP format
-    let datetime = parse_ctp_datetime(
-        &ctp_data.TradingDay,
-        &ctp_data.UpdateTime,
-        ctp_data.UpdateMillisec,
-    )?;
+/// CTP fills unavailable prices with DBL_MAX
+fn is_sentinel(value: f64) -> bool {
+    !value.is_finite() || value.abs() >= f64::MAX / 2.0
+}
 
-    // Extract exchange ID and instrument ID
-    let instrument_id = format_instrument_id(&ctp_data.ExchangeID, &ctp_data.InstrumentID)?;
+/// Kind of instrument a quote belongs to, decides which fields are meaningful
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteKind {
+    /// Futures and options, which carry settlement and open interest
+    Futures,
+    /// A-share stocks, funds and bonds, which have no settlement or open interest
+    Equity,
+}
 
-    // Helper function to safely convert CTP numeric strings to f64
-    let _parse_f64 = |s: &[u8]| -> Result<f64, GatewayError> {
-        let s = std::str::from_utf8(s)
-            .map_err(|_| GatewayError::ConversionError("Invalid UTF-8 string".to_string()))?
-            .trim_end_matches('\0')
-            .trim();
+impl QuoteKind {
+    /// Detect the kind from the exchange prefix, falling back to the source for bare codes
+    pub fn detect(source: MarketDataSource, instrument_id: &str) -> Self {
+        match instrument_id.split_once('.') {
+            Some(("SSE" | "SZSE" | "BSE", _)) => QuoteKind::Equity,
+            Some(_) => QuoteKind::Futures,
+            None => match source {
+                MarketDataSource::QQ | MarketDataSource::Sina | MarketDataSource::SinaHttp => {
+                    QuoteKind::Equity
+                }
+                _ if instrument_id.len() == 6 && instrument_id.bytes().all(|b| b.is_ascii_digit()) => {
+                    QuoteKind::Equity
+                }
+                _ => QuoteKind::Futures,
+            },
+        }
+    }
 
-        if s.is_empty() || s == "-" {
-            return Ok(0.0);
+    /// Kind of an instrument class from the reference data
+    pub fn of_class(class: InstrumentClass) -> Self {
+        if class.is_derivative() {
+            QuoteKind::Futures
+        } else {
+            QuoteKind::Equity
         }
+    }
+}
 
-        f64::from_str(s).map_err(|_| {
-            GatewayError::ConversionError(format!("Failed to parse f64 from string: {}", s))
-        })
-    };
+/// Source-aware conversion of CTP depth market data into MDSnapshot
+///
+/// Sentinel values are handled per field: level 1 prices fall back to 0, the close
+/// price and depth levels become absent, and futures-only fields are `-` for futures
+/// without a value yet and `null` for equities. Prices can optionally be rounded to
+/// the instrument's price tick.
+///
+/// With instrument reference data the quote kind follows the instrument's class instead
+/// of the exchange prefix, and snapshots carry `ins_class`. Options get their underlying
+/// and strike from the reference data or, failing that, from the option code. ETFs have
+/// `iopv` set to `-` as CTP does not publish it, other instruments leave it `null`.
+///
+/// In tolerant mode truncated or GBK-encoded payloads (seen on the QQ source) are
+/// published as partial snapshots: text fields are decoded leniently, an unparsable
+/// update time falls back to the receive time and unavailable futures-only fields
+/// become `null`. Only a missing instrument ID still drops the quote.
+#[derive(Debug, Clone)]
+pub struct SnapshotConverter {
+    source: MarketDataSource,
+    /// Instrument reference data used to classify quotes
+    instruments: Option<Arc<InstrumentRegistry>>,
+    /// Round prices to the price tick of known instruments
+    round_prices: bool,
+    /// Publish partial snapshots instead of failing on malformed fields
+    tolerant: bool,
+    /// Raw payloads of malformed quotes
+    dead_letter: Option<Arc<DeadLetterLog>>,
+}
 
-    // Helper function to convert CTP optional prices to Option<f64>
-    let optional_price = |price: f64| -> Option<f64> {
-        if price <= 0.0 || price.is_nan() {
-            None
-        } else {
-            Some(price)
+impl SnapshotConverter {
+    /// Create a converter for quotes from the given source
+    pub fn new(source: MarketDataSource) -> Self {
+        Self {
+            source,
+            instruments: None,
+            round_prices: false,
+            tolerant: false,
+            dead_letter: None,
         }
-    };
+    }
+
+    /// Classify quotes and fill in option terms from the instrument reference data
+    pub fn with_instruments(mut self, instruments: Arc<InstrumentRegistry>) -> Self {
+        self.instruments = Some(instruments);
+        self
+    }
+
+    /// Round prices to the price tick of instruments known to the registry
+    pub fn with_price_rounding(mut self, instruments: Arc<InstrumentRegistry>) -> Self {
+        self.round_prices = true;
+        self.with_instruments(instruments)
+    }
+
+    /// Publish partial snapshots from malformed payloads, optionally capturing them
+    pub fn with_tolerant_parsing(mut self, dead_letter: Option<Arc<DeadLetterLog>>) -> Self {
+        self.tolerant = true;
+        self.dead_letter = dead_letter;
+        self
+    }
+
+    /// Price tick of the instrument when rounding is enabled
+    fn price_tick(&self, info: Option<&InstrumentInfo>) -> Option<f64> {
+        if !self.round_prices {
+            return None;
+        }
+        info.map(|info| info.price_tick)
+            .filter(|tick| *tick > 0.0 && tick.is_finite())
+    }
+
+    /// Underlying and strike of an option, from the reference data or else the option code
+    fn option_terms(&self, instrument_id: &str, info: Option<&InstrumentInfo>) -> Option<(String, f64)> {
+        let listed = info.and_then(|info| Some((info.underlying_instrument.clone()?, info.strike_price?)));
+        match listed {
+            // Reference data lists bare codes, prefix the exchange like the option's ID
+            Some((underlying, strike)) => match instrument_id.split_once('.') {
+                Some((exchange, _)) if !underlying.contains('.') => {
+                    Some((format!("{}.{}", exchange, underlying), strike))
+                }
+                _ => Some((underlying, strike)),
+            },
+            None => parse_option_id(instrument_id).map(|code| (code.underlying, code.strike)),
+        }
+    }
+
+    /// Convert one CTP depth market data record
+    pub fn convert(&self, ctp_data: &CThostFtdcDepthMarketDataField) -> GatewayResult<MDSnapshot> {
+        let mut diagnostics = Vec::new();
+        let result = self.convert_fields(ctp_data, &mut diagnostics);
+        if self.tolerant {
+            match &result {
+                Ok(snapshot) if !diagnostics.is_empty() => {
+                    warn!(
+                        "Publishing partial {:?} quote for {}: {}",
+                        self.source,
+                        snapshot.instrument_id,
+                        diagnostics.join("; ")
+                    );
+                    self.capture(ctp_data, &diagnostics);
+                }
+                Err(e) => {
+                    diagnostics.push(e.to_string());
+                    self.capture(ctp_data, &diagnostics);
+                }
+                Ok(_) => {}
+            }
+        }
+        result
+    }
+
+    /// Write a malformed payload to the dead-letter file
+    fn capture(&self, ctp_data: &CThostFtdcDepthMarketDataField, diagnostics: &[String]) {
+        if let Some(dead_letter) = &self.dead_letter {
+            if let Err(e) = dead_letter.write(self.source, ctp_data, diagnostics) {
+                warn!("Failed to write dead letter to {}: {}", dead_letter.path, e);
+            }
+        }
+    }
 
-    // Helper function to convert CTP optional volumes to Option<i64>
-    let optional_volume = |volume: i32| -> Option<i64> {
-        if volume <= 0 {
-            None
+    /// Convert the fields, recording the problems tolerated in tolerant mode
+    fn convert_fields(
+        &self,
+        ctp_data: &CThostFtdcDepthMarketDataField,
+        diagnostics: &mut Vec<String>,
+    ) -> GatewayResult<MDSnapshot> {
+        // Extract exchange ID and instrument ID
+        let (exchange, instrument) = if self.tolerant {
+            (
+                decode_text(&ctp_data.ExchangeID, "exchange ID", diagnostics),
+                decode_text(&ctp_data.InstrumentID, "instrument ID", diagnostics),
+            )
         } else {
-            Some(volume as i64)
+            (
+                ctp_str(&ctp_data.ExchangeID, "exchange ID")?.to_string(),
+                ctp_str(&ctp_data.InstrumentID, "instrument ID")?.to_string(),
+            )
+        };
+        if instrument.is_empty() {
+            return Err(GatewayError::ConversionError("Missing instrument ID".to_string()));
         }
-    };
+        // Some fronts leave the exchange ID empty, infer it from the instrument ID
+        let exchange = if exchange.is_empty() {
+            exchange_of(&instrument).map(|info| info.code.to_string()).unwrap_or_default()
+        } else {
+            exchange
+        };
+        let instrument_id = format_instrument_id(&exchange, &instrument);
 
-    // Helper function to create OptionalF64 for special fields
-    let optional_f64 = |value: f64, field_name: &str| -> OptionalF64 {
-        if value <= 0.0 || value.is_nan() {
-            // For futures-specific fields, use "-" when not available
-            if field_name == "open_interest"
-                || field_name == "pre_open_interest"
-                || field_name == "settlement"
-                || field_name == "pre_settlement"
-            {
-                OptionalF64::String("-".to_string())
+        // Parse the update time from CTP format
+        let datetime = match parse_ctp_datetime(ctp_data, &exchange) {
+            Ok(datetime) => datetime,
+            Err(e) if self.tolerant => {
+                diagnostics.push(format!("{}, using the receive time", e));
+                Utc::now()
+            }
+            Err(e) => return Err(e),
+        };
+        // Fields cut off from a partial payload are unknown rather than not yet available
+        let partial = !diagnostics.is_empty();
+
+        let info = self.instruments.as_ref().and_then(|instruments| instruments.get(&instrument_id));
+        let class = info.and_then(|info| InstrumentClass::from_code(&info.product_class));
+        let kind = class.map_or_else(|| QuoteKind::detect(self.source, &instrument_id), QuoteKind::of_class);
+        let tick = self.price_tick(info);
+        let option_terms = match class {
+            Some(InstrumentClass::Option) | None => self.option_terms(&instrument_id, info),
+            Some(_) => None,
+        };
+
+        // Prices that are always present, 0 when unavailable
+        let price = |value: f64| -> f64 {
+            if is_sentinel(value) || value <= 0.0 {
+                0.0
             } else {
-                OptionalF64::Null
+                tick.map_or(value, |tick| round_to_tick(value, tick))
             }
-        } else {
-            OptionalF64::Value(value)
+        };
+
+        // Prices that are absent when unavailable
+        let optional_price = |value: f64| -> Option<f64> { Some(price(value)).filter(|p| *p > 0.0) };
+
+        // Helper function to convert CTP optional volumes to Option<i64>
+        let optional_volume = |volume: i32| -> Option<i64> {
+            if volume <= 0 {
+                None
+            } else {
+                Some(volume as i64)
+            }
+        };
+
+        // Futures-specific fields use "-" when not available yet and do not apply to equities
+        let futures_field = |value: Option<f64>| -> OptionalF64 {
+            match kind {
+                QuoteKind::Equity => OptionalF64::Null,
+                QuoteKind::Futures if partial => value.map_or(OptionalF64::Null, OptionalF64::from),
+                QuoteKind::Futures => value.map_or_else(OptionalF64::missing, OptionalF64::from),
+            }
+        };
+        let open_interest = |value: f64| -> Option<f64> {
+            (!is_sentinel(value) && value > 0.0).then_some(value)
+        };
+
+        // Create MDSnapshot from CTP data (CTP does not publish the IOPV of ETFs)
+        let amount = if is_sentinel(ctp_data.Turnover) { 0.0 } else { ctp_data.Turnover };
+        let average = if is_sentinel(ctp_data.AveragePrice) { 0.0 } else { ctp_data.AveragePrice };
+        let mut builder = MDSnapshot::builder(instrument_id, datetime)
+            .amount(amount)
+            .last_price(price(ctp_data.LastPrice))
+            .volume(ctp_data.Volume as i64)
+            .open(price(ctp_data.OpenPrice))
+            .highest(price(ctp_data.HighestPrice))
+            .lowest(price(ctp_data.LowestPrice))
+            .close(optional_price(ctp_data.ClosePrice))
+            .pre_close(price(ctp_data.PreClosePrice))
+            .limits(price(ctp_data.LowerLimitPrice), price(ctp_data.UpperLimitPrice))
+            .average(average)
+            .bid(1, price(ctp_data.BidPrice1), ctp_data.BidVolume1 as i64)
+            .ask(1, price(ctp_data.AskPrice1), ctp_data.AskVolume1 as i64)
+            // Futures-specific fields
+            .open_interest(futures_field(open_interest(ctp_data.OpenInterest)))
+            .pre_open_interest(futures_field(open_interest(ctp_data.PreOpenInterest)))
+            .settlement(futures_field(optional_price(ctp_data.SettlementPrice)))
+            .pre_settlement(futures_field(optional_price(ctp_data.PreSettlementPrice)))
+            .iopv(match class {
+                Some(InstrumentClass::Etf) => OptionalF64::missing(),
+                _ => OptionalF64::Null,
+            });
+        if let Some(class) = class {
+            builder = builder.ins_class(class);
+        }
+        if let Some((underlying, strike)) = option_terms {
+            builder = builder.option_terms(underlying, strike);
         }
-    };
 
-    // Create MDSnapshot from CTP data
-    let snapshot = MDSnapshot {
-        instrument_id,
-        amount: ctp_data.Turnover as f64,
-        ask_price1: ctp_data.AskPrice1,
-        ask_volume1: ctp_data.AskVolume1 as i64,
-        bid_price1: ctp_data.BidPrice1,
-        bid_volume1: ctp_data.BidVolume1 as i64,
-        last_price: ctp_data.LastPrice,
-        datetime,
-        highest: ctp_data.HighestPrice,
-        lowest: ctp_data.LowestPrice,
-        open: ctp_data.OpenPrice,
-        close: optional_f64(ctp_data.ClosePrice, "close"),
-        volume: ctp_data.Volume as i64,
-        pre_close: ctp_data.PreClosePrice,
-        lower_limit: ctp_data.LowerLimitPrice,
-        upper_limit: ctp_data.UpperLimitPrice,
-        average: ctp_data.AveragePrice,
-
-        // Optional depth levels
-        ask_price2: optional_price(ctp_data.AskPrice2),
-        ask_volume2: optional_volume(ctp_data.AskVolume2),
-        bid_price2: optional_price(ctp_data.BidPrice2),
-        bid_volume2: optional_volume(ctp_data.BidVolume2),
-        ask_price3: optional_price(ctp_data.AskPrice3),
-        ask_volume3: optional_volume(ctp_data.AskVolume3),
-        bid_price3: optional_price(ctp_data.BidPrice3),
-        bid_volume3: optional_volume(ctp_data.BidVolume3),
-        ask_price4: optional_price(ctp_data.AskPrice4),
-        ask_volume4: optional_volume(ctp_data.AskVolume4),
-        bid_price4: optional_price(ctp_data.BidPrice4),
-        bid_volume4: optional_volume(ctp_data.BidVolume4),
-        ask_price5: optional_price(ctp_data.AskPrice5),
-        ask_volume5: optional_volume(ctp_data.AskVolume5),
-        bid_price5: optional_price(ctp_data.BidPrice5),
-        bid_volume5: optional_volume(ctp_data.BidVolume5),
-
-        // Additional optional depth levels (not available in CTP, set to None)
-        ask_price6: None,
-        ask_volume6: None,
-        bid_price6: None,
-        bid_volume6: None,
-        ask_price7: None,
-        ask_volume7: None,
-        bid_price7: None,
-        bid_volume7: None,
-        ask_price8: None,
-        ask_volume8: None,
-        bid_price8: None,
-        bid_volume8: None,
-        ask_price9: None,
-        ask_volume9: None,
-        bid_price9: None,
-        bid_volume9: None,
-        ask_price10: None,
-        ask_volume10: None,
-        bid_price10: None,
-        bid_volume10: None,
-
-        // Futures-specific fields
-        open_interest: optional_f64(ctp_data.OpenInterest, "open_interest"),
-        pre_open_interest: optional_f64(ctp_data.PreOpenInterest, "pre_open_interest"),
-        settlement: optional_f64(ctp_data.SettlementPrice, "settlement"),
-        pre_settlement: optional_f64(ctp_data.PreSettlementPrice, "pre_settlement"),
-
-        // ETF-specific fields (not available in CTP, set to Null)
-        iopv: OptionalF64::Null,
-    };
+        // Optional depth levels 2-5 (levels 6-10 are not available in CTP)
+        let depth = [
+            (ctp_data.BidPrice2, ctp_data.BidVolume2, ctp_data.AskPrice2, ctp_data.AskVolume2),
+            (ctp_data.BidPrice3, ctp_data.BidVolume3, ctp_data.AskPrice3, ctp_data.AskVolume3),
+            (ctp_data.BidPrice4, ctp_data.BidVolume4, ctp_data.AskPrice4, ctp_data.AskVolume4),
+            (ctp_data.BidPrice5, ctp_data.BidVolume5, ctp_data.AskPrice5, ctp_data.AskVolume5),
+        ];
+        for (i, (bid_price, bid_volume, ask_price, ask_volume)) in depth.into_iter().enumerate() {
+            if let (Some(price), Some(volume)) = (optional_price(bid_price), optional_volume(bid_volume)) {
+                builder = builder.bid(i + 2, price, volume);
+            }
+            if let (Some(price), Some(volume)) = (optional_price(ask_price), optional_volume(ask_volume)) {
+                builder = builder.ask(i + 2, price, volume);
+            }
+        }
 
-    Ok(snapshot)
+        Ok(builder.build())
+    }
 }
 
-/// Parse CTP datetime format (trading_day + update_time + millisec) into a UTC DateTime
-fn parse_ctp_datetime(
-    trading_day: &[u8],
-    update_time: &[u8],
-    millisec: i32,
-) -> GatewayResult<DateTime<Utc>> {
-    // Convert from byte arrays to strings
-    let trading_day = std::str::from_utf8(trading_day)
-        .map_err(|_| GatewayError::ConversionError("Invalid trading day".to_string()))?
-        .trim_end_matches('\0')
-        .trim();
+/// Round a price to the nearest multiple of the price tick
+fn round_to_tick(price: f64, tick: f64) -> f64 {
+    let rounded = (price / tick).round() * tick;
+    // Remove the floating point noise introduced by the multiplication
+    (rounded * 1e8).round() / 1e8
+}
 
-    let update_time = std::str::from_utf8(update_time)
-        .map_err(|_| GatewayError::ConversionError("Invalid update time".to_string()))?
-        .trim_end_matches('\0')
-        .trim();
-
-    // Parse the date and time components
-    let year = i32::from_str(&trading_day[0..4]).map_err(|_| {
-        GatewayError::ConversionError(format!("Invalid year in trading day: {}", trading_day))
-    })?;
-    let month = u32::from_str(&trading_day[4..6]).map_err(|_| {
-        GatewayError::ConversionError(format!("Invalid month in trading day: {}", trading_day))
-    })?;
-    let day = u32::from_str(&trading_day[6..8]).map_err(|_| {
-        GatewayError::ConversionError(format!("Invalid day in trading day: {}", trading_day))
-    })?;
-
-    // Handle night trading session (next day's trading date)
-    // If update_time is after 17:00, we're in night trading for the next day
-    let (hour, minute, second) = if update_time.len() >= 8 {
-        let h = u32::from_str(&update_time[0..2]).map_err(|_| {
-            GatewayError::ConversionError(format!("Invalid hour in update time: {}", update_time))
-        })?;
-        let m = u32::from_str(&update_time[3..5]).map_err(|_| {
-            GatewayError::ConversionError(format!("Invalid minute in update time: {}", update_time))
-        })?;
-        let s = u32::from_str(&update_time[6..8]).map_err(|_| {
-            GatewayError::ConversionError(format!("Invalid second in update time: {}", update_time))
-        })?;
-        (h, m, s)
-    } else {
-        (0, 0, 0)
-    };
+/// Append-only JSON lines file of quotes that could not be parsed cleanly
+#[derive(Debug)]
+pub struct DeadLetterLog {
+    path: String,
+    file: Mutex<File>,
+}
 
-    // Create a naive datetime
-    let naive_dt = match chrono::NaiveDateTime::new(
-        chrono::NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| {
-            GatewayError::ConversionError(format!(
-                "Invalid date: {}/{}/{}",
-                year, month, day
-            ))
-        })?,
-        chrono::NaiveTime::from_hms_milli_opt(hour, minute, second, millisec as u32).ok_or_else(
-            || {
-                GatewayError::ConversionError(format!(
-                    "Invalid time: {}:{}:{}.{}",
-                    hour, minute, second, millisec
-                ))
+impl DeadLetterLog {
+    /// Open the file for appending, creating it if needed
+    pub fn open(path: &str) -> GatewayResult<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Record the raw text fields (hex encoded) and key numbers of a quote
+    fn write(
+        &self,
+        source: MarketDataSource,
+        ctp_data: &CThostFtdcDepthMarketDataField,
+        diagnostics: &[String],
+    ) -> GatewayResult<()> {
+        let hex = |bytes: &[u8]| -> String {
+            let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            bytes[..len].iter().map(|b| format!("{:02x}", b)).collect()
+        };
+        let record = serde_json::json!({
+            "received_at": Utc::now().to_rfc3339(),
+            "source": source,
+            "diagnostics": diagnostics,
+            "raw": {
+                "trading_day": hex(&ctp_data.TradingDay),
+                "action_day": hex(&ctp_data.ActionDay),
+                "update_time": hex(&ctp_data.UpdateTime),
+                "update_millisec": ctp_data.UpdateMillisec,
+                "exchange_id": hex(&ctp_data.ExchangeID),
+                "instrument_id": hex(&ctp_data.InstrumentID),
+                "exchange_inst_id": hex(&ctp_data.ExchangeInstID),
+                "last_price": ctp_data.LastPrice,
+                "volume": ctp_data.Volume,
+                "turnover": ctp_data.Turnover,
+                "bid_price1": ctp_data.BidPrice1,
+                "ask_price1": ctp_data.AskPrice1,
             },
-        )?,
-    ) {
-        dt => dt,
-    };
+        });
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(file, "{}", record)?;
+        Ok(())
+    }
+}
 
-    // Convert to UTC
-    Ok(Local.from_local_datetime(&naive_dt).unwrap().with_timezone(&Utc))
+/// Decode a NUL-terminated text field that may be GBK encoded, replacing invalid bytes
+fn decode_text(bytes: &[u8], field: &str, diagnostics: &mut Vec<String>) -> String {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    let (text, _, malformed) = encoding_rs::GBK.decode(&bytes[..len]);
+    if malformed {
+        diagnostics.push(format!("Invalid {} bytes", field));
+    }
+    text.trim().to_string()
 }
 
-/// Format instrument ID with exchange prefix
-fn format_instrument_id(exchange_id: &[u8], instrument_id: &[u8]) -> GatewayResult<String> {
-    let exchange = std::str::from_utf8(exchange_id)
-        .map_err(|_| GatewayError::ConversionError("Invalid exchange ID".to_string()))?
+/// Read a NUL-terminated CTP string field
+fn ctp_str<'a>(bytes: &'a [u8], field: &str) -> GatewayResult<&'a str> {
+    Ok(std::str::from_utf8(bytes)
+        .map_err(|_| GatewayError::ConversionError(format!("Invalid {}", field)))?
         .trim_end_matches('\0')
-        .trim();
+        .trim())
+}
 
-    let instrument = std::str::from_utf8(instrument_id)
-        .map_err(|_| GatewayError::ConversionError("Invalid instrument ID".to_string()))?
-        .trim_end_matches('\0')
-        .trim();
-
-    // Map CTP exchange IDs to QAMD exchange format
-    let exchange_prefix = match exchange {
-        "SHFE" => "SHFE.",   // Shanghai Futures Exchange
-        "DCE" => "DCE.",     // Dalian Commodity Exchange
-        "CZCE" => "CZCE.",   // Zhengzhou Commodity Exchange
-        "CFFEX" => "CFFEX.", // China Financial Futures Exchange
-        "INE" => "INE.",     // Shanghai International Energy Exchange
-        "SSE" => "SSE.",     // Shanghai Stock Exchange
-        "SZSE" => "SZSE.",   // Shenzhen Stock Exchange
-        "HKEX" => "HKEX.",   // Hong Kong Stock Exchange
-        "NYSE" => "NYSE.",   // New York Stock Exchange
-        "NASDAQ" => "NASDAQ.",   // Nasdaq Stock Market
-        "AMEX" => "AMEX.",   // American Stock Exchange
-        "BSE" => "BSE.",   // Bombay Stock Exchange
-        "NSE" => "NSE.",   // National Stock Exchange of India
-        _ => "",             // No prefix for unknown exchanges
+/// Parse CTP datetime fields (action day + trading day + update time + millisec) into a UTC DateTime
+///
+/// Times are interpreted in the exchange's timezone, night session quotes are assigned
+/// to their calendar day so timestamps from different sources are comparable
+fn parse_ctp_datetime(ctp_data: &CThostFtdcDepthMarketDataField, exchange: &str) -> GatewayResult<DateTime<Utc>> {
+    let normalizer = TimestampNormalizer::for_exchange(exchange).unwrap_or_default();
+    Ok(normalizer.normalize(
+        ctp_str(&ctp_data.ActionDay, "action day")?,
+        ctp_str(&ctp_data.TradingDay, "trading day")?,
+        ctp_str(&ctp_data.UpdateTime, "update time")?,
+        ctp_data.UpdateMillisec,
+    )?)
+}
+
+/// Format instrument ID with exchange prefix
+fn format_instrument_id(exchange: &str, instrument: &str) -> String {
+    // Registered exchanges keep their QAMD code, a few foreign exchanges pass through
+    let exchange_prefix = match exchange::lookup(exchange) {
+        Some(info) => info.code,
+        None => match exchange {
+            "HKEX" | "NYSE" | "NASDAQ" | "AMEX" | "NSE" => exchange,
+            _ => return instrument.to_string(), // No prefix for unknown exchanges
+        },
     };
 
-    Ok(format!("{}{}", exchange_prefix, instrument))
-} 
\ No newline at end of file
+    format!("{}.{}", exchange_prefix, instrument)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruments::InstrumentInfo;
+
+    fn fill(dst: &mut [u8], src: &str) {
+        dst[..src.len()].copy_from_slice(src.as_bytes());
+    }
+
+    fn depth_data(exchange: &str, instrument: &str) -> CThostFtdcDepthMarketDataField {
+        let mut data = CThostFtdcDepthMarketDataField::default();
+        fill(&mut data.TradingDay, "20240105");
+        fill(&mut data.UpdateTime, "10:30:00");
+        fill(&mut data.ExchangeID, exchange);
+        fill(&mut data.InstrumentID, instrument);
+        data.LastPrice = 3512.4;
+        data.OpenPrice = 3500.0;
+        data.HighestPrice = 3520.0;
+        data.LowestPrice = f64::MAX;
+        data.PreClosePrice = 3498.0;
+        data.ClosePrice = f64::MAX;
+        data.SettlementPrice = f64::MAX;
+        data.PreSettlementPrice = 3496.0;
+        data.OpenInterest = 120_000.0;
+        data.PreOpenInterest = 0.0;
+        data.Volume = 100;
+        data.BidPrice1 = 3512.0;
+        data.BidVolume1 = 5;
+        data.AskPrice1 = 3513.0;
+        data.AskVolume1 = 7;
+        data.BidPrice2 = f64::MAX;
+        data.BidVolume2 = 3;
+        data
+    }
+
+    #[test]
+    fn test_futures_sentinels() {
+        let snapshot = SnapshotConverter::new(MarketDataSource::CTP)
+            .convert(&depth_data("SHFE", "rb2410"))
+            .unwrap();
+
+        assert_eq!(snapshot.instrument_id, "SHFE.rb2410");
+        assert_eq!(snapshot.last_price, 3512.4);
+        assert_eq!(snapshot.lowest, 0.0);
+        assert!(snapshot.close.is_null());
+        assert_eq!(snapshot.settlement, OptionalF64::missing());
+        assert_eq!(snapshot.pre_settlement, OptionalF64::Value(3496.0));
+        assert_eq!(snapshot.open_interest, OptionalF64::Value(120_000.0));
+        assert_eq!(snapshot.pre_open_interest, OptionalF64::missing());
+        assert_eq!(snapshot.bid_price2, None);
+    }
+
+    #[test]
+    fn test_missing_exchange_inferred() {
+        let converter = SnapshotConverter::new(MarketDataSource::CTP);
+        assert_eq!(converter.convert(&depth_data("", "rb2410")).unwrap().instrument_id, "SHFE.rb2410");
+        assert_eq!(converter.convert(&depth_data("", "SR409")).unwrap().instrument_id, "CZCE.SR409");
+        assert_eq!(converter.convert(&depth_data("", "lc2409")).unwrap().instrument_id, "GFEX.lc2409");
+        assert_eq!(converter.convert(&depth_data("", "XYZ")).unwrap().instrument_id, "XYZ");
+    }
+
+    #[test]
+    fn test_equity_has_no_futures_fields() {
+        let mut data = depth_data("SSE", "600000");
+        data.OpenInterest = f64::MAX;
+        let snapshot = SnapshotConverter::new(MarketDataSource::CTP).convert(&data).unwrap();
+
+        assert_eq!(snapshot.instrument_id, "SSE.600000");
+        assert!(snapshot.open_interest.is_null());
+        assert!(snapshot.pre_open_interest.is_null());
+        assert!(snapshot.settlement.is_null());
+        assert!(snapshot.pre_settlement.is_null());
+        assert_eq!(snapshot.lowest, 0.0);
+    }
+
+    #[test]
+    fn test_tolerant_parsing_of_truncated_payload() {
+        let mut data = depth_data("SHFE", "rb2410");
+        data.UpdateTime = Default::default();
+        data.TradingDay = Default::default();
+        data.OpenInterest = 0.0;
+        assert!(SnapshotConverter::new(MarketDataSource::QQ).convert(&data).is_err());
+
+        let converter = SnapshotConverter::new(MarketDataSource::QQ).with_tolerant_parsing(None);
+        let snapshot = converter.convert(&data).unwrap();
+        assert_eq!(snapshot.instrument_id, "SHFE.rb2410");
+        assert_eq!(snapshot.last_price, 3512.4);
+        assert!(snapshot.open_interest.is_null());
+        assert!(snapshot.settlement.is_null());
+        assert_eq!(snapshot.pre_settlement, OptionalF64::Value(3496.0));
+
+        // Complete payloads are converted as in strict mode
+        let snapshot = converter.convert(&depth_data("SHFE", "rb2410")).unwrap();
+        assert_eq!(snapshot.settlement, OptionalF64::missing());
+    }
+
+    #[test]
+    fn test_tolerant_parsing_of_gbk_text() {
+        let mut data = depth_data("SSE", "600000");
+        // Invalid UTF-8 in the exchange ID, a GBK lead byte cut off at the end
+        data.ExchangeID = Default::default();
+        data.ExchangeID[..4].copy_from_slice(&[b'S', b'S', b'E', 0xB2]);
+        assert!(SnapshotConverter::new(MarketDataSource::QQ).convert(&data).is_err());
+
+        let snapshot = SnapshotConverter::new(MarketDataSource::QQ)
+            .with_tolerant_parsing(None)
+            .convert(&data)
+            .unwrap();
+        assert_eq!(snapshot.instrument_id, "600000");
+        assert_eq!(snapshot.last_price, 3512.4);
+
+        data.InstrumentID = Default::default();
+        assert!(SnapshotConverter::new(MarketDataSource::QQ)
+            .with_tolerant_parsing(None)
+            .convert(&data)
+            .is_err());
+    }
+
+    #[test]
+    fn test_quote_kind_detection() {
+        assert_eq!(QuoteKind::detect(MarketDataSource::CTP, "SZSE.000001"), QuoteKind::Equity);
+        assert_eq!(QuoteKind::detect(MarketDataSource::Sina, "SHFE.rb2410"), QuoteKind::Futures);
+        assert_eq!(QuoteKind::detect(MarketDataSource::CTP, "600000"), QuoteKind::Equity);
+        assert_eq!(QuoteKind::detect(MarketDataSource::CTP, "rb2410"), QuoteKind::Futures);
+        assert_eq!(QuoteKind::detect(MarketDataSource::Sina, "sh600000"), QuoteKind::Equity);
+    }
+
+    #[test]
+    fn test_round_to_price_tick() {
+        let mut registry = InstrumentRegistry::new();
+        registry.extend([InstrumentInfo {
+            instrument_id: "rb2410".to_string(),
+            exchange_id: "SHFE".to_string(),
+            instrument_name: String::new(),
+            product_id: "rb".to_string(),
+            product_class: "FUTURE".to_string(),
+            price_tick: 1.0,
+            volume_multiple: 10,
+            expire_date: None,
+            underlying_instrument: None,
+            strike_price: None,
+            option_class: None,
+            long_margin_ratio: None,
+            short_margin_ratio: None,
+        }]);
+        let converter =
+            SnapshotConverter::new(MarketDataSource::CTP).with_price_rounding(Arc::new(registry));
+
+        let snapshot = converter.convert(&depth_data("SHFE", "rb2410")).unwrap();
+        assert_eq!(snapshot.last_price, 3512.0);
+        assert_eq!(snapshot.lowest, 0.0);
+
+        // Instruments missing from the registry are left untouched
+        let snapshot = converter.convert(&depth_data("SHFE", "rb2501")).unwrap();
+        assert_eq!(snapshot.last_price, 3512.4);
+
+        assert_eq!(round_to_tick(10.234, 0.01), 10.23);
+        assert_eq!(round_to_tick(4567.3, 0.2), 4567.4);
+    }
+
+    #[test]
+    fn test_classification_from_reference_data() {
+        let info = |instrument_id: &str, exchange_id: &str, product_class: &str| InstrumentInfo {
+            instrument_id: instrument_id.to_string(),
+            exchange_id: exchange_id.to_string(),
+            instrument_name: String::new(),
+            product_id: String::new(),
+            product_class: product_class.to_string(),
+            price_tick: 1.0,
+            volume_multiple: 10,
+            expire_date: None,
+            underlying_instrument: None,
+            strike_price: None,
+            option_class: None,
+            long_margin_ratio: None,
+            short_margin_ratio: None,
+        };
+        let mut registry = InstrumentRegistry::new();
+        registry.extend([
+            info("510300", "SSE", "ETF"),
+            // A code the exchange prefix alone would take for an equity
+            info("10007000", "SSE", "OPTION"),
+            InstrumentInfo {
+                underlying_instrument: Some("m2409".to_string()),
+                strike_price: Some(3050.0),
+                ..info("m2409-C-3000", "DCE", "OPTION")
+            },
+        ]);
+        let converter = SnapshotConverter::new(MarketDataSource::CTP).with_instruments(Arc::new(registry));
+
+        let etf = converter.convert(&depth_data("SSE", "510300")).unwrap();
+        assert!(etf.is_etf());
+        assert_eq!(etf.iopv, OptionalF64::missing());
+        assert!(etf.open_interest.is_null());
+        // Reference data alone does not round prices
+        assert_eq!(etf.last_price, 3512.4);
+
+        let option = converter.convert(&depth_data("SSE", "10007000")).unwrap();
+        assert!(option.is_futures_or_options());
+        assert_eq!(option.open_interest, OptionalF64::Value(120_000.0));
+        assert_eq!(option.strike_price, None);
+
+        // Listed terms take precedence over the option code
+        let option = converter.convert(&depth_data("DCE", "m2409-C-3000")).unwrap();
+        assert_eq!(option.underlying_symbol.as_deref(), Some("DCE.m2409"));
+        assert_eq!(option.strike_price, Some(3050.0));
+
+        // Unknown instruments fall back to the exchange prefix and the option code
+        let option = converter.convert(&depth_data("SHFE", "cu2409C70000")).unwrap();
+        assert_eq!(option.ins_class, None);
+        assert_eq!(option.underlying_symbol.as_deref(), Some("SHFE.cu2409"));
+        assert_eq!(option.strike_price, Some(70000.0));
+        assert!(option.iopv.is_null());
+    }
+}
@@ -0,0 +1,253 @@
+//! 主力合约映射
+//!
+//! 客户端可以订阅品种的主力连续合约，由网关映射到当前持仓量最大的合约月份：
+//! - `SHFE.rb.HOT`
+//! - `SHFE.rb888`（文华风格）
+//! - `SHFE.rb(主力)`
+//!
+//! 不带交易所前缀时（如`rb888`）匹配任意交易所的该品种。主力合约只向后移动：
+//! 更远月份的持仓量超过当前主力合约的`switch_ratio`倍时才切换，避免在两个月份间来回切换。
+
+use hashbrown::HashMap;
+
+use crate::instruments::InstrumentInfo;
+
+/// 主力合约代码的后缀
+const SUFFIXES: [&str; 3] = [".HOT", "888", "(主力)"];
+
+/// 主力合约对应的品种
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DominantProduct {
+    /// 交易所代码（None表示任意交易所）
+    pub exchange: Option<String>,
+    /// 品种代码
+    pub product: String,
+}
+
+impl DominantProduct {
+    /// 合约是否为该品种的期货合约
+    pub fn matches(&self, info: &InstrumentInfo) -> bool {
+        info.product_class == "FUTURE"
+            && info.product_id.eq_ignore_ascii_case(&self.product)
+            && self
+                .exchange
+                .as_ref()
+                .is_none_or(|exchange| info.exchange_id.eq_ignore_ascii_case(exchange))
+    }
+
+    /// 配置中指定合约月份时使用的键（如`SHFE.rb`）
+    pub fn key(&self) -> String {
+        match &self.exchange {
+            Some(exchange) => format!("{}.{}", exchange, self.product),
+            None => self.product.clone(),
+        }
+    }
+}
+
+/// 解析主力合约代码，不是主力合约时返回None
+pub fn parse_dominant(instrument: &str) -> Option<DominantProduct> {
+    let rest = SUFFIXES.iter().find_map(|suffix| instrument.strip_suffix(suffix))?;
+    let (exchange, product) = match rest.split_once('.') {
+        Some((exchange, product)) => (Some(exchange.to_uppercase()), product),
+        None => (None, rest),
+    };
+    if product.is_empty() || !product.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    if exchange.as_ref().is_some_and(|exchange| exchange.is_empty()) {
+        return None;
+    }
+    Some(DominantProduct {
+        exchange,
+        product: product.to_string(),
+    })
+}
+
+/// 是否为主力合约代码
+pub fn is_dominant(instrument: &str) -> bool {
+    parse_dominant(instrument).is_some()
+}
+
+/// 行情中的合约代码是否为该合约月份（月份不含交易所前缀时忽略行情的交易所前缀）
+pub fn contract_matches(contract: &str, instrument_id: &str) -> bool {
+    if instrument_id == contract {
+        return true;
+    }
+    match (contract.split_once('.'), instrument_id.split_once('.')) {
+        (Some((_, code)), None) => code == instrument_id,
+        (None, Some((_, code))) => code == contract,
+        _ => false,
+    }
+}
+
+/// 按持仓量选择主力合约
+#[derive(Debug, Clone)]
+pub struct DominantTracker {
+    /// 合约月份，按交割先后排列
+    contracts: Vec<String>,
+    /// 合约 -> 最新持仓量
+    open_interest: HashMap<String, f64>,
+    /// 当前主力合约在contracts中的位置
+    current: Option<usize>,
+    /// 切换所需的持仓量倍数
+    switch_ratio: f64,
+}
+
+impl DominantTracker {
+    /// contracts须按交割先后排列
+    pub fn new(contracts: Vec<String>, switch_ratio: f64) -> Self {
+        Self {
+            contracts,
+            open_interest: HashMap::new(),
+            current: None,
+            switch_ratio: switch_ratio.max(1.0),
+        }
+    }
+
+    /// 全部合约月份
+    pub fn contracts(&self) -> &[String] {
+        &self.contracts
+    }
+
+    /// 当前主力合约
+    pub fn current(&self) -> Option<&str> {
+        self.current.map(|index| self.contracts[index].as_str())
+    }
+
+    /// 是否已收到全部合约月份的持仓量
+    pub fn is_complete(&self) -> bool {
+        self.open_interest.len() == self.contracts.len()
+    }
+
+    /// 记录合约的持仓量，返回是否为该品种的合约月份
+    pub fn update(&mut self, contract: &str, open_interest: f64) -> bool {
+        if !self.contracts.iter().any(|c| c == contract) || !open_interest.is_finite() {
+            return false;
+        }
+        self.open_interest.insert(contract.to_string(), open_interest);
+        true
+    }
+
+    /// 按最新持仓量重新选择主力合约，主力合约变化时返回新的主力合约
+    pub fn select(&mut self) -> Option<&str> {
+        // 持仓量最大的合约，持仓量相同时取较近的月份
+        let (best, best_oi) = self
+            .contracts
+            .iter()
+            .enumerate()
+            .filter_map(|(index, contract)| self.open_interest.get(contract).map(|oi| (index, *oi)))
+            .fold(None, |best: Option<(usize, f64)>, (index, oi)| match best {
+                Some((_, best_oi)) if best_oi >= oi => best,
+                _ => Some((index, oi)),
+            })?;
+
+        let switch = match self.current {
+            None => true,
+            Some(current) => {
+                let current_oi = self.open_interest.get(&self.contracts[current]).copied().unwrap_or_default();
+                best > current && best_oi > current_oi * self.switch_ratio
+            }
+        };
+        if !switch {
+            return None;
+        }
+        self.current = Some(best);
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn future(instrument_id: &str, exchange_id: &str, product_id: &str) -> InstrumentInfo {
+        InstrumentInfo {
+            instrument_id: instrument_id.to_string(),
+            exchange_id: exchange_id.to_string(),
+            instrument_name: String::new(),
+            product_id: product_id.to_string(),
+            product_class: "FUTURE".to_string(),
+            price_tick: 1.0,
+            volume_multiple: 10,
+            expire_date: None,
+            underlying_instrument: None,
+            strike_price: None,
+            option_class: None,
+            long_margin_ratio: None,
+            short_margin_ratio: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_dominant_suffixes() {
+        let rb = DominantProduct {
+            exchange: Some("SHFE".to_string()),
+            product: "rb".to_string(),
+        };
+        assert_eq!(parse_dominant("SHFE.rb.HOT"), Some(rb.clone()));
+        assert_eq!(parse_dominant("shfe.rb888"), Some(rb.clone()));
+        assert_eq!(parse_dominant("SHFE.rb(主力)"), Some(rb));
+        assert_eq!(parse_dominant("rb888").unwrap().exchange, None);
+        assert_eq!(parse_dominant("rb888").unwrap().key(), "rb");
+        assert_eq!(parse_dominant("SHFE.rb888").unwrap().key(), "SHFE.rb");
+
+        assert!(!is_dominant("SHFE.rb2410"));
+        assert!(!is_dominant("SHFE.888"));
+        assert!(!is_dominant(".rb888"));
+        assert!(!is_dominant("SHFE.rb2410888"));
+    }
+
+    #[test]
+    fn test_dominant_product_matches_futures_only() {
+        let rb = parse_dominant("SHFE.rb888").unwrap();
+        assert!(rb.matches(&future("rb2410", "SHFE", "rb")));
+        assert!(rb.matches(&future("RB2410", "shfe", "RB")));
+        assert!(!rb.matches(&future("rb2410", "INE", "rb")));
+        let option = InstrumentInfo {
+            product_class: "OPTION".to_string(),
+            ..future("rb2410C3500", "SHFE", "rb")
+        };
+        assert!(!rb.matches(&option));
+        // 不带交易所前缀时匹配任意交易所
+        assert!(parse_dominant("rb888").unwrap().matches(&future("rb2410", "INE", "rb")));
+    }
+
+    #[test]
+    fn test_contract_matches_ignores_missing_exchange() {
+        assert!(contract_matches("SHFE.rb2410", "SHFE.rb2410"));
+        assert!(contract_matches("SHFE.rb2410", "rb2410"));
+        assert!(contract_matches("rb2410", "SHFE.rb2410"));
+        assert!(!contract_matches("SHFE.rb2410", "INE.rb2410"));
+        assert!(!contract_matches("rb2410", "rb2501"));
+    }
+
+    #[test]
+    fn test_tracker_switches_forward_past_ratio() {
+        let contracts = vec!["SHFE.rb2410".to_string(), "SHFE.rb2501".to_string(), "SHFE.rb2505".to_string()];
+        let mut tracker = DominantTracker::new(contracts, 1.1);
+        assert_eq!(tracker.select(), None);
+        assert!(!tracker.update("SHFE.ag2412", 1.0));
+        assert!(!tracker.update("SHFE.rb2410", f64::NAN));
+
+        assert!(tracker.update("SHFE.rb2410", 1000.0));
+        assert!(tracker.update("SHFE.rb2501", 1000.0));
+        assert!(!tracker.is_complete());
+        // 持仓量相同时取较近的月份
+        assert_eq!(tracker.select(), Some("SHFE.rb2410"));
+
+        // 未超过切换倍数时不切换
+        tracker.update("SHFE.rb2501", 1050.0);
+        assert_eq!(tracker.select(), None);
+        assert_eq!(tracker.current(), Some("SHFE.rb2410"));
+
+        tracker.update("SHFE.rb2501", 1200.0);
+        assert_eq!(tracker.select(), Some("SHFE.rb2501"));
+
+        // 主力合约不向前回退
+        tracker.update("SHFE.rb2410", 5000.0);
+        tracker.update("SHFE.rb2505", 10.0);
+        assert!(tracker.is_complete());
+        assert_eq!(tracker.select(), None);
+        assert_eq!(tracker.current(), Some("SHFE.rb2501"));
+    }
+}
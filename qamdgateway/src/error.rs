@@ -1,5 +1,22 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Error categories reported to WebSocket clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Authentication failures (40xx)
+    Auth,
+    /// Malformed or unsupported client messages (41xx)
+    Protocol,
+    /// Rejected subscription or alert requests (42xx)
+    Subscription,
+    /// Upstream market data source failures (43xx)
+    Upstream,
+    /// Gateway internal errors (50xx)
+    Internal,
+}
+
 /// Custom error types for the QAMD Gateway
 #[derive(Error, Debug)]
 pub enum GatewayError {
@@ -39,10 +56,99 @@ pub enum GatewayError {
     #[error("Authentication error: {0}")]
     AuthError(String),
 
+    /// Token lacks the scope of a requested instrument
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// Client message could not be parsed
+    #[error("Invalid message format: {0}")]
+    InvalidMessage(String),
+
+    /// Client message has an unknown aid/type
+    #[error("Unknown message type")]
+    UnknownMessage,
+
+    /// Requested wire format is not supported
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+
+    /// Subscribe/unsubscribe request without instruments
+    #[error("No instruments specified")]
+    NoInstruments,
+
+    /// Alert request rejected
+    #[error("Alert rejected: {0}")]
+    AlertRejected(String),
+
+    /// Watchlist does not exist
+    #[error("Unknown watchlist: {0}")]
+    UnknownWatchlist(String),
+
+    /// Watchlist name or instruments rejected
+    #[error("Invalid watchlist: {0}")]
+    InvalidWatchlist(String),
+
+    /// Client exceeded its subscription quota
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    /// Client sent messages faster than allowed
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
+    /// Upstream session is not logged in
+    #[error("Market data source not logged in")]
+    NotLoggedIn,
+
+    /// Upstream API is not available
+    #[error("Market data source unavailable: {0}")]
+    UpstreamUnavailable(String),
+
     /// Other errors
     #[error("Other error: {0}")]
     Other(String),
 }
 
+impl GatewayError {
+    /// Numeric error code sent to clients, the leading digits encode the category
+    pub fn code(&self) -> u16 {
+        match self {
+            GatewayError::AuthError(_) => 4001,
+            GatewayError::PermissionDenied(_) => 4003,
+            GatewayError::InvalidMessage(_) => 4101,
+            GatewayError::UnknownMessage => 4102,
+            GatewayError::UnsupportedFormat(_) => 4103,
+            GatewayError::WebSocketError(_) => 4104,
+            GatewayError::RateLimited(_) => 4105,
+            GatewayError::NoInstruments => 4201,
+            GatewayError::InvalidInstrument(_) => 4202,
+            GatewayError::AlertRejected(_) => 4203,
+            GatewayError::QuotaExceeded(_) => 4204,
+            GatewayError::UnknownWatchlist(_) => 4205,
+            GatewayError::InvalidWatchlist(_) => 4206,
+            GatewayError::CtpError(_) => 4301,
+            GatewayError::NotLoggedIn => 4302,
+            GatewayError::UpstreamUnavailable(_) => 4303,
+            GatewayError::ConversionError(_) => 4304,
+            GatewayError::Other(_) => 5000,
+            GatewayError::IoError(_) => 5001,
+            GatewayError::JsonError(_) => 5002,
+            GatewayError::QamdError(_) => 5003,
+            GatewayError::ConfigError(_) => 5004,
+        }
+    }
+
+    /// Error category
+    pub fn category(&self) -> ErrorCategory {
+        match self.code() {
+            4000..=4099 => ErrorCategory::Auth,
+            4100..=4199 => ErrorCategory::Protocol,
+            4200..=4299 => ErrorCategory::Subscription,
+            4300..=4399 => ErrorCategory::Upstream,
+            _ => ErrorCategory::Internal,
+        }
+    }
+}
+
 /// Result type for the QAMD Gateway
-pub type GatewayResult<T> = Result<T, GatewayError>; 
\ No newline at end of file
+pub type GatewayResult<T> = Result<T, GatewayError>;
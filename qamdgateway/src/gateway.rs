@@ -0,0 +1,321 @@
+//! Embedding the gateway in another Rust program
+//!
+//! [`Gateway::builder()`] starts the same upstream connections, distributor and
+//! synthetic instruments as the `qamdgateway` binary, but hands snapshots to
+//! Rust callbacks and streams instead of WebSocket clients. The HTTP and
+//! WebSocket servers are not started; mount [`crate::ws_server::ws_handler`]
+//! yourself if you also need them.
+//!
+//! Everything runs on actix actors, so the builder must be started from
+//! within a running actix system (e.g. `#[actix_rt::main]`).
+
+use actix::prelude::*;
+use tracing::{debug, info};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::actors::binance_actor::BinanceMarketDataActor;
+use crate::actors::md_connector::MarketDataConnector;
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::{
+    GetSnapshotCache, MarketDataSource, MarketDataUpdate, RegisterSnapshotSink, StopMarketData,
+    UnregisterSnapshotSink, UpdateDefaultSubscriptions,
+};
+use crate::actors::mock_actor::MockMarketDataActor;
+use crate::actors::replay_actor::ReplayMarketDataActor;
+use crate::actors::sina_http_poller::SinaHttpPollerActor;
+use crate::actors::dominant_actor::DominantActor;
+use crate::actors::synthetic_actor::SyntheticActor;
+use crate::config::{BrokerConfig, Config};
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::InstrumentRegistry;
+use qamd_rs::MDSnapshot;
+
+/// Callback invoked for every snapshot the distributor receives
+pub type SnapshotCallback = Box<dyn FnMut(&MDSnapshot, MarketDataSource) + Send>;
+
+/// Builder of an embedded [`Gateway`]
+#[derive(Default)]
+pub struct GatewayBuilder {
+    config: Option<Config>,
+    brokers: Vec<BrokerConfig>,
+    instruments: Vec<String>,
+    sinks: Vec<(String, Recipient<MarketDataUpdate>)>,
+    callbacks: Vec<(String, SnapshotCallback)>,
+}
+
+impl GatewayBuilder {
+    /// Use a gateway configuration: its active brokers, default instruments,
+    /// replay/mock/Sina HTTP/Binance sources and distributor settings are applied
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Connect an upstream broker in addition to the configured ones
+    pub fn broker(mut self, broker: BrokerConfig) -> Self {
+        self.brokers.push(broker);
+        self
+    }
+
+    /// Subscribe instruments upstream at startup (instrument groups are expanded)
+    pub fn subscribe<I, S>(mut self, instruments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.instruments.extend(instruments.into_iter().map(Into::into));
+        self
+    }
+
+    /// Deliver every snapshot to an actor, registered as a distributor sink under `name`
+    pub fn sink(mut self, name: impl Into<String>, addr: Recipient<MarketDataUpdate>) -> Self {
+        self.sinks.push((name.into(), addr));
+        self
+    }
+
+    /// Call `callback` with every snapshot, registered as a distributor sink under `name`
+    pub fn on_snapshot<F>(mut self, name: impl Into<String>, callback: F) -> Self
+    where
+        F: FnMut(&MDSnapshot, MarketDataSource) + Send + 'static,
+    {
+        self.callbacks.push((name.into(), Box::new(callback)));
+        self
+    }
+
+    /// Start the distributor, sources and upstream connections
+    pub fn start(self) -> GatewayResult<Gateway> {
+        let config = self.config;
+
+        let registry = match config.as_ref().and_then(|c| c.subscription.instruments_file.as_ref()) {
+            Some(path) => {
+                let registry = InstrumentRegistry::from_file(path)?;
+                info!("Loaded {} instruments from {}", registry.len(), path);
+                registry
+            }
+            None => InstrumentRegistry::new(),
+        };
+        let registry = Arc::new(registry);
+
+        let mut distributor = MarketDataDistributor::new();
+        if let Some(config) = &config {
+            distributor = distributor
+                .with_failover(config.failover.clone())
+                .with_options(config.options.clone(), registry.clone())
+                .with_max_pattern_matches(config.subscription.max_pattern_matches)
+                .with_resume_grace(Duration::from_secs(config.websocket.resume_grace_secs))
+                .with_patch_refresh(Duration::from_secs(config.distributor.patch_refresh_secs))
+                .with_unsubscribe_linger(
+                    Duration::from_secs(config.distributor.unsubscribe_linger_secs),
+                    config.distributor.cache_retention_secs.map(Duration::from_secs),
+                )
+                .with_clock_skew(config.clock_skew.clone())
+                .with_price_check(config.price_check.clone())
+                .with_replay_depth(config.distributor.replay_depth)
+                .with_fanout_shards(config.distributor.fanout_shards);
+        }
+        let distributor = distributor.start();
+        SyntheticActor::new(distributor.clone()).start();
+        let dominant = config.as_ref().map(|config| config.dominant.clone()).unwrap_or_default();
+        DominantActor::new(distributor.clone(), registry.clone(), dominant).start();
+
+        // Consumers are registered before any source can publish
+        let mut consumers = Vec::new();
+        for (name, addr) in self.sinks {
+            distributor.do_send(RegisterSnapshotSink { name: name.clone(), addr });
+            consumers.push(name);
+        }
+        for (name, callback) in self.callbacks {
+            register_callback(&distributor, name.clone(), callback);
+            consumers.push(name);
+        }
+
+        let mut brokers = match &config {
+            Some(config) if !config.brokers.is_empty() => config.active_broker_configs()?,
+            _ => Vec::new(),
+        };
+        brokers.extend(self.brokers);
+
+        let mut instruments = self.instruments;
+        if let Some(config) = &config {
+            instruments.extend(config.subscription.default_instruments.iter().cloned());
+            instruments.extend(config.subscription.auto_subscribe_patterns.iter().cloned());
+
+            if let Some(replay_config) = config.replay.clone().filter(|r| r.enabled) {
+                info!("Replay mode enabled, reading from {}", replay_config.path);
+                ReplayMarketDataActor::new(replay_config, distributor.clone()).start();
+                brokers.clear();
+            }
+            if let Some(mock_config) = config.mock.clone().filter(|m| m.enabled) {
+                info!("Mock mode enabled for {} instruments", mock_config.instruments.len());
+                MockMarketDataActor::new(mock_config, distributor.clone()).start();
+                brokers.clear();
+            }
+            if let Some(sina_http_config) = config.sina_http.clone().filter(|s| s.enabled) {
+                SinaHttpPollerActor::new(sina_http_config, distributor.clone()).start();
+            }
+            if let Some(binance_config) = config.binance.clone().filter(|b| b.enabled) {
+                BinanceMarketDataActor::new(binance_config, distributor.clone()).start();
+            }
+        }
+        let mut default_instruments = Vec::new();
+        for instrument in registry.expand(&instruments) {
+            if !default_instruments.contains(&instrument) {
+                default_instruments.push(instrument);
+            }
+        }
+
+        let mut connector = MarketDataConnector::new(brokers, default_instruments, distributor.clone())
+            .with_instruments(registry.clone());
+        if let Some(config) = &config {
+            connector = connector
+                .with_flow(config.flow.clone())
+                .with_circuit_breaker(config.circuit_breaker.clone());
+            if config.converter.round_to_price_tick {
+                connector = connector.with_price_rounding(registry.clone());
+            }
+        }
+        let connector = connector.start();
+        info!("Embedded gateway started");
+
+        Ok(Gateway {
+            distributor,
+            connector,
+            registry,
+            consumers,
+        })
+    }
+}
+
+/// Handle of a gateway running in-process
+pub struct Gateway {
+    distributor: Addr<MarketDataDistributor>,
+    connector: Addr<MarketDataConnector>,
+    registry: Arc<InstrumentRegistry>,
+    consumers: Vec<String>,
+}
+
+impl Gateway {
+    /// Configure an embedded gateway
+    pub fn builder() -> GatewayBuilder {
+        GatewayBuilder::default()
+    }
+
+    /// Distributor actor, e.g. to send it messages not covered by this handle
+    pub fn distributor(&self) -> &Addr<MarketDataDistributor> {
+        &self.distributor
+    }
+
+    /// Connector actor managing the upstream brokers
+    pub fn connector(&self) -> &Addr<MarketDataConnector> {
+        &self.connector
+    }
+
+    /// Instrument reference data loaded from the configuration
+    pub fn instruments(&self) -> &Arc<InstrumentRegistry> {
+        &self.registry
+    }
+
+    /// Subscribe instruments upstream (instrument groups are expanded)
+    pub fn subscribe(&self, instruments: &[String]) {
+        self.connector.do_send(UpdateDefaultSubscriptions {
+            added: self.registry.expand(instruments),
+            removed: Vec::new(),
+        });
+    }
+
+    /// Unsubscribe instruments upstream, unless WebSocket clients still watch them
+    pub fn unsubscribe(&self, instruments: &[String]) {
+        self.connector.do_send(UpdateDefaultSubscriptions {
+            added: Vec::new(),
+            removed: self.registry.expand(instruments),
+        });
+    }
+
+    /// Feed snapshots of a source implemented by the embedding program
+    pub fn publish(&self, snapshot: MDSnapshot, source: MarketDataSource) {
+        self.distributor.do_send(MarketDataUpdate(Arc::new(snapshot), source));
+    }
+
+    /// Call `callback` with every snapshot from now on, replacing a consumer of the same name
+    pub fn on_snapshot<F>(&mut self, name: impl Into<String>, callback: F)
+    where
+        F: FnMut(&MDSnapshot, MarketDataSource) + Send + 'static,
+    {
+        let name = name.into();
+        register_callback(&self.distributor, name.clone(), Box::new(callback));
+        self.add_consumer(name);
+    }
+
+    /// Receive every snapshot from now on through a channel of `capacity` updates;
+    /// updates are dropped while the channel is full
+    pub fn stream(&mut self, name: impl Into<String>, capacity: usize) -> mpsc::Receiver<MarketDataUpdate> {
+        let name = name.into();
+        let (tx, rx) = mpsc::channel(capacity);
+        let label = name.clone();
+        self.on_snapshot(name, move |snapshot, source| {
+            if let Err(mpsc::error::TrySendError::Full(_)) = tx.try_send(MarketDataUpdate(Arc::new(snapshot.clone()), source)) {
+                debug!("Stream {} is full, dropping {}", label, snapshot.instrument_id);
+            }
+        });
+        rx
+    }
+
+    /// Stop delivering snapshots to a callback, stream or sink
+    pub fn remove_consumer(&mut self, name: &str) {
+        self.distributor.do_send(UnregisterSnapshotSink {
+            name: name.to_string(),
+        });
+        self.consumers.retain(|consumer| consumer != name);
+    }
+
+    /// Latest snapshot of every instrument seen so far
+    pub async fn snapshots(&self) -> GatewayResult<Vec<MDSnapshot>> {
+        self.distributor
+            .send(GetSnapshotCache)
+            .await
+            .map_err(|e| GatewayError::Other(format!("Distributor unavailable: {}", e)))
+    }
+
+    /// Disconnect the upstream sources and detach all consumers
+    pub fn stop(mut self) {
+        info!("Stopping embedded gateway");
+        self.connector.do_send(StopMarketData);
+        for name in std::mem::take(&mut self.consumers) {
+            self.distributor.do_send(UnregisterSnapshotSink { name });
+        }
+    }
+
+    fn add_consumer(&mut self, name: String) {
+        if !self.consumers.contains(&name) {
+            self.consumers.push(name);
+        }
+    }
+}
+
+/// Start an actor running `callback` and register it with the distributor
+fn register_callback(distributor: &Addr<MarketDataDistributor>, name: String, callback: SnapshotCallback) {
+    let addr = CallbackSink { callback }.start();
+    distributor.do_send(RegisterSnapshotSink {
+        name,
+        addr: addr.recipient(),
+    });
+}
+
+/// Distributor sink forwarding snapshots to a Rust callback
+struct CallbackSink {
+    callback: SnapshotCallback,
+}
+
+impl Actor for CallbackSink {
+    type Context = Context<Self>;
+}
+
+impl Handler<MarketDataUpdate> for CallbackSink {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdate, _: &mut Self::Context) -> Self::Result {
+        (self.callback)(&msg.0, msg.1);
+    }
+}
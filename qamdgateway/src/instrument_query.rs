@@ -0,0 +1,229 @@
+//! 通过CTP交易前置查询合约基础信息
+//!
+//! 行情API不提供合约查询，需要登录交易前置调用ReqQryInstrument。
+//! 启动时同步查询一次全市场合约，结果与合约文件合并后供REST接口和行情推送使用。
+//! 需要启用`ctp-instruments`特性。
+
+use ctp_common::{
+    ascii_cstr_to_str, gb18030_cstr_to_str, normalize_double, CThostFtdcInstrumentField,
+    CThostFtdcQryInstrumentField, CThostFtdcReqAuthenticateField, CThostFtdcReqUserLoginField,
+    ResumeType, RspResult, THOST_FTDC_PC_Futures, THOST_FTDC_PC_Options, THOST_FTDC_PC_SpotOption,
+    THOST_FTDC_PC_Spot,
+};
+use ctp_trader::{GenericTraderApi, SenderTraderSpi, TraderApi, TraderSpiOutput};
+use tracing::{info, warn};
+use std::ffi::CString;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::config::{InstrumentQueryConfig, TraderLoginConfig};
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::InstrumentInfo;
+
+// 查询请求被流控时的重试间隔
+const THROTTLE_RETRY: Duration = Duration::from_secs(1);
+
+/// 登录交易前置并查询全部合约
+///
+/// 在超时时间内未完成查询时返回错误，已收到的合约会被丢弃
+pub fn query_ctp_instruments(config: &InstrumentQueryConfig) -> GatewayResult<Vec<InstrumentInfo>> {
+    let (mut api, receiver) = connect(&config.login, &config.flow_path)?;
+    info!("Querying instruments from CTP trader front {}", config.login.front_addr);
+
+    let deadline = Instant::now() + Duration::from_secs(config.timeout_secs);
+    let mut instruments = Vec::new();
+    loop {
+        let output = recv_until(&receiver, deadline).map_err(|_| {
+            GatewayError::UpstreamUnavailable(format!(
+                "CTP instrument query timed out after {}s ({} instruments received)",
+                config.timeout_secs,
+                instruments.len()
+            ))
+        })?;
+
+        match output {
+            TraderSpiOutput::FrontConnected(_) => {
+                if config.login.app_id.is_empty() {
+                    login(&mut api, &config.login)?;
+                } else {
+                    authenticate(&mut api, &config.login)?;
+                }
+            }
+            TraderSpiOutput::FrontDisconnected(disconnected) => {
+                warn!("CTP trader front disconnected: {:?}", disconnected.reason);
+            }
+            TraderSpiOutput::RspAuthenticate(rsp) => {
+                check_rsp("authenticate", &rsp.result)?;
+                login(&mut api, &config.login)?;
+            }
+            TraderSpiOutput::RspUserLogin(rsp) => {
+                check_rsp("login", &rsp.result)?;
+                request_instruments(&mut api, deadline)?;
+            }
+            TraderSpiOutput::RspQryInstrument(rsp) => {
+                check_rsp("instrument query", &rsp.result)?;
+                if let Some(info) = rsp.instrument.as_ref().and_then(instrument_info) {
+                    instruments.push(info);
+                }
+                if rsp.is_last {
+                    info!("Received {} instruments from CTP", instruments.len());
+                    return Ok(instruments);
+                }
+            }
+            TraderSpiOutput::RspError(rsp) => check_rsp("request", &rsp.result)?,
+            _ => {}
+        }
+    }
+}
+
+/// 创建交易API并连接交易前置，只订阅公有流和私有流的最新数据
+pub(crate) fn connect(
+    account: &TraderLoginConfig,
+    flow_path: &str,
+) -> GatewayResult<(TraderApi, mpsc::Receiver<TraderSpiOutput>)> {
+    let (sender, receiver) = mpsc::channel::<TraderSpiOutput>();
+    let flow_path = CString::new(flow_path)
+        .map_err(|e| GatewayError::ConfigError(format!("Invalid flow path: {}", e)))?;
+    let front_addr = CString::new(account.front_addr.as_str())
+        .map_err(|e| GatewayError::ConfigError(format!("Invalid front address: {}", e)))?;
+
+    let mut api = TraderApi::new(flow_path);
+    api.register_spi(Box::new(SenderTraderSpi::new(sender)));
+    api.register_front(front_addr);
+    api.subscribe_public_topic(ResumeType::Quick);
+    api.subscribe_private_topic(ResumeType::Quick);
+    api.init();
+    Ok((api, receiver))
+}
+
+/// 等待前置连接后认证（配置了App ID时）并登录
+pub(crate) fn wait_login(
+    api: &mut TraderApi,
+    receiver: &mpsc::Receiver<TraderSpiOutput>,
+    account: &TraderLoginConfig,
+    deadline: Instant,
+) -> GatewayResult<()> {
+    loop {
+        let output = recv_until(receiver, deadline).map_err(|_| {
+            GatewayError::UpstreamUnavailable(format!("CTP trader front {} login timed out", account.front_addr))
+        })?;
+        match output {
+            TraderSpiOutput::FrontConnected(_) => {
+                if account.app_id.is_empty() {
+                    login(api, account)?;
+                } else {
+                    authenticate(api, account)?;
+                }
+            }
+            TraderSpiOutput::FrontDisconnected(disconnected) => {
+                warn!("CTP trader front disconnected: {:?}", disconnected.reason);
+            }
+            TraderSpiOutput::RspAuthenticate(rsp) => {
+                check_rsp("authenticate", &rsp.result)?;
+                login(api, account)?;
+            }
+            TraderSpiOutput::RspUserLogin(rsp) => return check_rsp("login", &rsp.result),
+            TraderSpiOutput::RspError(rsp) => check_rsp("request", &rsp.result)?,
+            _ => {}
+        }
+    }
+}
+
+/// 在截止时间前接收下一条回报
+pub(crate) fn recv_until(
+    receiver: &mpsc::Receiver<TraderSpiOutput>,
+    deadline: Instant,
+) -> Result<TraderSpiOutput, mpsc::RecvTimeoutError> {
+    receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+}
+
+fn authenticate(api: &mut TraderApi, account: &TraderLoginConfig) -> GatewayResult<()> {
+    let mut req = CThostFtdcReqAuthenticateField::default();
+    fill_cstr(&mut req.BrokerID, &account.broker_id);
+    fill_cstr(&mut req.UserID, &account.user_id);
+    fill_cstr(&mut req.AppID, &account.app_id);
+    fill_cstr(&mut req.AuthCode, &account.auth_code);
+    api.req_authenticate(&req, 1)
+        .map_err(|e| GatewayError::CtpError(format!("Failed to send authenticate request: {:?}", e)))
+}
+
+fn login(api: &mut TraderApi, account: &TraderLoginConfig) -> GatewayResult<()> {
+    let mut req = CThostFtdcReqUserLoginField::default();
+    fill_cstr(&mut req.BrokerID, &account.broker_id);
+    fill_cstr(&mut req.UserID, &account.user_id);
+    fill_cstr(&mut req.Password, &account.password);
+    api.req_user_login(&req, 2)
+        .map_err(|e| GatewayError::CtpError(format!("Failed to send login request: {:?}", e)))
+}
+
+/// 查询全部合约，被流控时等待后重试
+pub(crate) fn request_instruments(api: &mut TraderApi, deadline: Instant) -> GatewayResult<()> {
+    let req = CThostFtdcQryInstrumentField::default();
+    loop {
+        match api.req_qry_instrument(&req, 3) {
+            Ok(()) => return Ok(()),
+            Err(e) if Instant::now() + THROTTLE_RETRY < deadline => {
+                warn!("CTP instrument query rejected ({:?}), retrying", e);
+                std::thread::sleep(THROTTLE_RETRY);
+            }
+            Err(e) => {
+                return Err(GatewayError::CtpError(format!("Failed to send instrument query: {:?}", e)))
+            }
+        }
+    }
+}
+
+pub(crate) fn check_rsp(request: &str, result: &RspResult) -> GatewayResult<()> {
+    result.as_ref().map_err(|e| {
+        GatewayError::CtpError(format!("CTP {} failed: [{}] {}", request, e.id, e.msg))
+    })?;
+    Ok(())
+}
+
+/// 写入以0结尾的C字符串（超长时截断）
+pub(crate) fn fill_cstr(buffer: &mut [u8], text: &str) {
+    let len = text.len().min(buffer.len() - 1);
+    buffer[..len].copy_from_slice(&text.as_bytes()[..len]);
+    buffer[len] = 0;
+}
+
+fn cstr(bytes: &[u8]) -> String {
+    ascii_cstr_to_str(bytes).map(str::to_string).unwrap_or_default()
+}
+
+/// 转换CTP合约信息，组合合约等不支持的类型返回None
+#[allow(non_upper_case_globals)]
+pub(crate) fn instrument_info(field: &CThostFtdcInstrumentField) -> Option<InstrumentInfo> {
+    let product_class = match field.ProductClass {
+        THOST_FTDC_PC_Futures => "FUTURE",
+        THOST_FTDC_PC_Options | THOST_FTDC_PC_SpotOption => "OPTION",
+        THOST_FTDC_PC_Spot => "SPOT",
+        _ => return None,
+    };
+    let instrument_id = cstr(&field.InstrumentID);
+    if instrument_id.is_empty() {
+        return None;
+    }
+    let is_option = product_class == "OPTION";
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+    // CTP以DBL_MAX表示无效值
+    let ratio = |value: f64| normalize_double(value).filter(|v| v.is_finite() && *v >= 0.0);
+
+    Some(InstrumentInfo {
+        instrument_id,
+        exchange_id: cstr(&field.ExchangeID),
+        instrument_name: gb18030_cstr_to_str(&field.InstrumentName).into_owned(),
+        product_id: cstr(&field.ProductID),
+        product_class: product_class.to_string(),
+        price_tick: field.PriceTick,
+        volume_multiple: field.VolumeMultiple,
+        expire_date: non_empty(cstr(&field.ExpireDate)),
+        underlying_instrument: is_option.then(|| cstr(&field.UnderlyingInstrID)).and_then(non_empty),
+        strike_price: is_option.then(|| ratio(field.StrikePrice)).flatten(),
+        option_class: is_option
+            .then(|| (field.OptionsType as char).to_string())
+            .filter(|class| class != "\0"),
+        long_margin_ratio: ratio(field.LongMarginRatio),
+        short_margin_ratio: ratio(field.ShortMarginRatio),
+    })
+}
@@ -0,0 +1,414 @@
+//! 合约基础信息
+//!
+//! CTP行情API不提供合约查询，全市场合约列表从文件加载
+//! （例如由交易API的ReqQryInstrument导出），支持JSON数组或带表头的CSV。
+//!
+//! 客户端可以订阅合约组，由服务端展开为具体合约：
+//! - `ALL.SHFE`：上期所全部合约
+//! - `FUTURE.*`：全部期货合约
+//! - `OPTION.DCE`：大商所全部期权合约
+//!
+//! 也可以使用通配符模式（`*`匹配任意字符，`?`匹配单个字符）：
+//! - `au*`：合约代码以au开头（不含交易所前缀时只匹配合约代码部分）
+//! - `SHFE.*`：上期所全部合约
+//! - `*.rb2410`：任意交易所的rb2410
+
+use chrono::Utc;
+use hashbrown::HashMap;
+use qamd_rs::{OptionContract, OptionType, PricingModel};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::RwLock;
+
+use crate::calendar::{china_offset, parse_date, FUTURES_EXCHANGES};
+use crate::error::{GatewayError, GatewayResult};
+
+/// 合约基础信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstrumentInfo {
+    /// 合约代码（不含交易所前缀）
+    pub instrument_id: String,
+    /// 交易所代码
+    pub exchange_id: String,
+    /// 合约名称
+    #[serde(default)]
+    pub instrument_name: String,
+    /// 品种代码
+    #[serde(default)]
+    pub product_id: String,
+    /// 产品类型（FUTURE/OPTION/STOCK/ETF/...）
+    #[serde(default = "default_product_class")]
+    pub product_class: String,
+    /// 最小变动价位
+    pub price_tick: f64,
+    /// 合约乘数
+    pub volume_multiple: i32,
+    /// 到期日（YYYYMMDD）
+    #[serde(default)]
+    pub expire_date: Option<String>,
+    /// 期权标的合约代码
+    #[serde(default)]
+    pub underlying_instrument: Option<String>,
+    /// 期权行权价
+    #[serde(default)]
+    pub strike_price: Option<f64>,
+    /// 期权类型（C/CALL/P/PUT，或CTP的1/2）
+    #[serde(default)]
+    pub option_class: Option<String>,
+    /// 多头保证金率
+    #[serde(default)]
+    pub long_margin_ratio: Option<f64>,
+    /// 空头保证金率
+    #[serde(default)]
+    pub short_margin_ratio: Option<f64>,
+}
+
+impl InstrumentInfo {
+    /// 是否为期权合约
+    pub fn is_option(&self) -> bool {
+        self.product_class == "OPTION"
+    }
+
+    /// 价格小数位数（按最小变动价位计算）
+    pub fn price_decs(&self) -> i32 {
+        if !(self.price_tick.is_finite() && self.price_tick > 0.0) {
+            return 0;
+        }
+        let mut decs = 0;
+        let mut tick = self.price_tick;
+        while decs < 8 && (tick - tick.round()).abs() > 1e-9 {
+            tick *= 10.0;
+            decs += 1;
+        }
+        decs
+    }
+
+    /// 按给定价格计算的每手保证金（多空取较大的保证金率），没有保证金率时返回None
+    pub fn margin_per_lot(&self, price: f64) -> Option<f64> {
+        let ratio = match (self.long_margin_ratio, self.short_margin_ratio) {
+            (Some(long), Some(short)) => long.max(short),
+            (ratio, None) | (None, ratio) => ratio?,
+        };
+        Some(price * self.volume_multiple as f64 * ratio)
+    }
+
+    /// 期权类型
+    pub fn option_type(&self) -> Option<OptionType> {
+        match self.option_class.as_deref()? {
+            "1" => Some(OptionType::Call),
+            "2" => Some(OptionType::Put),
+            code => OptionType::from_code(code),
+        }
+    }
+
+    /// 期权合约条款（到期时间按到期日15:00收盘计算），非期权或信息不全时返回None
+    pub fn option_contract(&self, model: PricingModel) -> Option<OptionContract> {
+        if !self.is_option() {
+            return None;
+        }
+        let expire_date = parse_date(self.expire_date.as_deref()?).ok()?;
+        let expiry = expire_date
+            .and_hms_opt(EXPIRY_HOUR, 0, 0)?
+            .and_local_timezone(china_offset())
+            .single()?
+            .with_timezone(&Utc);
+        Some(OptionContract::new(self.option_type()?, self.strike_price?, expiry, model))
+    }
+}
+
+/// 期权到期日的收盘时间（北京时间）
+const EXPIRY_HOUR: u32 = 15;
+
+fn default_product_class() -> String {
+    "FUTURE".to_string()
+}
+
+/// 合约组前缀中的"全部类型"
+const GROUP_ALL: &str = "ALL";
+/// 合约组中的"全部交易所"
+const GROUP_ANY_EXCHANGE: &str = "*";
+
+/// 账户的保证金率（按金额的比例加上按手数的金额）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MarginRate {
+    /// 多头保证金率
+    pub long_ratio: f64,
+    /// 多头每手保证金
+    pub long_per_lot: f64,
+    /// 空头保证金率
+    pub short_ratio: f64,
+    /// 空头每手保证金
+    pub short_per_lot: f64,
+}
+
+impl MarginRate {
+    /// 按给定价格计算的每手保证金（多空取较大者）
+    pub fn per_lot(&self, price: f64, volume_multiple: i32) -> f64 {
+        let value = price * volume_multiple as f64;
+        (value * self.long_ratio + self.long_per_lot).max(value * self.short_ratio + self.short_per_lot)
+    }
+}
+
+/// 账户的手续费率（按金额的比例加上按手数的金额）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CommissionRate {
+    /// 开仓手续费率
+    pub open_ratio: f64,
+    /// 开仓每手手续费
+    pub open_per_lot: f64,
+    /// 平仓手续费率
+    pub close_ratio: f64,
+    /// 平仓每手手续费
+    pub close_per_lot: f64,
+    /// 平今手续费率
+    pub close_today_ratio: f64,
+    /// 平今每手手续费
+    pub close_today_per_lot: f64,
+}
+
+impl CommissionRate {
+    /// 按给定价格计算的每手开仓手续费
+    pub fn open_per_lot(&self, price: f64, volume_multiple: i32) -> f64 {
+        price * volume_multiple as f64 * self.open_ratio + self.open_per_lot
+    }
+}
+
+/// 从交易前置查询的合约费率，随交易日变化
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TradingRates {
+    /// 合约乘数
+    pub volume_multiple: i32,
+    /// 保证金率（未查询到时为None）
+    pub margin: Option<MarginRate>,
+    /// 手续费率（未查询到时为None）
+    pub commission: Option<CommissionRate>,
+}
+
+/// 合约信息注册表
+#[derive(Debug, Default)]
+pub struct InstrumentRegistry {
+    instruments: HashMap<String, InstrumentInfo>,
+    // 运行中从交易前置更新的费率 (合约代码 -> 费率)
+    rates: RwLock<HashMap<String, TradingRates>>,
+}
+
+impl InstrumentRegistry {
+    /// 创建空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从JSON（.json）或CSV文件加载合约信息
+    pub fn from_file<P: AsRef<Path>>(path: P) -> GatewayResult<Self> {
+        let path = path.as_ref();
+        let is_csv = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+        let instruments: Vec<InstrumentInfo> = if is_csv {
+            let mut reader = csv::Reader::from_path(path).map_err(|e| {
+                GatewayError::ConfigError(format!("Failed to open {}: {}", path.display(), e))
+            })?;
+            reader
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .map_err(|e| {
+                    GatewayError::ConfigError(format!("Failed to parse {}: {}", path.display(), e))
+                })?
+        } else {
+            let content = std::fs::read_to_string(path)?;
+            serde_json::from_str(&content)?
+        };
+
+        let mut registry = Self::new();
+        registry.extend(instruments);
+        Ok(registry)
+    }
+
+    /// 添加合约信息（已存在的合约会被覆盖）
+    pub fn extend(&mut self, instruments: impl IntoIterator<Item = InstrumentInfo>) {
+        for mut info in instruments {
+            info.exchange_id = info.exchange_id.to_uppercase();
+            info.product_class = info.product_class.to_uppercase();
+            self.instruments.insert(info.instrument_id.clone(), info);
+        }
+    }
+
+    /// 合约数量
+    pub fn len(&self) -> usize {
+        self.instruments.len()
+    }
+
+    /// 是否没有任何合约
+    pub fn is_empty(&self) -> bool {
+        self.instruments.is_empty()
+    }
+
+    /// 查询合约信息，支持带交易所前缀的代码（如"SHFE.rb2410"）
+    pub fn get(&self, instrument_id: &str) -> Option<&InstrumentInfo> {
+        self.instruments.get(instrument_id).or_else(|| {
+            instrument_id
+                .split_once('.')
+                .and_then(|(_, code)| self.instruments.get(code))
+        })
+    }
+
+    /// 更新合约费率（合约代码不含交易所前缀，已存在的会被覆盖），由交易前置的费率查询写入
+    #[cfg(feature = "ctp-trader-query")]
+    pub fn update_rates(&self, rates: impl IntoIterator<Item = (String, TradingRates)>) {
+        let mut table = self.rates.write().unwrap_or_else(|e| e.into_inner());
+        table.extend(rates);
+    }
+
+    /// 查询合约费率，支持带交易所前缀的代码
+    pub fn rates(&self, instrument_id: &str) -> Option<TradingRates> {
+        let table = self.rates.read().unwrap_or_else(|e| e.into_inner());
+        table.get(instrument_id).copied().or_else(|| {
+            instrument_id
+                .split_once('.')
+                .and_then(|(_, code)| table.get(code).copied())
+        })
+    }
+
+    /// 按给定价格计算的每手保证金，优先使用查询到的账户保证金率，其次是合约信息中的保证金率
+    pub fn margin_per_lot(&self, instrument_id: &str, price: f64) -> Option<f64> {
+        let queried = self
+            .rates(instrument_id)
+            .and_then(|rates| Some(rates.margin?.per_lot(price, rates.volume_multiple)));
+        queried.or_else(|| self.get(instrument_id)?.margin_per_lot(price))
+    }
+
+    /// 按给定价格计算的每手开仓手续费，没有查询到手续费率时返回None
+    pub fn commission_per_lot(&self, instrument_id: &str, price: f64) -> Option<f64> {
+        let rates = self.rates(instrument_id)?;
+        Some(rates.commission?.open_per_lot(price, rates.volume_multiple))
+    }
+
+    /// 期权合约条款及其标的合约代码
+    ///
+    /// 标的为期货时使用Black-76模型，否则使用Black-Scholes模型
+    pub fn option_contract(&self, instrument_id: &str) -> Option<(OptionContract, String)> {
+        let info = self.get(instrument_id)?;
+        let underlying = info.underlying_instrument.clone()?;
+        let is_futures_underlying = match self.get(&underlying) {
+            Some(underlying_info) => underlying_info.product_class == "FUTURE",
+            None => FUTURES_EXCHANGES.contains(&info.exchange_id.as_str()),
+        };
+        let model = if is_futures_underlying {
+            PricingModel::Black76
+        } else {
+            PricingModel::BlackScholes
+        };
+        Some((info.option_contract(model)?, underlying))
+    }
+
+    /// 全部合约
+    pub fn iter(&self) -> impl Iterator<Item = &InstrumentInfo> {
+        self.instruments.values()
+    }
+
+    /// 判断是否为合约组（如`ALL.SHFE`、`FUTURE.*`）
+    pub fn is_group(pattern: &str) -> bool {
+        Self::parse_group(pattern).is_some()
+    }
+
+    /// 解析合约组，返回(产品类型, 交易所)
+    fn parse_group(pattern: &str) -> Option<(&str, &str)> {
+        let (class, exchange) = pattern.split_once('.')?;
+        let is_class = class == GROUP_ALL
+            || matches!(class, "FUTURE" | "OPTION" | "STOCK" | "ETF" | "INDEX" | "BOND" | "SPOT");
+        is_class.then_some((class, exchange))
+    }
+
+    /// 展开合约组，返回组内全部合约代码（已排序）
+    pub fn expand_group(&self, pattern: &str) -> Vec<String> {
+        let Some((class, exchange)) = Self::parse_group(pattern) else {
+            return Vec::new();
+        };
+        let exchange = exchange.to_uppercase();
+
+        let mut instruments: Vec<String> = self
+            .instruments
+            .values()
+            .filter(|info| class == GROUP_ALL || info.product_class == class)
+            .filter(|info| exchange == GROUP_ANY_EXCHANGE || info.exchange_id == exchange)
+            .map(|info| info.instrument_id.clone())
+            .collect();
+        instruments.sort();
+        instruments
+    }
+
+    /// 匹配通配符模式的全部合约（带交易所前缀，已排序）
+    pub fn match_pattern(&self, pattern: &str) -> Vec<String> {
+        let mut instruments: Vec<String> = self
+            .instruments
+            .values()
+            .map(|info| format!("{}.{}", info.exchange_id, info.instrument_id))
+            .filter(|instrument| matches_pattern(pattern, instrument))
+            .collect();
+        instruments.sort();
+        instruments
+    }
+
+    /// 展开订阅列表中的合约组，普通合约原样保留，结果去重
+    pub fn expand(&self, patterns: &[String]) -> Vec<String> {
+        let mut result = Vec::new();
+        for pattern in patterns {
+            if Self::is_group(pattern) {
+                result.extend(self.expand_group(pattern));
+            } else {
+                result.push(pattern.clone());
+            }
+        }
+
+        let mut seen = hashbrown::HashSet::new();
+        result.retain(|instrument| seen.insert(instrument.clone()));
+        result
+    }
+}
+
+/// 是否为通配符模式（合约组如`FUTURE.*`除外）
+pub fn is_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?']) && !InstrumentRegistry::is_group(pattern)
+}
+
+/// 合约是否匹配通配符模式
+///
+/// 模式不含交易所前缀时只匹配合约代码部分，因此`au*`同时匹配`au2412`和`SHFE.au2412`
+pub fn matches_pattern(pattern: &str, instrument: &str) -> bool {
+    if !pattern.contains('.') {
+        if let Some((_, code)) = instrument.split_once('.') {
+            return glob_match(pattern.as_bytes(), code.as_bytes());
+        }
+    }
+    glob_match(pattern.as_bytes(), instrument.as_bytes())
+}
+
+/// 通配符匹配（`*`匹配任意个字符，`?`匹配单个字符）
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // 最近一个`*`的位置及其匹配到的文本位置，用于回溯
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
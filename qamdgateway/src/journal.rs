@@ -0,0 +1,422 @@
+//! Write-ahead journal of raw upstream messages
+//!
+//! Every CTP depth quote (and the raw payloads of the Binance and Sina HTTP feeds) is
+//! appended to a compact binary log before conversion, so conversion bugs can be
+//! reproduced offline against the captured data with the `qamdjournal` tool.
+//!
+//! A journal file starts with the magic `QAMDRAW1`, followed by records of
+//!
+//! | bytes | field |
+//! |-------|-------|
+//! | 4     | record length after this field (u32 LE) |
+//! | 8     | receive time in Unix microseconds (i64 LE) |
+//! | 1     | source |
+//! | 1     | payload kind: 1 CTP depth quote, 2 raw bytes |
+//! | ...   | payload |
+//!
+//! A CTP depth quote is stored field by field: the text fields with their full
+//! fixed-size buffers (including any garbage after the terminating NUL), then the
+//! prices and volumes in little endian. Files are named `raw-<UTC time>.qrj` and
+//! rotated by size.
+
+use chrono::Utc;
+use ctp_common::CThostFtdcDepthMarketDataField;
+use tracing::{info, warn};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::actors::messages::MarketDataSource;
+use crate::config::JournalConfig;
+use crate::error::{GatewayError, GatewayResult};
+
+/// Magic bytes at the start of every journal file
+const MAGIC: &[u8; 8] = b"QAMDRAW1";
+/// Extension of journal files
+const EXTENSION: &str = "qrj";
+/// Receive time, source and payload kind
+const RECORD_HEADER_LEN: usize = 10;
+/// Records beyond this length are treated as corruption
+const MAX_RECORD_LEN: usize = 16 * 1024 * 1024;
+
+const KIND_CTP_DEPTH: u8 = 1;
+const KIND_BYTES: u8 = 2;
+
+/// Generates the field-by-field encoding of a CTP depth quote
+macro_rules! depth_codec {
+    (text: [$($text:ident),*], float: [$($float:ident),*], int: [$($int:ident),*]) => {
+        fn encode_depth(data: &CThostFtdcDepthMarketDataField, out: &mut Vec<u8>) {
+            $(out.extend_from_slice(&data.$text);)*
+            $(out.extend_from_slice(&data.$float.to_le_bytes());)*
+            $(out.extend_from_slice(&data.$int.to_le_bytes());)*
+        }
+
+        fn decode_depth(mut bytes: &[u8]) -> Option<CThostFtdcDepthMarketDataField> {
+            let mut data = CThostFtdcDepthMarketDataField::default();
+            $(
+                let (head, rest) = bytes.split_at_checked(data.$text.len())?;
+                data.$text.copy_from_slice(head);
+                bytes = rest;
+            )*
+            $(
+                let (head, rest) = bytes.split_first_chunk::<8>()?;
+                data.$float = f64::from_le_bytes(*head);
+                bytes = rest;
+            )*
+            $(
+                let (head, rest) = bytes.split_first_chunk::<4>()?;
+                data.$int = i32::from_le_bytes(*head);
+                bytes = rest;
+            )*
+            bytes.is_empty().then_some(data)
+        }
+    };
+}
+
+depth_codec! {
+    text: [TradingDay, ActionDay, UpdateTime, ExchangeID, InstrumentID, ExchangeInstID],
+    float: [
+        LastPrice, PreSettlementPrice, PreClosePrice, PreOpenInterest, OpenPrice, HighestPrice,
+        LowestPrice, Turnover, OpenInterest, ClosePrice, SettlementPrice, UpperLimitPrice,
+        LowerLimitPrice, PreDelta, CurrDelta, BidPrice1, AskPrice1, BidPrice2, AskPrice2,
+        BidPrice3, AskPrice3, BidPrice4, AskPrice4, BidPrice5, AskPrice5, AveragePrice
+    ],
+    int: [
+        Volume, UpdateMillisec, BidVolume1, AskVolume1, BidVolume2, AskVolume2, BidVolume3,
+        AskVolume3, BidVolume4, AskVolume4, BidVolume5, AskVolume5
+    ]
+}
+
+fn source_code(source: MarketDataSource) -> u8 {
+    match source {
+        MarketDataSource::CTP => 0,
+        MarketDataSource::QQ => 1,
+        MarketDataSource::Sina => 2,
+        MarketDataSource::SinaHttp => 3,
+        MarketDataSource::Replay => 4,
+        MarketDataSource::Binance => 5,
+    }
+}
+
+fn source_from_code(code: u8) -> Option<MarketDataSource> {
+    match code {
+        0 => Some(MarketDataSource::CTP),
+        1 => Some(MarketDataSource::QQ),
+        2 => Some(MarketDataSource::Sina),
+        3 => Some(MarketDataSource::SinaHttp),
+        4 => Some(MarketDataSource::Replay),
+        5 => Some(MarketDataSource::Binance),
+        _ => None,
+    }
+}
+
+/// Payload of a journal record
+#[derive(Clone)]
+pub enum JournalPayload {
+    /// A depth quote of a CTP compatible API (CTP, QQ, Sina)
+    CtpDepth(Box<CThostFtdcDepthMarketDataField>),
+    /// A raw provider message, e.g. a Binance websocket frame or a Sina HTTP response body
+    Bytes(Vec<u8>),
+}
+
+/// A raw upstream message read back from the journal
+#[derive(Clone)]
+pub struct JournalRecord {
+    /// Receive time in Unix microseconds
+    pub received_us: i64,
+    pub source: MarketDataSource,
+    pub payload: JournalPayload,
+}
+
+/// The file currently written to
+struct JournalFile {
+    file: File,
+    path: PathBuf,
+    size: u64,
+}
+
+/// Appends raw upstream messages to size-rotated journal files
+///
+/// Each record is written with a single write call, so a crash loses at most the
+/// record being written and the reader stops at the truncated tail.
+pub struct RawJournal {
+    config: JournalConfig,
+    current: Mutex<Option<JournalFile>>,
+}
+
+impl RawJournal {
+    /// Create the journal directory and start a new journal file
+    pub fn open(config: JournalConfig) -> GatewayResult<Self> {
+        fs::create_dir_all(&config.dir).map_err(|e| {
+            GatewayError::ConfigError(format!("Cannot create journal directory {}: {}", config.dir, e))
+        })?;
+        let journal = Self {
+            config,
+            current: Mutex::new(None),
+        };
+        let file = journal.create_file()?;
+        info!("Journaling raw upstream messages to {}", file.path.display());
+        *journal.current.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+        Ok(journal)
+    }
+
+    /// Append a CTP depth quote received at `received_us` (Unix microseconds)
+    pub fn append_depth(
+        &self,
+        source: MarketDataSource,
+        received_us: i64,
+        data: &CThostFtdcDepthMarketDataField,
+    ) -> GatewayResult<()> {
+        let mut payload = Vec::with_capacity(std::mem::size_of::<CThostFtdcDepthMarketDataField>());
+        encode_depth(data, &mut payload);
+        self.append(source, received_us, KIND_CTP_DEPTH, &payload)
+    }
+
+    /// Append a raw provider message received at `received_us` (Unix microseconds)
+    pub fn append_bytes(&self, source: MarketDataSource, received_us: i64, bytes: &[u8]) -> GatewayResult<()> {
+        self.append(source, received_us, KIND_BYTES, bytes)
+    }
+
+    fn append(&self, source: MarketDataSource, received_us: i64, kind: u8, payload: &[u8]) -> GatewayResult<()> {
+        let len = RECORD_HEADER_LEN + payload.len();
+        if len > MAX_RECORD_LEN {
+            return Err(GatewayError::Other(format!("Journal record of {} bytes is too large", len)));
+        }
+        let mut record = Vec::with_capacity(4 + len);
+        record.extend_from_slice(&(len as u32).to_le_bytes());
+        record.extend_from_slice(&received_us.to_le_bytes());
+        record.push(source_code(source));
+        record.push(kind);
+        record.extend_from_slice(payload);
+
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let max_bytes = self.config.max_file_mb * 1024 * 1024;
+        let full = current
+            .as_ref()
+            .is_none_or(|file| max_bytes > 0 && file.size + record.len() as u64 > max_bytes);
+        if full {
+            // Without a usable file the record is dropped, the next append tries again
+            *current = None;
+            *current = Some(self.create_file()?);
+            self.remove_old_files();
+        }
+        let file = current.as_mut().expect("journal file is open");
+        file.file.write_all(&record)?;
+        file.size += record.len() as u64;
+        Ok(())
+    }
+
+    /// Start a new journal file named after the current time
+    fn create_file(&self) -> GatewayResult<JournalFile> {
+        let name = format!("raw-{}.{}", Utc::now().format("%Y%m%d-%H%M%S%.3f"), EXTENSION);
+        let path = Path::new(&self.config.dir).join(name);
+        let mut file = OpenOptions::new().create_new(true).append(true).open(&path)?;
+        file.write_all(MAGIC)?;
+        Ok(JournalFile {
+            file,
+            path,
+            size: MAGIC.len() as u64,
+        })
+    }
+
+    /// Delete the oldest journal files beyond `max_files`
+    fn remove_old_files(&self) {
+        if self.config.max_files == 0 {
+            return;
+        }
+        let files = match journal_files(Path::new(&self.config.dir)) {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("Failed to list journal directory {}: {}", self.config.dir, e);
+                return;
+            }
+        };
+        let excess = files.len().saturating_sub(self.config.max_files);
+        for path in &files[..excess] {
+            match fs::remove_file(path) {
+                Ok(()) => info!("Removed old journal file {}", path.display()),
+                Err(e) => warn!("Failed to remove journal file {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+/// Journal files in a directory, oldest first
+pub fn journal_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == EXTENSION)
+                && path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("raw-"))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Reads the records of a journal file in order
+pub struct JournalReader {
+    reader: BufReader<File>,
+    // Set after the end of the file or a corrupt record
+    done: bool,
+}
+
+impl JournalReader {
+    /// Open a journal file and check its magic
+    pub fn open<P: AsRef<Path>>(path: P) -> GatewayResult<Self> {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GatewayError::Other(format!("{} is not a journal file", path.display())));
+        }
+        Ok(Self { reader, done: false })
+    }
+
+    fn read_record(&mut self) -> GatewayResult<Option<JournalRecord>> {
+        let mut len = [0u8; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if !(RECORD_HEADER_LEN..=MAX_RECORD_LEN).contains(&len) {
+            return Err(GatewayError::Other(format!("Corrupt journal record length {}", len)));
+        }
+        let mut record = vec![0u8; len];
+        self.reader.read_exact(&mut record).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => GatewayError::Other("Truncated journal record".to_string()),
+            _ => e.into(),
+        })?;
+
+        let (header, payload) = record.split_at(RECORD_HEADER_LEN);
+        let received_us = i64::from_le_bytes(header[..8].try_into().expect("8 bytes"));
+        let source = source_from_code(header[8])
+            .ok_or_else(|| GatewayError::Other(format!("Unknown journal source {}", header[8])))?;
+        let payload = match header[9] {
+            KIND_CTP_DEPTH => decode_depth(payload)
+                .map(|data| JournalPayload::CtpDepth(Box::new(data)))
+                .ok_or_else(|| GatewayError::Other("Corrupt CTP depth quote in journal".to_string()))?,
+            KIND_BYTES => JournalPayload::Bytes(payload.to_vec()),
+            kind => return Err(GatewayError::Other(format!("Unknown journal payload kind {}", kind))),
+        };
+        Ok(Some(JournalRecord {
+            received_us,
+            source,
+            payload,
+        }))
+    }
+}
+
+impl Iterator for JournalReader {
+    type Item = GatewayResult<JournalRecord>;
+
+    /// Records in order, an error ends the iteration
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.read_record().transpose();
+        if !matches!(record, Some(Ok(_))) {
+            self.done = true;
+        }
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temporary journal directory of a test
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("qamd-journal-{}-{}", name, uuid::Uuid::new_v4()))
+    }
+
+    fn config(dir: &Path) -> JournalConfig {
+        JournalConfig {
+            enabled: true,
+            dir: dir.display().to_string(),
+            ..JournalConfig::default()
+        }
+    }
+
+    fn depth() -> CThostFtdcDepthMarketDataField {
+        let mut data = CThostFtdcDepthMarketDataField::default();
+        data.InstrumentID[..6].copy_from_slice(b"rb2410");
+        // Garbage after the terminating NUL is kept as received
+        data.InstrumentID[7] = b'x';
+        data.UpdateTime[..8].copy_from_slice(b"09:00:01");
+        data.LastPrice = 3500.0;
+        data.AskPrice1 = f64::MAX;
+        data.Volume = 120;
+        data.UpdateMillisec = 500;
+        data
+    }
+
+    #[test]
+    fn test_records_round_trip() {
+        let dir = temp_dir("round-trip");
+        let journal = RawJournal::open(config(&dir)).unwrap();
+        journal.append_depth(MarketDataSource::QQ, 1_000, &depth()).unwrap();
+        journal.append_bytes(MarketDataSource::Binance, 2_000, b"{\"e\":\"trade\"}").unwrap();
+
+        let files = journal_files(&dir).unwrap();
+        assert_eq!(files.len(), 1);
+        let records: Vec<JournalRecord> = JournalReader::open(&files[0]).unwrap().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].received_us, 1_000);
+        assert_eq!(records[0].source, MarketDataSource::QQ);
+        let JournalPayload::CtpDepth(data) = &records[0].payload else {
+            panic!("expected a CTP depth quote");
+        };
+        let expected = depth();
+        assert_eq!(data.InstrumentID, expected.InstrumentID);
+        assert_eq!(data.UpdateTime, expected.UpdateTime);
+        assert_eq!(data.LastPrice, 3500.0);
+        assert_eq!(data.AskPrice1, f64::MAX);
+        assert_eq!((data.Volume, data.UpdateMillisec), (120, 500));
+        assert_eq!(records[1].source, MarketDataSource::Binance);
+        assert!(matches!(&records[1].payload, JournalPayload::Bytes(bytes) if bytes == b"{\"e\":\"trade\"}"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reader_stops_at_truncated_tail() {
+        let dir = temp_dir("truncated");
+        let journal = RawJournal::open(config(&dir)).unwrap();
+        journal.append_bytes(MarketDataSource::SinaHttp, 1, b"first").unwrap();
+        journal.append_bytes(MarketDataSource::SinaHttp, 2, b"second").unwrap();
+        let path = journal_files(&dir).unwrap().remove(0);
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+
+        let mut reader = JournalReader::open(&path).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().received_us, 1);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+
+        let other = dir.join("raw-other.qrj");
+        fs::write(&other, b"NOTAJRNL").unwrap();
+        assert!(JournalReader::open(&other).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_source_codes_round_trip() {
+        for source in [
+            MarketDataSource::CTP,
+            MarketDataSource::QQ,
+            MarketDataSource::Sina,
+            MarketDataSource::SinaHttp,
+            MarketDataSource::Replay,
+            MarketDataSource::Binance,
+        ] {
+            assert_eq!(source_from_code(source_code(source)), Some(source));
+        }
+        assert_eq!(source_from_code(6), None);
+    }
+}
@@ -0,0 +1,295 @@
+//! 行情延迟追踪
+//!
+//! 开启追踪后，CTP类行情在每一跳记录时间戳（Unix微秒）：
+//! - `recv`：SPI回调收到行情
+//! - `conv`：转换为MDSnapshot后发给分发器
+//! - `dist`：分发器收到行情
+//! - `dispatch`：分发器把增量推送给客户端会话（或分片）
+//! - `ser`：客户端会话序列化消息
+//!
+//! 各跳的耗时汇总为直方图。追踪随行情JSON的`lat`字段从分发器传到会话，
+//! 只有开启`embed`时才保留在发给客户端的消息中。
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::config::LatencyConfig;
+
+/// 行情JSON中携带追踪的字段
+pub const LATENCY_FIELD: &str = "lat";
+
+/// 直方图的桶数，第i个桶为[2^i, 2^(i+1))微秒
+const BUCKETS: usize = 32;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static EMBED: AtomicBool = AtomicBool::new(false);
+
+/// 按配置开启或关闭追踪
+pub fn configure(config: &LatencyConfig) {
+    ENABLED.store(config.enabled, Ordering::Relaxed);
+    EMBED.store(config.enabled && config.embed, Ordering::Relaxed);
+}
+
+/// 是否开启追踪
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 是否在发给客户端的消息中保留追踪
+pub fn embeds() -> bool {
+    EMBED.load(Ordering::Relaxed)
+}
+
+/// 当前时间（Unix微秒）
+pub fn now_us() -> i64 {
+    Utc::now().timestamp_micros()
+}
+
+/// 一条行情各跳的时间戳（Unix微秒，0表示尚未经过）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyTrace {
+    pub recv: i64,
+    pub conv: i64,
+    #[serde(default)]
+    pub dist: i64,
+    #[serde(default)]
+    pub dispatch: i64,
+    #[serde(default)]
+    pub ser: i64,
+}
+
+impl LatencyTrace {
+    /// SPI回调收到、转换完成的行情
+    pub fn new(recv: i64) -> Self {
+        Self {
+            recv,
+            conv: now_us(),
+            ..Self::default()
+        }
+    }
+
+    /// 从行情JSON中取出追踪
+    pub fn take_from(data: &mut serde_json::Map<String, serde_json::Value>) -> Option<Self> {
+        data.remove(LATENCY_FIELD)
+            .and_then(|value| serde_json::from_value(value).ok())
+    }
+
+    /// 作为行情JSON的字段
+    pub fn to_value(self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// 分发器推送时记录前三跳的耗时
+    pub fn record_dispatch(&mut self) {
+        self.dispatch = now_us();
+        let metrics = metrics();
+        metrics.convert.record(self.conv - self.recv);
+        metrics.deliver.record(self.dist - self.conv);
+        metrics.batch.record(self.dispatch - self.dist);
+    }
+
+    /// 会话序列化时记录最后一跳和全程的耗时
+    pub fn record_serialize(&mut self) {
+        self.ser = now_us();
+        let metrics = metrics();
+        metrics.send.record(self.ser - self.dispatch);
+        metrics.total.record(self.ser - self.recv);
+    }
+}
+
+/// 按2的幂分桶的耗时直方图（微秒）
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+/// 直方图的一个桶
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucket {
+    /// 桶的上界（微秒）
+    pub le_us: u64,
+    pub count: u64,
+}
+
+/// 直方图统计（分位数为所在桶的上界）
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramSummary {
+    pub count: u64,
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+    /// 非空的桶
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    /// 记录一次耗时（时钟回拨产生的负值按0计）
+    pub fn record(&self, micros: i64) {
+        let micros = micros.max(0) as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()).saturating_sub(1) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(micros, Ordering::Relaxed);
+        self.max.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// 当前统计
+    pub fn summary(&self) -> HistogramSummary {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let count: u64 = counts.iter().sum();
+        let quantile = |q: f64| {
+            let target = (count as f64 * q).ceil() as u64;
+            let mut seen = 0;
+            for (i, &n) in counts.iter().enumerate() {
+                seen += n;
+                if seen >= target.max(1) {
+                    return 1u64 << (i + 1);
+                }
+            }
+            0
+        };
+        HistogramSummary {
+            count,
+            mean_us: if count > 0 {
+                self.sum.load(Ordering::Relaxed) as f64 / count as f64
+            } else {
+                0.0
+            },
+            p50_us: if count > 0 { quantile(0.5) } else { 0 },
+            p90_us: if count > 0 { quantile(0.9) } else { 0 },
+            p99_us: if count > 0 { quantile(0.99) } else { 0 },
+            max_us: self.max.load(Ordering::Relaxed),
+            buckets: counts
+                .iter()
+                .enumerate()
+                .filter(|(_, &n)| n > 0)
+                .map(|(i, &n)| HistogramBucket { le_us: 1u64 << (i + 1), count: n })
+                .collect(),
+        }
+    }
+}
+
+/// 各跳的耗时直方图
+pub struct LatencyMetrics {
+    /// SPI回调到转换完成
+    pub convert: Histogram,
+    /// 转换完成到分发器收到
+    pub deliver: Histogram,
+    /// 分发器收到到推送（批量合并的等待）
+    pub batch: Histogram,
+    /// 分发器推送到会话序列化（每个客户端记录一次）
+    pub send: Histogram,
+    /// SPI回调到会话序列化
+    pub total: Histogram,
+}
+
+/// 延迟统计报告
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    pub enabled: bool,
+    pub convert: HistogramSummary,
+    pub deliver: HistogramSummary,
+    pub batch: HistogramSummary,
+    pub send: HistogramSummary,
+    pub total: HistogramSummary,
+}
+
+impl LatencyMetrics {
+    /// 当前统计
+    pub fn report(&self) -> LatencyReport {
+        LatencyReport {
+            enabled: is_enabled(),
+            convert: self.convert.summary(),
+            deliver: self.deliver.summary(),
+            batch: self.batch.summary(),
+            send: self.send.summary(),
+            total: self.total.summary(),
+        }
+    }
+}
+
+/// 进程内共享的延迟直方图
+pub fn metrics() -> &'static LatencyMetrics {
+    static METRICS: OnceLock<LatencyMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| LatencyMetrics {
+        convert: Histogram::new(),
+        deliver: Histogram::new(),
+        batch: Histogram::new(),
+        send: Histogram::new(),
+        total: Histogram::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_histogram_buckets_and_quantiles() {
+        let histogram = Histogram::new();
+        let empty = histogram.summary();
+        assert_eq!((empty.count, empty.p50_us, empty.max_us), (0, 0, 0));
+        assert_eq!(empty.mean_us, 0.0);
+
+        // 负值（时钟回拨）按0计
+        histogram.record(-5);
+        for micros in [1, 3, 100, 100, 100, 100, 100, 100, 100, 5000] {
+            histogram.record(micros);
+        }
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 11);
+        assert_eq!(summary.max_us, 5000);
+        assert_eq!(summary.mean_us, 5704.0 / 11.0);
+        assert_eq!(summary.p50_us, 128);
+        assert_eq!(summary.p90_us, 128);
+        assert_eq!(summary.p99_us, 8192);
+        let buckets: Vec<(u64, u64)> = summary.buckets.iter().map(|b| (b.le_us, b.count)).collect();
+        assert_eq!(buckets, [(2, 2), (4, 1), (128, 7), (8192, 1)]);
+    }
+
+    #[test]
+    fn test_trace_travels_in_quote_json() {
+        let trace = LatencyTrace {
+            recv: 1,
+            conv: 2,
+            dist: 3,
+            ..LatencyTrace::default()
+        };
+        let mut quote = json!({"last_price": 3500.0}).as_object().cloned().unwrap();
+        quote.insert(LATENCY_FIELD.to_string(), trace.to_value());
+        assert_eq!(LatencyTrace::take_from(&mut quote), Some(trace));
+        assert!(!quote.contains_key(LATENCY_FIELD));
+        assert_eq!(LatencyTrace::take_from(&mut quote), None);
+
+        // 只有前两跳的追踪也能解析
+        quote.insert(LATENCY_FIELD.to_string(), json!({"recv": 1, "conv": 2}));
+        assert_eq!(LatencyTrace::take_from(&mut quote).unwrap().dist, 0);
+    }
+
+    #[test]
+    fn test_recording_hops_updates_metrics() {
+        let before = metrics().total.summary().count;
+        let mut trace = LatencyTrace::new(now_us() - 10);
+        trace.dist = now_us();
+        trace.record_dispatch();
+        trace.record_serialize();
+        assert!(trace.conv >= trace.recv && trace.ser >= trace.dispatch && trace.dispatch >= trace.dist);
+        assert!(metrics().total.summary().count > before);
+    }
+}
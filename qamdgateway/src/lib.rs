@@ -3,18 +3,47 @@
 //! 这是一个市场数据网关，支持多种数据源，并提供统一的WebSocket接口。
 //! 
 //! 主要功能：
-//! 1. 支持CTP、QQ、Sina等市场数据源
+//! 1. 支持CTP、QQ、Sina等市场数据源，可在同一进程中同时运行
 //! 2. 提供统一的WebSocket接口
 //! 3. 支持TradingView格式的消息
 
+pub mod acl;
 pub mod actors;
+pub mod alerts;
+pub mod analytics;
+pub mod calendar;
+pub mod circuit_breaker;
+pub mod clock_skew;
 pub mod config;
 pub mod converter;
+pub mod dominant;
 pub mod error;
+pub mod gateway;
+pub mod instruments;
+pub mod journal;
+pub mod latency;
+pub mod load_sharing;
+pub mod logging;
+pub mod naming;
+pub mod price_check;
+pub mod quote_encoding;
+#[cfg(feature = "ctp-instruments")]
+pub mod instrument_query;
+pub mod sinks;
+#[cfg(feature = "socketio")]
+pub mod socketio;
+pub mod sources;
+pub mod supervision;
+pub mod symbols;
+pub mod synthetic;
+pub mod tick_archive;
+pub mod trade_dates;
 pub mod ws_server;
 
 /// 重新导出qamd_rs中的类型
 pub use qamd_rs::MDSnapshot;
+/// 在其他Rust程序中内嵌网关
+pub use gateway::{Gateway, GatewayBuilder};
 
 /// 预导入模块，提供常用类型
 pub mod prelude {
@@ -22,14 +51,7 @@ pub mod prelude {
     pub use crate::actors::md_actor::MarketDataActor;
     pub use crate::actors::md_distributor::MarketDataDistributor;
     pub use crate::config::BrokerConfig;
+    pub use crate::gateway::{Gateway, GatewayBuilder};
+    pub use crate::sources::{create_adapter, MarketDataSourceAdapter};
     pub use crate::ws_server::ws_handler;
-
-    #[cfg(feature = "ctp")]
-    pub use crate::actors::messages::{RegisterCTPMdActor, MarketDataSource};
-
-    #[cfg(feature = "qq")]
-    pub use crate::actors::messages::{RegisterQQMdActor, MarketDataSource};
-
-    #[cfg(feature = "sina")]
-    pub use crate::actors::messages::{RegisterSinaMdActor, MarketDataSource};
 } 
\ No newline at end of file
@@ -0,0 +1,145 @@
+//! HTTP/WebSocket listener setup
+//!
+//! Every configured listener gets its own server so it can expose the
+//! WebSocket endpoint under its own path. Sockets are opened here, before the
+//! servers start, so a bad address or certificate fails the launch.
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+
+use crate::config::{ListenerConfig, RestApiConfig, TlsConfig};
+use crate::error::{GatewayError, GatewayResult};
+
+/// Address prefix selecting a unix domain socket
+const UNIX_PREFIX: &str = "unix:";
+
+/// Pending connections per TCP listener
+const BACKLOG: i32 = 1024;
+
+/// A bound socket ready to be handed to the HTTP server
+pub enum BoundSocket {
+    /// Plain HTTP/WS
+    Tcp(TcpListener),
+    /// HTTPS/WSS
+    #[cfg(feature = "tls")]
+    Tls(TcpListener, Box<rustls::ServerConfig>),
+    /// Unix domain socket
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+/// Configured listeners, or a single one on rest_api host/port when none are set
+pub fn resolve(rest_api: &RestApiConfig) -> Vec<ListenerConfig> {
+    if !rest_api.listeners.is_empty() {
+        return rest_api.listeners.clone();
+    }
+    let address = if rest_api.host.contains(':') && !rest_api.host.starts_with('[') {
+        format!("[{}]:{}", rest_api.host, rest_api.port)
+    } else {
+        format!("{}:{}", rest_api.host, rest_api.port)
+    };
+    vec![ListenerConfig {
+        address,
+        ws_path: None,
+        dual_stack: false,
+        tls: None,
+    }]
+}
+
+/// Open the sockets of a listener, one per resolved address
+pub fn open(listener: &ListenerConfig) -> GatewayResult<Vec<BoundSocket>> {
+    if let Some(path) = listener.address.strip_prefix(UNIX_PREFIX) {
+        if listener.tls.is_some() {
+            return Err(GatewayError::ConfigError(format!(
+                "TLS is not supported on unix socket listener {}",
+                listener.address
+            )));
+        }
+        return open_unix(path).map(|socket| vec![socket]);
+    }
+
+    let addrs: Vec<SocketAddr> = listener
+        .address
+        .to_socket_addrs()
+        .map_err(|e| {
+            GatewayError::ConfigError(format!("Invalid listener address {}: {}", listener.address, e))
+        })?
+        .collect();
+    if addrs.is_empty() {
+        return Err(GatewayError::ConfigError(format!(
+            "Listener address {} did not resolve",
+            listener.address
+        )));
+    }
+
+    let tls = listener.tls.as_ref().map(load_tls_config).transpose()?;
+    let mut sockets = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        let tcp = tcp_listener(addr, listener.dual_stack)?;
+        sockets.push(match &tls {
+            #[cfg(feature = "tls")]
+            Some(config) => BoundSocket::Tls(tcp, Box::new(config.clone())),
+            #[cfg(not(feature = "tls"))]
+            Some(never) => match *never {},
+            None => BoundSocket::Tcp(tcp),
+        });
+    }
+    Ok(sockets)
+}
+
+/// Bind a TCP socket; IPv6 sockets are v6-only unless dual_stack is set, so an
+/// IPv4 listener can share the port
+fn tcp_listener(addr: SocketAddr, dual_stack: bool) -> GatewayResult<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket
+        .bind(&addr.into())
+        .map_err(|e| GatewayError::ConfigError(format!("Failed to bind {}: {}", addr, e)))?;
+    socket.listen(BACKLOG)?;
+    Ok(socket.into())
+}
+
+/// Bind a unix socket, replacing a stale socket file left by a previous run
+#[cfg(unix)]
+fn open_unix(path: &str) -> GatewayResult<BoundSocket> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(GatewayError::ConfigError(format!(
+                "Unix socket path {} exists and is not a socket",
+                path
+            )));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| GatewayError::ConfigError(format!("Failed to bind unix:{}: {}", path, e)))?;
+    Ok(BoundSocket::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn open_unix(path: &str) -> GatewayResult<BoundSocket> {
+    Err(GatewayError::ConfigError(format!(
+        "Unix socket listener unix:{} is not supported on this platform",
+        path
+    )))
+}
+
+/// Build the TLS configuration of a listener, its certificate reloads on change
+#[cfg(feature = "tls")]
+fn load_tls_config(tls: &TlsConfig) -> GatewayResult<rustls::ServerConfig> {
+    crate::tls::server_config(tls)
+}
+
+#[cfg(not(feature = "tls"))]
+fn load_tls_config(tls: &TlsConfig) -> GatewayResult<std::convert::Infallible> {
+    Err(GatewayError::Other(format!(
+        "TLS listener with certificate {} requires the `tls` feature",
+        tls.cert_file
+    )))
+}
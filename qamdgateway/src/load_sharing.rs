@@ -0,0 +1,236 @@
+//! 多账户行情负载分担
+//!
+//! 单个CTP行情账户的订阅数有限。开启负载分担后，参与分担的账户组成一致性哈希环，
+//! 每个合约只由环上负责它的账户订阅。账户断线超过宽限期或被移除后从环上摘除，
+//! 它负责的合约转移到其他账户；账户恢复后重新加入，只有原本属于它的合约迁回。
+
+use hashbrown::HashSet;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// 稳定的字符串哈希（进程重启后分配不变）
+///
+/// FNV-1a之后再做一次MurmurHash3的最终混合，只差一两个字符的合约代码也能分散到环上各处
+fn stable_hash(key: &str) -> u64 {
+    let mut hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// 一致性哈希环，每个节点在环上放置多个虚拟节点使分配更均匀
+#[derive(Debug, Clone, Default)]
+pub struct HashRing {
+    virtual_nodes: usize,
+    ring: BTreeMap<u64, String>,
+}
+
+impl HashRing {
+    pub fn new(virtual_nodes: usize) -> Self {
+        Self {
+            virtual_nodes: virtual_nodes.max(1),
+            ring: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, node: &str) {
+        for replica in 0..self.virtual_nodes {
+            self.ring.insert(stable_hash(&format!("{}#{}", node, replica)), node.to_string());
+        }
+    }
+
+    /// 负责key的节点：环上顺时针方向的第一个虚拟节点
+    pub fn node_for(&self, key: &str) -> Option<&str> {
+        let hash = stable_hash(key);
+        self.ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, node)| node.as_str())
+    }
+}
+
+/// 账户的分担状态（用于状态接口）
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadSharingAccount {
+    pub broker_id: String,
+    /// 是否在哈希环上
+    pub live: bool,
+    /// 分配到的活跃合约数
+    pub instruments: usize,
+}
+
+/// 合约在账户之间的分配
+#[derive(Debug, Clone)]
+pub struct LoadSharing {
+    // 参与分担的全部账户
+    accounts: Vec<String>,
+    // 在哈希环上的账户
+    live: HashSet<String>,
+    ring: HashRing,
+    virtual_nodes: usize,
+}
+
+impl LoadSharing {
+    /// 所有账户初始都在环上
+    pub fn new(accounts: Vec<String>, virtual_nodes: usize) -> Self {
+        let mut ring = HashRing::new(virtual_nodes);
+        for account in &accounts {
+            ring.insert(account);
+        }
+        Self {
+            live: accounts.iter().cloned().collect(),
+            accounts,
+            ring,
+            virtual_nodes,
+        }
+    }
+
+    /// 是否参与分担
+    pub fn is_member(&self, broker_id: &str) -> bool {
+        self.accounts.iter().any(|account| account == broker_id)
+    }
+
+    pub fn accounts(&self) -> &[String] {
+        &self.accounts
+    }
+
+    pub fn is_live(&self, broker_id: &str) -> bool {
+        self.live.contains(broker_id)
+    }
+
+    /// 更新环上的账户，返回是否发生变化
+    ///
+    /// 没有可用账户时保留全部账户，合约仍按原分配等待账户恢复
+    pub fn set_live(&mut self, live: &[String]) -> bool {
+        let mut next: HashSet<String> = live
+            .iter()
+            .filter(|broker_id| self.is_member(broker_id))
+            .cloned()
+            .collect();
+        if next.is_empty() {
+            next = self.accounts.iter().cloned().collect();
+        }
+        if next == self.live {
+            return false;
+        }
+        let mut ring = HashRing::new(self.virtual_nodes);
+        for account in &next {
+            ring.insert(account);
+        }
+        self.ring = ring;
+        self.live = next;
+        true
+    }
+
+    /// 负责合约的账户
+    pub fn owner(&self, instrument: &str) -> Option<&str> {
+        self.ring.node_for(instrument)
+    }
+
+    /// 上游连接是否应订阅该合约（不参与分担的连接订阅全部合约）
+    pub fn owns(&self, broker_id: &str, instrument: &str) -> bool {
+        !self.is_member(broker_id) || self.owner(instrument) == Some(broker_id)
+    }
+
+    /// 各账户的分担状态
+    pub fn status<'a>(&self, instruments: impl IntoIterator<Item = &'a String>) -> Vec<LoadSharingAccount> {
+        let mut counts: hashbrown::HashMap<&str, usize> = hashbrown::HashMap::new();
+        for instrument in instruments {
+            if let Some(owner) = self.owner(instrument) {
+                *counts.entry(owner).or_default() += 1;
+            }
+        }
+        self.accounts
+            .iter()
+            .map(|account| LoadSharingAccount {
+                broker_id: account.clone(),
+                live: self.is_live(account),
+                instruments: counts.get(account.as_str()).copied().unwrap_or(0),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts() -> Vec<String> {
+        ["9999", "8888", "7777"].iter().map(|s| s.to_string()).collect()
+    }
+
+    fn instruments() -> Vec<String> {
+        (0..300).map(|i| format!("SHFE.rb{}", 2400 + i)).collect()
+    }
+
+    fn owners(sharing: &LoadSharing, instruments: &[String]) -> Vec<String> {
+        instruments
+            .iter()
+            .map(|instrument| sharing.owner(instrument).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_every_account_gets_instruments() {
+        let sharing = LoadSharing::new(accounts(), 64);
+        let instruments = instruments();
+        for account in sharing.status(&instruments) {
+            assert!(account.live);
+            assert!(account.instruments > 0, "{} owns nothing", account.broker_id);
+        }
+        assert!(sharing.owns("1234", "SHFE.rb2410"));
+    }
+
+    #[test]
+    fn test_remove_and_recover_moves_only_affected_instruments() {
+        let mut sharing = LoadSharing::new(accounts(), 64);
+        let instruments = instruments();
+        let before = owners(&sharing, &instruments);
+
+        // 8888断线：只有它的合约转移，其他合约不动
+        assert!(sharing.set_live(&["9999".to_string(), "7777".to_string()]));
+        assert!(!sharing.is_live("8888"));
+        let degraded = owners(&sharing, &instruments);
+        for (old, new) in before.iter().zip(&degraded) {
+            assert_ne!(new, "8888");
+            if old != "8888" {
+                assert_eq!(old, new);
+            }
+        }
+
+        // 8888恢复：只有原本属于它的合约迁回，分配与断线前一致
+        assert!(sharing.set_live(&accounts()));
+        let recovered = owners(&sharing, &instruments);
+        for ((old, degraded), new) in before.iter().zip(&degraded).zip(&recovered) {
+            assert_eq!(old, new);
+            if old != "8888" {
+                assert_eq!(degraded, new);
+            }
+        }
+        assert!(!sharing.set_live(&accounts()));
+    }
+
+    #[test]
+    fn test_all_accounts_down_keeps_assignment() {
+        let mut sharing = LoadSharing::new(accounts(), 64);
+        let instruments = instruments();
+        let before = owners(&sharing, &instruments);
+
+        // 全部断线时保留所有账户，分配不变
+        assert!(!sharing.set_live(&[]));
+        assert!(!sharing.set_live(&["1234".to_string()]));
+        assert!(accounts().iter().all(|account| sharing.is_live(account)));
+        assert_eq!(owners(&sharing, &instruments), before);
+
+        // 先剩一个账户，再全部断线时回到全部账户
+        assert!(sharing.set_live(&["7777".to_string()]));
+        assert!(instruments.iter().all(|instrument| sharing.owns("7777", instrument)));
+        assert!(sharing.set_live(&[]));
+        assert_eq!(owners(&sharing, &instruments), before);
+    }
+}
@@ -1,11 +1,61 @@
-use log::{info, LevelFilter};
-
-/// 初始化日志系统
-pub fn init_logger() {
-    // 使用env_logger初始化日志
-    env_logger::Builder::new()
-        .filter_level(LevelFilter::Info)
-        .init();
-    
-    info!("Logger initialized");
-} 
\ No newline at end of file
+//! 日志输出与逐笔日志采样
+//!
+//! 日志通过tracing输出，依赖库经`log`输出的日志也一并转发。日志级别取`RUST_LOG`，未设置时使用配置的`log_level`。
+//! 客户端会话和上游连接的日志分别处在`session`（client_id）和`upstream`（broker_id、source、front）span中，
+//! `logging.format`为`json`时每行输出一个JSON对象，包含所在span的字段。
+//!
+//! 逐笔行情的debug日志按合约采样：每个合约的第1笔以及此后每`tick_sample_rate`笔输出一笔。
+
+use hashbrown::HashMap;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogFormat, LoggingConfig};
+use crate::error::{GatewayError, GatewayResult};
+
+/// 逐笔日志的采样间隔（0为不输出）
+static TICK_SAMPLE_RATE: AtomicU64 = AtomicU64::new(1000);
+
+/// 初始化日志输出（进程内只能调用一次）
+pub fn init(config: &LoggingConfig, default_level: &str) -> GatewayResult<()> {
+    TICK_SAMPLE_RATE.store(config.tick_sample_rate, Ordering::Relaxed);
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(default_level))
+        .map_err(|e| GatewayError::ConfigError(format!("Invalid log level {}: {}", default_level, e)))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    let result = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(true).try_init(),
+    };
+    result.map_err(|e| GatewayError::ConfigError(format!("Failed to initialize logging: {}", e)))
+}
+
+/// 按合约对逐笔日志采样
+#[derive(Debug, Default)]
+pub struct TickLogSampler {
+    // 合约 -> 已收到的笔数
+    counts: HashMap<String, u64>,
+}
+
+impl TickLogSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 这一笔是否输出日志，输出时返回该合约累计的笔数
+    ///
+    /// 调用方先用`tracing::enabled!`检查所在模块是否开启了debug级别，未开启时不必计数
+    pub fn sample(&mut self, instrument: &str) -> Option<u64> {
+        let rate = TICK_SAMPLE_RATE.load(Ordering::Relaxed);
+        if rate == 0 {
+            return None;
+        }
+        let count = self.counts.entry_ref(instrument).or_insert(0);
+        *count += 1;
+        ((*count - 1) % rate == 0).then_some(*count)
+    }
+}
@@ -1,69 +1,512 @@
+mod acl;
+mod admin_ws;
+mod alerts;
+mod analytics;
 mod api;
+mod calendar;
+mod circuit_breaker;
+mod clock_skew;
 mod config;
 mod converter;
+mod dominant;
 mod error;
+mod instruments;
+// The journal reader is only used by the qamdjournal tool
+#[allow(dead_code)]
+mod journal;
+mod latency;
+mod load_sharing;
+mod logging;
+mod listeners;
+mod naming;
+mod price_check;
+mod quote_encoding;
+#[cfg(feature = "ctp-instruments")]
+mod instrument_query;
+mod sinks;
+#[cfg(feature = "socketio")]
+mod socketio;
+mod sources;
+mod supervision;
+mod symbols;
+mod synthetic;
+mod tick_archive;
+mod trade_dates;
+#[cfg(feature = "tls")]
+mod tls;
 // mod md_source; // Deprecated - using actors instead
 mod ws_server;
 mod actors;
 
 use actix_cors::Cors;
 use actix_web::{middleware, web, App, HttpServer};
-use log::info;
-use std::time::Instant;
+use tracing::{error, info, warn};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use actix_rt;
 
+use crate::acl::AclStore;
 use crate::api::{configure_routes, AppState};
-use crate::config::Config;
+use crate::calendar::TradingCalendar;
+use crate::instruments::InstrumentRegistry;
+use crate::symbols::SymbolTable;
+use crate::listeners::BoundSocket;
+use crate::config::{Config, ReplicationRole};
+use crate::converter::DeadLetterLog;
+use crate::journal::RawJournal;
 use crate::error::GatewayResult;
-use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::md_distributor::{DistributorState, MarketDataDistributor};
+use crate::actors::alert_engine::AlertEngine;
+use crate::actors::depth_engine::DepthEngine;
+use crate::actors::dominant_actor::DominantActor;
+use crate::actors::indicator_engine::IndicatorEngine;
+use crate::actors::quota_monitor::QuotaMonitor;
+use crate::actors::eod_builder::EodBarBuilder;
+use crate::actors::config_reloader::ConfigReloader;
+use crate::actors::data_quality::DataQualityActor;
+use crate::actors::trading_phase::TradingPhaseActor;
+use crate::actors::watchlist_store::WatchlistStore;
+use crate::actors::profile_store::ProfileStore;
+use crate::actors::md_stats::MarketDataStatsActor;
+use crate::actors::messages::{
+    RegisterSnapshotSink, ReloadConfig, RestoreDistributorState, ResubscribeProgress,
+    SaveDistributorState, SubscribePhase,
+};
+use crate::actors::binance_actor::BinanceMarketDataActor;
 use crate::actors::md_connector::MarketDataConnector;
 use crate::actors::md_actor::MarketDataActor;
+use crate::actors::mock_actor::MockMarketDataActor;
+use crate::actors::replay_actor::ReplayMarketDataActor;
+use crate::actors::replication::{replication_handler, StandbyActor};
+use crate::actors::sina_http_poller::SinaHttpPollerActor;
+use crate::actors::synthetic_actor::SyntheticActor;
+use crate::actors::warmup_scheduler::WarmupScheduler;
+use crate::actors::tick_history::TickHistoryActor;
+use crate::actors::tick_recorder::TickRecorderActor;
+use crate::tick_archive::TickArchive;
+use crate::sinks::{start_ipc_server, start_redis_bridge, start_zmq_publisher};
+use crate::dominant::is_dominant;
+use crate::synthetic::is_synthetic;
 
 #[actix_rt::main]
 async fn main() -> GatewayResult<()> {
-    // Initialize logger
-    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
-    
-    // Load configuration
+    // Load configuration, which also selects the log level and format
     let config = Config::load()?;
+    logging::init(&config.logging, &config.log_level)?;
     info!("Configuration loaded");
     
+    // Per-hop latency tracing is process-wide and fixed at startup
+    latency::configure(&config.latency);
+    if config.latency.enabled {
+        info!("Latency tracing enabled (embed: {})", config.latency.embed);
+    }
+    
+    // Load instrument reference data
+    let mut instrument_registry = match &config.subscription.instruments_file {
+        Some(path) => {
+            let registry = InstrumentRegistry::from_file(path)?;
+            info!("Loaded {} instruments from {}", registry.len(), path);
+            registry
+        }
+        None => InstrumentRegistry::new(),
+    };
+    // Queried instruments override the file, which keeps serving if the query fails
+    if let Some(query_config) = config.instrument_query.clone().filter(|q| q.enabled) {
+        query_instruments(&query_config, &mut instrument_registry).await;
+    }
+    if instrument_registry.is_empty() {
+        warn!("No instrument reference data loaded, groups such as ALL.SHFE will not expand");
+    }
+    let instrument_registry = Arc::new(instrument_registry);
+    // Margin and commission rates of the account, refreshed in the background
+    if let Some(trader_query) = config.trader_query.clone().filter(|q| q.enabled) {
+        start_trader_query(trader_query, instrument_registry.clone());
+    }
+    
+    // Equity symbol search, extended by the downloaded table once it arrives
+    let symbol_table = Arc::new(SymbolTable::load(&config.symbols, &instrument_registry)?);
+    symbols::spawn_refresh(symbol_table.clone(), &config.symbols);
+    
     // Create the market data distributor actor
-    let md_distributor = actix::Actor::start(MarketDataDistributor::default());
+    let md_distributor = actix::Actor::start(
+        MarketDataDistributor::new()
+            .with_failover(config.failover.clone())
+            .with_options(config.options.clone(), instrument_registry.clone())
+            .with_max_pattern_matches(config.subscription.max_pattern_matches)
+            .with_resume_grace(Duration::from_secs(config.websocket.resume_grace_secs))
+            .with_patch_refresh(Duration::from_secs(config.distributor.patch_refresh_secs))
+            .with_unsubscribe_linger(
+                Duration::from_secs(config.distributor.unsubscribe_linger_secs),
+                config.distributor.cache_retention_secs.map(Duration::from_secs),
+            )
+            .with_clock_skew(config.clock_skew.clone())
+            .with_price_check(config.price_check.clone())
+            .with_replay_depth(config.distributor.replay_depth)
+            .with_fanout_shards(config.distributor.fanout_shards),
+    );
     info!("Market data distributor initialized");
     
-    // Get broker configurations
-    let broker_config = config.get_broker(None)?;
-    let broker_configs = vec![broker_config.clone()];
+    // Compute spreads and baskets subscribed as synthetic instruments
+    actix::Actor::start(SyntheticActor::new(md_distributor.clone()));
+    
+    // Map continuous contracts (e.g. SHFE.rb.HOT) to the month with the highest open interest
+    actix::Actor::start(DominantActor::new(
+        md_distributor.clone(),
+        instrument_registry.clone(),
+        config.dominant.clone(),
+    ));
+    
+    // Get broker configurations, each broker selects its own market data source
+    let mut all_broker_configs = config.active_broker_configs()?;
+
+    // In replay mode historical data replaces the upstream brokers
+    let replay = config.replay.clone().filter(|r| r.enabled).map(|replay_config| {
+        info!("Replay mode enabled, reading from {}", replay_config.path);
+        actix::Actor::start(ReplayMarketDataActor::new(replay_config, md_distributor.clone()))
+    });
+    if replay.is_some() {
+        all_broker_configs.clear();
+    }
+    
+    // Mock mode generates random-walk ticks instead of connecting the brokers
+    if let Some(mock_config) = config.mock.clone().filter(|m| m.enabled) {
+        info!("Mock mode enabled for {} instruments", mock_config.instruments.len());
+        actix::Actor::start(MockMarketDataActor::new(mock_config, md_distributor.clone()));
+        all_broker_configs.clear();
+    }
+    
+    // Get default subscriptions (instrument groups are expanded)
+    let mut default_instruments = instrument_registry.expand(&config.subscription.default_instruments);
+    for instrument in instrument_registry.expand(&config.subscription.auto_subscribe_patterns) {
+        if !default_instruments.contains(&instrument) {
+            default_instruments.push(instrument);
+        }
+    }
+
+    // Restore subscriptions persisted by the previous run
+    let state_file = config.subscription.state_file.clone();
+    if let Some(path) = &state_file {
+        match DistributorState::load(path) {
+            Ok(Some(state)) => {
+                let restored = md_distributor
+                    .send(RestoreDistributorState {
+                        state,
+                        grace: Duration::from_secs(config.subscription.restore_grace_secs),
+                    })
+                    .await
+                    .unwrap_or_default();
+                info!("Resubscribing {} instruments from {}", restored.len(), path);
+                // Synthetic and continuous instruments are rebuilt from real contracts, not subscribed upstream
+                for instrument in restored
+                    .into_iter()
+                    .filter(|instrument| !is_synthetic(instrument) && !is_dominant(instrument))
+                {
+                    if !default_instruments.contains(&instrument) {
+                        default_instruments.push(instrument);
+                    }
+                }
+            }
+            Ok(None) => info!("No subscription state found at {}", path),
+            Err(e) => warn!("Failed to load subscription state from {}: {}", path, e),
+        }
+    }
+    
+    // Collect per-instrument tick statistics
+    let md_stats = actix::Actor::start(MarketDataStatsActor::new());
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: "stats".to_string(),
+        addr: md_stats.clone().recipient(),
+    });
+    
+    // Evaluate client price/volume alerts on every tick
+    let alert_engine = actix::Actor::start(AlertEngine::new());
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: "alerts".to_string(),
+        addr: alert_engine.clone().recipient(),
+    });
+    
+    // Named watchlists shared by clients
+    let watchlist_store = match &config.subscription.watchlists_file {
+        Some(path) => WatchlistStore::load(path)?,
+        None => WatchlistStore::new(),
+    };
+    let watchlist_store = actix::Actor::start(watchlist_store);
+
+    // Subscriptions and throttling preferences of clients connecting with a client_name
+    let profile_store = match &config.subscription.profiles_file {
+        Some(path) => ProfileStore::load(path)?,
+        None => ProfileStore::new(),
+    };
+    let profile_store = actix::Actor::start(profile_store);
+    
+    // Compute order flow and volatility indicators for subscribed instruments
+    let indicator_engine = actix::Actor::start(IndicatorEngine::new());
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: "indicators".to_string(),
+        addr: indicator_engine.clone().recipient(),
+    });
+    
+    // Publish incremental order book updates for subscribed instruments
+    let depth_engine = actix::Actor::start(DepthEngine::new());
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: "depth".to_string(),
+        addr: depth_engine.clone().recipient(),
+    });
+    
+    // Track clients exceeding their subscription and message rate limits
+    let quota_monitor = actix::Actor::start(QuotaMonitor::new());
+    
+    // Load the trading calendar
+    let calendar = Arc::new(TradingCalendar::from_config(&config.calendar)?);
+    
+    // Keep recent ticks in memory for ad-hoc exports, and in rotating files when configured
+    let tick_recorder = config.recorder.enabled.then(|| {
+        let mut recorder = TickRecorderActor::new(config.recorder.clone());
+        if config.recorder.dir.is_some() {
+            match TickArchive::open(config.recorder.clone(), calendar.clone()) {
+                Ok(archive) => recorder = recorder.with_archive(archive),
+                Err(e) => error!("Failed to open tick directory: {}", e),
+            }
+        }
+        let recorder = actix::Actor::start(recorder);
+        md_distributor.do_send(RegisterSnapshotSink {
+            name: "recorder".to_string(),
+            addr: recorder.clone().recipient(),
+        });
+        recorder
+    });
+    
+    // Keep the last minutes of ticks so charts can backfill their initial view
+    let tick_history = config.history.enabled.then(|| {
+        let history = actix::Actor::start(TickHistoryActor::new(&config.history));
+        md_distributor.do_send(RegisterSnapshotSink {
+            name: "history".to_string(),
+            addr: history.clone().recipient(),
+        });
+        history
+    });
+    
+    // Publish snapshots to Redis for non-WebSocket consumers
+    if let Some(redis_config) = config.redis.clone().filter(|r| r.enabled) {
+        start_redis_bridge(redis_config, instrument_registry.clone(), &md_distributor);
+    }
+    
+    // Publish snapshots on a ZeroMQ PUB socket for legacy consumers
+    if let Some(zmq_config) = config.zmq.clone().filter(|z| z.enabled) {
+        start_zmq_publisher(zmq_config, &md_distributor);
+    }
+    
+    // Stream snapshots to local consumers over a unix socket or named pipe
+    if let Some(ipc_config) = config.ipc.clone().filter(|i| i.enabled) {
+        start_ipc_server(ipc_config, &md_distributor);
+    }
     
-    // QQ broker configuration (if needed)
-    let qq_broker_config = config.get_broker(None)?; // Could be configured separately
-    let all_broker_configs = vec![broker_config, qq_broker_config];
+    // Journal raw upstream messages before conversion, without a usable directory they are not journaled
+    let raw_journal = if config.journal.enabled {
+        RawJournal::open(config.journal.clone())
+            .map(Arc::new)
+            .map_err(|e| warn!("Failed to open journal in {}: {}", config.journal.dir, e))
+            .ok()
+    } else {
+        None
+    };
     
-    // Get default subscriptions
-    let default_instruments = config.subscription.default_instruments.clone();
+    // Poll Sina's HTTP quote API as a fallback stock source
+    if let Some(sina_http_config) = config.sina_http.clone().filter(|s| s.enabled) {
+        let mut poller = SinaHttpPollerActor::new(sina_http_config, md_distributor.clone());
+        if let Some(journal) = &raw_journal {
+            poller = poller.with_journal(journal.clone());
+        }
+        actix::Actor::start(poller);
+    }
+    
+    // Stream Binance spot quotes and trades
+    if let Some(binance_config) = config.binance.clone().filter(|b| b.enabled) {
+        let mut binance = BinanceMarketDataActor::new(binance_config, md_distributor.clone());
+        if let Some(journal) = &raw_journal {
+            binance = binance.with_journal(journal.clone());
+        }
+        actix::Actor::start(binance);
+    }
+    
+    // Watch for gaps, stale quotes, invalid prices and crossed books
+    let data_quality = actix::Actor::start(DataQualityActor::new(
+        config.data_quality.clone(),
+        calendar.clone(),
+    ));
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: "data_quality".to_string(),
+        addr: data_quality.clone().recipient(),
+    });
+    
+    // Track per-exchange trading phases; they gate staleness reports and reconnects
+    let trading_phase = actix::Actor::start(TradingPhaseActor::new(
+        config.trading_phase.clone(),
+        calendar.clone(),
+    ));
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: "trading_phase".to_string(),
+        addr: trading_phase.clone().recipient(),
+    });
+    trading_phase.do_send(SubscribePhase {
+        client_id: "data_quality".to_string(),
+        addr: data_quality.clone().recipient(),
+        session_end: None,
+        exchanges: vec!["*".to_string()],
+    });
     
     // Create the market data connector actor
-    let md_connector = actix::Actor::start(MarketDataConnector::new(
-        all_broker_configs.into_iter().map(|bc| bc.clone()).collect(),
+    let mut connector = MarketDataConnector::new(
+        all_broker_configs,
         default_instruments,
         md_distributor.clone(),
-    ));
+    )
+    .with_flow(config.flow.clone())
+    .with_circuit_breaker(config.circuit_breaker.clone())
+    .with_supervision(config.supervision.clone());
+    if config.calendar.suppress_reconnect {
+        info!("Upstream reconnects are suppressed outside trading hours");
+        connector = connector
+            .with_calendar(
+                calendar.clone(),
+                chrono::Duration::minutes(config.calendar.reconnect_lead_minutes),
+            )
+            .with_trading_phases(trading_phase.clone());
+    }
+    if config.load_sharing.enabled {
+        connector = connector.with_load_sharing(config.load_sharing.clone());
+    }
+    if let Some(path) = &config.subscription.subscription_file {
+        connector = connector.with_subscription_file(path, instrument_registry.clone());
+    }
+    
+    // Subscribe instrument groups shortly before their sessions open
+    if config.warmup.enabled {
+        let scheduler = WarmupScheduler::new(
+            &config.warmup,
+            calendar.clone(),
+            &instrument_registry,
+            md_distributor.clone(),
+        )
+        .map_err(|e| {
+            error!("Invalid warm-up configuration: {}", e);
+            e
+        })?;
+        actix::Actor::start(scheduler);
+    }
+    
+    // Aggregate the day's recorded ticks into daily bars after the close
+    if config.eod.enabled {
+        match &tick_recorder {
+            Some(recorder) => {
+                let builder = EodBarBuilder::new(
+                    &config.eod,
+                    calendar.clone(),
+                    instrument_registry.clone(),
+                    recorder.clone(),
+                )
+                .map_err(|e| {
+                    error!("Invalid EOD configuration: {}", e);
+                    e
+                })?;
+                actix::Actor::start(builder);
+            }
+            None => warn!("EOD daily bars need the tick recorder, set recorder.enabled to build them"),
+        }
+    }
+    connector = connector.with_instruments(instrument_registry.clone());
+    if config.converter.round_to_price_tick {
+        info!("Rounding upstream prices to the instrument price tick");
+        connector = connector.with_price_rounding(instrument_registry.clone());
+    }
+    if let Some(journal) = &raw_journal {
+        connector = connector.with_journal(journal.clone());
+    }
+    if config.converter.tolerant_parsing {
+        info!("Publishing partial snapshots from malformed upstream quotes");
+        // Without a usable dead-letter file malformed quotes are only logged
+        let dead_letter = config.converter.dead_letter_file.as_deref().and_then(|path| {
+            DeadLetterLog::open(path)
+                .map(Arc::new)
+                .map_err(|e| warn!("Failed to open dead-letter file {}: {}", path, e))
+                .ok()
+        });
+        connector = connector.with_tolerant_parsing(dead_letter);
+    }
+    let (resubscribe_events, _) = tokio::sync::broadcast::channel::<ResubscribeProgress>(256);
+    connector = connector.with_resubscribe(config.resubscribe.clone(), resubscribe_events.clone());
+    let md_connector = actix::Actor::start(connector);
     info!("Market data connector initialized");
     
+    // Active/standby replication: the primary serves its state, the standby mirrors it
+    let replication = config.replication.clone().filter(|r| r.enabled);
+    if let Some(replication_config) = replication.clone().filter(|r| r.role == ReplicationRole::Standby) {
+        if replication_config.peer_url.is_none() {
+            warn!("Standby replication is enabled but no peer_url is configured");
+        }
+        actix::Actor::start(StandbyActor::new(
+            replication_config,
+            md_distributor.clone(),
+            md_connector.clone(),
+            Duration::from_secs(config.subscription.restore_grace_secs),
+        ));
+    }
+    let primary_replication = replication.filter(|r| r.role == ReplicationRole::Primary);
+    
+    // Token scopes restrict the instruments each WebSocket client may receive
+    let acl = if config.acl.enabled {
+        let store = AclStore::load(&config.acl)?;
+        info!("Access control enabled with {} tokens", store.token_count());
+        Some(Arc::new(store))
+    } else {
+        None
+    };
+    
+    // The admin channel only exists when a token is configured
+    let admin = Some(config.admin.clone()).filter(|admin| admin.token().is_some());
+    if let Some(admin) = &admin {
+        info!("Admin WebSocket channel enabled at {}", admin.path);
+    }
+    if let Some(replication_config) = &primary_replication {
+        info!("Serving replication to standby gateways at {}", replication_config.path);
+    }
+    
+    // Socket.IO compatibility endpoint for legacy dashboards
+    let socketio = config.socketio.clone().filter(|s| s.enabled);
+    #[cfg(feature = "socketio")]
+    if let Some(socketio_config) = &socketio {
+        info!("Socket.IO endpoint enabled at {}", socketio_config.path);
+    }
+    #[cfg(not(feature = "socketio"))]
+    if socketio.is_some() {
+        warn!("socketio is configured but the gateway was built without the `socketio` feature");
+    }
+    
+    // Reload the configuration on SIGHUP or POST /api/admin/reload
+    let send_queue = Arc::new(RwLock::new(config.websocket.send_queue.clone()));
+    let quota = Arc::new(RwLock::new(config.websocket.quota.clone()));
+    let heartbeat = Arc::new(RwLock::new(config.websocket.heartbeat.clone()));
+    let config_reloader = actix::Actor::start(ConfigReloader::new(
+        config.clone(),
+        md_connector.clone(),
+        md_distributor.clone(),
+        instrument_registry.clone(),
+        send_queue.clone(),
+        quota.clone(),
+        heartbeat.clone(),
+    ));
+    #[cfg(unix)]
+    reload_on_sighup(config_reloader.clone())?;
+    
     // Create application state for API endpoints
     let app_state = web::Data::new(AppState {
         md_connector: md_connector.clone(),
         start_time: Instant::now(),
     });
     
-    // Start HTTP server
-    info!(
-        "Starting HTTP server at {}:{}",
-        config.rest_api.host, config.rest_api.port
-    );
-    
-    HttpServer::new(move || {
+    // Every listener runs its own server so it can use its own WebSocket path
+    let shutdown_distributor = md_distributor.clone();
+    let make_app = move |ws_path: &str| {
         // Create CORS configuration
         let cors = Cors::permissive()
             .allow_any_origin()
@@ -77,12 +520,151 @@ async fn main() -> GatewayResult<()> {
             .app_data(app_state.clone())
             .app_data(web::Data::new(md_connector.clone()))
             .app_data(web::Data::new(md_distributor.clone()))
-            .service(web::resource(&config.websocket.path).route(web::get().to(ws_server::ws_handler)))
+            .app_data(web::Data::new(md_stats.clone()))
+            .app_data(web::Data::new(alert_engine.clone()))
+            .app_data(web::Data::from(calendar.clone()))
+            .app_data(web::Data::from(instrument_registry.clone()))
+            .app_data(web::Data::from(symbol_table.clone()))
+            .app_data(web::Data::from(send_queue.clone()))
+            .app_data(web::Data::from(quota.clone()))
+            .app_data(web::Data::from(heartbeat.clone()))
+            .app_data(web::Data::new(quota_monitor.clone()))
+            .app_data(web::Data::new(indicator_engine.clone()))
+            .app_data(web::Data::new(depth_engine.clone()))
+            .app_data(web::Data::new(data_quality.clone()))
+            .app_data(web::Data::new(trading_phase.clone()))
+            .app_data(web::Data::new(watchlist_store.clone()))
+            .app_data(web::Data::new(profile_store.clone()))
+            .app_data(web::Data::new(config_reloader.clone()))
+            .app_data(web::Data::new(resubscribe_events.clone()))
+            .service(web::resource(ws_path).route(web::get().to(ws_server::ws_handler)))
+            .configure(|cfg| {
+                if let Some(recorder) = &tick_recorder {
+                    cfg.app_data(web::Data::new(recorder.clone()));
+                }
+                if let Some(history) = &tick_history {
+                    cfg.app_data(web::Data::new(history.clone()));
+                }
+                if let Some(replay) = &replay {
+                    cfg.app_data(web::Data::new(replay.clone()));
+                }
+                if let Some(acl) = &acl {
+                    cfg.app_data(web::Data::from(acl.clone()));
+                }
+                if let Some(admin) = &admin {
+                    cfg.app_data(web::Data::new(admin.clone())).service(
+                        web::resource(&admin.path).route(web::get().to(admin_ws::admin_ws_handler)),
+                    );
+                }
+                #[cfg(feature = "socketio")]
+                if let Some(socketio_config) = &socketio {
+                    cfg.app_data(web::Data::new(socketio_config.clone())).service(
+                        web::resource(&socketio_config.path).route(web::get().to(socketio::socketio_handler)),
+                    );
+                }
+                if let Some(replication_config) = &primary_replication {
+                    cfg.app_data(web::Data::new(replication_config.clone())).service(
+                        web::resource(&replication_config.path).route(web::get().to(replication_handler)),
+                    );
+                }
+            })
             .configure(configure_routes)
-    })
-    .bind((config.rest_api.host.clone(), config.rest_api.port))?
-    .run()
-    .await?;
+    };
+
+    let mut servers = Vec::new();
+    for listener in listeners::resolve(&config.rest_api) {
+        let ws_path = listener.ws_path.clone().unwrap_or_else(|| config.websocket.path.clone());
+        info!(
+            "Starting HTTP server at {}{} (WebSocket path {})",
+            listener.address,
+            if listener.tls.is_some() { " with TLS" } else { "" },
+            ws_path
+        );
+        let make_app = make_app.clone();
+        let mut server = HttpServer::new(move || make_app(&ws_path));
+        for socket in listeners::open(&listener)? {
+            server = match socket {
+                BoundSocket::Tcp(tcp) => server.listen(tcp)?,
+                #[cfg(feature = "tls")]
+                BoundSocket::Tls(tcp, tls) => server.listen_rustls_0_23(tcp, *tls)?,
+                #[cfg(unix)]
+                BoundSocket::Unix(uds) => server.listen_uds(uds)?,
+            };
+        }
+        servers.push(server.run());
+    }
+    futures::future::try_join_all(servers).await?;
+
+    // The server returns after a shutdown signal: persist subscriptions for the next start
+    if let Some(path) = state_file {
+        match shutdown_distributor.send(SaveDistributorState { path: path.clone().into() }).await {
+            Ok(Ok(())) => info!("Subscription state saved"),
+            Ok(Err(e)) => error!("Failed to save subscription state to {}: {}", path, e),
+            Err(e) => error!("Failed to reach distributor on shutdown: {}", e),
+        }
+    }
+    
+    Ok(())
+}
+
+/// Reload the configuration whenever the process receives SIGHUP
+#[cfg(unix)]
+fn reload_on_sighup(reloader: actix::Addr<ConfigReloader>) -> GatewayResult<()> {
+    use tokio::signal::unix::{signal, SignalKind};
     
+    let mut hangup = signal(SignalKind::hangup())?;
+    actix_rt::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading configuration");
+            match reloader.send(ReloadConfig).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => error!("Failed to reload configuration: {}", e),
+                Err(e) => error!("Failed to reach config reloader: {}", e),
+            }
+        }
+    });
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(feature = "ctp-instruments")]
+async fn query_instruments(
+    query_config: &crate::config::InstrumentQueryConfig,
+    registry: &mut InstrumentRegistry,
+) {
+    if let Err(e) = std::fs::create_dir_all(&query_config.flow_path) {
+        warn!("Failed to create CTP flow directory {}: {}", query_config.flow_path, e);
+    }
+    // The trader API blocks while waiting for responses
+    let query_config = query_config.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        crate::instrument_query::query_ctp_instruments(&query_config)
+    })
+    .await;
+    match result {
+        Ok(Ok(instruments)) => {
+            info!("Loaded {} instruments from the CTP trader front", instruments.len());
+            registry.extend(instruments);
+        }
+        Ok(Err(e)) => error!("Failed to query instruments from CTP: {}", e),
+        Err(e) => error!("CTP instrument query panicked: {}", e),
+    }
+}
+
+#[cfg(feature = "ctp-trader-query")]
+fn start_trader_query(trader_query: crate::config::TraderQueryConfig, registry: Arc<InstrumentRegistry>) {
+    use crate::actors::trader_query::TraderQueryActor;
+    actix::Actor::start(TraderQueryActor::new(trader_query, registry));
+}
+
+#[cfg(not(feature = "ctp-trader-query"))]
+fn start_trader_query(_trader_query: crate::config::TraderQueryConfig, _registry: Arc<InstrumentRegistry>) {
+    warn!("Trader query is configured but the gateway was built without the `ctp-trader-query` feature");
+}
+
+#[cfg(not(feature = "ctp-instruments"))]
+async fn query_instruments(
+    _query_config: &crate::config::InstrumentQueryConfig,
+    _registry: &mut InstrumentRegistry,
+) {
+    warn!("Instrument query is configured but the gateway was built without the `ctp-instruments` feature");
+}
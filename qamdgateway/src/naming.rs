@@ -0,0 +1,173 @@
+//! 推送给客户端的JSON字段命名风格
+//!
+//! 网关内部的行情（MDSnapshot、TvQuote、分发器推送的增量）一律使用snake_case字段名，
+//! 会话按客户端选择的风格在序列化时统一改写字段名，各处消息无需分别处理。
+//! 只改写由小写字母、数字和下划线组成的键，合约ID（如`SHFE.rb2410`）和字段值保持不变。
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+/// 字段命名风格
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldNaming {
+    /// `last_price`（默认）
+    #[default]
+    #[serde(rename = "snake_case", alias = "snake")]
+    SnakeCase,
+    /// `lastPrice`
+    #[serde(rename = "camelCase", alias = "camel")]
+    CamelCase,
+}
+
+impl FieldNaming {
+    /// 从查询参数或订阅选项中的名称解析
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace(['_', '-'], "").as_str() {
+            "snakecase" | "snake" => Some(FieldNaming::SnakeCase),
+            "camelcase" | "camel" => Some(FieldNaming::CamelCase),
+            _ => None,
+        }
+    }
+
+    /// 按该风格改写snake_case字段名
+    pub fn rename<'a>(self, field: &'a str) -> Cow<'a, str> {
+        match self {
+            FieldNaming::CamelCase if is_field_name(field) && field.contains('_') => {
+                let mut renamed = String::with_capacity(field.len());
+                let mut upper = false;
+                for c in field.chars() {
+                    if c == '_' {
+                        upper = !renamed.is_empty();
+                    } else if upper {
+                        renamed.push(c.to_ascii_uppercase());
+                        upper = false;
+                    } else {
+                        renamed.push(c);
+                    }
+                }
+                Cow::Owned(renamed)
+            }
+            _ => Cow::Borrowed(field),
+        }
+    }
+
+    /// 将客户端使用的字段名还原为snake_case（用于订阅时指定的字段列表）
+    pub fn to_snake<'a>(self, field: &'a str) -> Cow<'a, str> {
+        match self {
+            FieldNaming::CamelCase if field.chars().any(|c| c.is_ascii_uppercase()) => {
+                let mut snake = String::with_capacity(field.len() + 4);
+                for c in field.chars() {
+                    if c.is_ascii_uppercase() {
+                        snake.push('_');
+                        snake.push(c.to_ascii_lowercase());
+                    } else {
+                        snake.push(c);
+                    }
+                }
+                Cow::Owned(snake)
+            }
+            _ => Cow::Borrowed(field),
+        }
+    }
+
+    /// 递归改写JSON对象的字段名
+    pub fn apply(self, value: Value) -> Value {
+        if self == FieldNaming::SnakeCase {
+            return value;
+        }
+        match value {
+            Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (self.rename(&field).into_owned(), self.apply(value)))
+                    .collect::<Map<String, Value>>(),
+            ),
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| self.apply(item)).collect()),
+            value => value,
+        }
+    }
+}
+
+/// 字段名：小写字母开头，只包含小写字母、数字和下划线
+fn is_field_name(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// 按命名风格序列化的消息
+///
+/// snake_case直接序列化原消息；其他风格先转为JSON值改写字段名再序列化
+pub struct Named<'a, T> {
+    value: &'a T,
+    naming: FieldNaming,
+}
+
+impl<'a, T: Serialize> Named<'a, T> {
+    pub fn new(value: &'a T, naming: FieldNaming) -> Self {
+        Self { value, naming }
+    }
+}
+
+impl<T: Serialize> Serialize for Named<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.naming == FieldNaming::SnakeCase {
+            return self.value.serialize(serializer);
+        }
+        let value = serde_json::to_value(self.value).map_err(serde::ser::Error::custom)?;
+        self.naming.apply(value).serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(FieldNaming::from_name("camelCase"), Some(FieldNaming::CamelCase));
+        assert_eq!(FieldNaming::from_name("camel-case"), Some(FieldNaming::CamelCase));
+        assert_eq!(FieldNaming::from_name("SNAKE_CASE"), Some(FieldNaming::SnakeCase));
+        assert_eq!(FieldNaming::from_name("snake"), Some(FieldNaming::SnakeCase));
+        assert_eq!(FieldNaming::from_name("kebab"), None);
+        assert_eq!(serde_json::from_str::<FieldNaming>(r#""camel""#).unwrap(), FieldNaming::CamelCase);
+    }
+
+    #[test]
+    fn test_rename_and_back() {
+        let camel = FieldNaming::CamelCase;
+        assert_eq!(camel.rename("last_price"), "lastPrice");
+        assert_eq!(camel.rename("bid_price1"), "bidPrice1");
+        assert_eq!(camel.rename("volume"), "volume");
+        // 合约ID和非字段名的键保持不变
+        assert_eq!(camel.rename("SHFE.rb_2410"), "SHFE.rb_2410");
+        assert_eq!(camel.rename("_private"), "_private");
+        assert_eq!(FieldNaming::SnakeCase.rename("last_price"), "last_price");
+
+        assert_eq!(camel.to_snake("lastPrice"), "last_price");
+        assert_eq!(camel.to_snake("bidPrice1"), "bid_price1");
+        assert_eq!(camel.to_snake("volume"), "volume");
+        assert_eq!(FieldNaming::SnakeCase.to_snake("lastPrice"), "lastPrice");
+    }
+
+    #[test]
+    fn test_apply_rewrites_nested_keys_only() {
+        let value = json!({
+            "aid": "rtn_data",
+            "data": [{"quotes": {"SHFE.rb2410": {"last_price": 3500.0, "instrument_id": "SHFE.rb2410"}}}],
+        });
+        let expected = json!({
+            "aid": "rtn_data",
+            "data": [{"quotes": {"SHFE.rb2410": {"lastPrice": 3500.0, "instrumentId": "SHFE.rb2410"}}}],
+        });
+        assert_eq!(FieldNaming::CamelCase.apply(value.clone()), expected);
+        assert_eq!(FieldNaming::SnakeCase.apply(value.clone()), value);
+
+        assert_eq!(
+            serde_json::to_value(Named::new(&value, FieldNaming::CamelCase)).unwrap(),
+            expected
+        );
+        assert_eq!(serde_json::to_value(Named::new(&value, FieldNaming::SnakeCase)).unwrap(), value);
+    }
+}
@@ -0,0 +1,394 @@
+//! 行情发布前的价格校验
+//!
+//! 上游偶尔推送错误的价格，如不是最小变动价位整数倍的盘口价、超出涨跌停板的最新价。
+//! 分发器在推送前检查最新价、开高低价和各档盘口价：价位按合约信息中的最小变动价位
+//! （没有合约信息时按交易所配置的价位）检查，涨跌停板取行情自带的涨跌停价。
+//! 价格为0表示无效值，不参与检查。
+
+use hashbrown::HashMap;
+use qamd_rs::MDSnapshot;
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::config::{PriceCheckConfig, PriceCheckMode};
+use crate::instruments::InstrumentRegistry;
+
+/// 价位检查允许的浮点误差（以价位为单位）
+const TICK_TOLERANCE: f64 = 1e-6;
+
+/// 价格校验统计
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PriceCheckStats {
+    /// 检查过的行情数
+    pub checked: u64,
+    /// 有价格不是最小变动价位整数倍的行情数
+    pub off_tick: u64,
+    /// 有价格超出涨跌停板的行情数
+    pub out_of_limits: u64,
+    /// 修正后发布的行情数
+    pub corrected: u64,
+    /// 丢弃的行情数
+    pub dropped: u64,
+}
+
+/// 合约的价格校验统计
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentPriceViolations {
+    pub instrument_id: String,
+    pub off_tick: u64,
+    pub out_of_limits: u64,
+}
+
+/// 价格校验报告
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceCheckReport {
+    pub enabled: bool,
+    pub mode: PriceCheckMode,
+    #[serde(flatten)]
+    pub stats: PriceCheckStats,
+    /// 出现过违规价格的合约，按违规次数从多到少排列
+    pub instruments: Vec<InstrumentPriceViolations>,
+}
+
+/// 行情价格校验器
+pub struct PriceChecker {
+    config: PriceCheckConfig,
+    stats: PriceCheckStats,
+    // 合约 -> (价位违规次数, 超出涨跌停次数)
+    violations: HashMap<String, (u64, u64)>,
+}
+
+impl PriceChecker {
+    /// 创建校验器
+    pub fn new(config: PriceCheckConfig) -> Self {
+        Self {
+            config,
+            stats: PriceCheckStats::default(),
+            violations: HashMap::new(),
+        }
+    }
+
+    /// 是否启用校验
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// 检查一条行情的价格，按配置的处理方式修正行情，返回false时丢弃这条行情
+    pub fn check(&mut self, snapshot: &mut Arc<MDSnapshot>, instruments: &InstrumentRegistry) -> bool {
+        if !self.config.enabled {
+            return true;
+        }
+        self.stats.checked += 1;
+
+        let tick = self.price_tick(&snapshot.instrument_id, instruments);
+        let limits = limits(snapshot);
+        let mut off_tick = false;
+        let mut out_of_limits = false;
+        for price in prices(snapshot).into_iter().flatten().filter(|price| is_valid(*price)) {
+            off_tick |= tick.is_some_and(|tick| !on_tick(price, tick));
+            out_of_limits |= limits.is_some_and(|(lower, upper)| price < lower || price > upper);
+        }
+        if !off_tick && !out_of_limits {
+            return true;
+        }
+
+        let counts = self.violations.entry_ref(snapshot.instrument_id.as_str()).or_default();
+        if off_tick {
+            self.stats.off_tick += 1;
+            counts.0 += 1;
+        }
+        if out_of_limits {
+            self.stats.out_of_limits += 1;
+            counts.1 += 1;
+        }
+        match self.config.mode {
+            PriceCheckMode::Flag => true,
+            PriceCheckMode::Drop => {
+                self.stats.dropped += 1;
+                false
+            }
+            PriceCheckMode::Correct => {
+                self.stats.corrected += 1;
+                // 涨跌停价向内取整到价位上，夹到涨跌停板内的价格仍是价位的整数倍
+                let limits = limits.map(|(lower, upper)| match tick {
+                    Some(tick) => limits_on_tick(lower, upper, tick),
+                    None => (lower, upper),
+                });
+                for price in prices_mut(Arc::make_mut(snapshot)).filter(|price| is_valid(**price)) {
+                    if let Some(tick) = tick {
+                        *price = round_to_tick(*price, tick);
+                    }
+                    if let Some((lower, upper)) = limits {
+                        *price = price.clamp(lower, upper);
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// 统计报告
+    pub fn report(&self) -> PriceCheckReport {
+        let mut instruments: Vec<InstrumentPriceViolations> = self
+            .violations
+            .iter()
+            .map(|(instrument, (off_tick, out_of_limits))| InstrumentPriceViolations {
+                instrument_id: instrument.clone(),
+                off_tick: *off_tick,
+                out_of_limits: *out_of_limits,
+            })
+            .collect();
+        instruments.sort_by(|a, b| {
+            (b.off_tick + b.out_of_limits)
+                .cmp(&(a.off_tick + a.out_of_limits))
+                .then_with(|| a.instrument_id.cmp(&b.instrument_id))
+        });
+        PriceCheckReport {
+            enabled: self.config.enabled,
+            mode: self.config.mode,
+            stats: self.stats,
+            instruments,
+        }
+    }
+
+    /// 合约的最小变动价位，没有合约信息时取交易所配置的价位
+    fn price_tick(&self, instrument_id: &str, instruments: &InstrumentRegistry) -> Option<f64> {
+        instruments
+            .get(instrument_id)
+            .map(|info| info.price_tick)
+            .or_else(|| {
+                let (exchange, _) = instrument_id.split_once('.')?;
+                self.config.exchange_ticks.get(exchange).copied()
+            })
+            .filter(|tick| tick.is_finite() && *tick > 0.0)
+    }
+}
+
+fn is_valid(price: f64) -> bool {
+    price.is_finite() && price > 0.0
+}
+
+fn on_tick(price: f64, tick: f64) -> bool {
+    let ticks = price / tick;
+    (ticks - ticks.round()).abs() <= TICK_TOLERANCE
+}
+
+fn round_to_tick(price: f64, tick: f64) -> f64 {
+    ticks_to_price((price / tick).round(), tick)
+}
+
+fn ticks_to_price(ticks: f64, tick: f64) -> f64 {
+    // 去掉乘法引入的浮点误差
+    (ticks * tick * 1e8).round() / 1e8
+}
+
+/// 涨跌停价向内取整到价位：跌停价向上、涨停价向下，涨跌停板内没有价位时保持不变
+fn limits_on_tick(lower: f64, upper: f64, tick: f64) -> (f64, f64) {
+    let to_tick = |price: f64, inward: fn(f64) -> f64| {
+        if on_tick(price, tick) {
+            round_to_tick(price, tick)
+        } else {
+            ticks_to_price(inward(price / tick), tick)
+        }
+    };
+    let (lower_tick, upper_tick) = (to_tick(lower, f64::ceil), to_tick(upper, f64::floor));
+    if lower_tick <= upper_tick {
+        (lower_tick, upper_tick)
+    } else {
+        (lower, upper)
+    }
+}
+
+/// 行情自带的涨跌停价，缺少或不合理时返回None
+fn limits(snapshot: &MDSnapshot) -> Option<(f64, f64)> {
+    let (lower, upper) = (snapshot.lower_limit, snapshot.upper_limit);
+    (is_valid(lower) && is_valid(upper) && lower <= upper).then_some((lower, upper))
+}
+
+/// 需要检查的价格
+fn prices(snapshot: &MDSnapshot) -> [Option<f64>; 24] {
+    [
+        Some(snapshot.last_price),
+        Some(snapshot.open),
+        Some(snapshot.highest),
+        Some(snapshot.lowest),
+        Some(snapshot.bid_price1),
+        snapshot.bid_price2,
+        snapshot.bid_price3,
+        snapshot.bid_price4,
+        snapshot.bid_price5,
+        snapshot.bid_price6,
+        snapshot.bid_price7,
+        snapshot.bid_price8,
+        snapshot.bid_price9,
+        snapshot.bid_price10,
+        Some(snapshot.ask_price1),
+        snapshot.ask_price2,
+        snapshot.ask_price3,
+        snapshot.ask_price4,
+        snapshot.ask_price5,
+        snapshot.ask_price6,
+        snapshot.ask_price7,
+        snapshot.ask_price8,
+        snapshot.ask_price9,
+        snapshot.ask_price10,
+    ]
+}
+
+/// 需要检查的价格（可修改）
+fn prices_mut(snapshot: &mut MDSnapshot) -> impl Iterator<Item = &mut f64> {
+    let MDSnapshot {
+        last_price,
+        open,
+        highest,
+        lowest,
+        bid_price1,
+        bid_price2,
+        bid_price3,
+        bid_price4,
+        bid_price5,
+        bid_price6,
+        bid_price7,
+        bid_price8,
+        bid_price9,
+        bid_price10,
+        ask_price1,
+        ask_price2,
+        ask_price3,
+        ask_price4,
+        ask_price5,
+        ask_price6,
+        ask_price7,
+        ask_price8,
+        ask_price9,
+        ask_price10,
+        ..
+    } = snapshot;
+    [last_price, open, highest, lowest, bid_price1, ask_price1].into_iter().chain(
+        [
+            bid_price2, bid_price3, bid_price4, bid_price5, bid_price6, bid_price7, bid_price8, bid_price9,
+            bid_price10, ask_price2, ask_price3, ask_price4, ask_price5, ask_price6, ask_price7, ask_price8,
+            ask_price9, ask_price10,
+        ]
+        .into_iter()
+        .filter_map(Option::as_mut),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn checker(mode: PriceCheckMode) -> PriceChecker {
+        let mut exchange_ticks = HashMap::new();
+        exchange_ticks.insert("SHFE".to_string(), 1.0);
+        exchange_ticks.insert("CFFEX".to_string(), 0.2);
+        PriceChecker::new(PriceCheckConfig {
+            enabled: true,
+            mode,
+            exchange_ticks,
+        })
+    }
+
+    fn snapshot(instrument_id: &str, last_price: f64, lower: f64, upper: f64) -> Arc<MDSnapshot> {
+        Arc::new(
+            MDSnapshot::builder(instrument_id, Utc::now())
+                .last_price(last_price)
+                .limits(lower, upper)
+                .bid(1, last_price, 1)
+                .ask(1, last_price, 1)
+                .build(),
+        )
+    }
+
+    #[test]
+    fn test_on_tick() {
+        assert!(on_tick(3500.0, 1.0));
+        assert!(on_tick(3856.2, 0.2));
+        assert!(on_tick(0.3, 0.1));
+        assert!(on_tick(3856.2 + 1e-9, 0.2));
+        assert!(!on_tick(3856.3, 0.2));
+        assert!(!on_tick(3500.5, 1.0));
+    }
+
+    #[test]
+    fn test_round_to_tick() {
+        assert_eq!(round_to_tick(3856.3, 0.2), 3856.4);
+        assert_eq!(round_to_tick(3856.25, 0.2), 3856.2);
+        assert_eq!(round_to_tick(0.29999, 0.1), 0.3);
+        assert_eq!(round_to_tick(3500.4, 1.0), 3500.0);
+        assert_eq!(limits_on_tick(3300.5, 3700.5, 1.0), (3301.0, 3700.0));
+        assert_eq!(limits_on_tick(3300.0, 3700.0, 1.0), (3300.0, 3700.0));
+        assert_eq!(limits_on_tick(3300.2, 3300.8, 1.0), (3300.2, 3300.8));
+    }
+
+    #[test]
+    fn test_valid_prices_pass() {
+        let instruments = InstrumentRegistry::new();
+        let mut checker = checker(PriceCheckMode::Drop);
+        let mut quote = snapshot("SHFE.rb2410", 3500.0, 3300.0, 3700.0);
+        assert!(checker.check(&mut quote, &instruments));
+        // 没有价位和涨跌停价时不检查
+        let mut quote = snapshot("DCE.m2409", 3100.5, 0.0, 0.0);
+        assert!(checker.check(&mut quote, &instruments));
+        let report = checker.report();
+        assert_eq!((report.stats.checked, report.stats.off_tick, report.stats.dropped), (2, 0, 0));
+    }
+
+    #[test]
+    fn test_flag_mode() {
+        let instruments = InstrumentRegistry::new();
+        let mut checker = checker(PriceCheckMode::Flag);
+        let mut quote = snapshot("SHFE.rb2410", 3500.5, 3300.0, 3700.0);
+        assert!(checker.check(&mut quote, &instruments));
+        assert_eq!(quote.last_price, 3500.5);
+        let mut quote = snapshot("SHFE.rb2410", 3800.0, 3300.0, 3700.0);
+        assert!(checker.check(&mut quote, &instruments));
+        assert_eq!(quote.last_price, 3800.0);
+
+        let report = checker.report();
+        assert_eq!((report.stats.off_tick, report.stats.out_of_limits), (1, 1));
+        assert_eq!(report.instruments.len(), 1);
+        assert_eq!(report.instruments[0].instrument_id, "SHFE.rb2410");
+    }
+
+    #[test]
+    fn test_drop_mode() {
+        let instruments = InstrumentRegistry::new();
+        let mut checker = checker(PriceCheckMode::Drop);
+        let mut quote = snapshot("SHFE.rb2410", 3500.5, 3300.0, 3700.0);
+        assert!(!checker.check(&mut quote, &instruments));
+        let mut quote = snapshot("SHFE.rb2410", 3200.0, 3300.0, 3700.0);
+        assert!(!checker.check(&mut quote, &instruments));
+        assert_eq!(checker.report().stats.dropped, 2);
+    }
+
+    #[test]
+    fn test_correct_mode() {
+        let instruments = InstrumentRegistry::new();
+        let mut checker = checker(PriceCheckMode::Correct);
+        let mut quote = snapshot("CFFEX.IF2409", 3856.3, 3500.0, 4200.0);
+        assert!(checker.check(&mut quote, &instruments));
+        assert_eq!((quote.last_price, quote.bid_price1, quote.ask_price1), (3856.4, 3856.4, 3856.4));
+
+        let mut quote = snapshot("SHFE.rb2410", 3800.0, 3300.0, 3700.0);
+        assert!(checker.check(&mut quote, &instruments));
+        assert_eq!(quote.last_price, 3700.0);
+        assert_eq!(checker.report().stats.corrected, 2);
+    }
+
+    #[test]
+    fn test_correct_mode_with_off_tick_limits() {
+        let instruments = InstrumentRegistry::new();
+        let mut checker = checker(PriceCheckMode::Correct);
+        // 涨跌停价不在价位上时，修正后的价格仍在价位上且不超出涨跌停板
+        let mut quote = snapshot("SHFE.rb2410", 3800.0, 3300.5, 3700.5);
+        assert!(checker.check(&mut quote, &instruments));
+        assert_eq!(quote.last_price, 3700.0);
+        let mut quote = snapshot("SHFE.rb2410", 3300.2, 3300.5, 3700.5);
+        assert!(checker.check(&mut quote, &instruments));
+        assert_eq!(quote.last_price, 3301.0);
+        assert!(on_tick(quote.bid_price1, 1.0) && on_tick(quote.ask_price1, 1.0));
+    }
+}
@@ -0,0 +1,216 @@
+//! 预编码的行情增量
+//!
+//! 分发器每批次为每个合约构建一次增量，各格式（JSON、MessagePack、CBOR）在首次使用时编码并缓存，
+//! 同一批次的所有会话共用编码结果。未改写字段名、未通道复用的会话直接用缓存的编码拼接rtn_data帧，
+//! 只插入本会话的序号，不再解析和重新序列化行情。
+
+use actix_web::web::Bytes;
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+
+use crate::ws_server::WsFormat;
+
+/// 一个合约的行情增量及各格式的编码缓存
+#[derive(Debug, Default)]
+pub struct EncodedQuote {
+    fields: Map<String, Value>,
+    json: OnceLock<Bytes>,
+    msgpack: OnceLock<Bytes>,
+    cbor: OnceLock<Bytes>,
+}
+
+impl EncodedQuote {
+    /// 由行情JSON创建（非对象的JSON视为没有字段）
+    pub fn new(value: Value) -> Self {
+        let fields = match value {
+            Value::Object(fields) => fields,
+            _ => Map::new(),
+        };
+        Self {
+            fields,
+            ..Self::default()
+        }
+    }
+
+    /// 行情字段
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+    /// 按格式编码的行情字段，每种格式只编码一次
+    pub fn encoded(&self, format: WsFormat) -> &Bytes {
+        match format {
+            WsFormat::Json => self
+                .json
+                .get_or_init(|| Bytes::from(serde_json::to_vec(&self.fields).unwrap_or_default())),
+            WsFormat::MsgPack => self
+                .msgpack
+                .get_or_init(|| Bytes::from(rmp_serde::to_vec_named(&self.fields).unwrap_or_default())),
+            WsFormat::Cbor => self.cbor.get_or_init(|| {
+                let mut bytes = Vec::new();
+                match ciborium::into_writer(&self.fields, &mut bytes) {
+                    Ok(()) => Bytes::from(bytes),
+                    Err(_) => Bytes::new(),
+                }
+            }),
+        }
+    }
+}
+
+/// 拼接rtn_data帧时出错（缓存的编码不是预期的对象）
+#[derive(Debug)]
+pub struct FrameError;
+
+/// 由预编码的合约增量拼接rtn_data帧，每个合约的字段前插入序号
+///
+/// 结果与序列化`WsServerMessage::quote_data`（字段中含seq）相同，JSON帧为UTF-8文本
+pub fn quote_frame(format: WsFormat, quotes: &[(&str, u64, &EncodedQuote)]) -> Result<Vec<u8>, FrameError> {
+    match format {
+        WsFormat::Json => json_frame(quotes),
+        WsFormat::MsgPack => msgpack_frame(quotes),
+        WsFormat::Cbor => cbor_frame(quotes),
+    }
+}
+
+fn json_frame(quotes: &[(&str, u64, &EncodedQuote)]) -> Result<Vec<u8>, FrameError> {
+    let capacity = quotes.iter().map(|(_, _, quote)| quote.encoded(WsFormat::Json).len() + 48).sum::<usize>();
+    let mut out = Vec::with_capacity(capacity + 48);
+    out.extend_from_slice(br#"{"aid":"rtn_data","data":[{"quotes":{"#);
+    for (index, (instrument, seq, quote)) in quotes.iter().enumerate() {
+        if index > 0 {
+            out.push(b',');
+        }
+        serde_json::to_writer(&mut out, instrument).map_err(|_| FrameError)?;
+        let fields = quote.encoded(WsFormat::Json);
+        let rest = fields.strip_prefix(b"{").ok_or(FrameError)?;
+        out.extend_from_slice(format!(r#":{{"seq":{}"#, seq).as_bytes());
+        if rest != b"}" {
+            out.push(b',');
+        }
+        out.extend_from_slice(rest);
+    }
+    out.extend_from_slice(b"}}]}");
+    Ok(out)
+}
+
+fn msgpack_frame(quotes: &[(&str, u64, &EncodedQuote)]) -> Result<Vec<u8>, FrameError> {
+    use rmp::{decode, encode};
+
+    let mut out = Vec::new();
+    encode::write_map_len(&mut out, 2).map_err(|_| FrameError)?;
+    encode::write_str(&mut out, "aid").map_err(|_| FrameError)?;
+    encode::write_str(&mut out, "rtn_data").map_err(|_| FrameError)?;
+    encode::write_str(&mut out, "data").map_err(|_| FrameError)?;
+    encode::write_array_len(&mut out, 1).map_err(|_| FrameError)?;
+    encode::write_map_len(&mut out, 1).map_err(|_| FrameError)?;
+    encode::write_str(&mut out, "quotes").map_err(|_| FrameError)?;
+    encode::write_map_len(&mut out, quotes.len() as u32).map_err(|_| FrameError)?;
+    for (instrument, seq, quote) in quotes {
+        let mut rest: &[u8] = quote.encoded(WsFormat::MsgPack);
+        let len = decode::read_map_len(&mut rest).map_err(|_| FrameError)?;
+        encode::write_str(&mut out, instrument).map_err(|_| FrameError)?;
+        encode::write_map_len(&mut out, len + 1).map_err(|_| FrameError)?;
+        encode::write_str(&mut out, "seq").map_err(|_| FrameError)?;
+        encode::write_uint(&mut out, *seq).map_err(|_| FrameError)?;
+        out.extend_from_slice(rest);
+    }
+    Ok(out)
+}
+
+fn cbor_frame(quotes: &[(&str, u64, &EncodedQuote)]) -> Result<Vec<u8>, FrameError> {
+    use ciborium_ll::{Decoder, Encoder, Header};
+
+    let mut out = Vec::new();
+    let mut encoder = Encoder::from(&mut out);
+    encoder.push(Header::Map(Some(2))).map_err(|_| FrameError)?;
+    encoder.text("aid", None).map_err(|_| FrameError)?;
+    encoder.text("rtn_data", None).map_err(|_| FrameError)?;
+    encoder.text("data", None).map_err(|_| FrameError)?;
+    encoder.push(Header::Array(Some(1))).map_err(|_| FrameError)?;
+    encoder.push(Header::Map(Some(1))).map_err(|_| FrameError)?;
+    encoder.text("quotes", None).map_err(|_| FrameError)?;
+    encoder.push(Header::Map(Some(quotes.len()))).map_err(|_| FrameError)?;
+    for (instrument, seq, quote) in quotes {
+        let fields = quote.encoded(WsFormat::Cbor);
+        let mut decoder = Decoder::from(&fields[..]);
+        let Ok(Header::Map(Some(len))) = decoder.pull() else {
+            return Err(FrameError);
+        };
+        let rest = &fields[decoder.offset()..];
+        let mut encoder = Encoder::from(&mut out);
+        encoder.text(instrument, None).map_err(|_| FrameError)?;
+        encoder.push(Header::Map(Some(len + 1))).map_err(|_| FrameError)?;
+        encoder.text("seq", None).map_err(|_| FrameError)?;
+        encoder.push(Header::Positive(*seq)).map_err(|_| FrameError)?;
+        out.extend_from_slice(rest);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_server::WsServerMessage;
+    use serde_json::json;
+
+    fn quotes() -> Vec<(String, u64, EncodedQuote)> {
+        vec![
+            (
+                "SHFE.rb2410".to_string(),
+                7,
+                EncodedQuote::new(json!({"instrument_id": "SHFE.rb2410", "last_price": 3500.0, "volume": 12})),
+            ),
+            ("DCE.m2409".to_string(), 1, EncodedQuote::new(json!({}))),
+        ]
+    }
+
+    /// 序列化WsServerMessage得到的rtn_data帧
+    fn expected(quotes: &[(String, u64, EncodedQuote)]) -> WsServerMessage {
+        let mut data = Map::new();
+        for (instrument, seq, quote) in quotes {
+            let mut fields = quote.fields().clone();
+            fields.insert("seq".to_string(), json!(seq));
+            data.insert(instrument.clone(), Value::Object(fields));
+        }
+        WsServerMessage::quote_data(data)
+    }
+
+    fn frame(format: WsFormat, quotes: &[(String, u64, EncodedQuote)]) -> Vec<u8> {
+        let quotes: Vec<(&str, u64, &EncodedQuote)> =
+            quotes.iter().map(|(instrument, seq, quote)| (instrument.as_str(), *seq, quote)).collect();
+        quote_frame(format, &quotes).unwrap()
+    }
+
+    #[test]
+    fn json_frame_matches_serialized_message() {
+        let quotes = quotes();
+        let frame: Value = serde_json::from_slice(&frame(WsFormat::Json, &quotes)).unwrap();
+        assert_eq!(frame, serde_json::to_value(expected(&quotes)).unwrap());
+    }
+
+    #[test]
+    fn msgpack_frame_matches_serialized_message() {
+        let quotes = quotes();
+        let frame: Value = rmp_serde::from_slice(&frame(WsFormat::MsgPack, &quotes)).unwrap();
+        let expected: Value = rmp_serde::from_slice(&rmp_serde::to_vec_named(&expected(&quotes)).unwrap()).unwrap();
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn cbor_frame_matches_serialized_message() {
+        let quotes = quotes();
+        let frame: Value = ciborium::from_reader(&frame(WsFormat::Cbor, &quotes)[..]).unwrap();
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&expected(&quotes), &mut bytes).unwrap();
+        let expected: Value = ciborium::from_reader(&bytes[..]).unwrap();
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn encoding_is_cached_per_format() {
+        let quote = EncodedQuote::new(json!({"instrument_id": "SHFE.rb2410"}));
+        let first = quote.encoded(WsFormat::MsgPack).as_ptr();
+        assert_eq!(quote.encoded(WsFormat::MsgPack).as_ptr(), first);
+        assert_ne!(quote.encoded(WsFormat::Json).as_ptr(), first);
+    }
+}
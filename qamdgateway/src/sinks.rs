@@ -0,0 +1,112 @@
+//! Optional snapshot sinks that can be started at launch and on config reload
+//!
+//! Sinks register with the distributor under a fixed name, so restarting one
+//! replaces the previous registration.
+
+use actix::Addr;
+use tracing::{info, warn};
+use std::sync::Arc;
+
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::UnregisterSnapshotSink;
+use crate::config::{IpcConfig, RedisConfig, ZmqConfig};
+use crate::instruments::InstrumentRegistry;
+
+/// Distributor sink name of the Redis bridge
+pub const REDIS_SINK: &str = "redis";
+/// Distributor sink name of the ZeroMQ publisher
+pub const ZMQ_SINK: &str = "zmq";
+/// Distributor sink name of the local IPC transport
+pub const IPC_SINK: &str = "ipc";
+
+/// Detach a sink from the distributor, its actor stops once the registration is dropped
+pub fn stop_sink(name: &str, md_distributor: &Addr<MarketDataDistributor>) {
+    info!("Stopping {} sink", name);
+    md_distributor.do_send(UnregisterSnapshotSink {
+        name: name.to_string(),
+    });
+}
+
+/// Publish snapshots to Redis for non-WebSocket consumers
+#[cfg(feature = "redis-bridge")]
+pub fn start_redis_bridge(
+    redis_config: RedisConfig,
+    instruments: Arc<InstrumentRegistry>,
+    md_distributor: &Addr<MarketDataDistributor>,
+) {
+    use crate::actors::messages::RegisterSnapshotSink;
+    use crate::actors::redis_bridge::RedisBridgeActor;
+    use tracing::error;
+
+    info!("Starting Redis bridge to {}", redis_config.url);
+    match redis::Client::open(redis_config.url.as_str()) {
+        Ok(client) => {
+            let addr = actix::SyncArbiter::start(1, move || {
+                RedisBridgeActor::new(redis_config.clone(), client.clone(), instruments.clone())
+            });
+            md_distributor.do_send(RegisterSnapshotSink {
+                name: REDIS_SINK.to_string(),
+                addr: addr.recipient(),
+            });
+        }
+        Err(e) => error!("Invalid Redis configuration: {}", e),
+    }
+}
+
+#[cfg(not(feature = "redis-bridge"))]
+pub fn start_redis_bridge(
+    _redis_config: RedisConfig,
+    _instruments: Arc<InstrumentRegistry>,
+    _md_distributor: &Addr<MarketDataDistributor>,
+) {
+    warn!("Redis bridge is configured but the gateway was built without the `redis-bridge` feature");
+}
+
+/// Publish snapshots on a ZeroMQ PUB socket for legacy consumers
+#[cfg(feature = "zmq-pub")]
+pub fn start_zmq_publisher(
+    zmq_config: ZmqConfig,
+    md_distributor: &Addr<MarketDataDistributor>,
+) {
+    use crate::actors::messages::{RegisterSnapshotSink, RegisterTickSink};
+    use crate::actors::zmq_publisher::ZmqPublisherActor;
+
+    info!("Starting ZeroMQ publisher on {}", zmq_config.bind);
+    let ticks = zmq_config.ticks;
+    let addr = actix::SyncArbiter::start(1, move || ZmqPublisherActor::new(zmq_config.clone()));
+    if ticks {
+        md_distributor.do_send(RegisterTickSink {
+            name: ZMQ_SINK.to_string(),
+            addr: addr.clone().recipient(),
+        });
+    }
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: ZMQ_SINK.to_string(),
+        addr: addr.recipient(),
+    });
+}
+
+#[cfg(not(feature = "zmq-pub"))]
+pub fn start_zmq_publisher(
+    _zmq_config: ZmqConfig,
+    _md_distributor: &Addr<MarketDataDistributor>,
+) {
+    warn!("ZeroMQ publisher is configured but the gateway was built without the `zmq-pub` feature");
+}
+
+/// Stream length-prefixed msgpack snapshots to local consumers over a unix socket or named pipe
+pub fn start_ipc_server(
+    ipc_config: IpcConfig,
+    md_distributor: &Addr<MarketDataDistributor>,
+) {
+    use crate::actors::ipc_server::IpcServerActor;
+    use crate::actors::messages::RegisterSnapshotSink;
+    use actix::Actor;
+
+    info!("Starting IPC transport on {}", ipc_config.path);
+    let addr = IpcServerActor::new(ipc_config).start();
+    md_distributor.do_send(RegisterSnapshotSink {
+        name: IPC_SINK.to_string(),
+        addr: addr.recipient(),
+    });
+}
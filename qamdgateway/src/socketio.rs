@@ -0,0 +1,461 @@
+//! Socket.IO兼容接口
+//!
+//! 为使用socket.io客户端的旧看板提供最小的Engine.IO（v3/v4，仅websocket传输）握手和
+//! Socket.IO事件帧，客户端发送`subscribe`/`unsubscribe`事件订阅合约，网关以配置的事件名
+//! （默认`quote`）推送合并后的完整行情。行情经分发器获取，与WebSocket接口走同一条管道。
+//!
+//! 客户端需要使用websocket传输（`transports: ["websocket"]`），不支持HTTP长轮询。
+
+use actix::{Actor, ActorContext, AsyncContext, Handler};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use hashbrown::{HashMap, HashSet};
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::acl::{request_token, AclStore, Permissions};
+use crate::actors::md_distributor::MarketDataDistributor;
+use crate::actors::messages::*;
+use crate::config::{ClientQuotaConfig, SocketIoConfig};
+use crate::error::GatewayError;
+use crate::instruments::InstrumentRegistry;
+use crate::ws_server::denied_message;
+
+// 客户端可发送的最大消息长度
+const MAX_PAYLOAD: usize = 1_000_000;
+
+/// Engine.IO协议版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EngineVersion {
+    /// v3：客户端发送ping，连接后自动进入默认命名空间
+    V3,
+    /// v4：服务端发送ping，客户端需先发送CONNECT
+    V4,
+}
+
+/// Socket.IO事件包（`2[/命名空间,][确认ID]["事件",参数...]`）
+#[derive(Debug, PartialEq)]
+struct EventPacket {
+    ack_id: Option<u64>,
+    name: String,
+    args: Vec<Value>,
+}
+
+/// 分离包前面的命名空间（`/命名空间,`），默认命名空间为空字符串
+fn split_namespace(data: &str) -> (&str, &str) {
+    match data.strip_prefix('/') {
+        Some(rest) => rest.split_once(',').unwrap_or((rest, "")),
+        None => ("", data),
+    }
+}
+
+/// 解析Socket.IO事件包（不含包类型），只支持默认命名空间
+fn parse_event(data: &str) -> Result<EventPacket, String> {
+    let (namespace, data) = split_namespace(data);
+    if !namespace.is_empty() {
+        return Err(format!("Unsupported namespace /{}", namespace));
+    }
+    let digits = data.bytes().take_while(u8::is_ascii_digit).count();
+    let (ack_id, payload) = data.split_at(digits);
+    let ack_id = (!ack_id.is_empty()).then(|| ack_id.parse().ok()).flatten();
+    let Ok(Value::Array(mut values)) = serde_json::from_str::<Value>(payload) else {
+        return Err(format!("Invalid event payload: {}", payload));
+    };
+    if values.is_empty() {
+        return Err("Event without a name".to_string());
+    }
+    let Value::String(name) = values.remove(0) else {
+        return Err("Event name must be a string".to_string());
+    };
+    Ok(EventPacket {
+        ack_id,
+        name,
+        args: values,
+    })
+}
+
+/// 事件参数中的合约列表（数组或逗号分隔的字符串）
+fn event_instruments(args: &[Value]) -> Vec<String> {
+    let mut instruments = Vec::new();
+    for arg in args {
+        match arg {
+            Value::String(list) => instruments.extend(
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|i| !i.is_empty())
+                    .map(str::to_string),
+            ),
+            Value::Array(items) => instruments.extend(event_instruments(items)),
+            Value::Object(object) => {
+                if let Some(list) = object.get("instruments").or_else(|| object.get("ins_list")) {
+                    instruments.extend(event_instruments(std::slice::from_ref(list)));
+                }
+            }
+            _ => {}
+        }
+    }
+    instruments
+}
+
+/// Socket.IO会话
+pub struct SocketIoSession {
+    client_id: String,
+    version: EngineVersion,
+    config: SocketIoConfig,
+    md_distributor: actix::Addr<MarketDataDistributor>,
+    instruments: Arc<InstrumentRegistry>,
+    // 最多订阅的合约数（0表示不限制）
+    max_instruments: usize,
+    acl: Option<Arc<AclStore>>,
+    token: Option<String>,
+    // 令牌权限（按访问控制的版本缓存）
+    permissions: Option<(u64, Permissions)>,
+    // 是否已进入默认命名空间
+    connected: bool,
+    subscriptions: HashSet<String>,
+    // 合并后的完整行情
+    quotes: HashMap<String, Map<String, Value>>,
+    // 最后收到客户端心跳的时间
+    heartbeat: Instant,
+}
+
+impl Actor for SocketIoSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("Socket.IO session {} started ({:?})", self.client_id, self.version);
+        let addr = ctx.address();
+        self.md_distributor.do_send(RegisterDataReceiver {
+            client_id: self.client_id.clone(),
+            addr: addr.clone().recipient(),
+            instruments: Vec::new(),
+            notify: None,
+            remap: None,
+            disconnect: Some(addr.recipient()),
+        });
+
+        // Engine.IO握手
+        let open = json!({
+            "sid": self.client_id,
+            "upgrades": [],
+            "pingInterval": self.config.ping_interval_ms,
+            "pingTimeout": self.config.ping_timeout_ms,
+            "maxPayload": MAX_PAYLOAD,
+        });
+        ctx.text(format!("0{}", open));
+        if self.version == EngineVersion::V3 {
+            self.connected = true;
+            ctx.text("40");
+        }
+
+        // v4由服务端发送ping，v3只检查客户端的ping
+        let interval = Duration::from_millis(self.config.ping_interval_ms.max(1000));
+        let timeout = interval + Duration::from_millis(self.config.ping_timeout_ms);
+        ctx.run_interval(interval, move |act, ctx| {
+            if act.heartbeat.elapsed() > timeout {
+                info!("Socket.IO session {} ping timeout, disconnecting", act.client_id);
+                ctx.stop();
+                return;
+            }
+            if act.version == EngineVersion::V4 {
+                ctx.text("2");
+            }
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        info!("Socket.IO session {} stopped", self.client_id);
+        self.md_distributor.do_send(UnregisterDataReceiver {
+            client_id: self.client_id.clone(),
+        });
+    }
+}
+
+impl SocketIoSession {
+    /// 处理Engine.IO包
+    fn handle_packet(&mut self, ctx: &mut ws::WebsocketContext<Self>, text: &str) {
+        let mut chars = text.chars();
+        match chars.next() {
+            // ping（v3客户端发送，探测包原样带回）
+            Some('2') => {
+                self.heartbeat = Instant::now();
+                ctx.text(format!("3{}", chars.as_str()));
+            }
+            // pong
+            Some('3') => self.heartbeat = Instant::now(),
+            Some('4') => self.handle_message(ctx, chars.as_str()),
+            Some('1') => ctx.stop(),
+            // upgrade/noop
+            Some('5') | Some('6') => {}
+            _ => debug!("Socket.IO session {} ignored packet {}", self.client_id, text),
+        }
+    }
+
+    /// 处理Socket.IO包
+    fn handle_message(&mut self, ctx: &mut ws::WebsocketContext<Self>, data: &str) {
+        let mut chars = data.chars();
+        match chars.next() {
+            Some('0') => self.handle_connect(ctx, chars.as_str()),
+            Some('1') => {
+                self.connected = false;
+                self.unsubscribe(self.subscriptions.iter().cloned().collect());
+            }
+            Some('2') if self.connected => match parse_event(chars.as_str()) {
+                Ok(event) => self.handle_event(ctx, event),
+                Err(e) => {
+                    warn!("Socket.IO session {}: {}", self.client_id, e);
+                    self.emit(ctx, "error", json!({ "message": e }));
+                }
+            },
+            _ => debug!("Socket.IO session {} ignored message {}", self.client_id, data),
+        }
+    }
+
+    /// 进入默认命名空间，可在CONNECT的auth中携带令牌（`{"token": "..."}`）
+    fn handle_connect(&mut self, ctx: &mut ws::WebsocketContext<Self>, data: &str) {
+        let (namespace, auth) = split_namespace(data);
+        if !namespace.is_empty() {
+            ctx.text(format!("44/{},{}", namespace, json!({ "message": "Invalid namespace" })));
+            return;
+        }
+        let token = serde_json::from_str::<Value>(auth)
+            .ok()
+            .and_then(|auth| auth.get("token").and_then(Value::as_str).map(str::to_string));
+        if let (Some(acl), Some(token)) = (&self.acl, &token) {
+            if !acl.contains(token) {
+                warn!("Socket.IO session {} rejected: unknown token", self.client_id);
+                ctx.text(format!("44{}", json!({ "message": "unknown token" })));
+                ctx.stop();
+                return;
+            }
+        }
+        if token.is_some() {
+            self.token = token;
+            self.permissions = None;
+        }
+        self.connected = true;
+        ctx.text(format!("40{}", json!({ "sid": self.client_id })));
+    }
+
+    /// 处理客户端事件，带确认ID时返回结果
+    fn handle_event(&mut self, ctx: &mut ws::WebsocketContext<Self>, event: EventPacket) {
+        let result = match event.name.as_str() {
+            "subscribe" => self.subscribe(event_instruments(&event.args)),
+            "unsubscribe" => Ok(self.unsubscribe(event_instruments(&event.args))),
+            name => Err(GatewayError::InvalidMessage(format!("Unknown event {}", name))),
+        };
+        match (event.ack_id, result) {
+            (Some(id), Ok(instruments)) => {
+                ctx.text(format!("43{}{}", id, json!([{ "ok": true, "instruments": instruments }])));
+            }
+            (Some(id), Err(e)) => {
+                let reply = json!({ "ok": false, "code": e.code(), "error": e.to_string() });
+                ctx.text(format!("43{}{}", id, json!([reply])));
+            }
+            (None, Ok(_)) => {}
+            (None, Err(e)) => self.emit(ctx, "error", json!({ "code": e.code(), "error": e.to_string() })),
+        }
+    }
+
+    /// 订阅合约（合约组在本地展开），返回新订阅的合约
+    fn subscribe(&mut self, instruments: Vec<String>) -> Result<Vec<String>, GatewayError> {
+        let instruments = self.instruments.expand(&instruments);
+        if instruments.is_empty() {
+            return Err(GatewayError::NoInstruments);
+        }
+        let (permitted, denied): (Vec<String>, Vec<String>) =
+            instruments.into_iter().partition(|instrument| self.permits(instrument));
+        if !denied.is_empty() {
+            return Err(GatewayError::PermissionDenied(denied_message(&denied)));
+        }
+        let added: Vec<String> = permitted
+            .into_iter()
+            .filter(|instrument| !self.subscriptions.contains(instrument))
+            .collect();
+        let total = self.subscriptions.len() + added.len();
+        if self.max_instruments > 0 && total > self.max_instruments {
+            return Err(GatewayError::QuotaExceeded(format!(
+                "subscription of {} instruments exceeds the limit of {}",
+                total, self.max_instruments
+            )));
+        }
+        self.subscriptions.extend(added.iter().cloned());
+        self.md_distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: self.subscriptions.iter().cloned().collect(),
+        });
+        Ok(added)
+    }
+
+    /// 取消订阅，返回取消的合约
+    fn unsubscribe(&mut self, instruments: Vec<String>) -> Vec<String> {
+        let removed: Vec<String> = self
+            .instruments
+            .expand(&instruments)
+            .into_iter()
+            .filter(|instrument| self.subscriptions.remove(instrument))
+            .collect();
+        if !removed.is_empty() {
+            for instrument in &removed {
+                self.quotes.remove(instrument);
+            }
+            self.md_distributor.do_send(UpdateSubscription {
+                client_id: self.client_id.clone(),
+                instruments: self.subscriptions.iter().cloned().collect(),
+            });
+        }
+        removed
+    }
+
+    /// 令牌是否允许订阅该合约（未开启访问控制时全部允许）
+    fn permits(&mut self, instrument: &str) -> bool {
+        let Some(acl) = &self.acl else {
+            return true;
+        };
+        let generation = acl.generation();
+        if !matches!(&self.permissions, Some((cached, _)) if *cached == generation) {
+            self.permissions = Some((generation, acl.permissions(self.token.as_deref())));
+        }
+        self.permissions
+            .as_ref()
+            .is_some_and(|(_, permissions)| permissions.allows(instrument, &self.instruments))
+    }
+
+    /// 发送事件（`42["事件",数据]`）
+    fn emit(&self, ctx: &mut ws::WebsocketContext<Self>, name: &str, data: Value) {
+        ctx.text(format!("42{}", json!([name, data])));
+    }
+}
+
+/// 合并增量行情后推送完整行情
+impl Handler<MarketDataUpdateMessage> for SocketIoSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarketDataUpdateMessage, ctx: &mut Self::Context) {
+        if !self.connected {
+            return;
+        }
+        for instrument in &msg.instruments {
+            if !self.subscriptions.contains(instrument) {
+                continue;
+            }
+            let Some(data) = msg.data.get(instrument) else {
+                continue;
+            };
+            let quote = self.quotes.entry_ref(instrument.as_str()).or_default();
+            quote.extend(data.fields().iter().map(|(field, value)| (field.clone(), value.clone())));
+            let quote = Value::Object(quote.clone());
+            self.emit(ctx, &self.config.event, quote);
+        }
+    }
+}
+
+/// 管理接口强制断开
+impl Handler<ForceDisconnect> for SocketIoSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: ForceDisconnect, ctx: &mut Self::Context) {
+        info!("Socket.IO session {} disconnected by admin: {}", self.client_id, msg.reason);
+        ctx.text("41");
+        ctx.stop();
+    }
+}
+
+/// 处理客户端消息
+impl actix::StreamHandler<Result<ws::Message, ws::ProtocolError>> for SocketIoSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Text(text)) => {
+                if text.len() > MAX_PAYLOAD {
+                    warn!("Socket.IO session {} sent {} bytes, disconnecting", self.client_id, text.len());
+                    ctx.stop();
+                    return;
+                }
+                self.handle_packet(ctx, &text);
+            }
+            Ok(ws::Message::Ping(msg)) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("Socket.IO protocol error: {}", e);
+                ctx.stop();
+            }
+        }
+    }
+}
+
+/// 创建Socket.IO处理器
+///
+/// 只接受`transport=websocket`的连接，其他传输按Engine.IO的错误格式返回400
+pub async fn socketio_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    config: web::Data<SocketIoConfig>,
+    md_distributor: web::Data<actix::Addr<MarketDataDistributor>>,
+    instruments: web::Data<InstrumentRegistry>,
+    quota: web::Data<RwLock<ClientQuotaConfig>>,
+    acl: Option<web::Data<AclStore>>,
+) -> Result<HttpResponse, Error> {
+    let params = web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string())
+        .map(|params| params.into_inner())
+        .unwrap_or_default();
+    let version = match params.get("EIO").map(String::as_str) {
+        Some("3") => EngineVersion::V3,
+        Some("4") => EngineVersion::V4,
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "code": 5,
+                "message": "Unsupported protocol version",
+            })));
+        }
+    };
+    if params.get("transport").map(String::as_str) != Some("websocket") {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "code": 0,
+            "message": "Transport unknown",
+        })));
+    }
+
+    // 开启访问控制时，携带的令牌必须已登记
+    let token = request_token(&req);
+    let acl = acl.map(|acl| acl.into_inner());
+    if let (Some(acl), Some(token)) = (&acl, &token) {
+        if !acl.contains(token) {
+            warn!(
+                "Rejected Socket.IO connection with an unknown token from {}",
+                req.connection_info().realip_remote_addr().unwrap_or("unknown")
+            );
+            let error = GatewayError::AuthError("unknown token".to_string());
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "code": error.code(),
+                "error": error.to_string(),
+            })));
+        }
+    }
+
+    let session = SocketIoSession {
+        client_id: Uuid::new_v4().to_string(),
+        version,
+        config: config.get_ref().clone(),
+        md_distributor: md_distributor.get_ref().clone(),
+        instruments: instruments.into_inner(),
+        max_instruments: quota.read().unwrap_or_else(|e| e.into_inner()).max_instruments,
+        acl,
+        token,
+        permissions: None,
+        connected: false,
+        subscriptions: HashSet::new(),
+        quotes: HashMap::new(),
+        heartbeat: Instant::now(),
+    };
+    ws::start(session, &req, stream)
+}
@@ -0,0 +1,300 @@
+//! CTP接口兼容的行情API（CTP、openctp的QQ和新浪行情）
+//!
+//! 三个API的接口完全相同，只是动态库不同，插件实现由宏为每个API生成
+
+use ctp_common::CThostFtdcReqUserLoginField;
+use std::ffi::CString;
+use std::path::Path;
+
+use crate::config::BrokerConfig;
+
+/// 把字符串写入以0结尾的定长字段（超长时截断）
+#[allow(dead_code)]
+fn fill_field(field: &mut [u8], value: &str) {
+    if value.is_empty() || field.is_empty() {
+        return;
+    }
+    let bytes = value.as_bytes();
+    let copy_len = bytes.len().min(field.len() - 1);
+    field[..copy_len].copy_from_slice(&bytes[..copy_len]);
+    field[copy_len] = 0;
+}
+
+/// 登录请求
+#[allow(dead_code)]
+fn login_request(config: &BrokerConfig) -> CThostFtdcReqUserLoginField {
+    let mut req = CThostFtdcReqUserLoginField::default();
+    fill_field(&mut req.BrokerID, &config.broker_id);
+    fill_field(&mut req.UserID, &config.user_id);
+    fill_field(&mut req.Password, &config.password);
+    req
+}
+
+/// API的流文件路径参数是文件名前缀，目录需以路径分隔符结尾
+#[allow(dead_code)]
+fn flow_prefix(flow_dir: &Path) -> String {
+    let mut prefix = flow_dir.to_string_lossy().into_owned();
+    if !prefix.ends_with(std::path::MAIN_SEPARATOR) {
+        prefix.push(std::path::MAIN_SEPARATOR);
+    }
+    prefix
+}
+
+/// 订阅请求的合约代码（股票代码可能带交易所前缀，API只接受不带前缀的代码）
+#[allow(dead_code)]
+fn instrument_codes(instruments: &[String]) -> Vec<CString> {
+    instruments
+        .iter()
+        .filter_map(|instrument| {
+            let code = instrument.rsplit('.').next().unwrap_or(instrument);
+            CString::new(code).ok()
+        })
+        .collect()
+}
+
+macro_rules! ctp_api_adapter {
+    ($module:ident, $api:ident, $source:expr) => {
+        pub mod $module {
+            use actix::Recipient;
+            use ctp_common::{CThostFtdcDepthMarketDataField, CThostFtdcRspUserLoginField, CThostFtdcSpecificInstrumentField};
+            use tracing::{error, info, warn};
+            use std::ffi::CString;
+            use std::panic::{self, AssertUnwindSafe};
+            use std::path::Path;
+
+            use $api::{DisconnectionReason, GenericMdApi, MdApi, MdSpi, RspResult};
+
+            use super::{flow_prefix, instrument_codes, login_request};
+            use crate::actors::messages::{MarketDataEvent, MarketDataSource};
+            use crate::config::BrokerConfig;
+            use crate::error::{GatewayError, GatewayResult};
+            use crate::latency;
+            use crate::sources::MarketDataSourceAdapter;
+            use crate::supervision;
+
+            /// 合约代码
+            fn instrument_id(instrument: &CThostFtdcSpecificInstrumentField) -> String {
+                String::from_utf8_lossy(&instrument.InstrumentID)
+                    .trim_end_matches('\0')
+                    .to_string()
+            }
+
+            // SPI回调转换为MarketDataEvent发送给行情Actor
+            struct Spi {
+                events: Recipient<MarketDataEvent>,
+            }
+
+            impl Spi {
+                // 捕获回调中的panic（panic不能穿过API的C++回调），转为事件交给行情Actor
+                fn guard(&mut self, f: impl FnOnce(&mut Self)) {
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| f(self))) {
+                        self.events.do_send(MarketDataEvent::Panicked(supervision::panic_message(payload.as_ref())));
+                    }
+                }
+            }
+
+            impl MdSpi for Spi {
+                fn on_front_connected(&mut self) {
+                    self.guard(|spi| {
+                        info!("MD Front connected");
+                        spi.events.do_send(MarketDataEvent::Connected);
+                    });
+                }
+
+                fn on_front_disconnected(&mut self, reason: DisconnectionReason) {
+                    self.guard(|spi| {
+                        warn!("MD Front disconnected: {:?}", reason);
+                        spi.events.do_send(MarketDataEvent::Disconnected(format!("{:?}", reason)));
+                    });
+                }
+
+                fn on_rsp_user_login(
+                    &mut self,
+                    rsp_user_login: Option<&CThostFtdcRspUserLoginField>,
+                    result: RspResult,
+                    request_id: i32,
+                    is_last: bool,
+                ) {
+                    self.guard(|spi| {
+                        info!("Login response: RequestID={}, IsLast={}", request_id, is_last);
+
+                        if let Some(login_info) = rsp_user_login {
+                            info!(
+                                "MD Logged in: Trading Day = {}, Login Time = {}, Broker ID = {}, User ID = {}",
+                                String::from_utf8_lossy(&login_info.TradingDay),
+                                String::from_utf8_lossy(&login_info.LoginTime),
+                                String::from_utf8_lossy(&login_info.BrokerID),
+                                String::from_utf8_lossy(&login_info.UserID)
+                            );
+                            spi.events.do_send(MarketDataEvent::LoggedIn);
+                        } else if let Some(error) = result.err() {
+                            let error_msg = format!("MD Login failed: Error = {}", error);
+                            error!("{}", error_msg);
+                            spi.events.do_send(MarketDataEvent::Error(error_msg));
+                        }
+                    });
+                }
+
+                fn on_rsp_sub_market_data(
+                    &mut self,
+                    specific_instrument: Option<&CThostFtdcSpecificInstrumentField>,
+                    result: RspResult,
+                    request_id: i32,
+                    is_last: bool,
+                ) {
+                    self.guard(|spi| {
+                        info!("Subscribe response: RequestID={}, IsLast={}", request_id, is_last);
+
+                        if let Some(instrument) = specific_instrument {
+                            let instrument_id = instrument_id(instrument);
+                            match result.err() {
+                                None => spi.events.do_send(MarketDataEvent::SubscriptionSuccess(instrument_id)),
+                                Some(error) => {
+                                    let error_msg = format!(
+                                        "Failed to subscribe to market data for {}: Error = {}",
+                                        instrument_id, error
+                                    );
+                                    spi.events.do_send(MarketDataEvent::SubscriptionFailure(instrument_id, error_msg));
+                                }
+                            }
+                        }
+                    });
+                }
+
+                fn on_rtn_depth_market_data(&mut self, depth_market_data: Option<&CThostFtdcDepthMarketDataField>) {
+                    self.guard(|spi| {
+                        if let Some(market_data) = depth_market_data {
+                            spi.events.do_send(MarketDataEvent::MarketData(*market_data, latency::now_us()));
+                        }
+                    });
+                }
+
+                fn on_rsp_un_sub_market_data(
+                    &mut self,
+                    specific_instrument: Option<&CThostFtdcSpecificInstrumentField>,
+                    result: RspResult,
+                    request_id: i32,
+                    is_last: bool,
+                ) {
+                    self.guard(|spi| {
+                        info!("Unsubscribe response: RequestID={}, IsLast={}", request_id, is_last);
+
+                        if let Some(instrument) = specific_instrument {
+                            let instrument_id = instrument_id(instrument);
+                            match result.err() {
+                                None => spi.events.do_send(MarketDataEvent::Unsubscribed(instrument_id)),
+                                Some(error) => error!(
+                                    "Failed to unsubscribe from market data for {}: Error = {}",
+                                    instrument_id, error
+                                ),
+                            }
+                        }
+                    });
+                }
+
+                fn on_rsp_error(&mut self, result: RspResult, request_id: i32, is_last: bool) {
+                    self.guard(|spi| {
+                        if let Some(error) = result.err() {
+                            let error_msg = format!(
+                                "MD error: Request ID = {}, Is Last = {}, Error = {}",
+                                request_id, is_last, error
+                            );
+                            error!("{}", error_msg);
+                            spi.events.do_send(MarketDataEvent::Error(error_msg));
+                        }
+                    });
+                }
+            }
+
+            /// 行情源插件
+            pub struct Adapter {
+                md_api: Option<MdApi>,
+            }
+
+            impl Adapter {
+                /// 加载行情API动态库，动态库不可用时返回错误
+                pub fn new() -> GatewayResult<Self> {
+                    $api::load_api().map_err(GatewayError::UpstreamUnavailable)?;
+                    Ok(Self { md_api: None })
+                }
+
+                fn api(&mut self) -> GatewayResult<&mut MdApi> {
+                    self.md_api
+                        .as_mut()
+                        .ok_or_else(|| GatewayError::UpstreamUnavailable("MD API not initialized".to_string()))
+                }
+            }
+
+            impl MarketDataSourceAdapter for Adapter {
+                fn source(&self) -> MarketDataSource {
+                    $source
+                }
+
+                fn connect(
+                    &mut self,
+                    front_addr: &str,
+                    flow_dir: Option<&Path>,
+                    events: Recipient<MarketDataEvent>,
+                ) -> GatewayResult<()> {
+                    let front_addr = CString::new(front_addr)
+                        .map_err(|e| GatewayError::ConfigError(format!("Invalid front address: {}", e)))?;
+                    let flow_path = CString::new(flow_dir.map(flow_prefix).unwrap_or_default())
+                        .map_err(|e| GatewayError::ConfigError(format!("Invalid flow directory: {}", e)))?;
+                    let mut md_api = MdApi::new(flow_path, false, false);
+                    md_api.register_spi(Box::new(Spi { events }));
+                    md_api.register_front(front_addr);
+                    md_api.init();
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+
+                    self.md_api = Some(md_api);
+                    Ok(())
+                }
+
+                fn is_connected(&self) -> bool {
+                    self.md_api.is_some()
+                }
+
+                fn login(&mut self, config: &BrokerConfig) -> GatewayResult<()> {
+                    let req = login_request(config);
+                    match self.api()?.req_user_login(&req, 1) {
+                        Ok(_) => {
+                            std::thread::sleep(std::time::Duration::from_secs(1));
+                            Ok(())
+                        }
+                        Err(e) => Err(GatewayError::CtpError(format!("Failed to send login request: {:?}", e))),
+                    }
+                }
+
+                fn subscribe(&mut self, instruments: &[String]) -> GatewayResult<()> {
+                    let codes = instrument_codes(instruments);
+                    info!("Subscribing to instruments: {:?}", codes);
+                    self.api()?
+                        .subscribe_market_data(&codes)
+                        .map(|_| ())
+                        .map_err(|e| GatewayError::CtpError(format!("Failed to subscribe to instruments, error: {:?}", e)))
+                }
+
+                fn unsubscribe(&mut self, instruments: &[String]) -> GatewayResult<()> {
+                    let codes = instrument_codes(instruments);
+                    self.api()?
+                        .unsubscribe_market_data(&codes)
+                        .map(|_| ())
+                        .map_err(|e| {
+                            GatewayError::CtpError(format!("Failed to unsubscribe from instruments, error: {:?}", e))
+                        })
+                }
+
+                fn disconnect(&mut self) {
+                    self.md_api = None;
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "ctp")]
+ctp_api_adapter!(ctp, ctp_md, MarketDataSource::CTP);
+#[cfg(feature = "qq")]
+ctp_api_adapter!(qq, ctp_md_qq, MarketDataSource::QQ);
+#[cfg(feature = "sina")]
+ctp_api_adapter!(sina, ctp_md_sina, MarketDataSource::Sina);
@@ -0,0 +1,115 @@
+//! 行情API流文件目录
+//!
+//! CTP接口把对话和查询流保存在流文件目录下的`.con`文件中，多个连接共用同一目录会
+//! 互相覆盖流状态。每个券商连接独占一个目录：同一进程内目录只能由一个券商占用
+//! （同一券商重启时可以重复占用），首次占用时创建目录并清理超过大小上限的旧`.con`文件。
+
+use hashbrown::HashMap;
+use tracing::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::error::{GatewayError, GatewayResult};
+
+/// 占用中的目录 -> (占用者, 占用次数)
+fn claims() -> &'static Mutex<HashMap<PathBuf, (String, usize)>> {
+    static CLAIMS: OnceLock<Mutex<HashMap<PathBuf, (String, usize)>>> = OnceLock::new();
+    CLAIMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 券商连接独占的流文件目录，释放时解除占用
+#[derive(Debug)]
+pub struct FlowDir {
+    path: PathBuf,
+}
+
+impl FlowDir {
+    /// 为`owner`（数据源和券商名称）占用流文件目录
+    ///
+    /// 目录不存在时创建；首次占用时按修改时间从旧到新删除`.con`文件，
+    /// 直到总大小不超过`max_bytes`（为0时不清理）。目录已被其他券商占用时返回错误
+    pub fn acquire(path: &Path, owner: &str, max_bytes: u64) -> GatewayResult<Self> {
+        std::fs::create_dir_all(path).map_err(|e| {
+            GatewayError::ConfigError(format!("Cannot create flow directory {}: {}", path.display(), e))
+        })?;
+        let path = path.canonicalize()?;
+
+        let mut claims = claims().lock().unwrap_or_else(|e| e.into_inner());
+        match claims.get_mut(&path) {
+            Some((holder, _)) if holder != owner => {
+                return Err(GatewayError::ConfigError(format!(
+                    "Flow directory {} is already used by {}, set a distinct flow_dir for {}",
+                    path.display(),
+                    holder,
+                    owner
+                )));
+            }
+            Some((_, count)) => *count += 1,
+            None => {
+                if max_bytes > 0 {
+                    match cleanup(&path, max_bytes) {
+                        Ok((0, _)) => {}
+                        Ok((removed, freed)) => info!(
+                            "Removed {} stale flow files ({} bytes) from {}",
+                            removed,
+                            freed,
+                            path.display()
+                        ),
+                        Err(e) => warn!("Failed to clean flow directory {}: {}", path.display(), e),
+                    }
+                }
+                claims.insert(path.clone(), (owner.to_string(), 1));
+            }
+        }
+        Ok(Self { path })
+    }
+
+    /// 目录路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for FlowDir {
+    fn drop(&mut self) {
+        let mut claims = claims().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, count)) = claims.get_mut(&self.path) {
+            *count -= 1;
+            if *count == 0 {
+                claims.remove(&self.path);
+            }
+        }
+    }
+}
+
+/// 按修改时间从旧到新删除`.con`文件，直到总大小不超过上限，返回删除的文件数和字节数
+fn cleanup(path: &Path, max_bytes: u64) -> std::io::Result<(usize, u64)> {
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file = entry.path();
+        if file.extension().is_none_or(|ext| ext != "con") {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, metadata.len(), file));
+        }
+    }
+
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_by_key(|(modified, _, _)| *modified);
+    let (mut removed, mut freed) = (0, 0);
+    for (_, len, file) in files {
+        if total <= max_bytes {
+            break;
+        }
+        std::fs::remove_file(&file)?;
+        total -= len;
+        removed += 1;
+        freed += len;
+    }
+    Ok((removed, freed))
+}
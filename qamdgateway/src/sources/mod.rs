@@ -0,0 +1,103 @@
+//! 行情源插件
+//!
+//! 每种柜台行情API实现`MarketDataSourceAdapter`，由`MarketDataActor`统一驱动连接、登录、
+//! 订阅和重连。行情源按`BrokerConfig::source_type`在运行时选择，编译时启用的
+//! 行情源（`ctp`、`qq`、`sina`特性）可以在同一进程中同时使用。
+
+use actix::Recipient;
+use std::path::Path;
+
+use crate::actors::messages::{MarketDataEvent, MarketDataSource};
+use crate::config::BrokerConfig;
+use crate::error::{GatewayError, GatewayResult};
+
+mod ctp_api;
+mod flow;
+
+pub use flow::FlowDir;
+
+/// 行情源插件接口
+///
+/// 连接和登录是异步的：结果以`MarketDataEvent`（Connected、LoggedIn、MarketData等）
+/// 发送到`connect`时传入的事件接收者
+pub trait MarketDataSourceAdapter {
+    /// 行情源类型
+    fn source(&self) -> MarketDataSource;
+
+    /// 连接前置，之后的回调事件发送到`events`；`flow_dir`为该连接独占的流文件目录
+    fn connect(
+        &mut self,
+        front_addr: &str,
+        flow_dir: Option<&Path>,
+        events: Recipient<MarketDataEvent>,
+    ) -> GatewayResult<()>;
+
+    /// 是否已经建立连接（API已初始化）
+    fn is_connected(&self) -> bool;
+
+    /// 发送登录请求
+    fn login(&mut self, config: &BrokerConfig) -> GatewayResult<()>;
+
+    /// 发送订阅请求
+    fn subscribe(&mut self, instruments: &[String]) -> GatewayResult<()>;
+
+    /// 发送退订请求
+    fn unsubscribe(&mut self, instruments: &[String]) -> GatewayResult<()>;
+
+    /// 释放API，断开前置连接
+    fn disconnect(&mut self);
+}
+
+/// 编译时启用的行情源
+pub fn available_sources() -> Vec<MarketDataSource> {
+    let mut sources = Vec::new();
+    if cfg!(feature = "ctp") {
+        sources.push(MarketDataSource::CTP);
+    }
+    if cfg!(feature = "qq") {
+        sources.push(MarketDataSource::QQ);
+    }
+    if cfg!(feature = "sina") {
+        sources.push(MarketDataSource::Sina);
+    }
+    sources
+}
+
+/// 按名称解析行情源（不区分大小写），未指定时使用第一个启用的行情源
+pub fn resolve_source(name: Option<&str>) -> GatewayResult<MarketDataSource> {
+    let source = match name.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("") => available_sources().first().copied().ok_or_else(|| {
+            GatewayError::ConfigError("No market data source compiled in, enable ctp, qq or sina".to_string())
+        })?,
+        Some("ctp") => MarketDataSource::CTP,
+        Some("qq") => MarketDataSource::QQ,
+        Some("sina") => MarketDataSource::Sina,
+        Some(other) => {
+            return Err(GatewayError::ConfigError(format!("Unknown market data source type: {}", other)))
+        }
+    };
+    if !available_sources().contains(&source) {
+        return Err(GatewayError::ConfigError(format!(
+            "Market data source {:?} is not compiled in, rebuild with its feature enabled",
+            source
+        )));
+    }
+    Ok(source)
+}
+
+/// 创建行情源插件
+#[allow(unreachable_code)]
+pub fn create_adapter(source: MarketDataSource) -> GatewayResult<Box<dyn MarketDataSourceAdapter>> {
+    match source {
+        #[cfg(feature = "ctp")]
+        MarketDataSource::CTP => Ok(Box::new(ctp_api::ctp::Adapter::new()?)),
+        #[cfg(feature = "qq")]
+        MarketDataSource::QQ => Ok(Box::new(ctp_api::qq::Adapter::new()?)),
+        #[cfg(feature = "sina")]
+        MarketDataSource::Sina => Ok(Box::new(ctp_api::sina::Adapter::new()?)),
+        other => Err(GatewayError::ConfigError(format!(
+            "Market data source {:?} has no adapter",
+            other
+        ))),
+    }
+}
@@ -0,0 +1,254 @@
+//! 故障隔离与监督
+//!
+//! 行情转换、SPI回调和分发器中的panic在原地捕获，转换为结构化的崩溃事件，
+//! 不再中止所在的arbiter线程：
+//! - SPI回调中的panic作为`MarketDataEvent::Panicked`发给上游行情Actor
+//! - 上游行情Actor处理行情时panic后释放连接并停止，由连接器按`supervision`的重启策略重新创建
+//! - 分发器处理单条行情时panic只丢弃这条行情
+//!
+//! 各组件的崩溃次数和最近的崩溃事件通过`/api/stats/crashes`和管理通道的状态推送查询。
+
+use chrono::{DateTime, Utc};
+use hashbrown::HashMap;
+use tracing::error;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::SupervisionConfig;
+
+/// 保留的最近崩溃事件数
+const RECENT_CRASHES: usize = 50;
+
+/// 一次被捕获的panic
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashEvent {
+    /// 发生panic的组件（如`md_actor`、`spi`、`distributor`）
+    pub component: String,
+    /// 所属的上游连接
+    pub broker_id: Option<String>,
+    /// panic信息
+    pub message: String,
+    pub datetime: DateTime<Utc>,
+}
+
+/// 崩溃统计报告
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CrashReport {
+    pub total: u64,
+    /// 组件 -> 崩溃次数
+    pub components: HashMap<String, u64>,
+    /// 上游连接 -> 崩溃次数
+    pub brokers: HashMap<String, u64>,
+    /// 最近的崩溃事件（从旧到新）
+    pub recent: Vec<CrashEvent>,
+}
+
+#[derive(Default)]
+struct CrashLog {
+    total: u64,
+    components: HashMap<String, u64>,
+    brokers: HashMap<String, u64>,
+    recent: VecDeque<CrashEvent>,
+}
+
+/// 进程内的崩溃计数
+#[derive(Default)]
+pub struct CrashMetrics {
+    log: Mutex<CrashLog>,
+}
+
+impl CrashMetrics {
+    /// 记录一次崩溃
+    pub fn record(&self, event: CrashEvent) {
+        error!(
+            "Recovered from panic in {}{}: {}",
+            event.component,
+            event.broker_id.as_deref().map(|id| format!(" (broker {})", id)).unwrap_or_default(),
+            event.message
+        );
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.total += 1;
+        *log.components.entry(event.component.clone()).or_default() += 1;
+        if let Some(broker_id) = &event.broker_id {
+            *log.brokers.entry(broker_id.clone()).or_default() += 1;
+        }
+        if log.recent.len() >= RECENT_CRASHES {
+            log.recent.pop_front();
+        }
+        log.recent.push_back(event);
+    }
+
+    /// 上游连接的崩溃次数
+    pub fn broker_crashes(&self, broker_id: &str) -> u64 {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.brokers.get(broker_id).copied().unwrap_or(0)
+    }
+
+    /// 当前统计
+    pub fn report(&self) -> CrashReport {
+        let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        CrashReport {
+            total: log.total,
+            components: log.components.clone(),
+            brokers: log.brokers.clone(),
+            recent: log.recent.iter().cloned().collect(),
+        }
+    }
+}
+
+/// 进程内共享的崩溃计数
+pub fn metrics() -> &'static CrashMetrics {
+    static METRICS: OnceLock<CrashMetrics> = OnceLock::new();
+    METRICS.get_or_init(CrashMetrics::default)
+}
+
+/// panic的信息（`panic!`的参数为字符串时）
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// 记录一次崩溃并返回崩溃事件
+pub fn record_crash(component: &str, broker_id: Option<&str>, message: String) -> CrashEvent {
+    let event = CrashEvent {
+        component: component.to_string(),
+        broker_id: broker_id.map(str::to_string),
+        message,
+        datetime: Utc::now(),
+    };
+    metrics().record(event.clone());
+    event
+}
+
+/// 执行f，panic时记录崩溃事件并返回该事件
+pub fn catch_panic<T>(
+    component: &str,
+    broker_id: Option<&str>,
+    f: impl FnOnce() -> T,
+) -> Result<T, CrashEvent> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|payload| record_crash(component, broker_id, panic_message(payload.as_ref())))
+}
+
+/// 单个上游连接的重启预算
+///
+/// 统计窗口内的重启次数达到`max_restarts`后不再重启；
+/// 每次重启前等待`restart_delay_ms`，窗口内每多一次重启等待时间加倍，不超过`max_restart_delay_ms`
+#[derive(Debug, Default)]
+pub struct RestartBudget {
+    restarts: VecDeque<Instant>,
+}
+
+impl RestartBudget {
+    /// 记录一次重启并返回重启前的等待时间，超出预算时返回None
+    pub fn next_delay(&mut self, config: &SupervisionConfig, now: Instant) -> Option<Duration> {
+        let window = Duration::from_secs(config.window_secs);
+        while self
+            .restarts
+            .front()
+            .is_some_and(|restart| now.duration_since(*restart) >= window)
+        {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= config.max_restarts {
+            return None;
+        }
+        let doublings = self.restarts.len().min(16) as u32;
+        let delay = config
+            .restart_delay_ms
+            .saturating_mul(1 << doublings)
+            .min(config.max_restart_delay_ms.max(config.restart_delay_ms));
+        self.restarts.push_back(now);
+        Some(Duration::from_millis(delay))
+    }
+
+    /// 统计窗口内的重启次数
+    pub fn restarts(&self) -> usize {
+        self.restarts.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(component: &str, broker_id: Option<&str>) -> CrashEvent {
+        CrashEvent {
+            component: component.to_string(),
+            broker_id: broker_id.map(str::to_string),
+            message: "boom".to_string(),
+            datetime: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_crash_metrics_count_by_component_and_broker() {
+        let metrics = CrashMetrics::default();
+        metrics.record(event("md_actor", Some("ctp1")));
+        metrics.record(event("spi", Some("ctp1")));
+        metrics.record(event("distributor", None));
+        assert_eq!(metrics.broker_crashes("ctp1"), 2);
+        assert_eq!(metrics.broker_crashes("ctp2"), 0);
+
+        let report = metrics.report();
+        assert_eq!(report.total, 3);
+        assert_eq!(report.components["md_actor"], 1);
+        assert_eq!(report.brokers.len(), 1);
+        assert_eq!(report.recent.last().unwrap().component, "distributor");
+
+        // 只保留最近的崩溃事件
+        for _ in 0..RECENT_CRASHES {
+            metrics.record(event("spi", None));
+        }
+        let report = metrics.report();
+        assert_eq!(report.total, 3 + RECENT_CRASHES as u64);
+        assert_eq!(report.recent.len(), RECENT_CRASHES);
+        assert!(report.recent.iter().all(|event| event.component == "spi"));
+    }
+
+    #[test]
+    fn test_catch_panic_records_message() {
+        assert_eq!(catch_panic("test", None, || 42).unwrap(), 42);
+
+        let event = catch_panic("test_component", Some("test_broker"), || -> i32 { panic!("bad tick {}", 7) })
+            .unwrap_err();
+        assert_eq!(event.component, "test_component");
+        assert_eq!(event.broker_id.as_deref(), Some("test_broker"));
+        assert_eq!(event.message, "bad tick 7");
+        assert!(metrics().broker_crashes("test_broker") >= 1);
+
+        assert_eq!(panic_message(&"static"), "static");
+        assert_eq!(panic_message(&42), "unknown panic");
+    }
+
+    #[test]
+    fn test_restart_budget_backs_off_within_window() {
+        let config = SupervisionConfig {
+            max_restarts: 3,
+            window_secs: 60,
+            restart_delay_ms: 100,
+            max_restart_delay_ms: 250,
+        };
+        let mut budget = RestartBudget::default();
+        let start = Instant::now();
+        assert_eq!(budget.next_delay(&config, start), Some(Duration::from_millis(100)));
+        assert_eq!(budget.next_delay(&config, start), Some(Duration::from_millis(200)));
+        assert_eq!(budget.next_delay(&config, start), Some(Duration::from_millis(250)));
+        assert_eq!(budget.next_delay(&config, start), None);
+        assert_eq!(budget.restarts(), 3);
+
+        // 窗口外的重启不再计入
+        let later = start + Duration::from_secs(60);
+        assert_eq!(budget.next_delay(&config, later), Some(Duration::from_millis(100)));
+        assert_eq!(budget.restarts(), 1);
+    }
+}
@@ -0,0 +1,360 @@
+//! 沪深北证券代码表与代码搜索
+//!
+//! QQ和新浪数据源只能按代码订阅，代码表供客户端按代码、名称或拼音首字母查找证券。
+//! 代码表由内置的常用证券、合约文件中的沪深北证券、`symbols.file`以及从`symbols.url`
+//! 下载的表依次合并，同一证券以后加载的为准。表格式为带表头的CSV：
+//! `code,exchange,name,abbr,kind`，例如`000001,SZSE,平安银行,PAYH,STOCK`。
+//!
+//! 代码统一为网关的合约代码（`SZSE.000001`），查询和交易所参数也接受
+//! `sz000001`、`000001.SZ`、`SZ`等写法。
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::actors::sina_http_poller::to_sina_code;
+use crate::config::SymbolsConfig;
+use crate::error::{GatewayError, GatewayResult};
+use crate::instruments::InstrumentRegistry;
+
+/// 内置的常用证券
+const BUNDLED_SYMBOLS: &str = include_str!("../data/symbols.csv");
+
+/// 单次搜索返回的最大结果数
+pub const MAX_SEARCH_RESULTS: usize = 200;
+
+/// 证券代码信息
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SymbolInfo {
+    /// 网关的合约代码（如`SZSE.000001`）
+    pub instrument_id: String,
+    /// 6位证券代码
+    pub code: String,
+    /// 交易所（SSE/SZSE/BSE）
+    pub exchange: String,
+    pub name: String,
+    /// 名称的拼音首字母
+    pub abbr: String,
+    /// 证券类型（STOCK/ETF/INDEX/BOND/...）
+    pub kind: String,
+    /// 新浪/QQ行情代码（如`sz000001`）
+    pub sina_code: String,
+}
+
+/// 代码表中的一行
+#[derive(Debug, Deserialize)]
+struct SymbolRecord {
+    code: String,
+    exchange: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    abbr: String,
+    #[serde(default)]
+    kind: String,
+}
+
+/// 交易所名称统一为网关的交易所代码（`SH`/`sh` -> `SSE`）
+pub fn normalize_exchange(exchange: &str) -> Option<&'static str> {
+    match exchange.trim().to_ascii_uppercase().as_str() {
+        "SSE" | "SH" | "SHSE" => Some("SSE"),
+        "SZSE" | "SZ" => Some("SZSE"),
+        "BSE" | "BJ" | "BJSE" => Some("BSE"),
+        _ => None,
+    }
+}
+
+fn is_code(code: &str) -> bool {
+    code.len() == 6 && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// 证券代码统一为(交易所, 6位代码)
+///
+/// 接受`SZSE.000001`、`sz000001`、`000001.SZ`以及不带交易所的`000001`（按代码段推断交易所）
+pub fn normalize_code(input: &str) -> Option<(&'static str, String)> {
+    let input = input.trim();
+    if let Some((left, right)) = input.split_once('.') {
+        if let (Some(exchange), true) = (normalize_exchange(left), is_code(right)) {
+            return Some((exchange, right.to_string()));
+        }
+        if let (true, Some(exchange)) = (is_code(left), normalize_exchange(right)) {
+            return Some((exchange, left.to_string()));
+        }
+        return None;
+    }
+    if input.len() == 8 && input.is_char_boundary(2) {
+        let (prefix, code) = input.split_at(2);
+        if let (Some(exchange), true) = (normalize_exchange(prefix), is_code(code)) {
+            return Some((exchange, code.to_string()));
+        }
+    }
+    if !is_code(input) {
+        return None;
+    }
+    let exchange = qamd_rs::exchange_of(input).and_then(|info| normalize_exchange(info.code))?;
+    Some((exchange, input.to_string()))
+}
+
+impl SymbolInfo {
+    fn new(exchange: &str, code: String, name: String, abbr: String, kind: String) -> Self {
+        let instrument_id = format!("{}.{}", exchange, code);
+        Self {
+            sina_code: to_sina_code(&instrument_id).unwrap_or_default(),
+            instrument_id,
+            code,
+            exchange: exchange.to_string(),
+            name,
+            abbr: abbr.to_uppercase(),
+            kind: if kind.is_empty() { "STOCK".to_string() } else { kind.to_uppercase() },
+        }
+    }
+
+    /// 与查询的匹配程度，越小越好，不匹配时返回None
+    fn rank(&self, query: &str, code: Option<&str>) -> Option<u8> {
+        if query.is_empty() || code.is_some_and(|code| code == self.instrument_id) {
+            return Some(0);
+        }
+        let upper = query.to_uppercase();
+        if self.code.starts_with(query) {
+            Some(1)
+        } else if self.abbr.starts_with(&upper) {
+            Some(2)
+        } else if self.name.starts_with(query) {
+            Some(3)
+        } else if self.name.contains(query) {
+            Some(4)
+        } else if self.abbr.contains(&upper) || self.name.to_uppercase().contains(&upper) {
+            Some(5)
+        } else {
+            None
+        }
+    }
+}
+
+/// 解析CSV代码表，跳过交易所或代码无效的行
+pub fn parse_symbols(data: &[u8]) -> GatewayResult<Vec<SymbolInfo>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(data);
+    let mut symbols = Vec::new();
+    let mut skipped = 0;
+    for record in reader.deserialize::<SymbolRecord>() {
+        let record = record.map_err(|e| GatewayError::ConfigError(format!("Invalid symbol table: {}", e)))?;
+        match normalize_exchange(&record.exchange) {
+            Some(exchange) if is_code(&record.code) => {
+                symbols.push(SymbolInfo::new(exchange, record.code, record.name, record.abbr, record.kind));
+            }
+            _ => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        warn!("Skipped {} symbols with an unknown exchange or invalid code", skipped);
+    }
+    Ok(symbols)
+}
+
+/// 证券代码表
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    // 合约代码 -> 证券信息
+    symbols: RwLock<HashMap<String, SymbolInfo>>,
+}
+
+impl SymbolTable {
+    /// 内置代码表
+    pub fn bundled() -> Self {
+        let table = Self::default();
+        match parse_symbols(BUNDLED_SYMBOLS.as_bytes()) {
+            Ok(symbols) => table.extend(symbols),
+            Err(e) => error!("Failed to parse the bundled symbol table: {}", e),
+        }
+        table
+    }
+
+    /// 按配置加载：内置代码表、合约文件中的证券和`symbols.file`
+    pub fn load(config: &SymbolsConfig, instruments: &InstrumentRegistry) -> GatewayResult<Self> {
+        let table = Self::bundled();
+        table.extend(instruments.iter().filter_map(|info| {
+            let exchange = normalize_exchange(&info.exchange_id)?;
+            is_code(&info.instrument_id).then(|| {
+                SymbolInfo::new(
+                    exchange,
+                    info.instrument_id.clone(),
+                    info.instrument_name.clone(),
+                    String::new(),
+                    info.product_class.clone(),
+                )
+            })
+        }));
+        if let Some(path) = &config.file {
+            let data = std::fs::read(path)
+                .map_err(|e| GatewayError::ConfigError(format!("Failed to read {}: {}", path, e)))?;
+            table.extend(parse_symbols(&data)?);
+        }
+        info!("Symbol table loaded with {} symbols", table.len());
+        Ok(table)
+    }
+
+    /// 添加证券（已存在的会被覆盖）
+    pub fn extend(&self, symbols: impl IntoIterator<Item = SymbolInfo>) {
+        let mut table = self.symbols.write().unwrap_or_else(|e| e.into_inner());
+        for symbol in symbols {
+            // 没有名称的证券（如合约文件中未填写名称）不覆盖已有的信息
+            if symbol.name.is_empty() && table.contains_key(&symbol.instrument_id) {
+                continue;
+            }
+            table.insert(symbol.instrument_id.clone(), symbol);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.symbols.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// 按代码、拼音首字母或名称搜索，精确的代码在前，其次是代码前缀、首字母前缀和名称
+    pub fn search(&self, query: &str, exchange: Option<&str>, limit: usize) -> Vec<SymbolInfo> {
+        let query = query.trim();
+        let code = normalize_code(query).map(|(exchange, code)| format!("{}.{}", exchange, code));
+        let table = self.symbols.read().unwrap_or_else(|e| e.into_inner());
+        let mut matches: Vec<(u8, &SymbolInfo)> = table
+            .values()
+            .filter(|symbol| exchange.is_none_or(|exchange| symbol.exchange == exchange))
+            .filter_map(|symbol| symbol.rank(query, code.as_deref()).map(|rank| (rank, symbol)))
+            .collect();
+        matches.sort_by(|(a_rank, a), (b_rank, b)| a_rank.cmp(b_rank).then_with(|| a.instrument_id.cmp(&b.instrument_id)));
+        matches
+            .into_iter()
+            .take(limit.min(MAX_SEARCH_RESULTS))
+            .map(|(_, symbol)| symbol.clone())
+            .collect()
+    }
+}
+
+/// 下载CSV代码表
+pub async fn download(url: &str, timeout: Duration) -> GatewayResult<Vec<SymbolInfo>> {
+    let client = awc::Client::builder().timeout(timeout).finish();
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| GatewayError::Other(format!("Failed to download symbols from {}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(GatewayError::Other(format!(
+            "Failed to download symbols from {}: HTTP {}",
+            url,
+            response.status()
+        )));
+    }
+    let body = response
+        .body()
+        .limit(64 * 1024 * 1024)
+        .await
+        .map_err(|e| GatewayError::Other(format!("Failed to download symbols from {}: {}", url, e)))?;
+    parse_symbols(&body)
+}
+
+/// 启动时下载`symbols.url`，之后每`refresh_secs`秒重新下载（0为只下载一次）
+pub fn spawn_refresh(table: std::sync::Arc<SymbolTable>, config: &SymbolsConfig) {
+    let Some(url) = config.url.clone() else {
+        return;
+    };
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let refresh = Duration::from_secs(config.refresh_secs);
+    actix_rt::spawn(async move {
+        loop {
+            match download(&url, timeout).await {
+                Ok(symbols) => {
+                    let count = symbols.len();
+                    table.extend(symbols);
+                    info!("Downloaded {} symbols from {}, {} in total", count, url, table.len());
+                }
+                Err(e) => warn!("{}", e),
+            }
+            if refresh.is_zero() {
+                break;
+            }
+            actix_rt::time::sleep(refresh).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "code,exchange,name,abbr,kind
+000001,SZSE,平安银行,payh,
+600000,SH,浦发银行,PFYH,stock
+510300,sse,沪深300ETF,HS300ETF,ETF
+000001,SSE,上证指数,SZZS,INDEX
+12345,SZ,无效代码,WXDM,STOCK
+000002,NYSE,未知交易所,WZJYS,STOCK
+";
+
+    fn table() -> SymbolTable {
+        let table = SymbolTable::default();
+        table.extend(parse_symbols(TABLE.as_bytes()).unwrap());
+        table
+    }
+
+    #[test]
+    fn test_normalize_code_forms() {
+        assert_eq!(normalize_exchange("sh"), Some("SSE"));
+        assert_eq!(normalize_exchange(" BJ "), Some("BSE"));
+        assert_eq!(normalize_exchange("HKEX"), None);
+
+        let expected = Some(("SZSE", "000001".to_string()));
+        assert_eq!(normalize_code("SZSE.000001"), expected);
+        assert_eq!(normalize_code("sz000001"), expected);
+        assert_eq!(normalize_code("000001.SZ"), expected);
+        assert_eq!(normalize_code("600000"), Some(("SSE", "600000".to_string())));
+        assert_eq!(normalize_code("SHFE.rb2410"), None);
+        assert_eq!(normalize_code("00001"), None);
+    }
+
+    #[test]
+    fn test_parse_skips_invalid_rows() {
+        let symbols = parse_symbols(TABLE.as_bytes()).unwrap();
+        assert_eq!(symbols.len(), 4);
+        assert_eq!(
+            symbols[0],
+            SymbolInfo {
+                instrument_id: "SZSE.000001".to_string(),
+                code: "000001".to_string(),
+                exchange: "SZSE".to_string(),
+                name: "平安银行".to_string(),
+                abbr: "PAYH".to_string(),
+                kind: "STOCK".to_string(),
+                sina_code: "sz000001".to_string(),
+            }
+        );
+        assert_eq!(symbols[1].instrument_id, "SSE.600000");
+        assert_eq!(symbols[1].kind, "STOCK");
+    }
+
+    #[test]
+    fn test_search_ranking_and_filters() {
+        let table = table();
+        let ids = |results: Vec<SymbolInfo>| results.into_iter().map(|s| s.instrument_id).collect::<Vec<_>>();
+
+        assert_eq!(ids(table.search("sz000001", None, 10)), ["SZSE.000001"]);
+        assert_eq!(ids(table.search("000001.SH", None, 10)), ["SSE.000001"]);
+        assert_eq!(ids(table.search("0000", None, 10)), ["SSE.000001", "SZSE.000001"]);
+        assert_eq!(ids(table.search("000001", Some("SSE"), 10)), ["SSE.000001"]);
+        assert_eq!(ids(table.search("pf", None, 10)), ["SSE.600000"]);
+        assert_eq!(ids(table.search("银行", None, 10)), ["SSE.600000", "SZSE.000001"]);
+        assert_eq!(ids(table.search("etf", None, 10)), ["SSE.510300"]);
+        assert!(table.search("不存在", None, 10).is_empty());
+        assert_eq!(table.search("", None, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_extend_keeps_named_symbols() {
+        let table = table();
+        table.extend([SymbolInfo::new("SZSE", "000001".to_string(), String::new(), String::new(), String::new())]);
+        assert_eq!(table.search("SZSE.000001", None, 1)[0].name, "平安银行");
+        table.extend([SymbolInfo::new("SZSE", "000001".to_string(), "平安".to_string(), String::new(), String::new())]);
+        assert_eq!(table.search("SZSE.000001", None, 1)[0].name, "平安");
+        assert_eq!(table.len(), 4);
+    }
+}
@@ -0,0 +1,364 @@
+//! 合成合约（价差、加权组合）
+//!
+//! 客户端直接订阅合成合约公式，合约代码即公式本身：
+//! - `SPREAD:rb2410-rb2501`：跨期价差
+//! - `SPREAD:SHFE.rb2410-SHFE.hc2410`：跨品种价差
+//! - `BASKET:0.5*rb2410+0.3*hc2410+0.2*i2409`：加权组合
+//! - `INDEX:2900000000*SSE.600000+2100000000*SSE.600036`：市值加权指数（权重为股本）
+//! - `EQINDEX:SSE.600000+SSE.600036+SZSE.000001`：等权指数
+//!
+//! 公式是各腿的线性组合，每条腿可带权重（默认为1）。
+//! 期权合约代码中的连字符（如`m2409-C-3000`）和科学计数法权重（如`1e-3`）中的符号不分隔各腿。
+//! 腿不含交易所前缀时匹配任意交易所的同名合约。
+//! 指数由`qamd_rs::index`计算，首次计算时的点位为1000。
+
+use chrono::{DateTime, Utc};
+use hashbrown::HashMap;
+use qamd_rs::index::DEFAULT_BASE_VALUE;
+use qamd_rs::{Constituent, IndexCalculator, IndexWeighting, MDSnapshot, OptionalF64};
+
+use crate::error::{GatewayError, GatewayResult};
+
+/// 合成合约类型（公式前缀）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntheticKind {
+    /// 价差
+    Spread,
+    /// 加权组合
+    Basket,
+    /// 市值加权指数
+    Index,
+    /// 等权指数
+    EqualIndex,
+}
+
+impl SyntheticKind {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix.to_ascii_uppercase().as_str() {
+            "SPREAD" => Some(SyntheticKind::Spread),
+            "BASKET" => Some(SyntheticKind::Basket),
+            "INDEX" => Some(SyntheticKind::Index),
+            "EQINDEX" => Some(SyntheticKind::EqualIndex),
+            _ => None,
+        }
+    }
+
+    /// 指数的加权方式，价差和组合返回None
+    fn weighting(self) -> Option<IndexWeighting> {
+        match self {
+            SyntheticKind::Index => Some(IndexWeighting::Capitalization),
+            SyntheticKind::EqualIndex => Some(IndexWeighting::Equal),
+            SyntheticKind::Spread | SyntheticKind::Basket => None,
+        }
+    }
+}
+
+/// 合成合约的一条腿
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leg {
+    /// 合约代码
+    pub instrument: String,
+    /// 权重（负数表示卖出）
+    pub weight: f64,
+}
+
+impl Leg {
+    /// 行情中的合约代码是否属于该腿（腿不含交易所前缀时忽略行情的交易所前缀）
+    pub fn matches(&self, instrument_id: &str) -> bool {
+        if instrument_id == self.instrument {
+            return true;
+        }
+        !self.instrument.contains('.')
+            && instrument_id
+                .split_once('.')
+                .is_some_and(|(_, code)| code == self.instrument)
+    }
+}
+
+/// 是否为合成合约公式
+pub fn is_synthetic(instrument: &str) -> bool {
+    instrument
+        .split_once(':')
+        .is_some_and(|(prefix, _)| SyntheticKind::from_prefix(prefix).is_some())
+}
+
+/// 合成合约定义
+#[derive(Debug, Clone)]
+pub struct SyntheticInstrument {
+    /// 合约代码（即公式）
+    pub instrument_id: String,
+    /// 类型
+    pub kind: SyntheticKind,
+    /// 各腿
+    pub legs: Vec<Leg>,
+    // 指数计算器（仅指数）
+    index: Option<IndexCalculator>,
+    // 合成价格的当日开盘、最高、最低价（从首次计算开始）
+    open: Option<f64>,
+    highest: f64,
+    lowest: f64,
+}
+
+impl SyntheticInstrument {
+    /// 解析合成合约公式
+    pub fn parse(formula: &str) -> GatewayResult<Self> {
+        let invalid = |reason: &str| {
+            GatewayError::ConfigError(format!("Invalid synthetic instrument {}: {}", formula, reason))
+        };
+
+        let (prefix, expression) = formula.split_once(':').ok_or_else(|| invalid("missing prefix"))?;
+        let kind = SyntheticKind::from_prefix(prefix).ok_or_else(|| invalid("unknown prefix"))?;
+
+        let mut legs: Vec<Leg> = Vec::new();
+        for (sign, term) in split_terms(expression) {
+            let term = term.trim();
+            let (weight, instrument) = match term.split_once('*') {
+                Some((weight, instrument)) => (
+                    weight.trim().parse::<f64>().map_err(|_| invalid("bad weight"))?,
+                    instrument.trim(),
+                ),
+                None => (1.0, term),
+            };
+            if instrument.is_empty() || instrument.contains([':', '*', ' ']) {
+                return Err(invalid("bad instrument"));
+            }
+
+            // 同一合约出现多次时合并权重
+            let weight = sign * weight;
+            match legs.iter_mut().find(|leg| leg.instrument == instrument) {
+                Some(leg) => leg.weight += weight,
+                None => legs.push(Leg {
+                    instrument: instrument.to_string(),
+                    weight,
+                }),
+            }
+        }
+        legs.retain(|leg| leg.weight != 0.0);
+
+        if legs.is_empty() {
+            return Err(invalid("no legs"));
+        }
+        if kind == SyntheticKind::Spread && legs.len() < 2 {
+            return Err(invalid("a spread needs at least two legs"));
+        }
+        let index = match kind.weighting() {
+            Some(_) if legs.iter().any(|leg| leg.weight < 0.0) => {
+                return Err(invalid("index weights must be positive"));
+            }
+            Some(weighting) => {
+                let constituents = legs
+                    .iter()
+                    .map(|leg| Constituent::new(leg.instrument.clone(), leg.weight))
+                    .collect();
+                Some(IndexCalculator::new(weighting, constituents, DEFAULT_BASE_VALUE))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            instrument_id: formula.to_string(),
+            kind,
+            legs,
+            index,
+            open: None,
+            highest: f64::MIN,
+            lowest: f64::MAX,
+        })
+    }
+
+    /// 根据各腿最新行情计算合成行情，任一腿没有行情时返回None
+    ///
+    /// 合成买价按买入腿的买价、卖出腿的卖价计算（即立即卖出组合能成交的价格），
+    /// 合成卖价反之；挂单量取各腿可成交量的最小值。
+    pub fn compute(&mut self, quotes: &HashMap<String, MDSnapshot>) -> Option<MDSnapshot> {
+        if self.index.is_some() {
+            return self.compute_index(quotes);
+        }
+        let mut last_price = 0.0;
+        let mut pre_close = 0.0;
+        let mut bid_price = 0.0;
+        let mut ask_price = 0.0;
+        let mut bid_volume = i64::MAX;
+        let mut ask_volume = i64::MAX;
+        let mut datetime: Option<DateTime<Utc>> = None;
+
+        for leg in &self.legs {
+            let quote = quotes.get(&leg.instrument)?;
+            let weight = leg.weight;
+            last_price += weight * quote.last_price;
+            pre_close += weight * quote.pre_close;
+
+            let (bid, bid_qty, ask, ask_qty) = if weight > 0.0 {
+                (quote.bid_price1, quote.bid_volume1, quote.ask_price1, quote.ask_volume1)
+            } else {
+                (quote.ask_price1, quote.ask_volume1, quote.bid_price1, quote.bid_volume1)
+            };
+            bid_price += weight * bid;
+            ask_price += weight * ask;
+            // 按权重折算为组合数量
+            let lots = |qty: i64| (qty as f64 / weight.abs()).floor() as i64;
+            bid_volume = bid_volume.min(lots(bid_qty));
+            ask_volume = ask_volume.min(lots(ask_qty));
+
+            datetime = Some(datetime.map_or(quote.datetime, |dt| dt.max(quote.datetime)));
+        }
+
+        let open = *self.open.get_or_insert(last_price);
+        self.highest = self.highest.max(last_price);
+        self.lowest = self.lowest.min(last_price);
+
+        Some(
+            MDSnapshot::builder(self.instrument_id.clone(), datetime?)
+                .last_price(last_price)
+                .pre_close(pre_close)
+                .open(open)
+                .highest(self.highest)
+                .lowest(self.lowest)
+                .bid(1, bid_price, bid_volume)
+                .ask(1, ask_price, ask_volume)
+                .open_interest(OptionalF64::missing())
+                .build(),
+        )
+    }
+
+    /// 计算指数行情，成交量和成交额为各成分合约之和
+    fn compute_index(&mut self, quotes: &HashMap<String, MDSnapshot>) -> Option<MDSnapshot> {
+        let level = self.index.as_mut()?.compute(|instrument| quotes.get(instrument))?;
+        let open = *self.open.get_or_insert(level.value);
+        self.highest = self.highest.max(level.value);
+        self.lowest = self.lowest.min(level.value);
+
+        Some(
+            MDSnapshot::builder(self.instrument_id.clone(), level.datetime)
+                .last_price(level.value)
+                .pre_close(level.pre_close)
+                .open(open)
+                .highest(self.highest)
+                .lowest(self.lowest)
+                .volume(level.volume)
+                .amount(level.amount)
+                .open_interest(OptionalF64::missing())
+                .build(),
+        )
+    }
+}
+
+/// 将表达式拆分为带符号的项（第一项前的符号可省略）
+///
+/// 只在项之间拆分：科学计数法的权重（如`1e-3`）和期权合约代码（如`m2409-C-3000`）中的符号属于项本身
+fn split_terms(expression: &str) -> Vec<(f64, &str)> {
+    let mut terms = Vec::new();
+    let mut sign = 1.0;
+    let mut start = 0;
+    for (i, c) in expression.char_indices() {
+        if c != '+' && c != '-' {
+            continue;
+        }
+        if is_exponent_sign(&expression[start..i]) || is_option_dash(expression.as_bytes(), i) {
+            continue;
+        }
+        if i > start || !terms.is_empty() {
+            terms.push((sign, &expression[start..i]));
+        }
+        sign = if c == '-' { -1.0 } else { 1.0 };
+        start = i + 1;
+    }
+    terms.push((sign, &expression[start..]));
+    terms
+}
+
+/// 项中已读的部分是否为科学计数法的尾数（如`1e`、`2.5E`），其后的符号属于指数
+fn is_exponent_sign(term: &str) -> bool {
+    term.trim_start()
+        .strip_suffix(['e', 'E'])
+        .is_some_and(|mantissa| !mantissa.is_empty() && mantissa.bytes().all(|b| b.is_ascii_digit() || b == b'.'))
+}
+
+/// 位置`i`的`-`是否为期权合约代码中的连字符（`m2409-C-3000`的两个`-`）
+fn is_option_dash(expression: &[u8], i: usize) -> bool {
+    let at = |j: Option<usize>| j.and_then(|j| expression.get(j)).copied();
+    let is_digit = |b: Option<u8>| b.is_some_and(|b| b.is_ascii_digit());
+    let is_option_type = |b: Option<u8>| matches!(b, Some(b'C' | b'P'));
+    // 合约月份与期权类型之间
+    let before_type = is_digit(at(i.checked_sub(1))) && is_option_type(at(Some(i + 1))) && at(Some(i + 2)) == Some(b'-');
+    // 期权类型与行权价之间
+    let before_strike = is_option_type(at(i.checked_sub(1)))
+        && at(i.checked_sub(2)) == Some(b'-')
+        && is_digit(at(i.checked_sub(3)))
+        && is_digit(at(Some(i + 1)));
+    before_type || before_strike
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legs(formula: &str) -> Vec<(String, f64)> {
+        SyntheticInstrument::parse(formula)
+            .unwrap()
+            .legs
+            .into_iter()
+            .map(|leg| (leg.instrument, leg.weight))
+            .collect()
+    }
+
+    fn quote(instrument: &str, last: f64, bid: (f64, i64), ask: (f64, i64)) -> (String, MDSnapshot) {
+        let snapshot = MDSnapshot::builder(instrument.to_string(), Utc::now())
+            .last_price(last)
+            .pre_close(last)
+            .bid(1, bid.0, bid.1)
+            .ask(1, ask.0, ask.1)
+            .build();
+        (instrument.to_string(), snapshot)
+    }
+
+    #[test]
+    fn parse_splits_signed_weighted_legs() {
+        assert_eq!(
+            legs("BASKET:0.5*rb2410+0.3*hc2410-0.2*i2409"),
+            vec![("rb2410".to_string(), 0.5), ("hc2410".to_string(), 0.3), ("i2409".to_string(), -0.2)]
+        );
+        assert_eq!(legs("SPREAD:-rb2410+rb2501"), vec![("rb2410".to_string(), -1.0), ("rb2501".to_string(), 1.0)]);
+        // 同一合约合并权重，权重为0的腿去掉
+        assert_eq!(legs("BASKET:rb2410+2*hc2410-rb2410"), vec![("hc2410".to_string(), 2.0)]);
+    }
+
+    #[test]
+    fn parse_keeps_option_codes_and_exponents_intact() {
+        assert_eq!(
+            legs("SPREAD:DCE.m2409-C-3000-DCE.m2409-P-3000"),
+            vec![("DCE.m2409-C-3000".to_string(), 1.0), ("DCE.m2409-P-3000".to_string(), -1.0)]
+        );
+        assert_eq!(
+            legs("BASKET:1e-3*rb2410+2.5E+2*hc2410"),
+            vec![("rb2410".to_string(), 0.001), ("hc2410".to_string(), 250.0)]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_formulas() {
+        assert!(SyntheticInstrument::parse("rb2410-rb2501").is_err());
+        assert!(SyntheticInstrument::parse("FOO:rb2410-rb2501").is_err());
+        assert!(SyntheticInstrument::parse("SPREAD:rb2410").is_err());
+        assert!(SyntheticInstrument::parse("BASKET:x*rb2410").is_err());
+        assert!(SyntheticInstrument::parse("INDEX:SSE.600000-SSE.600036").is_err());
+    }
+
+    #[test]
+    fn compute_takes_bid_and_ask_by_leg_sign() {
+        let mut spread = SyntheticInstrument::parse("SPREAD:rb2410-2*hc2410").unwrap();
+        let mut quotes: HashMap<String, MDSnapshot> = HashMap::new();
+        quotes.extend([quote("rb2410", 3500.0, (3499.0, 10), (3501.0, 7))]);
+        assert!(spread.compute(&quotes).is_none());
+        quotes.extend([quote("hc2410", 1600.0, (1599.0, 8), (1602.0, 30))]);
+
+        let synthetic = spread.compute(&quotes).unwrap();
+        assert_eq!(synthetic.last_price, 3500.0 - 2.0 * 1600.0);
+        // 卖出组合：卖rb2410的买价，买hc2410的卖价
+        assert_eq!(synthetic.bid_price1, 3499.0 - 2.0 * 1602.0);
+        assert_eq!(synthetic.ask_price1, 3501.0 - 2.0 * 1599.0);
+        // hc2410按两手折算为一组
+        assert_eq!(synthetic.bid_volume1, 10);
+        assert_eq!(synthetic.ask_volume1, 4);
+    }
+}
@@ -0,0 +1,507 @@
+//! Rotating tick files of the recorder
+//!
+//! With `recorder.dir` set, every recorded snapshot is also appended to a JSON Lines
+//! file in the format the replay actor reads. Files are named
+//! `ticks-<trading day>-<sequence>.jsonl`; a new file is started when the trading day
+//! changes, when the current file exceeds `max_file_mb` or after `rotate_minutes`.
+//! Finished files are compressed with zstd (`.jsonl.zst`) and files of trading days
+//! older than `retention_days` are deleted.
+//!
+//! `index.json` lists every finished file with the time range of each instrument in
+//! it, so a replay of a few instruments or days only opens the files it needs. Files
+//! left behind by a crash are scanned and added to the index on the next start.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use qamd_rs::MDSnapshot;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::calendar::{china_offset, TradingCalendar};
+use crate::config::RecorderConfig;
+use crate::error::{GatewayError, GatewayResult};
+
+/// Name of the index file in the archive directory
+pub const INDEX_FILE: &str = "index.json";
+/// Prefix of tick file names
+const PREFIX: &str = "ticks-";
+/// Extension of uncompressed tick files
+const EXTENSION: &str = "jsonl";
+/// Extension appended to compressed tick files
+const COMPRESSED_EXTENSION: &str = "zst";
+
+/// Time range of snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl TimeRange {
+    fn at(time: DateTime<Utc>) -> Self {
+        Self { start: time, end: time }
+    }
+
+    fn extend(&mut self, time: DateTime<Utc>) {
+        self.start = self.start.min(time);
+        self.end = self.end.max(time);
+    }
+}
+
+/// A finished tick file listed in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    /// File name within the archive directory
+    pub file: String,
+    /// Trading day of the snapshots
+    pub trading_day: NaiveDate,
+    /// Number of snapshots
+    pub records: u64,
+    /// Time range of all snapshots
+    pub range: TimeRange,
+    /// Time range of the snapshots of each instrument
+    pub instruments: BTreeMap<String, TimeRange>,
+}
+
+impl ArchiveFile {
+    fn new(file: String, trading_day: NaiveDate, snapshot: &MDSnapshot) -> Self {
+        Self {
+            file,
+            trading_day,
+            records: 0,
+            range: TimeRange::at(snapshot.datetime),
+            instruments: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, snapshot: &MDSnapshot) {
+        self.records += 1;
+        self.range.extend(snapshot.datetime);
+        match self.instruments.get_mut(snapshot.instrument_id.as_str()) {
+            Some(range) => range.extend(snapshot.datetime),
+            None => {
+                self.instruments
+                    .insert(snapshot.instrument_id.clone(), TimeRange::at(snapshot.datetime));
+            }
+        }
+    }
+
+    fn is_compressed(&self) -> bool {
+        self.file.ends_with(COMPRESSED_EXTENSION)
+    }
+}
+
+/// Index of the tick files in an archive directory, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    pub files: Vec<ArchiveFile>,
+}
+
+impl ArchiveIndex {
+    /// Read the index of an archive directory, empty if there is none yet
+    pub fn load(dir: &Path) -> GatewayResult<Self> {
+        let path = dir.join(INDEX_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| GatewayError::Other(format!("Invalid tick index {}: {}", path.display(), e)))
+    }
+
+    /// Write the index (to a temporary file first, so readers never see half of it)
+    fn save(&self, dir: &Path) -> GatewayResult<()> {
+        let path = dir.join(INDEX_FILE);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Files of trading days within `start..=end` holding at least one accepted instrument,
+    /// in time order
+    pub fn select(
+        &self,
+        start: Option<NaiveDate>,
+        end: Option<NaiveDate>,
+        accepts: impl Fn(&str) -> bool,
+    ) -> Vec<&ArchiveFile> {
+        let mut files: Vec<&ArchiveFile> = self
+            .files
+            .iter()
+            .filter(|f| start.is_none_or(|s| f.trading_day >= s) && end.is_none_or(|e| f.trading_day <= e))
+            .filter(|f| f.instruments.keys().any(|instrument| accepts(instrument)))
+            .collect();
+        files.sort_by_key(|f| f.range.start);
+        files
+    }
+
+    fn sort(&mut self) {
+        self.files.sort_by(|a, b| a.trading_day.cmp(&b.trading_day).then_with(|| a.file.cmp(&b.file)));
+    }
+}
+
+/// The file currently written to
+struct CurrentFile {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    entry: ArchiveFile,
+    opened: Instant,
+    size: u64,
+}
+
+/// Writes recorded snapshots to rotating tick files and keeps the index up to date
+pub struct TickArchive {
+    dir: PathBuf,
+    config: RecorderConfig,
+    calendar: Arc<TradingCalendar>,
+    index: ArchiveIndex,
+    current: Option<CurrentFile>,
+}
+
+impl TickArchive {
+    /// Create the archive directory, load its index and add files left behind by a crash
+    pub fn open(config: RecorderConfig, calendar: Arc<TradingCalendar>) -> GatewayResult<Self> {
+        let dir = PathBuf::from(config.dir.as_deref().unwrap_or_default());
+        fs::create_dir_all(&dir).map_err(|e| {
+            GatewayError::ConfigError(format!("Cannot create tick directory {}: {}", dir.display(), e))
+        })?;
+        let mut archive = Self {
+            index: ArchiveIndex::load(&dir)?,
+            dir,
+            config,
+            calendar,
+            current: None,
+        };
+        archive.recover()?;
+        info!(
+            "Writing recorded ticks to {} ({} files indexed)",
+            archive.dir.display(),
+            archive.index.files.len()
+        );
+        Ok(archive)
+    }
+
+    /// Append a snapshot, returns the file finished by a rotation
+    pub fn append(&mut self, snapshot: &MDSnapshot) -> GatewayResult<Option<PathBuf>> {
+        let local = snapshot.datetime.with_timezone(&china_offset()).naive_local();
+        let trading_day = self.calendar.trading_day(local);
+
+        let mut line = serde_json::to_vec(snapshot)?;
+        line.push(b'\n');
+
+        let max_bytes = self.config.max_file_mb * 1024 * 1024;
+        // Late snapshots of the previous trading day stay in the current file
+        let full = self.current.as_ref().is_some_and(|current| {
+            trading_day > current.entry.trading_day
+                || (max_bytes > 0 && current.size + line.len() as u64 > max_bytes)
+        });
+        let finished = if full { self.finish()? } else { None };
+
+        if self.current.is_none() {
+            self.current = Some(self.create_file(trading_day, snapshot)?);
+        }
+        let current = self.current.as_mut().expect("tick file is open");
+        current.writer.write_all(&line)?;
+        current.size += line.len() as u64;
+        current.entry.add(snapshot);
+        Ok(finished)
+    }
+
+    /// Flush the current file and rotate it when it has been open for `rotate_minutes`
+    /// or belongs to a past trading day, returns the finished file
+    pub fn check(&mut self) -> GatewayResult<Option<PathBuf>> {
+        let Some(current) = &mut self.current else {
+            return Ok(None);
+        };
+        current.writer.flush()?;
+        let expired = self.config.rotate_minutes > 0
+            && current.opened.elapsed().as_secs() >= self.config.rotate_minutes * 60;
+        let today = self.calendar.trading_day(TradingCalendar::now_local());
+        if expired || current.entry.trading_day < today {
+            return self.finish();
+        }
+        Ok(None)
+    }
+
+    /// Close the current file and add it to the index, returns its path
+    pub fn finish(&mut self) -> GatewayResult<Option<PathBuf>> {
+        let Some(mut current) = self.current.take() else {
+            return Ok(None);
+        };
+        current.writer.flush()?;
+        self.index.files.push(current.entry);
+        self.index.sort();
+        self.remove_expired();
+        self.index.save(&self.dir)?;
+        Ok(Some(current.path))
+    }
+
+    /// Indexed files that are not compressed yet
+    pub fn uncompressed_files(&self) -> Vec<PathBuf> {
+        self.index
+            .files
+            .iter()
+            .filter(|f| !f.is_compressed())
+            .map(|f| self.dir.join(&f.file))
+            .collect()
+    }
+
+    /// Point the index at the compressed copy of a file
+    pub fn compressed(&mut self, path: &Path, compressed: &Path) -> GatewayResult<()> {
+        let (Some(name), Some(compressed_name)) = (file_name(path), file_name(compressed)) else {
+            return Ok(());
+        };
+        match self.index.files.iter_mut().find(|f| f.file == name) {
+            Some(entry) => {
+                entry.file = compressed_name;
+                self.index.save(&self.dir)
+            }
+            // Removed by the retention policy in the meantime
+            None => Ok(fs::remove_file(compressed)?),
+        }
+    }
+
+    /// Delete the files of trading days older than `retention_days`
+    pub fn remove_expired(&mut self) {
+        if self.config.retention_days == 0 {
+            return;
+        }
+        let today = self.calendar.trading_day(TradingCalendar::now_local());
+        let oldest = today - Duration::days(self.config.retention_days as i64 - 1);
+        let dir = &self.dir;
+        self.index.files.retain(|f| {
+            if f.trading_day >= oldest {
+                return true;
+            }
+            let path = dir.join(&f.file);
+            match fs::remove_file(&path) {
+                Ok(()) => info!("Removed expired tick file {}", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("Failed to remove tick file {}: {}", path.display(), e),
+            }
+            false
+        });
+    }
+
+    /// Start a new file for a trading day with the next free sequence number
+    fn create_file(&mut self, trading_day: NaiveDate, snapshot: &MDSnapshot) -> GatewayResult<CurrentFile> {
+        let mut seq = self.index.files.iter().filter(|f| f.trading_day == trading_day).count() + 1;
+        loop {
+            let name = format!("{}{}-{:04}.{}", PREFIX, trading_day, seq, EXTENSION);
+            let path = self.dir.join(&name);
+            let compressed = self.dir.join(format!("{}.{}", name, COMPRESSED_EXTENSION));
+            if path.exists() || compressed.exists() {
+                seq += 1;
+                continue;
+            }
+            let file = OpenOptions::new().create_new(true).append(true).open(&path)?;
+            return Ok(CurrentFile {
+                writer: BufWriter::new(file),
+                path,
+                entry: ArchiveFile::new(name, trading_day, snapshot),
+                opened: Instant::now(),
+                size: 0,
+            });
+        }
+    }
+
+    /// Index the tick files that are missing from the index, e.g. after a crash
+    fn recover(&mut self) -> GatewayResult<()> {
+        let mut added = false;
+        for path in tick_files(&self.dir)? {
+            let Some(name) = file_name(&path) else {
+                continue;
+            };
+            if self.index.files.iter().any(|f| f.file == name) {
+                continue;
+            }
+            // Interrupted after the compressed copy was complete
+            if self.dir.join(format!("{}.{}", name, COMPRESSED_EXTENSION)).exists() {
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            match scan_file(&path, name, &self.calendar) {
+                Ok(Some(entry)) => {
+                    info!("Indexed {} recorded ticks from {}", entry.records, path.display());
+                    self.index.files.push(entry);
+                    added = true;
+                }
+                Ok(None) => {
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) => warn!("Failed to index tick file {}: {}", path.display(), e),
+            }
+        }
+        if added {
+            self.index.sort();
+            self.index.save(&self.dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compress a tick file with zstd and delete the original, returns the compressed path
+pub fn compress_file(path: &Path, level: i32) -> GatewayResult<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", COMPRESSED_EXTENSION));
+    let compressed = PathBuf::from(name);
+    let tmp_path = compressed.with_extension("tmp");
+
+    let mut reader = File::open(path)?;
+    let mut encoder = zstd::Encoder::new(File::create(&tmp_path)?, level)?;
+    std::io::copy(&mut reader, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::rename(&tmp_path, &compressed)?;
+    fs::remove_file(path)?;
+    Ok(compressed)
+}
+
+/// Open a file for reading, decompressing it on the fly when it ends in `.zst`
+pub fn open_tick_file(path: &Path) -> GatewayResult<Box<dyn Read>> {
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|ext| ext == COMPRESSED_EXTENSION) {
+        Ok(Box::new(zstd::Decoder::new(file)?))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Tick files in the archive directory, compressed or not
+fn tick_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let suffixes = [format!(".{}", EXTENSION), format!(".{}.{}", EXTENSION, COMPRESSED_EXTENSION)];
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            file_name(path).is_some_and(|name| {
+                name.starts_with(PREFIX) && suffixes.iter().any(|suffix| name.ends_with(suffix.as_str()))
+            })
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Build the index entry of a file by reading it, `None` if it holds no snapshots
+///
+/// A line cut off by a crash ends the scan.
+fn scan_file(path: &Path, name: String, calendar: &TradingCalendar) -> GatewayResult<Option<ArchiveFile>> {
+    let mut entry: Option<ArchiveFile> = None;
+    for line in BufReader::new(open_tick_file(path)?).lines() {
+        let Ok(snapshot) = serde_json::from_str::<MDSnapshot>(&line?) else {
+            break;
+        };
+        let entry = entry.get_or_insert_with(|| {
+            let local = snapshot.datetime.with_timezone(&china_offset()).naive_local();
+            ArchiveFile::new(name.clone(), calendar.trading_day(local), &snapshot)
+        });
+        entry.add(&snapshot);
+    }
+    Ok(entry)
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name().and_then(|name| name.to_str()).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Temporary archive directory of a test
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("qamd-ticks-{}-{}", name, uuid::Uuid::new_v4()))
+    }
+
+    fn open(dir: &Path) -> TickArchive {
+        let config = RecorderConfig {
+            dir: Some(dir.display().to_string()),
+            ..RecorderConfig::default()
+        };
+        TickArchive::open(config, Arc::new(TradingCalendar::default())).unwrap()
+    }
+
+    /// Snapshot at a Beijing time on 2024-07-01 (a Monday)
+    fn snapshot(instrument_id: &str, hour: u32, minute: u32) -> MDSnapshot {
+        let local = china_offset().with_ymd_and_hms(2024, 7, 1, hour, minute, 0).unwrap();
+        MDSnapshot::builder(instrument_id, local.with_timezone(&Utc))
+            .last_price(3500.0)
+            .build()
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 7, d).unwrap()
+    }
+
+    #[test]
+    fn test_rotates_on_trading_day_and_indexes_ranges() {
+        let dir = temp_dir("rotate");
+        let mut archive = open(&dir);
+        assert!(archive.append(&snapshot("SHFE.rb2410", 10, 0)).unwrap().is_none());
+        assert!(archive.append(&snapshot("DCE.m2409", 10, 5)).unwrap().is_none());
+        assert!(archive.append(&snapshot("SHFE.rb2410", 14, 0)).unwrap().is_none());
+        // The night session belongs to the next trading day
+        let finished = archive.append(&snapshot("SHFE.rb2410", 21, 0)).unwrap().unwrap();
+        assert_eq!(file_name(&finished).unwrap(), "ticks-2024-07-01-0001.jsonl");
+        archive.finish().unwrap();
+
+        let index = ArchiveIndex::load(&dir).unwrap();
+        assert_eq!(index.files.len(), 2);
+        let first = &index.files[0];
+        assert_eq!(first.trading_day, day(1));
+        assert_eq!(first.records, 3);
+        assert_eq!(first.range.start, snapshot("", 10, 0).datetime);
+        assert_eq!(first.range.end, snapshot("", 14, 0).datetime);
+        assert_eq!(first.instruments["DCE.m2409"], TimeRange::at(snapshot("", 10, 5).datetime));
+        assert_eq!(index.files[1].file, "ticks-2024-07-02-0001.jsonl");
+
+        let files = |start, end, instrument: &str| -> Vec<String> {
+            index.select(start, end, |i| i == instrument).into_iter().map(|f| f.file.clone()).collect()
+        };
+        assert_eq!(files(None, None, "SHFE.rb2410").len(), 2);
+        assert_eq!(files(None, None, "DCE.m2409"), ["ticks-2024-07-01-0001.jsonl"]);
+        assert_eq!(files(Some(day(2)), None, "SHFE.rb2410"), ["ticks-2024-07-02-0001.jsonl"]);
+        assert!(files(None, Some(day(1)), "SHFE.ag2412").is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recovers_unindexed_files_and_compresses() {
+        let dir = temp_dir("recover");
+        {
+            // Dropped without finishing, as after a crash
+            let mut archive = open(&dir);
+            archive.append(&snapshot("SHFE.rb2410", 10, 0)).unwrap();
+            archive.append(&snapshot("SHFE.rb2410", 10, 1)).unwrap();
+        }
+        let path = dir.join("ticks-2024-07-01-0001.jsonl");
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"instrument_id\":\"SHFE.rb").unwrap();
+        drop(file);
+
+        let mut archive = open(&dir);
+        assert_eq!(archive.index.files.len(), 1);
+        assert_eq!(archive.index.files[0].records, 2);
+        assert_eq!(archive.uncompressed_files(), std::slice::from_ref(&path));
+        // A new file of the same day takes the next sequence number
+        archive.append(&snapshot("SHFE.rb2410", 11, 0)).unwrap();
+        assert_eq!(
+            file_name(&archive.finish().unwrap().unwrap()).unwrap(),
+            "ticks-2024-07-01-0002.jsonl"
+        );
+
+        let compressed = compress_file(&path, 3).unwrap();
+        assert!(!path.exists());
+        archive.compressed(&path, &compressed).unwrap();
+        assert_eq!(ArchiveIndex::load(&dir).unwrap().files[0].file, "ticks-2024-07-01-0001.jsonl.zst");
+        let lines = BufReader::new(open_tick_file(&compressed).unwrap()).lines().count();
+        assert_eq!(lines, 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! TLS termination with certificate hot-reload
+//!
+//! Handshakes take their certificate from [`ReloadingCert`], which re-reads the
+//! PEM files once their modification time changes. Established connections keep
+//! the certificate they were negotiated with, so a renewal (e.g. by certbot)
+//! does not drop any session.
+
+use tracing::{info, warn};
+use rustls::crypto::CryptoProvider;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::TlsConfig;
+use crate::error::{GatewayError, GatewayResult};
+
+/// Build the rustls server configuration of a TLS listener
+pub fn server_config(tls: &TlsConfig) -> GatewayResult<ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let cert = ReloadingCert::load(tls, provider.clone())?;
+    Ok(ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error("protocol versions", &e))?
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::new(cert)))
+}
+
+/// Certificate that is reloaded when its files change on disk
+#[derive(Debug)]
+pub struct ReloadingCert {
+    config: TlsConfig,
+    provider: Arc<CryptoProvider>,
+    state: RwLock<CertState>,
+}
+
+#[derive(Debug)]
+struct CertState {
+    key: Arc<CertifiedKey>,
+    /// Modification times of the certificate and key files when loaded
+    modified: (Option<SystemTime>, Option<SystemTime>),
+    checked_at: Instant,
+}
+
+impl ReloadingCert {
+    /// Load the certificate, failing if the files are missing or invalid
+    pub fn load(config: &TlsConfig, provider: Arc<CryptoProvider>) -> GatewayResult<Self> {
+        let modified = modified_times(config);
+        let key = load_certified_key(config, &provider)?;
+        Ok(Self {
+            config: config.clone(),
+            provider,
+            state: RwLock::new(CertState {
+                key: Arc::new(key),
+                modified,
+                checked_at: Instant::now(),
+            }),
+        })
+    }
+
+    /// Re-read the files if they changed since the last load. A failed load
+    /// (e.g. the key is not yet written) keeps the current certificate and is
+    /// retried at the next check.
+    fn reload_if_changed(&self) {
+        let interval = Duration::from_secs(self.config.reload_interval_secs);
+        if interval.is_zero() {
+            return;
+        }
+        {
+            let state = self.state.read().unwrap();
+            if state.checked_at.elapsed() < interval {
+                return;
+            }
+        }
+
+        let mut state = self.state.write().unwrap();
+        if state.checked_at.elapsed() < interval {
+            return;
+        }
+        state.checked_at = Instant::now();
+        let modified = modified_times(&self.config);
+        if modified == state.modified {
+            return;
+        }
+        match load_certified_key(&self.config, &self.provider) {
+            Ok(key) => {
+                info!("Reloaded TLS certificate {}", self.config.cert_file);
+                state.key = Arc::new(key);
+                state.modified = modified;
+            }
+            Err(e) => warn!("Keeping the current TLS certificate: {}", e),
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.reload_if_changed();
+        Some(self.state.read().unwrap().key.clone())
+    }
+}
+
+fn modified_times(config: &TlsConfig) -> (Option<SystemTime>, Option<SystemTime>) {
+    let modified = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    (modified(&config.cert_file), modified(&config.key_file))
+}
+
+/// Read the PEM certificate chain and private key, checking that they match
+fn load_certified_key(config: &TlsConfig, provider: &CryptoProvider) -> GatewayResult<CertifiedKey> {
+    let mut cert_reader = BufReader::new(File::open(&config.cert_file)?);
+    let certs = rustls_pemfile::certs(&mut cert_reader)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| tls_error(&config.cert_file, &e))?;
+    if certs.is_empty() {
+        return Err(tls_error(&config.cert_file, &"no certificates found"));
+    }
+
+    let mut key_reader = BufReader::new(File::open(&config.key_file)?);
+    let key = rustls_pemfile::private_key(&mut key_reader)
+        .map_err(|e| tls_error(&config.key_file, &e))?
+        .ok_or_else(|| tls_error(&config.key_file, &"no private key found"))?;
+
+    CertifiedKey::from_der(certs, key, provider).map_err(|e| tls_error(&config.cert_file, &e))
+}
+
+fn tls_error(what: &str, e: &dyn std::fmt::Display) -> GatewayError {
+    GatewayError::ConfigError(format!("Failed to load TLS {}: {}", what, e))
+}
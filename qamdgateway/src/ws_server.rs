@@ -1,35 +1,486 @@
-use actix::{Actor, ActorContext, AsyncContext, Handler, StreamHandler};
+use actix::{Actor, ActorContext, ActorFutureExt, AsyncContext, ContextFutureSpawner, Handler, StreamHandler, WrapFuture};
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use hashbrown::{HashMap, HashSet};
+use std::collections::VecDeque;
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
-use log::{info, debug, warn, error};
+use tracing::{info, info_span, debug, warn, error, Span};
 
+use crate::acl::{request_token, AclStore, Permissions};
 use crate::actors::messages::*;
+use crate::actors::alert_engine::AlertEngine;
+use crate::actors::data_quality::DataQualityActor;
+use crate::actors::depth_engine::DepthEngine;
+use crate::actors::indicator_engine::IndicatorEngine;
 use crate::actors::md_distributor::MarketDataDistributor;
-use crate::config::BrokerConfig;
+use crate::actors::quota_monitor::QuotaMonitor;
+use crate::actors::trading_phase::TradingPhaseActor;
+use crate::actors::watchlist_store::WatchlistStore;
+use crate::actors::profile_store::{is_valid_client_name, ClientProfile, ProfileStore};
+use crate::alerts::{AlertCondition, AlertRule};
+use crate::analytics::{Indicators, Kline};
+use crate::instruments::{is_pattern, matches_pattern, InstrumentRegistry};
+use crate::latency::{self, LatencyTrace, LATENCY_FIELD};
+use crate::naming::{FieldNaming, Named};
+use crate::quote_encoding::{self, EncodedQuote};
+use crate::config::{
+    ClientQuotaConfig, HeartbeatConfig, MessageOutput, QueuePolicy, SendQueueConfig, SlowConsumerConfig,
+};
+use crate::error::{ErrorCategory, GatewayError};
 
-// 心跳间隔，保持连接活跃（10秒）
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
-// 如果客户端在此期间未响应ping，则终止连接（30秒）
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+// 客户端可设置的最大限速间隔（60秒）
+const MAX_THROTTLE_INTERVAL_MS: u64 = 60_000;
+// 客户端可设置的最大批量发送窗口（1秒）
+const MAX_BATCH_WINDOW_MS: u64 = 1_000;
+// 发送队列状态检查间隔（1秒）
+const QUEUE_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+// 上行消息限速的统计窗口（1秒）
+const MESSAGE_RATE_WINDOW: Duration = Duration::from_secs(1);
+// 订阅请求限速的统计窗口（1分钟）
+const SUBSCRIBE_RATE_WINDOW: Duration = Duration::from_secs(60);
+// 订阅事务等待上游结果的默认时长（5秒）和最大时长（60秒）
+const DEFAULT_TRANSACTION_TIMEOUT_MS: u64 = 5_000;
+const MAX_TRANSACTION_TIMEOUT_MS: u64 = 60_000;
+// 订阅事务查询上游订阅状态的间隔
+const TRANSACTION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+// 限时订阅的过期检查间隔（1秒）
+const SUBSCRIPTION_EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 固定窗口计数器
+struct RateWindow {
+    started: Instant,
+    count: u32,
+}
+
+impl RateWindow {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            count: 0,
+        }
+    }
+
+    /// 计入一次请求，返回当前窗口内的请求数
+    fn hit(&mut self, window: Duration) -> u32 {
+        if self.started.elapsed() >= window {
+            self.started = Instant::now();
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(1);
+        self.count
+    }
+}
+
+/// ping时间戳的起点（进程内单调时钟）
+fn heartbeat_epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// 编码为ping负载的单调时间戳（起点以来的微秒，小端8字节）
+fn ping_payload() -> [u8; 8] {
+    (heartbeat_epoch().elapsed().as_micros() as u64).to_le_bytes()
+}
+
+/// 从pong负载解析往返时间，不是本服务发出的ping则返回None
+fn pong_rtt(payload: &[u8]) -> Option<Duration> {
+    let sent = u64::from_le_bytes(payload.try_into().ok()?);
+    let now = heartbeat_epoch().elapsed().as_micros() as u64;
+    now.checked_sub(sent).map(Duration::from_micros)
+}
+
+/// 心跳往返时间统计（平滑往返时间和抖动按RFC 6298计算）
+#[derive(Default)]
+struct RttTracker {
+    last: f64,
+    srtt: f64,
+    /// 往返时间的平均偏差
+    jitter: f64,
+    min: f64,
+    max: f64,
+    pongs: u64,
+    /// 连续超出阈值的pong数
+    violations: u32,
+}
+
+impl RttTracker {
+    /// 记录一次往返时间（毫秒），返回超出的阈值说明
+    fn record(&mut self, rtt: f64, config: &HeartbeatConfig) -> Option<String> {
+        if self.pongs == 0 {
+            self.srtt = rtt;
+            self.jitter = rtt / 2.0;
+            self.min = rtt;
+            self.max = rtt;
+        } else {
+            self.jitter = 0.75 * self.jitter + 0.25 * (self.srtt - rtt).abs();
+            self.srtt = 0.875 * self.srtt + 0.125 * rtt;
+            self.min = self.min.min(rtt);
+            self.max = self.max.max(rtt);
+        }
+        self.last = rtt;
+        self.pongs += 1;
+
+        let exceeded = if config.max_rtt_ms > 0 && self.srtt > config.max_rtt_ms as f64 {
+            Some(format!("RTT {:.1}ms exceeds {}ms", self.srtt, config.max_rtt_ms))
+        } else if config.max_jitter_ms > 0 && self.jitter > config.max_jitter_ms as f64 {
+            Some(format!("RTT jitter {:.1}ms exceeds {}ms", self.jitter, config.max_jitter_ms))
+        } else {
+            None
+        };
+        match exceeded {
+            Some(_) => self.violations = self.violations.saturating_add(1),
+            None => self.violations = 0,
+        }
+        exceeded
+    }
+}
+
+/// 慢速客户端的状态变化
+enum SlowConsumerChange {
+    /// 降级为合并推送（附原因）
+    Downgrade(String),
+    /// 恢复全速推送
+    Restore,
+}
+
+/// 排空探测ping的负载（与心跳ping的8字节时间戳区分）
+const DRAIN_PROBE: &[u8] = b"drain";
+
+/// 慢速客户端检测：按秒检查连接的排空延迟以及发送队列的增长
+///
+/// 排空延迟由跟在已写入行情之后的探测ping测量：客户端读完之前的所有数据才能回复pong，
+/// 因此pong的往返时间减去最快的心跳往返时间即为连接积压数据的排空时间
+#[derive(Default)]
+struct SlowConsumerTracker {
+    /// 尚未回复的探测ping的发送时间
+    probe: Option<Instant>,
+    /// 最近一次回复的探测ping的往返时间
+    probe_rtt: Duration,
+    /// 上一秒结束时的队列深度
+    last_depth: usize,
+    /// 上一秒结束时的丢弃数
+    last_dropped: u64,
+    /// 连续慢速的秒数
+    slow_secs: u32,
+    /// 降级后连续空闲的秒数
+    idle_secs: u32,
+    /// 是否已降级为合并推送
+    downgraded: bool,
+}
+
+impl SlowConsumerTracker {
+    /// 上一个探测ping已回复时开始新的探测，返回是否需要发送探测ping
+    fn start_probe(&mut self, now: Instant) -> bool {
+        if self.probe.is_some() {
+            return false;
+        }
+        self.probe = Some(now);
+        true
+    }
+
+    /// 收到探测ping的pong
+    fn probe_answered(&mut self, now: Instant) {
+        if let Some(sent) = self.probe.take() {
+            self.probe_rtt = now.saturating_duration_since(sent);
+        }
+    }
+
+    /// 排空延迟：最近探测的往返时间（未回复时为已等待的时间）减去网络往返时间
+    fn drain_lag(&self, now: Instant, network_rtt: Duration) -> Duration {
+        let waiting = self.probe.map(|sent| now.saturating_duration_since(sent)).unwrap_or_default();
+        self.probe_rtt.max(waiting).saturating_sub(network_rtt)
+    }
+
+    /// 每秒检查一次，连续慢速时降级，降级后连续空闲时恢复
+    fn evaluate(
+        &mut self,
+        now: Instant,
+        network_rtt: Duration,
+        depth: usize,
+        capacity: usize,
+        dropped: u64,
+        config: &SlowConsumerConfig,
+    ) -> Option<SlowConsumerChange> {
+        let lag_ms = self.drain_lag(now, network_rtt).as_millis() as u64;
+        let high_water = capacity * usize::from(config.queue_pct.min(100)) / 100;
+        let slow = if lag_ms > config.max_drain_ms {
+            Some(format!("connection drains {}ms behind", lag_ms))
+        } else if dropped > self.last_dropped {
+            Some(format!("{} updates dropped in the last second", dropped - self.last_dropped))
+        } else if depth > high_water && depth >= self.last_depth {
+            Some(format!("send queue at {} of {}", depth, capacity))
+        } else {
+            None
+        };
+        self.last_depth = depth;
+        self.last_dropped = dropped;
+
+        if self.downgraded {
+            let idle = slow.is_none() && depth == 0 && lag_ms * 4 <= config.max_drain_ms;
+            self.idle_secs = if idle { self.idle_secs + 1 } else { 0 };
+            if self.idle_secs < config.recover_secs.max(1) {
+                return None;
+            }
+            self.idle_secs = 0;
+            self.downgraded = false;
+            return Some(SlowConsumerChange::Restore);
+        }
+        let Some(reason) = slow else {
+            self.slow_secs = 0;
+            return None;
+        };
+        self.slow_secs += 1;
+        if self.slow_secs < config.slow_secs.max(1) {
+            return None;
+        }
+        self.slow_secs = 0;
+        self.downgraded = true;
+        Some(SlowConsumerChange::Downgrade(reason))
+    }
+}
+
+/// WebSocket消息编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WsFormat {
+    /// JSON文本帧（默认）
+    Json,
+    /// MessagePack二进制帧
+    MsgPack,
+    /// CBOR二进制帧
+    Cbor,
+}
+
+impl WsFormat {
+    /// 从查询参数或协议切换消息中的名称解析
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(WsFormat::Json),
+            "msgpack" | "messagepack" => Some(WsFormat::MsgPack),
+            "cbor" => Some(WsFormat::Cbor),
+            _ => None,
+        }
+    }
+}
+
+/// 同一连接上复用的消息通道
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// 行情、盘口、逐笔回放和订阅结果
+    #[default]
+    Quote,
+    /// K线和衍生指标
+    Kline,
+    /// 行情预警
+    Alert,
+    /// 数据质量、交易阶段、数据源切换和发送队列状态
+    Status,
+    /// 会话信息、设置应答和错误
+    Admin,
+}
+
+impl Channel {
+    /// 从请求中的通道名称解析
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "quote" => Some(Channel::Quote),
+            "kline" => Some(Channel::Kline),
+            "alert" => Some(Channel::Alert),
+            "status" => Some(Channel::Status),
+            "admin" => Some(Channel::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// 带通道的客户端请求
+///
+/// `subscribe`/`unsubscribe`按通道处理，其他请求按普通请求处理（channel字段只用于启用通道复用）
+#[derive(Debug, Deserialize)]
+struct ChannelRequest {
+    channel: String,
+    aid: String,
+    #[serde(default)]
+    ins_list: String,
+}
+
+/// 通道复用时附带channel字段的消息
+#[derive(Serialize)]
+struct Routed<'a> {
+    channel: Channel,
+    #[serde(flatten)]
+    message: &'a WsServerMessage,
+}
+
+/// 衍生指标订阅请求的aid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndicatorAid {
+    SubscribeIndicator,
+}
+
+/// 重发请求的aid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResendAid {
+    ReqResend,
+}
+
+/// 行情质量事件订阅请求的aid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityAid {
+    SubscribeQuality,
+}
+
+/// 交易阶段订阅请求的aid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseAid {
+    SubscribePhase,
+}
+
+/// 盘口请求的aid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthAid {
+    /// 订阅盘口（ins_list为完整订阅列表）
+    SubscribeDepth,
+    /// 盘口序号不连续时请求重发全量帧
+    ResyncDepth,
+}
 
 /// WebSocket客户端消息类型
+///
+/// 消息按字段匹配第一个符合的变体，字段相同的请求以只接受特定aid的类型区分
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum WsClientMessage {
+    /// 订阅事务：逐个合约报告上游的订阅结果（atomic为true时任一合约失败则全部撤销）
+    #[serde(rename_all = "snake_case")]
+    SubscribeTransaction {
+        aid: String,
+        tx_id: String,
+        ins_list: String,
+        /// 等待上游结果的时长（毫秒）
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+        #[serde(default)]
+        atomic: bool,
+    },
+    /// 订阅衍生指标（ins_list为完整订阅列表）
+    #[serde(rename_all = "snake_case")]
+    SubscribeIndicator {
+        aid: IndicatorAid,
+        ins_list: String,
+    },
+    /// 请求重发全量行情：`instrument`与`seq`为客户端最后收到的合约及序号，否则按`ins_list`重发
+    #[serde(rename_all = "snake_case")]
+    ResendRequest {
+        aid: ResendAid,
+        #[serde(default)]
+        ins_list: String,
+        #[serde(default)]
+        instrument: Option<String>,
+        #[serde(default)]
+        seq: Option<u64>,
+    },
+    /// 订阅行情质量事件（ins_list为合约或通配符模式列表，空字符串取消订阅）
+    #[serde(rename_all = "snake_case")]
+    SubscribeQuality {
+        aid: QualityAid,
+        ins_list: String,
+    },
+    /// 订阅交易阶段变化（ins_list为交易所代码或通配符模式列表，空字符串取消订阅）
+    #[serde(rename_all = "snake_case")]
+    SubscribePhase {
+        aid: PhaseAid,
+        ins_list: String,
+    },
+    /// 订阅盘口或请求重发盘口全量帧
+    #[serde(rename_all = "snake_case")]
+    DepthRequest {
+        aid: DepthAid,
+        ins_list: String,
+    },
     /// TradingView格式订阅行情
     #[serde(rename_all = "snake_case")]
     TvSubscribeQuote {
         aid: String,
         ins_list: String,
+        /// 只推送这些行情字段（不指定时推送全部字段）
+        #[serde(default)]
+        fields: Option<Vec<String>>,
+        /// 补丁模式：行情以全量帧和字段补丁（rtn_patch）推送
+        #[serde(default)]
+        patch: bool,
+        /// 推送消息的字段命名风格（不指定时保持当前设置）
+        #[serde(default)]
+        naming: Option<FieldNaming>,
+        /// 新订阅的合约先回放最近的若干笔行情（rtn_ticks）
+        #[serde(default)]
+        depth: usize,
+        /// 限时订阅：新增合约在若干秒后自动退订（0表示改为长期订阅）
+        #[serde(default)]
+        ttl_sec: Option<u64>,
     },
     /// 传统格式兼容
     LegacyMessage(LegacyClientMessage),
+    /// 设置推送频率限制（interval_ms为0表示取消限速）
+    #[serde(rename_all = "snake_case")]
+    SetThrottle {
+        aid: String,
+        interval_ms: u64,
+    },
+    /// 设置批量发送窗口（window_ms为0表示按发送队列间隔发送）
+    #[serde(rename_all = "snake_case")]
+    SetBatchWindow {
+        aid: String,
+        window_ms: u64,
+    },
+    /// 设置发送队列溢出策略（drop_oldest/conflate/disconnect）
+    #[serde(rename_all = "snake_case")]
+    SetQueuePolicy {
+        aid: String,
+        policy: QueuePolicy,
+    },
+    /// 切换消息编码格式（json/msgpack/cbor）
+    #[serde(rename_all = "snake_case")]
+    SetFormat {
+        aid: String,
+        format: String,
+    },
+    /// 切换行情消息格式（tv/legacy/both）
+    #[serde(rename_all = "snake_case")]
+    SetOutput {
+        aid: String,
+        output: String,
+    },
+    /// 登记行情预警
+    #[serde(rename_all = "snake_case")]
+    SetAlert {
+        aid: String,
+        #[serde(flatten)]
+        rule: AlertRule,
+    },
+    /// 删除行情预警
+    #[serde(rename_all = "snake_case")]
+    RemoveAlert {
+        aid: String,
+        alert_id: String,
+    },
+    /// 订阅/取消订阅自选列表
+    #[serde(rename_all = "snake_case")]
+    WatchlistRequest {
+        aid: String,
+        name: String,
+    },
     /// Peek message
     #[serde(rename_all = "snake_case")]
     PeekMessage {
@@ -43,7 +494,15 @@ pub enum WsClientMessage {
 pub enum LegacyClientMessage {
     /// 订阅一个或多个合约
     #[serde(rename = "subscribe")]
-    Subscribe { instruments: Vec<String> },
+    Subscribe {
+        instruments: Vec<String>,
+        /// 推送消息的字段命名风格（不指定时保持当前设置）
+        #[serde(default)]
+        naming: Option<FieldNaming>,
+        /// 新订阅的合约先回放最近的若干笔行情（rtn_ticks）
+        #[serde(default)]
+        depth: usize,
+    },
     /// 取消订阅一个或多个合约
     #[serde(rename = "unsubscribe")]
     Unsubscribe { instruments: Vec<String> },
@@ -67,6 +526,11 @@ pub enum WsServerMessage {
         aid: String,
         data: Vec<TvMarketDataItem>,
     },
+    /// 行情增量（rtn_data），只包含变化的字段
+    QuoteData {
+        aid: String,
+        data: Vec<QuoteDataItem>,
+    },
     /// 旧版格式
     LegacyMessage(LegacyServerMessage),
     /// Peek message响应
@@ -74,6 +538,243 @@ pub enum WsServerMessage {
         aid: String,
         ins_list: String,
     },
+    /// 指标、K线、盘口、质量事件和交易阶段的订阅响应（channel只用于路由，不发送给客户端）
+    SubscribeResponse {
+        aid: String,
+        #[serde(skip)]
+        channel: Channel,
+        ins_list: String,
+    },
+    /// 限速设置响应
+    ThrottleResponse {
+        aid: String,
+        interval_ms: u64,
+    },
+    /// 批量发送窗口设置响应
+    BatchWindowResponse {
+        aid: String,
+        window_ms: u64,
+    },
+    /// 编码格式切换响应
+    FormatResponse {
+        aid: String,
+        format: WsFormat,
+    },
+    /// 行情消息格式切换响应
+    OutputResponse {
+        aid: String,
+        output: MessageOutput,
+    },
+    /// 发送队列状态
+    QueueStatus {
+        aid: String,
+        depth: usize,
+        capacity: usize,
+        dropped: u64,
+        policy: QueuePolicy,
+    },
+    /// 合约行情数据源切换通知
+    SourceChange {
+        aid: String,
+        instrument_id: String,
+        from: MarketDataSource,
+        to: MarketDataSource,
+    },
+    /// 主力合约切换通知（from为null表示订阅时的当前映射）
+    Remap {
+        aid: String,
+        instrument_id: String,
+        from: Option<String>,
+        to: String,
+    },
+    /// 预警登记/删除响应
+    AlertResponse {
+        aid: String,
+        alert_id: String,
+    },
+    /// 预警触发通知
+    Alert {
+        aid: String,
+        alert_id: String,
+        instrument_id: String,
+        condition: AlertCondition,
+        value: f64,
+        datetime: chrono::DateTime<chrono::Utc>,
+    },
+    /// 衍生指标推送
+    Indicator {
+        aid: String,
+        data: Indicators,
+    },
+    /// 盘口全量和增量帧推送
+    Depth {
+        aid: String,
+        data: Vec<DepthFrame>,
+    },
+    /// 行情质量事件推送
+    Quality {
+        aid: String,
+        data: QualityEvent,
+    },
+    /// 交易阶段变化推送
+    Phase {
+        aid: String,
+        data: PhaseChanged,
+    },
+    /// 交易日结束通知，quotes为已订阅合约的最终行情（含结算价）
+    SessionEnd {
+        aid: String,
+        data: SessionEnd,
+        quotes: Vec<qamd_rs::MDSnapshot>,
+    },
+    /// 自选列表订阅响应和修改通知（ins_list为空表示已取消订阅或列表已删除）
+    Watchlist {
+        aid: String,
+        name: String,
+        ins_list: String,
+    },
+    /// 订阅时回放的最近行情（从旧到新）
+    Ticks {
+        aid: String,
+        instrument_id: String,
+        data: Vec<Value>,
+    },
+    /// 补丁模式的行情帧
+    QuotePatch {
+        aid: String,
+        data: Vec<QuoteFrame>,
+    },
+    /// 错误通知（code/category见GatewayError）
+    Error {
+        aid: String,
+        code: u16,
+        category: ErrorCategory,
+        message: String,
+    },
+    /// 会话信息（resumed表示恢复了断线前的订阅）
+    Session {
+        aid: String,
+        session_id: String,
+        resumed: bool,
+        ins_list: String,
+        patterns: Vec<String>,
+    },
+    /// 按客户端名称恢复的订阅和限速设置
+    Profile {
+        aid: String,
+        client_name: String,
+        ins_list: String,
+        patterns: Vec<String>,
+        watchlists: Vec<String>,
+        throttle_ms: u64,
+        batch_window_ms: u64,
+    },
+    /// 订阅事务结果（pending为超时仍未确认的合约，rolled_back表示原子事务已撤销）
+    SubscribeResult {
+        aid: String,
+        tx_id: String,
+        succeeded: Vec<String>,
+        failed: Vec<SubscribeFailure>,
+        pending: Vec<String>,
+        rolled_back: bool,
+    },
+    /// 慢速客户端降级/恢复通知（downgraded时行情按interval_ms合并推送）
+    SlowConsumer {
+        aid: String,
+        downgraded: bool,
+        interval_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// 限时订阅到期后自动退订的合约
+    SubscriptionExpired {
+        aid: String,
+        instruments: Vec<String>,
+    },
+    /// K线推送（当前K线）
+    Kline {
+        aid: String,
+        data: Kline,
+    },
+    /// 通道订阅和取消订阅的应答
+    ChannelResponse {
+        aid: String,
+        channel: Channel,
+        ins_list: String,
+    },
+}
+
+impl WsServerMessage {
+    /// 消息所属的通道
+    pub fn channel(&self) -> Channel {
+        match self {
+            WsServerMessage::TvMarketData { .. }
+            | WsServerMessage::QuoteData { .. }
+            | WsServerMessage::QuotePatch { .. }
+            | WsServerMessage::Ticks { .. }
+            | WsServerMessage::Depth { .. }
+            | WsServerMessage::Remap { .. }
+            | WsServerMessage::Watchlist { .. }
+            | WsServerMessage::SubscribeResult { .. }
+            | WsServerMessage::SubscriptionExpired { .. } => Channel::Quote,
+            WsServerMessage::PeekMessageResponse { .. } => Channel::Quote,
+            WsServerMessage::SubscribeResponse { channel, .. } => *channel,
+            WsServerMessage::Indicator { .. } | WsServerMessage::Kline { .. } => Channel::Kline,
+            WsServerMessage::Alert { .. } | WsServerMessage::AlertResponse { .. } => Channel::Alert,
+            WsServerMessage::QueueStatus { .. }
+            | WsServerMessage::SlowConsumer { .. }
+            | WsServerMessage::SourceChange { .. }
+            | WsServerMessage::Quality { .. }
+            | WsServerMessage::Phase { .. }
+            | WsServerMessage::SessionEnd { .. } => Channel::Status,
+            WsServerMessage::LegacyMessage(
+                LegacyServerMessage::MarketData { .. } | LegacyServerMessage::Subscriptions { .. },
+            ) => Channel::Quote,
+            WsServerMessage::LegacyMessage(LegacyServerMessage::System { .. } | LegacyServerMessage::Pong)
+            | WsServerMessage::ThrottleResponse { .. }
+            | WsServerMessage::BatchWindowResponse { .. }
+            | WsServerMessage::FormatResponse { .. }
+            | WsServerMessage::OutputResponse { .. }
+            | WsServerMessage::Error { .. }
+            | WsServerMessage::Session { .. }
+            | WsServerMessage::Profile { .. } => Channel::Admin,
+            WsServerMessage::ChannelResponse { channel, .. } => *channel,
+        }
+    }
+
+    /// 是否为取消alert或status通道订阅后不再推送的事件
+    fn is_event(&self) -> bool {
+        matches!(
+            self,
+            WsServerMessage::Alert { .. }
+                | WsServerMessage::QueueStatus { .. }
+                | WsServerMessage::SlowConsumer { .. }
+                | WsServerMessage::SourceChange { .. }
+                | WsServerMessage::Quality { .. }
+                | WsServerMessage::Phase { .. }
+                | WsServerMessage::SessionEnd { .. }
+        )
+    }
+}
+
+/// 订阅事务中失败的合约
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscribeFailure {
+    pub instrument_id: String,
+    pub reason: String,
+}
+
+/// 进行中的订阅事务
+struct SubscribeTransaction {
+    tx_id: String,
+    /// 等待上游结果的合约
+    instruments: Vec<String>,
+    /// 本事务新加入订阅的合约（原子事务失败时撤销）
+    added: Vec<String>,
+    /// 未向上游订阅即失败的合约
+    rejected: Vec<SubscribeFailure>,
+    atomic: bool,
+    deadline: Instant,
 }
 
 /// TradingView格式的行情数据项
@@ -82,6 +783,22 @@ pub struct TvMarketDataItem {
     pub quotes: HashMap<String, TvQuote>,
 }
 
+/// 行情增量数据项（合约ID -> 变化的字段）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuoteDataItem {
+    pub quotes: serde_json::Map<String, Value>,
+}
+
+impl WsServerMessage {
+    /// 由合约增量组成的rtn_data消息
+    pub fn quote_data(quotes: serde_json::Map<String, Value>) -> Self {
+        WsServerMessage::QuoteData {
+            aid: "rtn_data".to_string(),
+            data: vec![QuoteDataItem { quotes }],
+        }
+    }
+}
+
 /// TradingView格式的行情数据
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TvQuote {
@@ -140,18 +857,13 @@ pub enum LegacyServerMessage {
     /// 市场数据更新
     #[serde(rename = "market_data")]
     MarketData {
-        data: qamd_rs::MDSnapshot,
+        data: serde_json::Map<String, Value>,
     },
     /// 系统消息
     #[serde(rename = "system")]
     System {
         message: String,
     },
-    /// 错误消息
-    #[serde(rename = "error")]
-    Error {
-        message: String,
-    },
     /// 订阅请求的响应
     #[serde(rename = "subscriptions")]
     Subscriptions {
@@ -162,83 +874,1238 @@ pub enum LegacyServerMessage {
     Pong,
 }
 
+/// 发送队列中的行情增量
+enum QueuedQuote {
+    /// 分发器共享的增量，发送时可直接使用缓存的编码
+    Shared(Arc<EncodedQuote>),
+    /// 本会话改写过的增量（如附加了合约基础信息、合并了多条增量）
+    Owned(serde_json::Map<String, Value>),
+}
+
+impl QueuedQuote {
+    fn into_fields(self) -> serde_json::Map<String, Value> {
+        match self {
+            QueuedQuote::Shared(quote) => quote.fields().clone(),
+            QueuedQuote::Owned(fields) => fields,
+        }
+    }
+
+    /// 合并较新的增量，较新的值覆盖旧值
+    fn merge(&mut self, newer: QueuedQuote) {
+        let mut fields = std::mem::replace(self, QueuedQuote::Owned(serde_json::Map::new())).into_fields();
+        fields.extend(newer.into_fields());
+        *self = QueuedQuote::Owned(fields);
+    }
+}
+
+/// 发送队列已满（Disconnect策略）
+#[derive(Debug)]
+struct QueueFull;
+
+/// 会话的行情发送队列
+///
+/// 队列中是增量，丢弃一条会丢失其中的字段：同一合约后面还有增量时并入后者，
+/// 否则记录该合约，待队列排空后请分发器重发全量行情
+#[derive(Default)]
+struct SendQueue {
+    entries: VecDeque<(String, QueuedQuote)>,
+    /// 增量被丢弃、需要重发全量行情的合约
+    resync: HashSet<String>,
+}
+
+impl SendQueue {
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 放入一条增量，队列已满时按策略腾出位置，返回丢失了增量的合约
+    fn push(
+        &mut self,
+        instrument: String,
+        quote: QueuedQuote,
+        capacity: usize,
+        policy: QueuePolicy,
+    ) -> Result<Option<String>, QueueFull> {
+        let mut lost = None;
+        if self.entries.len() >= capacity.max(1) {
+            match policy {
+                QueuePolicy::Disconnect => return Err(QueueFull),
+                // 同一合约的增量按字段合并，较新的值覆盖旧值
+                QueuePolicy::Conflate => {
+                    if let Some((_, queued)) = self.entries.iter_mut().find(|(queued, _)| *queued == instrument) {
+                        queued.merge(quote);
+                        return Ok(None);
+                    }
+                }
+                QueuePolicy::DropOldest => {}
+            }
+            lost = self.drop_oldest();
+        }
+        self.entries.push_back((instrument, quote));
+        Ok(lost)
+    }
+
+    /// 丢弃队首的增量，返回丢失了增量的合约（并入同一合约较新的增量时为None）
+    fn drop_oldest(&mut self) -> Option<String> {
+        let (instrument, mut oldest) = self.entries.pop_front()?;
+        if let Some((_, newer)) = self.entries.iter_mut().find(|(queued, _)| *queued == instrument) {
+            oldest.merge(std::mem::replace(newer, QueuedQuote::Owned(serde_json::Map::new())));
+            *newer = oldest;
+            return None;
+        }
+        self.resync.insert(instrument.clone());
+        Some(instrument)
+    }
+
+    /// 取出队首至多limit条增量
+    fn take(&mut self, limit: usize) -> Vec<(String, QueuedQuote)> {
+        let batch = limit.min(self.entries.len());
+        self.entries.drain(..batch).collect()
+    }
+
+    /// 取出需要重发全量行情的合约
+    fn take_resync(&mut self) -> Vec<String> {
+        self.resync.drain().collect()
+    }
+}
+
 /// WebSocket会话状态
 pub struct WsSession {
     /// 唯一会话ID
     client_id: String,
     /// 客户端心跳状态
     heartbeat: Instant,
+    /// 心跳设置
+    heartbeat_config: HeartbeatConfig,
+    /// 心跳往返时间统计
+    rtt: RttTracker,
+    /// 客户端最近一次发送请求的时间（不含ping/pong）
+    last_request: Instant,
     /// 市场数据分发器地址
     md_distributor: actix::Addr<MarketDataDistributor>,
+    /// 行情预警Actor地址
+    alerts: actix::Addr<AlertEngine>,
     /// 已订阅的合约
     subscriptions: HashSet<String>,
+    /// 限时订阅的合约 -> 到期时间
+    expiries: HashMap<String, Instant>,
+    /// 已订阅的通配符模式（匹配的新合约收到行情时自动加入订阅）
+    patterns: HashSet<String>,
+    /// 客户端要求推送的行情字段（None表示全部字段）
+    fields: Option<HashSet<String>>,
     /// 市场数据源类型
     market_data_source: MarketDataSource,
+    /// 是否使用DIFF协议（客户端发送过peek_message后启用，行情只在peek时返回）
+    diff_mode: bool,
+    /// 客户端当前持有的行情状态（合约ID -> 字段）
+    quote_state: HashMap<String, serde_json::Map<String, Value>>,
+    /// 上次peek之后变化的字段（合约ID -> 字段）
+    pending_diff: HashMap<String, serde_json::Map<String, Value>>,
+    /// 是否有尚未应答的peek_message
+    peek_pending: bool,
+    /// 是否使用补丁模式（行情以全量帧和字段补丁推送，不经过发送队列）
+    patch: bool,
+    /// 发送给客户端的消息编码格式
+    format: WsFormat,
+    /// 发送给客户端的消息字段命名风格
+    naming: FieldNaming,
+    /// 合约基础信息（用于展开合约组、补充合约乘数等静态字段）
+    instruments: Arc<InstrumentRegistry>,
+    /// 已发送过合约基础信息的合约
+    described: HashSet<String>,
+    /// 合约 -> 最近一条行情消息的序号（丢弃的行情也占用序号）
+    quote_seq: HashMap<String, u64>,
+    /// 待发送的行情（合约ID, 增量），按间隔批量发送
+    send_queue: SendQueue,
+    /// 发送队列配置
+    queue_config: SendQueueConfig,
+    /// 发送队列溢出策略
+    queue_policy: QueuePolicy,
+    /// 发送队列中的行情以哪些格式推送（TradingView的rtn_data和/或旧版market_data）
+    output: MessageOutput,
+    /// 批量发送窗口：窗口内的行情合并为一帧发送（为0时按发送队列间隔发送）
+    batch_window: Duration,
+    /// 是否已安排窗口结束时的发送
+    batch_scheduled: bool,
+    /// 窗口内待发送的补丁模式行情帧
+    pending_frames: Vec<QuoteFrame>,
+    /// 请求恢复的断线前会话ID
+    resume_session: Option<String>,
+    /// 因队列溢出丢弃的行情数
+    dropped: u64,
+    /// 上次向客户端报告的丢弃数
+    reported_dropped: u64,
+    /// 慢速客户端检测
+    slow_consumer: SlowConsumerTracker,
+    /// 客户端限额
+    quota: ClientQuotaConfig,
+    /// 限额监控Actor地址（超限时上报）
+    quota_monitor: Option<actix::Addr<QuotaMonitor>>,
+    /// 客户端地址
+    remote_addr: Option<String>,
+    /// 上行消息计数
+    message_rate: RateWindow,
+    /// 订阅请求计数
+    subscribe_rate: RateWindow,
+    /// 衍生指标Actor地址
+    indicators: Option<actix::Addr<IndicatorEngine>>,
+    /// 订阅衍生指标的合约
+    indicator_subscriptions: Vec<String>,
+    /// 订阅K线的合约
+    kline_subscriptions: Vec<String>,
+    /// 是否通道复用（客户端发送过带channel字段的请求后，推送的消息都带channel字段）
+    multiplex: bool,
+    /// 已取消订阅的事件通道（alert、status）
+    muted: HashSet<Channel>,
+    /// 盘口增量Actor地址
+    depth: Option<actix::Addr<DepthEngine>>,
+    /// 订阅盘口增量的合约
+    depth_subscriptions: Vec<String>,
+    /// 行情质量监控Actor地址
+    quality: Option<actix::Addr<DataQualityActor>>,
+    /// 订阅行情质量事件的合约模式
+    quality_subscriptions: Vec<String>,
+    /// 交易阶段Actor地址
+    phases: Option<actix::Addr<TradingPhaseActor>>,
+    /// 订阅交易阶段变化的交易所
+    phase_subscriptions: Vec<String>,
+    /// 自选列表存储Actor地址
+    watchlist_store: Option<actix::Addr<WatchlistStore>>,
+    /// 订阅的自选列表（名称 -> 展开后的合约）
+    watchlists: HashMap<String, HashSet<String>>,
+    /// 客户端配置存储Actor地址
+    profile_store: Option<actix::Addr<ProfileStore>>,
+    /// 客户端名称（连接时指定，按名称保存和恢复订阅及限速设置）
+    client_name: Option<String>,
+    /// 限速间隔（毫秒，0表示不限速）
+    throttle_ms: u64,
+    /// 合约访问控制（None表示不限制）
+    acl: Option<Arc<AclStore>>,
+    /// 客户端的令牌
+    token: Option<String>,
+    /// 缓存的权限及读取时令牌存储的版本号
+    permissions: Option<(u64, Permissions)>,
+    /// 本会话日志所在的span（会话启动后创建）
+    span: Span,
 }
 
 impl Actor for WsSession {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        self.span = self.session_span();
+        let _span = self.span.clone().entered();
+
         // 启动心跳进程
         self.start_heartbeat(ctx);
-
-        // 注册到市场数据分发器
-        let addr = ctx.address();
         
-        // 向分发器注册，提供会话ID和接收者地址
-        self.md_distributor.do_send(RegisterDataReceiver {
-            client_id: self.client_id.clone(),
-            addr: addr.recipient(),
-            instruments: Vec::new(),
-        });
+        // 行情先进入发送队列，由定时任务批量发送；邮箱容量与队列一致，避免分发器投递失败
+        ctx.set_mailbox_capacity(self.queue_config.capacity);
+        self.start_send_queue(ctx);
+        ctx.run_interval(SUBSCRIPTION_EXPIRY_INTERVAL, |act, ctx| act.expire_subscriptions(ctx));
 
-        // 发送欢迎消息
-        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
-            message: format!("Connected to QAMD Gateway WebSocket. Session ID: {}", self.client_id),
-        });
-        if let Ok(json) = serde_json::to_string(&msg) {
-            ctx.text(json);
-        }
+        // 带会话ID重连时先尝试恢复之前的订阅，失败则作为新会话注册
+        let Some(session_id) = self.resume_session.take() else {
+            self.register(ctx);
+            return;
+        };
+        let addr = ctx.address();
+        self.md_distributor
+            .send(ResumeSession {
+                client_id: session_id.clone(),
+                addr: addr.clone().recipient(),
+                notify: Some(addr.clone().recipient()),
+                remap: Some(addr.clone().recipient()),
+                disconnect: Some(addr.recipient()),
+            })
+            .into_actor(self)
+            .map(move |res, act, ctx| match res {
+                Ok(Some(resumed)) => {
+                    act.client_id = session_id;
+                    act.span = act.session_span();
+                    act.subscriptions = resumed.instruments.into_iter().collect();
+                    act.patterns = resumed.patterns.iter().cloned().collect();
+                    act.fields = resumed.fields;
+                    act.throttle_ms = resumed.throttle.map_or(0, |interval| interval.as_millis() as u64);
+                    act.send_session_info(ctx, true);
+                }
+                _ => {
+                    info!("Session {} cannot be resumed, starting a new session", session_id);
+                    act.register(ctx);
+                }
+            })
+            .wait(ctx);
     }
 
-    fn stopping(&mut self, _: &mut Self::Context) -> actix::Running {
+    fn stopping(&mut self, ctx: &mut Self::Context) -> actix::Running {
+        let _span = self.span.clone().entered();
         // 从市场数据分发器取消注册
         self.md_distributor.do_send(UnregisterDataReceiver {
             client_id: self.client_id.clone(),
         });
-        actix::Running::Stop
-    }
-}
-
-impl WsSession {
-    /// 创建新的WebSocket会话
-    pub fn new(md_distributor: actix::Addr<MarketDataDistributor>, source: MarketDataSource) -> Self {
-        Self {
-            client_id: Uuid::new_v4().to_string(),
-            heartbeat: Instant::now(),
-            md_distributor,
+        if let Some(monitor) = &self.quota_monitor {
+            monitor.do_send(ForgetClient {
+                client_id: self.client_id.clone(),
+            });
+        }
+        if let (Some(indicators), false) = (&self.indicators, self.indicator_subscriptions.is_empty()) {
+            indicators.do_send(SubscribeIndicators {
+                client_id: self.client_id.clone(),
+                addr: ctx.address().recipient(),
+                instruments: Vec::new(),
+            });
+        }
+        if let (Some(indicators), false) = (&self.indicators, self.kline_subscriptions.is_empty()) {
+            indicators.do_send(SubscribeKlines {
+                client_id: self.client_id.clone(),
+                addr: ctx.address().recipient(),
+                instruments: Vec::new(),
+            });
+        }
+        if let (Some(depth), false) = (&self.depth, self.depth_subscriptions.is_empty()) {
+            depth.do_send(SubscribeDepth {
+                client_id: self.client_id.clone(),
+                addr: ctx.address().recipient(),
+                instruments: Vec::new(),
+            });
+        }
+        if let (Some(quality), false) = (&self.quality, self.quality_subscriptions.is_empty()) {
+            quality.do_send(SubscribeQuality {
+                client_id: self.client_id.clone(),
+                addr: ctx.address().recipient(),
+                patterns: Vec::new(),
+            });
+        }
+        if let (Some(phases), false) = (&self.phases, self.phase_subscriptions.is_empty()) {
+            phases.do_send(SubscribePhase {
+                client_id: self.client_id.clone(),
+                addr: ctx.address().recipient(),
+                session_end: None,
+                exchanges: Vec::new(),
+            });
+        }
+        self.alerts.do_send(RemoveAlert {
+            client_id: self.client_id.clone(),
+            alert_id: None,
+        });
+        if let (Some(store), false) = (&self.watchlist_store, self.watchlists.is_empty()) {
+            store.do_send(UnsubscribeWatchlist {
+                name: None,
+                client_id: self.client_id.clone(),
+            });
+        }
+        actix::Running::Stop
+    }
+}
+
+/// 被拒绝合约的说明（最多列出10个）
+pub(crate) fn denied_message(instruments: &[String]) -> String {
+    let mut listed: Vec<&str> = instruments.iter().take(10).map(String::as_str).collect();
+    listed.sort_unstable();
+    let more = instruments.len() - listed.len();
+    if more > 0 {
+        format!("no scope for {} and {} more", listed.join(", "), more)
+    } else {
+        format!("no scope for {}", listed.join(", "))
+    }
+}
+
+/// 记录行情的序列化时间和各跳延迟，未开启嵌入时从消息中去掉追踪
+///
+/// 限速和补丁模式的行情由分发器从缓存重新生成，不带追踪
+fn stamp_latency(quotes: &mut serde_json::Map<String, Value>) {
+    if !latency::is_enabled() {
+        return;
+    }
+    for fields in quotes.values_mut().filter_map(Value::as_object_mut) {
+        if let Some(mut trace) = LatencyTrace::take_from(fields) {
+            trace.record_serialize();
+            if latency::embeds() {
+                fields.insert(LATENCY_FIELD.to_string(), trace.to_value());
+            }
+        }
+    }
+}
+
+impl WsSession {
+    /// 创建新的WebSocket会话
+    ///
+    /// resume_session为断线前的会话ID，连接建立后尝试恢复该会话的订阅
+    pub fn new(
+        md_distributor: actix::Addr<MarketDataDistributor>,
+        alerts: actix::Addr<AlertEngine>,
+        source: MarketDataSource,
+        format: WsFormat,
+        instruments: Arc<InstrumentRegistry>,
+        queue_config: SendQueueConfig,
+        resume_session: Option<String>,
+    ) -> Self {
+        Self {
+            client_id: Uuid::new_v4().to_string(),
+            heartbeat: Instant::now(),
+            heartbeat_config: HeartbeatConfig::default(),
+            rtt: RttTracker::default(),
+            last_request: Instant::now(),
+            md_distributor,
+            alerts,
             subscriptions: HashSet::new(),
+            expiries: HashMap::new(),
+            patterns: HashSet::new(),
+            fields: None,
             market_data_source: source,
+            diff_mode: false,
+            quote_state: HashMap::new(),
+            pending_diff: HashMap::new(),
+            peek_pending: false,
+            patch: false,
+            format,
+            naming: FieldNaming::default(),
+            instruments,
+            described: HashSet::new(),
+            quote_seq: HashMap::new(),
+            send_queue: SendQueue::default(),
+            queue_policy: queue_config.policy,
+            output: queue_config.output,
+            batch_window: Duration::from_millis(queue_config.batch_window_ms.min(MAX_BATCH_WINDOW_MS)),
+            batch_scheduled: false,
+            pending_frames: Vec::new(),
+            queue_config,
+            resume_session,
+            dropped: 0,
+            reported_dropped: 0,
+            slow_consumer: SlowConsumerTracker::default(),
+            quota: ClientQuotaConfig::default(),
+            quota_monitor: None,
+            remote_addr: None,
+            span: Span::none(),
+            message_rate: RateWindow::new(),
+            subscribe_rate: RateWindow::new(),
+            indicators: None,
+            indicator_subscriptions: Vec::new(),
+            kline_subscriptions: Vec::new(),
+            multiplex: false,
+            muted: HashSet::new(),
+            depth: None,
+            depth_subscriptions: Vec::new(),
+            quality: None,
+            quality_subscriptions: Vec::new(),
+            phases: None,
+            phase_subscriptions: Vec::new(),
+            watchlist_store: None,
+            watchlists: HashMap::new(),
+            profile_store: None,
+            client_name: None,
+            throttle_ms: 0,
+            acl: None,
+            token: None,
+            permissions: None,
+        }
+    }
+
+    /// 设置消息字段命名风格
+    pub fn with_naming(mut self, naming: FieldNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// 允许客户端通过subscribe_indicator订阅衍生指标
+    pub fn with_indicators(mut self, indicators: actix::Addr<IndicatorEngine>) -> Self {
+        self.indicators = Some(indicators);
+        self
+    }
+
+    /// 允许客户端通过subscribe_depth订阅盘口增量
+    pub fn with_depth(mut self, depth: actix::Addr<DepthEngine>) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// 允许客户端通过subscribe_quality订阅行情质量事件
+    pub fn with_quality(mut self, quality: actix::Addr<DataQualityActor>) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// 允许客户端通过subscribe_phase订阅交易阶段变化
+    pub fn with_phases(mut self, phases: actix::Addr<TradingPhaseActor>) -> Self {
+        self.phases = Some(phases);
+        self
+    }
+
+    /// 允许客户端通过subscribe_watchlist订阅共享的自选列表
+    pub fn with_watchlists(mut self, store: actix::Addr<WatchlistStore>) -> Self {
+        self.watchlist_store = Some(store);
+        self
+    }
+
+    /// 以客户端名称连接：新会话恢复该名称保存的订阅和限速设置，之后的修改写回存储
+    pub fn with_profile(mut self, store: actix::Addr<ProfileStore>, client_name: String) -> Self {
+        self.profile_store = Some(store);
+        self.client_name = Some(client_name);
+        self
+    }
+
+    /// 设置客户端限额，超限事件上报给限额监控Actor
+    pub fn with_quota(
+        mut self,
+        quota: ClientQuotaConfig,
+        quota_monitor: actix::Addr<QuotaMonitor>,
+        remote_addr: Option<String>,
+    ) -> Self {
+        self.quota = quota;
+        self.quota_monitor = Some(quota_monitor);
+        self.remote_addr = remote_addr;
+        self
+    }
+
+    /// 设置心跳间隔、超时、往返时间阈值和空闲退订时间
+    pub fn with_heartbeat(mut self, heartbeat: HeartbeatConfig) -> Self {
+        self.heartbeat_config = heartbeat;
+        self
+    }
+
+    /// 按令牌的权限范围限制可订阅和推送的合约
+    pub fn with_acl(mut self, acl: Arc<AclStore>, token: Option<String>) -> Self {
+        self.acl = Some(acl);
+        self.token = token;
+        self
+    }
+
+    /// 令牌是否允许该合约（令牌存储修改后重新读取权限）
+    fn permits(&mut self, instrument: &str) -> bool {
+        let Some(acl) = &self.acl else {
+            return true;
+        };
+        let generation = acl.generation();
+        if !matches!(&self.permissions, Some((cached, _)) if *cached == generation) {
+            self.permissions = Some((generation, acl.permissions(self.token.as_deref())));
+        }
+        self.permissions
+            .as_ref()
+            .is_some_and(|(_, permissions)| permissions.allows(instrument, &self.instruments))
+    }
+
+    /// 去掉令牌不允许的合约，有被拒绝的合约时通知客户端
+    fn filter_permitted<I>(&mut self, ctx: &mut ws::WebsocketContext<Self>, instruments: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let (permitted, denied): (Vec<String>, Vec<String>) =
+            instruments.into_iter().partition(|instrument| self.permits(instrument));
+        if !denied.is_empty() {
+            self.send_error(ctx, &GatewayError::PermissionDenied(denied_message(&denied)));
+        }
+        permitted
+    }
+
+    /// 撤销令牌不再允许的合约订阅（令牌在会话期间被修改或删除）
+    fn revoke(&mut self, ctx: &mut ws::WebsocketContext<Self>, instruments: Vec<String>) {
+        if instruments.is_empty() {
+            return;
+        }
+        for instrument in &instruments {
+            self.subscriptions.remove(instrument);
+            self.quote_state.remove(instrument);
+            self.pending_diff.remove(instrument);
+            self.described.remove(instrument);
+            self.expiries.remove(instrument);
+        }
+        self.md_distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: self.subscriptions.iter().cloned().collect(),
+        });
+        info!("Revoked {} instruments of client {} after a token change", instruments.len(), self.client_id);
+        self.save_profile();
+        self.send_error(ctx, &GatewayError::PermissionDenied(denied_message(&instruments)));
+    }
+
+    /// 处理认证请求，之后的订阅和推送按该令牌的权限检查
+    fn handle_auth(&mut self, ctx: &mut ws::WebsocketContext<Self>, token: String) {
+        let Some(acl) = &self.acl else {
+            let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
+                message: "Authentication is not enabled".to_string(),
+            });
+            self.send(ctx, &msg);
+            return;
+        };
+        let Some(name) = acl.name(&token) else {
+            warn!("Client {} sent an unknown token", self.client_id);
+            self.send_error(ctx, &GatewayError::AuthError("unknown token".to_string()));
+            return;
+        };
+        info!("Client {} authenticated as {:?}", self.client_id, name);
+        self.token = Some(token);
+        self.permissions = None;
+        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
+            message: format!("Authenticated as {}", name),
+        });
+        self.send(ctx, &msg);
+    }
+
+    /// 向客户端发送超限错误并上报限额监控Actor
+    fn report_violation(&self, ctx: &mut ws::WebsocketContext<Self>, kind: QuotaKind, error: GatewayError) {
+        self.send_error(ctx, &error);
+        if let Some(monitor) = &self.quota_monitor {
+            monitor.do_send(QuotaViolation {
+                client_id: self.client_id.clone(),
+                remote_addr: self.remote_addr.clone(),
+                kind,
+                detail: error.to_string(),
+                addr: ctx.address().recipient(),
+            });
+        }
+    }
+
+    /// 检查上行消息频率，超限的消息被丢弃；每个窗口只通知一次客户端
+    fn check_message_rate(&mut self, ctx: &mut ws::WebsocketContext<Self>) -> bool {
+        let limit = self.quota.max_messages_per_second;
+        if limit == 0 {
+            return true;
+        }
+        let count = self.message_rate.hit(MESSAGE_RATE_WINDOW);
+        if count <= limit {
+            return true;
+        }
+        if count == limit + 1 {
+            let error = GatewayError::RateLimited(format!(
+                "more than {} messages per second, messages are dropped",
+                limit
+            ));
+            self.report_violation(ctx, QuotaKind::MessageRate, error);
+        }
+        false
+    }
+
+    /// 检查订阅请求频率，超限的请求被拒绝
+    fn check_subscribe_rate(&mut self, ctx: &mut ws::WebsocketContext<Self>) -> bool {
+        let limit = self.quota.max_subscribe_per_minute;
+        if limit == 0 || self.subscribe_rate.hit(SUBSCRIBE_RATE_WINDOW) <= limit {
+            return true;
+        }
+        let error = GatewayError::RateLimited(format!(
+            "more than {} subscribe requests per minute",
+            limit
+        ));
+        self.report_violation(ctx, QuotaKind::SubscribeRate, error);
+        false
+    }
+
+    /// 检查订阅后的合约数（通配符模式各计为一个）是否超出限额
+    fn check_instrument_quota(&self, ctx: &mut ws::WebsocketContext<Self>, total: usize) -> bool {
+        let limit = self.quota.max_instruments;
+        if limit == 0 || total <= limit {
+            return true;
+        }
+        let error = GatewayError::QuotaExceeded(format!(
+            "subscription of {} instruments exceeds the limit of {}",
+            total, limit
+        ));
+        self.report_violation(ctx, QuotaKind::Instruments, error);
+        false
+    }
+
+    /// 作为新会话注册到市场数据分发器
+    fn register(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let addr = ctx.address();
+        
+        // 向分发器注册，提供会话ID和接收者地址
+        self.md_distributor.do_send(RegisterDataReceiver {
+            client_id: self.client_id.clone(),
+            addr: addr.clone().recipient(),
+            instruments: Vec::new(),
+            notify: Some(addr.clone().recipient()),
+            remap: Some(addr.clone().recipient()),
+            disconnect: Some(addr.recipient()),
+        });
+
+        // 发送欢迎消息
+        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
+            message: format!("Connected to QAMD Gateway WebSocket. Session ID: {}", self.client_id),
+        });
+        self.send(ctx, &msg);
+        self.send_session_info(ctx, false);
+        self.restore_profile(ctx);
+    }
+
+    /// 恢复客户端名称保存的订阅和限速设置，恢复完成前不处理客户端的请求
+    fn restore_profile(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let (Some(store), Some(name)) = (&self.profile_store, &self.client_name) else {
+            return;
+        };
+        store
+            .send(LoadProfile { name: name.clone() })
+            .into_actor(self)
+            .map(|res, act, ctx| match res {
+                Ok(Some(profile)) => act.apply_profile(ctx, profile),
+                Ok(None) => {}
+                Err(e) => error!("Failed to load profile of client {}: {}", act.client_id, e),
+            })
+            .wait(ctx);
+    }
+
+    /// 应用保存的客户端配置，订阅仍按令牌权限和合约数限额检查
+    fn apply_profile(&mut self, ctx: &mut ws::WebsocketContext<Self>, profile: ClientProfile) {
+        info!(
+            "Client {} restoring profile {}: {} instruments, {} patterns, {} watchlists",
+            self.client_id,
+            profile.name,
+            profile.instruments.len(),
+            profile.patterns.len(),
+            profile.watchlists.len()
+        );
+        self.apply_throttle(profile.throttle_ms);
+        self.batch_window = Duration::from_millis(profile.batch_window_ms.min(MAX_BATCH_WINDOW_MS));
+        if let Some(policy) = profile.queue_policy {
+            self.queue_policy = policy;
+        }
+        let requested: Vec<String> = profile.instruments.into_iter().chain(profile.patterns).collect();
+        if !requested.is_empty() {
+            self.handle_subscribe(ctx, requested, 0);
+        }
+        for name in &profile.watchlists {
+            self.handle_subscribe_watchlist(ctx, name.clone());
         }
+
+        let mut ins_list: Vec<&str> = self.subscriptions.iter().map(String::as_str).collect();
+        ins_list.sort_unstable();
+        let msg = WsServerMessage::Profile {
+            aid: "rtn_profile".to_string(),
+            client_name: profile.name,
+            ins_list: ins_list.join(","),
+            patterns: self.patterns.iter().cloned().collect(),
+            watchlists: profile.watchlists,
+            throttle_ms: self.throttle_ms,
+            batch_window_ms: self.batch_window.as_millis() as u64,
+        };
+        self.send(ctx, &msg);
+    }
+
+    /// 保存当前的订阅和限速设置（以客户端名称连接时）
+    ///
+    /// 经通配符模式或自选列表订阅的合约不单独保存，恢复时由模式和列表重新带入
+    fn save_profile(&self) {
+        let (Some(store), Some(name)) = (&self.profile_store, &self.client_name) else {
+            return;
+        };
+        let mut instruments: Vec<String> = self
+            .subscriptions
+            .iter()
+            .filter(|instrument| !self.patterns.iter().any(|pattern| matches_pattern(pattern, instrument)))
+            .filter(|instrument| !self.watchlists.values().any(|list| list.contains(*instrument)))
+            .cloned()
+            .collect();
+        instruments.sort_unstable();
+        let mut patterns: Vec<String> = self.patterns.iter().cloned().collect();
+        patterns.sort_unstable();
+        let mut watchlists: Vec<String> = self.watchlists.keys().cloned().collect();
+        watchlists.sort_unstable();
+        store.do_send(SaveProfile(ClientProfile {
+            name: name.clone(),
+            instruments,
+            patterns,
+            watchlists,
+            throttle_ms: self.throttle_ms,
+            batch_window_ms: self.batch_window.as_millis() as u64,
+            queue_policy: Some(self.queue_policy),
+            updated_at: chrono::Utc::now(),
+        }));
+    }
+
+    /// 设置限速：分发器按间隔合并推送，每个合约只保留最新行情；返回生效的间隔
+    fn apply_throttle(&mut self, interval_ms: u64) -> u64 {
+        self.throttle_ms = interval_ms.min(MAX_THROTTLE_INTERVAL_MS);
+        self.update_throttle();
+        self.throttle_ms
+    }
+
+    /// 按客户端设置的限速和慢速降级的合并间隔中较长者设置分发器的推送频率
+    fn update_throttle(&self) {
+        let mut interval_ms = self.throttle_ms;
+        if self.slow_consumer.downgraded {
+            interval_ms = interval_ms.max(self.queue_config.slow_consumer.conflate_ms);
+        }
+        let interval = if interval_ms == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(interval_ms))
+        };
+        self.md_distributor.do_send(SetClientThrottle {
+            client_id: self.client_id.clone(),
+            interval,
+        });
+    }
+
+    /// 每秒检查客户端是否跟得上行情：跟不上时降级为合并推送，追上后恢复原来的推送频率
+    fn check_slow_consumer(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let config = &self.queue_config.slow_consumer;
+        // DIFF协议由客户端的peek控制节奏
+        if !config.enabled || self.diff_mode {
+            return;
+        }
+        let conflate_ms = config.conflate_ms;
+        let now = Instant::now();
+        let network_rtt = Duration::from_secs_f64(self.rtt.min / 1000.0);
+        let change = self.slow_consumer.evaluate(
+            now,
+            network_rtt,
+            self.send_queue.len(),
+            self.queue_config.capacity,
+            self.dropped,
+            config,
+        );
+        // 探测ping排在已写入的行情之后
+        if self.slow_consumer.start_probe(now) {
+            ctx.ping(DRAIN_PROBE);
+        }
+        let msg = match change {
+            Some(SlowConsumerChange::Downgrade(reason)) => {
+                warn!(
+                    "Client {} cannot keep up ({}), sending conflated updates every {}ms",
+                    self.client_id, reason, conflate_ms
+                );
+                WsServerMessage::SlowConsumer {
+                    aid: "rtn_slow_consumer".to_string(),
+                    downgraded: true,
+                    interval_ms: self.throttle_ms.max(conflate_ms),
+                    reason: Some(reason),
+                }
+            }
+            Some(SlowConsumerChange::Restore) => {
+                info!("Client {} caught up, restoring full rate updates", self.client_id);
+                WsServerMessage::SlowConsumer {
+                    aid: "rtn_slow_consumer".to_string(),
+                    downgraded: false,
+                    interval_ms: self.throttle_ms,
+                    reason: None,
+                }
+            }
+            None => return,
+        };
+        self.update_throttle();
+        self.send(ctx, &msg);
+    }
+
+    /// 告知客户端会话ID（断线重连时用于恢复会话）及当前订阅
+    fn send_session_info(&self, ctx: &mut ws::WebsocketContext<Self>, resumed: bool) {
+        let mut ins_list: Vec<&str> = self.subscriptions.iter().map(String::as_str).collect();
+        ins_list.sort_unstable();
+        let msg = WsServerMessage::Session {
+            aid: "rtn_session".to_string(),
+            session_id: self.client_id.clone(),
+            resumed,
+            ins_list: ins_list.join(","),
+            patterns: self.patterns.iter().cloned().collect(),
+        };
+        self.send(ctx, &msg);
+    }
+
+    /// 发送消息，通道复用时附带消息所属的通道
+    fn send(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &WsServerMessage) {
+        let channel = msg.channel();
+        if msg.is_event() && self.muted.contains(&channel) {
+            return;
+        }
+        // 通道应答自带channel字段
+        if self.multiplex && !matches!(msg, WsServerMessage::ChannelResponse { .. }) {
+            self.encode(ctx, &Routed { channel, message: msg });
+        } else {
+            self.encode(ctx, msg);
+        }
+    }
+
+    /// 按会话协商的格式和字段命名风格编码并发送消息
+    fn encode<T: Serialize>(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &T) {
+        let msg = &Named::new(msg, self.naming);
+        match self.format {
+            WsFormat::Json => match serde_json::to_string(msg) {
+                Ok(json) => ctx.text(json),
+                Err(e) => error!("Failed to encode JSON message for client {}: {}", self.client_id, e),
+            },
+            WsFormat::MsgPack => match rmp_serde::to_vec_named(msg) {
+                Ok(bytes) => ctx.binary(bytes),
+                Err(e) => error!("Failed to encode MessagePack message for client {}: {}", self.client_id, e),
+            },
+            WsFormat::Cbor => {
+                let mut bytes = Vec::new();
+                match ciborium::into_writer(msg, &mut bytes) {
+                    Ok(()) => ctx.binary(bytes),
+                    Err(e) => error!("Failed to encode CBOR message for client {}: {}", self.client_id, e),
+                }
+            }
+        }
+    }
+
+    /// 发送结构化错误通知（rtn_error）
+    fn send_error(&self, ctx: &mut ws::WebsocketContext<Self>, error: &GatewayError) {
+        let msg = WsServerMessage::Error {
+            aid: "rtn_error".to_string(),
+            code: error.code(),
+            category: error.category(),
+            message: error.to_string(),
+        };
+        self.send(ctx, &msg);
+    }
+
+    /// 本会话日志所在的span
+    fn session_span(&self) -> Span {
+        info_span!(
+            "session",
+            client_id = %self.client_id,
+            remote_addr = self.remote_addr.as_deref().unwrap_or("unknown")
+        )
     }
 
     /// 启动心跳检测
     fn start_heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
-        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+        let interval = Duration::from_secs(self.heartbeat_config.interval_secs.max(1));
+        let timeout = Duration::from_secs(self.heartbeat_config.timeout_secs);
+        ctx.run_interval(interval, move |act, ctx| {
+            let _span = act.span.clone().entered();
             // 检查客户端心跳
-            if Instant::now().duration_since(act.heartbeat) > CLIENT_TIMEOUT {
+            if Instant::now().duration_since(act.heartbeat) > timeout {
                 // 心跳超时，关闭连接
                 info!("WebSocket Client {} heartbeat failed, disconnecting", act.client_id);
                 ctx.stop();
                 return;
             }
+            act.check_idle(ctx);
+
+            // 发送带时间戳的ping，客户端回复的pong原样带回
+            ctx.ping(&ping_payload());
+        });
+    }
+
+    /// 连接保持但长时间没有请求的客户端，退订全部行情以释放上游订阅
+    fn check_idle(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let idle = self.heartbeat_config.idle_unsubscribe_secs;
+        if idle == 0
+            || self.last_request.elapsed() < Duration::from_secs(idle)
+            || (self.subscriptions.is_empty() && self.patterns.is_empty() && self.watchlists.is_empty())
+        {
+            return;
+        }
+        info!(
+            "WebSocket Client {} sent no request for {}s, unsubscribing {} instruments",
+            self.client_id,
+            idle,
+            self.subscriptions.len()
+        );
+        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
+            message: format!("No request for {} seconds, unsubscribed from all market data", idle),
+        });
+        self.send(ctx, &msg);
+
+        let names: Vec<String> = self.watchlists.keys().cloned().collect();
+        for name in names {
+            self.handle_unsubscribe_watchlist(ctx, name);
+        }
+        let instruments: Vec<String> = self.patterns.iter().chain(self.subscriptions.iter()).cloned().collect();
+        if !instruments.is_empty() {
+            self.handle_unsubscribe(ctx, instruments);
+        }
+    }
+
+    /// 退订已到期的限时订阅，并通知客户端（rtn_subscription_expired）
+    ///
+    /// 上游退订仍由分发器按退订延迟处理，到期后很快重新订阅不会造成上游反复订阅
+    fn expire_subscriptions(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.expiries.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let mut expired: Vec<String> = Vec::new();
+        self.expiries.retain(|instrument, expiry| {
+            if *expiry > now {
+                return true;
+            }
+            expired.push(instrument.clone());
+            false
+        });
+        expired.retain(|instrument| self.subscriptions.contains(instrument));
+        if expired.is_empty() {
+            return;
+        }
+        expired.sort();
+        for instrument in &expired {
+            self.subscriptions.remove(instrument);
+            self.quote_state.remove(instrument);
+            self.pending_diff.remove(instrument);
+            self.described.remove(instrument);
+        }
+        self.md_distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: self.subscriptions.iter().cloned().collect(),
+        });
+        debug!("Subscriptions of client {} expired: {:?}", self.client_id, expired);
+        self.save_profile();
+        let msg = WsServerMessage::SubscriptionExpired {
+            aid: "rtn_subscription_expired".to_string(),
+            instruments: expired,
+        };
+        self.send(ctx, &msg);
+    }
+
+    /// 根据pong计算往返时间，连续超出阈值时断开连接
+    fn handle_pong(&mut self, payload: &[u8], ctx: &mut ws::WebsocketContext<Self>) {
+        if payload == DRAIN_PROBE {
+            self.slow_consumer.probe_answered(Instant::now());
+            return;
+        }
+        let Some(rtt) = pong_rtt(payload) else {
+            return;
+        };
+        let exceeded = self.rtt.record(rtt.as_secs_f64() * 1000.0, &self.heartbeat_config);
+
+        if let Some(monitor) = &self.quota_monitor {
+            monitor.do_send(ClientRtt {
+                info: ClientRttInfo {
+                    client_id: self.client_id.clone(),
+                    remote_addr: self.remote_addr.clone(),
+                    last_rtt_ms: self.rtt.last,
+                    srtt_ms: self.rtt.srtt,
+                    min_rtt_ms: self.rtt.min,
+                    max_rtt_ms: self.rtt.max,
+                    jitter_ms: self.rtt.jitter,
+                    pongs: self.rtt.pongs,
+                    violations: self.rtt.violations,
+                    updated_at: chrono::Utc::now(),
+                },
+            });
+        }
+
+        let Some(reason) = exceeded else {
+            return;
+        };
+        if self.rtt.violations < self.heartbeat_config.max_violations.max(1) {
+            debug!("Client {} heartbeat: {}", self.client_id, reason);
+            return;
+        }
+        warn!(
+            "Client {} ({}) disconnected after {} slow heartbeats: {}",
+            self.client_id,
+            self.remote_addr.as_deref().unwrap_or("unknown"),
+            self.rtt.violations,
+            reason
+        );
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some(reason),
+        }));
+        ctx.stop();
+    }
+
+    /// 启动发送队列的批量发送和状态报告
+    fn start_send_queue(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let drain_interval = Duration::from_millis(self.queue_config.drain_interval_ms.max(1));
+        ctx.run_interval(drain_interval, |act, ctx| {
+            // 开启批量窗口时由窗口结束统一发送
+            if act.batch_window.is_zero() {
+                let _span = act.span.clone().entered();
+                act.drain_send_queue(ctx, act.queue_config.drain_batch.max(1));
+            }
+        });
+        
+        ctx.run_interval(QUEUE_STATUS_INTERVAL, |act, ctx| {
+            // 有新的丢弃或队列超过一半时通知客户端，慢速客户端可据此降低订阅量或设置限速
+            let congested = act.send_queue.len() * 2 >= act.queue_config.capacity;
+            if act.dropped != act.reported_dropped || congested {
+                act.reported_dropped = act.dropped;
+                act.send_queue_status(ctx);
+            }
+            act.check_slow_consumer(ctx);
+        });
+    }
+
+    /// 发送队列状态
+    fn send_queue_status(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let msg = WsServerMessage::QueueStatus {
+            aid: "rtn_queue_status".to_string(),
+            depth: self.send_queue.len(),
+            capacity: self.queue_config.capacity,
+            dropped: self.dropped,
+            policy: self.queue_policy,
+        };
+        self.send(ctx, &msg);
+    }
+
+    /// 该合约下一条行情消息的序号
+    fn next_seq(&mut self, instrument: &str) -> u64 {
+        let seq = self.quote_seq.entry_ref(instrument).or_default();
+        *seq += 1;
+        *seq
+    }
+
+    /// 为每个合约的行情附加序号
+    fn stamp_seq(&mut self, quotes: &mut serde_json::Map<String, Value>) {
+        for (instrument, fields) in quotes.iter_mut() {
+            if let Value::Object(fields) = fields {
+                fields.insert("seq".to_string(), json!(self.next_seq(instrument)));
+            }
+        }
+    }
+
+    /// 将行情放入发送队列，队列已满时按溢出策略处理
+    ///
+    /// 丢失了增量的合约跳过一个序号，使客户端能发现丢失
+    fn enqueue(&mut self, ctx: &mut ws::WebsocketContext<Self>, instrument: String, data: QueuedQuote) {
+        match self.send_queue.push(instrument, data, self.queue_config.capacity, self.queue_policy) {
+            Ok(None) => {}
+            Ok(Some(lost)) => {
+                self.next_seq(&lost);
+                self.dropped += 1;
+            }
+            Err(QueueFull) => {
+                warn!("Send queue of client {} is full, disconnecting", self.client_id);
+                self.send_queue_status(ctx);
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Policy,
+                    description: Some("send queue full".to_string()),
+                }));
+                ctx.stop();
+                return;
+            }
+        }
+        self.schedule_batch(ctx);
+    }
 
-            // 发送ping
-            ctx.ping(b"");
+    /// 队列排空到一半以下后，请分发器重发丢失了增量的合约的全量行情
+    fn request_resync(&mut self) {
+        if self.send_queue.len() * 2 >= self.queue_config.capacity {
+            return;
+        }
+        let instruments = self.send_queue.take_resync();
+        if instruments.is_empty() {
+            return;
+        }
+        // 全量行情重新附带合约基础信息
+        for instrument in &instruments {
+            self.described.remove(instrument);
+        }
+        debug!("Requesting full snapshots of {} instruments for client {}", instruments.len(), self.client_id);
+        self.md_distributor.do_send(ResendSnapshots {
+            client_id: self.client_id.clone(),
+            instruments,
+        });
+    }
+
+    /// 开启批量窗口时，在窗口结束时发送窗口内的全部行情
+    fn schedule_batch(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.batch_window.is_zero() || self.batch_scheduled {
+            return;
+        }
+        self.batch_scheduled = true;
+        ctx.run_later(self.batch_window, |act, ctx| {
+            let _span = act.span.clone().entered();
+            act.batch_scheduled = false;
+            act.drain_send_queue(ctx, usize::MAX);
+            if !act.pending_frames.is_empty() {
+                let msg = WsServerMessage::QuotePatch {
+                    aid: "rtn_patch".to_string(),
+                    data: std::mem::take(&mut act.pending_frames),
+                };
+                act.send(ctx, &msg);
+            }
         });
     }
 
+    /// 设置批量发送窗口，窗口开启前已排队的行情在新窗口结束时发送
+    fn handle_batch_window(&mut self, ctx: &mut ws::WebsocketContext<Self>, window_ms: u64) {
+        let window_ms = window_ms.min(MAX_BATCH_WINDOW_MS);
+        self.batch_window = Duration::from_millis(window_ms);
+        info!("Client {} batch window set to {}ms", self.client_id, window_ms);
+        self.save_profile();
+        if !self.send_queue.is_empty() {
+            self.schedule_batch(ctx);
+        }
+        let msg = WsServerMessage::BatchWindowResponse {
+            aid: "rsp_set_batch_window".to_string(),
+            window_ms,
+        };
+        self.send(ctx, &msg);
+    }
+
+    /// 从发送队列取出至多limit个行情，合并为一条rtn_data发送，旧版格式每个合约一条market_data
+    fn drain_send_queue(&mut self, ctx: &mut ws::WebsocketContext<Self>, limit: usize) {
+        if self.send_queue.is_empty() {
+            return;
+        }
+        
+        let queued = self.send_queue.take(limit);
+        let batch = queued.len();
+        self.request_resync();
+        if self.send_pre_encoded(ctx, &queued) {
+            debug!("Sent {} queued updates to client {}", batch, self.client_id);
+            return;
+        }
+        let mut quotes: serde_json::Map<String, Value> = serde_json::Map::new();
+        for (instrument, data) in queued {
+            match quotes.get_mut(&instrument) {
+                Some(Value::Object(fields)) => fields.extend(data.into_fields()),
+                _ => {
+                    quotes.insert(instrument, Value::Object(data.into_fields()));
+                }
+            }
+        }
+        stamp_latency(&mut quotes);
+        self.stamp_seq(&mut quotes);
+        
+        // 只序列化客户端选择的格式
+        if self.output.includes_legacy() {
+            for data in quotes.values() {
+                let Value::Object(data) = data else {
+                    continue;
+                };
+                let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::MarketData { data: data.clone() });
+                self.send(ctx, &msg);
+            }
+        }
+        if self.output.includes_tv() {
+            self.send(ctx, &WsServerMessage::quote_data(quotes));
+        }
+        debug!("Sent {} queued updates to client {}", batch, self.client_id);
+    }
+
+    /// 直接用分发器缓存的编码拼接rtn_data帧发送，返回是否已发送
+    ///
+    /// 只适用于不改写行情的会话：字段名为snake_case、未通道复用、只推送rtn_data，
+    /// 且本批次都是未经改写、不带延迟追踪的共享增量，每个合约至多一条
+    fn send_pre_encoded(&mut self, ctx: &mut ws::WebsocketContext<Self>, queued: &[(String, QueuedQuote)]) -> bool {
+        if self.naming != FieldNaming::SnakeCase || self.multiplex || self.output.includes_legacy() {
+            return false;
+        }
+        let mut seen = HashSet::with_capacity(queued.len());
+        let mut quotes = Vec::with_capacity(queued.len());
+        for (instrument, data) in queued {
+            let QueuedQuote::Shared(quote) = data else {
+                return false;
+            };
+            if quote.fields().contains_key(LATENCY_FIELD) || !seen.insert(instrument.as_str()) {
+                return false;
+            }
+            let seq = self.quote_seq.get(instrument.as_str()).copied().unwrap_or_default() + 1;
+            quotes.push((instrument.as_str(), seq, quote.as_ref()));
+        }
+        let frame = match quote_encoding::quote_frame(self.format, &quotes) {
+            Ok(frame) => frame,
+            Err(_) => return false,
+        };
+        match self.format {
+            WsFormat::Json => match String::from_utf8(frame) {
+                Ok(text) => ctx.text(text),
+                Err(_) => return false,
+            },
+            WsFormat::MsgPack | WsFormat::Cbor => ctx.binary(frame),
+        }
+        for (instrument, _) in queued {
+            self.next_seq(instrument);
+        }
+        true
+    }
+
     /// 将TradingView格式的订阅字符串转换为合约列表
     fn parse_tv_instruments(&self, ins_list: &str) -> Vec<String> {
         ins_list
@@ -249,70 +2116,839 @@ impl WsSession {
     }
 
     /// 处理订阅请求
-    fn handle_subscribe(&mut self, ctx: &mut ws::WebsocketContext<Self>, instruments: Vec<String>) {
+    fn handle_subscribe(&mut self, ctx: &mut ws::WebsocketContext<Self>, instruments: Vec<String>, depth: usize) {
+        // 通配符模式由分发器展开，合约组（如ALL.SHFE）在本地展开
+        let (patterns, instruments): (Vec<String>, Vec<String>) =
+            instruments.into_iter().partition(|instrument| is_pattern(instrument));
+        let instruments = self.instruments.expand(&instruments);
+        if instruments.is_empty() && patterns.is_empty() {
+            self.send_error(ctx, &GatewayError::NoInstruments);
+            return;
+        }
+        let instruments = self.filter_permitted(ctx, instruments);
+        if instruments.is_empty() && patterns.is_empty() {
+            return;
+        }
+        let total = self.subscriptions.len()
+            + instruments.iter().filter(|i| !self.subscriptions.contains(*i)).count()
+            + self.patterns.len()
+            + patterns.iter().filter(|p| !self.patterns.contains(*p)).count();
+        if !self.check_instrument_quota(ctx, total) {
+            return;
+        }
+
+        if !instruments.is_empty() {
+            // 更新本地订阅集合
+            let added: Vec<String> = instruments
+                .iter()
+                .filter(|instrument| self.subscriptions.insert((*instrument).clone()))
+                .cloned()
+                .collect();
+
+            // 更新分发器的订阅
+            self.md_distributor.do_send(UpdateSubscription {
+                client_id: self.client_id.clone(),
+                instruments: self.subscriptions.iter().cloned().collect(),
+            });
+            self.replay_ticks(ctx, added, depth);
+        }
+
+        if !patterns.is_empty() {
+            self.patterns.extend(patterns.iter().cloned());
+            self.md_distributor.do_send(SubscribePattern {
+                client_id: self.client_id.clone(),
+                patterns: patterns.clone(),
+            });
+        }
+
+        self.save_profile();
+
+        // 发送确认消息
+        let message = if patterns.is_empty() {
+            format!("Subscribed to {} instruments", instruments.len())
+        } else {
+            format!("Subscribed to {} instruments and patterns {:?}", instruments.len(), patterns)
+        };
+        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System { message });
+        self.send(ctx, &msg);
+    }
+
+    /// 处理订阅事务：跟踪每个合约的上游订阅结果，全部有结果或超时后返回汇总（rtn_subscribe_result）
+    fn handle_subscribe_transaction(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        tx_id: String,
+        ins_list: &str,
+        timeout_ms: Option<u64>,
+        atomic: bool,
+    ) {
+        // 通配符模式匹配的合约随行情陆续加入，无法在事务内确认
+        let (patterns, instruments): (Vec<String>, Vec<String>) =
+            self.parse_tv_instruments(ins_list).into_iter().partition(|instrument| is_pattern(instrument));
+        let mut rejected: Vec<SubscribeFailure> = patterns
+            .into_iter()
+            .map(|instrument_id| SubscribeFailure {
+                instrument_id,
+                reason: "patterns cannot be subscribed in a transaction".to_string(),
+            })
+            .collect();
+        let mut seen = HashSet::new();
+        let mut permitted = Vec::new();
+        for instrument in self.instruments.expand(&instruments) {
+            if !seen.insert(instrument.clone()) {
+                continue;
+            }
+            if self.permits(&instrument) {
+                permitted.push(instrument);
+            } else {
+                rejected.push(SubscribeFailure {
+                    reason: denied_message(std::slice::from_ref(&instrument)),
+                    instrument_id: instrument,
+                });
+            }
+        }
+        if permitted.is_empty() && rejected.is_empty() {
+            self.send_error(ctx, &GatewayError::NoInstruments);
+            return;
+        }
+
+        let total = self.subscriptions.len()
+            + permitted.iter().filter(|i| !self.subscriptions.contains(*i)).count()
+            + self.patterns.len();
+        let aborted = if atomic && !rejected.is_empty() {
+            Some("transaction aborted")
+        } else if !self.check_instrument_quota(ctx, total) {
+            Some("subscription exceeds the instrument limit")
+        } else {
+            None
+        };
+        let timeout = timeout_ms.unwrap_or(DEFAULT_TRANSACTION_TIMEOUT_MS).min(MAX_TRANSACTION_TIMEOUT_MS);
+        let mut tx = SubscribeTransaction {
+            tx_id,
+            instruments: Vec::new(),
+            added: Vec::new(),
+            rejected,
+            atomic,
+            deadline: Instant::now() + Duration::from_millis(timeout),
+        };
+        if let Some(reason) = aborted {
+            tx.rejected.extend(permitted.into_iter().map(|instrument_id| SubscribeFailure {
+                instrument_id,
+                reason: reason.to_string(),
+            }));
+            self.finish_transaction(ctx, tx, Vec::new());
+            return;
+        }
+
+        tx.added = permitted
+            .iter()
+            .filter(|instrument| self.subscriptions.insert((*instrument).clone()))
+            .cloned()
+            .collect();
+        tx.instruments = permitted;
+        if !tx.added.is_empty() {
+            self.md_distributor.do_send(UpdateSubscription {
+                client_id: self.client_id.clone(),
+                instruments: self.subscriptions.iter().cloned().collect(),
+            });
+            self.save_profile();
+        }
+        self.poll_transaction(ctx, tx);
+    }
+
+    /// 查询事务中合约的上游订阅状态，仍有未确认的合约且未超时则稍后再查
+    fn poll_transaction(&mut self, ctx: &mut ws::WebsocketContext<Self>, tx: SubscribeTransaction) {
+        self.md_distributor
+            .send(QueryUpstreamStatus {
+                instruments: tx.instruments.clone(),
+            })
+            .into_actor(self)
+            .map(move |res, act, ctx| {
+                let statuses = res.unwrap_or_else(|e| {
+                    error!("Failed to query upstream subscriptions: {}", e);
+                    tx.instruments.iter().map(|i| (i.clone(), UpstreamStatus::Pending)).collect()
+                });
+                let waiting = statuses.iter().any(|(_, status)| *status == UpstreamStatus::Pending);
+                if waiting && Instant::now() < tx.deadline {
+                    ctx.run_later(TRANSACTION_POLL_INTERVAL, move |act, ctx| act.poll_transaction(ctx, tx));
+                    return;
+                }
+                act.finish_transaction(ctx, tx, statuses);
+            })
+            .spawn(ctx);
+    }
+
+    /// 汇总订阅事务的结果，原子事务有合约失败或未确认时撤销本事务新加入的订阅
+    fn finish_transaction(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        tx: SubscribeTransaction,
+        statuses: Vec<(String, UpstreamStatus)>,
+    ) {
+        let _span = self.span.clone().entered();
+        let mut succeeded = Vec::new();
+        let mut pending = Vec::new();
+        let mut failed = tx.rejected;
+        for (instrument, status) in statuses {
+            match status {
+                UpstreamStatus::Subscribed => succeeded.push(instrument),
+                UpstreamStatus::Pending => pending.push(instrument),
+                UpstreamStatus::Failed(reason) => failed.push(SubscribeFailure {
+                    instrument_id: instrument,
+                    reason,
+                }),
+            }
+        }
+
+        let rolled_back = tx.atomic && (!failed.is_empty() || !pending.is_empty());
+        if rolled_back {
+            // 只撤销本事务新加入、且期间未被取消的合约
+            let removed: Vec<String> = tx
+                .added
+                .into_iter()
+                .filter(|instrument| self.subscriptions.remove(instrument))
+                .collect();
+            for instrument in &removed {
+                self.quote_state.remove(instrument);
+                self.pending_diff.remove(instrument);
+                self.described.remove(instrument);
+                self.expiries.remove(instrument);
+            }
+            if !removed.is_empty() {
+                self.md_distributor.do_send(UpdateSubscription {
+                    client_id: self.client_id.clone(),
+                    instruments: self.subscriptions.iter().cloned().collect(),
+                });
+                self.save_profile();
+            }
+        }
+
+        info!(
+            "Subscribe transaction {} of client {}: {} succeeded, {} failed, {} pending{}",
+            tx.tx_id,
+            self.client_id,
+            succeeded.len(),
+            failed.len(),
+            pending.len(),
+            if rolled_back { ", rolled back" } else { "" }
+        );
+        let msg = WsServerMessage::SubscribeResult {
+            aid: "rtn_subscribe_result".to_string(),
+            tx_id: tx.tx_id,
+            succeeded,
+            failed,
+            pending,
+            rolled_back,
+        };
+        self.send(ctx, &msg);
+    }
+
+    /// 处理取消订阅请求
+    fn handle_unsubscribe(&mut self, ctx: &mut ws::WebsocketContext<Self>, instruments: Vec<String>) {
         if instruments.is_empty() {
-            let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::Error {
-                message: "No instruments specified".to_string(),
+            self.send_error(ctx, &GatewayError::NoInstruments);
+            return;
+        }
+
+        // 取消通配符模式时同时取消其匹配的合约
+        let (patterns, mut instruments): (Vec<String>, Vec<String>) =
+            instruments.into_iter().partition(|instrument| is_pattern(instrument));
+        if !patterns.is_empty() {
+            for pattern in &patterns {
+                self.patterns.remove(pattern);
+            }
+            instruments.extend(
+                self.subscriptions
+                    .iter()
+                    .filter(|instrument| patterns.iter().any(|pattern| matches_pattern(pattern, instrument)))
+                    .cloned(),
+            );
+            self.md_distributor.do_send(UnsubscribePattern {
+                client_id: self.client_id.clone(),
+                patterns,
             });
-            if let Ok(json) = serde_json::to_string(&msg) {
-                ctx.text(json);
+        }
+
+        // 更新本地订阅集合
+        for instrument in &instruments {
+            self.subscriptions.remove(instrument);
+            self.quote_state.remove(instrument);
+            self.pending_diff.remove(instrument);
+            self.described.remove(instrument);
+            self.expiries.remove(instrument);
+        }
+
+        // 获取当前所有订阅
+        let current_subscriptions: Vec<String> = self.subscriptions.iter().cloned().collect();
+
+        // 更新分发器的订阅
+        self.md_distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: current_subscriptions,
+        });
+        self.save_profile();
+
+        // 发送确认消息
+        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
+            message: format!("Unsubscribed from {} instruments", instruments.len()),
+        });
+        self.send(ctx, &msg);
+    }
+
+    /// 处理TradingView/DIFF格式的订阅请求
+    ///
+    /// `ins_list`是客户端完整的订阅列表，与当前订阅比较后增加新合约、退订不再需要的合约；
+    /// 超出合约数限额时保留当前订阅和字段设置并返回false。
+    /// 指定`ttl_sec`时新增的合约和已有的限时合约从现在起`ttl_sec`秒后到期，已有的长期订阅不受影响；
+    /// `ttl_sec`为0时列表中的合约都改为长期订阅
+    #[allow(clippy::too_many_arguments)]
+    fn handle_subscribe_quote(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        ins_list: &str,
+        fields: Option<Vec<String>>,
+        patch: bool,
+        naming: Option<FieldNaming>,
+        depth: usize,
+        ttl_sec: Option<u64>,
+    ) -> bool {
+        let requested = self.instruments.expand(&self.parse_tv_instruments(ins_list));
+        let requested: HashSet<String> = self.filter_permitted(ctx, requested).into_iter().collect();
+        if !self.check_instrument_quota(ctx, requested.len() + self.patterns.len()) {
+            return false;
+        }
+        // 先设置命名、字段和推送方式，使新订阅合约的首次推送即按新的设置
+        self.handle_naming(naming);
+        self.handle_quote_fields(fields);
+        self.handle_quote_patch(ctx, patch);
+        
+        let removed: Vec<String> = self.subscriptions.difference(&requested).cloned().collect();
+        let added: Vec<String> = requested.difference(&self.subscriptions).cloned().collect();
+        for instrument in &removed {
+            self.quote_state.remove(instrument);
+            self.pending_diff.remove(instrument);
+            self.described.remove(instrument);
+            self.expiries.remove(instrument);
+        }
+        self.subscriptions = requested;
+        match ttl_sec {
+            Some(0) => self.expiries.clear(),
+            Some(ttl) => {
+                let expiry = Instant::now() + Duration::from_secs(ttl);
+                for expiring in self.expiries.values_mut() {
+                    *expiring = expiry;
+                }
+                for instrument in &added {
+                    self.expiries.insert(instrument.clone(), expiry);
+                }
+            }
+            None => {}
+        }
+        
+        // 更新分发器的订阅
+        self.md_distributor.do_send(UpdateSubscription {
+            client_id: self.client_id.clone(),
+            instruments: self.subscriptions.iter().cloned().collect(),
+        });
+        debug!(
+            "Client {} ins_list updated: {} added, {} removed",
+            self.client_id, added.len(), removed.len()
+        );
+        self.save_profile();
+        self.replay_ticks(ctx, added, depth);
+        true
+    }
+
+    /// 向客户端回放新订阅合约最近的若干笔行情（rtn_ticks）
+    ///
+    /// 回放期间暂停处理其他消息，使回放先于订阅后的全量和增量行情到达客户端
+    fn replay_ticks(&mut self, ctx: &mut ws::WebsocketContext<Self>, instruments: Vec<String>, depth: usize) {
+        if depth == 0 || instruments.is_empty() {
+            return;
+        }
+        self.md_distributor
+            .send(GetRecentTicks {
+                client_id: self.client_id.clone(),
+                instruments,
+                depth,
+            })
+            .into_actor(self)
+            .map(|res, act, ctx| {
+                for (instrument_id, data) in res.unwrap_or_default() {
+                    let msg = WsServerMessage::Ticks {
+                        aid: "rtn_ticks".to_string(),
+                        instrument_id,
+                        data,
+                    };
+                    act.send(ctx, &msg);
+                }
+            })
+            .wait(ctx);
+    }
+
+    /// 处理衍生指标订阅，`ins_list`为完整的指标订阅列表（空字符串取消全部）
+    fn handle_subscribe_indicator(&mut self, ctx: &mut ws::WebsocketContext<Self>, ins_list: &str) -> bool {
+        let Some(indicators) = &self.indicators else {
+            self.send_error(ctx, &GatewayError::Other("Indicators are not available".to_string()));
+            return false;
+        };
+        let instruments = self.instruments.expand(&self.parse_tv_instruments(ins_list));
+        if !self.check_instrument_quota(ctx, instruments.len()) {
+            return false;
+        }
+        indicators.do_send(SubscribeIndicators {
+            client_id: self.client_id.clone(),
+            addr: ctx.address().recipient(),
+            instruments: instruments.clone(),
+        });
+        self.indicator_subscriptions = instruments;
+        true
+    }
+
+    /// 处理K线订阅，`ins_list`为完整的K线订阅列表（空字符串取消全部）
+    fn handle_subscribe_kline(&mut self, ctx: &mut ws::WebsocketContext<Self>, ins_list: &str) -> bool {
+        let Some(indicators) = &self.indicators else {
+            self.send_error(ctx, &GatewayError::Other("Klines are not available".to_string()));
+            return false;
+        };
+        let instruments = self.instruments.expand(&self.parse_tv_instruments(ins_list));
+        if !self.check_instrument_quota(ctx, instruments.len()) {
+            return false;
+        }
+        indicators.do_send(SubscribeKlines {
+            client_id: self.client_id.clone(),
+            addr: ctx.address().recipient(),
+            instruments: instruments.clone(),
+        });
+        self.kline_subscriptions = instruments;
+        true
+    }
+
+    /// 处理带通道的请求，返回false时按普通请求继续处理
+    ///
+    /// 带channel字段的请求使会话进入通道复用模式。各通道的订阅互相独立：
+    /// quote和kline通道的`ins_list`为该通道完整的合约列表，unsubscribe取消该通道的全部合约；
+    /// alert和status通道的订阅开关控制事件推送（预警规则和事件订阅保留）；admin通道不能取消
+    fn handle_channel_request(&mut self, ctx: &mut ws::WebsocketContext<Self>, request: ChannelRequest) -> bool {
+        let Some(channel) = Channel::from_name(&request.channel) else {
+            self.send_error(ctx, &GatewayError::InvalidMessage(format!("Unknown channel: {}", request.channel)));
+            return true;
+        };
+        self.multiplex = true;
+        let subscribe = match request.aid.as_str() {
+            "subscribe" => true,
+            "unsubscribe" => false,
+            _ => return false,
+        };
+        if !self.check_subscribe_rate(ctx) {
+            return true;
+        }
+        let ins_list = if subscribe { request.ins_list } else { String::new() };
+        let accepted = match channel {
+            Channel::Quote => {
+                // 沿用会话当前的字段和推送方式
+                let fields = self.fields.as_ref().map(|fields| fields.iter().cloned().collect());
+                let patch = self.patch;
+                self.handle_subscribe_quote(ctx, &ins_list, fields, patch, None, 0, None)
+            }
+            Channel::Kline => self.handle_subscribe_kline(ctx, &ins_list),
+            Channel::Alert | Channel::Status => {
+                if subscribe {
+                    self.muted.remove(&channel);
+                } else {
+                    self.muted.insert(channel);
+                }
+                self.md_distributor.do_send(SetClientChannels {
+                    client_id: self.client_id.clone(),
+                    muted: self.muted.clone(),
+                });
+                true
+            }
+            Channel::Admin if subscribe => true,
+            Channel::Admin => {
+                let error = GatewayError::InvalidMessage("The admin channel cannot be unsubscribed".to_string());
+                self.send_error(ctx, &error);
+                false
+            }
+        };
+        if accepted {
+            let msg = WsServerMessage::ChannelResponse {
+                aid: format!("rsp_{}", request.aid),
+                channel,
+                ins_list,
+            };
+            self.send(ctx, &msg);
+        }
+        true
+    }
+
+    /// 处理盘口增量订阅，`ins_list`为完整的盘口订阅列表（空字符串取消全部）
+    fn handle_subscribe_depth(&mut self, ctx: &mut ws::WebsocketContext<Self>, ins_list: &str) -> bool {
+        let Some(depth) = self.depth.clone() else {
+            self.send_error(ctx, &GatewayError::Other("Market depth is not available".to_string()));
+            return false;
+        };
+        let instruments = self.instruments.expand(&self.parse_tv_instruments(ins_list));
+        let instruments = self.filter_permitted(ctx, instruments);
+        if !self.check_instrument_quota(ctx, instruments.len()) {
+            return false;
+        }
+        depth.do_send(SubscribeDepth {
+            client_id: self.client_id.clone(),
+            addr: ctx.address().recipient(),
+            instruments: instruments.clone(),
+        });
+        self.depth_subscriptions = instruments;
+        true
+    }
+
+    /// 客户端发现盘口序号不连续时请求重新发送全量帧
+    fn handle_resync_depth(&mut self, ins_list: &str) {
+        let Some(depth) = &self.depth else {
+            return;
+        };
+        depth.do_send(ResyncDepth {
+            client_id: self.client_id.clone(),
+            instruments: self.instruments.expand(&self.parse_tv_instruments(ins_list)),
+        });
+    }
+
+    /// 客户端发现行情序号不连续时请求重新推送全量行情（空字符串表示全部已订阅合约）
+    fn handle_resend(&mut self, ins_list: &str) {
+        let requested = self.instruments.expand(&self.parse_tv_instruments(ins_list));
+        let instruments: Vec<String> = if requested.is_empty() {
+            self.subscriptions.iter().cloned().collect()
+        } else {
+            requested.into_iter().filter(|instrument| self.subscriptions.contains(instrument)).collect()
+        };
+        if instruments.is_empty() {
+            return;
+        }
+        // 全量行情重建客户端的合约状态，并重新附带合约基础信息
+        for instrument in &instruments {
+            self.quote_state.remove(instrument);
+            self.described.remove(instrument);
+        }
+        debug!("Client {} requested a resend of {} instruments", self.client_id, instruments.len());
+        self.md_distributor.do_send(ResendSnapshots {
+            client_id: self.client_id.clone(),
+            instruments,
+        });
+    }
+
+    /// 客户端报告某合约最后收到的序号，序号落后于已发送的序号时重新推送该合约的全量行情
+    fn handle_resend_from(&mut self, instrument: &str, seq: Option<u64>) {
+        if seq.is_some() && seq == self.quote_seq.get(instrument).copied() {
+            return;
+        }
+        self.handle_resend(instrument);
+    }
+
+    /// 处理行情质量事件订阅，`ins_list`为合约或通配符模式列表（空字符串取消订阅）
+    fn handle_subscribe_quality(&mut self, ctx: &mut ws::WebsocketContext<Self>, ins_list: &str) -> bool {
+        let Some(quality) = &self.quality else {
+            self.send_error(ctx, &GatewayError::Other("Data quality events are not available".to_string()));
+            return false;
+        };
+        let patterns = self.parse_tv_instruments(ins_list);
+        quality.do_send(SubscribeQuality {
+            client_id: self.client_id.clone(),
+            addr: ctx.address().recipient(),
+            patterns: patterns.clone(),
+        });
+        self.quality_subscriptions = patterns;
+        true
+    }
+
+    /// 处理交易阶段订阅，`ins_list`为交易所代码或通配符模式列表（空字符串取消订阅）
+    fn handle_subscribe_phase(&mut self, ctx: &mut ws::WebsocketContext<Self>, ins_list: &str) -> bool {
+        let Some(phases) = &self.phases else {
+            self.send_error(ctx, &GatewayError::Other("Trading phases are not available".to_string()));
+            return false;
+        };
+        let exchanges: Vec<String> = ins_list
+            .split(',')
+            .map(|exchange| exchange.trim().to_uppercase())
+            .filter(|exchange| !exchange.is_empty())
+            .collect();
+        phases.do_send(SubscribePhase {
+            client_id: self.client_id.clone(),
+            addr: ctx.address().recipient(),
+            session_end: Some(ctx.address().recipient()),
+            exchanges: exchanges.clone(),
+        });
+        self.phase_subscriptions = exchanges;
+        true
+    }
+
+    /// 切换字段命名风格，之后发送的消息（包括补丁和错误通知）都按新的风格命名
+    fn handle_naming(&mut self, naming: Option<FieldNaming>) {
+        let Some(naming) = naming.filter(|naming| *naming != self.naming) else {
+            return;
+        };
+        self.naming = naming;
+        debug!("Client {} field naming set to {:?}", self.client_id, naming);
+    }
+
+    /// 处理订阅请求中的字段列表，每次subscribe_quote都会替换之前的设置
+    ///
+    /// 字段名可以使用会话的命名风格，按snake_case交给分发器过滤
+    fn handle_quote_fields(&mut self, fields: Option<Vec<String>>) {
+        let naming = self.naming;
+        let fields: Option<HashSet<String>> =
+            fields.map(|fields| fields.iter().map(|field| naming.to_snake(field).into_owned()).collect());
+        if fields == self.fields {
+            return;
+        }
+        self.fields = fields.clone();
+        
+        // 客户端持有的行情状态只包含之前的字段，由分发器重新推送全量数据
+        self.quote_state.clear();
+        self.pending_diff.clear();
+        self.md_distributor.do_send(SetClientFields {
+            client_id: self.client_id.clone(),
+            fields,
+        });
+    }
+
+    /// 切换补丁模式，与字段设置一样每次subscribe_quote都会替换之前的设置
+    fn handle_quote_patch(&mut self, ctx: &mut ws::WebsocketContext<Self>, patch: bool) {
+        if patch == self.patch {
+            return;
+        }
+        self.patch = patch;
+        info!("Client {} patch mode {}", self.client_id, if patch { "enabled" } else { "disabled" });
+        
+        // 切换后由分发器重新推送全量数据
+        self.quote_state.clear();
+        self.pending_diff.clear();
+        self.md_distributor.do_send(SetClientPatch {
+            client_id: self.client_id.clone(),
+            addr: patch.then(|| ctx.address().recipient()),
+        });
+    }
+
+    /// 是否推送该合约的行情（已订阅，或经通配符模式新加入订阅）
+    fn accepts_instrument(&mut self, instrument: &str) -> bool {
+        if self.subscriptions.contains(instrument) {
+            return true;
+        }
+        if !self.patterns.iter().any(|pattern| matches_pattern(pattern, instrument)) {
+            return false;
+        }
+        // 无权限的合约不经通配符模式加入订阅
+        if !self.permits(instrument) {
+            return false;
+        }
+        self.subscriptions.insert(instrument.to_string());
+        true
+    }
+
+    /// 附加合约基础信息
+    fn describe(&self, instrument: &str, data: &mut serde_json::Map<String, Value>) {
+        // 保证金和手续费按昨结算价（无则昨收盘价）计算，交易前置查询的费率不依赖合约文件
+        let base_price = ["pre_settlement", "pre_close"]
+            .iter()
+            .filter_map(|field| data.get(*field).and_then(Value::as_f64))
+            .find(|price| *price > 0.0);
+        if let Some(price) = base_price {
+            if let Some(margin) = self.instruments.margin_per_lot(instrument, price) {
+                data.insert("margin".to_string(), json!(margin));
+            }
+            if let Some(commission) = self.instruments.commission_per_lot(instrument, price) {
+                data.insert("commission".to_string(), json!(commission));
             }
+        }
+        let Some(info) = self.instruments.get(instrument) else {
             return;
+        };
+        data.insert("exchange_id".to_string(), json!(info.exchange_id));
+        data.insert("ins_class".to_string(), json!(info.product_class));
+        data.insert("instrument_name".to_string(), json!(info.instrument_name));
+        data.insert("volume_multiple".to_string(), json!(info.volume_multiple));
+        data.insert("price_tick".to_string(), json!(info.price_tick));
+        data.insert("price_decs".to_string(), json!(info.price_decs()));
+        if let Some(expire_date) = &info.expire_date {
+            data.insert("expire_datetime".to_string(), json!(expire_date));
+        }
+    }
+
+    /// 将分发器推送的行情合并到客户端状态，记录真正变化的字段
+    fn merge_quote_diff(&mut self, instrument: &str, data: serde_json::Map<String, Value>) {
+        let state = self.quote_state.entry_ref(instrument).or_default();
+        let mut changed = serde_json::Map::new();
+        for (field, value) in data {
+            if state.get(&field) != Some(&value) {
+                state.insert(field.clone(), value.clone());
+                changed.insert(field, value);
+            }
+        }
+        
+        if !changed.is_empty() {
+            self.pending_diff
+                .entry_ref(instrument)
+                .or_default()
+                .extend(changed);
         }
+    }
 
-        // 更新本地订阅集合
-        for instrument in &instruments {
-            self.subscriptions.insert(instrument.clone());
+    /// 应答peek_message：有变化时返回自上次peek以来的增量，否则挂起等待下一次行情
+    fn flush_diff(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.pending_diff.is_empty() {
+            self.peek_pending = true;
+            return;
         }
+        
+        let mut quotes: serde_json::Map<String, Value> = self.pending_diff
+            .drain()
+            .map(|(instrument, fields)| (instrument, Value::Object(fields)))
+            .collect();
+        stamp_latency(&mut quotes);
+        self.stamp_seq(&mut quotes);
+        self.peek_pending = false;
+        self.send(ctx, &WsServerMessage::quote_data(quotes));
+    }
 
-        // 更新分发器的订阅
-        self.md_distributor.do_send(UpdateSubscription {
-            client_id: self.client_id.clone(),
-            instruments: instruments.clone(),
-        });
+    /// 向预警Actor登记预警，登记成功后应答客户端
+    fn handle_set_alert(&mut self, ctx: &mut ws::WebsocketContext<Self>, rule: AlertRule) {
+        let alert_id = rule.alert_id.clone();
+        self.alerts
+            .send(SetAlert {
+                client_id: self.client_id.clone(),
+                addr: ctx.address().recipient(),
+                rule,
+            })
+            .into_actor(self)
+            .map(move |res, act, ctx| {
+                match res {
+                    Ok(Ok(())) => {
+                        let msg = WsServerMessage::AlertResponse {
+                            aid: "rsp_set_alert".to_string(),
+                            alert_id,
+                        };
+                        act.send(ctx, &msg);
+                    }
+                    Ok(Err(e)) => act.send_error(ctx, &e),
+                    Err(e) => act.send_error(
+                        ctx,
+                        &GatewayError::Other(format!("Failed to set alert {}: {}", alert_id, e)),
+                    ),
+                }
+            })
+            .spawn(ctx);
+    }
 
-        // 发送确认消息
-        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
-            message: format!("Subscribed to {} instruments", instruments.len()),
-        });
-        if let Ok(json) = serde_json::to_string(&msg) {
-            ctx.text(json);
-        }
+    /// 订阅自选列表，列表中的合约加入订阅，之后列表的修改同步到订阅
+    fn handle_subscribe_watchlist(&mut self, ctx: &mut ws::WebsocketContext<Self>, name: String) {
+        let Some(store) = &self.watchlist_store else {
+            self.send_error(ctx, &GatewayError::Other("Watchlists are not available".to_string()));
+            return;
+        };
+        store
+            .send(SubscribeWatchlist {
+                name: name.clone(),
+                client_id: self.client_id.clone(),
+                addr: ctx.address().recipient(),
+            })
+            .into_actor(self)
+            .map(move |res, act, ctx| match res {
+                Ok(Ok(watchlist)) => {
+                    act.apply_watchlist(ctx, &name, Some(watchlist.instruments), "rsp_subscribe_watchlist");
+                }
+                Ok(Err(e)) => act.send_error(ctx, &e),
+                Err(e) => act.send_error(
+                    ctx,
+                    &GatewayError::Other(format!("Failed to subscribe watchlist {}: {}", name, e)),
+                ),
+            })
+            .spawn(ctx);
     }
 
-    /// 处理取消订阅请求
-    fn handle_unsubscribe(&mut self, ctx: &mut ws::WebsocketContext<Self>, instruments: Vec<String>) {
-        if instruments.is_empty() {
-            let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::Error {
-                message: "No instruments specified".to_string(),
+    /// 取消订阅自选列表
+    fn handle_unsubscribe_watchlist(&mut self, ctx: &mut ws::WebsocketContext<Self>, name: String) {
+        if !self.watchlists.contains_key(&name) {
+            self.send_error(ctx, &GatewayError::UnknownWatchlist(name));
+            return;
+        }
+        if let Some(store) = &self.watchlist_store {
+            store.do_send(UnsubscribeWatchlist {
+                name: Some(name.clone()),
+                client_id: self.client_id.clone(),
             });
-            if let Ok(json) = serde_json::to_string(&msg) {
-                ctx.text(json);
+        }
+        self.apply_watchlist(ctx, &name, None, "rsp_unsubscribe_watchlist");
+    }
+
+    /// 按自选列表的合约更新订阅并通知客户端（instruments为None表示不再跟随该列表）
+    ///
+    /// 从列表中移除的合约，如果不在其他订阅的列表中，同时取消订阅；
+    /// 超出合约数限额时保留之前的订阅
+    fn apply_watchlist(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        name: &str,
+        instruments: Option<Vec<String>>,
+        aid: &str,
+    ) {
+        let following = instruments.is_some();
+        let current = instruments
+            .map(|instruments| self.instruments.expand(&instruments))
+            .unwrap_or_default();
+        let current: HashSet<String> = self.filter_permitted(ctx, current).into_iter().collect();
+        let previous = self.watchlists.remove(name).unwrap_or_default();
+
+        let added = current.difference(&self.subscriptions).count();
+        if added > 0 && !self.check_instrument_quota(ctx, self.subscriptions.len() + added + self.patterns.len()) {
+            if previous.is_empty() {
+                if let Some(store) = &self.watchlist_store {
+                    store.do_send(UnsubscribeWatchlist {
+                        name: Some(name.to_string()),
+                        client_id: self.client_id.clone(),
+                    });
+                }
+            } else {
+                self.watchlists.insert(name.to_string(), previous);
             }
             return;
         }
 
-        // 更新本地订阅集合
-        for instrument in &instruments {
+        let removed: Vec<String> = previous
+            .difference(&current)
+            .filter(|instrument| !self.watchlists.values().any(|other| other.contains(*instrument)))
+            .cloned()
+            .collect();
+        for instrument in &removed {
             self.subscriptions.remove(instrument);
+            self.quote_state.remove(instrument);
+            self.pending_diff.remove(instrument);
+            self.described.remove(instrument);
+            self.expiries.remove(instrument);
         }
+        self.subscriptions.extend(current.iter().cloned());
 
-        // 获取当前所有订阅
-        let current_subscriptions: Vec<String> = self.subscriptions.iter().cloned().collect();
-
-        // 更新分发器的订阅
-        self.md_distributor.do_send(UpdateSubscription {
-            client_id: self.client_id.clone(),
-            instruments: current_subscriptions,
-        });
-
-        // 发送确认消息
-        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
-            message: format!("Unsubscribed from {} instruments", instruments.len()),
-        });
-        if let Ok(json) = serde_json::to_string(&msg) {
-            ctx.text(json);
+        let mut ins_list: Vec<String> = current.iter().cloned().collect();
+        ins_list.sort();
+        if following {
+            self.watchlists.insert(name.to_string(), current);
+        }
+        if added > 0 || !removed.is_empty() {
+            self.md_distributor.do_send(UpdateSubscription {
+                client_id: self.client_id.clone(),
+                instruments: self.subscriptions.iter().cloned().collect(),
+            });
         }
+        debug!(
+            "Client {} watchlist {} applied: {} added, {} removed",
+            self.client_id, name, added, removed.len()
+        );
+        self.save_profile();
+
+        let msg = WsServerMessage::Watchlist {
+            aid: aid.to_string(),
+            name: name.to_string(),
+            ins_list: ins_list.join(","),
+        };
+        self.send(ctx, &msg);
     }
 
     /// 处理获取订阅列表请求
@@ -322,60 +2958,230 @@ impl WsSession {
         let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::Subscriptions {
             instruments: subscriptions,
         });
-        if let Ok(json) = serde_json::to_string(&msg) {
-            ctx.text(json);
-        }
+        self.send(ctx, &msg);
+    }
+}
+
+/// 解析客户端请求：文本帧为JSON，二进制帧按会话协商的格式解码（JSON格式时为UTF-8编码的JSON）
+fn decode_request(format: WsFormat, frame: &ws::Message) -> Result<Value, GatewayError> {
+    let invalid = |e: &dyn std::fmt::Display| GatewayError::InvalidMessage(e.to_string());
+    match frame {
+        ws::Message::Text(text) => serde_json::from_str(text).map_err(|e| invalid(&e)),
+        ws::Message::Binary(bytes) => match format {
+            WsFormat::Json => serde_json::from_slice(bytes).map_err(|e| invalid(&e)),
+            WsFormat::MsgPack => rmp_serde::from_slice(bytes).map_err(|e| invalid(&e)),
+            WsFormat::Cbor => ciborium::from_reader(&bytes[..]).map_err(|e| invalid(&e)),
+        },
+        _ => Err(invalid(&"unsupported frame type")),
     }
 }
 
 /// 处理来自WebSocket的消息
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let _span = self.span.clone().entered();
         match msg {
             Ok(ws::Message::Ping(msg)) => {
                 self.heartbeat = Instant::now();
                 ctx.pong(&msg);
             }
-            Ok(ws::Message::Pong(_)) => {
+            Ok(ws::Message::Pong(msg)) => {
                 self.heartbeat = Instant::now();
+                self.handle_pong(&msg, ctx);
             }
-            Ok(ws::Message::Text(text)) => {
+            Ok(frame @ (ws::Message::Text(_) | ws::Message::Binary(_))) => {
                 self.heartbeat = Instant::now();
+                self.last_request = Instant::now();
+                if !self.check_message_rate(ctx) {
+                    return;
+                }
+                let request = match decode_request(self.format, &frame) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        error!("Failed to decode WebSocket message: {}", e);
+                        self.send_error(ctx, &e);
+                        return;
+                    }
+                };
+                
+                // 带channel字段的请求启用通道复用，按通道订阅和取消订阅
+                if let Ok(request) = ChannelRequest::deserialize(&request) {
+                    if self.handle_channel_request(ctx, request) {
+                        return;
+                    }
+                }
                 
                 // 尝试解析消息
-                match serde_json::from_str::<WsClientMessage>(&text) {
-                    Ok(WsClientMessage::TvSubscribeQuote { aid, ins_list }) if aid == "subscribe_quote" => {
-                        // TradingView格式的订阅
-                        let instruments = self.parse_tv_instruments(&ins_list);
-                        self.handle_subscribe(ctx, instruments);
+                match WsClientMessage::deserialize(&request) {
+                    Ok(WsClientMessage::SubscribeTransaction { aid, tx_id, ins_list, timeout_ms, atomic })
+                        if aid == "subscribe_transaction" =>
+                    {
+                        if self.check_subscribe_rate(ctx) {
+                            self.handle_subscribe_transaction(ctx, tx_id, &ins_list, timeout_ms, atomic);
+                        }
+                    }
+                    Ok(WsClientMessage::TvSubscribeQuote { aid, ins_list, fields, patch, naming, depth, ttl_sec }) if aid == "subscribe_quote" => {
+                        if !self.check_subscribe_rate(ctx) {
+                            return;
+                        }
+                        // TradingView/DIFF格式的订阅，ins_list为完整订阅列表
+                        if !self.handle_subscribe_quote(ctx, &ins_list, fields, patch, naming, depth, ttl_sec) {
+                            return;
+                        }
                         
                         // 发送订阅确认，返回订阅列表
                         let msg = WsServerMessage::PeekMessageResponse {
                             aid: "rsp_subscribe_quote".to_string(),
                             ins_list,
                         };
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            ctx.text(json);
+                        self.send(ctx, &msg);
+                    }
+                    Ok(WsClientMessage::SubscribeIndicator { ins_list, .. }) => {
+                        if !self.check_subscribe_rate(ctx) || !self.handle_subscribe_indicator(ctx, &ins_list) {
+                            return;
+                        }
+                        let msg = WsServerMessage::SubscribeResponse {
+                            aid: "rsp_subscribe_indicator".to_string(),
+                            channel: Channel::Kline,
+                            ins_list,
+                        };
+                        self.send(ctx, &msg);
+                    }
+                    Ok(WsClientMessage::TvSubscribeQuote { aid, ins_list, .. }) if aid == "subscribe_kline" => {
+                        if !self.check_subscribe_rate(ctx) || !self.handle_subscribe_kline(ctx, &ins_list) {
+                            return;
                         }
+                        let msg = WsServerMessage::SubscribeResponse {
+                            aid: "rsp_subscribe_kline".to_string(),
+                            channel: Channel::Kline,
+                            ins_list,
+                        };
+                        self.send(ctx, &msg);
                     }
-                    Ok(WsClientMessage::PeekMessage { aid }) if aid == "peek_message" => {
-                        // 查询当前订阅列表并返回TradingView格式
-                        let subscriptions: Vec<String> = self.subscriptions.iter().cloned().collect();
-                        let ins_list = subscriptions.join(",");
-                        
-                        let msg = WsServerMessage::PeekMessageResponse {
-                            aid: "rsp_peek_message".to_string(),
+                    Ok(WsClientMessage::DepthRequest { aid: DepthAid::SubscribeDepth, ins_list }) => {
+                        if !self.check_subscribe_rate(ctx) || !self.handle_subscribe_depth(ctx, &ins_list) {
+                            return;
+                        }
+                        let msg = WsServerMessage::SubscribeResponse {
+                            aid: "rsp_subscribe_depth".to_string(),
+                            channel: Channel::Quote,
+                            ins_list,
+                        };
+                        self.send(ctx, &msg);
+                    }
+                    Ok(WsClientMessage::DepthRequest { aid: DepthAid::ResyncDepth, ins_list }) => {
+                        self.handle_resync_depth(&ins_list);
+                    }
+                    Ok(WsClientMessage::ResendRequest { instrument: Some(instrument), seq, .. }) => {
+                        self.handle_resend_from(&instrument, seq);
+                    }
+                    Ok(WsClientMessage::ResendRequest { ins_list, .. }) => {
+                        self.handle_resend(&ins_list);
+                    }
+                    Ok(WsClientMessage::SubscribeQuality { ins_list, .. }) => {
+                        if !self.check_subscribe_rate(ctx) || !self.handle_subscribe_quality(ctx, &ins_list) {
+                            return;
+                        }
+                        let msg = WsServerMessage::SubscribeResponse {
+                            aid: "rsp_subscribe_quality".to_string(),
+                            channel: Channel::Status,
+                            ins_list,
+                        };
+                        self.send(ctx, &msg);
+                    }
+                    Ok(WsClientMessage::SubscribePhase { ins_list, .. }) => {
+                        if !self.check_subscribe_rate(ctx) || !self.handle_subscribe_phase(ctx, &ins_list) {
+                            return;
+                        }
+                        let msg = WsServerMessage::SubscribeResponse {
+                            aid: "rsp_subscribe_phase".to_string(),
+                            channel: Channel::Status,
                             ins_list,
                         };
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            ctx.text(json);
+                        self.send(ctx, &msg);
+                    }
+                    Ok(WsClientMessage::SetThrottle { aid, interval_ms }) if aid == "set_throttle" => {
+                        let interval_ms = self.apply_throttle(interval_ms);
+                        self.save_profile();
+                        
+                        let msg = WsServerMessage::ThrottleResponse {
+                            aid: "rsp_set_throttle".to_string(),
+                            interval_ms,
+                        };
+                        self.send(ctx, &msg);
+                    }
+                    Ok(WsClientMessage::SetBatchWindow { aid, window_ms }) if aid == "set_batch_window" => {
+                        self.handle_batch_window(ctx, window_ms);
+                    }
+                    Ok(WsClientMessage::SetQueuePolicy { aid, policy }) if aid == "set_queue_policy" => {
+                        self.queue_policy = policy;
+                        info!("Client {} send queue policy set to {:?}", self.client_id, policy);
+                        self.save_profile();
+                        self.send_queue_status(ctx);
+                    }
+                    Ok(WsClientMessage::SetFormat { aid, format }) if aid == "set_format" => {
+                        match WsFormat::from_name(&format) {
+                            Some(format) => {
+                                // 响应使用切换后的格式，客户端据此确认切换生效
+                                self.format = format;
+                                let msg = WsServerMessage::FormatResponse {
+                                    aid: "rsp_set_format".to_string(),
+                                    format,
+                                };
+                                self.send(ctx, &msg);
+                            }
+                            None => self.send_error(ctx, &GatewayError::UnsupportedFormat(format)),
+                        }
+                    }
+                    Ok(WsClientMessage::SetOutput { aid, output }) if aid == "set_output" => {
+                        match MessageOutput::from_name(&output) {
+                            Some(output) => {
+                                self.output = output;
+                                info!("Client {} message output set to {:?}", self.client_id, output);
+                                let msg = WsServerMessage::OutputResponse {
+                                    aid: "rsp_set_output".to_string(),
+                                    output,
+                                };
+                                self.send(ctx, &msg);
+                            }
+                            None => self.send_error(ctx, &GatewayError::UnsupportedFormat(output)),
+                        }
+                    }
+                    Ok(WsClientMessage::SetAlert { aid, rule }) if aid == "set_alert" => {
+                        self.handle_set_alert(ctx, rule);
+                    }
+                    Ok(WsClientMessage::RemoveAlert { aid, alert_id }) if aid == "remove_alert" => {
+                        self.alerts.do_send(RemoveAlert {
+                            client_id: self.client_id.clone(),
+                            alert_id: Some(alert_id.clone()),
+                        });
+                        let msg = WsServerMessage::AlertResponse {
+                            aid: "rsp_remove_alert".to_string(),
+                            alert_id,
+                        };
+                        self.send(ctx, &msg);
+                    }
+                    Ok(WsClientMessage::WatchlistRequest { aid, name }) if aid == "subscribe_watchlist" => {
+                        if self.check_subscribe_rate(ctx) {
+                            self.handle_subscribe_watchlist(ctx, name);
                         }
                     }
+                    Ok(WsClientMessage::WatchlistRequest { aid, name }) if aid == "unsubscribe_watchlist" => {
+                        self.handle_unsubscribe_watchlist(ctx, name);
+                    }
+                    Ok(WsClientMessage::PeekMessage { aid }) if aid == "peek_message" => {
+                        // DIFF协议：返回自上次peek以来变化的字段
+                        self.diff_mode = true;
+                        self.flush_diff(ctx);
+                    }
                     Ok(WsClientMessage::LegacyMessage(client_msg)) => {
                         match client_msg {
-                            LegacyClientMessage::Subscribe { instruments } => {
+                            LegacyClientMessage::Subscribe { instruments, naming, depth } => {
                                 // 处理传统格式的订阅
-                                self.handle_subscribe(ctx, instruments);
+                                if self.check_subscribe_rate(ctx) {
+                                    self.handle_naming(naming);
+                                    self.handle_subscribe(ctx, instruments, depth);
+                                }
                             }
                             LegacyClientMessage::Unsubscribe { instruments } => {
                                 // 处理传统格式的取消订阅
@@ -385,49 +3191,28 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
                                 // 处理获取订阅列表请求
                                 self.handle_get_subscriptions(ctx);
                             }
-                            LegacyClientMessage::Auth { token: _ } => {
-                                // 目前不处理认证
-                                let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::System {
-                                    message: "Authentication not implemented".to_string(),
-                                });
-                                if let Ok(json) = serde_json::to_string(&msg) {
-                                    ctx.text(json);
-                                }
+                            LegacyClientMessage::Auth { token } => {
+                                self.handle_auth(ctx, token);
                             }
                             LegacyClientMessage::Ping => {
                                 // 响应ping
                                 let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::Pong);
-                                if let Ok(json) = serde_json::to_string(&msg) {
-                                    ctx.text(json);
-                                }
+                                self.send(ctx, &msg);
                             }
                         }
                     }
                     Err(e) => {
                         // 消息解析错误
                         error!("Failed to parse WebSocket message: {}", e);
-                        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::Error {
-                            message: format!("Invalid message format: {}", e),
-                        });
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            ctx.text(json);
-                        }
+                        self.send_error(ctx, &GatewayError::InvalidMessage(e.to_string()));
                     }
                     _ => {
                         // 未知消息类型
-                        warn!("Unknown WebSocket message type: {}", text);
-                        let msg = WsServerMessage::LegacyMessage(LegacyServerMessage::Error {
-                            message: "Unknown message type".to_string(),
-                        });
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            ctx.text(json);
-                        }
+                        warn!("Unknown WebSocket message type: {}", request);
+                        self.send_error(ctx, &GatewayError::UnknownMessage);
                     }
                 }
             }
-            Ok(ws::Message::Binary(_)) => {
-                warn!("Binary WebSocket messages are not supported");
-            }
             Ok(ws::Message::Close(reason)) => {
                 info!("WebSocket connection closed: {:?}", reason);
                 ctx.close(reason);
@@ -445,57 +3230,314 @@ impl Handler<MarketDataUpdateMessage> for WsSession {
     type Result = ();
 
     fn handle(&mut self, msg: MarketDataUpdateMessage, ctx: &mut Self::Context) {
+        let mut revoked = Vec::new();
         // 遍历收到的合约数据
         for instrument in &msg.instruments {
-            // 检查该客户端是否订阅了该合约
-            if self.subscriptions.contains(instrument) {
-                if let Some(data_json) = msg.data.get(instrument) {
-                    // 将JSON字符串解析为Value对象
-                    if let Ok(data_value) = serde_json::from_str::<Value>(data_json) {
-                        // 创建TradingView格式的响应
-                        let mut quotes = HashMap::new();
-                        
-                        // 从data_value提取字段创建TvQuote
-                        // 注意：这里的数据可能是增量的，只包含变化的字段
-                        if let Some(instrument_id) = data_value.get("instrument_id").and_then(|v| v.as_str()) {
-                            // 只处理拥有instrument_id字段的数据
-                            quotes.insert(instrument_id.to_string(), data_value.clone());
-                            
-                            // 创建TradingView格式的市场数据响应
-                            let tv_market_data = json!({
-                                "aid": "rtn_data",
-                                "data": [
-                                    {
-                                        "quotes": quotes
-                                    }
-                                ]
-                            });
-                            
-                            // 将响应发送给客户端
-                            if let Ok(json_str) = serde_json::to_string(&tv_market_data) {
-                                ctx.text(json_str);
-                                debug!("Sent market data update for {} to client {}", instrument, self.client_id);
-                            } else {
-                                error!("Failed to serialize market data for {}", instrument);
-                            }
-                        } else {
-                            error!("Market data missing instrument_id field: {}", data_json);
-                        }
-                    } else {
-                        error!("Failed to parse market data JSON for {}: {}", instrument, data_json);
-                    }
+            // 检查该客户端是否订阅了该合约（或经通配符模式新加入的合约）
+            if !self.accepts_instrument(instrument) {
+                continue;
+            }
+            // 订阅后令牌的权限可能已被修改
+            if !self.permits(instrument) {
+                revoked.push(instrument.clone());
+                continue;
+            }
+            let Some(quote) = msg.data.get(instrument) else {
+                continue;
+            };
+            
+            // 注意：这里的数据可能是增量的，只包含变化的字段
+            let Some(instrument_id) = quote.fields().get("instrument_id").and_then(|v| v.as_str()).map(str::to_string) else {
+                error!("Market data for {} missing instrument_id field", instrument);
+                continue;
+            };
+            
+            // 不需要改写的增量直接放入发送队列，发送时使用分发器缓存的编码
+            if !self.diff_mode && self.described.contains(instrument) {
+                self.enqueue(ctx, instrument_id, QueuedQuote::Shared(quote.clone()));
+                continue;
+            }
+            
+            // 首次推送时附带合约基础信息
+            let mut data_value = quote.fields().clone();
+            if !self.described.contains(instrument) {
+                self.describe(instrument, &mut data_value);
+                self.described.insert(instrument.clone());
+            }
+            
+            if self.diff_mode {
+                // DIFF协议：累积变化，等待peek_message（延迟追踪不计入客户端状态）
+                let trace = LatencyTrace::take_from(&mut data_value);
+                self.merge_quote_diff(&instrument_id, data_value);
+                if let (Some(trace), Some(pending)) = (trace, self.pending_diff.get_mut(&instrument_id)) {
+                    pending.insert(LATENCY_FIELD.to_string(), trace.to_value());
                 }
+                continue;
+            }
+            
+            // 放入发送队列，由定时任务批量发送
+            self.enqueue(ctx, instrument_id, QueuedQuote::Owned(data_value));
+        }
+        self.revoke(ctx, revoked);
+        
+        // 有挂起的peek时立即返回新的增量
+        if self.diff_mode && self.peek_pending {
+            self.flush_diff(ctx);
+        }
+    }
+}
+
+/// 处理补丁模式的行情帧
+impl Handler<QuotePatchUpdate> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: QuotePatchUpdate, ctx: &mut Self::Context) {
+        let mut frames = Vec::with_capacity(msg.frames.len());
+        let mut revoked = Vec::new();
+        for mut frame in msg.frames {
+            let instrument = frame.instrument_id().to_string();
+            if !self.accepts_instrument(&instrument) {
+                continue;
+            }
+            if !self.permits(&instrument) {
+                revoked.push(instrument);
+                continue;
             }
+            // 全量帧替换客户端的合约状态，每次都附带合约基础信息
+            if let QuoteFrame::Full { quote, .. } = &mut frame {
+                self.describe(&instrument, quote);
+            }
+            frame.set_seq(self.next_seq(&instrument));
+            frames.push(frame);
+        }
+        self.revoke(ctx, revoked);
+        if frames.is_empty() {
+            return;
+        }
+        if !self.batch_window.is_zero() {
+            self.pending_frames.extend(frames);
+            self.schedule_batch(ctx);
+            return;
+        }
+        
+        let msg = WsServerMessage::QuotePatch {
+            aid: "rtn_patch".to_string(),
+            data: frames,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 处理分发器发出的数据源切换通知
+impl Handler<SourceChanged> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: SourceChanged, ctx: &mut Self::Context) {
+        if !self.subscriptions.contains(&msg.instrument) {
+            return;
+        }
+        
+        let msg = WsServerMessage::SourceChange {
+            aid: "rtn_source_change".to_string(),
+            instrument_id: msg.instrument,
+            from: msg.from,
+            to: msg.to,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 处理分发器转发的主力合约切换通知
+impl Handler<ContractRemapped> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: ContractRemapped, ctx: &mut Self::Context) {
+        if !self.subscriptions.contains(&msg.instrument) {
+            return;
+        }
+        
+        let msg = WsServerMessage::Remap {
+            aid: "rtn_remap".to_string(),
+            instrument_id: msg.instrument,
+            from: msg.from,
+            to: msg.to,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+impl Handler<AlertTriggered> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: AlertTriggered, ctx: &mut Self::Context) {
+        let msg = WsServerMessage::Alert {
+            aid: "rtn_alert".to_string(),
+            alert_id: msg.alert_id,
+            instrument_id: msg.instrument_id,
+            condition: msg.condition,
+            value: msg.value,
+            datetime: msg.datetime,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 推送衍生指标
+impl Handler<IndicatorUpdate> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: IndicatorUpdate, ctx: &mut Self::Context) {
+        let msg = WsServerMessage::Indicator {
+            aid: "rtn_indicator".to_string(),
+            data: msg.0,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 推送K线
+impl Handler<KlineUpdate> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: KlineUpdate, ctx: &mut Self::Context) {
+        let msg = WsServerMessage::Kline {
+            aid: "rtn_kline".to_string(),
+            data: msg.0,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 推送盘口帧
+impl Handler<DepthUpdate> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: DepthUpdate, ctx: &mut Self::Context) {
+        // 订阅后令牌的权限可能已被修改
+        let frames: Vec<DepthFrame> = msg
+            .frames
+            .into_iter()
+            .filter(|frame| self.permits(frame.instrument_id()))
+            .collect();
+        if frames.is_empty() {
+            return;
+        }
+        let msg = WsServerMessage::Depth {
+            aid: "rtn_depth".to_string(),
+            data: frames,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 推送行情质量事件
+impl Handler<QualityEvent> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: QualityEvent, ctx: &mut Self::Context) {
+        let msg = WsServerMessage::Quality {
+            aid: "rtn_quality".to_string(),
+            data: msg,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 推送交易阶段变化
+impl Handler<PhaseChanged> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: PhaseChanged, ctx: &mut Self::Context) {
+        let msg = WsServerMessage::Phase {
+            aid: "rtn_phase".to_string(),
+            data: msg,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 交易日结束时推送已订阅合约的最终行情
+impl Handler<SessionEnd> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: SessionEnd, ctx: &mut Self::Context) {
+        let quotes = msg
+            .snapshots
+            .iter()
+            .filter(|snapshot| self.subscriptions.contains(&snapshot.instrument_id) && self.permits(&snapshot.instrument_id))
+            .cloned()
+            .collect();
+        let msg = WsServerMessage::SessionEnd {
+            aid: "rtn_session_end".to_string(),
+            data: msg,
+            quotes,
+        };
+        self.send(ctx, &msg);
+    }
+}
+
+/// 同步订阅的自选列表的修改
+impl Handler<WatchlistChanged> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: WatchlistChanged, ctx: &mut Self::Context) {
+        if self.watchlists.contains_key(&msg.name) {
+            self.apply_watchlist(ctx, &msg.name, msg.instruments, "rtn_watchlist");
         }
     }
 }
 
+/// 管理接口强制断开客户端
+impl Handler<ForceDisconnect> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: ForceDisconnect, ctx: &mut Self::Context) {
+        let _span = self.span.clone().entered();
+        info!("Client {} disconnected by admin: {}", self.client_id, msg.reason);
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some(msg.reason),
+        }));
+        ctx.stop();
+    }
+}
+
 /// 创建WebSocket处理器
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_handler(
     req: HttpRequest,
     stream: web::Payload,
     md_distributor: web::Data<actix::Addr<MarketDataDistributor>>,
+    alerts: web::Data<actix::Addr<AlertEngine>>,
+    instruments: web::Data<InstrumentRegistry>,
+    queue_config: web::Data<RwLock<SendQueueConfig>>,
+    quota: web::Data<RwLock<ClientQuotaConfig>>,
+    heartbeat: web::Data<RwLock<HeartbeatConfig>>,
+    quota_monitor: web::Data<actix::Addr<QuotaMonitor>>,
+    indicators: web::Data<actix::Addr<IndicatorEngine>>,
+    depth: web::Data<actix::Addr<DepthEngine>>,
+    quality: web::Data<actix::Addr<DataQualityActor>>,
+    phases: web::Data<actix::Addr<TradingPhaseActor>>,
+    watchlists: web::Data<actix::Addr<WatchlistStore>>,
+    profiles: web::Data<actix::Addr<ProfileStore>>,
+    acl: Option<web::Data<AclStore>>,
 ) -> Result<HttpResponse, Error> {
+    // 开启访问控制时，携带的令牌必须已登记
+    let acl = acl.map(|acl| (acl.into_inner(), request_token(&req)));
+    if let Some((acl, Some(token))) = &acl {
+        if !acl.contains(token) {
+            warn!(
+                "Rejected connection with an unknown token from {}",
+                req.connection_info().realip_remote_addr().unwrap_or("unknown")
+            );
+            let error = GatewayError::AuthError("unknown token".to_string());
+            return Ok(HttpResponse::Unauthorized().json(json!({
+                "code": error.code(),
+                "error": error.to_string(),
+            })));
+        }
+    }
+    
     // 获取查询参数
     let query = req.query_string();
     let source_type = if query.contains("source=qq") {
@@ -506,10 +3548,308 @@ pub async fn ws_handler(
         MarketDataSource::CTP
     };
     
+    // 获取编码格式（默认JSON）和要恢复的会话ID
+    let params = web::Query::<HashMap<String, String>>::from_query(query)
+        .map(|params| params.into_inner())
+        .unwrap_or_default();
+    let format = params
+        .get("format")
+        .and_then(|name| WsFormat::from_name(name))
+        .unwrap_or(WsFormat::Json);
+    let naming = params
+        .get("naming")
+        .and_then(|name| FieldNaming::from_name(name))
+        .unwrap_or_default();
+    // 重新加载配置后新连接使用新的发送队列设置
+    let mut queue_config = queue_config.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(output) = params.get("output").and_then(|name| MessageOutput::from_name(name)) {
+        queue_config.output = output;
+    }
+    if let Some(window_ms) = params.get("batch_window_ms").and_then(|window| window.parse().ok()) {
+        queue_config.batch_window_ms = window_ms;
+    }
+    let resume_session = params
+        .get("session_id")
+        .filter(|session_id| Uuid::parse_str(session_id).is_ok())
+        .cloned();
+    let client_name = params.get("client_name").cloned();
+    if let Some(name) = client_name.as_deref().filter(|name| !is_valid_client_name(name)) {
+        let error = GatewayError::InvalidMessage(format!(
+            "client_name {:?} must be 1-64 letters, digits, '_', '-' or '.'",
+            name
+        ));
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "code": error.code(),
+            "error": error.to_string(),
+        })));
+    }
+    
     // 创建WebSocket会话
-    let session = WsSession::new(md_distributor.get_ref().clone(), source_type);
+    let mut session = WsSession::new(
+        md_distributor.get_ref().clone(),
+        alerts.get_ref().clone(),
+        source_type,
+        format,
+        instruments.into_inner(),
+        queue_config,
+        resume_session,
+    )
+    .with_quota(
+        quota.read().unwrap_or_else(|e| e.into_inner()).clone(),
+        quota_monitor.get_ref().clone(),
+        req.connection_info().realip_remote_addr().map(str::to_string),
+    )
+    .with_naming(naming)
+    .with_heartbeat(heartbeat.read().unwrap_or_else(|e| e.into_inner()).for_route(req.path()))
+    .with_indicators(indicators.get_ref().clone())
+    .with_depth(depth.get_ref().clone())
+    .with_quality(quality.get_ref().clone())
+    .with_phases(phases.get_ref().clone())
+    .with_watchlists(watchlists.get_ref().clone());
+    if let Some((acl, token)) = acl {
+        session = session.with_acl(acl, token);
+    }
+    if let Some(client_name) = client_name {
+        session = session.with_profile(profiles.get_ref().clone(), client_name);
+    }
     
     // 启动WebSocket连接
     let resp = ws::start(session, &req, stream)?;
     Ok(resp)
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(fields: Value) -> QueuedQuote {
+        QueuedQuote::Shared(Arc::new(EncodedQuote::new(fields)))
+    }
+
+    fn fields(queue: &mut SendQueue) -> Vec<(String, serde_json::Map<String, Value>)> {
+        queue
+            .take(usize::MAX)
+            .into_iter()
+            .map(|(instrument, quote)| (instrument, quote.into_fields()))
+            .collect()
+    }
+
+    #[test]
+    fn drop_oldest_merges_into_newer_update_of_same_instrument() {
+        let mut queue = SendQueue::default();
+        let policy = QueuePolicy::DropOldest;
+        queue.push("A".to_string(), quote(json!({"last_price": 1.0, "volume": 1})), 2, policy).unwrap();
+        queue.push("A".to_string(), quote(json!({"last_price": 2.0})), 2, policy).unwrap();
+        // 队首的A并入后面的A，volume没有丢失
+        assert_eq!(queue.push("B".to_string(), quote(json!({"last_price": 3.0})), 2, policy).unwrap(), None);
+        let queued = fields(&mut queue);
+        assert_eq!(queued[0].0, "A");
+        assert_eq!(Value::Object(queued[0].1.clone()), json!({"last_price": 2.0, "volume": 1}));
+        assert_eq!(queued[1].0, "B");
+        assert!(queue.take_resync().is_empty());
+    }
+
+    #[test]
+    fn drop_oldest_marks_lost_instrument_for_resync() {
+        let mut queue = SendQueue::default();
+        let policy = QueuePolicy::DropOldest;
+        queue.push("A".to_string(), quote(json!({"volume": 1})), 1, policy).unwrap();
+        assert_eq!(
+            queue.push("B".to_string(), quote(json!({"volume": 2})), 1, policy).unwrap(),
+            Some("A".to_string())
+        );
+        assert_eq!(queue.take_resync(), vec!["A".to_string()]);
+        assert_eq!(fields(&mut queue)[0].0, "B");
+    }
+
+    #[test]
+    fn conflate_merges_update_of_queued_instrument() {
+        let mut queue = SendQueue::default();
+        let policy = QueuePolicy::Conflate;
+        queue.push("A".to_string(), quote(json!({"last_price": 1.0, "volume": 1})), 2, policy).unwrap();
+        queue.push("B".to_string(), quote(json!({"last_price": 5.0})), 2, policy).unwrap();
+        assert_eq!(queue.push("A".to_string(), quote(json!({"last_price": 2.0})), 2, policy).unwrap(), None);
+        let queued = fields(&mut queue);
+        assert_eq!(queued.len(), 2);
+        assert_eq!(Value::Object(queued[0].1.clone()), json!({"last_price": 2.0, "volume": 1}));
+        assert!(queue.take_resync().is_empty());
+    }
+
+    #[test]
+    fn conflate_drops_oldest_other_instrument_for_resync() {
+        let mut queue = SendQueue::default();
+        let policy = QueuePolicy::Conflate;
+        queue.push("A".to_string(), quote(json!({"volume": 1})), 2, policy).unwrap();
+        queue.push("B".to_string(), quote(json!({"volume": 2})), 2, policy).unwrap();
+        // 新合约C挤掉队首的A，A的全量行情稍后重发
+        assert_eq!(
+            queue.push("C".to_string(), quote(json!({"volume": 3})), 2, policy).unwrap(),
+            Some("A".to_string())
+        );
+        assert_eq!(queue.take_resync(), vec!["A".to_string()]);
+        let instruments: Vec<String> = fields(&mut queue).into_iter().map(|(instrument, _)| instrument).collect();
+        assert_eq!(instruments, vec!["B".to_string(), "C".to_string()]);
+    }
+
+    #[test]
+    fn disconnect_rejects_when_full() {
+        let mut queue = SendQueue::default();
+        let policy = QueuePolicy::Disconnect;
+        queue.push("A".to_string(), quote(json!({"volume": 1})), 1, policy).unwrap();
+        assert!(queue.push("B".to_string(), quote(json!({"volume": 2})), 1, policy).is_err());
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn subscribe_indicator_parses_into_own_message() {
+        let msg: WsClientMessage =
+            serde_json::from_str(r#"{"aid":"subscribe_indicator","ins_list":"SHFE.rb2410.ma5"}"#).unwrap();
+        assert!(matches!(msg, WsClientMessage::SubscribeIndicator { ins_list, .. } if ins_list == "SHFE.rb2410.ma5"));
+        let msg: WsClientMessage =
+            serde_json::from_str(r#"{"aid":"subscribe_quote","ins_list":"SHFE.rb2410"}"#).unwrap();
+        assert!(matches!(msg, WsClientMessage::TvSubscribeQuote { .. }));
+    }
+
+    #[test]
+    fn depth_requests_parse_into_own_message() {
+        let msg: WsClientMessage =
+            serde_json::from_str(r#"{"aid":"subscribe_depth","ins_list":"SHFE.rb2410"}"#).unwrap();
+        assert!(matches!(msg, WsClientMessage::DepthRequest { aid: DepthAid::SubscribeDepth, .. }));
+        let msg: WsClientMessage = serde_json::from_str(r#"{"aid":"resync_depth","ins_list":""}"#).unwrap();
+        assert!(matches!(msg, WsClientMessage::DepthRequest { aid: DepthAid::ResyncDepth, .. }));
+    }
+
+    #[test]
+    fn resend_request_carries_instrument_and_seq() {
+        let msg: WsClientMessage =
+            serde_json::from_str(r#"{"aid":"req_resend","instrument":"SHFE.rb2410","seq":41}"#).unwrap();
+        assert!(matches!(
+            msg,
+            WsClientMessage::ResendRequest { instrument: Some(instrument), seq: Some(41), .. } if instrument == "SHFE.rb2410"
+        ));
+        let msg: WsClientMessage = serde_json::from_str(r#"{"aid":"req_resend","ins_list":""}"#).unwrap();
+        assert!(matches!(msg, WsClientMessage::ResendRequest { instrument: None, seq: None, .. }));
+    }
+
+    #[test]
+    fn subscribe_quality_parses_into_own_message() {
+        let msg: WsClientMessage = serde_json::from_str(r#"{"aid":"subscribe_quality","ins_list":"SHFE.*"}"#).unwrap();
+        assert!(matches!(msg, WsClientMessage::SubscribeQuality { ins_list, .. } if ins_list == "SHFE.*"));
+    }
+
+    #[test]
+    fn subscribe_phase_parses_into_own_message() {
+        let msg: WsClientMessage = serde_json::from_str(r#"{"aid":"subscribe_phase","ins_list":"SHFE"}"#).unwrap();
+        assert!(matches!(msg, WsClientMessage::SubscribePhase { ins_list, .. } if ins_list == "SHFE"));
+    }
+
+    #[test]
+    fn subscribe_response_routes_by_channel_without_sending_it() {
+        let msg = WsServerMessage::SubscribeResponse {
+            aid: "rsp_subscribe_phase".to_string(),
+            channel: Channel::Status,
+            ins_list: "SHFE".to_string(),
+        };
+        assert_eq!(msg.channel(), Channel::Status);
+        assert_eq!(serde_json::to_value(&msg).unwrap(), json!({"aid": "rsp_subscribe_phase", "ins_list": "SHFE"}));
+        let routed = Routed { channel: msg.channel(), message: &msg };
+        assert_eq!(serde_json::to_value(&routed).unwrap()["channel"], "status");
+    }
+
+    fn slow_config() -> SlowConsumerConfig {
+        SlowConsumerConfig {
+            max_drain_ms: 500,
+            queue_pct: 50,
+            slow_secs: 2,
+            recover_secs: 2,
+            ..SlowConsumerConfig::default()
+        }
+    }
+
+    #[test]
+    fn unanswered_drain_probe_downgrades_after_slow_secs() {
+        let config = slow_config();
+        let mut tracker = SlowConsumerTracker::default();
+        let start = Instant::now();
+        let rtt = Duration::from_millis(20);
+        assert!(tracker.start_probe(start));
+        // 探测未回复时不重复发送
+        assert!(!tracker.start_probe(start + Duration::from_millis(100)));
+
+        assert!(tracker.evaluate(start + Duration::from_millis(300), rtt, 0, 100, 0, &config).is_none());
+        assert!(tracker.evaluate(start + Duration::from_secs(1), rtt, 0, 100, 0, &config).is_none());
+        let change = tracker.evaluate(start + Duration::from_secs(2), rtt, 0, 100, 0, &config);
+        assert!(matches!(change, Some(SlowConsumerChange::Downgrade(reason)) if reason.contains("drains")));
+    }
+
+    #[test]
+    fn network_round_trip_is_not_drain_lag() {
+        let config = slow_config();
+        let mut tracker = SlowConsumerTracker::default();
+        let start = Instant::now();
+        for second in 0..5 {
+            let now = start + Duration::from_secs(second);
+            assert!(tracker.start_probe(now));
+            // 往返时间高但与心跳往返时间相当，连接没有积压
+            tracker.probe_answered(now + Duration::from_millis(700));
+            let change = tracker.evaluate(now + Duration::from_millis(800), Duration::from_millis(650), 0, 100, 0, &config);
+            assert!(change.is_none());
+        }
+    }
+
+    #[test]
+    fn growing_queue_or_drops_count_as_slow() {
+        let config = slow_config();
+        let now = Instant::now();
+        let mut tracker = SlowConsumerTracker::default();
+        assert!(tracker.evaluate(now, Duration::ZERO, 60, 100, 0, &config).is_none());
+        assert!(tracker.evaluate(now, Duration::ZERO, 70, 100, 0, &config).is_some());
+
+        let mut tracker = SlowConsumerTracker::default();
+        assert!(tracker.evaluate(now, Duration::ZERO, 0, 100, 3, &config).is_none());
+        assert!(tracker.evaluate(now, Duration::ZERO, 0, 100, 5, &config).is_some());
+        // 队列在缩小、没有新的丢弃时不算慢
+        let mut tracker = SlowConsumerTracker::default();
+        assert!(tracker.evaluate(now, Duration::ZERO, 90, 100, 0, &config).is_none());
+        assert!(tracker.evaluate(now, Duration::ZERO, 80, 100, 0, &config).is_none());
+        assert!(tracker.evaluate(now, Duration::ZERO, 70, 100, 0, &config).is_none());
+    }
+
+    #[test]
+    fn downgraded_client_restores_after_idle_secs() {
+        let config = slow_config();
+        let now = Instant::now();
+        let mut tracker = SlowConsumerTracker::default();
+        tracker.evaluate(now, Duration::ZERO, 0, 100, 1, &config);
+        assert!(matches!(
+            tracker.evaluate(now, Duration::ZERO, 0, 100, 2, &config),
+            Some(SlowConsumerChange::Downgrade(_))
+        ));
+        // 队列不空时不算空闲
+        assert!(tracker.evaluate(now, Duration::ZERO, 1, 100, 2, &config).is_none());
+        assert!(tracker.evaluate(now, Duration::ZERO, 0, 100, 2, &config).is_none());
+        assert!(matches!(
+            tracker.evaluate(now, Duration::ZERO, 0, 100, 2, &config),
+            Some(SlowConsumerChange::Restore)
+        ));
+    }
+
+    #[test]
+    fn binary_requests_decode_in_negotiated_format() {
+        let request = json!({"aid": "subscribe_quote", "ins_list": "SHFE.rb2410"});
+        let msgpack = ws::Message::Binary(rmp_serde::to_vec_named(&request).unwrap().into());
+        assert_eq!(decode_request(WsFormat::MsgPack, &msgpack).unwrap(), request);
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&request, &mut cbor).unwrap();
+        assert_eq!(decode_request(WsFormat::Cbor, &ws::Message::Binary(cbor.into())).unwrap(), request);
+        let json = ws::Message::Binary(request.to_string().into_bytes().into());
+        assert_eq!(decode_request(WsFormat::Json, &json).unwrap(), request);
+        // 文本帧总是JSON
+        let text = ws::Message::Text(request.to_string().into());
+        assert_eq!(decode_request(WsFormat::MsgPack, &text).unwrap(), request);
+        assert!(decode_request(WsFormat::Cbor, &msgpack).is_err());
+
+        let decoded = decode_request(WsFormat::MsgPack, &msgpack).unwrap();
+        assert!(matches!(WsClientMessage::deserialize(&decoded), Ok(WsClientMessage::TvSubscribeQuote { .. })));
+    }
+}
@@ -0,0 +1,297 @@
+//! End-to-end tests against the gateway binary fed by the mock market data source
+//!
+//! Each test starts `qamdgateway` on a free port with `mock.enabled`, connects
+//! WebSocket clients and checks what they receive.
+
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use qamdgateway::actors::mock_actor::MockTickGenerator;
+use qamdgateway::config::MockConfig;
+use serde_json::{json, Value};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream as AsyncTcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<AsyncTcpStream>>;
+
+const REBAR: &str = "SHFE.rb2410";
+const HOT_COIL: &str = "SHFE.hc2410";
+
+/// Gateway process, killed when dropped
+struct TestGateway {
+    child: Child,
+    port: u16,
+}
+
+impl TestGateway {
+    fn start(mock: Value) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("no free port")
+            .port();
+        let config = json!({
+            "brokers": {
+                "mock": { "name": "mock", "front_addr": "tcp://127.0.0.1:1" }
+            },
+            "default_broker": "mock",
+            "websocket": { "host": "127.0.0.1", "port": port, "path": "/ws/market" },
+            "rest_api": {
+                "host": "127.0.0.1",
+                "port": port,
+                "cors": { "allow_all": true, "allowed_origins": [], "allow_credentials": true },
+                "listeners": [{ "address": format!("127.0.0.1:{}", port) }]
+            },
+            "mock": mock,
+        });
+        let child = Command::new(env!("CARGO_BIN_EXE_qamdgateway"))
+            .env("QAMDGATEWAY_CONFIG", config.to_string())
+            .env("RUST_LOG", "warn")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to start qamdgateway");
+        let gateway = Self { child, port };
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(Instant::now() < deadline, "gateway did not start listening");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        gateway
+    }
+
+    async fn connect(&self) -> Client {
+        let url = format!("ws://127.0.0.1:{}/ws/market", self.port);
+        let (client, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .expect("WebSocket handshake failed");
+        client
+    }
+}
+
+impl Drop for TestGateway {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn mock_config(instruments: &[&str], ticks_per_sec: f64) -> Value {
+    json!({
+        "enabled": true,
+        "instruments": instruments,
+        "ticks_per_sec": ticks_per_sec,
+    })
+}
+
+/// A quote received by a client
+struct Received {
+    at: DateTime<Utc>,
+    instrument: String,
+    quote: Value,
+}
+
+async fn send(client: &mut Client, message: Value) {
+    client
+        .send(Message::Text(message.to_string()))
+        .await
+        .expect("failed to send request");
+}
+
+async fn subscribe(client: &mut Client, instruments: &[&str]) {
+    send(client, json!({ "type": "subscribe", "payload": { "instruments": instruments } })).await;
+}
+
+/// Collect the quotes of every `rtn_data` frame received during `window`
+async fn collect(client: &mut Client, window: Duration) -> Vec<Received> {
+    let mut received = Vec::new();
+    let deadline = tokio::time::Instant::now() + window;
+    while let Ok(Some(message)) = tokio::time::timeout_at(deadline, client.next()).await {
+        let Ok(Message::Text(text)) = message else {
+            continue;
+        };
+        let at = Utc::now();
+        let frame: Value = serde_json::from_str(&text).expect("invalid JSON frame");
+        if frame["aid"] != "rtn_data" {
+            continue;
+        }
+        for data in frame["data"].as_array().into_iter().flatten() {
+            for (instrument, quote) in data["quotes"].as_object().into_iter().flatten() {
+                received.push(Received {
+                    at,
+                    instrument: instrument.clone(),
+                    quote: quote.clone(),
+                });
+            }
+        }
+    }
+    received
+}
+
+/// Wait for the next frame with the given `aid`, skipping other frames
+async fn expect(client: &mut Client, aid: &str) -> Value {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    loop {
+        let message = tokio::time::timeout_at(deadline, client.next())
+            .await
+            .unwrap_or_else(|_| panic!("no {} frame received", aid));
+        let Some(Ok(Message::Text(text))) = message else {
+            continue;
+        };
+        let frame: Value = serde_json::from_str(&text).expect("invalid JSON frame");
+        if frame["aid"] == aid {
+            return frame;
+        }
+    }
+}
+
+fn count(received: &[Received], instrument: &str) -> usize {
+    received.iter().filter(|r| r.instrument == instrument).count()
+}
+
+#[test]
+fn mock_generator_is_deterministic() {
+    let config = MockConfig {
+        enabled: true,
+        instruments: vec![REBAR.to_string(), HOT_COIL.to_string()],
+        ..MockConfig::default()
+    };
+    let now = Utc::now();
+    let mut first = MockTickGenerator::new(&config);
+    let mut second = MockTickGenerator::new(&config);
+    for _ in 0..100 {
+        let a = first.next_ticks(now);
+        let b = second.next_ticks(now);
+        assert_eq!(a.len(), 2);
+        for (a, b) in a.iter().zip(&b) {
+            assert_eq!(a.instrument_id, b.instrument_id);
+            assert_eq!(a.last_price, b.last_price);
+            assert_eq!(a.volume, b.volume);
+            assert!((a.last_price - config.start_price).abs() <= 200.0);
+            assert_eq!(a.ask_price1 - a.bid_price1, 2.0 * config.price_tick);
+        }
+    }
+
+    let mut reseeded = MockTickGenerator::new(&MockConfig { seed: 7, ..config.clone() });
+    let mut original = MockTickGenerator::new(&config);
+    let prices = |generator: &mut MockTickGenerator| -> Vec<f64> {
+        (0..20).map(|_| generator.next_ticks(now)[0].last_price).collect()
+    };
+    assert_ne!(prices(&mut reseeded), prices(&mut original));
+}
+
+#[tokio::test]
+async fn delivers_only_subscribed_instruments() {
+    let gateway = TestGateway::start(mock_config(&[REBAR, HOT_COIL], 20.0));
+    let mut client = gateway.connect().await;
+    subscribe(&mut client, &[REBAR]).await;
+
+    let received = collect(&mut client, Duration::from_millis(1500)).await;
+    assert!(count(&received, REBAR) > 0, "no quotes for {}", REBAR);
+    assert_eq!(count(&received, HOT_COIL), 0);
+}
+
+#[tokio::test]
+async fn unsubscribe_stops_delivery() {
+    let gateway = TestGateway::start(mock_config(&[REBAR], 20.0));
+    let mut client = gateway.connect().await;
+    subscribe(&mut client, &[REBAR]).await;
+    assert!(count(&collect(&mut client, Duration::from_secs(1)).await, REBAR) > 0);
+
+    send(&mut client, json!({ "type": "unsubscribe", "payload": { "instruments": [REBAR] } })).await;
+    // Updates already batched for the client may still arrive
+    collect(&mut client, Duration::from_millis(300)).await;
+    assert_eq!(count(&collect(&mut client, Duration::from_secs(1)).await, REBAR), 0);
+}
+
+#[tokio::test]
+async fn clients_receive_the_same_instrument() {
+    let gateway = TestGateway::start(mock_config(&[REBAR], 20.0));
+    let mut first = gateway.connect().await;
+    let mut second = gateway.connect().await;
+    subscribe(&mut first, &[REBAR]).await;
+    subscribe(&mut second, &[REBAR]).await;
+
+    let (a, b) = tokio::join!(
+        collect(&mut first, Duration::from_millis(1500)),
+        collect(&mut second, Duration::from_millis(1500)),
+    );
+    assert!(count(&a, REBAR) > 0);
+    assert!(count(&b, REBAR) > 0);
+}
+
+#[tokio::test]
+async fn quotes_arrive_within_latency_budget() {
+    let gateway = TestGateway::start(mock_config(&[REBAR], 20.0));
+    let mut client = gateway.connect().await;
+    subscribe(&mut client, &[REBAR]).await;
+
+    let received = collect(&mut client, Duration::from_secs(2)).await;
+    assert!(count(&received, REBAR) >= 5);
+    for quote in received.iter().filter(|r| r.instrument == REBAR) {
+        let generated: DateTime<Utc> = quote.quote["datetime"]
+            .as_str()
+            .and_then(|datetime| datetime.parse().ok())
+            .expect("quote without datetime");
+        // Updates are batched every 100ms, allow for slow CI machines
+        let latency = quote.at - generated;
+        assert!(
+            latency < chrono::Duration::milliseconds(1000),
+            "quote delivered after {}ms",
+            latency.num_milliseconds()
+        );
+    }
+}
+
+#[tokio::test]
+async fn fast_ticks_are_conflated() {
+    let gateway = TestGateway::start(mock_config(&[REBAR], 500.0));
+    let mut client = gateway.connect().await;
+    subscribe(&mut client, &[REBAR]).await;
+
+    let window = Duration::from_secs(2);
+    let received: Vec<Received> = collect(&mut client, window)
+        .await
+        .into_iter()
+        .filter(|r| r.instrument == REBAR)
+        .collect();
+    assert!(!received.is_empty());
+    // About 1000 ticks were generated, clients get at most one update per batch
+    assert!(received.len() < 100, "{} updates were not conflated", received.len());
+
+    // Conflated updates carry the latest state, so the day's volume never goes back
+    let volumes: Vec<i64> = received
+        .iter()
+        .filter_map(|r| r.quote["volume"].as_i64())
+        .collect();
+    assert!(volumes.windows(2).all(|w| w[0] <= w[1]));
+    assert!(volumes.last().copied().unwrap_or_default() > received.len() as i64);
+}
+
+#[tokio::test]
+async fn channel_requests_are_answered_on_their_channel() {
+    let gateway = TestGateway::start(mock_config(&[REBAR], 20.0));
+    let mut client = gateway.connect().await;
+    send(&mut client, json!({ "channel": "quote", "aid": "subscribe", "ins_list": REBAR })).await;
+    let response = expect(&mut client, "rsp_subscribe").await;
+    assert_eq!(response["channel"], "quote");
+    assert_eq!(response["ins_list"], REBAR);
+    // Once multiplexed, every frame names its channel
+    assert_eq!(expect(&mut client, "rtn_data").await["channel"], "quote");
+
+    send(&mut client, json!({ "channel": "status", "aid": "unsubscribe" })).await;
+    let response = expect(&mut client, "rsp_unsubscribe").await;
+    assert_eq!(response["channel"], "status");
+
+    send(&mut client, json!({ "channel": "admin", "aid": "unsubscribe" })).await;
+    assert_eq!(expect(&mut client, "rtn_error").await["channel"], "admin");
+
+    send(&mut client, json!({ "channel": "quote", "aid": "unsubscribe" })).await;
+    let response = expect(&mut client, "rsp_unsubscribe").await;
+    assert_eq!(response["ins_list"], "");
+    collect(&mut client, Duration::from_millis(300)).await;
+    assert_eq!(count(&collect(&mut client, Duration::from_secs(1)).await, REBAR), 0);
+}
@@ -1,9 +1,14 @@
 use std::fs::{self, File};
+use std::path::Path;
 
 use polars::prelude::*;
+use serde::Serialize;
 
 use crate::util::tradedate::QATradeDate;
 
+/// Instrument column shared by every dataset
+pub const CODE_COLUMN: &str = "order_book_id";
+
 pub struct QALfs {
     base_dir: String,
     td: QATradeDate,
@@ -15,223 +20,393 @@ impl QALfs {
         QALfs { base_dir, td }
     }
 
-    pub fn get_files(&self, files: Vec<String>) -> Result<DataFrame, PolarsError> {
-        let mut dfs: Vec<DataFrame> = Vec::new();
-
-        for file_path in files {
-            let lf = LazyFrame::scan_parquet(&file_path, Default::default()).map_err(|_| {
-                PolarsError::ComputeError("Failed to create LazyFrame from file".into())
-            })?;
-            let df = lf
-                .collect()
-                .map_err(|_| PolarsError::ComputeError("Failed to collect DataFrame".into()))?;
-            dfs.push(df);
-        }
-
-        if let Some(first_df) = dfs.first().cloned() {
-            let mut acc = first_df;
-            for df in dfs.into_iter().skip(1) {
-                acc = acc.vstack(&df).map_err(|_| {
-                    PolarsError::ComputeError("Failed to vertically stack DataFrames".into())
-                })?;
-            }
-            Ok(acc)
-        } else {
-            Err(PolarsError::NoData(
+    /// Lazily scans the files in parallel and concatenates them, nothing is read until collect
+    pub fn scan_files(&self, files: Vec<String>) -> Result<LazyFrame, PolarsError> {
+        if files.is_empty() {
+            return Err(PolarsError::NoData(
                 "No DataFrames were created from the files".into(),
-            ))
+            ));
         }
+        let args = ScanArgsParquet {
+            parallel: ParallelStrategy::Auto,
+            ..Default::default()
+        };
+        let frames = files
+            .iter()
+            .map(|file_path| {
+                LazyFrame::scan_parquet(file_path, args.clone()).map_err(|e| {
+                    PolarsError::ComputeError(
+                        format!("Failed to create LazyFrame from {}: {}", file_path, e).into(),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, PolarsError>>()?;
+        concat(
+            frames,
+            UnionArgs {
+                parallel: true,
+                rechunk: false,
+                ..Default::default()
+            },
+        )
     }
 
-    pub fn load_bfq_day(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        // Generate file paths based on trade dates
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|tradedate| format!("{}/bfqdata/stock_day_bfq_{}.pq", self.base_dir, tradedate))
-            .collect();
+    pub fn get_files(&self, files: Vec<String>) -> Result<DataFrame, PolarsError> {
+        self.get_files_filtered(files, &LfsFilter::default())
+    }
 
-        // Collect all DataFrames individually and then concatenate
-        self.get_files(files)
+    /// Loads the files keeping only the filtered rows and columns, which are pushed down to the scan
+    pub fn get_files_filtered(
+        &self,
+        files: Vec<String>,
+        filter: &LfsFilter,
+    ) -> Result<DataFrame, PolarsError> {
+        filter
+            .apply(self.scan_files(files)?)
+            .with_streaming(true)
+            .collect()
     }
-    pub fn load_hfq_day(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        // Generate file paths based on trade dates
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|tradedate| format!("{}/daydata/stock_day_hfq_{}.pq", self.base_dir, tradedate))
-            .collect();
 
-        // Collect all DataFrames individually and then concatenate
-        self.get_files(files)
+    /// Loads only the given instruments and columns of any dataset, empty means all
+    pub fn load_dataset_filtered(
+        &self,
+        dataset: &LfsDataset,
+        start: &str,
+        end: &str,
+        codes: &[&str],
+        cols: &[&str],
+    ) -> Result<DataFrame, PolarsError> {
+        let files = self.trade_files(start, end, |date| dataset.file(&self.base_dir, date));
+        self.get_files_filtered(files, &LfsFilter::select(codes, cols))
     }
-    pub fn load_bfq_min(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
+
+    /// One file per trade date in [start, end]
+    fn trade_files(&self, start: &str, end: &str, path: impl Fn(&str) -> String) -> Vec<String> {
+        self.td
             .get_trade_range(start, end)
             .iter()
-            .map(|tradedate| format!("{}/mindata/stock_min_{}.pq", self.base_dir, tradedate))
-            .collect();
-        self.get_files(files)
+            .map(|tradedate| path(tradedate))
+            .collect()
     }
-    pub fn load_hfq_min(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        // Generate file paths based on trade dates
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|tradedate| format!("{}/mindata/stock_min_hfq_{}.pq", self.base_dir, tradedate))
-            .collect();
+}
+
+/// Generates `load_*` (collected) and `load_*_lazy` for a dataset stored as one
+/// parquet file per trade date under the base directory
+macro_rules! lfs_datasets {
+    ($($load:ident, $lazy:ident, $filtered:ident => $path:literal;)*) => {
+        /// Every dataset of the layout
+        pub const DATASETS: &[LfsDataset] = &[$(LfsDataset {
+            name: stringify!($load),
+            path: $path,
+        },)*];
+
+        impl QALfs {
+            $(
+                #[doc = concat!("Loads `", $path, "` for every trade date in [start, end]")]
+                pub fn $load(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
+                    self.get_files(self.trade_files(start, end, |date| {
+                        format!(concat!("{}/", $path), self.base_dir, date)
+                    }))
+                }
+
+                #[doc = concat!("Scans `", $path, "` for every trade date in [start, end] without reading it")]
+                pub fn $lazy(&self, start: &str, end: &str) -> Result<LazyFrame, PolarsError> {
+                    self.scan_files(self.trade_files(start, end, |date| {
+                        format!(concat!("{}/", $path), self.base_dir, date)
+                    }))
+                }
+
+                #[doc = concat!("Loads only the given instruments and columns of `", $path, "`, empty means all")]
+                pub fn $filtered(
+                    &self,
+                    start: &str,
+                    end: &str,
+                    codes: &[&str],
+                    cols: &[&str],
+                ) -> Result<DataFrame, PolarsError> {
+                    let files = self.trade_files(start, end, |date| {
+                        format!(concat!("{}/", $path), self.base_dir, date)
+                    });
+                    self.get_files_filtered(files, &LfsFilter::select(codes, cols))
+                }
+            )*
+        }
+    };
+}
+
+lfs_datasets! {
+    load_bfq_day, load_bfq_day_lazy, load_bfq_day_filtered => "bfqdata/stock_day_bfq_{}.pq";
+    load_hfq_day, load_hfq_day_lazy, load_hfq_day_filtered => "daydata/stock_day_hfq_{}.pq";
+    load_bfq_min, load_bfq_min_lazy, load_bfq_min_filtered => "mindata/stock_min_{}.pq";
+    load_hfq_min, load_hfq_min_lazy, load_hfq_min_filtered => "mindata/stock_min_hfq_{}.pq";
+    load_turnover, load_turnover_lazy, load_turnover_filtered => "turnover/turnover_{}.pq";
+    load_bfq_twap_stock_day, load_bfq_twap_stock_day_lazy, load_bfq_twap_stock_day_filtered => "bfqtwapdaydata/twap_stock_day_bfq_{}.pq";
+    load_twap_index_day, load_twap_index_day_lazy, load_twap_index_day_filtered => "twapindexdaydata/twap_index_day_bfq_{}.pq";
+    load_twap_index_pool_day, load_twap_index_pool_day_lazy, load_twap_index_pool_day_filtered => "twapindexpooldaydata/twap_index_pool_day_bfq_{}.pq";
+    load_future_min, load_future_min_lazy, load_future_min_filtered => "futuremin/future_min_{}.pq";
+    load_future_day, load_future_day_lazy, load_future_day_filtered => "futureday/future_day_{}.pq";
+    load_stock_semi_day, load_stock_semi_day_lazy, load_stock_semi_day_filtered => "stock/semiday/stock_semiday_hfq_{}.pq";
+    load_stockshare, load_stockshare_lazy, load_stockshare_filtered => "stockshare/stockshare_{}.pq";
+    load_barra, load_barra_lazy, load_barra_filtered => "basic_data/barrav1_{}.pq";
+    load_financial, load_financial_lazy, load_financial_filtered => "financial/financial_v1_{}.pq";
+    load_stock_industry, load_stock_industry_lazy, load_stock_industry_filtered => "basic_data/industry_{}.pq";
+}
 
-        // Collect all DataFrames individually and then concatenate
-        self.get_files(files)
+/// A dataset stored as one parquet file per trade date
+#[derive(Debug, Clone, Copy)]
+pub struct LfsDataset {
+    /// Name of its load function
+    pub name: &'static str,
+    /// Path under the base directory, `{}` stands for the trade date
+    pub path: &'static str,
+}
+
+impl LfsDataset {
+    pub fn file(&self, base_dir: &str, date: &str) -> String {
+        format!("{}/{}", base_dir, self.path.replacen("{}", date, 1))
     }
+}
 
-    pub fn load_turnover(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|tradedate| format!("{}/turnover/turnover_{}.pq", self.base_dir, tradedate))
-            .collect();
+/// Result of `QALfs::validate`
+#[derive(Debug, Clone, Serialize)]
+pub struct LfsReport {
+    pub base_dir: String,
+    pub start: String,
+    pub end: String,
+    /// Trade dates in [start, end]
+    pub trade_dates: usize,
+    pub datasets: Vec<DatasetReport>,
+}
 
-        // Collect all DataFrames individually and then concatenate
-        self.get_files(files)
+impl LfsReport {
+    /// No dataset that exists has missing, unreadable or mismatching files
+    pub fn is_ok(&self) -> bool {
+        self.datasets.iter().all(DatasetReport::is_ok)
     }
+}
 
-    pub fn load_bfq_twap_stock_day(
-        &self,
-        start: &str,
-        end: &str,
-    ) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| {
-                format!(
-                    "{}/bfqtwapdaydata/twap_stock_day_bfq_{}.pq",
-                    self.base_dir, date
-                )
-            })
-            .collect();
-        self.get_files(files)
+/// Files of one dataset over the validated trade dates
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetReport {
+    pub name: String,
+    pub path: String,
+    /// Whether its directory exists, other fields are empty when it does not
+    pub present: bool,
+    /// Files found
+    pub files: usize,
+    pub missing_dates: Vec<String>,
+    pub unreadable: Vec<FileError>,
+    /// Columns of the first readable file, the reference for the others
+    pub columns: Vec<ColumnInfo>,
+    pub mismatches: Vec<SchemaMismatch>,
+}
+
+impl DatasetReport {
+    pub fn is_ok(&self) -> bool {
+        !self.present
+            || (self.missing_dates.is_empty()
+                && self.unreadable.is_empty()
+                && self.mismatches.is_empty())
     }
+}
 
-    pub fn load_twap_index_day(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
+#[derive(Debug, Clone, Serialize)]
+pub struct FileError {
+    pub file: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub dtype: String,
+}
+
+/// Differences between a file's schema and the dataset's reference schema
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaMismatch {
+    pub file: String,
+    pub missing_columns: Vec<String>,
+    pub extra_columns: Vec<String>,
+    pub type_changes: Vec<TypeChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeChange {
+    pub column: String,
+    pub expected: String,
+    pub found: String,
+}
+
+impl SchemaMismatch {
+    /// None when the schemas have the same columns and types (order is ignored)
+    fn compare(file: &str, expected: &Schema, found: &Schema) -> Option<Self> {
+        let missing_columns: Vec<String> = expected
             .iter()
-            .map(|date| {
-                format!(
-                    "{}/twapindexdaydata/twap_index_day_bfq_{}.pq",
-                    self.base_dir, date
-                )
-            })
+            .filter(|(name, _)| found.get(name).is_none())
+            .map(|(name, _)| name.to_string())
             .collect();
-        self.get_files(files)
-    }
-
-    pub fn load_twap_index_pool_day(
-        &self,
-        start: &str,
-        end: &str,
-    ) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
+        let extra_columns: Vec<String> = found
             .iter()
-            .map(|date| {
-                format!(
-                    "{}/twapindexpooldaydata/twap_index_pool_day_bfq_{}.pq",
-                    self.base_dir, date
-                )
+            .filter(|(name, _)| expected.get(name).is_none())
+            .map(|(name, _)| name.to_string())
+            .collect();
+        let type_changes: Vec<TypeChange> = expected
+            .iter()
+            .filter_map(|(name, dtype)| {
+                let other = found.get(name)?;
+                (other != dtype).then(|| TypeChange {
+                    column: name.to_string(),
+                    expected: dtype.to_string(),
+                    found: other.to_string(),
+                })
             })
             .collect();
-        self.get_files(files)
+        if missing_columns.is_empty() && extra_columns.is_empty() && type_changes.is_empty() {
+            return None;
+        }
+        Some(SchemaMismatch {
+            file: file.to_string(),
+            missing_columns,
+            extra_columns,
+            type_changes,
+        })
     }
+}
 
-    pub fn load_future_min(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| format!("{}/futuremin/future_min_{}.pq", self.base_dir, date))
-            .collect();
-        self.get_files(files)
+impl QALfs {
+    /// Checks every dataset for missing trade dates, unreadable files and schema drift
+    pub fn validate(&self, start: &str, end: &str) -> LfsReport {
+        let dates = self.td.get_trade_range(start, end);
+        LfsReport {
+            base_dir: self.base_dir.clone(),
+            start: start.to_string(),
+            end: end.to_string(),
+            trade_dates: dates.len(),
+            datasets: DATASETS
+                .iter()
+                .map(|dataset| self.validate_dataset(dataset, &dates))
+                .collect(),
+        }
     }
 
-    pub fn load_future_day(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| format!("{}/futureday/future_day_{}.pq", self.base_dir, date))
-            .collect();
-        self.get_files(files)
+    fn validate_dataset(&self, dataset: &LfsDataset, dates: &[String]) -> DatasetReport {
+        let path = format!("{}/{}", self.base_dir, dataset.path);
+        let present = Path::new(&path).parent().map_or(false, Path::is_dir);
+        let mut report = DatasetReport {
+            name: dataset.name.to_string(),
+            path,
+            present,
+            files: 0,
+            missing_dates: Vec::new(),
+            unreadable: Vec::new(),
+            columns: Vec::new(),
+            mismatches: Vec::new(),
+        };
+        if !present {
+            return report;
+        }
+
+        let mut reference: Option<SchemaRef> = None;
+        for date in dates {
+            let file = dataset.file(&self.base_dir, date);
+            if !Path::new(&file).is_file() {
+                report.missing_dates.push(date.clone());
+                continue;
+            }
+            report.files += 1;
+            let schema = match LazyFrame::scan_parquet(&file, Default::default())
+                .and_then(|lf| lf.schema())
+            {
+                Ok(schema) => schema,
+                Err(e) => {
+                    report.unreadable.push(FileError {
+                        file,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            match &reference {
+                Some(expected) => {
+                    if let Some(mismatch) = SchemaMismatch::compare(&file, expected, &schema) {
+                        report.mismatches.push(mismatch);
+                    }
+                }
+                None => {
+                    report.columns = schema
+                        .iter()
+                        .map(|(name, dtype)| ColumnInfo {
+                            name: name.to_string(),
+                            dtype: dtype.to_string(),
+                        })
+                        .collect();
+                    reference = Some(schema);
+                }
+            }
+        }
+        report
     }
+}
 
-    pub fn load_stock_semi_day(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| {
-                format!(
-                    "{}/stock/semiday/stock_semiday_hfq_{}.pq",
-                    self.base_dir, date
-                )
-            })
-            .collect();
-        self.get_files(files)
+/// Optional row and column filters applied while loading
+#[derive(Clone, Default)]
+pub struct LfsFilter {
+    /// Columns to keep, all when None
+    pub columns: Option<Vec<String>>,
+    /// Rows to keep, e.g. `col("code").eq(lit("000001"))`
+    pub predicate: Option<Expr>,
+}
+
+impl LfsFilter {
+    /// Keeps the given instruments and columns, an empty list keeps all
+    pub fn select(codes: &[&str], cols: &[&str]) -> Self {
+        let filter = Self::default().codes(codes);
+        if cols.is_empty() {
+            filter
+        } else {
+            filter.columns(cols.iter().copied())
+        }
     }
 
-    pub fn load_stockshare(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| format!("{}/stockshare/stockshare_{}.pq", self.base_dir, date))
-            .collect();
-        self.get_files(files)
+    /// Keeps the rows of the given instruments, no-op for an empty list
+    pub fn codes(self, codes: &[&str]) -> Self {
+        if codes.is_empty() {
+            return self;
+        }
+        self.predicate(col(CODE_COLUMN).is_in(lit(Series::new(CODE_COLUMN, codes))))
     }
 
-    pub fn load_barra(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| format!("{}/basic_data/barrav1_{}.pq", self.base_dir, date))
-            .collect();
-        self.get_files(files)
+    pub fn columns<S: Into<String>>(mut self, columns: impl IntoIterator<Item = S>) -> Self {
+        self.columns = Some(columns.into_iter().map(Into::into).collect());
+        self
     }
 
-    pub fn load_financial(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| format!("{}/financial/financial_v1_{}.pq", self.base_dir, date))
-            .collect();
-        self.get_files(files)
+    /// Adds a row filter, combined with earlier ones by AND
+    pub fn predicate(mut self, predicate: Expr) -> Self {
+        self.predicate = Some(match self.predicate.take() {
+            Some(existing) => existing.and(predicate),
+            None => predicate,
+        });
+        self
     }
-    pub fn load_stock_industry(&self, start: &str, end: &str) -> Result<DataFrame, PolarsError> {
-        let files: Vec<String> = self
-            .td
-            .get_trade_range(start, end)
-            .iter()
-            .map(|date| format!("{}/basic_data/industry_{}.pq", self.base_dir, date))
-            .collect();
-        self.get_files(files)
+
+    /// Adds the filters to a query, the optimizer pushes them into the parquet scans
+    pub fn apply(&self, mut lf: LazyFrame) -> LazyFrame {
+        if let Some(predicate) = &self.predicate {
+            lf = lf.filter(predicate.clone());
+        }
+        if let Some(columns) = &self.columns {
+            lf = lf.select(columns.iter().map(|name| col(name)).collect::<Vec<_>>());
+        }
+        lf
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::QALfs;
+    use super::{LfsFilter, QALfs, SchemaMismatch, DATASETS};
+    use polars::prelude::*;
     #[test]
     fn load_bfq_day() {
         let base_dir = "/opt/cache/data".to_string();
@@ -239,4 +414,65 @@ mod test {
         let res = lfs.load_twap_index_pool_day("2024-01-01", "2024-01-22");
         println!("{:#?}", res);
     }
+
+    #[test]
+    fn filter_keeps_rows_and_columns() {
+        let df = df!("code" => ["000001", "000002"], "close" => [10.0, 20.0]).unwrap();
+        let res = LfsFilter::default()
+            .columns(["close"])
+            .predicate(col("code").eq(lit("000002")))
+            .apply(df.lazy())
+            .collect()
+            .unwrap();
+        assert_eq!(res.shape(), (1, 1));
+        assert_eq!(res.column("close").unwrap().f64().unwrap().get(0), Some(20.0));
+    }
+
+    #[test]
+    fn select_codes_and_columns() {
+        let df = df!(
+            "order_book_id" => ["000001", "000002", "600000"],
+            "close" => [10.0, 20.0, 30.0],
+            "volume" => [1.0, 2.0, 3.0]
+        )
+        .unwrap();
+        let res = LfsFilter::select(&["000001", "600000"], &["order_book_id", "close"])
+            .apply(df.clone().lazy())
+            .collect()
+            .unwrap();
+        assert_eq!(res.shape(), (2, 2));
+
+        let all = LfsFilter::select(&[], &[]).apply(df.lazy()).collect().unwrap();
+        assert_eq!(all.shape(), (3, 3));
+    }
+
+    #[test]
+    fn schema_mismatch() {
+        let expected = Schema::from_iter([
+            Field::new("order_book_id", DataType::String),
+            Field::new("close", DataType::Float64),
+            Field::new("volume", DataType::Float64),
+        ]);
+        let found = Schema::from_iter([
+            Field::new("close", DataType::Float32),
+            Field::new("order_book_id", DataType::String),
+            Field::new("amount", DataType::Float64),
+        ]);
+        assert!(SchemaMismatch::compare("a.pq", &expected, &expected).is_none());
+
+        let mismatch = SchemaMismatch::compare("b.pq", &expected, &found).unwrap();
+        assert_eq!(mismatch.missing_columns, vec!["volume"]);
+        assert_eq!(mismatch.extra_columns, vec!["amount"]);
+        assert_eq!(mismatch.type_changes.len(), 1);
+        assert_eq!(mismatch.type_changes[0].column, "close");
+    }
+
+    #[test]
+    fn dataset_files() {
+        let future_day = DATASETS.iter().find(|d| d.name == "load_future_day").unwrap();
+        assert_eq!(
+            future_day.file("/data", "2024-01-02"),
+            "/data/futureday/future_day_2024-01-02.pq"
+        );
+    }
 }
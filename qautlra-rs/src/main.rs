@@ -6,28 +6,61 @@ use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
 use actix_web_actors::ws;
 use env_logger;
 
-use crate::server::websocket::mdserver::MDServer;
+use crate::data::lfs::QALfs;
+use crate::server::dataserver::{kline_handler, KlineService};
+use crate::server::websocket::mdserver::{GetNamespaceStats, MDServer};
 use crate::server::websocket::mdsession::MDSession;
+use crate::server::websocket::namespace::{NamespaceQuota, DEFAULT_NAMESPACE};
 
-/// WebSocket connection handler for market data
-async fn ws_market_data_handler(
-    req: HttpRequest,
+/// Start a WebSocket session in a namespace
+fn start_session(
+    namespace: String,
+    req: &HttpRequest,
     stream: web::Payload,
-    md_server: web::Data<Addr<MDServer>>,
+    md_server: &Addr<MDServer>,
 ) -> Result<HttpResponse, Error> {
-    // Create a new WebSocket session
     ws::start(
         MDSession {
             id: 0,
             hb: Instant::now(),
-            room: "main".to_owned(),
-            md_addr: md_server.get_ref().clone(),
+            room: namespace,
+            md_addr: md_server.clone(),
+            registered: false,
         },
-        &req,
+        req,
         stream,
     )
 }
 
+/// WebSocket connection handler for market data
+async fn ws_market_data_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    md_server: web::Data<Addr<MDServer>>,
+) -> Result<HttpResponse, Error> {
+    // Create a new WebSocket session
+    start_session(DEFAULT_NAMESPACE.to_owned(), &req, stream, md_server.get_ref())
+}
+
+/// WebSocket connection handler for market data in a tenant namespace
+async fn ws_namespace_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    namespace: web::Path<String>,
+    md_server: web::Data<Addr<MDServer>>,
+) -> Result<HttpResponse, Error> {
+    start_session(namespace.into_inner(), &req, stream, md_server.get_ref())
+}
+
+/// Per-namespace clients, subscriptions and traffic
+async fn namespace_stats_handler(md_server: web::Data<Addr<MDServer>>) -> Result<HttpResponse, Error> {
+    let stats = md_server
+        .send(GetNamespaceStats)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Initialize logger
@@ -48,17 +81,33 @@ async fn main() -> std::io::Result<()> {
     let password = "your_password";
     let broker_id = "your_broker_id";
     
+    // Namespace quotas (max instruments, max clients), other namespaces use the default quota
+    let namespace_quotas = vec![(DEFAULT_NAMESPACE, NamespaceQuota::new(2000, 200))];
+    let default_quota = NamespaceQuota::new(500, 50);
+    
     // Start the market data server in its own thread
     let md_server = MDServer::start_in_arbiter(&arbiter.handle(), move |_| {
-        MDServer::new(front_servers, user_id, password, broker_id)
+        let mut server = MDServer::new(front_servers, user_id, password, broker_id)
+            .with_default_quota(default_quota);
+        for (namespace, quota) in namespace_quotas {
+            server = server.with_namespace(namespace, quota);
+        }
+        server
     });
     
+    // Historical bars served from the local parquet store, keeping the last 256 loaded frames
+    let kline = web::Data::new(KlineService::new(QALfs::new("/opt/cache/data".to_string()), 256));
+    
     // Start the HTTP server with WebSocket support
     println!("Starting WebSocket market data server on 0.0.0.0:8080");
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(md_server.clone()))
+            .app_data(kline.clone())
             .service(web::resource("/ws/marketdata").route(web::get().to(ws_market_data_handler)))
+            .service(web::resource("/ws/market/{namespace}").route(web::get().to(ws_namespace_handler)))
+            .service(web::resource("/api/namespaces").route(web::get().to(namespace_stats_handler)))
+            .service(web::resource("/api/kline").route(web::get().to(kline_handler)))
             .wrap(Logger::default())
     })
     .workers(4)
@@ -1 +1,362 @@
+use actix_web::{error, web, Error, HttpResponse};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Asia::Shanghai;
+use moka::sync::Cache;
+use polars::prelude::*;
+use qamd_rs::{DailyBar, InstrumentType, MinuteBar};
+use serde::{Deserialize, Serialize};
 
+use crate::data::lfs::{QALfs, CODE_COLUMN, DATASETS};
+
+/// Days from 0001-01-01 (CE) to 1970-01-01, polars dates count from the epoch
+const EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+/// Query string of `GET /api/kline`
+#[derive(Debug, Clone, Deserialize)]
+pub struct KlineQuery {
+    /// e.g. `000001.XSHE` or `rb2410`
+    pub instrument: String,
+    /// `1d` or `1m`
+    pub freq: String,
+    /// First trade date, `YYYY-MM-DD`
+    pub start: String,
+    /// Last trade date, `YYYY-MM-DD`
+    pub end: String,
+    /// Stock prices: `none` (default) or `hfq`, ignored for futures
+    #[serde(default)]
+    pub adjust: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BarFreq {
+    Day,
+    Minute,
+}
+
+impl BarFreq {
+    pub fn parse(freq: &str) -> Option<Self> {
+        match freq.to_ascii_lowercase().as_str() {
+            "1d" | "d" | "day" => Some(BarFreq::Day),
+            "1m" | "1min" | "min" | "minute" => Some(BarFreq::Minute),
+            _ => None,
+        }
+    }
+}
+
+/// Shanghai and Shenzhen listings are read from the stock datasets, everything else from the futures ones
+pub fn instrument_type(instrument: &str) -> InstrumentType {
+    if instrument.ends_with(".XSHE") || instrument.ends_with(".XSHG") {
+        InstrumentType::Stock
+    } else {
+        InstrumentType::Future
+    }
+}
+
+/// A validated query and the dataset that answers it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KlineRequest {
+    pub instrument: String,
+    pub freq: BarFreq,
+    pub start: String,
+    pub end: String,
+    /// Name of the QALfs dataset
+    pub dataset: &'static str,
+}
+
+impl KlineRequest {
+    pub fn parse(query: &KlineQuery) -> Result<Self, String> {
+        let freq = BarFreq::parse(&query.freq)
+            .ok_or_else(|| format!("Unsupported freq {}, expected 1d or 1m", query.freq))?;
+        for date in [&query.start, &query.end] {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| format!("Invalid date {}, expected YYYY-MM-DD", date))?;
+        }
+        if query.start > query.end {
+            return Err(format!("start {} is after end {}", query.start, query.end));
+        }
+        let hfq = match query.adjust.as_deref() {
+            None | Some("none") | Some("bfq") => false,
+            Some("hfq") => true,
+            Some(other) => return Err(format!("Unsupported adjust {}, expected none or hfq", other)),
+        };
+        let dataset = match (instrument_type(&query.instrument), freq, hfq) {
+            (InstrumentType::Stock, BarFreq::Day, false) => "load_bfq_day",
+            (InstrumentType::Stock, BarFreq::Day, true) => "load_hfq_day",
+            (InstrumentType::Stock, BarFreq::Minute, false) => "load_bfq_min",
+            (InstrumentType::Stock, BarFreq::Minute, true) => "load_hfq_min",
+            (_, BarFreq::Day, _) => "load_future_day",
+            (_, BarFreq::Minute, _) => "load_future_min",
+        };
+        Ok(KlineRequest {
+            instrument: query.instrument.clone(),
+            freq,
+            start: query.start.clone(),
+            end: query.end.clone(),
+            dataset,
+        })
+    }
+}
+
+/// Bars returned by `/api/kline`
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Bars {
+    Daily(Vec<DailyBar>),
+    Minute(Vec<MinuteBar>),
+}
+
+/// Historical bars read from QALfs, keeping the most recently loaded frames in memory
+pub struct KlineService {
+    lfs: QALfs,
+    frames: Cache<KlineRequest, DataFrame>,
+}
+
+impl KlineService {
+    /// `capacity` is the number of frames (one per query) kept in the cache
+    pub fn new(lfs: QALfs, capacity: u64) -> Self {
+        Self {
+            lfs,
+            frames: Cache::new(capacity),
+        }
+    }
+
+    /// Rows of the instrument over the request's trade dates, from the cache when loaded recently
+    pub fn frame(&self, request: &KlineRequest) -> Result<DataFrame, PolarsError> {
+        if let Some(frame) = self.frames.get(request) {
+            return Ok(frame);
+        }
+        let dataset = DATASETS
+            .iter()
+            .find(|dataset| dataset.name == request.dataset)
+            .ok_or_else(|| PolarsError::ComputeError(format!("Unknown dataset {}", request.dataset).into()))?;
+        let frame = self.lfs.load_dataset_filtered(
+            dataset,
+            &request.start,
+            &request.end,
+            &[request.instrument.as_str()],
+            &[],
+        )?;
+        self.frames.insert(request.clone(), frame.clone());
+        Ok(frame)
+    }
+
+    pub fn bars(&self, request: &KlineRequest) -> Result<Bars, PolarsError> {
+        let frame = self.frame(request)?;
+        let instrument_type = instrument_type(&request.instrument);
+        Ok(match request.freq {
+            BarFreq::Day => Bars::Daily(daily_bars(&frame, instrument_type)?),
+            BarFreq::Minute => Bars::Minute(minute_bars(&frame, instrument_type)?),
+        })
+    }
+}
+
+fn f32_column(df: &DataFrame, name: &str) -> Result<Float32Chunked, PolarsError> {
+    Ok(df.column(name)?.cast(&DataType::Float32)?.f32()?.clone())
+}
+
+/// Columns missing from a dataset (e.g. `settlement` for stocks) read as None
+fn optional_f32_column(df: &DataFrame, name: &str) -> Result<Option<Float32Chunked>, PolarsError> {
+    match df.column(name) {
+        Ok(_) => f32_column(df, name).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+fn date_column(df: &DataFrame, name: &str) -> Result<Vec<Option<NaiveDate>>, PolarsError> {
+    let dates = df.column(name)?.cast(&DataType::Date)?;
+    Ok(dates
+        .date()?
+        .into_iter()
+        .map(|days| days.and_then(|days| NaiveDate::from_num_days_from_ce_opt(days + EPOCH_DAYS_FROM_CE)))
+        .collect())
+}
+
+/// The files store Beijing wall-clock times without a zone
+fn datetime_column(df: &DataFrame, name: &str) -> Result<Vec<Option<DateTime<Utc>>>, PolarsError> {
+    let datetimes = df
+        .column(name)?
+        .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?;
+    Ok(datetimes
+        .datetime()?
+        .into_iter()
+        .map(|ms| {
+            let ms = ms?;
+            let naive = NaiveDateTime::from_timestamp_opt(ms.div_euclid(1000), (ms.rem_euclid(1000) * 1_000_000) as u32)?;
+            Shanghai
+                .from_local_datetime(&naive)
+                .single()
+                .map(|local| local.with_timezone(&Utc))
+        })
+        .collect())
+}
+
+/// Rows without a date or an instrument are skipped, missing prices read as 0
+pub fn daily_bars(df: &DataFrame, instrument_type: InstrumentType) -> Result<Vec<DailyBar>, PolarsError> {
+    let dates = date_column(df, "date")?;
+    let codes = df.column(CODE_COLUMN)?.cast(&DataType::String)?;
+    let codes = codes.str()?;
+    let [open, high, low, close, volume, total_turnover] =
+        ["open", "high", "low", "close", "volume", "total_turnover"].map(|name| f32_column(df, name));
+    let (open, high, low, close, volume, total_turnover) = (open?, high?, low?, close?, volume?, total_turnover?);
+    let [num_trades, limit_up, limit_down, open_interest, prev_settlement, settlement, iopv] = [
+        "num_trades",
+        "limit_up",
+        "limit_down",
+        "open_interest",
+        "prev_settlement",
+        "settlement",
+        "iopv",
+    ]
+    .map(|name| optional_f32_column(df, name));
+    let (num_trades, limit_up, limit_down, open_interest, prev_settlement, settlement, iopv) = (
+        num_trades?,
+        limit_up?,
+        limit_down?,
+        open_interest?,
+        prev_settlement?,
+        settlement?,
+        iopv?,
+    );
+    let optional = |column: &Option<Float32Chunked>, row: usize| column.as_ref().and_then(|c| c.get(row));
+
+    Ok((0..df.height())
+        .filter_map(|row| {
+            Some(DailyBar {
+                date: dates[row]?,
+                order_book_id: codes.get(row)?.to_string(),
+                instrument_type,
+                open: open.get(row).unwrap_or_default(),
+                high: high.get(row).unwrap_or_default(),
+                low: low.get(row).unwrap_or_default(),
+                close: close.get(row).unwrap_or_default(),
+                volume: volume.get(row).unwrap_or_default(),
+                total_turnover: total_turnover.get(row).unwrap_or_default(),
+                num_trades: optional(&num_trades, row),
+                limit_up: optional(&limit_up, row),
+                limit_down: optional(&limit_down, row),
+                open_interest: optional(&open_interest, row),
+                prev_settlement: optional(&prev_settlement, row),
+                settlement: optional(&settlement, row),
+                iopv: optional(&iopv, row),
+            })
+        })
+        .collect())
+}
+
+/// Rows without a time or an instrument are skipped, missing prices read as 0
+pub fn minute_bars(df: &DataFrame, instrument_type: InstrumentType) -> Result<Vec<MinuteBar>, PolarsError> {
+    let datetimes = datetime_column(df, "datetime")?;
+    let trading_dates = match df.column("trading_date") {
+        Ok(_) => Some(date_column(df, "trading_date")?),
+        Err(_) => None,
+    };
+    let codes = df.column(CODE_COLUMN)?.cast(&DataType::String)?;
+    let codes = codes.str()?;
+    let [open, high, low, close, volume, total_turnover] =
+        ["open", "high", "low", "close", "volume", "total_turnover"].map(|name| f32_column(df, name));
+    let (open, high, low, close, volume, total_turnover) = (open?, high?, low?, close?, volume?, total_turnover?);
+    let open_interest = optional_f32_column(df, "open_interest")?;
+
+    Ok((0..df.height())
+        .filter_map(|row| {
+            Some(MinuteBar {
+                datetime: datetimes[row]?,
+                trading_date: trading_dates.as_ref().and_then(|dates| dates[row]),
+                order_book_id: codes.get(row)?.to_string(),
+                instrument_type,
+                open: open.get(row).unwrap_or_default(),
+                high: high.get(row).unwrap_or_default(),
+                low: low.get(row).unwrap_or_default(),
+                close: close.get(row).unwrap_or_default(),
+                volume: volume.get(row).unwrap_or_default(),
+                total_turnover: total_turnover.get(row).unwrap_or_default(),
+                open_interest: open_interest.as_ref().and_then(|c| c.get(row)),
+            })
+        })
+        .collect())
+}
+
+/// `GET /api/kline?instrument=000001.XSHE&freq=1d&start=2024-01-01&end=2024-02-01`
+pub async fn kline_handler(
+    query: web::Query<KlineQuery>,
+    service: web::Data<KlineService>,
+) -> Result<HttpResponse, Error> {
+    let request = KlineRequest::parse(&query).map_err(error::ErrorBadRequest)?;
+    // Parquet scans block, keep them off the worker threads
+    let bars = web::block(move || service.bars(&request))
+        .await?
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(bars))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn query(instrument: &str, freq: &str, adjust: Option<&str>) -> KlineQuery {
+        KlineQuery {
+            instrument: instrument.to_string(),
+            freq: freq.to_string(),
+            start: "2024-01-01".to_string(),
+            end: "2024-02-01".to_string(),
+            adjust: adjust.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn request_dataset() {
+        let stock = KlineRequest::parse(&query("000001.XSHE", "1d", None)).unwrap();
+        assert_eq!(stock.dataset, "load_bfq_day");
+        let hfq = KlineRequest::parse(&query("600000.XSHG", "1m", Some("hfq"))).unwrap();
+        assert_eq!(hfq.dataset, "load_hfq_min");
+        let future = KlineRequest::parse(&query("RB2410", "1m", Some("hfq"))).unwrap();
+        assert_eq!(future.dataset, "load_future_min");
+
+        assert!(KlineRequest::parse(&query("000001.XSHE", "5m", None)).is_err());
+        assert!(KlineRequest::parse(&query("000001.XSHE", "1d", Some("qfq"))).is_err());
+        let mut reversed = query("000001.XSHE", "1d", None);
+        reversed.end = "2023-12-01".to_string();
+        assert!(KlineRequest::parse(&reversed).is_err());
+    }
+
+    #[test]
+    fn frame_to_daily_bars() {
+        let df = df!(
+            "order_book_id" => ["RB2410", "RB2410"],
+            "date" => ["2024-01-02", "2024-01-03"],
+            "open" => [3500.0, 3510.0],
+            "high" => [3520.0, 3530.0],
+            "low" => [3490.0, 3500.0],
+            "close" => [3510.0, 3525.0],
+            "volume" => [1000.0, 1200.0],
+            "total_turnover" => [3.5e7, 4.2e7],
+            "open_interest" => [25000.0, 25500.0]
+        )
+        .unwrap();
+        let bars = daily_bars(&df, InstrumentType::Future).unwrap();
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[1].date, NaiveDate::from_ymd_opt(2024, 1, 3).unwrap());
+        assert_eq!(bars[1].close, 3525.0);
+        assert_eq!(bars[1].open_interest, Some(25500.0));
+        assert_eq!(bars[1].settlement, None);
+    }
+
+    #[test]
+    fn frame_to_minute_bars() {
+        let df = df!(
+            "order_book_id" => ["000001.XSHE"],
+            "datetime" => ["2024-01-02 09:31:00"],
+            "open" => [9.4],
+            "high" => [9.5],
+            "low" => [9.4],
+            "close" => [9.45],
+            "volume" => [12000.0],
+            "total_turnover" => [113400.0]
+        )
+        .unwrap();
+        let bars = minute_bars(&df, InstrumentType::Stock).unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].datetime.to_rfc3339(), "2024-01-02T01:31:00+00:00");
+        assert_eq!(bars[0].open_interest, None);
+    }
+}
@@ -1,4 +1,3 @@
-use std::collections::HashSet;
 use std::sync::mpsc::channel;
 use std::time::Duration;
 use std::ffi::CString;
@@ -10,6 +9,7 @@ use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 
 use super::mdspi::CTPMDSPI;
+use super::namespace::{Namespace, NamespaceQuota, NamespaceStats};
 
 /// Market data message
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
@@ -68,8 +68,9 @@ impl From<DepthMarketData> for MarketData {
     }
 }
 
+/// Subscribe a client, fails when the namespace instrument quota is exceeded
 #[derive(Message)]
-#[rtype(result = "()")]
+#[rtype(result = "Result<(), String>")]
 pub struct Subscribe {
     pub subscribe: Vec<String>,
     pub client_id: usize,
@@ -82,10 +83,12 @@ pub struct UnSubscribe {
     pub client_id: usize,
 }
 
+/// Register a client in a namespace, fails when the namespace client quota is exceeded
 #[derive(Message)]
-#[rtype(usize)]
+#[rtype(result = "Result<usize, String>")]
 pub struct Connect {
     pub addr: Recipient<MarketData>,
+    pub namespace: String,
 }
 
 #[derive(Message)]
@@ -94,6 +97,11 @@ pub struct Disconnect {
     pub id: usize,
 }
 
+/// Get per-namespace client, subscription and traffic counters
+#[derive(Message)]
+#[rtype(result = "Vec<NamespaceStats>")]
+pub struct GetNamespaceStats;
+
 /// Market data server that integrates CTP and WebSocket
 pub struct MDServer {
     /// Market data API
@@ -102,10 +110,18 @@ pub struct MDServer {
     rx: std::sync::mpsc::Receiver<DepthMarketData>,
     /// Connected sessions
     sessions: HashMap<usize, Recipient<MarketData>>,
-    /// Subscriptions by instrument
-    subscriptions: HashMap<String, HashSet<usize>>,
-    /// Current front server
-    front_server: String,
+    /// Next session id
+    next_id: usize,
+    /// Namespace of each connected session
+    session_namespaces: HashMap<usize, String>,
+    /// Subscription spaces by namespace
+    namespaces: HashMap<String, Namespace>,
+    /// Quotas of configured namespaces
+    quotas: HashMap<String, NamespaceQuota>,
+    /// Quota of namespaces without a configured one
+    default_quota: NamespaceQuota,
+    /// Registered front servers, in priority order
+    front_servers: Vec<String>,
     /// User ID for login
     user_id: String,
     /// Password for login
@@ -119,11 +135,11 @@ impl MDServer {
         // Create channel to receive market data
         let (tx, rx) = channel();
         
-        // Initialize the Market Data API with the first front server
-        let front = if front_servers.is_empty() {
-            "tcp://180.168.146.187:10131"
+        // Register every front server, the API fails over between them on disconnect
+        let front_servers: Vec<String> = if front_servers.is_empty() {
+            vec!["tcp://180.168.146.187:10131".to_string()]
         } else {
-            front_servers[0]
+            front_servers.into_iter().map(str::to_string).collect()
         };
         
         // Create SPI
@@ -131,32 +147,74 @@ impl MDServer {
         
         // Configure and start the MD API
         let mut md_api = MdApi::new(CString::new("./flow/").unwrap(), false, false);
-        md_api.register_front(CString::new(front).unwrap());
+        for front in &front_servers {
+            md_api.register_front(CString::new(front.as_str()).unwrap());
+        }
         md_api.register_spi(md_spi);
         md_api.init();
         
-        println!("Starting Market Data Server with front server: {}", front);
+        println!("Starting Market Data Server with front servers: {}", front_servers.join(", "));
         
         Self {
             md_api,
             rx,
             sessions: HashMap::new(),
-            subscriptions: HashMap::new(),
-            front_server: front.to_string(),
+            next_id: 0,
+            session_namespaces: HashMap::new(),
+            namespaces: HashMap::new(),
+            quotas: HashMap::new(),
+            default_quota: NamespaceQuota::default(),
+            front_servers,
             user_id: user_id.to_string(),
             password: password.to_string(),
             broker_id: broker_id.to_string(),
         }
     }
 
-    /// Send market data to subscribed clients
-    fn send_market_data(&self, market_data: &MarketData) {
-        if let Some(sessions) = self.subscriptions.get(&market_data.instrument_id) {
+    /// Set the quota of a namespace
+    pub fn with_namespace(mut self, namespace: &str, quota: NamespaceQuota) -> Self {
+        self.quotas.insert(namespace.to_string(), quota);
+        self
+    }
+
+    /// Set the quota of namespaces without a configured one
+    pub fn with_default_quota(mut self, quota: NamespaceQuota) -> Self {
+        self.default_quota = quota;
+        self
+    }
+
+    /// Send market data to subscribed clients of every namespace
+    fn send_market_data(&mut self, market_data: &MarketData) {
+        for namespace in self.namespaces.values_mut() {
+            let Some(sessions) = namespace.subscribers(&market_data.instrument_id) else {
+                continue;
+            };
+            let mut sent = 0;
             for session_id in sessions {
                 if let Some(recipient) = self.sessions.get(session_id) {
                     recipient.do_send(market_data.clone());
+                    sent += 1;
                 }
             }
+            namespace.record_sent(sent);
+        }
+    }
+
+    /// Whether any namespace still subscribes an instrument
+    fn is_subscribed(&self, instrument: &str) -> bool {
+        self.namespaces
+            .values()
+            .any(|namespace| namespace.subscribers(instrument).is_some())
+    }
+
+    /// Drop upstream subscriptions no namespace needs anymore
+    fn release_instruments(&mut self, released: Vec<String>) {
+        let unused: Vec<String> = released
+            .into_iter()
+            .filter(|instrument| !self.is_subscribed(instrument))
+            .collect();
+        if !unused.is_empty() {
+            self.unsubscribe_market_data(&unused);
         }
     }
     
@@ -259,37 +317,45 @@ impl Actor for MDServer {
             }
         });
         
-        // Log active subscriptions periodically
+        // Log namespace usage periodically
         ctx.run_interval(Duration::from_secs(60), |act, _ctx| {
-            println!("Active subscriptions: {:?}", act.subscriptions);
+            for (name, namespace) in &act.namespaces {
+                println!("Namespace {}: {:?}", name, namespace.stats(name));
+            }
         });
     }
 }
 
 impl Handler<Subscribe> for MDServer {
-    type Result = ();
+    type Result = Result<(), String>;
 
     fn handle(&mut self, msg: Subscribe, _ctx: &mut Self::Context) -> Self::Result {
         println!("MDServer: handling Subscribe request");
         
-        // Update subscriptions
-        let mut new_instruments = Vec::new();
+        let namespace_name = self
+            .session_namespaces
+            .get(&msg.client_id)
+            .cloned()
+            .ok_or_else(|| "Session is not connected".to_string())?;
         
-        for instrument in &msg.subscribe {
-            let is_new = self.subscriptions
-                .entry(instrument.clone())
-                .or_insert_with(HashSet::new)
-                .insert(msg.client_id);
-                
-            if is_new {
-                new_instruments.push(instrument.clone());
-            }
-        }
+        // Instruments no namespace subscribed so far need an upstream subscription
+        let new_instruments: Vec<String> = msg
+            .subscribe
+            .iter()
+            .filter(|instrument| !self.is_subscribed(instrument))
+            .cloned()
+            .collect();
+        
+        self.namespaces
+            .entry(namespace_name)
+            .or_default()
+            .subscribe(msg.client_id, &msg.subscribe)?;
         
         // Subscribe to new instruments
         if !new_instruments.is_empty() {
             self.subscribe_market_data(&new_instruments);
         }
+        Ok(())
     }
 }
 
@@ -299,39 +365,45 @@ impl Handler<UnSubscribe> for MDServer {
     fn handle(&mut self, msg: UnSubscribe, _ctx: &mut Self::Context) -> Self::Result {
         println!("MDServer: handling UnSubscribe request");
         
-        // Update subscriptions
-        let mut instruments_to_unsubscribe = Vec::new();
-        
-        for instrument in &msg.unsubscribe {
-            if let Some(sessions) = self.subscriptions.get_mut(instrument) {
-                sessions.remove(&msg.client_id);
-                
-                // If no more subscribers, unsubscribe from the feed
-                if sessions.is_empty() {
-                    instruments_to_unsubscribe.push(instrument.clone());
-                }
-            }
-        }
+        let Some(namespace) = self
+            .session_namespaces
+            .get(&msg.client_id)
+            .and_then(|name| self.namespaces.get_mut(name))
+        else {
+            return;
+        };
         
-        // Unsubscribe from instruments with no subscribers
-        if !instruments_to_unsubscribe.is_empty() {
-            self.unsubscribe_market_data(&instruments_to_unsubscribe);
-        }
+        // If no more subscribers, unsubscribe from the feed
+        let released = namespace.unsubscribe(msg.client_id, &msg.unsubscribe);
+        self.release_instruments(released);
     }
 }
 
 impl Handler<Connect> for MDServer {
-    type Result = usize;
+    type Result = Result<usize, String>;
 
     fn handle(&mut self, msg: Connect, _ctx: &mut Self::Context) -> Self::Result {
-        println!("MDServer: New client connected");
+        println!("MDServer: New client connected to namespace {}", msg.namespace);
         
         // Generate a new session ID
-        let id = self.sessions.len();
+        let id = self.next_id;
+        
+        let quota = self
+            .quotas
+            .get(&msg.namespace)
+            .copied()
+            .unwrap_or(self.default_quota);
+        self.namespaces
+            .entry(msg.namespace.clone())
+            .or_insert_with(|| Namespace::new(quota))
+            .join(id)?;
+        
+        self.next_id += 1;
         // Store the client's recipient
         self.sessions.insert(id, msg.addr);
+        self.session_namespaces.insert(id, msg.namespace);
         
-        id
+        Ok(id)
     }
 }
 
@@ -342,14 +414,31 @@ impl Handler<Disconnect> for MDServer {
         println!("MDServer: Client disconnected");
         
         // Remove from sessions
-        if self.sessions.remove(&msg.id).is_some() {
-            // Remove from all subscriptions
-            for (_instrument, sessions) in &mut self.subscriptions {
-                sessions.remove(&msg.id);
-            }
-            
-            // Clean up empty subscriptions
-            self.subscriptions.retain(|_, sessions| !sessions.is_empty());
-        }
+        self.sessions.remove(&msg.id);
+        let Some(namespace) = self
+            .session_namespaces
+            .remove(&msg.id)
+            .and_then(|name| self.namespaces.get_mut(&name))
+        else {
+            return;
+        };
+        
+        // Remove from all subscriptions of its namespace
+        let released = namespace.leave(msg.id);
+        self.release_instruments(released);
+    }
+}
+
+impl Handler<GetNamespaceStats> for MDServer {
+    type Result = MessageResult<GetNamespaceStats>;
+
+    fn handle(&mut self, _: GetNamespaceStats, _ctx: &mut Self::Context) -> Self::Result {
+        let mut stats: Vec<NamespaceStats> = self
+            .namespaces
+            .iter()
+            .map(|(name, namespace)| namespace.stats(name))
+            .collect();
+        stats.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+        MessageResult(stats)
     }
 } 
\ No newline at end of file
@@ -52,10 +52,12 @@ pub struct MDSession {
     /// Client must send ping at least once per CLIENT_TIMEOUT seconds,
     /// otherwise we drop connection.
     pub hb: Instant,
-    /// Joined room/channel
+    /// Joined room/channel, the namespace whose subscription space and quotas apply
     pub room: String,
     /// Market data server
     pub md_addr: Addr<MDServer>,
+    /// Whether the session was accepted by the market data server
+    pub registered: bool,
 }
 
 impl MDSession {
@@ -86,11 +88,21 @@ impl Actor for MDSession {
         self.md_addr
             .send(Connect {
                 addr: addr.recipient(),
+                namespace: self.room.clone(),
             })
             .into_actor(self)
             .then(|res, act, ctx| {
                 match res {
-                    Ok(res) => act.id = res,
+                    Ok(Ok(id)) => {
+                        act.id = id;
+                        act.registered = true;
+                    }
+                    // namespace quota exceeded
+                    Ok(Err(e)) => {
+                        ctx.text(WebSocketResponse::fail(e, "connect").to_string());
+                        ctx.close(Some(ws::CloseCode::Policy.into()));
+                        ctx.stop();
+                    }
                     // something is wrong with market data server
                     _ => ctx.stop(),
                 }
@@ -101,7 +113,9 @@ impl Actor for MDSession {
 
     fn stopping(&mut self, _: &mut Self::Context) -> Running {
         // Notify market data server when disconnecting
-        self.md_addr.do_send(Disconnect { id: self.id });
+        if self.registered {
+            self.md_addr.do_send(Disconnect { id: self.id });
+        }
         Running::Stop
     }
 }
@@ -147,11 +161,22 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for MDSession {
                                     .collect();
                                 
                                 if !symbols.is_empty() {
-                                    self.md_addr.do_send(Subscribe {
-                                        client_id: self.id,
-                                        subscribe: symbols,
-                                    });
-                                    ctx.text(WebSocketResponse::ok("Subscribed", "subscribe").to_string());
+                                    self.md_addr
+                                        .send(Subscribe {
+                                            client_id: self.id,
+                                            subscribe: symbols,
+                                        })
+                                        .into_actor(self)
+                                        .then(|res, _act, ctx| {
+                                            let response = match res {
+                                                Ok(Ok(())) => WebSocketResponse::ok("Subscribed".to_string(), "subscribe"),
+                                                Ok(Err(e)) => WebSocketResponse::fail(e, "subscribe"),
+                                                Err(e) => WebSocketResponse::fail(e.to_string(), "subscribe"),
+                                            };
+                                            ctx.text(response.to_string());
+                                            fut::ready(())
+                                        })
+                                        .wait(ctx);
                                 }
                             }
                         }
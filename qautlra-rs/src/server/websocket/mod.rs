@@ -1,3 +1,4 @@
 pub mod mdserver;
 pub mod mdsession;
-pub mod mdspi; 
\ No newline at end of file
+pub mod mdspi;
+pub mod namespace; 
\ No newline at end of file
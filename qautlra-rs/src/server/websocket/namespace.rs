@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Namespace used by clients connecting without one
+pub const DEFAULT_NAMESPACE: &str = "main";
+
+/// Resource limits of a namespace, `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct NamespaceQuota {
+    /// Maximum number of distinct instruments subscribed in the namespace
+    pub max_instruments: Option<usize>,
+    /// Maximum number of concurrently connected clients
+    pub max_clients: Option<usize>,
+}
+
+impl NamespaceQuota {
+    pub fn new(max_instruments: usize, max_clients: usize) -> Self {
+        Self {
+            max_instruments: Some(max_instruments),
+            max_clients: Some(max_clients),
+        }
+    }
+}
+
+/// Per-namespace counters reported by the server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NamespaceStats {
+    pub namespace: String,
+    pub quota: NamespaceQuota,
+    /// Currently connected clients
+    pub clients: usize,
+    /// Distinct instruments currently subscribed
+    pub instruments: usize,
+    /// Market data messages delivered to clients
+    pub messages_sent: u64,
+    /// Connections refused because of `max_clients`
+    pub rejected_clients: u64,
+    /// Subscribe requests refused because of `max_instruments`
+    pub rejected_subscriptions: u64,
+}
+
+/// A tenant's isolated subscription space
+///
+/// Upstream subscriptions are shared between namespaces, but every namespace
+/// only sees and is only charged for the instruments its own clients subscribed.
+#[derive(Debug, Default)]
+pub struct Namespace {
+    pub quota: NamespaceQuota,
+    /// Connected session ids
+    sessions: HashSet<usize>,
+    /// Subscribers by instrument
+    subscriptions: HashMap<String, HashSet<usize>>,
+    messages_sent: u64,
+    rejected_clients: u64,
+    rejected_subscriptions: u64,
+}
+
+impl Namespace {
+    pub fn new(quota: NamespaceQuota) -> Self {
+        Self {
+            quota,
+            ..Default::default()
+        }
+    }
+
+    /// Register a session, fails when the namespace is full
+    pub fn join(&mut self, session_id: usize) -> Result<(), String> {
+        if let Some(max_clients) = self.quota.max_clients {
+            if self.sessions.len() >= max_clients {
+                self.rejected_clients += 1;
+                return Err(format!("Namespace is full ({} clients)", max_clients));
+            }
+        }
+        self.sessions.insert(session_id);
+        Ok(())
+    }
+
+    /// Remove a session and return the instruments nobody in the namespace subscribes anymore
+    pub fn leave(&mut self, session_id: usize) -> Vec<String> {
+        self.sessions.remove(&session_id);
+        let instruments: Vec<String> = self.subscriptions.keys().cloned().collect();
+        self.unsubscribe(session_id, &instruments)
+    }
+
+    /// Subscribe a session to instruments
+    ///
+    /// The request is refused as a whole when the new instruments would exceed
+    /// `max_instruments`.
+    pub fn subscribe(&mut self, session_id: usize, instruments: &[String]) -> Result<(), String> {
+        if let Some(max_instruments) = self.quota.max_instruments {
+            let new: HashSet<&String> = instruments
+                .iter()
+                .filter(|instrument| !self.subscriptions.contains_key(*instrument))
+                .collect();
+            if self.subscriptions.len() + new.len() > max_instruments {
+                self.rejected_subscriptions += 1;
+                return Err(format!(
+                    "Instrument quota exceeded ({} of {} in use)",
+                    self.subscriptions.len(),
+                    max_instruments
+                ));
+            }
+        }
+        for instrument in instruments {
+            self.subscriptions
+                .entry(instrument.clone())
+                .or_default()
+                .insert(session_id);
+        }
+        Ok(())
+    }
+
+    /// Unsubscribe a session and return the instruments left without subscribers
+    pub fn unsubscribe(&mut self, session_id: usize, instruments: &[String]) -> Vec<String> {
+        let mut released = Vec::new();
+        for instrument in instruments {
+            if let Some(sessions) = self.subscriptions.get_mut(instrument) {
+                sessions.remove(&session_id);
+                if sessions.is_empty() {
+                    self.subscriptions.remove(instrument);
+                    released.push(instrument.clone());
+                }
+            }
+        }
+        released
+    }
+
+    /// Sessions of this namespace subscribed to an instrument
+    pub fn subscribers(&self, instrument: &str) -> Option<&HashSet<usize>> {
+        self.subscriptions.get(instrument)
+    }
+
+    pub fn record_sent(&mut self, messages: u64) {
+        self.messages_sent += messages;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn stats(&self, name: &str) -> NamespaceStats {
+        NamespaceStats {
+            namespace: name.to_string(),
+            quota: self.quota,
+            clients: self.sessions.len(),
+            instruments: self.subscriptions.len(),
+            messages_sent: self.messages_sent,
+            rejected_clients: self.rejected_clients,
+            rejected_subscriptions: self.rejected_subscriptions,
+        }
+    }
+}